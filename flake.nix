@@ -41,7 +41,7 @@
         lib = pkgs.lib;
 
         tex = with pkgs;
-          texlive.combine { inherit (texlive) scheme-medium bitter titlesec; };
+          texlive.combine { inherit (texlive) scheme-medium bitter titlesec pdfjam; };
 
         fontsConf = pkgs.symlinkJoin {
             name = "typst-fonts";
@@ -162,18 +162,11 @@
               ${pkgs.unzip}/bin/unzip ${
                 self.inputs.iosevka.packages.${system}.default
               }/ttf.zip
-              for ttf in ttf/*.ttf; do
-                cp $ttf out
-                name=`basename -s .ttf $ttf`
-                pyftsubset \
-                    $ttf \
-                    --output-file=out/"$name".woff2 \
-                    --flavor=woff2 \
-                    --layout-features=* \
-                    --no-hinting \
-                    --desubroutinize \
-                    --unicodes="U+0000-0170,U+00D7,U+00F7,U+2000-206F,U+2074,U+20AC,U+2122,U+2190-21BB,U+2212,U+2215,U+F8FF,U+FEFF,U+FFFD,U+00E8"
-              done
+              # typst uses the ttf files for the resume
+              cp ttf/*.ttf out
+              # subset to the characters used in posts, writes hashed woff2
+              # files and manifest.json
+              ${bin}/bin/subset_fonts ttf out ${posts} ${./dhall}
 
             '';
             installPhase = ''
@@ -208,6 +201,8 @@
               cp -vrf $src/blog $out
               cp -vrf $src/gallery $out
               cp -vrf $src/talks $out
+              cp -vrf $src/incidents $out
+              cp -vrf $src/notes $out
             '';
           };
 
@@ -241,6 +236,8 @@
             # system dependencies
             openssl
             pkg-config
+            kubo
+            (hunspellWithDicts [ hunspellDicts.en_US ])
 
             # dhall
             dhall
@@ -268,6 +265,7 @@
           RUST_LOG = "debug";
           RUST_BACKTRACE = "1";
           GITHUB_SHA = "devel";
+          XESITE_ENV = "dev";
           DHALL_PRELUDE = "${pkgs.dhallPackages.Prelude}";
         };
       }) // {
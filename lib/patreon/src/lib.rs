@@ -62,6 +62,9 @@ pub struct User {
     pub thumb_url: String,
     pub created: DateTime<Utc>,
     pub url: String,
+    /// Only there when the creator's token is allowed to see it.
+    #[serde(default)]
+    pub email: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
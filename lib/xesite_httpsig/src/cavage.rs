@@ -0,0 +1,269 @@
+//! draft-cavage-http-signatures, as used by Mastodon and most of the
+//! fediverse. The signature goes in one `Signature` header that says which
+//! headers it covers.
+
+use crate::{header_value, Algorithm, Error, Result, SigningKey, VerifyingKey};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use http::{HeaderMap, HeaderValue, Method};
+
+/// A parsed `Signature` header.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Signature {
+    pub key_id: String,
+    pub algorithm: Option<String>,
+    /// The names of the signed headers in order, lowercase.
+    pub headers: Vec<String>,
+    pub signature: Vec<u8>,
+}
+
+impl Signature {
+    pub fn parse(header: &str) -> Result<Self> {
+        let mut key_id = None;
+        let mut algorithm = None;
+        let mut headers = None;
+        let mut signature = None;
+
+        let mut rest = header.trim();
+        while !rest.is_empty() {
+            let (name, after) = rest
+                .split_once('=')
+                .ok_or_else(|| Error::Malformed(format!("signature parameter {rest}")))?;
+            let (value, after) = match after.strip_prefix('"') {
+                Some(quoted) => quoted
+                    .split_once('"')
+                    .ok_or_else(|| Error::Malformed(format!("signature parameter {name}")))?,
+                None => after.split_once(',').unwrap_or((after, "")),
+            };
+            match name.trim() {
+                "keyId" => key_id = Some(value.to_string()),
+                "algorithm" => algorithm = Some(value.to_string()),
+                "headers" => {
+                    headers = Some(value.split_whitespace().map(str::to_lowercase).collect())
+                }
+                "signature" => signature = Some(STANDARD.decode(value)?),
+                _ => {}
+            }
+            rest = after.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+        }
+
+        Ok(Self {
+            key_id: key_id.ok_or_else(|| Error::Missing("keyId in signature".into()))?,
+            algorithm,
+            // Only the Date header is signed when the list is left out.
+            headers: headers.unwrap_or_else(|| vec!["date".to_string()]),
+            signature: signature.ok_or_else(|| Error::Missing("signature in signature".into()))?,
+        })
+    }
+
+    /// Fails unless every header in `names` was signed.
+    pub fn require(&self, names: &[&str]) -> Result<()> {
+        match names
+            .iter()
+            .find(|name| !self.headers.iter().any(|h| h == *name))
+        {
+            Some(name) => Err(Error::NotCovered(name.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks the signature of a request against the key of whoever made it.
+    /// `path` is the path and query the request was sent to.
+    pub fn verify(
+        &self,
+        method: &Method,
+        path: &str,
+        headers: &HeaderMap,
+        key: &impl VerifyingKey,
+    ) -> Result<()> {
+        let alg = match self.algorithm.as_deref() {
+            // hs2019 means "whatever the key is for".
+            None | Some("hs2019") => key.algorithm(),
+            Some("rsa-sha256") => Algorithm::RsaV1_5Sha256,
+            Some("hmac-sha256") => Algorithm::HmacSha256,
+            Some("ed25519") => Algorithm::Ed25519,
+            Some(other) => return Err(Error::Unsupported(format!("signature algorithm {other}"))),
+        };
+        let data = signing_string(method, path, headers, &self.headers)?;
+        key.verify(alg, data.as_bytes(), &self.signature)
+    }
+
+    /// The value of the `Signature` header.
+    pub fn header(&self) -> String {
+        let mut params = vec![format!(r#"keyId="{}""#, self.key_id)];
+        if let Some(algorithm) = &self.algorithm {
+            params.push(format!(r#"algorithm="{algorithm}""#));
+        }
+        params.push(format!(r#"headers="{}""#, self.headers.join(" ")));
+        params.push(format!(
+            r#"signature="{}""#,
+            STANDARD.encode(&self.signature)
+        ));
+        params.join(",")
+    }
+}
+
+/// Signs a request and adds the `Signature` header to it. Every header in
+/// `names` other than `(request-target)` has to be set already.
+pub fn sign(
+    key: &impl SigningKey,
+    key_id: &str,
+    method: &Method,
+    path: &str,
+    headers: &mut HeaderMap,
+    names: &[&str],
+) -> Result<Signature> {
+    let names: Vec<String> = names.iter().map(|s| s.to_string()).collect();
+    let data = signing_string(method, path, headers, &names)?;
+    let alg = key.algorithm();
+    let signature = Signature {
+        key_id: key_id.to_string(),
+        algorithm: Some(
+            match alg {
+                Algorithm::RsaV1_5Sha256 => "rsa-sha256",
+                Algorithm::HmacSha256 => "hmac-sha256",
+                _ => "hs2019",
+            }
+            .to_string(),
+        ),
+        headers: names,
+        signature: key.sign(alg, data.as_bytes())?,
+    };
+    headers.insert("signature", HeaderValue::from_str(&signature.header())?);
+    Ok(signature)
+}
+
+/// What gets signed: each named header on its own line, in order.
+pub fn signing_string(
+    method: &Method,
+    path: &str,
+    headers: &HeaderMap,
+    names: &[String],
+) -> Result<String> {
+    let mut lines = vec![];
+    for name in names {
+        if name == "(request-target)" {
+            lines.push(format!(
+                "(request-target): {} {path}",
+                method.as_str().to_lowercase()
+            ));
+        } else {
+            lines.push(format!("{name}: {}", header_value(headers, name)?));
+        }
+    }
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{key::tests::rsa, PublicKey};
+
+    const NAMES: &[&str] = &["(request-target)", "host", "date", "digest"];
+
+    /// The request from the examples in the draft.
+    fn request() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("host", HeaderValue::from_static("example.com"));
+        headers.insert(
+            "date",
+            HeaderValue::from_static("Sun, 05 Jan 2014 21:31:40 GMT"),
+        );
+        headers.insert(
+            "digest",
+            HeaderValue::from_static("SHA-256=X48E9qOokqqrvdts8nOJRJN3OWDUoyWxBf7kbu9DBPE="),
+        );
+        headers
+    }
+
+    #[test]
+    fn parse() {
+        let sig = Signature::parse(r#"keyId="https://pony.social/users/cadey#main-key",algorithm="rsa-sha256",headers="(request-target) host date digest",signature="aGk=""#).unwrap();
+        assert_eq!(sig.key_id, "https://pony.social/users/cadey#main-key");
+        assert_eq!(sig.algorithm.as_deref(), Some("rsa-sha256"));
+        assert_eq!(sig.headers, NAMES);
+        assert_eq!(sig.signature, b"hi");
+        sig.require(NAMES).unwrap();
+        assert_eq!(Signature::parse(&sig.header()).unwrap(), sig);
+
+        let sig = Signature::parse(r#"keyId="a", signature="aGk=""#).unwrap();
+        assert_eq!(sig.headers, vec!["date"]);
+        assert!(matches!(sig.require(NAMES), Err(Error::NotCovered(_))));
+        assert!(Signature::parse(r#"keyId="a""#).is_err());
+    }
+
+    #[test]
+    fn signing_string_order() {
+        let names: Vec<String> = NAMES.iter().map(|s| s.to_string()).collect();
+        assert_eq!(
+            signing_string(
+                &Method::POST,
+                "/foo?param=value&pet=dog",
+                &request(),
+                &names
+            )
+            .unwrap(),
+            include_str!("testdata/cavage-signing-string.txt")
+        );
+        let missing = vec!["x-missing".to_string()];
+        assert!(signing_string(&Method::POST, "/", &request(), &missing).is_err());
+    }
+
+    /// The signature was made with `openssl dgst -sha256 -sign` over
+    /// `testdata/cavage-signing-string.txt`, so this checks against something
+    /// other than this code.
+    #[test]
+    fn rsa_sha256_vector() {
+        const SIGNATURE: &str = "LN0tVjAOh+b8WQpaTecIyNT27aj8YJ93DXrhMWQ2ymOv4ek2fDyOyRbXp8tvoa4Jdi53AB2elBj/rvzaMQxkTLIkgqp5husxgSZpOHVFvkAKHRaKs+hdojiIeGFi+GxT3Xhm8g8wq8QuXFstqaa/LCrzNifjs7sIJP9pj4NrQbdSn1irKfGN/xTJcrEVTAX7Ug38XN9rIsVEwFCYgO19KVZ3eXMH+cXeMRg9WpW3c/sAfn2JM3fLwkQ3nYLfj/qdA5iSvVFatd+Inix7/jFu+sWamfgqBmtCpBj4FCRdshPtORAs5g4u0lqshTIVKtJrPsdKGh6nzEkRx4MvnUglrQ==";
+
+        let mut headers = request();
+        let sig = sign(
+            &rsa(),
+            "Test",
+            &Method::POST,
+            "/foo?param=value&pet=dog",
+            &mut headers,
+            NAMES,
+        )
+        .unwrap();
+        assert_eq!(STANDARD.encode(&sig.signature), SIGNATURE);
+        assert_eq!(
+            headers["signature"],
+            format!(
+                r#"keyId="Test",algorithm="rsa-sha256",headers="(request-target) host date digest",signature="{SIGNATURE}""#
+            )
+        );
+
+        let public =
+            PublicKey::from_pem(rsa().public_key().unwrap().to_pem().unwrap().as_bytes()).unwrap();
+        let sig = Signature::parse(headers["signature"].to_str().unwrap()).unwrap();
+        sig.verify(&Method::POST, "/foo?param=value&pet=dog", &headers, &public)
+            .unwrap();
+        assert!(matches!(
+            sig.verify(&Method::POST, "/foo", &headers, &public),
+            Err(Error::Mismatch)
+        ));
+    }
+
+    #[test]
+    fn hs2019_ed25519() {
+        let key = crate::key::tests::ed25519();
+        let mut headers = request();
+        sign(
+            &key,
+            "Test",
+            &Method::GET,
+            "/",
+            &mut headers,
+            &["(request-target)", "date"],
+        )
+        .unwrap();
+
+        let sig = Signature::parse(headers["signature"].to_str().unwrap()).unwrap();
+        assert_eq!(sig.algorithm.as_deref(), Some("hs2019"));
+        sig.verify(&Method::GET, "/", &headers, &key.public_key().unwrap())
+            .unwrap();
+        assert!(sig
+            .verify(&Method::GET, "/", &headers, &rsa().public_key().unwrap())
+            .is_err());
+    }
+}
@@ -0,0 +1,356 @@
+//! Keys to sign with and check signatures against.
+
+use crate::{Error, Result};
+use openssl::{
+    hash::MessageDigest,
+    memcmp,
+    pkey::{Id, PKey, Private, Public},
+    rsa::{Padding, Rsa},
+    sign::{RsaPssSaltlen, Signer, Verifier},
+};
+use std::{fmt, path::Path};
+
+/// The signature algorithms from the RFC 9421 registry that are supported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    /// RSASSA-PKCS1-v1_5 with SHA-256, what Mastodon signs with.
+    RsaV1_5Sha256,
+    RsaPssSha512,
+    Ed25519,
+    HmacSha256,
+}
+
+impl Algorithm {
+    /// The name used in the `alg` parameter.
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::RsaV1_5Sha256 => "rsa-v1_5-sha256",
+            Algorithm::RsaPssSha512 => "rsa-pss-sha512",
+            Algorithm::Ed25519 => "ed25519",
+            Algorithm::HmacSha256 => "hmac-sha256",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "rsa-v1_5-sha256" => Ok(Algorithm::RsaV1_5Sha256),
+            "rsa-pss-sha512" => Ok(Algorithm::RsaPssSha512),
+            "ed25519" => Ok(Algorithm::Ed25519),
+            "hmac-sha256" => Ok(Algorithm::HmacSha256),
+            other => Err(Error::Unsupported(format!("signature algorithm {other}"))),
+        }
+    }
+
+    fn key_type(self) -> Id {
+        match self {
+            Algorithm::RsaV1_5Sha256 | Algorithm::RsaPssSha512 => Id::RSA,
+            Algorithm::Ed25519 => Id::ED25519,
+            Algorithm::HmacSha256 => Id::HMAC,
+        }
+    }
+
+    /// What a key of type `id` signs with when nothing says otherwise.
+    fn default_for(id: Id) -> Result<Self> {
+        match id {
+            Id::RSA => Ok(Algorithm::RsaV1_5Sha256),
+            Id::ED25519 => Ok(Algorithm::Ed25519),
+            _ => Err(Error::Unsupported("key type".into())),
+        }
+    }
+
+    fn check_key(self, id: Id) -> Result<()> {
+        if self.key_type() == id {
+            Ok(())
+        } else {
+            Err(Error::Unsupported(format!(
+                "{} with this type of key",
+                self.name()
+            )))
+        }
+    }
+}
+
+pub trait SigningKey {
+    /// The algorithm this key signs with by default.
+    fn algorithm(&self) -> Algorithm;
+    fn sign(&self, alg: Algorithm, data: &[u8]) -> Result<Vec<u8>>;
+}
+
+pub trait VerifyingKey {
+    /// The algorithm signatures by this key are assumed to use when they don't
+    /// say.
+    fn algorithm(&self) -> Algorithm;
+    /// Fails with [Error::Mismatch] unless `signature` is a signature of `data`.
+    fn verify(&self, alg: Algorithm, data: &[u8], signature: &[u8]) -> Result<()>;
+}
+
+/// An RSA or Ed25519 private key.
+pub struct PrivateKey(PKey<Private>);
+
+impl PrivateKey {
+    pub fn generate_rsa(bits: u32) -> Result<Self> {
+        Ok(Self(PKey::from_rsa(Rsa::generate(bits)?)?))
+    }
+
+    pub fn generate_ed25519() -> Result<Self> {
+        Ok(Self(PKey::generate_ed25519()?))
+    }
+
+    /// Reads a PEM-encoded key, in PKCS#8 or PKCS#1 form.
+    pub fn from_pem(pem: &[u8]) -> Result<Self> {
+        let key = PKey::private_key_from_pem(pem)?;
+        Algorithm::default_for(key.id())?;
+        Ok(Self(key))
+    }
+
+    /// The key as PKCS#8 PEM.
+    pub fn to_pem(&self) -> Result<Vec<u8>> {
+        Ok(self.0.private_key_to_pem_pkcs8()?)
+    }
+
+    /// Reads the key at `path`. If there isn't one, makes one with `generate`
+    /// and saves it there so only its owner can read it. Returns the key and
+    /// whether it's new.
+    pub fn load_or_generate(
+        path: &Path,
+        generate: impl FnOnce() -> Result<Self>,
+    ) -> Result<(Self, bool)> {
+        match std::fs::read(path) {
+            Ok(pem) => return Ok((Self::from_pem(&pem)?, false)),
+            Err(why) if why.kind() == std::io::ErrorKind::NotFound => {}
+            Err(why) => return Err(why.into()),
+        }
+
+        let key = generate()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        std::io::Write::write_all(&mut options.open(path)?, &key.to_pem()?)?;
+        Ok((key, true))
+    }
+
+    pub fn public_key(&self) -> Result<PublicKey> {
+        Ok(PublicKey(PKey::public_key_from_der(
+            &self.0.public_key_to_der()?,
+        )?))
+    }
+}
+
+impl fmt::Debug for PrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PrivateKey({:?})", self.0.id())
+    }
+}
+
+impl SigningKey for PrivateKey {
+    fn algorithm(&self) -> Algorithm {
+        // Only keys with a default algorithm can be made.
+        Algorithm::default_for(self.0.id()).unwrap()
+    }
+
+    fn sign(&self, alg: Algorithm, data: &[u8]) -> Result<Vec<u8>> {
+        alg.check_key(self.0.id())?;
+        Ok(match alg {
+            Algorithm::RsaV1_5Sha256 => {
+                let mut signer = Signer::new(MessageDigest::sha256(), &self.0)?;
+                signer.update(data)?;
+                signer.sign_to_vec()?
+            }
+            Algorithm::RsaPssSha512 => {
+                let mut signer = Signer::new(MessageDigest::sha512(), &self.0)?;
+                pss(&mut signer)?;
+                signer.update(data)?;
+                signer.sign_to_vec()?
+            }
+            Algorithm::Ed25519 => Signer::new_without_digest(&self.0)?.sign_oneshot_to_vec(data)?,
+            Algorithm::HmacSha256 => unreachable!("checked above"),
+        })
+    }
+}
+
+impl VerifyingKey for PrivateKey {
+    fn algorithm(&self) -> Algorithm {
+        SigningKey::algorithm(self)
+    }
+
+    fn verify(&self, alg: Algorithm, data: &[u8], signature: &[u8]) -> Result<()> {
+        self.public_key()?.verify(alg, data, signature)
+    }
+}
+
+/// An RSA or Ed25519 public key, such as the one in an actor document.
+pub struct PublicKey(PKey<Public>);
+
+impl PublicKey {
+    pub fn from_pem(pem: &[u8]) -> Result<Self> {
+        let key = PKey::public_key_from_pem(pem)?;
+        Algorithm::default_for(key.id())?;
+        Ok(Self(key))
+    }
+
+    /// The key as SubjectPublicKeyInfo PEM, the way actor documents have it.
+    pub fn to_pem(&self) -> Result<String> {
+        Ok(String::from_utf8(self.0.public_key_to_pem()?).expect("PEM is ASCII"))
+    }
+}
+
+impl fmt::Debug for PublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PublicKey({:?})", self.0.id())
+    }
+}
+
+impl VerifyingKey for PublicKey {
+    fn algorithm(&self) -> Algorithm {
+        Algorithm::default_for(self.0.id()).unwrap()
+    }
+
+    fn verify(&self, alg: Algorithm, data: &[u8], signature: &[u8]) -> Result<()> {
+        alg.check_key(self.0.id())?;
+        let ok = match alg {
+            Algorithm::RsaV1_5Sha256 => {
+                let mut verifier = Verifier::new(MessageDigest::sha256(), &self.0)?;
+                verifier.update(data)?;
+                verifier.verify(signature)?
+            }
+            Algorithm::RsaPssSha512 => {
+                let mut verifier = Verifier::new(MessageDigest::sha512(), &self.0)?;
+                verifier.set_rsa_padding(Padding::PKCS1_PSS)?;
+                verifier.set_rsa_mgf1_md(MessageDigest::sha512())?;
+                verifier.set_rsa_pss_saltlen(RsaPssSaltlen::custom(64))?;
+                verifier.update(data)?;
+                verifier.verify(signature)?
+            }
+            Algorithm::Ed25519 => {
+                Verifier::new_without_digest(&self.0)?.verify_oneshot(signature, data)?
+            }
+            Algorithm::HmacSha256 => unreachable!("checked above"),
+        };
+        if ok {
+            Ok(())
+        } else {
+            Err(Error::Mismatch)
+        }
+    }
+}
+
+fn pss(signer: &mut Signer) -> Result<()> {
+    signer.set_rsa_padding(Padding::PKCS1_PSS)?;
+    signer.set_rsa_mgf1_md(MessageDigest::sha512())?;
+    signer.set_rsa_pss_saltlen(RsaPssSaltlen::custom(64))?;
+    Ok(())
+}
+
+/// A secret both sides know, for HMAC signatures.
+pub struct SharedSecret(Vec<u8>);
+
+impl SharedSecret {
+    pub fn new(secret: impl Into<Vec<u8>>) -> Self {
+        Self(secret.into())
+    }
+}
+
+impl fmt::Debug for SharedSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedSecret(..)")
+    }
+}
+
+impl SigningKey for SharedSecret {
+    fn algorithm(&self) -> Algorithm {
+        Algorithm::HmacSha256
+    }
+
+    fn sign(&self, alg: Algorithm, data: &[u8]) -> Result<Vec<u8>> {
+        alg.check_key(Id::HMAC)?;
+        let key = PKey::hmac(&self.0)?;
+        let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+        signer.update(data)?;
+        Ok(signer.sign_to_vec()?)
+    }
+}
+
+impl VerifyingKey for SharedSecret {
+    fn algorithm(&self) -> Algorithm {
+        Algorithm::HmacSha256
+    }
+
+    fn verify(&self, alg: Algorithm, data: &[u8], signature: &[u8]) -> Result<()> {
+        let want = self.sign(alg, data)?;
+        if want.len() == signature.len() && memcmp::eq(&want, signature) {
+            Ok(())
+        } else {
+            Err(Error::Mismatch)
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub fn rsa() -> PrivateKey {
+        PrivateKey::from_pem(include_bytes!("testdata/test-key-rsa.pem")).unwrap()
+    }
+
+    /// The test-key-ed25519 key from RFC 9421, section B.1.4.
+    pub fn ed25519() -> PrivateKey {
+        PrivateKey::from_pem(include_bytes!("testdata/test-key-ed25519.pem")).unwrap()
+    }
+
+    #[test]
+    fn round_trips() {
+        let data = b"hello";
+        for (key, alg) in [
+            (rsa(), Algorithm::RsaV1_5Sha256),
+            (rsa(), Algorithm::RsaPssSha512),
+            (ed25519(), Algorithm::Ed25519),
+        ] {
+            let public =
+                PublicKey::from_pem(key.public_key().unwrap().to_pem().unwrap().as_bytes())
+                    .unwrap();
+            let signature = key.sign(alg, data).unwrap();
+            public.verify(alg, data, &signature).unwrap();
+            assert!(matches!(
+                public.verify(alg, b"goodbye", &signature),
+                Err(Error::Mismatch)
+            ));
+        }
+
+        let secret = SharedSecret::new(b"hunter2".to_vec());
+        let signature = secret.sign(Algorithm::HmacSha256, data).unwrap();
+        secret
+            .verify(Algorithm::HmacSha256, data, &signature)
+            .unwrap();
+        assert!(secret.verify(Algorithm::HmacSha256, data, b"nope").is_err());
+    }
+
+    #[test]
+    fn wrong_key_type() {
+        assert!(ed25519().sign(Algorithm::RsaV1_5Sha256, b"hi").is_err());
+        assert!(rsa().sign(Algorithm::HmacSha256, b"hi").is_err());
+        assert_eq!(SigningKey::algorithm(&ed25519()), Algorithm::Ed25519);
+        assert_eq!(SigningKey::algorithm(&rsa()), Algorithm::RsaV1_5Sha256);
+    }
+
+    #[test]
+    fn load_or_generate() {
+        let dir = std::env::temp_dir().join(format!("xesite_httpsig-{}", std::process::id()));
+        let path = dir.join("key.pem");
+        let _ = std::fs::remove_file(&path);
+
+        let (made, new) =
+            PrivateKey::load_or_generate(&path, PrivateKey::generate_ed25519).unwrap();
+        assert!(new);
+        let (loaded, new) =
+            PrivateKey::load_or_generate(&path, || panic!("made another key")).unwrap();
+        assert!(!new);
+        assert_eq!(made.to_pem().unwrap(), loaded.to_pem().unwrap());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,181 @@
+//! HTTP signatures: signing requests and checking the signatures on them.
+//!
+//! There are two schemes. [cavage] is draft-cavage-http-signatures, the one
+//! Mastodon and most of the fediverse use. [rfc9421] is the standard that
+//! replaces it. Both sign with the keys in [key].
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::prelude::*;
+use http::HeaderMap;
+
+pub mod cavage;
+pub mod key;
+pub mod rfc9421;
+
+pub use key::{Algorithm, PrivateKey, PublicKey, SharedSecret, SigningKey, VerifyingKey};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("{0} is missing")]
+    Missing(String),
+    #[error("malformed {0}")]
+    Malformed(String),
+    #[error("unsupported {0}")]
+    Unsupported(String),
+    #[error("{0} is not signed")]
+    NotCovered(String),
+    #[error("signature doesn't match")]
+    Mismatch,
+    #[error("{0} doesn't match the body")]
+    Digest(&'static str),
+    #[error("{0}")]
+    Stale(String),
+    #[error("header value isn't text: {0}")]
+    HeaderValue(#[from] http::header::ToStrError),
+    #[error(transparent)]
+    InvalidHeaderValue(#[from] http::header::InvalidHeaderValue),
+    #[error(transparent)]
+    Base64(#[from] base64::DecodeError),
+    #[error(transparent)]
+    OpenSSL(#[from] openssl::error::ErrorStack),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Every value of a header joined with commas, the way both schemes sign
+/// headers that show up more than once.
+pub(crate) fn header_value(headers: &HeaderMap, name: &str) -> Result<String> {
+    let values = headers
+        .get_all(name)
+        .iter()
+        .map(|value| value.to_str().map(str::trim))
+        .collect::<Result<Vec<_>, _>>()?;
+    if values.is_empty() {
+        return Err(Error::Missing(format!("signed header {name}")));
+    }
+    Ok(values.join(", "))
+}
+
+/// The value of a `Digest` header for `body`, which goes with [cavage]
+/// signatures.
+pub fn digest(body: &[u8]) -> String {
+    format!("SHA-256={}", STANDARD.encode(openssl::sha::sha256(body)))
+}
+
+/// Fails unless the `Digest` header matches the body.
+pub fn check_digest(headers: &HeaderMap, body: &[u8]) -> Result<()> {
+    let header = headers
+        .get("digest")
+        .ok_or_else(|| Error::Missing("Digest header".into()))?
+        .to_str()?;
+    let want = digest(body);
+    if header.split(',').any(|d| d.trim() == want) {
+        Ok(())
+    } else {
+        Err(Error::Digest("Digest"))
+    }
+}
+
+/// The value of a `Content-Digest` header (RFC 9530) for `body`, which goes
+/// with [rfc9421] signatures.
+pub fn content_digest(body: &[u8]) -> String {
+    format!("sha-256=:{}:", STANDARD.encode(openssl::sha::sha256(body)))
+}
+
+/// Fails unless the `Content-Digest` header has a SHA-256 or SHA-512 digest
+/// and it matches the body.
+pub fn check_content_digest(headers: &HeaderMap, body: &[u8]) -> Result<()> {
+    let header = headers
+        .get("content-digest")
+        .ok_or_else(|| Error::Missing("Content-Digest header".into()))?
+        .to_str()?;
+
+    let mut checked = false;
+    for member in header.split(',') {
+        let Some((alg, value)) = member.trim().split_once('=') else {
+            return Err(Error::Malformed(format!("Content-Digest {member}")));
+        };
+        let want = match alg {
+            "sha-256" => openssl::sha::sha256(body).to_vec(),
+            "sha-512" => openssl::sha::sha512(body).to_vec(),
+            _ => continue,
+        };
+        let value = value
+            .strip_prefix(':')
+            .and_then(|v| v.strip_suffix(':'))
+            .ok_or_else(|| Error::Malformed(format!("Content-Digest {member}")))?;
+        if STANDARD.decode(value)? != want {
+            return Err(Error::Digest("Content-Digest"));
+        }
+        checked = true;
+    }
+
+    if checked {
+        Ok(())
+    } else {
+        Err(Error::Unsupported("Content-Digest algorithms".into()))
+    }
+}
+
+/// The value of a `Date` header for `now`.
+pub fn date(now: DateTime<Utc>) -> String {
+    now.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// Fails if the `Date` header is more than `max_skew` seconds from `now`, so
+/// old requests can't be replayed.
+pub fn check_date(headers: &HeaderMap, now: DateTime<Utc>, max_skew: i64) -> Result<()> {
+    let date = headers
+        .get("date")
+        .ok_or_else(|| Error::Missing("Date header".into()))?
+        .to_str()?;
+    let date = DateTime::parse_from_rfc2822(date)
+        .map_err(|why| Error::Malformed(format!("Date {date}: {why}")))?;
+    if (now - date.with_timezone(&Utc)).num_seconds().abs() > max_skew {
+        Err(Error::Stale(format!("Date {date} is too far from now")))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::HeaderValue;
+
+    #[test]
+    fn digests() {
+        let mut headers = HeaderMap::new();
+        headers.insert("digest", HeaderValue::from_str(&digest(b"{}")).unwrap());
+        check_digest(&headers, b"{}").unwrap();
+        assert!(check_digest(&headers, b"{\"a\":1}").is_err());
+
+        // RFC 9530, section B.2.
+        assert_eq!(
+            content_digest(b"{\"hello\": \"world\"}\n"),
+            "sha-256=:RK/0qy18MlBSVnWgjwz6lZEWjP/lF5HF9bvEF8FabDg=:"
+        );
+        headers.insert(
+            "content-digest",
+            HeaderValue::from_static("sha-256=:RK/0qy18MlBSVnWgjwz6lZEWjP/lF5HF9bvEF8FabDg=:"),
+        );
+        check_content_digest(&headers, b"{\"hello\": \"world\"}\n").unwrap();
+        assert!(check_content_digest(&headers, b"{}").is_err());
+
+        headers.insert("content-digest", HeaderValue::from_static("md5=:aGk=:"));
+        assert!(check_content_digest(&headers, b"{}").is_err());
+    }
+
+    #[test]
+    fn dates() {
+        let sent = Utc.with_ymd_and_hms(2014, 1, 5, 21, 31, 40).unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("date", HeaderValue::from_str(&date(sent)).unwrap());
+        assert_eq!(headers["date"], "Sun, 05 Jan 2014 21:31:40 GMT");
+
+        check_date(&headers, sent + chrono::Duration::hours(1), 2 * 60 * 60).unwrap();
+        assert!(check_date(&headers, sent + chrono::Duration::hours(3), 2 * 60 * 60).is_err());
+    }
+}
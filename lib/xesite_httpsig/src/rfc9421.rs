@@ -0,0 +1,660 @@
+//! RFC 9421 HTTP message signatures. A request can have several signatures,
+//! each with a label that ties its entry in the `Signature-Input` header
+//! (what it covers) to the one in the `Signature` header (the signature
+//! itself).
+//!
+//! Components with parameters, such as `"@query-param";name="foo"`, aren't
+//! supported.
+
+use crate::{header_value, Algorithm, Error, Result, SigningKey, VerifyingKey};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use http::{HeaderMap, HeaderValue, Method, Uri};
+use std::fmt;
+
+/// The parts of a request signatures can cover.
+#[derive(Clone, Copy, Debug)]
+pub struct Message<'a> {
+    pub method: &'a Method,
+    /// The full URI, with the scheme and authority.
+    pub uri: &'a Uri,
+    pub headers: &'a HeaderMap,
+}
+
+impl Message<'_> {
+    /// The value of a component, either a derived one like `@method` or a
+    /// header.
+    pub fn component(&self, name: &str) -> Result<String> {
+        Ok(match name {
+            "@method" => self.method.as_str().to_string(),
+            "@target-uri" => self.uri.to_string(),
+            "@authority" => match self.uri.authority() {
+                Some(authority) => authority.as_str().to_lowercase(),
+                None => header_value(self.headers, "host")?.to_lowercase(),
+            },
+            "@scheme" => self.uri.scheme_str().unwrap_or("https").to_lowercase(),
+            "@request-target" => self
+                .uri
+                .path_and_query()
+                .map(|p| p.as_str())
+                .unwrap_or("/")
+                .to_string(),
+            "@path" => self.uri.path().to_string(),
+            "@query" => format!("?{}", self.uri.query().unwrap_or_default()),
+            name if name.starts_with('@') => {
+                return Err(Error::Unsupported(format!("component {name}")))
+            }
+            name => header_value(self.headers, name)?,
+        })
+    }
+}
+
+/// A bare item from a structured header (RFC 8941).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Item {
+    Integer(i64),
+    String(String),
+    Token(String),
+    Bytes(Vec<u8>),
+    Boolean(bool),
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Item::Integer(n) => write!(f, "{n}"),
+            Item::String(s) => {
+                f.write_str("\"")?;
+                for c in s.chars() {
+                    if c == '"' || c == '\\' {
+                        f.write_str("\\")?;
+                    }
+                    write!(f, "{c}")?;
+                }
+                f.write_str("\"")
+            }
+            Item::Token(t) => f.write_str(t),
+            Item::Bytes(b) => write!(f, ":{}:", STANDARD.encode(b)),
+            Item::Boolean(b) => write!(f, "?{}", u8::from(*b)),
+        }
+    }
+}
+
+/// What a signature covers and its parameters, one entry of the
+/// `Signature-Input` header.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Input {
+    /// The names of the signed components in order.
+    pub components: Vec<String>,
+    /// Parameters like `created` and `keyid`, in order.
+    pub params: Vec<(String, Item)>,
+}
+
+impl Input {
+    pub fn new(components: &[&str]) -> Self {
+        Self {
+            components: components.iter().map(|c| c.to_string()).collect(),
+            params: vec![],
+        }
+    }
+
+    /// Adds a parameter.
+    pub fn with(mut self, name: &str, value: Item) -> Self {
+        self.params.push((name.to_string(), value));
+        self
+    }
+
+    pub fn param(&self, name: &str) -> Option<&Item> {
+        self.params.iter().find(|(n, _)| n == name).map(|(_, v)| v)
+    }
+
+    fn integer(&self, name: &str) -> Option<i64> {
+        match self.param(name) {
+            Some(Item::Integer(n)) => Some(*n),
+            _ => None,
+        }
+    }
+
+    fn string(&self, name: &str) -> Option<&str> {
+        match self.param(name) {
+            Some(Item::String(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// When the signature was made, in seconds since the Unix epoch.
+    pub fn created(&self) -> Option<i64> {
+        self.integer("created")
+    }
+
+    pub fn expires(&self) -> Option<i64> {
+        self.integer("expires")
+    }
+
+    pub fn keyid(&self) -> Option<&str> {
+        self.string("keyid")
+    }
+
+    pub fn alg(&self) -> Option<&str> {
+        self.string("alg")
+    }
+}
+
+/// The serialized inner list, as in the header and in `@signature-params`.
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("(")?;
+        for (i, component) in self.components.iter().enumerate() {
+            if i != 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{}", Item::String(component.clone()))?;
+        }
+        f.write_str(")")?;
+        for (name, value) in &self.params {
+            match value {
+                Item::Boolean(true) => write!(f, ";{name}")?,
+                value => write!(f, ";{name}={value}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What gets signed: each component on its own line, then the parameters.
+pub fn signature_base(msg: &Message, input: &Input) -> Result<String> {
+    let mut lines = vec![];
+    for name in &input.components {
+        if input.components.iter().filter(|c| *c == name).count() != 1 {
+            return Err(Error::Malformed(format!(
+                "signature input, {name} is in it twice"
+            )));
+        }
+        lines.push(format!("\"{name}\": {}", msg.component(name)?));
+    }
+    lines.push(format!("\"@signature-params\": {input}"));
+    Ok(lines.join("\n"))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Signature {
+    pub label: String,
+    pub input: Input,
+    pub signature: Vec<u8>,
+}
+
+impl Signature {
+    /// Fails unless every component in `names` was signed.
+    pub fn require(&self, names: &[&str]) -> Result<()> {
+        match names
+            .iter()
+            .find(|name| !self.input.components.iter().any(|c| c == *name))
+        {
+            Some(name) => Err(Error::NotCovered(name.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    /// Fails unless the signature says when it was made, that was at most
+    /// `max_age` seconds from `now`, and it hasn't expired.
+    pub fn check_age(&self, now: i64, max_age: i64) -> Result<()> {
+        let created = self
+            .input
+            .created()
+            .ok_or_else(|| Error::Missing("created parameter".into()))?;
+        if (now - created).abs() > max_age {
+            return Err(Error::Stale(format!(
+                "signature made at {created} is too far from now"
+            )));
+        }
+        match self.input.expires() {
+            Some(expires) if expires < now => {
+                Err(Error::Stale(format!("signature expired at {expires}")))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the signature against the key of whoever made it. The algorithm
+    /// is the one in the `alg` parameter, or the key's.
+    pub fn verify(&self, msg: &Message, key: &impl VerifyingKey) -> Result<()> {
+        let alg = match self.input.alg() {
+            Some(name) => Algorithm::from_name(name)?,
+            None => key.algorithm(),
+        };
+        let base = signature_base(msg, &self.input)?;
+        key.verify(alg, base.as_bytes(), &self.signature)
+    }
+
+    /// Adds the signature to the `Signature-Input` and `Signature` headers.
+    pub fn insert(&self, headers: &mut HeaderMap) -> Result<()> {
+        headers.append(
+            "signature-input",
+            HeaderValue::from_str(&format!("{}={}", self.label, self.input))?,
+        );
+        headers.append(
+            "signature",
+            HeaderValue::from_str(&format!(
+                "{}={}",
+                self.label,
+                Item::Bytes(self.signature.clone())
+            ))?,
+        );
+        Ok(())
+    }
+}
+
+/// Signs a request. The signature still has to be added to it with
+/// [Signature::insert].
+pub fn sign(key: &impl SigningKey, label: &str, msg: &Message, input: Input) -> Result<Signature> {
+    let alg = match input.alg() {
+        Some(name) => Algorithm::from_name(name)?,
+        None => key.algorithm(),
+    };
+    let base = signature_base(msg, &input)?;
+    Ok(Signature {
+        label: label.to_string(),
+        signature: key.sign(alg, base.as_bytes())?,
+        input,
+    })
+}
+
+/// Every signature on a request.
+pub fn signatures(headers: &HeaderMap) -> Result<Vec<Signature>> {
+    let inputs = dictionary(headers, "signature-input", |fields| {
+        let components = fields
+            .inner_list()?
+            .into_iter()
+            .map(|(item, params)| match item {
+                Item::String(name) if params.is_empty() => Ok(name),
+                Item::String(name) => Err(Error::Unsupported(format!(
+                    "component parameters on {name}"
+                ))),
+                _ => Err(Error::Malformed("component name".into())),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if let Some(name) = components
+            .iter()
+            .find(|c| c.chars().any(|c| c.is_ascii_uppercase()))
+        {
+            return Err(Error::Malformed(format!("component name {name}")));
+        }
+        Ok(Input {
+            components,
+            params: fields.params()?,
+        })
+    })?;
+    let mut signatures = dictionary(headers, "signature", |fields| {
+        let item = fields.bare_item()?;
+        fields.params()?;
+        match item {
+            Item::Bytes(b) => Ok(b),
+            _ => Err(Error::Malformed("signature".into())),
+        }
+    })?;
+
+    inputs
+        .into_iter()
+        .map(|(label, input)| {
+            let i = signatures
+                .iter()
+                .position(|(l, _)| *l == label)
+                .ok_or_else(|| Error::Missing(format!("signature {label}")))?;
+            let (_, signature) = signatures.swap_remove(i);
+            Ok(Signature {
+                label,
+                input,
+                signature,
+            })
+        })
+        .collect()
+}
+
+/// Reads a dictionary header, with `member` reading each member's value.
+fn dictionary<T>(
+    headers: &HeaderMap,
+    name: &str,
+    mut member: impl FnMut(&mut Fields) -> Result<T>,
+) -> Result<Vec<(String, T)>> {
+    let value = match header_value(headers, name) {
+        Ok(value) => value,
+        Err(Error::Missing(_)) => return Err(Error::Missing(format!("{name} header"))),
+        Err(why) => return Err(why),
+    };
+
+    let mut fields = Fields { rest: value.trim() };
+    let mut result = vec![];
+    while !fields.rest.is_empty() {
+        let label = fields.key()?.to_string();
+        if !fields.eat('=') {
+            return Err(Error::Malformed(format!("{name} member {label}")));
+        }
+        result.push((label, member(&mut fields)?));
+
+        fields.rest = fields.rest.trim_start();
+        if !fields.rest.is_empty() && !fields.eat(',') {
+            return Err(Error::Malformed(format!("{name} header")));
+        }
+        fields.rest = fields.rest.trim_start();
+    }
+    Ok(result)
+}
+
+/// Just enough of a structured header parser for the two signature headers.
+struct Fields<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Fields<'a> {
+    fn eat(&mut self, c: char) -> bool {
+        match self.rest.strip_prefix(c) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+        let end = self.rest.find(|c| !f(c)).unwrap_or(self.rest.len());
+        let (taken, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        taken
+    }
+
+    fn key(&mut self) -> Result<&'a str> {
+        if !self
+            .rest
+            .starts_with(|c: char| c.is_ascii_lowercase() || c == '*')
+        {
+            return Err(Error::Malformed(format!("key at {}", self.rest)));
+        }
+        Ok(self.take_while(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "_-.*".contains(c)))
+    }
+
+    fn bare_item(&mut self) -> Result<Item> {
+        match self.rest.chars().next() {
+            Some('"') => {
+                self.eat('"');
+                let mut s = String::new();
+                let mut chars = self.rest.char_indices();
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '"' => {
+                            self.rest = &self.rest[i + 1..];
+                            return Ok(Item::String(s));
+                        }
+                        '\\' => match chars.next() {
+                            Some((_, c @ ('"' | '\\'))) => s.push(c),
+                            _ => return Err(Error::Malformed("string escape".into())),
+                        },
+                        c if c.is_ascii() && !c.is_ascii_control() => s.push(c),
+                        _ => return Err(Error::Malformed("string".into())),
+                    }
+                }
+                Err(Error::Malformed("unterminated string".into()))
+            }
+            Some(':') => {
+                self.eat(':');
+                let b = self.take_while(|c| c != ':');
+                if !self.eat(':') {
+                    return Err(Error::Malformed("unterminated byte sequence".into()));
+                }
+                Ok(Item::Bytes(STANDARD.decode(b)?))
+            }
+            Some('?') => {
+                self.eat('?');
+                if self.eat('1') {
+                    Ok(Item::Boolean(true))
+                } else if self.eat('0') {
+                    Ok(Item::Boolean(false))
+                } else {
+                    Err(Error::Malformed("boolean".into()))
+                }
+            }
+            Some(c) if c == '-' || c.is_ascii_digit() => {
+                let negative = self.eat('-');
+                let digits = self.take_while(|c| c.is_ascii_digit());
+                if digits.is_empty() || digits.len() > 15 {
+                    return Err(Error::Malformed("integer".into()));
+                }
+                let n: i64 = digits.parse().expect("only digits");
+                Ok(Item::Integer(if negative { -n } else { n }))
+            }
+            Some(c) if c.is_ascii_alphabetic() || c == '*' => Ok(Item::Token(
+                self.take_while(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~:/".contains(c))
+                    .to_string(),
+            )),
+            _ => Err(Error::Malformed(format!("item at {}", self.rest))),
+        }
+    }
+
+    fn params(&mut self) -> Result<Vec<(String, Item)>> {
+        let mut params = vec![];
+        while self.eat(';') {
+            self.rest = self.rest.trim_start_matches(' ');
+            let name = self.key()?.to_string();
+            let value = if self.eat('=') {
+                self.bare_item()?
+            } else {
+                Item::Boolean(true)
+            };
+            params.push((name, value));
+        }
+        Ok(params)
+    }
+
+    fn inner_list(&mut self) -> Result<Vec<(Item, Vec<(String, Item)>)>> {
+        if !self.eat('(') {
+            return Err(Error::Malformed("inner list".into()));
+        }
+        let mut items = vec![];
+        loop {
+            self.rest = self.rest.trim_start_matches(' ');
+            if self.eat(')') {
+                return Ok(items);
+            }
+            let item = self.bare_item()?;
+            items.push((item, self.params()?));
+            if !self.rest.starts_with([' ', ')']) {
+                return Err(Error::Malformed("inner list".into()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{key::tests::ed25519, SharedSecret};
+
+    /// The request from the examples in RFC 9421, section B.2.
+    fn request() -> (Method, Uri, HeaderMap) {
+        let mut headers = HeaderMap::new();
+        headers.insert("host", HeaderValue::from_static("example.com"));
+        headers.insert(
+            "date",
+            HeaderValue::from_static("Tue, 20 Apr 2021 02:07:55 GMT"),
+        );
+        headers.insert("content-type", HeaderValue::from_static("application/json"));
+        headers.insert(
+            "content-digest",
+            HeaderValue::from_static("sha-512=:WZDPaVn/7XgHaAy8pmojAkGWoRx2UFChF41A2svX+TaPm+AbwAgBWnrIiYllu7BNNyealdVLvRwEmTHWXvJwew==:"),
+        );
+        headers.insert("content-length", HeaderValue::from_static("18"));
+        (
+            Method::POST,
+            "https://example.com/foo?param=Value&Pet=dog"
+                .parse()
+                .unwrap(),
+            headers,
+        )
+    }
+
+    #[test]
+    fn components() {
+        let (method, uri, headers) = request();
+        let msg = Message {
+            method: &method,
+            uri: &uri,
+            headers: &headers,
+        };
+        assert_eq!(msg.component("@method").unwrap(), "POST");
+        assert_eq!(
+            msg.component("@target-uri").unwrap(),
+            "https://example.com/foo?param=Value&Pet=dog"
+        );
+        assert_eq!(msg.component("@authority").unwrap(), "example.com");
+        assert_eq!(msg.component("@scheme").unwrap(), "https");
+        assert_eq!(
+            msg.component("@request-target").unwrap(),
+            "/foo?param=Value&Pet=dog"
+        );
+        assert_eq!(msg.component("@path").unwrap(), "/foo");
+        assert_eq!(msg.component("@query").unwrap(), "?param=Value&Pet=dog");
+        assert_eq!(msg.component("content-length").unwrap(), "18");
+        assert!(msg.component("@status").is_err());
+        assert!(msg.component("x-missing").is_err());
+
+        let uri: Uri = "/foo".parse().unwrap();
+        let msg = Message { uri: &uri, ..msg };
+        assert_eq!(msg.component("@authority").unwrap(), "example.com");
+    }
+
+    /// RFC 9421, section B.2.5.
+    #[test]
+    fn hmac_sha256_vector() {
+        const SECRET: &str = "uzvJfB4u3N0Jy4T7NZ75MDVcr8zSTInedJtkgcu46YW4XByzNJjxBdtjUkdJPBtbmHhIDi6pcl8jsasjlTMtDQ==";
+        let (method, uri, mut headers) = request();
+        let key = SharedSecret::new(STANDARD.decode(SECRET).unwrap());
+        let msg = Message {
+            method: &method,
+            uri: &uri,
+            headers: &headers,
+        };
+
+        let input = Input::new(&["date", "@authority", "content-type"])
+            .with("created", Item::Integer(1618884473))
+            .with("keyid", Item::String("test-shared-secret".into()));
+        assert_eq!(
+            signature_base(&msg, &input).unwrap(),
+            "\"date\": Tue, 20 Apr 2021 02:07:55 GMT\n\"@authority\": example.com\n\"content-type\": application/json\n\"@signature-params\": (\"date\" \"@authority\" \"content-type\");created=1618884473;keyid=\"test-shared-secret\""
+        );
+
+        let sig = sign(&key, "sig-b25", &msg, input).unwrap();
+        sig.insert(&mut headers).unwrap();
+        assert_eq!(
+            headers["signature-input"],
+            r#"sig-b25=("date" "@authority" "content-type");created=1618884473;keyid="test-shared-secret""#
+        );
+        assert_eq!(
+            headers["signature"],
+            "sig-b25=:pxcQw6G3AjtMBQjwo8XzkZf/bws5LelbaMk5rGIGtE8=:"
+        );
+    }
+
+    /// RFC 9421, section B.2.6.
+    #[test]
+    fn ed25519_vector() {
+        let (method, uri, mut headers) = request();
+        headers.insert(
+            "signature-input",
+            HeaderValue::from_static(r#"sig-b26=("date" "@method" "@path" "@authority" "content-type" "content-length");created=1618884473;keyid="test-key-ed25519""#),
+        );
+        headers.insert(
+            "signature",
+            HeaderValue::from_static("sig-b26=:wqcAqbmYJ2ji2glfAMaRy4gruYYnx2nEFN2HN6jrnDnQCK1u02Gb04v9EDgwUPiu4A0w6vuQv5lIp5WPpBKRCw==:"),
+        );
+        let msg = Message {
+            method: &method,
+            uri: &uri,
+            headers: &headers,
+        };
+
+        let sigs = signatures(&headers).unwrap();
+        assert_eq!(sigs.len(), 1);
+        let sig = &sigs[0];
+        assert_eq!(sig.label, "sig-b26");
+        assert_eq!(sig.input.keyid(), Some("test-key-ed25519"));
+        assert_eq!(sig.input.created(), Some(1618884473));
+        sig.require(&["@method", "@authority"]).unwrap();
+        assert!(sig.require(&["content-digest"]).is_err());
+
+        let public = ed25519().public_key().unwrap();
+        sig.verify(&msg, &public).unwrap();
+        let resigned = sign(&ed25519(), "sig-b26", &msg, sig.input.clone()).unwrap();
+        assert_eq!(&resigned, sig);
+
+        let put = Method::PUT;
+        let tampered = Message {
+            method: &put,
+            ..msg
+        };
+        assert!(matches!(
+            sig.verify(&tampered, &public),
+            Err(Error::Mismatch)
+        ));
+    }
+
+    #[test]
+    fn rsa_pss_round_trip() {
+        let (method, uri, mut headers) = request();
+        let key = crate::key::tests::rsa();
+        let input = Input::new(&["@method", "@target-uri", "content-digest"])
+            .with("created", Item::Integer(1618884473))
+            .with("expires", Item::Integer(1618884773))
+            .with("keyid", Item::String("test-key-rsa".into()))
+            .with("alg", Item::String("rsa-pss-sha512".into()));
+        let msg = Message {
+            method: &method,
+            uri: &uri,
+            headers: &headers,
+        };
+        let sig = sign(&key, "sig1", &msg, input).unwrap();
+
+        sig.insert(&mut headers).unwrap();
+        let sigs = signatures(&headers).unwrap();
+        let msg = Message {
+            method: &method,
+            uri: &uri,
+            headers: &headers,
+        };
+        sigs[0].verify(&msg, &key.public_key().unwrap()).unwrap();
+        sigs[0].check_age(1618884500, 60).unwrap();
+        assert!(sigs[0].check_age(1618884800, 600).is_err());
+        assert!(sigs[0].check_age(1618890000, 60).is_err());
+    }
+
+    #[test]
+    fn parse_several() {
+        let mut headers = HeaderMap::new();
+        headers.append(
+            "signature-input",
+            HeaderValue::from_static(
+                r#"a=("@method");created=1, b=("@path" "x-y");keyid="k\"1";tag=app;flag"#,
+            ),
+        );
+        headers.append("signature", HeaderValue::from_static("b=:aGk=:, a=:aGk=:"));
+
+        let sigs = signatures(&headers).unwrap();
+        assert_eq!(sigs[0].label, "a");
+        assert_eq!(sigs[1].input.components, vec!["@path", "x-y"]);
+        assert_eq!(sigs[1].input.keyid(), Some("k\"1"));
+        assert_eq!(sigs[1].input.param("tag"), Some(&Item::Token("app".into())));
+        assert_eq!(sigs[1].input.param("flag"), Some(&Item::Boolean(true)));
+        assert_eq!(
+            sigs[1].input.to_string(),
+            r#"("@path" "x-y");keyid="k\"1";tag=app;flag"#
+        );
+
+        headers.insert("signature", HeaderValue::from_static("a=:aGk=:"));
+        assert!(matches!(signatures(&headers), Err(Error::Missing(_))));
+        headers.insert(
+            "signature-input",
+            HeaderValue::from_static(r#"a=("@query-param";name="x")"#),
+        );
+        assert!(matches!(signatures(&headers), Err(Error::Unsupported(_))));
+        headers.insert("signature-input", HeaderValue::from_static(r#"a=("Date")"#));
+        assert!(signatures(&headers).is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use maud::html;
+use std::fs;
+use xesite_types::mastodon::{Toot, User};
+
+/// Shortcodes read their data relative to the root of the repo.
+fn chdir_to_repo() {
+    let _ = std::env::set_current_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/../.."));
+}
+
+/// Strips the YAML frontmatter off of a post.
+fn body(post: &str) -> &str {
+    post.splitn(3, "---\n").nth(2).unwrap_or(post)
+}
+
+fn conv(c: &mut Criterion) {
+    c.bench_function("conv", |b| {
+        b.iter(|| {
+            xesite_templates::conv(
+                black_box("Mara".into()),
+                black_box("hacker".into()),
+                html! { "Wait, you can do that with " code {"async"} "?" },
+            )
+        })
+    });
+}
+
+fn toot_embed(c: &mut Criterion) {
+    chdir_to_repo();
+
+    let toot = fs::read_dir("./data/toots")
+        .expect("toots to exist")
+        .filter_map(Result::ok)
+        .next()
+        .expect("at least one toot");
+    let toot = fs::read_to_string(toot.path()).unwrap();
+    let t: Toot = serde_json::from_str(&toot).unwrap();
+    let user = fs::read_dir("./data/users")
+        .unwrap()
+        .filter_map(Result::ok)
+        .map(|f| fs::read_to_string(f.path()).unwrap())
+        .find(|u| serde_json::from_str::<User>(u).unwrap().id == t.attributed_to)
+        .expect("the user that posted the toot");
+
+    c.bench_function("toot_embed", |b| {
+        b.iter(|| {
+            let u: User = serde_json::from_str(&user).unwrap();
+            let t: Toot = serde_json::from_str(&toot).unwrap();
+            xesite_templates::toot_embed(u, t)
+        })
+    });
+}
+
+fn full_post(c: &mut Criterion) {
+    chdir_to_repo();
+
+    let mut posts: Vec<String> = glob_posts();
+    posts.sort_by_key(|p| std::cmp::Reverse(p.len()));
+
+    c.bench_function("render longest post", |b| {
+        b.iter(|| xesite_markdown::render(black_box(body(&posts[0]))).unwrap())
+    });
+
+    let mut group = c.benchmark_group("corpus");
+    group.sample_size(10);
+    group.bench_function("render every blogpost", |b| {
+        b.iter(|| {
+            for post in &posts {
+                xesite_markdown::render(black_box(body(post))).unwrap();
+            }
+        })
+    });
+    group.finish();
+}
+
+fn glob_posts() -> Vec<String> {
+    fs::read_dir("./blog")
+        .expect("blog folder to exist")
+        .filter_map(Result::ok)
+        .filter(|f| f.path().extension().map_or(false, |e| e == "markdown"))
+        .map(|f| fs::read_to_string(f.path()).unwrap())
+        .collect()
+}
+
+criterion_group!(benches, conv, toot_embed, full_post);
+criterion_main!(benches);
@@ -0,0 +1,67 @@
+use color_eyre::eyre::{eyre, Result};
+use std::io::Read;
+use xesite_types::route::{Route, TrackPoint};
+use xml::reader::{EventReader, XmlEvent};
+
+/// Parses the track and route points out of a GPX file.
+pub fn parse<R: Read>(input: R) -> Result<Route> {
+    let mut route = Route::default();
+    let mut current: Option<TrackPoint> = None;
+    let mut in_ele = false;
+    let mut in_name = false;
+
+    for event in EventReader::new(input) {
+        match event? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => match name.local_name.as_str() {
+                "trkpt" | "rtept" => {
+                    let attr = |key: &str| -> Result<f64> {
+                        attributes
+                            .iter()
+                            .find(|a| a.name.local_name == key)
+                            .ok_or_else(|| eyre!("{} is missing the {key} attribute", name.local_name))?
+                            .value
+                            .parse()
+                            .map_err(|why| eyre!("can't parse {key}: {why}"))
+                    };
+                    current = Some(TrackPoint {
+                        lat: attr("lat")?,
+                        lon: attr("lon")?,
+                        ele: None,
+                    });
+                }
+                "ele" => in_ele = current.is_some(),
+                "name" => in_name = route.name.is_none() && current.is_none(),
+                _ => {}
+            },
+            XmlEvent::Characters(text) => {
+                if in_ele {
+                    if let Some(point) = current.as_mut() {
+                        point.ele = text.trim().parse().ok();
+                    }
+                }
+                if in_name {
+                    route.name = Some(text.trim().to_string());
+                }
+            }
+            XmlEvent::EndElement { name } => match name.local_name.as_str() {
+                "trkpt" | "rtept" => {
+                    if let Some(point) = current.take() {
+                        route.points.push(point);
+                    }
+                }
+                "ele" => in_ele = false,
+                "name" => in_name = false,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    if route.points.is_empty() {
+        return Err(eyre!("GPX file has no track or route points"));
+    }
+
+    Ok(route)
+}
@@ -2,16 +2,29 @@ use color_eyre::eyre::{Result, WrapErr};
 use comrak::nodes::{Ast, AstNode, LineColumn, NodeValue};
 use comrak::plugins::syntect::SyntectAdapter;
 use comrak::{
-    format_html_with_plugins, markdown_to_html_with_plugins, parse_document, Arena, ComrakOptions,
-    ComrakPlugins,
+    format_html_with_plugins, markdown_to_html, markdown_to_html_with_plugins, parse_document,
+    Arena, ComrakOptions, ComrakPlugins,
 };
 use lazy_static::lazy_static;
-use lol_html::{element, html_content::ContentType, rewrite_str, RewriteStrSettings};
-use maud::PreEscaped;
+use lol_html::{
+    element,
+    html_content::{ContentType, Element},
+    rewrite_str, RewriteStrSettings,
+};
+use maud::{html, PreEscaped};
 use sha2::{Digest, Sha256};
-use std::{cell::RefCell, fmt::Write};
+use std::{cell::RefCell, fmt::Write, rc::Rc};
 use url::Url;
-use xesite_types::mastodon::{Toot, User};
+use xesite_templates::{context, CalloutKind};
+use xesite_types::{
+    bench::BenchData,
+    embed::{self, Embed, EmbedKind},
+    mastodon::{Toot, User},
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+mod gpx;
+pub mod toc;
 
 pub fn hash_string(inp: String) -> String {
     let mut h = Sha256::new();
@@ -27,9 +40,55 @@ lazy_static! {
 pub enum Error {
     #[error("missing element attribute {0}")]
     MissingElementAttribute(String),
+    #[error("invalid element attribute {0}: {1}")]
+    InvalidElementAttribute(String, String),
+}
+
+/// Stands in for the body of an element when a template is rendered around
+/// it.
+const BODY: &str = "<!--body-->";
+
+/// The `widths` attribute of an image element, a comma-separated list of
+/// widths that overrides the site's.
+fn widths(el: &Element) -> Result<Option<Vec<u32>>, Error> {
+    let Some(widths) = el.get_attribute("widths") else {
+        return Ok(None);
+    };
+    widths
+        .split(',')
+        .map(|w| w.trim().parse::<u32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map(Some)
+        .map_err(|why| Error::InvalidElementAttribute("widths".into(), format!("{widths}: {why}")))
 }
 
 pub fn render(inp: &str) -> Result<String> {
+    use xesite_templates::context;
+
+    // Every document numbers its figures and footnotes from 1, and its
+    // footnotes go at the end of it.
+    let (html, notes) =
+        context::collect_footnotes(|| context::with_figures(|| render_document(inp)));
+    let mut html = html?;
+    html.push_str(&xesite_templates::footnotes(&notes).0);
+    Ok(html)
+}
+
+/// Renders a line of markdown from a shortcode attribute. Raw HTML is left
+/// out, and a lone paragraph is unwrapped so it can go inside other text.
+fn render_inline(inp: &str) -> String {
+    let html = markdown_to_html(inp, &ComrakOptions::default());
+    let html = html.trim();
+    match html
+        .strip_prefix("<p>")
+        .and_then(|html| html.strip_suffix("</p>"))
+    {
+        Some(inner) if !inner.contains("<p>") => inner.to_string(),
+        _ => html.to_string(),
+    }
+}
+
+fn render_document(inp: &str) -> Result<String> {
     let mut options = ComrakOptions::default();
 
     options.extension.autolink = true;
@@ -65,7 +124,7 @@ pub fn render(inp: &str) -> Result<String> {
                 let message = std::str::from_utf8(&message)?;
                 let mut message = markdown_to_html_with_plugins(message, &options, &plugins);
                 crop_letters(&mut message, 3);
-                message.drain((message.len() - 5)..);
+                message.drain(message.len().saturating_sub(5)..);
                 let mood = without_first(u.path());
                 let name = u.host_str().unwrap_or("Mara");
 
@@ -98,6 +157,13 @@ pub fn render(inp: &str) -> Result<String> {
 
     let html = String::from_utf8(html).wrap_err("post is somehow invalid UTF-8")?;
 
+    // The slides of the <xeblog-slide-deck> that's open, if one is.
+    let deck: Rc<RefCell<Option<Vec<xesite_templates::Slide>>>> = Rc::default();
+    let slides = deck.clone();
+    // The captions of the <xeblog-video> that's open, if one is.
+    let video: Rc<RefCell<Option<Vec<xesite_templates::Track>>>> = Rc::default();
+    let captions = video.clone();
+
     let html = rewrite_str(
         &html,
         RewriteStrSettings {
@@ -106,54 +172,144 @@ pub fn render(inp: &str) -> Result<String> {
                     let name = el
                         .get_attribute("name")
                         .ok_or(Error::MissingElementAttribute("name".to_string()))?;
-                    let name_lower = name.clone().to_lowercase();
                     let mood = el
                         .get_attribute("mood")
                         .ok_or(Error::MissingElementAttribute("mood".to_string()))?;
-                    let name = name.replace("_", " ");
+                    let standalone = el.get_attribute("standalone").is_some();
+                    let body = PreEscaped(BODY.into());
+                    let conv = xesite_templates::conv_sized(name, mood, standalone, body).0;
+                    // An override might leave the body out.
+                    let (open, close) = conv.split_once(BODY).unwrap_or((conv.as_str(), ""));
 
-                    let (size, class) = el
-                        .get_attribute("standalone")
-                        .map_or((64, "conversation-smol"), |_| {
-                            (128, "conversation-standalone")
-                        });
+                    el.before(open, ContentType::Html);
+                    el.after(close, ContentType::Html);
+                    el.remove_and_keep_content();
+                    Ok(())
+                }),
+                element!("xeblog-sidenote", |el| {
+                    let marker = el
+                        .get_attribute("marker")
+                        .ok_or(Error::MissingElementAttribute("marker".to_string()))?;
+                    let sidenote = xesite_templates::sidenote(marker, PreEscaped("".into())).0;
+                    let (open, close) = sidenote.split_at(sidenote.rfind("</span>").unwrap());
 
+                    el.before(open, ContentType::Html);
+                    el.after(close, ContentType::Html);
+                    el.remove_and_keep_content();
+                    Ok(())
+                }),
+                element!("xeblog-callout", |el| {
+                    let kind = match el
+                        .get_attribute("kind")
+                        .ok_or(Error::MissingElementAttribute("kind".to_string()))?
+                        .as_str()
+                    {
+                        "note" => CalloutKind::Note,
+                        "tip" => CalloutKind::Tip,
+                        "warning" => CalloutKind::Warning,
+                        "danger" => CalloutKind::Danger,
+                        "xe-says" => CalloutKind::XeSays {
+                            mood: el
+                                .get_attribute("mood")
+                                .ok_or(Error::MissingElementAttribute("mood".to_string()))?,
+                        },
+                        kind => {
+                            return Err(Error::InvalidElementAttribute(
+                                "kind".into(),
+                                format!("{kind} isn't note, tip, warning, danger or xe-says"),
+                            )
+                            .into())
+                        }
+                    };
+                    // The body is Markdown that's already been rendered, so the
+                    // callout goes around it.
+                    let callout = xesite_templates::callout(kind, PreEscaped(BODY.into())).0;
+                    let (open, close) = callout.split_once(BODY).unwrap();
+
+                    el.before(open, ContentType::Html);
+                    el.after(close, ContentType::Html);
+                    el.remove_and_keep_content();
+                    Ok(())
+                }),
+                element!("xeblog-figure", |el| {
+                    let id = el
+                        .get_attribute("id")
+                        .ok_or(Error::MissingElementAttribute("id".to_string()))?;
+                    let caption = el.get_attribute("caption").unwrap_or_default();
+                    let figure =
+                        xesite_templates::figure(&id, PreEscaped("".into()), html! { (caption) }).0;
+                    let (open, close) = figure.split_at(figure.find("<figcaption>").unwrap());
+
+                    el.before(open, ContentType::Html);
+                    el.after(close, ContentType::Html);
+                    el.remove_and_keep_content();
+                    Ok(())
+                }),
+                element!("xeblog-figref", |el| {
+                    let id = el
+                        .get_attribute("id")
+                        .ok_or(Error::MissingElementAttribute("id".to_string()))?;
+                    el.replace(&xesite_templates::figref(&id).0, ContentType::Html);
+                    Ok(())
+                }),
+                element!("xeblog-footnote", |el| {
+                    let id = el
+                        .get_attribute("id")
+                        .ok_or(Error::MissingElementAttribute("id".to_string()))?;
+                    let note = match el.get_attribute("note") {
+                        Some(note) => {
+                            xesite_templates::footnote(&id, PreEscaped(render_inline(&note)))
+                        }
+                        None => xesite_templates::footnote_ref(&id),
+                    };
+                    el.replace(&note.0, ContentType::Html);
+                    Ok(())
+                }),
+                element!("xeblog-walkthrough", |el| {
+                    el.before(r#"<div class="code-walkthrough">"#, ContentType::Html);
+                    el.after("</div>", ContentType::Html);
+                    el.remove_and_keep_content();
+                    Ok(())
+                }),
+                // Each step starts with a code block and everything after it is
+                // the explanation for that code.
+                element!("xeblog-walkthrough-step", |el| {
                     el.before(
-                        &format!(
-                            r#"
-<div class="conversation">
-    <div class="{class}">
-        <img src="https://cdn.xeiaso.net/sticker/{name_lower}/{mood}/{size}" alt="{name} is {mood}">
-    </div>
-    <div class="conversation-chat">&lt;<a href="/characters#{name_lower}"><b>{name}</b></a>&gt; "#
-                        ),
+                        r#"<div class="code-walkthrough-step"><div class="code-walkthrough-code">"#,
                         ContentType::Html,
                     );
                     el.after("</div></div>", ContentType::Html);
-
                     el.remove_and_keep_content();
                     Ok(())
                 }),
+                element!("xeblog-walkthrough-step > pre", |el| {
+                    el.after(
+                        r#"</div><div class="code-walkthrough-prose">"#,
+                        ContentType::Html,
+                    );
+                    Ok(())
+                }),
                 element!("xeblog-picture", |el| {
                     let path = el
                         .get_attribute("path")
-                        .expect("wanted xeblog-picture to contain path");
-                    el.replace(&xesite_templates::picture(path).0, ContentType::Html);
+                        .ok_or(Error::MissingElementAttribute("path".to_string()))?;
+                    let picture = match widths(el)? {
+                        Some(widths) => xesite_templates::picture_with(path, &widths),
+                        None => xesite_templates::picture(path),
+                    };
+                    el.replace(&picture.0, ContentType::Html);
                     Ok(())
                 }),
                 element!("xeblog-hero", |el| {
                     let file = el
                         .get_attribute("file")
                         .ok_or(Error::MissingElementAttribute("file".to_string()))?;
-                    el.replace(
-                        &xesite_templates::hero(
-                            file,
-                            el.get_attribute("prompt"),
-                            el.get_attribute("ai"),
-                        )
-                        .0,
-                        ContentType::Html,
-                    );
+                    let (prompt, ai) = (el.get_attribute("prompt"), el.get_attribute("ai"));
+                    let hero = match widths(el)? {
+                        Some(widths) => xesite_templates::hero_with(file, prompt, ai, &widths),
+                        None => xesite_templates::hero(file, prompt, ai),
+                    };
+                    el.replace(&hero.0, ContentType::Html);
                     Ok(())
                 }),
                 element!("xeblog-sticker", |el| {
@@ -167,13 +323,36 @@ pub fn render(inp: &str) -> Result<String> {
 
                     Ok(())
                 }),
-                element!("xeblog-slide", |el| {
+                element!("xeblog-slide-deck", move |el| {
+                    *deck.borrow_mut() = Some(vec![]);
+                    el.remove_and_keep_content();
+                    let deck = deck.clone();
+                    el.on_end_tag(move |end| {
+                        let slides = deck.borrow_mut().take().unwrap_or_default();
+                        end.after(&xesite_templates::slide_deck(slides).0, ContentType::Html);
+                        Ok(())
+                    })?;
+                    Ok(())
+                }),
+                element!("xeblog-slide", move |el| {
                     let name = el
                         .get_attribute("name")
                         .ok_or(Error::MissingElementAttribute("name".to_string()))?;
                     let essential = el.get_attribute("essential").is_some();
+                    let notes = el
+                        .get_attribute("notes")
+                        .map(|notes| PreEscaped(render_inline(&notes)));
+                    if let Some(deck) = slides.borrow_mut().as_mut() {
+                        deck.push(xesite_templates::Slide {
+                            name,
+                            essential,
+                            notes,
+                        });
+                        el.remove();
+                        return Ok(());
+                    }
                     el.replace(
-                        &xesite_templates::slide(name, essential).0,
+                        &xesite_templates::slide(name, essential, notes).0,
                         ContentType::Html,
                     );
 
@@ -183,44 +362,152 @@ pub fn render(inp: &str) -> Result<String> {
                     el.replace(&xesite_templates::talk_warning().0, ContentType::Html);
                     Ok(())
                 }),
-                element!("xeblog-video", |el| {
+                element!("xeblog-year-stats", |el| {
+                    let year = el
+                        .get_attribute("year")
+                        .ok_or(Error::MissingElementAttribute("year".to_string()))?;
+
+                    el.replace(&xesite_templates::year_stats(year).0, ContentType::Html);
+                    Ok(())
+                }),
+                element!("xeblog-video", move |el| {
                     let path = el
                         .get_attribute("path")
                         .ok_or(Error::MissingElementAttribute("path".to_string()))?;
-
-                    el.replace(&xesite_templates::video(path).0, ContentType::Html);
+                    if el.get_attribute("xeact").is_some() {
+                        el.replace(&xesite_templates::video(path).0, ContentType::Html);
+                        return Ok(());
+                    }
+                    let poster = el.get_attribute("poster");
+                    *video.borrow_mut() = Some(vec![]);
+                    el.remove_and_keep_content();
+                    let video = video.clone();
+                    el.on_end_tag(move |end| {
+                        let tracks = video.borrow_mut().take().unwrap_or_default();
+                        end.after(
+                            &xesite_templates::video_native(path, poster, tracks).0,
+                            ContentType::Html,
+                        );
+                        Ok(())
+                    })?;
+                    Ok(())
+                }),
+                element!("xeblog-captions", move |el| {
+                    let path = el
+                        .get_attribute("src")
+                        .ok_or(Error::MissingElementAttribute("src".to_string()))?;
+                    let lang = el
+                        .get_attribute("lang")
+                        .ok_or(Error::MissingElementAttribute("lang".to_string()))?;
+                    let label = el.get_attribute("label").unwrap_or_else(|| lang.clone());
+                    if let Some(tracks) = captions.borrow_mut().as_mut() {
+                        tracks.push(xesite_templates::Track { path, lang, label });
+                    }
+                    el.remove();
                     Ok(())
                 }),
                 #[cfg(not(target_arch = "wasm32"))]
-                element!("xeblog-toot", |el| {
+                element!("xeblog-bench-chart", |el| {
                     use serde_json::from_reader;
                     use std::fs;
 
-                    let mut toot_url = el
-                        .get_attribute("url")
-                        .ok_or(Error::MissingElementAttribute("url".to_string()))?;
-
-                    if !toot_url.ends_with(".json") {
-                        toot_url = format!("{toot_url}.json");
-                    }
+                    let name = el
+                        .get_attribute("name")
+                        .ok_or(Error::MissingElementAttribute("name".to_string()))?;
+                    let fname = format!("./data/bench/{name}.json");
+                    tracing::debug!("opening {fname}");
+                    let mut fin = fs::File::open(&fname).context(fname)?;
+                    let data: BenchData = from_reader(&mut fin)?;
+                    data.validate().map_err(|why| {
+                        Error::InvalidElementAttribute("name".into(), format!("{name}: {why}"))
+                    })?;
 
-                    let toot_fname = format!("./data/toots/{}.json", hash_string(toot_url.clone()));
-                    tracing::debug!("opening {toot_fname}");
-                    let mut fin = fs::File::open(&toot_fname).context(toot_url)?;
-                    let t: Toot = from_reader(&mut fin)?;
+                    el.replace(&xesite_templates::bench_chart(data).0, ContentType::Html);
+                    Ok(())
+                }),
+                #[cfg(not(target_arch = "wasm32"))]
+                element!("xeblog-route", |el| {
+                    use std::fs;
 
-                    let user_fname = format!(
-                        "./data/users/{}.json",
-                        hash_string(format!("{}.json", t.attributed_to.clone()))
-                    );
-                    tracing::debug!("opening {user_fname}");
-                    let mut fin = fs::File::open(&user_fname).context(t.attributed_to.clone())?;
+                    let name = el
+                        .get_attribute("gpx")
+                        .ok_or(Error::MissingElementAttribute("gpx".to_string()))?;
+                    let fname = format!("./data/routes/{name}.gpx");
+                    tracing::debug!("opening {fname}");
+                    let fin = fs::File::open(&fname).context(fname.clone())?;
+                    let route = gpx::parse(std::io::BufReader::new(fin)).context(fname)?;
 
-                    let u: User = from_reader(&mut fin)?;
+                    el.replace(&xesite_templates::route_map(route).0, ContentType::Html);
+                    Ok(())
+                }),
+                #[cfg(not(target_arch = "wasm32"))]
+                element!("xeblog-toot", |el| {
+                    let toot_url = el
+                        .get_attribute("url")
+                        .ok_or(Error::MissingElementAttribute("url".to_string()))?;
+                    let (u, t) = load_toot(toot_url)?;
 
                     el.replace(&xesite_templates::toot_embed(u, t).0, ContentType::Html);
                     Ok(())
                 }),
+                #[cfg(not(target_arch = "wasm32"))]
+                element!("xeblog-thread", |el| {
+                    let urls = el
+                        .get_attribute("urls")
+                        .ok_or(Error::MissingElementAttribute("urls".to_string()))?;
+                    let toots = urls
+                        .split_whitespace()
+                        .map(|url| load_toot(url.to_string()))
+                        .collect::<Result<Vec<_>>>()?;
+
+                    el.replace(&xesite_templates::thread_embed(toots).0, ContentType::Html);
+                    Ok(())
+                }),
+                #[cfg(not(target_arch = "wasm32"))]
+                element!("xeblog-embed", |el| {
+                    let url = el
+                        .get_attribute("url")
+                        .ok_or(Error::MissingElementAttribute("url".to_string()))?;
+                    let Some(provider) = embed::provider(&url) else {
+                        return Err(Error::InvalidElementAttribute(
+                            "url".into(),
+                            format!("{url} isn't from a provider in the allow-list"),
+                        )
+                        .into());
+                    };
+                    let html = if provider.kind == EmbedKind::Toot {
+                        let (u, t) = load_toot(url)?;
+                        xesite_templates::toot_embed(u, t)
+                    } else {
+                        xesite_templates::embed(load_embed(&url)?)
+                    };
+
+                    el.replace(&html.0, ContentType::Html);
+                    Ok(())
+                }),
+                element!("a[href]", |el| {
+                    let href = el.get_attribute("href").unwrap_or_default();
+                    let Some(link) = context::archived_link(&href) else {
+                        return Ok(());
+                    };
+                    let Some(snapshot) = &link.snapshot else {
+                        return Ok(());
+                    };
+                    if link.dead {
+                        el.set_attribute("href", snapshot)?;
+                        el.set_attribute("data-original-href", &href)?;
+                        el.set_attribute(
+                            "title",
+                            "The original page is gone, this is a copy from the Internet Archive",
+                        )?;
+                    } else {
+                        el.after(
+                            &xesite_templates::archive_link(snapshot).0,
+                            ContentType::Html,
+                        );
+                    }
+                    Ok(())
+                }),
             ],
             ..RewriteStrSettings::default()
         },
@@ -229,6 +516,46 @@ pub fn render(inp: &str) -> Result<String> {
     Ok(html)
 }
 
+/// Reads a toot and who posted it from `./data`, where `xesite fetch-toot`
+/// puts them.
+#[cfg(not(target_arch = "wasm32"))]
+fn load_toot(mut toot_url: String) -> Result<(User, Toot)> {
+    use serde_json::from_reader;
+    use std::fs;
+
+    if !toot_url.ends_with(".json") {
+        toot_url = format!("{toot_url}.json");
+    }
+
+    let toot_fname = format!("./data/toots/{}.json", hash_string(toot_url.clone()));
+    tracing::debug!("opening {toot_fname}");
+    let mut fin = fs::File::open(&toot_fname).context(toot_url)?;
+    let t: Toot = from_reader(&mut fin)?;
+
+    let user_fname = format!(
+        "./data/users/{}.json",
+        hash_string(format!("{}.json", t.attributed_to.clone()))
+    );
+    tracing::debug!("opening {user_fname}");
+    let mut fin = fs::File::open(&user_fname).context(t.attributed_to.clone())?;
+
+    let u: User = from_reader(&mut fin)?;
+    Ok((u, t))
+}
+
+/// Reads an embed from `./data/embeds`, where `fetch_embed` puts it.
+#[cfg(not(target_arch = "wasm32"))]
+fn load_embed(url: &str) -> Result<Embed> {
+    use serde_json::from_reader;
+    use std::fs;
+
+    let fname = format!("./data/embeds/{}.json", hash_string(url.to_string()));
+    tracing::debug!("opening {fname}");
+    let mut fin = fs::File::open(&fname)
+        .with_context(|| format!("{url} hasn't been fetched, run fetch_embed {url}"))?;
+    Ok(from_reader(&mut fin)?)
+}
+
 fn iter_nodes<'a, F>(node: &'a AstNode<'a>, f: &F) -> Result<()>
 where
     F: Fn(&'a AstNode<'a>) -> Result<()>,
@@ -258,3 +585,254 @@ fn crop_letters(s: &mut String, pos: usize) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn render_never_panics(inp in "\\PC*") {
+            let _ = render(&inp);
+        }
+
+        #[test]
+        fn shortcodes_never_panic(
+            name in "(xeblog-(conv|sidenote|callout|figure|figref|footnote|picture|hero|sticker|slide-deck|slide|video|captions|walkthrough|year-stats|embed))",
+            attr in "[a-z]{0,8}",
+            value in "\\PC*",
+            body in "\\PC*",
+        ) {
+            let value = value.replace('"', "&quot;");
+            let _ = render(&format!("<{name} {attr}=\"{value}\">{body}</{name}>"));
+        }
+
+        #[test]
+        fn conv_attributes_are_escaped(name in "[^\"\r\n]*", mood in "[^\"\r\n]*") {
+            let baseline = render(r#"<xeblog-conv name="Mara" mood="hacker">hi</xeblog-conv>"#).unwrap();
+            let result = render(&format!(
+                r#"<xeblog-conv name="{name}" mood="{mood}">hi</xeblog-conv>"#
+            ))
+            .unwrap();
+
+            prop_assert_eq!(result.matches('<').count(), baseline.matches('<').count());
+        }
+    }
+
+    #[test]
+    fn image_widths() {
+        let result =
+            render(r#"<xeblog-picture path="blog/foo" widths="480, 960"></xeblog-picture>"#)
+                .unwrap();
+        assert!(result.contains("blog/foo-960.avif 960w"));
+        assert!(!result.contains("blog/foo-1440.avif"));
+        assert!(render(r#"<xeblog-hero file="foo" widths="big"></xeblog-hero>"#).is_err());
+    }
+
+    #[test]
+    fn conversation_logs() {
+        use xesite_templates::context::{with_target, RenderTarget};
+
+        let result = with_target(RenderTarget::Feed, || {
+            render(r#"<xeblog-conv name="Mara" mood="hacker">hi</xeblog-conv>"#).unwrap()
+        });
+        assert!(result.contains(r#"<div class="conversation-log">&lt;<b>Mara</b>&gt; hi</div>"#));
+        assert!(!result.contains("<img"));
+
+        let result = with_target(RenderTarget::PlainText, || {
+            render("[hi](conversation://Cadey/coffee)").unwrap()
+        });
+        assert!(result.contains("&lt;Cadey&gt; hi"), "{result}");
+    }
+
+    #[test]
+    fn repeated_sidenotes() {
+        let inp = r#"One<xeblog-sidenote marker="*">first</xeblog-sidenote> and
+two<xeblog-sidenote marker="*">second</xeblog-sidenote>."#;
+        let result = render(inp).unwrap();
+        assert!(result.contains(r#"for="sidenote--""#), "{result}");
+        assert!(result.contains(r#"id="sidenote--""#), "{result}");
+        assert!(result.contains(r#"for="sidenote--_2""#), "{result}");
+        assert!(result.contains(r#"id="sidenote--_2""#), "{result}");
+
+        // Each post counts from the start.
+        assert_eq!(render(inp).unwrap(), result);
+    }
+
+    #[test]
+    fn conv_overrides() {
+        let dir = std::env::temp_dir().join(format!("xesite-overrides-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("conv.html"),
+            r#"<p class="override">{{name}} ({{mood}}): {{body}}</p>"#,
+        )
+        .unwrap();
+
+        let inp = r#"<xeblog-conv name="Mara" mood="hacker">hi</xeblog-conv>"#;
+        let result = xesite_templates::overrides::with_folder(&dir, || render(inp).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(
+            result.contains(r#"<p class="override">Mara (hacker): hi</p>"#),
+            "{result}"
+        );
+        assert!(!result.contains("conversation-chat"));
+    }
+
+    #[test]
+    fn callouts() {
+        let inp = r#"<xeblog-callout kind="danger">
+
+This deletes *everything*.
+
+</xeblog-callout>
+
+<xeblog-callout kind="xe-says" mood="coffee">Worth it.</xeblog-callout>
+"#;
+        let result = render(inp).unwrap();
+        assert!(result.contains(r#"<aside class="callout callout-danger" role="alert""#));
+        assert!(result.contains("<p>This deletes <em>everything</em>.</p>\n</div></aside>"));
+        assert!(result.contains(r#"<aside class="callout callout-xe-says""#));
+        assert!(result.contains("Worth it."));
+        assert!(!result.contains("<xeblog-callout"));
+
+        assert!(render(r#"<xeblog-callout kind="fyi">hi</xeblog-callout>"#).is_err());
+        assert!(render(r#"<xeblog-callout kind="xe-says">hi</xeblog-callout>"#).is_err());
+    }
+
+    #[test]
+    fn archived_links() {
+        use xesite_types::archive::ArchivedLink;
+
+        context::install_link_archive(
+            [
+                (
+                    "https://alive.example/post".to_string(),
+                    ArchivedLink {
+                        snapshot: Some(
+                            "https://web.archive.org/web/2023/https://alive.example/post".into(),
+                        ),
+                        dead: false,
+                        checked: "2023-06-01T00:00:00Z".into(),
+                    },
+                ),
+                (
+                    "https://dead.example/post".to_string(),
+                    ArchivedLink {
+                        snapshot: Some(
+                            "https://web.archive.org/web/2020/https://dead.example/post".into(),
+                        ),
+                        dead: true,
+                        checked: "2023-06-01T00:00:00Z".into(),
+                    },
+                ),
+            ]
+            .into(),
+        );
+        let result = render(
+            "[alive](https://alive.example/post), [dead](https://dead.example/post) and [other](https://other.example/).",
+        )
+        .unwrap();
+        assert!(result.contains(
+            r#"<a href="https://alive.example/post">alive</a><sup class="archive-link"><a href="https://web.archive.org/web/2023/https://alive.example/post""#
+        ));
+        assert!(result.contains(
+            r#"<a href="https://web.archive.org/web/2020/https://dead.example/post" data-original-href="https://dead.example/post""#
+        ));
+        assert!(result.contains(r#"<a href="https://other.example/">other</a>.</p>"#));
+    }
+
+    #[test]
+    fn embeds() {
+        assert!(render(r#"<xeblog-embed></xeblog-embed>"#).is_err());
+        assert!(render(r#"<xeblog-embed url="https://example.com/"></xeblog-embed>"#).is_err());
+        assert!(render(
+            r#"<xeblog-embed url="https://github.com/Xe/site/not-fetched"></xeblog-embed>"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn figures() {
+        let inp = r#"As <xeblog-figref id="graph"></xeblog-figref> shows:
+
+<xeblog-figure id="graph" caption="Requests per day">
+
+![](/graph.png)
+
+</xeblog-figure>
+"#;
+        let result = render(inp).unwrap();
+        assert!(result.contains(r##"<a class="figref" href="#figure-graph">Figure 1</a>"##));
+        assert!(result.contains(r#"<figure class="figure" id="figure-graph">"#));
+        assert!(result.contains("<b>Figure 1: </b>Requests per day</figcaption></figure>"));
+        // Rendering the same post again doesn't carry on counting.
+        assert_eq!(render(inp).unwrap(), result);
+    }
+
+    #[test]
+    fn slide_decks() {
+        let inp = r#"<xeblog-slide-deck>
+<xeblog-slide name="my-website/001" essential></xeblog-slide>
+<xeblog-slide name="my-website/002"></xeblog-slide>
+</xeblog-slide-deck>
+
+<xeblog-slide name="my-website/003" notes="This got a *laugh*."></xeblog-slide>
+"#;
+        let result = render(inp).unwrap();
+        assert_eq!(result.matches(r#"<div class="slide-deck">"#).count(), 1);
+        assert!(result.contains(r#"id="slide-2"><div class="hero xeblog-slides-fluff">"#));
+        assert!(!result.contains("slide-3"));
+        assert!(result.contains("my-website/003"));
+        assert!(
+            result.contains(r#"<div class="slide-notes-body">This got a <em>laugh</em>.</div>"#)
+        );
+        assert!(!result.contains("<xeblog-slide"));
+    }
+
+    #[test]
+    fn videos() {
+        let inp = r#"<xeblog-video path="blog/hls-post" poster="blog/hls-post/poster.jpg">
+<xeblog-captions src="blog/hls-post/en.vtt" lang="en" label="English"></xeblog-captions>
+</xeblog-video>
+
+<xeblog-video path="blog/basketball_10yr" xeact></xeblog-video>
+"#;
+        let result = render(inp).unwrap();
+        assert!(result.contains(r#"<video class="video-native""#));
+        assert!(result.contains(r#"srclang="en" label="English" default>"#));
+        assert!(result.contains(r#"data-xeact-component="Video""#));
+        assert!(!result.contains("<xeblog-"));
+    }
+
+    #[test]
+    fn footnotes() {
+        let inp = r#"Mara<xeblog-footnote id="mara" note="A *shark*. <script>no</script>"></xeblog-footnote> and Cadey<xeblog-footnote id="cadey" note="An alicorn."></xeblog-footnote>.
+
+Mara again<xeblog-footnote id="mara"></xeblog-footnote>.
+"#;
+        let result = render(inp).unwrap();
+        let (text, notes) = result.split_once(r#"<section class="footnotes">"#).unwrap();
+        assert!(text.contains(r##"<a id="footnote-mara-ref" href="#footnote-mara""##));
+        assert!(text.contains(r##"<a id="footnote-mara-ref-2" href="#footnote-mara""##));
+        assert!(notes.contains(r#"<li id="footnote-mara" value="1">A <em>shark</em>. "#));
+        assert!(notes.contains(r#"<li id="footnote-cadey" value="2">An alicorn."#));
+        assert!(!result.contains("<script>"));
+        assert!(!render("No notes.").unwrap().contains("footnotes"));
+    }
+
+    #[test]
+    fn empty_conversation_link() {
+        render("[](conversation://Mara/hacker)").unwrap();
+    }
+
+    #[test]
+    fn huge_input() {
+        let inp = "*a* <xeblog-conv name=\"Mara\" mood=\"hacker\">b</xeblog-conv>\n\n".repeat(10_000);
+        render(&inp).unwrap();
+    }
+}
@@ -0,0 +1,269 @@
+//! Tables of contents built from the headings in rendered HTML.
+
+use color_eyre::eyre::Result;
+use lol_html::{element, rewrite_str, text, RewriteStrSettings};
+use maud::{html, Markup};
+use std::{cell::RefCell, collections::HashSet};
+
+/// A heading in a document.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Heading {
+    /// 1 for `h1` through 6 for `h6`.
+    pub level: u8,
+    /// What the heading links to, without the `#`.
+    pub id: String,
+    pub text: String,
+}
+
+/// Turns heading text into an anchor: lowercase letters and digits with
+/// dashes between words.
+pub fn slugify(text: &str) -> String {
+    let mut result = String::new();
+    for word in text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+    {
+        if !result.is_empty() {
+            result.push('-');
+        }
+        result.extend(word.chars().flat_map(char::to_lowercase));
+    }
+    if result.is_empty() {
+        result.push_str("section");
+    }
+    result
+}
+
+/// Undoes the escaping in HTML text, for the few entities renderers use.
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&amp;", "&")
+}
+
+const HEADINGS: &str = "h1, h2, h3, h4, h5, h6";
+
+/// A heading as it was found, before it has an id.
+struct Found {
+    level: u8,
+    id: Option<String>,
+    text: String,
+    /// Headings that aren't meant to be seen, like the one over footnotes.
+    hidden: bool,
+}
+
+fn find(html: &str) -> Result<Vec<Found>> {
+    let found = RefCell::new(Vec::<Found>::new());
+    rewrite_str(
+        html,
+        RewriteStrSettings {
+            element_content_handlers: vec![
+                element!(HEADINGS, |el| {
+                    let level = el.tag_name()[1..].parse().unwrap_or(1);
+                    let hidden = el
+                        .get_attribute("class")
+                        .map_or(false, |c| c.split_whitespace().any(|c| c == "sr-only"));
+                    found.borrow_mut().push(Found {
+                        level,
+                        id: el.get_attribute("id").filter(|id| !id.is_empty()),
+                        text: String::new(),
+                        hidden,
+                    });
+                    Ok(())
+                }),
+                text!(HEADINGS, |t| {
+                    if let Some(last) = found.borrow_mut().last_mut() {
+                        last.text.push_str(t.as_str());
+                    }
+                    Ok(())
+                }),
+            ],
+            ..RewriteStrSettings::default()
+        },
+    )?;
+    Ok(found.into_inner())
+}
+
+/// Gives every heading an id, keeping the ones that have one and making
+/// the rest from their text. Ids are unique within the document.
+fn assign(found: &[Found]) -> Vec<String> {
+    let mut taken: HashSet<String> = found.iter().filter_map(|f| f.id.clone()).collect();
+    found
+        .iter()
+        .map(|f| match &f.id {
+            Some(id) => id.clone(),
+            None => {
+                let base = slugify(&unescape(&f.text));
+                let mut id = base.clone();
+                let mut n = 1;
+                while taken.contains(&id) {
+                    n += 1;
+                    id = format!("{base}-{n}");
+                }
+                taken.insert(id.clone());
+                id
+            }
+        })
+        .collect()
+}
+
+fn headings_of(found: Vec<Found>, ids: Vec<String>) -> Vec<Heading> {
+    found
+        .into_iter()
+        .zip(ids)
+        .filter(|(f, _)| !f.hidden)
+        .map(|(f, id)| Heading {
+            level: f.level,
+            id,
+            text: unescape(f.text.trim()),
+        })
+        .collect()
+}
+
+/// The headings in `html`. Headings without an id are given the one
+/// [inject_ids] would give them.
+pub fn headings(html: &str) -> Result<Vec<Heading>> {
+    let found = find(html)?;
+    let ids = assign(&found);
+    Ok(headings_of(found, ids))
+}
+
+/// Adds an id to every heading in `html` that doesn't have one, so it can
+/// be linked to, and returns the new HTML with its headings.
+pub fn inject_ids(html: &str) -> Result<(String, Vec<Heading>)> {
+    let found = find(html)?;
+    let ids = assign(&found);
+    let mut missing = found
+        .iter()
+        .zip(&ids)
+        .map(|(f, id)| f.id.is_none().then(|| id.clone()));
+
+    let html = rewrite_str(
+        html,
+        RewriteStrSettings {
+            element_content_handlers: vec![element!(HEADINGS, |el| {
+                if let Some(Some(id)) = missing.next() {
+                    el.set_attribute("id", &id)?;
+                }
+                Ok(())
+            })],
+            ..RewriteStrSettings::default()
+        },
+    )?;
+
+    Ok((html, headings_of(found, ids)))
+}
+
+/// A nested list of links to `headings`. Each heading holds the deeper
+/// ones after it, up to the next one that's as high or higher.
+fn list(headings: &[Heading]) -> Markup {
+    let mut items = vec![];
+    let mut i = 0;
+    while i < headings.len() {
+        let heading = &headings[i];
+        let end = headings[i + 1..]
+            .iter()
+            .position(|h| h.level <= heading.level)
+            .map_or(headings.len(), |n| i + 1 + n);
+        items.push((heading, &headings[i + 1..end]));
+        i = end;
+    }
+
+    html! {
+        ol {
+            @for (heading, children) in items {
+                li {
+                    a href={"#" (heading.id)} { (heading.text) }
+                    @if !children.is_empty() {
+                        (list(children))
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The table of contents for `headings`, or nothing when there are none.
+pub fn render(headings: &[Heading]) -> Markup {
+    html! {
+        @if !headings.is_empty() {
+            nav.toc aria-labelledby="toc-label" {
+                h2 #toc-label { "Contents" }
+                (list(headings))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slugs() {
+        assert_eq!(slugify("Why Nix?"), "why-nix");
+        assert_eq!(slugify("  Rust & C++: a <review>  "), "rust-c-a-review");
+        assert_eq!(slugify("Ça marche"), "ça-marche");
+        assert_eq!(slugify("!!!"), "section");
+    }
+
+    #[test]
+    fn ids_are_injected() -> Result<()> {
+        let html = r#"<h2>Setup</h2><p>a</p><h3 id="keep">Keys &amp; <code>ssh</code></h3><h2>Setup</h2><h2 class="sr-only" id="footnotes-label">Footnotes</h2>"#;
+        let (out, headings) = inject_ids(html)?;
+        assert_eq!(
+            out,
+            r#"<h2 id="setup">Setup</h2><p>a</p><h3 id="keep">Keys &amp; <code>ssh</code></h3><h2 id="setup-2">Setup</h2><h2 class="sr-only" id="footnotes-label">Footnotes</h2>"#
+        );
+        assert_eq!(
+            headings,
+            vec![
+                Heading {
+                    level: 2,
+                    id: "setup".into(),
+                    text: "Setup".into()
+                },
+                Heading {
+                    level: 3,
+                    id: "keep".into(),
+                    text: "Keys & ssh".into()
+                },
+                Heading {
+                    level: 2,
+                    id: "setup-2".into(),
+                    text: "Setup".into()
+                },
+            ]
+        );
+        assert_eq!(super::headings(html)?, headings);
+        Ok(())
+    }
+
+    #[test]
+    fn nested() {
+        let heading = |level, id: &str| Heading {
+            level,
+            id: id.into(),
+            text: id.to_uppercase(),
+        };
+        let toc = render(&[
+            heading(2, "a"),
+            heading(3, "b"),
+            heading(4, "c"),
+            heading(2, "d"),
+            heading(4, "e"),
+        ]);
+        assert_eq!(
+            toc.0,
+            concat!(
+                r#"<nav class="toc" aria-labelledby="toc-label"><h2 id="toc-label">Contents</h2>"#,
+                r##"<ol><li><a href="#a">A</a><ol><li><a href="#b">B</a><ol><li><a href="#c">C</a></li></ol></li></ol></li>"##,
+                r##"<li><a href="#d">D</a><ol><li><a href="#e">E</a></li></ol></li></ol></nav>"##,
+            )
+        );
+        assert_eq!(render(&[]).0, "");
+    }
+}
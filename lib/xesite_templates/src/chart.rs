@@ -0,0 +1,178 @@
+use maud::{html, Markup};
+use xesite_types::bench::{BenchData, ChartKind};
+
+const WIDTH: f64 = 640.0;
+const HEIGHT: f64 = 320.0;
+const MARGIN_LEFT: f64 = 64.0;
+const MARGIN_BOTTOM: f64 = 48.0;
+const MARGIN_TOP: f64 = 16.0;
+const TICKS: usize = 4;
+
+const COLORS: &[&str] = &[
+    "#fb4934", "#b8bb26", "#fabd2f", "#83a598", "#d3869b", "#8ec07c", "#fe8019",
+];
+
+fn color(idx: usize) -> &'static str {
+    COLORS[idx % COLORS.len()]
+}
+
+struct Scale {
+    max: f64,
+    plot_width: f64,
+    plot_height: f64,
+}
+
+impl Scale {
+    fn new(data: &BenchData) -> Self {
+        let max = data.max_value();
+        Self {
+            max: if max > 0.0 { max } else { 1.0 },
+            plot_width: WIDTH - MARGIN_LEFT,
+            plot_height: HEIGHT - MARGIN_TOP - MARGIN_BOTTOM,
+        }
+    }
+
+    /// Where `value` goes on the chart. Values the chart can't show are drawn
+    /// at zero, see [BenchData::validate].
+    fn y(&self, value: f64) -> f64 {
+        let value = if value.is_finite() {
+            value.clamp(0.0, self.max)
+        } else {
+            0.0
+        };
+        MARGIN_TOP + self.plot_height - (value / self.max) * self.plot_height
+    }
+}
+
+fn coord(n: f64) -> String {
+    format!("{n:.2}")
+}
+
+/// Renders benchmark results as an inline SVG chart with a data table
+/// fallback for screen readers and people that want the raw numbers.
+pub fn bench_chart(data: BenchData) -> Markup {
+    let scale = Scale::new(&data);
+    let label_count = data.labels.len().max(1) as f64;
+    let band = scale.plot_width / label_count;
+    let baseline = MARGIN_TOP + scale.plot_height;
+
+    html! {
+        figure.bench-chart {
+            svg xmlns="http://www.w3.org/2000/svg" viewBox={"0 0 " (WIDTH) " " (HEIGHT)} role="img" aria-label={(data.title) " (" (data.unit) ")"} {
+                title { (data.title) }
+                @for tick in 0..=TICKS {
+                    @let value = scale.max * tick as f64 / TICKS as f64;
+                    @let y = coord(scale.y(value));
+                    line x1=(MARGIN_LEFT) x2=(WIDTH) y1=(y) y2=(y) stroke="#504945" stroke-width="1" {}
+                    text x=(MARGIN_LEFT - 8.0) y=(y) text-anchor="end" dominant-baseline="middle" fill="currentColor" font-size="12" {
+                        (format!("{value:.1}"))
+                    }
+                }
+
+                @match data.kind {
+                    ChartKind::Bar => {
+                        @let bar_width = band * 0.8 / data.series.len().max(1) as f64;
+                        @for (i, _) in data.labels.iter().enumerate() {
+                            @for (j, series) in data.series.iter().enumerate() {
+                                @if let Some(value) = series.values.get(i) {
+                                    @let y = scale.y(*value);
+                                    rect x=(coord(MARGIN_LEFT + band * i as f64 + band * 0.1 + bar_width * j as f64)) y=(coord(y)) width=(coord(bar_width)) height=(coord(baseline - y)) fill=(color(j)) {
+                                        title { (series.name) ": " (value) " " (data.unit) }
+                                    }
+                                }
+                            }
+                        }
+                    },
+                    ChartKind::Line => {
+                        @for (j, series) in data.series.iter().enumerate() {
+                            @let points = series
+                                .values
+                                .iter()
+                                .enumerate()
+                                .map(|(i, value)| format!("{},{}", coord(MARGIN_LEFT + band * (i as f64 + 0.5)), coord(scale.y(*value))))
+                                .collect::<Vec<_>>()
+                                .join(" ");
+                            polyline points=(points) fill="none" stroke=(color(j)) stroke-width="2" {}
+                        }
+                    },
+                }
+
+                @for (i, label) in data.labels.iter().enumerate() {
+                    text x=(coord(MARGIN_LEFT + band * (i as f64 + 0.5))) y=(coord(baseline + 20.0)) text-anchor="middle" fill="currentColor" font-size="12" {
+                        (label)
+                    }
+                }
+                line x1=(MARGIN_LEFT) x2=(WIDTH) y1=(coord(baseline)) y2=(coord(baseline)) stroke="currentColor" stroke-width="1" {}
+            }
+
+            figcaption {
+                (data.title)
+                " ("
+                (data.unit)
+                ")"
+                @if data.series.len() > 1 {
+                    ": "
+                    @for (j, series) in data.series.iter().enumerate() {
+                        span style={"color:" (color(j))} { "■ " }
+                        (series.name)
+                        " "
+                    }
+                }
+            }
+
+            details {
+                summary { "Data table" }
+                table {
+                    caption { (data.title) " (" (data.unit) ")" }
+                    tr {
+                        th { "" }
+                        @for series in &data.series {
+                            th scope="col" { (series.name) }
+                        }
+                    }
+                    @for (i, label) in data.labels.iter().enumerate() {
+                        tr {
+                            th scope="row" { (label) }
+                            @for series in &data.series {
+                                td {
+                                    @if let Some(value) = series.values.get(i) {
+                                        (value)
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xesite_types::bench::BenchSeries;
+
+    #[test]
+    fn draws_bad_values_at_zero() {
+        let data = BenchData {
+            title: "Requests".into(),
+            unit: "ms".into(),
+            kind: ChartKind::Bar,
+            labels: vec!["a".into(), "b".into(), "c".into()],
+            series: vec![BenchSeries {
+                name: "axum".into(),
+                values: vec![-5.0, f64::NAN, 10.0],
+            }],
+        };
+        let scale = Scale::new(&data);
+        let baseline = MARGIN_TOP + scale.plot_height;
+        assert_eq!(scale.y(-5.0), baseline);
+        assert_eq!(scale.y(f64::NAN), baseline);
+        assert_eq!(scale.y(10.0), MARGIN_TOP);
+
+        let chart = bench_chart(data).0;
+        assert!(!chart.contains(r#"="NaN"#), "{chart}");
+        assert!(!chart.contains(r#"height="-"#), "{chart}");
+    }
+}
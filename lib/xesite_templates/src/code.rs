@@ -0,0 +1,82 @@
+use crate::context::{self, RenderTarget};
+use maud::{html, Markup, PreEscaped};
+use std::sync::OnceLock;
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{Theme, ThemeSet},
+    html::{styled_line_to_highlighted_html, IncludeBackground},
+    parsing::SyntaxSet,
+};
+
+/// The same theme code fences in markdown are highlighted with.
+pub const CODE_THEME: &str = "base16-mocha.dark";
+
+/// The syntaxes and theme syntect ships with, loaded the first time a code
+/// block is rendered.
+fn syntect() -> &'static (SyntaxSet, Theme) {
+    static SYNTECT: OnceLock<(SyntaxSet, Theme)> = OnceLock::new();
+    SYNTECT.get_or_init(|| {
+        let mut themes = ThemeSet::load_defaults();
+        (
+            SyntaxSet::load_defaults_nonewlines(),
+            themes.themes.remove(CODE_THEME).unwrap(),
+        )
+    })
+}
+
+/// Highlights one line, or escapes it if syntect can't.
+fn highlight(highlighter: &mut HighlightLines, syntaxes: &SyntaxSet, line: &str) -> String {
+    highlighter
+        .highlight_line(line, syntaxes)
+        .ok()
+        .and_then(|regions| styled_line_to_highlighted_html(&regions, IncludeBackground::No).ok())
+        .unwrap_or_else(|| html! {(line)}.0)
+}
+
+/// Source code highlighted on the server, with line numbers and a button
+/// to copy it. `lang` is a language name or file extension like `rust` or
+/// `rs`; anything syntect doesn't know is shown as plain text.
+///
+/// The line numbers are drawn with CSS so they aren't selected or copied
+/// along with the code. The copy button is hidden until
+/// `/static/js/code-blocks.js` shows it, and is left out of feeds and emails.
+pub fn code_block(lang: &str, source: &str) -> Markup {
+    let (syntaxes, theme) = syntect();
+    let syntax = syntaxes
+        .find_syntax_by_token(lang)
+        .unwrap_or_else(|| syntaxes.find_syntax_plain_text());
+    let mut highlighter = HighlightLines::new(syntax, theme);
+    let lines: Vec<String> = source
+        .lines()
+        .map(|line| highlight(&mut highlighter, syntaxes, line))
+        .collect();
+    let background = theme
+        .settings
+        .background
+        .map(|c| format!("background-color:#{:02x}{:02x}{:02x}", c.r, c.g, c.b));
+    let web = context::render_target() == RenderTarget::Web;
+
+    html! {
+        figure.code-block {
+            figcaption.code-block-header {
+                span.code-block-lang { (syntax.name) }
+                @if web {
+                    button.code-block-copy type="button" hidden { "Copy" }
+                }
+            }
+            pre.code-block-pre style=[background] {
+                code.{"language-" (lang)} {
+                    @for (i, line) in lines.iter().enumerate() {
+                        @if i != 0 { "\n" }
+                        span.code-block-line { (PreEscaped(line)) }
+                    }
+                }
+            }
+        }
+        @if web {
+            // Module scripts only run once per page, however many code
+            // blocks there are.
+            script type="module" src="/static/js/code-blocks.js" {}
+        }
+    }
+}
@@ -0,0 +1,670 @@
+//! Settings that templates read while rendering. The server installs these
+//! once at startup; anything rendered before that (or in tools that never
+//! install anything) gets the defaults.
+//!
+//! Components also register the assets they depend on here while they
+//! render, so the layout can ask the browser to fetch them early.
+
+use crate::og::OpenGraph;
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    sync::OnceLock,
+};
+use xesite_types::{
+    archive::{ArchivedLink, LinkArchive},
+    character::{ChatTheme, Hovercard, StickerRegistry},
+    fonts::Font,
+    media::MediaCredit,
+    site::SiteConfig,
+};
+
+static SITE: OnceLock<SiteConfig> = OnceLock::new();
+static FONTS: OnceLock<Vec<Font>> = OnceLock::new();
+static MEDIA: OnceLock<Vec<MediaCredit>> = OnceLock::new();
+static HOVERCARDS: OnceLock<Vec<Hovercard>> = OnceLock::new();
+static CHAT_THEMES: OnceLock<HashMap<String, ChatTheme>> = OnceLock::new();
+static STICKERS: OnceLock<StickerRegistry> = OnceLock::new();
+static EXPORTED_AT: OnceLock<String> = OnceLock::new();
+static LINK_ARCHIVE: OnceLock<LinkArchive> = OnceLock::new();
+
+/// Where the subsetted fonts are served from.
+pub const FONT_PATH: &str = "/static/css/iosevka";
+
+/// Sets the site config for every template. Returns false if it was already
+/// set, in which case the old value is kept.
+pub fn install(site: SiteConfig) -> bool {
+    SITE.set(site).is_ok()
+}
+
+pub fn site() -> &'static SiteConfig {
+    SITE.get_or_init(SiteConfig::default)
+}
+
+/// Sets the fonts listed in the font manifest. Returns false if they were
+/// already set.
+pub fn install_fonts(fonts: Vec<Font>) -> bool {
+    FONTS.set(fonts).is_ok()
+}
+
+/// The subsetted fonts, or nothing if the font manifest wasn't loaded.
+pub fn fonts() -> &'static [Font] {
+    FONTS.get_or_init(Vec::new)
+}
+
+/// Sets the credits from the media manifest. Returns false if they were
+/// already set.
+pub fn install_media(media: Vec<MediaCredit>) -> bool {
+    MEDIA.set(media).is_ok()
+}
+
+/// Everything in the media manifest, or nothing if it wasn't loaded.
+pub fn media() -> &'static [MediaCredit] {
+    MEDIA.get_or_init(Vec::new)
+}
+
+/// Sets the hovercards of the characters. Returns false if they were already
+/// set.
+pub fn install_hovercards(cards: Vec<Hovercard>) -> bool {
+    HOVERCARDS.set(cards).is_ok()
+}
+
+/// Every character's hovercard, or nothing if they weren't loaded.
+pub fn hovercards() -> &'static [Hovercard] {
+    HOVERCARDS.get_or_init(Vec::new)
+}
+
+/// Sets the chat themes of the characters, by sticker name. Returns false if
+/// they were already set.
+pub fn install_chat_themes(themes: HashMap<String, ChatTheme>) -> bool {
+    CHAT_THEMES.set(themes).is_ok()
+}
+
+/// How the lines of the character with this sticker name are set apart in
+/// conversations, if they are.
+pub fn chat_theme(sticker_name: &str) -> Option<&'static ChatTheme> {
+    CHAT_THEMES.get()?.get(sticker_name)
+}
+
+/// Sets what's known about the external links in posts, from the file
+/// `link_archive` writes. Returns false if it was already set.
+pub fn install_link_archive(links: LinkArchive) -> bool {
+    LINK_ARCHIVE.set(links).is_ok()
+}
+
+/// What's known about the external link `url`, if anything.
+pub fn archived_link(url: &str) -> Option<&'static ArchivedLink> {
+    LINK_ARCHIVE.get()?.get(url)
+}
+
+/// Sets which stickers there are of each character. Returns false if they
+/// were already set.
+pub fn install_stickers(stickers: StickerRegistry) -> bool {
+    STICKERS.set(stickers).is_ok()
+}
+
+/// Which stickers there are of each character. Before they're installed,
+/// every mood is allowed.
+pub fn stickers() -> &'static StickerRegistry {
+    STICKERS.get_or_init(StickerRegistry::default)
+}
+
+/// Marks everything rendered from now on as part of a static export made at
+/// the given time.
+pub fn install_static_export(made_at: String) -> bool {
+    EXPORTED_AT.set(made_at).is_ok()
+}
+
+/// When the static export being rendered was made, or None when pages are
+/// rendered by the server as they're asked for.
+pub fn static_export() -> Option<&'static str> {
+    EXPORTED_AT.get().map(String::as_str)
+}
+
+/// Shorthand for a URL in the static CDN bucket.
+pub fn cdn(path: &str) -> String {
+    site().cdn(path)
+}
+
+/// What kind of asset a [Preload] fetches. This decides the `as` attribute
+/// and whether it's a `modulepreload`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AssetKind {
+    Font,
+    Image,
+    Style,
+    /// A JavaScript module, preloaded with `rel=modulepreload`.
+    Module,
+}
+
+/// An asset the page is going to need soon enough that the browser should
+/// start fetching it before it finds it on its own.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Preload {
+    pub href: String,
+    pub kind: AssetKind,
+    /// The MIME type, so browsers that can't use the asset skip it.
+    pub mime: Option<String>,
+    /// For responsive images, the `srcset` and `sizes` of the image, so the
+    /// browser fetches the size it's going to use instead of `href`.
+    pub image_srcset: Option<(String, String)>,
+}
+
+impl Preload {
+    pub fn new(href: impl Into<String>, kind: AssetKind) -> Self {
+        Self {
+            href: href.into(),
+            kind,
+            mime: None,
+            image_srcset: None,
+        }
+    }
+
+    pub fn mime(mut self, mime: impl Into<String>) -> Self {
+        self.mime = Some(mime.into());
+        self
+    }
+
+    pub fn srcset(mut self, srcset: impl Into<String>, sizes: impl Into<String>) -> Self {
+        self.image_srcset = Some((srcset.into(), sizes.into()));
+        self
+    }
+
+    fn rel(&self) -> &'static str {
+        match self.kind {
+            AssetKind::Module => "modulepreload",
+            _ => "preload",
+        }
+    }
+
+    fn r#as(&self) -> Option<&'static str> {
+        match self.kind {
+            AssetKind::Font => Some("font"),
+            AssetKind::Image => Some("image"),
+            AssetKind::Style => Some("style"),
+            AssetKind::Module => None,
+        }
+    }
+
+    /// The value of a `Link` header for this asset, as used by 103 Early
+    /// Hints.
+    pub fn link_header(&self) -> String {
+        let mut result = format!("<{}>; rel={}", self.href, self.rel());
+        if let Some(r#as) = self.r#as() {
+            result.push_str(&format!("; as={as}"));
+        }
+        if let Some(mime) = &self.mime {
+            result.push_str(&format!("; type=\"{mime}\""));
+        }
+        if let Some((srcset, sizes)) = &self.image_srcset {
+            result.push_str(&format!(
+                "; imagesrcset=\"{srcset}\"; imagesizes=\"{sizes}\""
+            ));
+        }
+        // Fonts are always fetched in CORS mode, even from the same origin.
+        if self.kind == AssetKind::Font {
+            result.push_str("; crossorigin");
+        }
+        result
+    }
+}
+
+impl maud::Render for Preload {
+    fn render(&self) -> maud::Markup {
+        maud::html! {
+            link rel=(self.rel()) href=(self.href) as=[self.r#as()] type=[self.mime.as_ref()] imagesrcset=[self.image_srcset.as_ref().map(|(srcset, _)| srcset)] imagesizes=[self.image_srcset.as_ref().map(|(_, sizes)| sizes)] crossorigin=[(self.kind == AssetKind::Font).then_some("")];
+        }
+    }
+}
+
+thread_local! {
+    static PRELOADS: RefCell<Vec<Preload>> = RefCell::new(Vec::new());
+}
+
+/// Asks for an asset to be preloaded by the next page rendered on this
+/// thread. Registering the same URL twice does nothing.
+///
+/// Registrations are per-thread, so a page's body has to be built and handed
+/// to the layout without awaiting in between.
+pub fn preload(asset: Preload) {
+    PRELOADS.with(|preloads| {
+        let mut preloads = preloads.borrow_mut();
+        if !preloads.iter().any(|p| p.href == asset.href) {
+            preloads.push(asset);
+        }
+    })
+}
+
+/// Returns true if anything of the given kind has been registered so far.
+pub fn is_preloading(kind: AssetKind) -> bool {
+    PRELOADS.with(|preloads| preloads.borrow().iter().any(|p| p.kind == kind))
+}
+
+/// Takes everything registered with [preload] so far. Layouts call this when
+/// they render their head.
+pub fn take_preloads() -> Vec<Preload> {
+    PRELOADS.with(|preloads| std::mem::take(&mut *preloads.borrow_mut()))
+}
+
+thread_local! {
+    static OPEN_GRAPH: RefCell<Option<OpenGraph>> = RefCell::new(None);
+}
+
+/// Sets the link preview tags of the next page rendered on this thread. Like
+/// [preload], this has to happen while the page's content is rendered.
+pub fn open_graph(og: OpenGraph) {
+    OPEN_GRAPH.with(|cell| *cell.borrow_mut() = Some(og));
+}
+
+/// Takes what was set with [open_graph]. Layouts call this when they render
+/// their head.
+pub fn take_open_graph() -> Option<OpenGraph> {
+    OPEN_GRAPH.with(|cell| cell.borrow_mut().take())
+}
+
+/// Runs `f` and returns what it registered with [preload] instead of leaving
+/// it for the next page. This is for things rendered ahead of time, such as
+/// post bodies, that need to register their assets again every time they're
+/// shown.
+pub fn collect<T>(f: impl FnOnce() -> T) -> (T, Vec<Preload>) {
+    let outer = take_preloads();
+    let result = f();
+    let inner = take_preloads();
+    PRELOADS.with(|preloads| *preloads.borrow_mut() = outer);
+    (result, inner)
+}
+
+/// A stylesheet or script a page asked for on top of the ones every page
+/// gets, such as the ones in a post's `extra_css` and `extra_scripts`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Include {
+    /// Where the asset is served, fingerprinted with its contents.
+    pub href: String,
+    /// Either [AssetKind::Style] or [AssetKind::Module].
+    pub kind: AssetKind,
+    /// The subresource integrity hash of the asset, `sha256-` and the
+    /// base64 of its digest.
+    pub integrity: String,
+}
+
+impl maud::Render for Include {
+    fn render(&self) -> maud::Markup {
+        maud::html! {
+            @if self.kind == AssetKind::Module {
+                script type="module" src=(self.href) integrity=(self.integrity) {}
+            } @else {
+                link rel="stylesheet" href=(self.href) integrity=(self.integrity);
+            }
+        }
+    }
+}
+
+thread_local! {
+    static INCLUDES: RefCell<Vec<Include>> = RefCell::new(Vec::new());
+}
+
+/// Adds a stylesheet or script to the head of the next page rendered on
+/// this thread, the same way as [preload].
+pub fn include(asset: Include) {
+    INCLUDES.with(|includes| {
+        let mut includes = includes.borrow_mut();
+        if !includes.iter().any(|i| i.href == asset.href) {
+            includes.push(asset);
+        }
+    })
+}
+
+/// Takes everything registered with [include] so far.
+pub fn take_includes() -> Vec<Include> {
+    INCLUDES.with(|includes| std::mem::take(&mut *includes.borrow_mut()))
+}
+
+thread_local! {
+    static CREDITS: RefCell<Option<Vec<MediaCredit>>> = RefCell::new(None);
+}
+
+/// Notes that an asset from the CDN bucket was rendered, so its credit shows
+/// up in the [collect_credits] being run on this thread. Assets that aren't in
+/// the media manifest are skipped, as is everything rendered outside of
+/// [collect_credits].
+pub fn credit(path: &str) {
+    let Some(credit) = media().iter().find(|m| m.path == path) else {
+        return;
+    };
+    CREDITS.with(|credits| {
+        if let Some(credits) = credits.borrow_mut().as_mut() {
+            if !credits.contains(credit) {
+                credits.push(credit.clone());
+            }
+        }
+    })
+}
+
+/// Runs `f` and returns the credits of every asset it rendered, in the order
+/// they were first rendered.
+pub fn collect_credits<T>(f: impl FnOnce() -> T) -> (T, Vec<MediaCredit>) {
+    let outer = CREDITS.with(|credits| credits.replace(Some(Vec::new())));
+    let result = f();
+    let inner = CREDITS.with(|credits| credits.replace(outer));
+    (result, inner.unwrap_or_default())
+}
+
+/// What is being rendered. Components that are mostly pictures, like
+/// conversations, render as plain chat logs for anything but the website.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RenderTarget {
+    #[default]
+    Web,
+    /// RSS, Atom and JSON feeds.
+    Feed,
+    Email,
+    /// HTML that is going to be turned into plain text, so formatting is
+    /// lost.
+    PlainText,
+}
+
+thread_local! {
+    static TARGET: Cell<RenderTarget> = Cell::new(RenderTarget::Web);
+}
+
+/// What the component being rendered on this thread is for.
+pub fn render_target() -> RenderTarget {
+    TARGET.with(Cell::get)
+}
+
+/// Runs `f` with everything it renders being for `target`.
+pub fn with_target<T>(target: RenderTarget, f: impl FnOnce() -> T) -> T {
+    let outer = TARGET.with(|t| t.replace(target));
+    let result = f();
+    TARGET.with(|t| t.set(outer));
+    result
+}
+
+/// Figure numbers for one document. Figures are numbered in the order they
+/// are first mentioned, either by the figure itself or by a reference to it,
+/// so a reference can come before the figure it points at.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FigureCounter {
+    numbers: HashMap<String, u32>,
+}
+
+impl FigureCounter {
+    /// The number of the figure `id`, starting at 1.
+    pub fn number(&mut self, id: &str) -> u32 {
+        let next = self.numbers.len() as u32 + 1;
+        *self.numbers.entry(id.to_string()).or_insert(next)
+    }
+}
+
+thread_local! {
+    static FIGURES: RefCell<FigureCounter> = RefCell::new(FigureCounter::default());
+}
+
+/// The number of the figure `id` in the document being rendered on this
+/// thread.
+pub fn figure_number(id: &str) -> u32 {
+    FIGURES.with(|figures| figures.borrow_mut().number(id))
+}
+
+thread_local! {
+    static SIDENOTES: RefCell<HashMap<String, u32>> = RefCell::new(HashMap::new());
+}
+
+/// How many sidenotes with the marker `marker` the document being rendered on
+/// this thread has had, counting this one.
+pub fn sidenote_mention(marker: &str) -> u32 {
+    SIDENOTES.with(|sidenotes| {
+        let mut sidenotes = sidenotes.borrow_mut();
+        let count = sidenotes.entry(marker.to_string()).or_default();
+        *count += 1;
+        *count
+    })
+}
+
+/// Runs `f` with figures numbered from 1 again and sidenotes counted from
+/// scratch, such as for each post.
+pub fn with_figures<T>(f: impl FnOnce() -> T) -> T {
+    let outer = FIGURES.with(|figures| figures.take());
+    let outer_sidenotes = SIDENOTES.with(|sidenotes| sidenotes.take());
+    let result = f();
+    FIGURES.with(|figures| figures.replace(outer));
+    SIDENOTES.with(|sidenotes| sidenotes.replace(outer_sidenotes));
+    result
+}
+
+/// A note shown at the bottom of the article, see [collect_footnotes].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Footnote {
+    pub id: String,
+    pub number: u32,
+    /// The note as HTML. It's empty when something referred to the note
+    /// but nothing said what it is.
+    pub body: String,
+    /// How many times the note was mentioned.
+    pub mentions: u32,
+}
+
+/// The footnotes of one document, numbered in the order they're first
+/// mentioned.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FootnoteCollector {
+    notes: Vec<Footnote>,
+}
+
+impl FootnoteCollector {
+    /// The note `id`, counting this as a mention of it.
+    fn mention(&mut self, id: &str) -> &mut Footnote {
+        let index = match self.notes.iter().position(|note| note.id == id) {
+            Some(index) => index,
+            None => {
+                self.notes.push(Footnote {
+                    id: id.to_string(),
+                    number: self.notes.len() as u32 + 1,
+                    body: String::new(),
+                    mentions: 0,
+                });
+                self.notes.len() - 1
+            }
+        };
+        let note = &mut self.notes[index];
+        note.mentions += 1;
+        note
+    }
+
+    /// Says what the note `id` is. Returns its number and which mention of
+    /// it this is. Only the first body given for a note is kept.
+    pub fn add(&mut self, id: &str, body: String) -> (u32, u32) {
+        let note = self.mention(id);
+        if note.body.is_empty() {
+            note.body = body;
+        }
+        (note.number, note.mentions)
+    }
+
+    /// The number of the note `id` and which mention of it this is.
+    pub fn number(&mut self, id: &str) -> (u32, u32) {
+        let note = self.mention(id);
+        (note.number, note.mentions)
+    }
+
+    pub fn into_notes(self) -> Vec<Footnote> {
+        self.notes
+    }
+}
+
+thread_local! {
+    static FOOTNOTES: RefCell<Option<FootnoteCollector>> = RefCell::new(None);
+}
+
+/// Adds a note to the [collect_footnotes] being run on this thread. See
+/// [FootnoteCollector::add], this is `None` outside of [collect_footnotes].
+pub fn footnote(id: &str, body: String) -> Option<(u32, u32)> {
+    FOOTNOTES.with(|notes| notes.borrow_mut().as_mut().map(|notes| notes.add(id, body)))
+}
+
+/// See [FootnoteCollector::number], this is `None` outside of
+/// [collect_footnotes].
+pub fn footnote_number(id: &str) -> Option<(u32, u32)> {
+    FOOTNOTES.with(|notes| notes.borrow_mut().as_mut().map(|notes| notes.number(id)))
+}
+
+/// Runs `f` and returns every footnote it rendered, in order.
+pub fn collect_footnotes<T>(f: impl FnOnce() -> T) -> (T, Vec<Footnote>) {
+    let outer = FOOTNOTES.with(|notes| notes.replace(Some(FootnoteCollector::default())));
+    let result = f();
+    let inner = FOOTNOTES.with(|notes| notes.replace(outer));
+    (
+        result,
+        inner.map(FootnoteCollector::into_notes).unwrap_or_default(),
+    )
+}
+
+/// The assets every page needs: the body font and the Xeact runtime.
+pub fn critical_preloads(cachebuster: &str) -> Vec<Preload> {
+    let mut result: Vec<Preload> = fonts()
+        .iter()
+        .filter(|font| font.preload)
+        .map(|font| {
+            Preload::new(format!("{FONT_PATH}/{}", font.file), AssetKind::Font).mime("font/woff2")
+        })
+        .collect();
+    result.push(Preload::new(
+        format!("/static/js/xeact-runtime.js?cacheBuster={cachebuster}"),
+        AssetKind::Module,
+    ));
+    result
+}
+
+/// The `@font-face` rules for every subsetted font. The file names have a
+/// hash of their contents in them, so these never need a cache buster.
+pub fn font_faces() -> String {
+    fonts()
+        .iter()
+        .map(|font| font.font_face(FONT_PATH))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collect_is_scoped() {
+        preload(Preload::new("/outer.png", AssetKind::Image));
+        let ((), inner) = collect(|| {
+            preload(Preload::new("/inner.png", AssetKind::Image));
+            preload(Preload::new("/inner.png", AssetKind::Image));
+        });
+        assert_eq!(inner, vec![Preload::new("/inner.png", AssetKind::Image)]);
+        assert_eq!(
+            take_preloads(),
+            vec![Preload::new("/outer.png", AssetKind::Image)]
+        );
+        assert!(take_preloads().is_empty());
+    }
+
+    #[test]
+    fn collect_credits_is_scoped() {
+        let hero = MediaCredit {
+            path: "hero/foo".into(),
+            creator: "Mara".into(),
+            source: None,
+            license: "CC-BY 4.0".into(),
+            license_url: None,
+        };
+        install_media(vec![hero.clone()]);
+
+        credit("hero/foo");
+        let ((), credits) = collect_credits(|| {
+            credit("hero/foo");
+            credit("hero/unknown");
+            credit("hero/foo");
+        });
+        assert_eq!(credits, vec![hero]);
+        assert!(collect_credits(|| ()).1.is_empty());
+    }
+
+    #[test]
+    fn with_target_is_scoped() {
+        assert_eq!(render_target(), RenderTarget::Web);
+        let inner = with_target(RenderTarget::Feed, || {
+            with_target(RenderTarget::PlainText, render_target);
+            render_target()
+        });
+        assert_eq!(inner, RenderTarget::Feed);
+        assert_eq!(render_target(), RenderTarget::Web);
+    }
+
+    #[test]
+    fn with_figures_is_scoped() {
+        assert_eq!(figure_number("outer"), 1);
+        let inner = with_figures(|| {
+            let numbers = (figure_number("b"), figure_number("a"), figure_number("b"));
+            (numbers, with_figures(|| figure_number("a")))
+        });
+        assert_eq!(inner, ((1, 2, 1), 1));
+        assert_eq!(figure_number("a"), 2);
+        assert_eq!(figure_number("outer"), 1);
+    }
+
+    #[test]
+    fn collect_footnotes_is_scoped() {
+        assert_eq!(footnote("outside", "lost".into()), None);
+        let (numbers, notes) = collect_footnotes(|| {
+            let numbers = (
+                footnote_number("later"),
+                footnote("first", "<b>hi</b>".into()),
+                footnote("later", "there".into()),
+                footnote("first", "ignored".into()),
+            );
+            assert_eq!(
+                collect_footnotes(|| footnote("inner", "x".into())).1.len(),
+                1
+            );
+            numbers
+        });
+        assert_eq!(
+            numbers,
+            (Some((1, 1)), Some((2, 1)), Some((1, 2)), Some((2, 2)))
+        );
+        assert_eq!(
+            notes,
+            vec![
+                Footnote {
+                    id: "later".into(),
+                    number: 1,
+                    body: "there".into(),
+                    mentions: 2,
+                },
+                Footnote {
+                    id: "first".into(),
+                    number: 2,
+                    body: "<b>hi</b>".into(),
+                    mentions: 2,
+                },
+            ]
+        );
+        assert_eq!(footnote_number("later"), None);
+    }
+
+    #[test]
+    fn link_header() {
+        assert_eq!(
+            Preload::new("/a.woff2", AssetKind::Font)
+                .mime("font/woff2")
+                .link_header(),
+            "</a.woff2>; rel=preload; as=font; type=\"font/woff2\"; crossorigin"
+        );
+        assert_eq!(
+            Preload::new("/a.js", AssetKind::Module).link_header(),
+            "</a.js>; rel=modulepreload"
+        );
+        assert_eq!(
+            Preload::new("/a.avif", AssetKind::Image)
+                .srcset("/a-480.avif 480w, /a-960.avif 960w", "100vw")
+                .link_header(),
+            "</a.avif>; rel=preload; as=image; imagesrcset=\"/a-480.avif 480w, /a-960.avif 960w\"; imagesizes=\"100vw\""
+        );
+    }
+}
@@ -1,50 +1,213 @@
 use maud::{html, Markup, PreEscaped};
-use xesite_types::mastodon::{Toot, User};
+use std::collections::HashMap;
+
+pub mod chart;
+pub mod code;
+pub mod context;
+pub mod og;
+pub mod overrides;
+pub mod route;
+pub mod stats;
+
+pub use chart::bench_chart;
+pub use code::code_block;
+use context::{cdn, site};
+pub use route::route_map;
+use xesite_types::{
+    character::Character,
+    embed::{is_player, Embed, EmbedKind},
+    mastodon::{Poll, PollKind, Toot, User},
+    media::MediaCredit,
+    site::Sponsor,
+    EventMeta, OriginMeta, ReaderPrefs, Series,
+};
 
 pub fn talk_warning() -> Markup {
     html! {
         div.warning {
             (conv("Cadey".to_string(), "coffee".to_string(), html!{
                 "So you are aware: you are reading the written version of a conference talk. This is written in a different style that is more lighthearted, conversational and different than the content normally on this blog. The words being said are the verbatim words that were spoken at the conference. The slides are the literal slides for each spoken utterance. If you want to hide the non-essential slides, please press this button: "
-                (xeact_component("NoFunAllowed", serde_json::Value::Null))
+                (xeact_component_with_fallback("NoFunAllowed", serde_json::Value::Null, html! {
+                    a href="?slides=essential" { "Only show the essential slides" }
+                }))
             }))
         }
     }
 }
 
-pub fn slide(name: String, essential: bool) -> Markup {
+/// Where the reader's choice to only see essential slides is kept, so later
+/// talks are filtered too.
+pub const ESSENTIAL_SLIDES_KEY: &str = "xesite-slides";
+
+/// A script that hides the non-essential slides before they're drawn when
+/// the reader asked for that on an earlier visit. It has to come before the
+/// slides, so it runs before they're parsed.
+pub fn essential_slides_hint() -> Markup {
+    let script = format!(
+        r#"try{{if(localStorage.getItem("{ESSENTIAL_SLIDES_KEY}")==="essential")document.documentElement.classList.add("slides-essential-only")}}catch(e){{}}"#
+    );
+    html! {
+        script { (PreEscaped(script)) }
+    }
+}
+
+/// A slide from a talk. When there are `notes` it's drawn by
+/// [slide_with_notes].
+pub fn slide(name: String, essential: bool, notes: Option<Markup>) -> Markup {
+    match notes {
+        Some(notes) => slide_with_notes(name, essential, notes),
+        None => slide_picture(&name, essential),
+    }
+}
+
+/// The presenter's view of a slide: the slide, with what the speaker had to
+/// say about it folded away under it. This is for commentary that isn't in
+/// the transcript, like why a slide is there or what got cut.
+pub fn slide_with_notes(name: String, essential: bool, notes: Markup) -> Markup {
+    html! {
+        div.slide-with-notes {
+            (slide_picture(&name, essential))
+            details.slide-notes {
+                summary { "Speaker notes" }
+                div.slide-notes-body { (notes) }
+            }
+        }
+    }
+}
+
+fn slide_picture(name: &str, essential: bool) -> Markup {
     html! {
         div.hero.{@if essential {("xeblog-slides-essential")} @else {("xeblog-slides-fluff")}} {
             picture style="margin:0" {
-                source type="image/avif" srcset={"https://cdn.xeiaso.net/file/christine-static/talks/" (name) ".avif"};
-                source type="image/webp" srcset={"https://cdn.xeiaso.net/file/christine-static/talks/" (name) ".webp"};
-                img style="padding:0" loading="lazy" src={"https://cdn.xeiaso.net/file/christine-static/talks/" (name) "-smol.png"};
+                source type="image/avif" srcset=(cdn(&format!("talks/{name}.avif")));
+                source type="image/webp" srcset=(cdn(&format!("talks/{name}.webp")));
+                img style="padding:0" loading="lazy" src=(cdn(&format!("talks/{name}-smol.png")));
             }
         }
     }
 }
 
+/// A slide in a [slide_deck], drawn like [slide] draws it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Slide {
+    pub name: String,
+    pub essential: bool,
+    pub notes: Option<Markup>,
+}
+
+/// Slides that can be stepped through one at a time with the buttons or the
+/// arrow keys. Each slide is at `#slide-N`, counting from 1, so links can go
+/// straight to one. Without `/static/js/slide-deck.js` the slides are stacked
+/// like a run of [slide]s, which is also how feeds and emails get them.
+pub fn slide_deck(slides: Vec<Slide>) -> Markup {
+    let web = context::render_target() == context::RenderTarget::Web;
+    let count = slides.len();
+
+    html! {
+        div.slide-deck {
+            @if web {
+                div.slide-deck-controls hidden {
+                    button.slide-deck-prev type="button" aria-label="Previous slide" { "←" }
+                    span.slide-deck-counter aria-live="polite" { "1 / " (count) }
+                    button.slide-deck-next type="button" aria-label="Next slide" { "→" }
+                }
+            }
+            ol.slide-deck-slides {
+                @for (i, s) in slides.into_iter().enumerate() {
+                    li.slide-deck-slide id={"slide-" (i + 1)} {
+                        (slide(s.name, s.essential, s.notes))
+                    }
+                }
+            }
+        }
+        @if web {
+            script type="module" src="/static/js/slide-deck.js" {}
+        }
+    }
+}
+
+/// How wide images in posts are drawn at each screen width, for the `sizes`
+/// attribute. Posts are at most 80 characters wide.
+pub const IMAGE_SIZES: &str = "(max-width: 80ch) 100vw, 80ch";
+
+/// The `srcset` of an image in the bucket. The sizes of it are named
+/// `{path}-{width}.{ext}`, or there is only `{path}.{ext}` when `widths` is
+/// empty.
+pub fn srcset(path: &str, ext: &str, widths: &[u32]) -> String {
+    if widths.is_empty() {
+        return cdn(&format!("{path}.{ext}"));
+    }
+    widths
+        .iter()
+        .map(|w| format!("{} {w}w", cdn(&format!("{path}-{w}.{ext}"))))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn image_sizes(widths: &[u32]) -> Option<&'static str> {
+    (!widths.is_empty()).then_some(IMAGE_SIZES)
+}
+
 pub fn picture(path: String) -> Markup {
+    picture_with(path, &site().image_widths)
+}
+
+/// A [picture] with its own list of widths instead of the site's.
+pub fn picture_with(path: String, widths: &[u32]) -> Markup {
+    context::credit(&path);
     html! {
-        a href={"https://cdn.xeiaso.net/file/christine-static/" (path) ".jpg"} target="_blank" {
+        a href=(cdn(&format!("{path}.jpg"))) target="_blank" {
             picture.picture style="margin:0" {
-                source type="image/avif" srcset={"https://cdn.xeiaso.net/file/christine-static/" (path) ".avif"};
-                source type="image/webp" srcset={"https://cdn.xeiaso.net/file/christine-static/" (path) ".webp"};
-                img.picture style="padding:0" loading="lazy" alt={"hero image " (path)} src={"https://cdn.xeiaso.net/file/christine-static/" (path) "-smol.png"};
+                source type="image/avif" srcset=(srcset(&path, "avif", widths)) sizes=[image_sizes(widths)];
+                source type="image/webp" srcset=(srcset(&path, "webp", widths)) sizes=[image_sizes(widths)];
+                img.picture style="padding:0" loading="lazy" alt={"hero image " (path)} src=(cdn(&format!("{path}-smol.png")));
             }
         }
     }
 }
 
 pub fn hero(file: String, prompt: Option<String>, ai: Option<String>) -> Markup {
+    hero_with(file, prompt, ai, &site().image_widths)
+}
+
+/// A [hero] with its own list of widths instead of the site's.
+pub fn hero_with(
+    file: String,
+    prompt: Option<String>,
+    ai: Option<String>,
+    widths: &[u32],
+) -> Markup {
     let ai = ai.unwrap_or("MidJourney".to_string());
+    context::credit(&format!("hero/{file}"));
+    if let Some(result) = overrides::lookup(
+        "hero",
+        &[
+            ("file", &html! {(file)}),
+            ("prompt", &html! {(prompt.clone().unwrap_or_default())}),
+            ("ai", &html! {(ai)}),
+            ("cdn", &html! {(cdn("hero"))}),
+        ],
+    ) {
+        return result;
+    }
+
+    // Only the first hero on a page is likely to be above the fold.
+    if !context::is_preloading(context::AssetKind::Image) {
+        let mut preload =
+            context::Preload::new(cdn(&format!("hero/{file}.avif")), context::AssetKind::Image)
+                .mime("image/avif");
+        if let Some(sizes) = image_sizes(widths) {
+            preload = preload.srcset(srcset(&format!("hero/{file}"), "avif", widths), sizes);
+        }
+        context::preload(preload);
+    }
+
     html! {
-        meta property="og:image" content={"https://cdn.xeiaso.net/file/christine-static/hero/" (file) "-smol.png"};
         figure.hero style="margin:0" {
             picture style="margin:0" {
-                source type="image/avif" srcset={"https://cdn.xeiaso.net/file/christine-static/hero/" (file) ".avif"};
-                source type="image/webp" srcset={"https://cdn.xeiaso.net/file/christine-static/hero/" (file) ".webp"};
-                img style="padding:0" loading="lazy" alt={"hero image " (file)} src={"https://cdn.xeiaso.net/file/christine-static/hero/" (file) "-smol.png"};
+                source type="image/avif" srcset=(srcset(&format!("hero/{file}"), "avif", widths)) sizes=[image_sizes(widths)];
+                source type="image/webp" srcset=(srcset(&format!("hero/{file}"), "webp", widths)) sizes=[image_sizes(widths)];
+                img style="padding:0" loading="lazy" alt={"hero image " (file)} src=(cdn(&format!("hero/{file}-smol.png")));
             }
             figcaption {
                 (ai)
@@ -55,165 +218,2409 @@ pub fn hero(file: String, prompt: Option<String>, ai: Option<String>) -> Markup
 }
 
 pub fn conv(name: String, mood: String, body: Markup) -> Markup {
+    conv_sized(name, mood, true, body)
+}
+
+/// A [conv] with a small sticker unless it's `standalone`, the way
+/// `<xeblog-conv>` shows them in posts.
+pub fn conv_sized(name: String, mood: String, standalone: bool, body: Markup) -> Markup {
     let name_lower = name.clone().to_lowercase();
+    let mood = sticker_mood(&name_lower, &mood);
     let name = name.replace("_", " ");
+    if context::render_target() != context::RenderTarget::Web {
+        return conv_log(&name, body);
+    }
+    if let Some(result) = overrides::lookup(
+        "conv",
+        &[
+            ("name", &html! {(name)}),
+            ("name_lower", &html! {(name_lower)}),
+            ("mood", &html! {(mood)}),
+            ("body", &body),
+            ("cdn", &html! {(cdn("stickers"))}),
+        ],
+    ) {
+        return result;
+    }
 
     html! {
         .conversation {
-            ."conversation-standalone" {
-                picture {
-                    source type="image/avif" srcset={"https://cdn.xeiaso.net/file/christine-static/stickers/" (name_lower) "/" (mood) ".avif"};
-                    source type="image/webp" srcset={"https://cdn.xeiaso.net/file/christine-static/stickers/" (name_lower) "/" (mood) ".webp"};
-                    img style="max-height:4.5rem" alt={(name) " is " (mood)} loading="lazy" src={"https://cdn.xeiaso.net/file/christine-static/stickers/" (name_lower) "/" (mood) ".png"};
+            @if standalone {
+                ."conversation-standalone" {
+                    (conv_sticker(&name, &name_lower, &mood))
+                }
+            } @else {
+                ."conversation-smol" {
+                    img src=(site().sticker(&name_lower, &mood, 64)) alt={(name) " is " (mood)};
                 }
             }
-            ."conversation-chat" {
-                "<"
-                a href={"/characters#" (name_lower)} { b { (name) } }
-                "> "
+            @let style = chat_style(&name_lower);
+            ."conversation-chat".conversation-accent[style.is_some()] style=[style] {
+                (conv_name(&name, &name_lower))
                 (body)
             }
         }
     }
 }
 
-pub fn sticker(name: String, mood: String) -> Markup {
-    let name_lower = name.to_lowercase();
+/// A line of a conversation the way an IRC log would show it, for feeds and
+/// everything else that isn't the website.
+pub fn conv_log(name: &str, body: Markup) -> Markup {
+    log_line(None, name, body)
+}
+
+fn log_line(time: Option<&str>, name: &str, body: Markup) -> Markup {
     html! {
-        center {
-            picture {
-                source type="image/avif" srcset={"https://cdn.xeiaso.net/file/christine-static/stickers/" (name_lower) "/" (mood) ".avif"};
-                source type="image/webp" srcset={"https://cdn.xeiaso.net/file/christine-static/stickers/" (name_lower) "/" (mood) ".webp"};
-                img alt={(name) " is " (mood)} src={"https://cdn.xeiaso.net/file/christine-static/stickers/" (name_lower) "/" (mood) ".png"};
+        div.conversation-log {
+            @if let Some(time) = time {
+                "[" (time) "] "
             }
+            @if context::render_target() == context::RenderTarget::PlainText {
+                "<" (name) "> "
+            } @else {
+                "<" b { (name) } "> "
+            }
+            (body)
         }
     }
 }
 
-pub fn video(path: String) -> Markup {
-    xeact_component("Video", serde_json::json!({"path": path}))
+/// The mood to show a sticker of a character in. There's no sticker of a
+/// mood that isn't in the characters manifest, so instead of a broken image
+/// the character's default pose is shown and the typo is logged.
+fn sticker_mood(name_lower: &str, mood: &str) -> String {
+    match context::stickers().fallback(name_lower, mood) {
+        Some(pose) => {
+            tracing::warn!("there's no {mood:?} sticker of {name_lower}, showing {pose} instead");
+            pose.to_string()
+        }
+        None => mood.to_string(),
+    }
 }
 
-pub fn advertiser_nag(nag: Option<Markup>) -> Markup {
+fn conv_sticker(name: &str, name_lower: &str, mood: &str) -> Markup {
+    let mood = sticker_mood(name_lower, mood);
     html! {
-        script async src="https://media.ethicalads.io/media/client/ethicalads.min.js" { "" }
-        div.adaptive data-ea-publisher="christinewebsite" data-ea-type="text" data-ea-style="fixedfooter" {
-            .warning {
-                @if let Some(nag) = nag {
-                    (nag)
-                } @else {
-                    (conv(
-                        "Cadey".into(),
-                        "coffee".into(),
-                        html! {
-                            "Hello! Thank you for visiting my website. You seem to be using an ad-blocker. I understand why you do this, but I'd really appreciate if it you would turn it off for my website. These ads help pay for running the website and are done by "
-                            a href="https://www.ethicalads.io/" { "Ethical Ads" }
-                            ". I do not receive detailed analytics on the ads and from what I understand neither does Ethical Ads. If you don't want to disable your ad blocker, please consider donating on "
-                            a href="https://www.patreon.com/cadey" { "Patreon" }
-                            " or sending some extra cash to "
-                            code { "xeiaso.eth" }
-                            " or "
-                            code { "0xeA223Ca8968Ca59e0Bc79Ba331c2F6f636A3fB82" }
-                            ". It helps fund the website's hosting bills and pay for the expensive technical editor that I use for my longer articles. Thanks and be well!"
-                        },
-                    ))
+        picture {
+            source type="image/avif" srcset=(cdn(&format!("stickers/{name_lower}/{mood}.avif")));
+            source type="image/webp" srcset=(cdn(&format!("stickers/{name_lower}/{mood}.webp")));
+            img style="max-height:4.5rem" alt={(name) " is " (mood)} loading="lazy" src=(cdn(&format!("stickers/{name_lower}/{mood}.png")));
+        }
+    }
+}
+
+/// The inline style of a character's chat bubbles, if they have a chat theme.
+pub fn chat_style(name_lower: &str) -> Option<String> {
+    context::chat_theme(name_lower).map(|theme| theme.style())
+}
+
+fn conv_name(name: &str, name_lower: &str) -> Markup {
+    html! {
+        "<"
+        a href={"/characters#" (name_lower)} data-character=(name_lower) { b { (name) } }
+        "> "
+    }
+}
+
+/// Cards about the characters that talk in `body`, found by the
+/// `data-character` attribute on their name links. Each card is a disclosure
+/// that works without JavaScript. With it, the cards also pop up over the
+/// name links when they're hovered or focused.
+pub fn hovercards(body: &str) -> Markup {
+    let cards: Vec<_> = context::hovercards()
+        .iter()
+        .filter(|card| body.contains(&format!(r#"data-character="{}""#, card.sticker_name)))
+        .collect();
+    if cards.is_empty() {
+        return html! {};
+    }
+
+    html! {
+        section.hovercards aria-label="Characters in this post" {
+            @for card in cards {
+                details.hovercard id={"hovercard-" (card.sticker_name)} {
+                    summary { (card.name) }
+                    .hovercard-body {
+                        (conv_sticker(&card.name, &card.sticker_name, &card.default_pose))
+                        p.hovercard-pronouns { (card.name) " (" (card.pronouns) ")" }
+                        (PreEscaped(&card.bio))
+                        p { a href={"/characters#" (card.sticker_name)} { "More about " (card.name) } }
+                    }
                 }
             }
         }
+        script src="/static/js/hovercards.js" defer {}
     }
 }
 
-pub fn toot_embed(u: User, t: Toot) -> Markup {
-    let content = html! {
-        (PreEscaped::<String>(t.content))
+/// One message in a [conv_thread].
+#[derive(Clone, Debug)]
+pub struct ConvMessage {
+    pub name: String,
+    pub mood: String,
+    pub body: Markup,
+}
 
-        @for att in &t.attachment {
-            @if att.media_type.starts_with("image/") {
-                a href=(att.url) {
-                    img width="100%" height="100%" src=(att.url) alt=(att.name.clone().unwrap_or("no description provided".into()));
+impl ConvMessage {
+    pub fn new(name: impl Into<String>, mood: impl Into<String>, body: Markup) -> Self {
+        Self {
+            name: name.into(),
+            mood: mood.into(),
+            body,
+        }
+    }
+}
+
+/// Splits messages into runs of consecutive messages by the same character.
+fn conv_runs<T>(messages: Vec<T>, name: impl Fn(&T) -> &str) -> Vec<Vec<T>> {
+    let mut runs: Vec<Vec<T>> = vec![];
+    for message in messages {
+        match runs.last_mut() {
+            Some(run) if name(&run[0]).to_lowercase() == name(&message).to_lowercase() => {
+                run.push(message)
+            }
+            _ => runs.push(vec![message]),
+        }
+    }
+    runs
+}
+
+/// Says who is talking in a list of runs, such as "between Cadey and Mara".
+fn conv_label<T>(what: &str, runs: &[Vec<T>], name: impl Fn(&T) -> &str) -> String {
+    let mut speakers: Vec<String> = vec![];
+    for run in runs {
+        let name = name(&run[0]).replace('_', " ");
+        if !speakers.contains(&name) {
+            speakers.push(name);
+        }
+    }
+    match speakers.split_last() {
+        None => what.to_string(),
+        Some((only, [])) => format!("{what} with {only}"),
+        Some((last, rest)) => format!("{what} between {} and {last}", rest.join(", ")),
+    }
+}
+
+/// A back-and-forth between characters. Consecutive messages by the same
+/// character are grouped under one sticker, in the mood of the first of them.
+/// The thread is a list with one item per turn, so screen readers say when
+/// someone else starts talking.
+pub fn conv_thread(messages: Vec<ConvMessage>) -> Markup {
+    if context::render_target() != context::RenderTarget::Web {
+        return html! {
+            @for message in messages {
+                (conv_log(&message.name.replace('_', " "), message.body))
+            }
+        };
+    }
+    let runs = conv_runs(messages, |m| &m.name);
+    let label = conv_label("Conversation", &runs, |m| &m.name);
+
+    html! {
+        .conversation-thread role="list" aria-label=(label) {
+            @for run in runs {
+                @let name_lower = run[0].name.to_lowercase();
+                @let name = run[0].name.replace('_', " ");
+                .conversation.conversation-turn role="listitem" {
+                    ."conversation-smol" aria-hidden="true" {
+                        (conv_sticker(&name, &name_lower, &run[0].mood))
+                    }
+                    @let style = chat_style(&name_lower);
+                    .conversation-messages {
+                        @for (i, message) in run.into_iter().enumerate() {
+                            ."conversation-chat".conversation-accent[style.is_some()] style=[style.as_deref()] {
+                                @if i == 0 {
+                                    (conv_name(&name, &name_lower))
+                                }
+                                (message.body)
+                            }
+                        }
+                    }
                 }
             }
+        }
+    }
+}
 
-            @if att.media_type.starts_with("video/") {
-                video width="100%" height="100%" controls {
-                    source src=(att.url) type=(att.media_type);
-                    "Your browser does not support the video tag, see this URL: "
-                        a href=(att.url) {(att.url)}
+/// A message in a [chat_log].
+pub struct ChatMessage {
+    pub name: String,
+    pub mood: String,
+    /// When it was sent, shown as is, such as `14:02 UTC` or `Day 3, 9am`.
+    pub time: Option<String>,
+    pub body: Markup,
+}
+
+impl ChatMessage {
+    pub fn new(name: impl Into<String>, mood: impl Into<String>, body: Markup) -> Self {
+        Self {
+            name: name.into(),
+            mood: mood.into(),
+            time: None,
+            body,
+        }
+    }
+
+    pub fn at(mut self, time: impl Into<String>) -> Self {
+        self.time = Some(time.into());
+        self
+    }
+}
+
+/// A group chat, for fiction and for piecing together what people said
+/// during an incident. Consecutive messages by the same character share one
+/// avatar and name, and each message shows when it was sent if that's known.
+pub fn chat_log(messages: Vec<ChatMessage>) -> Markup {
+    if context::render_target() != context::RenderTarget::Web {
+        return html! {
+            @for message in messages {
+                (log_line(message.time.as_deref(), &message.name.replace('_', " "), message.body))
+            }
+        };
+    }
+    let runs = conv_runs(messages, |m| &m.name);
+    let label = conv_label("Chat", &runs, |m| &m.name);
+
+    html! {
+        .chat-log role="list" aria-label=(label) {
+            @for run in runs {
+                @let name_lower = run[0].name.to_lowercase();
+                @let name = run[0].name.replace('_', " ");
+                @let style = chat_style(&name_lower);
+                .chat-log-group role="listitem" {
+                    .chat-log-avatar aria-hidden="true" {
+                        (conv_sticker(&name, &name_lower, &run[0].mood))
+                    }
+                    .chat-log-messages {
+                        .chat-log-header {
+                            a href={"/characters#" (name_lower)} data-character=(name_lower) { b { (name) } }
+                            @if let Some(time) = &run[0].time {
+                                " " span.chat-log-time { (time) }
+                            }
+                        }
+                        @for (i, message) in run.into_iter().enumerate() {
+                            .chat-log-message.conversation-accent[style.is_some()] style=[style.as_deref()] {
+                                @if i > 0 {
+                                    @if let Some(time) = &message.time {
+                                        span.chat-log-time { (time) }
+                                    }
+                                }
+                                .chat-log-body { (message.body) }
+                            }
+                        }
+                    }
                 }
             }
         }
-        @if t.attachment.len() != 0 {
-            br;
+    }
+}
+
+/// The ID of a sidenote. Markers can be used more than once in a document,
+/// so every use after the first gets its count added after a `_`, which
+/// can't come from a marker.
+fn sidenote_id(marker: &str) -> String {
+    let mention = context::sidenote_mention(marker);
+    let marker: String = marker
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    match mention {
+        1 => format!("sidenote-{marker}"),
+        n => format!("sidenote-{marker}_{n}"),
+    }
+}
+
+fn figure_id(id: &str) -> String {
+    let id: String = id
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    format!("figure-{id}")
+}
+
+/// A numbered figure with a caption. Figures are numbered per document, see
+/// [context::with_figures], and can be linked to with [figref].
+pub fn figure(id: &str, content: Markup, caption: Markup) -> Markup {
+    let number = context::figure_number(id);
+
+    html! {
+        figure.figure id=(figure_id(id)) {
+            (content)
+            figcaption {
+                b { "Figure " (number) ": " }
+                (caption)
+            }
         }
+    }
+}
+
+/// A link to the [figure] `id`, such as "Figure 3".
+pub fn figref(id: &str) -> Markup {
+    let number = context::figure_number(id);
+
+    html! {
+        a.figref href={"#" (figure_id(id))} { "Figure " (number) }
+    }
+}
+
+fn footnote_id(id: &str) -> String {
+    let id: String = id
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    format!("footnote-{id}")
+}
 
-        a href=(t.url.unwrap_or(t.id)) { "Link" }
+/// Declares a footnote and links to it. The note itself is shown by
+/// [footnotes] at the bottom of the article. Outside of
+/// [context::collect_footnotes] there's no bottom to put it at, so it's
+/// shown in brackets where it is instead.
+pub fn footnote(id: &str, body: Markup) -> Markup {
+    match context::footnote(id, body.0.clone()) {
+        Some((number, mention)) => footnote_link(id, number, mention),
+        None => html! { span.footnote-inline { " (" (body) ")" } },
+    }
+}
+
+/// Another link to the footnote `id`. Links can come before the note is
+/// declared.
+pub fn footnote_ref(id: &str) -> Markup {
+    match context::footnote_number(id) {
+        Some((number, mention)) => footnote_link(id, number, mention),
+        None => html! {},
+    }
+}
+
+/// The link for a mention of a note. The note links back to the first one.
+fn footnote_link(id: &str, number: u32, mention: u32) -> Markup {
+    let id = footnote_id(id);
+    let ref_id = match mention {
+        1 => format!("{id}-ref"),
+        n => format!("{id}-ref-{n}"),
     };
+
     html! {
-        .media {
-            .media-left {
-                .avatarholder {
-                    img src=(u.icon.url) alt={"the profile picture for " (u.preferred_username)};
+        sup.footnote-ref {
+            a id=(ref_id) href={"#" (id)} aria-describedby="footnotes-label" { (number) }
+        }
+    }
+}
+
+/// The notes gathered by [context::collect_footnotes], each with a link back
+/// to where it was mentioned. Nothing is rendered when there are none.
+pub fn footnotes(list: &[context::Footnote]) -> Markup {
+    html! {
+        @if !list.is_empty() {
+            section.footnotes {
+                h2 #footnotes-label.sr-only { "Footnotes" }
+                ol {
+                    @for note in list {
+                        li id=(footnote_id(&note.id)) value=(note.number) {
+                            (PreEscaped(&note.body))
+                            " "
+                            a.footnote-backref href={"#" (footnote_id(&note.id)) "-ref"} aria-label={"Back to note " (note.number)} { "↩" }
+                        }
+                    }
                 }
             }
-            .media-body {
-                .media-heading {
-                    (u.name.replace(":verified:", ""))
-                    @if u.id == "https://pony.social/users/cadey" {
-                        img.verified src="https://cdn.xeiaso.net/file/christine-static/blog/verified.png";
+        }
+    }
+}
+
+/// A margin note. On wide screens this floats into the right margin of the
+/// article. On narrow screens it collapses into the text and can be toggled by
+/// tapping the marker (this uses the checkbox hack, no JavaScript needed).
+pub fn sidenote(marker: String, body: Markup) -> Markup {
+    let id = sidenote_id(&marker);
+
+    html! {
+        label.sidenote-marker for=(id) { sup { (marker) } }
+        input.sidenote-toggle type="checkbox" id=(id);
+        span.sidenote {
+            sup { (marker) }
+            " "
+            (body)
+        }
+    }
+}
+
+/// Renders code and the prose explaining it side by side. Each step is a pair
+/// of (code, explanation). On narrow screens the steps get stacked.
+pub fn code_walkthrough(steps: Vec<(Markup, Markup)>) -> Markup {
+    html! {
+        .code-walkthrough {
+            @for (code, explanation) in steps {
+                .code-walkthrough-step {
+                    .code-walkthrough-code { (code) }
+                    .code-walkthrough-prose { (explanation) }
+                }
+            }
+        }
+    }
+}
+
+pub fn sticker(name: String, mood: String) -> Markup {
+    let name_lower = name.to_lowercase();
+    let mood = sticker_mood(&name_lower, &mood);
+    html! {
+        center {
+            picture {
+                source type="image/avif" srcset=(cdn(&format!("stickers/{name_lower}/{mood}.avif")));
+                source type="image/webp" srcset=(cdn(&format!("stickers/{name_lower}/{mood}.webp")));
+                img alt={(name) " is " (mood)} src=(cdn(&format!("stickers/{name_lower}/{mood}.png")));
+            }
+        }
+    }
+}
+
+/// A character on the characters page, with every sticker there is of
+/// them. It's anchored at their sticker name, which is where the names in
+/// conversations link to.
+pub fn character_card(character: &Character) -> Markup {
+    html! {
+        section.character-card #(character.sticker_name) {
+            h3 { (character.name) }
+            (sticker(character.name.clone(), character.default_pose.clone()))
+            p.character-pronouns { "Pronouns: " (character.pronouns) }
+            (PreEscaped(&character.description))
+            @if let Some(artist) = &character.artist {
+                p.character-artist {
+                    "Stickers by "
+                    @if let Some(url) = &artist.url {
+                        a href=(safe_url(url)) { (artist.name) }
+                    } @else {
+                        (artist.name)
                     }
-                    " "
-                    a href=(u.url) {"@" (u.preferred_username)}
-                    br;
-                    (t.published.format("M%m %d %Y %H:%M (UTC)").to_string())
+                    "."
                 }
-                .media-content {
-                    @if let Some(warning) = t.summary {
-                        details {
-                            summary { "Content warning: " (warning) }
-                            (content)
+            }
+            details {
+                summary { "All stickers (" (character.stickers.len()) ")" }
+                ul.sticker-grid {
+                    @for mood in &character.stickers {
+                        li {
+                            (sticker(character.name.clone(), mood.clone()))
+                            code { (mood) }
                         }
+                    }
+                }
+            }
+            @if let Some(pack) = &character.pack {
+                p {
+                    a href=(pack.path) download { "Download " (character.name) "'s stickers" }
+                    " to use in Signal or Telegram. They are licensed under "
+                    @if let Some(url) = &pack.license_url {
+                        a href=(safe_url(url)) { (pack.license) }
                     } @else {
-                        (content)
+                        (pack.license)
                     }
+                    "."
                 }
             }
         }
     }
 }
 
-pub fn xeact_component(name: &str, data: serde_json::Value) -> Markup {
-    let uuid = uuid::Uuid::new_v4();
-    let uuid = format!("{uuid}").replace("-", "");
-
-    let script = PreEscaped(format!(
-        r#"
-<script type="module">
-import Component from "/static/xeact/{name}.js?cacheBuster={uuid}";
-
-const g = (name) => document.getElementById(name);
-const x = (elem) => {{
-    while (elem.lastChild) {{
-        elem.removeChild(elem.lastChild);
-    }}
-}};
-
-const root = g("{uuid}");
-x(g);
-
-root.appendChild(Component({data}))
-</script>
-"#,
-        data=serde_json::to_string(&data).unwrap(),
-    ));
-    
-    html! {
-        div id=(uuid) {
-            noscript {
-                div.warning {
-                    (conv("Aoi".into(), "coffee".into(), PreEscaped("This dynamic component requires JavaScript to function, sorry!".to_string())))
+/// Every character, with a list of them at the top to jump to each one.
+pub fn characters_page(characters: &[Character]) -> Markup {
+    html! {
+        nav.character-roster aria-label="Characters" {
+            ul {
+                @for character in characters {
+                    li { a href={"#" (character.sticker_name)} { (character.name) } }
+                }
+            }
+        }
+        @for character in characters {
+            (character_card(character))
+        }
+    }
+}
+
+pub fn video(path: String) -> Markup {
+    context::credit(&path);
+    xeact_component_with("Video", serde_json::json!({"path": path}), Hydrate::Visible)
+}
+
+/// A WebVTT captions file for a [video_native].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Track {
+    /// The path of the `.vtt` file in the bucket.
+    pub path: String,
+    /// The language of the captions, like `en`.
+    pub lang: String,
+    /// What the captions are called in the player's menu.
+    pub label: String,
+}
+
+/// A video that plays without the Xeact player. The HLS stream at
+/// `{path}/index.m3u8` plays natively where browsers can, and
+/// `/static/js/video.js` loads hls.js for the ones that can't. `poster` is
+/// the path of an image in the bucket, with its extension. The first of the
+/// `tracks` is on by default.
+pub fn video_native(path: String, poster: Option<String>, tracks: Vec<Track>) -> Markup {
+    context::credit(&path);
+    let stream = cdn(&format!("{path}/index.m3u8"));
+    // Captions on another origin are only loaded with CORS.
+    let cors = (!tracks.is_empty()).then_some("anonymous");
+
+    html! {
+        video.video-native controls preload="metadata" playsinline poster=[poster.map(|p| cdn(&p))] crossorigin=[cors] data-stream=(stream) {
+            source src=(stream) type="application/vnd.apple.mpegurl";
+            source src=(cdn("blog/HLSBROKE.mp4")) type="video/mp4";
+            @for (i, track) in tracks.iter().enumerate() {
+                track kind="captions" src=(cdn(&track.path)) srclang=(track.lang) label=(track.label) default[i == 0];
+            }
+            p {
+                "Your browser can't play this video, but you can "
+                a href=(stream) { "open the stream" }
+                " in a video player."
+            }
+        }
+        @if context::render_target() == context::RenderTarget::Web {
+            script type="module" src="/static/js/video.js" {}
+        }
+    }
+}
+
+/// A point in an [audio_player_with] that can be jumped to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Chapter {
+    /// How far into the audio the chapter starts.
+    pub start: std::time::Duration,
+    pub title: String,
+}
+
+/// Times like `4:05` or `1:02:03`, the way players show them.
+fn clock(time: std::time::Duration) -> String {
+    let secs = time.as_secs();
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if h > 0 {
+        format!("{h}:{m:02}:{s:02}")
+    } else {
+        format!("{m}:{s:02}")
+    }
+}
+
+/// A podcast-style audio file from the bucket, like an episode. `path` has
+/// its extension.
+pub fn audio_player(
+    path: String,
+    title: String,
+    duration: std::time::Duration,
+    transcript: Option<Markup>,
+) -> Markup {
+    audio_player_with(path, title, duration, &[], transcript)
+}
+
+/// An [audio_player] with chapters. Each one links to its time in the file,
+/// and `/static/js/audio.js` makes the links jump the player there instead.
+/// The transcript, if there is one, is folded away under the player.
+pub fn audio_player_with(
+    path: String,
+    title: String,
+    duration: std::time::Duration,
+    chapters: &[Chapter],
+    transcript: Option<Markup>,
+) -> Markup {
+    context::credit(&path);
+    let src = cdn(&path);
+    let web = context::render_target() == context::RenderTarget::Web;
+
+    html! {
+        figure.audio-player {
+            figcaption {
+                (title) " "
+                small { "(" time datetime={"PT" (duration.as_secs()) "S"} { (clock(duration)) } ")" }
+            }
+            audio controls preload="metadata" src=(src) aria-label=(title) {
+                a href=(src) { "Download " (title) }
+            }
+            @if !chapters.is_empty() {
+                nav.audio-chapters aria-label={"Chapters of " (title)} {
+                    ol {
+                        @for chapter in chapters {
+                            li {
+                                a href={(src) "#t=" (chapter.start.as_secs())} data-start=(chapter.start.as_secs()) {
+                                    time datetime={"PT" (chapter.start.as_secs()) "S"} { (clock(chapter.start)) }
+                                    " " (chapter.title)
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            @if let Some(transcript) = transcript {
+                details.audio-transcript {
+                    summary { "Transcript" }
+                    div.audio-transcript-body { (transcript) }
                 }
             }
         }
-        (script)
+        @if web && !chapters.is_empty() {
+            script type="module" src="/static/js/audio.js" {}
+        }
+    }
+}
+
+/// Renders the addresses people can donate to, joined with "or".
+fn donation_addresses() -> Markup {
+    let addresses = &site().donation.addresses;
+    html! {
+        @for (i, address) in addresses.iter().enumerate() {
+            @if i != 0 { " or " }
+            code { (address) }
+        }
+    }
+}
+
+/// Where readers can turn ads off.
+const PREFS_URL: &str = "/.within/prefs";
+
+/// The ads and the message shown to people who block them. Readers who
+/// turned ads off and patrons get neither, so the ad script isn't loaded
+/// for them at all.
+pub fn advertiser_nag(prefs: ReaderPrefs, nag: Option<Markup>) -> Markup {
+    let Some(publisher) = site().ads() else {
+        return html! {};
+    };
+    if !prefs.shows_ads() {
+        return html! {};
+    }
+
+    html! {
+        script async src="https://media.ethicalads.io/media/client/ethicalads.min.js" { "" }
+        div.adaptive data-ea-publisher=(publisher) data-ea-type="text" data-ea-style="fixedfooter" {
+            .warning {
+                @if let Some(nag) = nag {
+                    (nag)
+                } @else {
+                    (conv(
+                        "Cadey".into(),
+                        "coffee".into(),
+                        html! {
+                            "Hello! Thank you for visiting my website. You seem to be using an ad-blocker. I understand why you do this, but I'd really appreciate if it you would turn it off for my website. These ads help pay for running the website and are done by "
+                            a href="https://www.ethicalads.io/" { "Ethical Ads" }
+                            ". I do not receive detailed analytics on the ads and from what I understand neither does Ethical Ads. If you don't want to disable your ad blocker, please consider donating on "
+                            a href=(site().donation.patreon_url) { "Patreon" }
+                            @if !site().donation.addresses.is_empty() {
+                                " or sending some extra cash to "
+                                (donation_addresses())
+                            }
+                            ". It helps fund the website's hosting bills and pay for the expensive technical editor that I use for my longer articles. Thanks and be well!"
+                        },
+                    ))
+                }
+                p.ad-prefs {
+                    small {
+                        "Rather not see ads at all? You can "
+                        a href=(PREFS_URL) { "turn them off" }
+                        " for this browser."
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Which of `sponsors` gets the slot on the page for `slug`. This is a
+/// 64-bit FNV-1a hash of the slug, so a post always shows the same sponsor
+/// across restarts and builds, and posts are spread evenly between them.
+fn sponsor_for<'a>(slug: &str, sponsors: &'a [Sponsor]) -> Option<&'a Sponsor> {
+    if sponsors.is_empty() {
+        return None;
+    }
+    let hash = slug.bytes().fold(0xcbf29ce484222325u64, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    });
+    sponsors.get((hash % sponsors.len() as u64) as usize)
+}
+
+/// A sponsor slot for the end of the post at `slug`, with one of `sponsors`
+/// picked by [sponsor_for]. This is a first-party alternative to
+/// [advertiser_nag]: nothing is loaded from anywhere else and nobody is
+/// tracked. It's empty when there are no sponsors.
+pub fn sponsor_footer(slug: &str, sponsors: Vec<Sponsor>) -> Markup {
+    let Some(sponsor) = sponsor_for(slug, &sponsors) else {
+        return html! {};
+    };
+
+    html! {
+        aside.sponsor-footer aria-label="Sponsor" {
+            a href=(sponsor.url) rel="sponsored noopener" {
+                img.sponsor-logo src=(sponsor.logo) alt=(sponsor.name) loading="lazy";
+            }
+            p {
+                small { "Sponsored by " }
+                a href=(sponsor.url) rel="sponsored noopener" { strong { (sponsor.name) } }
+                br;
+                (sponsor.blurb)
+            }
+        }
+    }
+}
+
+pub fn originally_published(origin: OriginMeta) -> Markup {
+    html! {
+        .warning {
+            "This article was originally published on "
+            a href=(origin.url) { (origin.venue) }
+            @if let Some(date) = origin.date {
+                " on "
+                (date)
+            }
+            ". It is republished here so that it can live alongside the rest of my writing. The original version is the canonical one."
+        }
+    }
+}
+
+pub fn event_card(event: EventMeta) -> Markup {
+    let schema = serde_json::json!({
+        "@context": "https://schema.org",
+        "@type": "Event",
+        "name": event.name,
+        "startDate": event.start_date,
+        "endDate": event.end_date.as_ref().unwrap_or(&event.start_date),
+        "eventAttendanceMode": "https://schema.org/OfflineEventAttendanceMode",
+        "location": {
+            "@type": "Place",
+            "name": event.venue,
+            "address": event.location.as_ref().unwrap_or(&event.venue),
+        },
+        "url": event.url,
+        "description": event.talk_title,
+        "performer": {
+            "@type": "Person",
+            "name": "Xe Iaso",
+        },
+    });
+
+    html! {
+        .card.event-card {
+            header.card-header { (event.name) }
+            .card-content {
+                dl {
+                    @if let Some(talk_title) = &event.talk_title {
+                        dt { "Talk" }
+                        dd { (talk_title) }
+                    }
+                    dt { "When" }
+                    dd {
+                        (event.start_date)
+                        @if let Some(end_date) = &event.end_date {
+                            " to "
+                            (end_date)
+                        }
+                    }
+                    dt { "Where" }
+                    dd {
+                        (event.venue)
+                        @if let Some(location) = &event.location {
+                            ", "
+                            (location)
+                        }
+                    }
+                }
+                @if let Some(url) = &event.url {
+                    a href=(safe_url(url)) { "Event website and registration" }
+                }
+            }
+        }
+        (json_ld(&schema))
+    }
+}
+
+/// Who made the images and videos a post shows and what license they're
+/// under, from the credits [context::collect_credits] gathered while it was
+/// rendered.
+pub fn media_credits(credits: &[MediaCredit]) -> Markup {
+    html! {
+        @if !credits.is_empty() {
+            section.media-credits {
+                h2 { "Media credits" }
+                table {
+                    thead {
+                        tr {
+                            th { "Asset" }
+                            th { "Creator" }
+                            th { "License" }
+                        }
+                    }
+                    tbody {
+                        @for credit in credits {
+                            tr {
+                                td {
+                                    @if let Some(source) = &credit.source {
+                                        a href=(source) { code { (credit.path) } }
+                                    } @else {
+                                        code { (credit.path) }
+                                    }
+                                }
+                                td { (credit.creator) }
+                                td {
+                                    @if let Some(url) = &credit.license_url {
+                                        a href=(url) rel="license" { (credit.license) }
+                                    } @else {
+                                        (credit.license)
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// One step of a [breadcrumbs] trail. `url` is a path on the site.
+#[derive(Clone, Debug)]
+pub struct Crumb {
+    pub name: String,
+    pub url: String,
+}
+
+impl Crumb {
+    pub fn new(name: impl Into<String>, url: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            url: url.into(),
+        }
+    }
+}
+
+/// Where a post is in its series: which part it is, links to the parts
+/// before and after it, and every part in a list that's closed until it's
+/// clicked. Posts show it at the top and the bottom.
+pub fn series_nav(series: &Series) -> Markup {
+    html! {
+        nav.series-nav aria-label="Series" {
+            p {
+                "Part " (series.number()) " of " (series.parts.len()) " in "
+                a href={"/blog/series/" (series.title)} { (series.title) }
+            }
+            @if series.previous().is_some() || series.next().is_some() {
+                p.series-nav-links {
+                    @if let Some(previous) = series.previous() {
+                        a rel="prev" href=(previous.url) { "← " (previous.title) }
+                    }
+                    @if let Some(next) = series.next() {
+                        a rel="next" href=(next.url) { (next.title) " →" }
+                    }
+                }
+            }
+            details {
+                summary { "All parts" }
+                ol {
+                    @for (i, part) in series.parts.iter().enumerate() {
+                        li {
+                            @if i == series.current {
+                                span aria-current="page" { (part.title) }
+                            } @else {
+                                a href=(part.url) { (part.title) }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Links from the top of the site down to the current page, which is the last
+/// crumb. The same trail is emitted as a schema.org `BreadcrumbList` so search
+/// results can show it.
+pub fn breadcrumbs(crumbs: Vec<Crumb>) -> Markup {
+    let schema = serde_json::json!({
+        "@context": "https://schema.org",
+        "@type": "BreadcrumbList",
+        "itemListElement": crumbs
+            .iter()
+            .enumerate()
+            .map(|(i, crumb)| serde_json::json!({
+                "@type": "ListItem",
+                "position": i + 1,
+                "name": crumb.name,
+                "item": site().url(&crumb.url),
+            }))
+            .collect::<Vec<_>>(),
+    });
+
+    html! {
+        @if !crumbs.is_empty() {
+            nav.breadcrumbs aria-label="Breadcrumbs" {
+                ol {
+                    @for (i, crumb) in crumbs.iter().enumerate() {
+                        li {
+                            @if i + 1 == crumbs.len() {
+                                span aria-current="page" { (crumb.name) }
+                            } @else {
+                                a href=(crumb.url) { (crumb.name) }
+                            }
+                        }
+                    }
+                }
+            }
+            (json_ld(&schema))
+        }
+    }
+}
+
+/// The page numbers [pagination] links to: the first and last pages and the
+/// ones next to `current`. `None` stands for a gap.
+fn page_numbers(current: usize, total: usize) -> Vec<Option<usize>> {
+    let mut result = vec![];
+    for page in 1..=total {
+        if page == 1 || page == total || page.abs_diff(current) <= 2 {
+            result.push(Some(page));
+        } else if result.last() != Some(&None) {
+            result.push(None);
+        }
+    }
+    result
+}
+
+/// Links between the pages of a listing split into `total` pages, numbered
+/// from 1. `route` gives the path of each page. Also emits `rel=prev` and
+/// `rel=next` links for the pages around `current`.
+pub fn pagination(current: usize, total: usize, route: impl Fn(usize) -> String) -> Markup {
+    html! {
+        @if total > 1 {
+            @if current > 1 {
+                link rel="prev" href=(route(current - 1));
+            }
+            @if current < total {
+                link rel="next" href=(route(current + 1));
+            }
+            nav.pagination aria-label="Pages" {
+                @if current > 1 {
+                    a rel="prev" href=(route(current - 1)) { "← Previous" }
+                }
+                @for page in page_numbers(current, total) {
+                    @match page {
+                        Some(page) if page == current => {
+                            span aria-current="page" { (page) }
+                        }
+                        Some(page) => {
+                            a href=(route(page)) { (page) }
+                        }
+                        None => {
+                            span.gap { "…" }
+                        }
+                    }
+                }
+                @if current < total {
+                    a rel="next" href=(route(current + 1)) { "Next →" }
+                }
+            }
+        }
+    }
+}
+
+/// Shown on posts that were signed when they were published, with what a
+/// reader needs to check the signatures themselves. `source` is the URL of
+/// the signed markdown; signatures are served next to it.
+pub fn signature_badge(source: String, minisign: bool, pgp: bool) -> Markup {
+    let signing = &site().signing;
+    let file = source.rsplit('/').next().unwrap_or_default().to_string();
+    html! {
+        details.signature-badge {
+            summary { "✓ Signed post" }
+            p {
+                "This post was signed when it was published. Download "
+                a href=(source) { "the source" }
+                @if minisign {
+                    ", "
+                    a href={(source) ".minisig"} { "its minisign signature" }
+                }
+                @if pgp {
+                    ", "
+                    a href={(source) ".asc"} { "its PGP signature" }
+                }
+                " and check them:"
+            }
+            @if let (true, Some(key)) = (minisign, &signing.minisign_public_key) {
+                pre { code { "minisign -Vm " (file) " -P " (key) } }
+            }
+            @if pgp {
+                pre { code { "gpg --verify " (file) ".asc " (file) } }
+                @if let Some(url) = &signing.pgp_key_url {
+                    p {
+                        "The PGP key is "
+                        a href=(url) rel="pgpkey" { "here" }
+                        "."
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// About how many people follow the site, and how that changed over the
+/// last `days` when there's a `change` to show.
+pub fn subscriber_badge(readers: u64, change: Option<i64>, days: i64) -> Markup {
+    html! {
+        span.subscriber-badge title="Estimated from feed readers and fediverse followers" {
+            (readers) " " (if readers == 1 { "reader" } else { "readers" })
+            @if let Some(change) = change.filter(|c| *c != 0) {
+                " "
+                small { (format!("{change:+}")) " in " (days) " days" }
+            }
+        }
+    }
+}
+
+pub fn content_warning(warning: String, body: Markup) -> Markup {
+    html! {
+        details.content-warning {
+            summary { "Content warning: " (warning) }
+            (body)
+        }
+    }
+}
+
+/// What a [callout] is for, which decides how it looks and how assistive
+/// technology announces it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CalloutKind {
+    Note,
+    Tip,
+    Warning,
+    /// Something that can break things or lose data if it's ignored.
+    Danger,
+    /// An aside from me, shown as a conversation with my sticker in `mood`.
+    XeSays {
+        mood: String,
+    },
+}
+
+impl CalloutKind {
+    fn name(&self) -> &'static str {
+        match self {
+            CalloutKind::Note => "note",
+            CalloutKind::Tip => "tip",
+            CalloutKind::Warning => "warning",
+            CalloutKind::Danger => "danger",
+            CalloutKind::XeSays { .. } => "xe-says",
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            CalloutKind::Note => "Note",
+            CalloutKind::Tip => "Tip",
+            CalloutKind::Warning => "Warning",
+            CalloutKind::Danger => "Danger",
+            CalloutKind::XeSays { .. } => "Xe says",
+        }
+    }
+
+    /// Warnings and dangers are alerts so screen readers stress them.
+    /// Asides from me are left as the `complementary` an `aside` is.
+    fn role(&self) -> Option<&'static str> {
+        match self {
+            CalloutKind::Note | CalloutKind::Tip => Some("note"),
+            CalloutKind::Warning | CalloutKind::Danger => Some("alert"),
+            CalloutKind::XeSays { .. } => None,
+        }
+    }
+}
+
+/// A box set apart from the text around it, like a note or a warning. Use
+/// this instead of a `div.warning` in posts.
+pub fn callout(kind: CalloutKind, body: Markup) -> Markup {
+    html! {
+        aside class={"callout callout-" (kind.name())} role=[kind.role()] aria-label=(kind.label()) {
+            @if let CalloutKind::XeSays { mood } = &kind {
+                (conv("Cadey".into(), mood.clone(), body))
+            } @else {
+                p.callout-label aria-hidden="true" { (kind.label()) }
+                .callout-body { (body) }
+            }
+        }
+    }
+}
+
+/// A JSON-LD script tag with `data` in it. Every `<` is escaped, so text in
+/// the data can't close the tag or open a comment.
+pub fn json_ld(data: &impl serde::Serialize) -> Markup {
+    let json = serde_json::to_string(data).unwrap().replace('<', "\\u003c");
+    html! {
+        script type="application/ld+json" { (PreEscaped(json)) }
+    }
+}
+
+/// Only lets http(s) URLs through so that toots can't smuggle in
+/// `javascript:` links.
+fn safe_url(url: &str) -> &str {
+    if url.starts_with("https://") || url.starts_with("http://") {
+        url
+    } else {
+        "#"
+    }
+}
+
+/// Strips everything but basic formatting and links out of the HTML of a
+/// toot, keeping the classes Mastodon uses to shorten long links.
+pub fn sanitize_toot(content: &str) -> String {
+    ammonia::Builder::default()
+        .url_schemes(["http", "https"].into())
+        .add_allowed_classes("a", &["mention", "hashtag", "u-url"])
+        .add_allowed_classes("span", &["invisible", "ellipsis", "h-card"])
+        .clean(content)
+        .to_string()
+}
+
+/// The results of a poll in a toot. Votes can only be cast on the server the
+/// toot is from, so there's a link to it while the poll is open.
+fn toot_poll(poll: &Poll, link: &str) -> Markup {
+    let host = link.split('/').nth(2).unwrap_or("Mastodon");
+    html! {
+        table.toot-poll {
+            caption {
+                @if poll.closed { "Final results" } @else { "Results so far" }
+                @if let Some(ends) = poll.ends {
+                    @if poll.closed { ", closed " } @else { ", closes " }
+                    (ends.format("M%m %d %Y %H:%M (UTC)").to_string())
+                }
+                @if poll.kind == PollKind::Any { ", more than one option could be picked" }
+            }
+            thead {
+                tr {
+                    th scope="col" { "Option" }
+                    th scope="col" { "Votes" }
+                    th scope="col" { "Share" }
+                }
+            }
+            tbody {
+                @for (name, votes) in &poll.options {
+                    @let percent = poll.percent(*votes);
+                    tr {
+                        th scope="row" { (name) }
+                        td { (votes) }
+                        td {
+                            span.toot-poll-bar style={"width:" (percent.min(100)) "%"} {}
+                            (percent) "%"
+                        }
+                    }
+                }
+            }
+        }
+        @if !poll.closed {
+            p { a href=(safe_url(link)) { "Vote on " (host) } }
+        }
+    }
+}
+
+/// Something from another site that a post embeds with `<xeblog-embed>`.
+/// Videos start as their thumbnail, which is swapped for the player when
+/// it's clicked so nothing loads from the provider until the reader asks.
+/// Without scripts the thumbnail links to the video. Toots are shown with
+/// [toot_embed] instead.
+pub fn embed(embed: Embed) -> Markup {
+    let player = embed.player.as_deref().filter(|url| is_player(url));
+    let web = context::render_target() == context::RenderTarget::Web;
+    html! {
+        @if embed.kind == EmbedKind::Video {
+            figure.embed.embed-video data-player=[player] data-title=(embed.title) {
+                a.embed-video-facade href=(safe_url(&embed.url)) {
+                    @if let Some(image) = &embed.image {
+                        img src=(image) alt="" loading="lazy";
+                    }
+                    span.embed-play aria-hidden="true" { "▶" }
+                    span.sr-only { "Play " (embed.title) " from " (embed.provider) }
+                }
+                figcaption {
+                    a href=(safe_url(&embed.url)) { (embed.title) }
+                    @if let Some(author) = &embed.author { " by " (author) }
+                    " on " (embed.provider)
+                }
+            }
+            @if web && player.is_some() {
+                script type="module" src="/static/js/embed.js" {}
+            }
+        } @else {
+            aside.embed.embed-card aria-label={"Link to " (embed.provider)} {
+                a href=(safe_url(&embed.url)) rel="noopener" {
+                    @if let Some(image) = &embed.image {
+                        img src=(image) alt="" loading="lazy";
+                    }
+                    span.embed-card-text {
+                        strong { (embed.title) }
+                        @if let Some(description) = &embed.description {
+                            span.embed-card-description { (description) }
+                        }
+                        small { (embed.provider) }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A link to the copy of the page linked just before it on the Internet
+/// Archive, for when the original moves or goes away.
+pub fn archive_link(snapshot: &str) -> Markup {
+    html! {
+        sup.archive-link {
+            a href=(safe_url(snapshot)) rel="nofollow" title="A copy of this page on the Internet Archive" { "archived" }
+        }
+    }
+}
+
+pub fn toot_embed(u: User, t: Toot) -> Markup {
+    html! {
+        .media {
+            (toot_avatar(&u))
+            .media-body {
+                .media-heading {
+                    (toot_author(&u))
+                    br;
+                    (toot_time(&t))
+                }
+                .media-content {
+                    (toot_content(&t))
+                }
+            }
+        }
+    }
+}
+
+fn toot_avatar(u: &User) -> Markup {
+    html! {
+        .media-left {
+            .avatarholder {
+                img src=(safe_url(&u.icon.url)) alt={"the profile picture for " (u.preferred_username)};
+            }
+        }
+    }
+}
+
+fn toot_author(u: &User) -> Markup {
+    html! {
+        (u.name.replace(":verified:", ""))
+        @if u.id == site().mastodon.actor {
+            img.verified src=(cdn("blog/verified.png"));
+        }
+        " "
+        a href=(safe_url(&u.url)) {"@" (u.preferred_username)}
+    }
+}
+
+fn toot_time(t: &Toot) -> String {
+    t.published.format("M%m %d %Y %H:%M (UTC)").to_string()
+}
+
+fn toot_content(t: &Toot) -> Markup {
+    let link = t.url.clone().unwrap_or_else(|| t.id.clone());
+    let content = html! {
+        (PreEscaped(sanitize_toot(&t.content)))
+
+        @if let Some(poll) = t.poll() {
+            (toot_poll(&poll, &link))
+        }
+
+        @for att in &t.attachment {
+            @if att.media_type.starts_with("image/") {
+                a href=(safe_url(&att.url)) {
+                    img width="100%" height="100%" src=(safe_url(&att.url)) alt=(att.name.clone().unwrap_or("no description provided".into()));
+                }
+            }
+
+            @if att.media_type.starts_with("video/") {
+                video width="100%" height="100%" controls {
+                    source src=(safe_url(&att.url)) type=(att.media_type);
+                    "Your browser does not support the video tag, see this URL: "
+                        a href=(safe_url(&att.url)) {(att.url)}
+                }
+            }
+        }
+        @if t.attachment.len() != 0 {
+            br;
+        }
+
+        a href=(safe_url(&link)) { "Link" }
+    };
+    html! {
+        @if let Some(warning) = &t.summary {
+            (content_warning(warning.clone(), content))
+        } @else {
+            (content)
+        }
+    }
+}
+
+/// Replies below a reply are hidden behind a `<details>` when there are more
+/// of them than this.
+const THREAD_COLLAPSE_AFTER: usize = 3;
+
+/// A whole Mastodon conversation, with each reply indented under the toot it
+/// replies to. Toots that reply to something that isn't in `toots` start a
+/// thread of their own. When someone replies to themselves, the reply is
+/// shown without their name and picture again.
+pub fn thread_embed(toots: Vec<(User, Toot)>) -> Markup {
+    let mut order: Vec<usize> = (0..toots.len()).collect();
+    order.sort_by_key(|&i| toots[i].1.published);
+
+    let mut replies: HashMap<&str, Vec<usize>> = HashMap::new();
+    let mut roots = vec![];
+    for &i in &order {
+        match toots[i].1.in_reply_to.as_deref() {
+            Some(parent) if toots.iter().any(|(_, t)| t.id == parent) => {
+                replies.entry(parent).or_default().push(i)
+            }
+            _ => roots.push(i),
+        }
+    }
+
+    html! {
+        .toot-thread {
+            @for i in roots {
+                (thread_node(&toots, &replies, i, None, 0))
+            }
+        }
+    }
+}
+
+fn thread_node(
+    toots: &[(User, Toot)],
+    replies: &HashMap<&str, Vec<usize>>,
+    i: usize,
+    parent_author: Option<&str>,
+    depth: usize,
+) -> Markup {
+    let (u, t) = &toots[i];
+    let children = replies.get(t.id.as_str()).map_or(&[][..], Vec::as_slice);
+    let continued = parent_author == Some(t.attributed_to.as_str());
+    let nested = html! {
+        @for &child in children {
+            (thread_node(toots, replies, child, Some(t.attributed_to.as_str()), depth + 1))
+        }
+    };
+
+    html! {
+        .toot-thread-item {
+            @if continued {
+                .media.toot-thread-continued {
+                    .media-body {
+                        .media-heading { (toot_time(t)) }
+                        .media-content { (toot_content(t)) }
+                    }
+                }
+            } @else {
+                .media {
+                    (toot_avatar(u))
+                    .media-body {
+                        .media-heading {
+                            (toot_author(u))
+                            br;
+                            (toot_time(t))
+                        }
+                        .media-content { (toot_content(t)) }
+                    }
+                }
+            }
+            @if !children.is_empty() {
+                @let count = thread_size(toots, replies, i) - 1;
+                @if depth > 0 && count > THREAD_COLLAPSE_AFTER {
+                    details.toot-thread-replies {
+                        summary { (count) " more replies" }
+                        (nested)
+                    }
+                } @else {
+                    .toot-thread-replies { (nested) }
+                }
+            }
+        }
+    }
+}
+
+/// How many toots are in the subtree starting at `i`, counting it.
+fn thread_size(toots: &[(User, Toot)], replies: &HashMap<&str, Vec<usize>>, i: usize) -> usize {
+    1 + replies.get(toots[i].1.id.as_str()).map_or(0, |children| {
+        children
+            .iter()
+            .map(|&child| thread_size(toots, replies, child))
+            .sum()
+    })
+}
+
+/// Live statistics for a year of blogposts, fetched from the stats API so
+/// year in review posts don't need to hand-compute anything.
+pub fn year_stats(year: String) -> Markup {
+    xeact_component_with(
+        "YearStats",
+        serde_json::json!({ "year": year }),
+        Hydrate::Visible,
+    )
+}
+
+/// A banner on every page that isn't being served by production, so it's
+/// obvious when you are looking at a dev or staging copy of the site.
+pub fn environment_banner() -> Markup {
+    use xesite_types::site::Environment;
+
+    let site = site();
+    html! {
+        @if site.environment != Environment::Prod {
+            div.environment-banner.{"environment-" (site.environment)} {
+                "This is the " (site.environment) " environment, served from "
+                code {(site.profile().base_url)}
+                ". Ads and analytics may be disabled and things may be broken."
+            }
+        }
+    }
+}
+
+/// A banner on pages from a static export, because anything that changes
+/// while the site is up (like the status page) is frozen at export time.
+pub fn static_export_banner() -> Markup {
+    html! {
+        @if let Some(made_at) = context::static_export() {
+            div.environment-banner.environment-static {
+                "This is a copy of the site made at " (made_at)
+                ". Things that change on their own, like the status page, may be out of date."
+            }
+        }
+    }
+}
+
+/// The banner on pages served from a static export in maintenance mode.
+/// `message` says what's going on, if it's not empty.
+pub fn maintenance_banner(message: &str) -> Markup {
+    html! {
+        div.environment-banner.environment-maintenance role="status" {
+            "The site is down for maintenance, so this is a saved copy of the page."
+            @if !message.is_empty() {
+                " " (message)
+            }
+        }
+    }
+}
+
+/// The script that mounts every [xeact_component] on a page. Include this once
+/// per page, usually in the layout.
+pub fn xeact_runtime(cachebuster: &str) -> Markup {
+    html! {
+        script type="module" src={"/static/js/xeact-runtime.js?cacheBuster=" (cachebuster)} {}
+    }
+}
+
+/// When the shared runtime should load and mount a component.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Hydrate {
+    /// As soon as the page loads.
+    #[default]
+    Load,
+    /// When the component scrolls into view.
+    Visible,
+    /// When the browser has nothing better to do.
+    Idle,
+    /// When the reader hovers over, focuses or taps the component.
+    Interaction,
+}
+
+impl Hydrate {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Hydrate::Load => "load",
+            Hydrate::Visible => "visible",
+            Hydrate::Idle => "idle",
+            Hydrate::Interaction => "interaction",
+        }
+    }
+}
+
+/// A placeholder for a Xeact component that is mounted as soon as the page
+/// loads. See [xeact_component_with] for components that can wait.
+pub fn xeact_component(name: &str, data: serde_json::Value) -> Markup {
+    xeact_component_with(name, data, Hydrate::Load)
+}
+
+/// A placeholder for a Xeact component. The shared runtime (see
+/// [xeact_runtime]) finds these by their data attributes, imports the
+/// component when `hydrate` says to and replaces the placeholder's contents
+/// with it.
+pub fn xeact_component_with(name: &str, data: serde_json::Value, hydrate: Hydrate) -> Markup {
+    xeact_placeholder(
+        name,
+        data,
+        hydrate,
+        html! {
+            noscript {
+                div.warning {
+                    (conv("Aoi".into(), "coffee".into(), PreEscaped("This dynamic component requires JavaScript to function, sorry!".to_string())))
+                }
+            }
+        },
+    )
+}
+
+/// A placeholder for a Xeact component that shows `fallback` until the
+/// component is mounted, such as a static table for an interactive chart.
+/// People without JavaScript and feed readers only ever see the fallback, so
+/// it should make sense on its own.
+pub fn xeact_component_with_fallback(
+    name: &str,
+    data: serde_json::Value,
+    fallback: Markup,
+) -> Markup {
+    xeact_placeholder(name, data, Hydrate::Load, fallback)
+}
+
+/// The runtime replaces everything inside the placeholder when it mounts the
+/// component.
+fn xeact_placeholder(
+    name: &str,
+    data: serde_json::Value,
+    hydrate: Hydrate,
+    contents: Markup,
+) -> Markup {
+    html! {
+        div data-xeact-component=(name) data-xeact-props=(data.to_string()) data-xeact-hydrate=(hydrate.as_str()) {
+            (contents)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::fs;
+
+    /// Strings that have caused trouble for HTML templates in the past, mixed
+    /// with arbitrary Unicode.
+    fn adversarial() -> impl Strategy<Value = String> {
+        let payloads = prop_oneof![
+            Just("<script>alert(1)</script>".to_string()),
+            Just("\"><img src=x onerror=alert(1)>".to_string()),
+            Just("' onmouseover='alert(1)".to_string()),
+            Just("javascript:alert(1)".to_string()),
+            Just("</div></div><div>".to_string()),
+            Just("&lt;&amp;&#x3C;".to_string()),
+            Just("\u{202e}\u{200b}\u{feff}".to_string()),
+            Just("👩🏽‍💻🏳️‍⚧️".to_string()),
+            "\\PC*",
+        ];
+        prop::collection::vec(payloads, 0..8).prop_map(|v| v.concat())
+    }
+
+    /// The contents of every tag in some HTML, such as `a href="/"`.
+    fn tags(html: &str) -> Vec<&str> {
+        html.split('<')
+            .skip(1)
+            .map(|s| s.split('>').next().unwrap_or(""))
+            .collect()
+    }
+
+    /// Removes quoted attribute values from the contents of a tag.
+    fn without_values(tag: &str) -> String {
+        tag.split('"').step_by(2).collect()
+    }
+
+    fn first_json<T: serde::de::DeserializeOwned>(dir: &str) -> T {
+        let dir = format!("{}/../../data/{dir}", env!("CARGO_MANIFEST_DIR"));
+        let file = fs::read_dir(dir).unwrap().next().unwrap().unwrap();
+        serde_json::from_str(&fs::read_to_string(file.path()).unwrap()).unwrap()
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(128))]
+
+        #[test]
+        fn conv_escapes_everything(name in adversarial(), mood in adversarial(), body in adversarial()) {
+            let baseline = conv("Mara".into(), "hacker".into(), html! {"hi"}).0;
+            let result = conv(name, mood, html! {(body)}).0;
+
+            // None of the inputs can add tags or close attribute values.
+            prop_assert_eq!(result.matches('<').count(), baseline.matches('<').count());
+            prop_assert_eq!(result.matches('"').count(), baseline.matches('"').count());
+        }
+
+        #[test]
+        fn breadcrumbs_escape_everything(name in adversarial()) {
+            let crumbs = |name: String| vec![Crumb::new("Blog", "/blog"), Crumb::new(name, "/blog/post")];
+            let baseline = breadcrumbs(crumbs("Post".into())).0;
+            let result = breadcrumbs(crumbs(name)).0;
+
+            prop_assert_eq!(result.matches('<').count(), baseline.matches('<').count());
+            prop_assert_eq!(result.matches("</script").count(), 1);
+        }
+
+        #[test]
+        fn event_card_escapes_everything(text in adversarial()) {
+            let event = |text: String| EventMeta {
+                name: text.clone(),
+                start_date: "2023-10-14".into(),
+                end_date: None,
+                venue: text.clone(),
+                location: Some(text.clone()),
+                talk_title: Some(text),
+                url: Some("https://example.com".into()),
+            };
+            let baseline = event_card(event("Hi".into())).0;
+            let result = event_card(event(text)).0;
+
+            prop_assert_eq!(result.matches('<').count(), baseline.matches('<').count());
+            prop_assert_eq!(result.matches("</script").count(), 1);
+        }
+
+        #[test]
+        fn toot_embed_sanitizes(content in adversarial(), link in adversarial()) {
+            let u: User = first_json("users");
+            let mut t: Toot = first_json("toots");
+            t.content = content;
+            t.url = Some(link);
+
+            let result = toot_embed(u, t).0.to_lowercase();
+            for tag in tags(&result) {
+                let attrs = without_values(tag);
+                prop_assert!(!attrs.starts_with("script"), "script tag: <{}>", tag);
+                prop_assert!(!attrs.contains(" on"), "event handler: <{}>", tag);
+                prop_assert!(!tag.contains("=\"javascript:"), "javascript url: <{}>", tag);
+            }
+        }
+    }
+
+    #[test]
+    fn toot_embed_keeps_links() {
+        let u: User = first_json("users");
+        let mut t: Toot = first_json("toots");
+        t.content = r#"<p>see <a href="https://xeiaso.net" class="u-url"><span class="invisible">https://</span>xeiaso.net</a></p>"#.into();
+
+        let result = toot_embed(u, t).0;
+        assert!(result.contains(r#"<span class="invisible">https://</span>"#));
+    }
+
+    #[test]
+    fn toot_embed_shows_polls() {
+        let u: User = first_json("users");
+        let poll = include_str!("../../xesite_types/src/testdata/post_poll.json");
+        let t: Toot = serde_json::from_str(poll).unwrap();
+
+        let result = toot_embed(u, t).0;
+        assert!(result.contains("Final results, closed M05 13 2023 14:02 (UTC)"));
+        assert!(result.contains(r#"<th scope="row">Tabs</th><td>3</td>"#));
+        assert!(result.contains("63%"));
+        assert!(!result.contains("Vote on"));
+
+        let mut t: Toot = serde_json::from_str(poll).unwrap();
+        t.closed = None;
+        let result = toot_embed(first_json("users"), t).0;
+        assert!(result.contains("Results so far, closes"));
+        assert!(result.contains(
+            r#"<a href="https://pony.social/@cadey/110354553974011651">Vote on pony.social</a>"#
+        ));
+    }
+
+    #[test]
+    fn thread_embed_nests_replies() {
+        // Toots in the thread as (id, replying to, author).
+        let thread = [
+            ("a", None, "cadey"),
+            ("b", Some("a"), "cadey"),
+            ("c", Some("a"), "mara"),
+            ("d", Some("c"), "aoi"),
+            ("e", Some("d"), "mara"),
+            ("f", Some("e"), "aoi"),
+            ("g", Some("f"), "mara"),
+            ("h", Some("gone"), "numa"),
+        ];
+        // They're all from the same time, so they stay in this order.
+        let toots = thread
+            .iter()
+            .map(|&(id, parent, author)| {
+                let mut u: User = first_json("users");
+                u.id = format!("https://pony.social/users/{author}");
+                u.name = author.to_string();
+                let mut t: Toot = first_json("toots");
+                t.id = id.to_string();
+                t.url = None;
+                t.in_reply_to = parent.map(str::to_string);
+                t.attributed_to = u.id.clone();
+                t.summary = None;
+                t.content = format!("<p>toot {id}</p>");
+                (u, t)
+            })
+            .collect();
+
+        let result = thread_embed(toots).0;
+        assert_eq!(result.matches(r#"class="toot-thread-item""#).count(), 8);
+        // b is Cadey replying to themselves.
+        assert_eq!(result.matches("toot-thread-continued").count(), 1);
+        // c has four replies under it, which is more than THREAD_COLLAPSE_AFTER.
+        assert_eq!(result.matches("<summary>").count(), 1);
+        assert!(result.contains("<summary>4 more replies</summary>"));
+        assert!(result.find("toot a").unwrap() < result.find("toot b").unwrap());
+        assert!(result.contains("toot h"));
+    }
+
+    #[test]
+    fn breadcrumbs_list_every_crumb() {
+        let result = breadcrumbs(vec![
+            Crumb::new("Blog", "/blog"),
+            Crumb::new("Series", "/blog/series/foo"),
+            Crumb::new("Post", "/blog/foo"),
+        ])
+        .0;
+
+        assert!(result.contains(r#"<a href="/blog">Blog</a>"#));
+        assert!(result.contains(r#"<span aria-current="page">Post</span>"#));
+        assert!(result.contains(r#""position":3"#));
+        assert!(result.contains(&format!(r#""item":"{}""#, site().url("/blog/foo"))));
+        assert!(breadcrumbs(vec![]).0.is_empty());
+    }
+
+    #[test]
+    fn page_numbers_skip_the_middle() {
+        assert_eq!(page_numbers(1, 1), vec![Some(1)]);
+        assert_eq!(
+            page_numbers(1, 10),
+            vec![Some(1), Some(2), Some(3), None, Some(10)]
+        );
+        assert_eq!(
+            page_numbers(6, 10),
+            vec![Some(1), None, Some(4), Some(5), Some(6), Some(7), Some(8), None, Some(10)]
+        );
+        assert_eq!(page_numbers(3, 4), (1..=4).map(Some).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn pagination_links_neighbours() {
+        let route = |page| format!("/blog/page/{page}");
+        assert!(pagination(1, 1, route).0.is_empty());
+
+        let first = pagination(1, 3, route).0;
+        assert!(!first.contains(r#"rel="prev""#));
+        assert!(first.contains(r#"<link rel="next" href="/blog/page/2">"#));
+
+        let middle = pagination(2, 3, route).0;
+        assert!(middle.contains(r#"<link rel="prev" href="/blog/page/1">"#));
+        assert!(middle.contains(r#"<span aria-current="page">2</span>"#));
+    }
+
+    #[test]
+    fn conv_thread_groups_turns() {
+        let result = conv_thread(vec![
+            ConvMessage::new("Cadey", "coffee", html! {"one"}),
+            ConvMessage::new("cadey", "enby", html! {"two"}),
+            ConvMessage::new("Mara", "hacker", html! {"three"}),
+            ConvMessage::new("Cadey", "aha", html! {"four"}),
+        ])
+        .0;
+
+        assert!(result.contains(r#"aria-label="Conversation between Cadey and Mara""#));
+        assert_eq!(result.matches(r#"role="listitem""#).count(), 3);
+        assert_eq!(result.matches("<picture>").count(), 3);
+        assert_eq!(result.matches(r#"class="conversation-chat""#).count(), 4);
+        assert_eq!(result.matches("<b>Cadey</b>").count(), 2);
+        assert!(result.contains("stickers/cadey/coffee.png"));
+        assert!(!result.contains("stickers/cadey/enby.png"));
+    }
+
+    #[test]
+    fn chat_log_collapses_runs() {
+        let result = chat_log(vec![
+            ChatMessage::new("Aoi", "wut", html! {"is prod down?"}).at("14:02"),
+            ChatMessage::new("aoi", "rage", html! {"it's down"}).at("14:03"),
+            ChatMessage::new("Cadey", "coffee", html! {"looking"}),
+        ])
+        .0;
+
+        assert!(result.contains(r#"aria-label="Chat between Aoi and Cadey""#));
+        assert_eq!(result.matches(r#"role="listitem""#).count(), 2);
+        assert_eq!(result.matches("<picture>").count(), 2);
+        assert_eq!(result.matches("<b>Aoi</b>").count(), 1);
+        assert_eq!(result.matches(r#"class="chat-log-time""#).count(), 2);
+        assert!(!result.contains("stickers/aoi/rage.png"));
+
+        let message = ChatMessage::new("Aoi", "wut", html! {"is prod down?"}).at("14:02");
+        let result =
+            context::with_target(context::RenderTarget::Feed, || chat_log(vec![message]).0);
+        assert_eq!(
+            result,
+            r#"<div class="conversation-log">[14:02] &lt;<b>Aoi</b>&gt; is prod down?</div>"#
+        );
+    }
+
+    #[test]
+    fn hovercards_for_speakers() {
+        context::install_hovercards(vec![xesite_types::character::Hovercard {
+            name: "Mara".into(),
+            sticker_name: "mara".into(),
+            default_pose: "hacker".into(),
+            pronouns: "she/her".into(),
+            bio: "<p>A shark.</p>".into(),
+        }]);
+
+        let body = conv("Mara".into(), "hmm".into(), html! {"hi"}).0;
+        assert!(body.contains(r#"data-character="mara""#));
+        let cards = hovercards(&body).0;
+        assert!(cards.contains(r#"<details class="hovercard" id="hovercard-mara">"#));
+        assert!(cards.contains("Mara (she/her)"));
+        assert!(cards.contains("<p>A shark.</p>"));
+        assert!(cards.contains("stickers/mara/hacker.png"));
+
+        let body = conv("Cadey".into(), "coffee".into(), html! {"hi"}).0;
+        assert!(hovercards(&body).0.is_empty());
+    }
+
+    #[test]
+    fn conv_logs() {
+        use context::{with_target, RenderTarget};
+
+        let result = with_target(RenderTarget::Feed, || {
+            conv("Mara".into(), "hacker".into(), html! {"hi <3"}).0
+        });
+        assert_eq!(
+            result,
+            r#"<div class="conversation-log">&lt;<b>Mara</b>&gt; hi &lt;3</div>"#
+        );
+
+        let result = with_target(RenderTarget::PlainText, || {
+            conv_thread(vec![
+                ConvMessage::new("Cadey", "coffee", html! {"one"}),
+                ConvMessage::new("Cadey", "enby", html! {"two"}),
+                ConvMessage::new("Mara", "hacker", html! {"three"}),
+            ])
+            .0
+        });
+        assert!(!result.contains("<picture>"));
+        assert_eq!(result.matches("&lt;Cadey&gt; ").count(), 2);
+        assert!(result.contains("&lt;Mara&gt; three"));
+    }
+
+    #[test]
+    fn responsive_images() {
+        let result = picture_with("blog/foo".into(), &[480, 960]).0;
+        assert!(result.contains(r#"srcset="https://cdn.xeiaso.net/file/christine-static/blog/foo-480.avif 480w, https://cdn.xeiaso.net/file/christine-static/blog/foo-960.avif 960w""#));
+        assert_eq!(
+            result.matches(&format!(r#"sizes="{IMAGE_SIZES}""#)).count(),
+            2
+        );
+
+        let result = picture_with("blog/foo".into(), &[]).0;
+        assert!(result
+            .contains(r#"srcset="https://cdn.xeiaso.net/file/christine-static/blog/foo.webp""#));
+        assert!(!result.contains("sizes="));
+
+        let (result, preloads) =
+            context::collect(|| hero_with("foo".into(), None, None, &[1440]).0);
+        assert!(result.contains("hero/foo-1440.webp 1440w"));
+        assert!(!result.contains("og:image"));
+        assert_eq!(
+            preloads[0].image_srcset,
+            Some((
+                "https://cdn.xeiaso.net/file/christine-static/hero/foo-1440.avif 1440w".into(),
+                IMAGE_SIZES.into()
+            ))
+        );
+    }
+
+    #[test]
+    fn open_graph() {
+        use og::{render_head_meta, OpenGraph};
+
+        let page = render_head_meta(&OpenGraph::new("About")).0;
+        assert!(page.contains(r#"<meta property="og:type" content="website">"#));
+        assert!(page.contains(r#"<meta name="twitter:card" content="summary">"#));
+        assert!(page.contains(r#"<meta name="description" content="About - Xe's Blog">"#));
+        assert!(!page.contains("og:image"));
+        assert!(!page.contains("fediverse:creator"));
+
+        let og = OpenGraph::new("A <post>")
+            .image("https://xeiaso.net/og/blog/foo.png")
+            .published("2023-07-10T00:00:00+00:00")
+            .author("Xe Iaso")
+            .creator("@cadey@pony.social")
+            .reading_time(1);
+        context::open_graph(og.clone());
+        assert_eq!(context::take_open_graph(), Some(og.clone()));
+        assert_eq!(context::take_open_graph(), None);
+
+        let post = render_head_meta(&og).0;
+        assert!(post.contains(r#"<meta property="og:type" content="article">"#));
+        assert!(post.contains(r#"<meta property="og:title" content="A &lt;post&gt;">"#));
+        assert!(post.contains(r#"<meta name="twitter:card" content="summary_large_image">"#));
+        assert!(post.contains(
+            r#"<meta property="article:published_time" content="2023-07-10T00:00:00+00:00">"#
+        ));
+        assert!(post.contains(r#"<meta name="twitter:data1" content="1 minute">"#));
+        assert!(post.contains(r#"<meta name="fediverse:creator" content="@cadey@pony.social">"#));
+
+        let video = render_head_meta(&OpenGraph::new("Talk").video(xesite_types::Media {
+            url: "https://cdn.xeiaso.net/talk.mp4".into(),
+            media_type: "video/mp4".into(),
+            width: None,
+            height: None,
+        }))
+        .0;
+        assert!(video.contains(r#"<meta name="twitter:card" content="player">"#));
+        assert!(video.contains(r#"<meta name="twitter:player:width" content="1280">"#));
+    }
+
+    #[test]
+    fn series_navs() {
+        use xesite_types::SeriesPart;
+
+        let part = |n| SeriesPart {
+            title: format!("Part {n}"),
+            url: format!("/blog/part-{n}"),
+        };
+        let mut series = Series {
+            title: "howto".into(),
+            parts: (1..=5).map(part).collect(),
+            current: 2,
+        };
+        let nav = series_nav(&series).0;
+        assert!(nav.contains(r#"Part 3 of 5 in <a href="/blog/series/howto">howto</a>"#));
+        assert!(nav.contains(r#"<a rel="prev" href="/blog/part-2">← Part 2</a>"#));
+        assert!(nav.contains(r#"<a rel="next" href="/blog/part-4">Part 4 →</a>"#));
+        assert!(nav.contains(r#"<span aria-current="page">Part 3</span>"#));
+        assert_eq!(nav.matches("<li>").count(), 5);
+
+        series.current = 0;
+        let nav = series_nav(&series).0;
+        assert!(!nav.contains(r#"rel="prev""#));
+        assert!(nav.contains(r#"rel="next""#));
+        series.current = 4;
+        assert!(!series_nav(&series).0.contains(r#"rel="next""#));
+    }
+
+    #[test]
+    fn essential_slides() {
+        let warning = talk_warning().0;
+        assert!(warning.contains(r#"data-xeact-component="NoFunAllowed""#));
+        assert!(warning.contains(r#"<a href="?slides=essential">"#));
+
+        let hint = essential_slides_hint().0;
+        assert!(hint.starts_with("<script>try{"));
+        assert!(hint.contains(r#"localStorage.getItem("xesite-slides")==="essential""#));
+    }
+
+    #[test]
+    fn stats() {
+        use stats::{minutes, reading_time, word_count};
+
+        assert_eq!(word_count(""), 0);
+        assert_eq!(
+            word_count("# Hello\n\n- one *two* [three](https://example.com/a-b-c)\n"),
+            4
+        );
+        let conv =
+            r#"<xeblog-conv name="Cadey" mood="coffee">Hi there &amp; welcome</xeblog-conv>"#;
+        assert_eq!(word_count(conv), 3);
+        assert_eq!(word_count("<p>Same</p> <p>words</p>"), 2);
+        assert_eq!(word_count("```rust\nlet x = 1;\n```"), 3);
+
+        assert_eq!(minutes(0), 0);
+        assert_eq!(minutes(1), 1);
+        assert_eq!(minutes(200), 1);
+        assert_eq!(minutes(201), 2);
+        assert_eq!(
+            reading_time(2400).0,
+            r#"<span class="reading-time">12 minute read · 2,400 words</span>"#
+        );
+        assert!(reading_time(1).0.contains("1 minute read · 1 word<"));
+        assert!(reading_time(1_234_567).0.contains("1,234,567 words"));
+    }
+
+    #[test]
+    fn subscriber_badges() {
+        let badge = subscriber_badge(1, None, 30).0;
+        assert!(badge.contains("1 reader<"));
+        assert!(!badge.contains("<small>"));
+        let badge = |change| subscriber_badge(120, Some(change), 30).0;
+        assert!(badge(0).contains("120 readers<"));
+        assert!(badge(12).contains("<small>+12 in 30 days</small>"));
+        assert!(badge(-3).contains("-3 in 30 days"));
+    }
+
+    #[test]
+    fn chat_themes() {
+        use xesite_types::character::{BorderStyle, ChatTheme};
+        context::install_chat_themes(
+            [(
+                "numa".to_string(),
+                ChatTheme {
+                    accent: "#b8bb26".into(),
+                    accent_light: Some("#79740e".into()),
+                    border: BorderStyle::Dashed,
+                },
+            )]
+            .into(),
+        );
+        let style = r#"style="--chat-accent: #b8bb26; --chat-accent-light: #79740e; --chat-border: dashed""#;
+
+        let result = conv("Numa".into(), "delet".into(), html! {"hi"}).0;
+        assert!(result.contains(r#"class="conversation-chat conversation-accent""#));
+        assert!(result.contains(style));
+
+        let result = conv_thread(vec![
+            ConvMessage::new("Numa", "delet", html! {"one"}),
+            ConvMessage::new("Numa", "delet", html! {"two"}),
+            ConvMessage::new("Aoi", "wut", html! {"three"}),
+        ])
+        .0;
+        assert_eq!(result.matches(style).count(), 2);
+        assert_eq!(result.matches(r#"class="conversation-chat""#).count(), 1);
+    }
+
+    #[test]
+    fn xeact_fallback() {
+        let result = xeact_component_with_fallback(
+            "Chart",
+            serde_json::json!({"year": 2023}),
+            html! { table { tr { td {"42"} } } },
+        )
+        .0;
+        assert!(result.starts_with(r#"<div data-xeact-component="Chart""#));
+        assert!(result.contains("<td>42</td>"));
+        assert!(!result.contains("<noscript>"));
+
+        let result = xeact_component("Chart", serde_json::Value::Null).0;
+        assert!(result.contains("requires JavaScript"));
+        // Pages with components have to render the same every time for
+        // golden files and ETags to work.
+        assert_eq!(result, xeact_component("Chart", serde_json::Value::Null).0);
+    }
+
+    #[test]
+    fn code_blocks() {
+        let result = code_block("rs", "fn main() {\n    println!(\"<hi>\");\n}\n").0;
+        assert!(result.contains(r#"<span class="code-block-lang">Rust</span>"#));
+        assert_eq!(result.matches(r#"class="code-block-line""#).count(), 3);
+        assert!(result.contains("<span style=\"color:"));
+        assert!(result.contains("&lt;hi&gt;"));
+        assert!(result.contains("code-blocks.js"));
+
+        let result = code_block("nonsense", "<b>").0;
+        assert!(result.contains("Plain Text"));
+        assert!(result.contains("&lt;b&gt;"));
+
+        let result = context::with_target(context::RenderTarget::Feed, || {
+            code_block("rust", "let x = 1;").0
+        });
+        assert!(!result.contains("<button"));
+        assert!(!result.contains("<script"));
+    }
+
+    #[test]
+    fn sponsors() {
+        let sponsor = |name: &str| Sponsor {
+            name: name.into(),
+            url: format!("https://{name}.example/"),
+            logo: format!("/static/img/sponsors/{name}.svg"),
+            blurb: "They make <things>.".into(),
+        };
+        let sponsors: Vec<_> = ["a", "b", "c"].into_iter().map(sponsor).collect();
+
+        assert_eq!(sponsor_footer("foo", vec![]).0, "");
+        assert_eq!(
+            sponsor_footer("foo", sponsors.clone()).0,
+            sponsor_footer("foo", sponsors.clone()).0
+        );
+        let result = sponsor_footer("foo", sponsors.clone()).0;
+        assert!(result.starts_with(r#"<aside class="sponsor-footer" aria-label="Sponsor">"#));
+        assert!(result.contains(r#"rel="sponsored noopener""#));
+        assert!(result.contains("They make &lt;things&gt;."));
+
+        // Every sponsor gets some posts.
+        let mut seen = std::collections::HashSet::new();
+        for slug in (0..30).map(|n| format!("post-{n}")) {
+            seen.insert(sponsor_for(&slug, &sponsors).unwrap().name.clone());
+        }
+        assert_eq!(seen.len(), 3);
+    }
+
+    #[test]
+    fn unknown_moods() {
+        let mut stickers = xesite_types::character::StickerRegistry::default();
+        stickers.add(
+            "registrytest".into(),
+            "happy".into(),
+            vec!["happy".into(), "sad".into()],
+        );
+        context::install_stickers(stickers);
+
+        let result = conv("RegistryTest".into(), "sad".into(), html! {"hi"}).0;
+        assert!(result.contains("stickers/registrytest/sad.png"));
+        let result = conv("RegistryTest".into(), "sda".into(), html! {"hi"}).0;
+        assert!(result.contains("stickers/registrytest/happy.png"));
+        assert!(!result.contains("sda"));
+        assert!(sticker("RegistryTest".into(), "nope".into())
+            .0
+            .contains("stickers/registrytest/happy.avif"));
+        // Characters that aren't in the manifest are left alone.
+        assert!(sticker("Scoots".into(), "anything".into())
+            .0
+            .contains("stickers/scoots/anything.png"));
+    }
+
+    #[test]
+    fn characters() {
+        let mara = Character {
+            name: "Mara".into(),
+            sticker_name: "mara".into(),
+            default_pose: "hacker".into(),
+            pronouns: "she/her".into(),
+            description: "<p>A shark.</p>".into(),
+            stickers: vec!["hacker".into(), "hmm".into()],
+            artist: Some(xesite_types::character::ArtistCredit {
+                name: "Someone".into(),
+                url: Some("javascript:alert(1)".into()),
+            }),
+            pack: None,
+        };
+        let result = character_card(&mara).0;
+        assert!(result.starts_with(r#"<section class="character-card" id="mara"><h3>Mara</h3>"#));
+        assert!(result.contains("<p>A shark.</p>"));
+        assert!(result.contains(r##"Stickers by <a href="#">Someone</a>."##));
+        assert!(result.contains("All stickers (2)"));
+        assert!(result.contains("stickers/mara/hmm.png"));
+        assert!(!result.contains("Download"));
+
+        let result = characters_page(&[
+            mara.clone(),
+            Character {
+                name: "Cadey".into(),
+                sticker_name: "cadey".into(),
+                artist: None,
+                pack: Some(xesite_types::character::StickerPack {
+                    path: "/stickers/cadey.zip".into(),
+                    license: "CC BY-NC-SA 4.0".into(),
+                    license_url: None,
+                }),
+                ..mara
+            },
+        ])
+        .0;
+        assert!(result.contains(r##"<li><a href="#cadey">Cadey</a></li>"##));
+        assert!(result.contains(r#"<a href="/stickers/cadey.zip" download>"#));
+        assert_eq!(result.matches("character-card").count(), 2);
+    }
+
+    #[test]
+    fn embeds() {
+        let video = Embed {
+            url: "https://www.youtube.com/watch?v=dQw4w9WgXcQ".into(),
+            kind: EmbedKind::Video,
+            provider: "YouTube".into(),
+            title: "A video".into(),
+            description: None,
+            author: Some("Xe".into()),
+            image: Some("/static/img/embeds/abc.jpg".into()),
+            player: Some("https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ".into()),
+        };
+        let result = embed(video.clone()).0;
+        assert!(result.starts_with(
+            r#"<figure class="embed embed-video" data-player="https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ""#
+        ));
+        assert!(result.contains(r#"<img src="/static/img/embeds/abc.jpg""#));
+        assert!(result.contains("/static/js/embed.js"));
+        assert!(!result.contains("<iframe"));
+
+        let result = embed(Embed {
+            player: Some("https://evil.example/player".into()),
+            ..video.clone()
+        })
+        .0;
+        assert!(!result.contains("data-player"));
+        assert!(!result.contains("<script"));
+
+        let result = embed(Embed {
+            url: "javascript:alert(1)".into(),
+            kind: EmbedKind::Link,
+            description: Some("Some code".into()),
+            ..video
+        })
+        .0;
+        assert!(result.starts_with(r#"<aside class="embed embed-card""#));
+        assert!(result.contains(r##"<a href="#" rel="noopener">"##));
+        assert!(result.contains("Some code"));
+    }
+
+    #[test]
+    fn callouts() {
+        assert_eq!(
+            callout(CalloutKind::Warning, html! { p { "Back up first." } }).0,
+            concat!(
+                r#"<aside class="callout callout-warning" role="alert" aria-label="Warning">"#,
+                r#"<p class="callout-label" aria-hidden="true">Warning</p>"#,
+                r#"<div class="callout-body"><p>Back up first.</p></div></aside>"#,
+            )
+        );
+        assert!(callout(CalloutKind::Tip, html! {"hi"})
+            .0
+            .contains(r#"class="callout callout-tip" role="note""#));
+
+        let result = callout(
+            CalloutKind::XeSays {
+                mood: "coffee".into(),
+            },
+            html! {"hi"},
+        )
+        .0;
+        assert!(
+            result.starts_with(r#"<aside class="callout callout-xe-says" aria-label="Xe says">"#)
+        );
+        assert!(result.contains("stickers/cadey/coffee.png"));
+        assert!(!result.contains("callout-label"));
+    }
+
+    #[test]
+    fn slide_decks() {
+        let slides = vec![
+            Slide {
+                name: "my-website/001".into(),
+                essential: true,
+                notes: None,
+            },
+            Slide {
+                name: "my-website/002".into(),
+                essential: false,
+                notes: Some(html! { "Nobody laughed at this one." }),
+            },
+        ];
+        let result = slide_deck(slides.clone()).0;
+        assert!(result.contains(r#"id="slide-1"><div class="hero xeblog-slides-essential">"#));
+        assert!(result.contains(
+            r#"id="slide-2"><div class="slide-with-notes"><div class="hero xeblog-slides-fluff">"#
+        ));
+        assert!(result.contains("1 / 2"));
+        assert!(result.contains("slide-deck.js"));
+
+        let result = context::with_target(context::RenderTarget::Feed, || slide_deck(slides).0);
+        assert!(result.contains(r#"id="slide-2""#));
+        assert!(!result.contains("<button"));
+        assert!(!result.contains("<script"));
+    }
+
+    #[test]
+    fn native_videos() {
+        let result = video_native(
+            "blog/hls-post".into(),
+            Some("blog/hls-post/poster.jpg".into()),
+            vec![
+                Track {
+                    path: "blog/hls-post/en.vtt".into(),
+                    lang: "en".into(),
+                    label: "English".into(),
+                },
+                Track {
+                    path: "blog/hls-post/fr.vtt".into(),
+                    lang: "fr".into(),
+                    label: "Français".into(),
+                },
+            ],
+        )
+        .0;
+        assert!(result.contains(r#"/blog/hls-post/poster.jpg""#));
+        assert!(result.contains(r#"crossorigin="anonymous""#));
+        assert!(
+            result.contains(r#"blog/hls-post/index.m3u8" type="application/vnd.apple.mpegurl">"#)
+        );
+        assert!(result.contains(r#"srclang="en" label="English" default>"#));
+        assert!(result.contains(r#"srclang="fr" label="Français">"#));
+        assert!(result.contains("video.js"));
+
+        let result = context::with_target(context::RenderTarget::Feed, || {
+            video_native("blog/hls-post".into(), None, vec![]).0
+        });
+        assert!(!result.contains("poster="));
+        assert!(!result.contains("crossorigin"));
+        assert!(!result.contains("<script"));
+    }
+
+    #[test]
+    fn audio_players() {
+        use std::time::Duration;
+        assert_eq!(clock(Duration::from_secs(65)), "1:05");
+        assert_eq!(clock(Duration::from_secs(3723)), "1:02:03");
+
+        let result = audio_player_with(
+            "podcast/001.mp3".into(),
+            "Episode 1".into(),
+            Duration::from_secs(1830),
+            &[
+                Chapter {
+                    start: Duration::ZERO,
+                    title: "Intro".into(),
+                },
+                Chapter {
+                    start: Duration::from_secs(95),
+                    title: "Nix".into(),
+                },
+            ],
+            Some(html! { p { "Hi, welcome to the show." } }),
+        )
+        .0;
+        assert!(result.contains(r#"<time datetime="PT1830S">30:30</time>"#));
+        assert!(result.contains(r#"aria-label="Chapters of Episode 1""#));
+        assert!(result.contains(r#"podcast/001.mp3#t=95" data-start="95">"#));
+        assert!(result.contains("<summary>Transcript</summary>"));
+        assert!(result.contains("audio.js"));
+
+        let result = audio_player(
+            "podcast/002.mp3".into(),
+            "Episode 2".into(),
+            Duration::from_secs(60),
+            None,
+        )
+        .0;
+        assert!(!result.contains("audio-chapters"));
+        assert!(!result.contains("<details"));
+        assert!(!result.contains("<script"));
+    }
+
+    #[test]
+    fn figures() {
+        let (before, figure, after) = context::with_figures(|| {
+            (
+                figref("graph").0,
+                figure(
+                    "graph",
+                    html! { img src="/graph.png"; },
+                    html! { "Requests <per> day" },
+                )
+                .0,
+                figref("other figure").0,
+            )
+        });
+        assert_eq!(
+            before,
+            r##"<a class="figref" href="#figure-graph">Figure 1</a>"##
+        );
+        assert!(figure.starts_with(r#"<figure class="figure" id="figure-graph"><img"#));
+        assert!(figure.contains("<b>Figure 1: </b>Requests &lt;per&gt; day"));
+        assert!(after.contains(r##"href="#figure-other-figure">Figure 2<"##));
+    }
+
+    #[test]
+    fn footnotes_are_gathered() {
+        let (text, notes) = context::collect_footnotes(|| {
+            html! {
+                "Cats" (footnote("cats", html! { "They're " em { "liquid" } "." }))
+                " again" (footnote_ref("cats"))
+            }
+            .0
+        });
+        assert!(text.contains(r##"<a id="footnote-cats-ref" href="#footnote-cats""##));
+        assert!(text.contains(r##"<a id="footnote-cats-ref-2" href="#footnote-cats""##));
+        let list = footnotes(&notes).0;
+        assert!(list.contains(r#"<li id="footnote-cats" value="1">They're <em>liquid</em>."#));
+        assert!(list.contains(r##"href="#footnote-cats-ref""##));
+        assert_eq!(footnotes(&[]).0, "");
+
+        let inline = footnote("cats", html! { "liquid" }).0;
+        assert_eq!(inline, r#"<span class="footnote-inline"> (liquid)</span>"#);
+    }
+
+    #[test]
+    fn huge_inputs() {
+        let body = "<".repeat(1 << 20);
+        let result = conv("Mara".into(), "hacker".into(), html! {(body)}).0;
+        assert_eq!(result.matches("&lt;").count(), 1 << 20);
     }
 }
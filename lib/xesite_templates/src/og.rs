@@ -0,0 +1,160 @@
+//! The OpenGraph, Twitter Card and fediverse tags that link previews are
+//! made from. Pages build an [OpenGraph] while their content is rendered
+//! and hand it to [crate::context::open_graph], then the layout puts
+//! [render_head_meta] in the `<head>`.
+
+use crate::context::site;
+use maud::{html, Markup};
+use xesite_types::Media;
+
+/// Everything a link preview of a page can show.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OpenGraph {
+    title: String,
+    description: Option<String>,
+    image: Option<String>,
+    url: Option<String>,
+    /// When it was published, as RFC 3339. Pages with one are articles.
+    published: Option<String>,
+    author: Option<String>,
+    /// The fediverse account to credit when the page is shared, like
+    /// `@cadey@pony.social`.
+    creator: Option<String>,
+    reading_time: Option<u64>,
+    video: Option<Media>,
+    audio: Option<Media>,
+}
+
+impl OpenGraph {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// The absolute URL of the preview image.
+    pub fn image(mut self, image: impl Into<String>) -> Self {
+        self.image = Some(image.into());
+        self
+    }
+
+    /// The page's canonical URL.
+    pub fn url(mut self, url: impl Into<String>) -> Self {
+        self.url = Some(url.into());
+        self
+    }
+
+    pub fn published(mut self, published: impl Into<String>) -> Self {
+        self.published = Some(published.into());
+        self
+    }
+
+    pub fn author(mut self, author: impl Into<String>) -> Self {
+        self.author = Some(author.into());
+        self
+    }
+
+    pub fn creator(mut self, creator: impl Into<String>) -> Self {
+        self.creator = Some(creator.into());
+        self
+    }
+
+    /// How long the page takes to read, in minutes.
+    pub fn reading_time(mut self, minutes: u64) -> Self {
+        self.reading_time = Some(minutes);
+        self
+    }
+
+    pub fn video(mut self, video: Media) -> Self {
+        self.video = Some(video);
+        self
+    }
+
+    pub fn audio(mut self, audio: Media) -> Self {
+        self.audio = Some(audio);
+        self
+    }
+}
+
+/// The tags for `og`, for the `<head>`.
+pub fn render_head_meta(og: &OpenGraph) -> Markup {
+    let description = og
+        .description
+        .clone()
+        .unwrap_or_else(|| format!("{} - {}", og.title, site().feed_title));
+    let player = og.video.as_ref().or(og.audio.as_ref());
+    let card = match (player, &og.image) {
+        (Some(_), _) => "player",
+        (None, Some(_)) => "summary_large_image",
+        (None, None) => "summary",
+    };
+
+    html! {
+        meta name="description" content=(description);
+        @if let Some(author) = &og.author {
+            meta name="author" content=(author);
+        }
+
+        meta property="og:type" content=(if og.published.is_some() { "article" } else { "website" });
+        meta property="og:title" content=(og.title);
+        meta property="og:description" content=(description);
+        meta property="og:site_name" content=(site().feed_title);
+        @if let Some(url) = &og.url {
+            meta property="og:url" content=(url);
+        }
+        @if let Some(image) = &og.image {
+            meta property="og:image" content=(image);
+        }
+        @if let Some(published) = &og.published {
+            meta property="article:published_time" content=(published);
+            @if let Some(author) = &og.author {
+                meta property="article:author" content=(author);
+            }
+        }
+        @if let Some(video) = &og.video {
+            meta property="og:video" content=(video.url);
+            meta property="og:video:secure_url" content=(video.url);
+            meta property="og:video:type" content=(video.media_type);
+            @if let Some(width) = video.width {
+                meta property="og:video:width" content=(width);
+            }
+            @if let Some(height) = video.height {
+                meta property="og:video:height" content=(height);
+            }
+        }
+        @if let Some(audio) = &og.audio {
+            meta property="og:audio" content=(audio.url);
+            meta property="og:audio:secure_url" content=(audio.url);
+            meta property="og:audio:type" content=(audio.media_type);
+        }
+
+        meta name="twitter:card" content=(card);
+        meta name="twitter:site" content="@theprincessxena";
+        meta name="twitter:title" content=(og.title);
+        meta name="twitter:description" content=(description);
+        @if let Some(image) = &og.image {
+            meta name="twitter:image" content=(image);
+        }
+        @if let Some(player) = player {
+            meta name="twitter:player" content=(player.url);
+            meta name="twitter:player:width" content=(player.width.unwrap_or(1280));
+            meta name="twitter:player:height" content=(player.height.unwrap_or(720));
+            meta name="twitter:player:stream" content=(player.url);
+            meta name="twitter:player:stream:content_type" content=(player.media_type);
+        }
+        @if let Some(minutes) = og.reading_time {
+            meta name="twitter:label1" content="Reading time";
+            meta name="twitter:data1" content=(format!("{minutes} {}", if minutes == 1 { "minute" } else { "minutes" }));
+        }
+
+        @if let Some(creator) = &og.creator {
+            meta name="fediverse:creator" content=(creator);
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! Template overrides for iterating on designs without recompiling.
+//!
+//! When the site is running in the dev environment and
+//! `site.templateOverrides` points at a folder, templates that support it
+//! check for `<folder>/<name>.html` every time they render. If the file
+//! exists, its contents are used instead of the compiled template, with every
+//! `{{variable}}` replaced by that variable's (already escaped) HTML.
+
+use crate::context::site;
+use maud::{html, Markup, PreEscaped};
+use std::{cell::RefCell, fs, io, path::PathBuf};
+use xesite_types::site::Environment;
+
+thread_local! {
+    static FOLDER: RefCell<Option<PathBuf>> = RefCell::new(None);
+}
+
+/// Runs `f` with the overrides in `dir`, whatever the environment and
+/// `site.templateOverrides` say. Useful for previewing a design against real
+/// posts.
+pub fn with_folder<T>(dir: impl Into<PathBuf>, f: impl FnOnce() -> T) -> T {
+    let outer = FOLDER.with(|folder| folder.replace(Some(dir.into())));
+    let result = f();
+    FOLDER.with(|folder| *folder.borrow_mut() = outer);
+    result
+}
+
+/// The folder overrides come from, if they're enabled.
+fn folder() -> Option<PathBuf> {
+    if let Some(dir) = FOLDER.with(|folder| folder.borrow().clone()) {
+        return Some(dir);
+    }
+    let site = site();
+    if site.environment != Environment::Dev {
+        return None;
+    }
+    site.template_overrides.as_ref().map(PathBuf::from)
+}
+
+/// Replaces every `{{name}}` in `template` with the matching variable.
+/// Unknown variables are left alone so typos are easy to spot.
+pub fn substitute(template: &str, vars: &[(&str, &Markup)]) -> String {
+    let mut result = template.to_string();
+    for (name, value) in vars {
+        result = result.replace(&format!("{{{{{name}}}}}"), &value.0);
+    }
+    result
+}
+
+/// Looks up the override for template `name`. Returns None when overrides
+/// are disabled or the template hasn't been overridden.
+pub fn lookup(name: &str, vars: &[(&str, &Markup)]) -> Option<Markup> {
+    let path = folder()?.join(format!("{name}.html"));
+
+    match fs::read_to_string(&path) {
+        Ok(template) => Some(PreEscaped(substitute(&template, vars))),
+        Err(why) if why.kind() == io::ErrorKind::NotFound => None,
+        Err(why) => Some(html! {
+            div.warning {
+                "Can't read template override "
+                code {(path.display().to_string())}
+                ": "
+                (why.to_string())
+            }
+        }),
+    }
+}
@@ -0,0 +1,88 @@
+use maud::{html, Markup};
+use xesite_types::route::Route;
+
+const WIDTH: f64 = 640.0;
+const MAP_HEIGHT: f64 = 400.0;
+const PROFILE_HEIGHT: f64 = 120.0;
+const PADDING: f64 = 16.0;
+
+fn map_points(route: &Route) -> String {
+    let (min_lat, min_lon, max_lat, max_lon) = match route.bounds() {
+        Some(bounds) => bounds,
+        None => return String::new(),
+    };
+
+    // Equirectangular projection is good enough at the scale of a single run.
+    let lon_scale = ((min_lat + max_lat) / 2.0).to_radians().cos();
+    let span_x = ((max_lon - min_lon) * lon_scale).max(f64::EPSILON);
+    let span_y = (max_lat - min_lat).max(f64::EPSILON);
+    let scale = ((WIDTH - 2.0 * PADDING) / span_x).min((MAP_HEIGHT - 2.0 * PADDING) / span_y);
+
+    let offset_x = (WIDTH - span_x * scale) / 2.0;
+    let offset_y = (MAP_HEIGHT - span_y * scale) / 2.0;
+
+    route
+        .points
+        .iter()
+        .map(|p| {
+            let x = offset_x + (p.lon - min_lon) * lon_scale * scale;
+            let y = offset_y + (max_lat - p.lat) * scale;
+            format!("{x:.1},{y:.1}")
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn profile_points(elevations: &[f64], min: f64, max: f64) -> String {
+    let span = (max - min).max(1.0);
+    let step = (WIDTH - 2.0 * PADDING) / (elevations.len().max(2) - 1) as f64;
+
+    elevations
+        .iter()
+        .enumerate()
+        .map(|(i, ele)| {
+            let x = PADDING + step * i as f64;
+            let y = PADDING + (max - ele) / span * (PROFILE_HEIGHT - 2.0 * PADDING);
+            format!("{x:.1},{y:.1}")
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Renders a route as a static SVG map with distance and elevation stats.
+/// This is done when the post is rendered, so there is no map tile provider
+/// involved.
+pub fn route_map(route: Route) -> Markup {
+    let title = route.name.clone().unwrap_or("Route map".to_string());
+    let distance = route.distance_m() / 1000.0;
+    let elevations = route.elevations();
+
+    html! {
+        figure.route-map {
+            svg xmlns="http://www.w3.org/2000/svg" viewBox={"0 0 " (WIDTH) " " (MAP_HEIGHT)} role="img" aria-label=(title) {
+                title { (title) }
+                polyline points=(map_points(&route)) fill="none" stroke="#fe8019" stroke-width="3" stroke-linejoin="round" stroke-linecap="round" {}
+            }
+
+            @if let Some((min, max)) = route.elevation_range_m() {
+                svg xmlns="http://www.w3.org/2000/svg" viewBox={"0 0 " (WIDTH) " " (PROFILE_HEIGHT)} role="img" aria-label={"Elevation profile between " (format!("{min:.0}")) " and " (format!("{max:.0}")) " meters"} {
+                    polyline points=(profile_points(&elevations, min, max)) fill="none" stroke="#83a598" stroke-width="2" {}
+                }
+            }
+
+            figcaption {
+                (title)
+                dl {
+                    dt { "Distance" }
+                    dd { (format!("{distance:.2}")) " km" }
+                    @if let Some((min, max)) = route.elevation_range_m() {
+                        dt { "Elevation gain" }
+                        dd { (format!("{:.0}", route.elevation_gain_m())) " m" }
+                        dt { "Elevation range" }
+                        dd { (format!("{min:.0}")) " m to " (format!("{max:.0}")) " m" }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! How long posts are. Posts are counted when they're loaded, so pages
+//! only render the numbers.
+
+use maud::{html, Markup};
+
+/// How fast people read the posts here. It's slower than for prose because
+/// most of them are technical.
+pub const WORDS_PER_MINUTE: usize = 200;
+
+/// The words in `source`, which can be markdown or HTML. Tags, link targets,
+/// entities, code fences and anything without a letter or digit in it, like
+/// list markers, don't count.
+pub fn word_count(source: &str) -> usize {
+    let mut text = String::with_capacity(source.len());
+    let mut chars = source.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            // HTML tags.
+            '<' if chars
+                .peek()
+                .map_or(false, |c| c.is_ascii_alphabetic() || *c == '/' || *c == '!') =>
+            {
+                for ch in chars.by_ref() {
+                    if ch == '>' {
+                        break;
+                    }
+                }
+                text.push(' ');
+            }
+            // Where a markdown link goes, after its text.
+            ']' if chars.peek() == Some(&'(') => {
+                for ch in chars.by_ref() {
+                    if ch == ')' {
+                        break;
+                    }
+                }
+                text.push(' ');
+            }
+            ch => text.push(ch),
+        }
+    }
+
+    text.split_whitespace()
+        .filter(|word| !(word.starts_with('&') && word.ends_with(';')))
+        .filter(|word| !word.starts_with("```"))
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .count()
+}
+
+/// How many minutes it takes to read `words`, rounded up. Anything with
+/// words in it takes at least a minute.
+pub fn minutes(words: usize) -> usize {
+    words.div_ceil(WORDS_PER_MINUTE)
+}
+
+/// `n` with commas between the thousands, like `2,400`.
+fn thousands(n: usize) -> String {
+    let digits = n.to_string();
+    let mut result = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i != 0 && (digits.len() - i) % 3 == 0 {
+            result.push(',');
+        }
+        result.push(digit);
+    }
+    result
+}
+
+/// "12 minute read · 2,400 words", for article headers.
+pub fn reading_time(words: usize) -> Markup {
+    html! {
+        span.reading-time {
+            (minutes(words)) " minute read · "
+            (thousands(words)) " " (if words == 1 { "word" } else { "words" })
+        }
+    }
+}
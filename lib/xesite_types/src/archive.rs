@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// What `link_archive` found out about an external link in a post.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ArchivedLink {
+    /// The newest copy of the page on the Internet Archive, if there is one.
+    pub snapshot: Option<String>,
+    /// Whether the page was gone the last time it was checked.
+    pub dead: bool,
+    /// When the link was last checked, in RFC 3339.
+    pub checked: String,
+}
+
+/// Every link `link_archive` knows about, by URL exactly as posts have it.
+pub type LinkArchive = HashMap<String, ArchivedLink>;
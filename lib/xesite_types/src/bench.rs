@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+
+/// Benchmark results for a performance post. Every series has one value per
+/// label, in the same order as the labels.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchData {
+    pub title: String,
+    pub unit: String,
+    #[serde(default)]
+    pub kind: ChartKind,
+    pub labels: Vec<String>,
+    pub series: Vec<BenchSeries>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchSeries {
+    pub name: String,
+    pub values: Vec<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ChartKind {
+    #[default]
+    Bar,
+    Line,
+}
+
+impl BenchData {
+    /// The largest value in any series, used to scale the chart. Values that
+    /// aren't finite are skipped.
+    pub fn max_value(&self) -> f64 {
+        self.series
+            .iter()
+            .flat_map(|s| s.values.iter().copied())
+            .filter(|v| v.is_finite())
+            .fold(0.0, f64::max)
+    }
+
+    /// Checks that every value can be drawn. Charts start at zero, so values
+    /// have to be finite and not negative.
+    pub fn validate(&self) -> Result<(), String> {
+        for series in &self.series {
+            if let Some(value) = series.values.iter().find(|v| !v.is_finite() || **v < 0.0) {
+                return Err(format!(
+                    "{} has the value {value}, but values have to be zero or more",
+                    series.name
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(values: Vec<f64>) -> BenchData {
+        BenchData {
+            title: "Requests".into(),
+            unit: "ms".into(),
+            kind: ChartKind::Bar,
+            labels: values.iter().map(|v| v.to_string()).collect(),
+            series: vec![BenchSeries {
+                name: "axum".into(),
+                values,
+            }],
+        }
+    }
+
+    #[test]
+    fn validates_values() {
+        assert_eq!(data(vec![0.0, 1.5]).validate(), Ok(()));
+        assert!(data(vec![1.0, -2.0]).validate().is_err());
+        assert!(data(vec![f64::NAN]).validate().is_err());
+        assert!(data(vec![f64::INFINITY]).validate().is_err());
+        assert_eq!(data(vec![f64::NAN, 3.0, f64::INFINITY]).max_value(), 3.0);
+    }
+}
@@ -0,0 +1,255 @@
+use crate::site::parse_color;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// What the hovercard on a character's name in a conversation says about
+/// them, from the characters manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hovercard {
+    pub name: String,
+    /// The lowercase name their stickers are under, which is also their
+    /// anchor on the characters page.
+    #[serde(rename = "stickerName")]
+    pub sticker_name: String,
+    #[serde(rename = "defaultPose")]
+    pub default_pose: String,
+    /// Such as `they/them`.
+    pub pronouns: String,
+    /// Rendered HTML.
+    pub bio: String,
+}
+
+/// Everything the characters page shows about a character, from the
+/// characters manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Character {
+    pub name: String,
+    #[serde(rename = "stickerName")]
+    pub sticker_name: String,
+    #[serde(rename = "defaultPose")]
+    pub default_pose: String,
+    /// Such as `they/them`.
+    pub pronouns: String,
+    /// Rendered HTML.
+    pub description: String,
+    /// Every mood there's a sticker for.
+    pub stickers: Vec<String>,
+    /// Who drew the stickers, if anyone is credited.
+    pub artist: Option<ArtistCredit>,
+    /// Where the stickers can be downloaded as a pack, if they can be.
+    pub pack: Option<StickerPack>,
+}
+
+/// Who made a character's stickers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArtistCredit {
+    pub name: String,
+    pub url: Option<String>,
+}
+
+/// A character's downloadable stickers and what they're licensed under.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StickerPack {
+    pub path: String,
+    pub license: String,
+    #[serde(rename = "licenseURL")]
+    pub license_url: Option<String>,
+}
+
+/// Which stickers there are of each character, from the characters
+/// manifest, so a typo in a mood is noticed instead of being a broken image.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StickerRegistry {
+    characters: HashMap<String, (String, HashSet<String>)>,
+}
+
+impl StickerRegistry {
+    /// Adds the character with this sticker name, whose stickers are
+    /// `moods`.
+    pub fn add(&mut self, sticker_name: String, default_pose: String, moods: Vec<String>) {
+        self.characters
+            .insert(sticker_name, (default_pose, moods.into_iter().collect()));
+    }
+
+    /// Whether there's a sticker of the character in `mood`. Characters that
+    /// aren't in the manifest, like other people's, always have one.
+    pub fn has(&self, sticker_name: &str, mood: &str) -> bool {
+        self.characters
+            .get(sticker_name)
+            .map_or(true, |(_, moods)| moods.contains(mood))
+    }
+
+    /// What to show instead of `mood` if there's no sticker of it: the
+    /// character's default pose.
+    pub fn fallback(&self, sticker_name: &str, mood: &str) -> Option<&str> {
+        if self.has(sticker_name, mood) {
+            return None;
+        }
+        self.characters
+            .get(sticker_name)
+            .map(|(default_pose, _)| default_pose.as_str())
+    }
+}
+
+/// The background of chat bubbles in the dark colour scheme. Accents have to
+/// stand out against it.
+pub const DARK_CHAT_BACKGROUND: &str = "#3c3836";
+
+/// The background of chat bubbles in the light colour scheme.
+pub const LIGHT_CHAT_BACKGROUND: &str = "#ebdbb2";
+
+/// How much an accent has to contrast with the chat background. This is what
+/// WCAG asks of borders and other things that aren't text.
+pub const MIN_ACCENT_CONTRAST: f64 = 3.0;
+
+/// How a character's lines in a conversation are set apart from everyone
+/// else's: a coloured border down the side of each chat bubble.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatTheme {
+    /// A `#rrggbb` colour for the dark colour scheme.
+    pub accent: String,
+    /// The colour for the light colour scheme, if `accent` doesn't work there.
+    #[serde(rename = "accentLight")]
+    pub accent_light: Option<String>,
+    pub border: BorderStyle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum BorderStyle {
+    #[default]
+    Solid,
+    Dashed,
+    Dotted,
+    Double,
+}
+
+impl BorderStyle {
+    pub fn css(self) -> &'static str {
+        match self {
+            BorderStyle::Solid => "solid",
+            BorderStyle::Dashed => "dashed",
+            BorderStyle::Dotted => "dotted",
+            BorderStyle::Double => "double",
+        }
+    }
+}
+
+impl ChatTheme {
+    /// The accent in the light colour scheme.
+    pub fn light(&self) -> &str {
+        self.accent_light.as_deref().unwrap_or(&self.accent)
+    }
+
+    /// The inline style of a chat bubble, which sets the custom properties
+    /// the stylesheet draws the border with.
+    pub fn style(&self) -> String {
+        format!(
+            "--chat-accent: {}; --chat-accent-light: {}; --chat-border: {}",
+            self.accent,
+            self.light(),
+            self.border.css()
+        )
+    }
+
+    /// Everything wrong with the theme, such as accents that are hard to see
+    /// against the chat background.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+        for (field, color, background) in [
+            ("accent", self.accent.as_str(), DARK_CHAT_BACKGROUND),
+            ("accentLight", self.light(), LIGHT_CHAT_BACKGROUND),
+        ] {
+            match contrast(color, background) {
+                None => problems.push(format!("{field} {color:?} is not a #rrggbb colour")),
+                Some(ratio) if ratio < MIN_ACCENT_CONTRAST => problems.push(format!(
+                    "{field} {color} has a contrast of {ratio:.2}:1 with {background}, it needs at least {MIN_ACCENT_CONTRAST}:1"
+                )),
+                Some(_) => {}
+            }
+        }
+        problems
+    }
+}
+
+/// The relative luminance of a `#rrggbb` colour, as WCAG defines it.
+pub fn luminance(color: &str) -> Option<f64> {
+    let [r, g, b] = parse_color(color)?.map(|c| {
+        let c = c as f64 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    });
+    Some(0.2126 * r + 0.7152 * g + 0.0722 * b)
+}
+
+/// The WCAG contrast ratio of two `#rrggbb` colours, from 1 to 21.
+pub fn contrast(a: &str, b: &str) -> Option<f64> {
+    let (a, b) = (luminance(a)?, luminance(b)?);
+    let (light, dark) = if a > b { (a, b) } else { (b, a) };
+    Some((light + 0.05) / (dark + 0.05))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn theme(accent: &str, accent_light: Option<&str>) -> ChatTheme {
+        ChatTheme {
+            accent: accent.into(),
+            accent_light: accent_light.map(Into::into),
+            border: BorderStyle::Dashed,
+        }
+    }
+
+    #[test]
+    fn contrast_ratios() {
+        assert_eq!(contrast("#000000", "#ffffff"), Some(21.0));
+        assert_eq!(contrast("#ffffff", "#ffffff"), Some(1.0));
+        let ratio = contrast("#fb4934", DARK_CHAT_BACKGROUND).unwrap();
+        assert!((ratio - 3.37).abs() < 0.01, "{ratio}");
+        assert_eq!(contrast("fb4934", "#ffffff"), None);
+        assert_eq!(contrast("#fb493", "#ffffff"), None);
+        assert_eq!(contrast("#gggggg", "#ffffff"), None);
+    }
+
+    #[test]
+    fn accent_problems() {
+        assert!(theme("#fb4934", Some("#9d0006")).problems().is_empty());
+
+        // Gray is fine on the dark background but not the light one.
+        let problems = theme("#928374", None).problems();
+        assert_eq!(problems.len(), 1);
+        assert!(
+            problems[0].starts_with("accentLight #928374"),
+            "{problems:?}"
+        );
+
+        assert_eq!(theme("red", Some("#9d0006")).problems().len(), 1);
+    }
+
+    #[test]
+    fn sticker_registry() {
+        let mut stickers = StickerRegistry::default();
+        stickers.add(
+            "mara".into(),
+            "hacker".into(),
+            vec!["hacker".into(), "hmm".into()],
+        );
+        assert!(stickers.has("mara", "hmm"));
+        assert_eq!(stickers.fallback("mara", "hmm"), None);
+        assert!(!stickers.has("mara", "hmmm"));
+        assert_eq!(stickers.fallback("mara", "hmmm"), Some("hacker"));
+        assert!(stickers.has("scoots", "anything"));
+        assert_eq!(stickers.fallback("scoots", "anything"), None);
+    }
+
+    #[test]
+    fn style() {
+        assert_eq!(
+            theme("#fb4934", None).style(),
+            "--chat-accent: #fb4934; --chat-accent-light: #fb4934; --chat-border: dashed"
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+
+/// How an embed is shown in a post.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EmbedKind {
+    /// A thumbnail that loads the provider's player when clicked.
+    Video,
+    /// A card with the page's title, description and picture.
+    Link,
+    /// A toot, kept in `./data/toots` like the ones in `<xeblog-toot>`.
+    Toot,
+}
+
+/// A site that posts can embed things from.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Provider {
+    pub name: &'static str,
+    pub hosts: &'static [&'static str],
+    pub kind: EmbedKind,
+    /// The provider's oEmbed endpoint. Pages from providers without one are
+    /// read for OpenGraph tags.
+    pub oembed: Option<&'static str>,
+}
+
+/// The providers embeds can be made from. Anything else is refused, so
+/// posts can't pull random third-party pages into the site.
+pub const PROVIDERS: &[Provider] = &[
+    Provider {
+        name: "YouTube",
+        hosts: &["www.youtube.com", "youtube.com", "youtu.be"],
+        kind: EmbedKind::Video,
+        oembed: Some("https://www.youtube.com/oembed"),
+    },
+    Provider {
+        name: "Vimeo",
+        hosts: &["vimeo.com"],
+        kind: EmbedKind::Video,
+        oembed: Some("https://vimeo.com/api/oembed.json"),
+    },
+    Provider {
+        name: "GitHub",
+        hosts: &["github.com"],
+        kind: EmbedKind::Link,
+        oembed: None,
+    },
+    Provider {
+        name: "Wikipedia",
+        hosts: &["en.wikipedia.org"],
+        kind: EmbedKind::Link,
+        oembed: None,
+    },
+    Provider {
+        name: "Mastodon",
+        hosts: &["pony.social", "hachyderm.io", "mastodon.social"],
+        kind: EmbedKind::Toot,
+        oembed: None,
+    },
+];
+
+/// Hosts video players can be loaded from.
+pub const PLAYER_HOSTS: &[&str] = &["www.youtube-nocookie.com", "player.vimeo.com"];
+
+/// The host of an `https` URL, if it has one without a login or port.
+fn host(url: &str) -> Option<&str> {
+    let rest = url.strip_prefix("https://")?;
+    let host = rest.split(['/', '?', '#']).next()?;
+    if host.is_empty() || host.contains(['@', ':']) {
+        return None;
+    }
+    Some(host)
+}
+
+/// The provider `url` is from, if it's one posts can embed.
+pub fn provider(url: &str) -> Option<&'static Provider> {
+    let host = host(url)?;
+    PROVIDERS.iter().find(|p| p.hosts.contains(&host))
+}
+
+/// Whether `url` is a player from one of [PLAYER_HOSTS].
+pub fn is_player(url: &str) -> bool {
+    host(url).map_or(false, |host| PLAYER_HOSTS.contains(&host))
+}
+
+/// What was fetched for an embed by `fetch_embed`, kept in `./data/embeds`
+/// so building the site doesn't need the network.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Embed {
+    pub url: String,
+    pub kind: EmbedKind,
+    pub provider: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub author: Option<String>,
+    /// Where the thumbnail was saved in `static`, so readers don't load it
+    /// from the provider.
+    pub image: Option<String>,
+    /// For videos, the player that's loaded when the thumbnail is clicked.
+    pub player: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn providers() {
+        assert_eq!(
+            provider("https://www.youtube.com/watch?v=dQw4w9WgXcQ").map(|p| p.name),
+            Some("YouTube")
+        );
+        assert_eq!(
+            provider("https://youtu.be/dQw4w9WgXcQ").map(|p| p.kind),
+            Some(EmbedKind::Video)
+        );
+        assert_eq!(
+            provider("https://pony.social/@cadey/110019625935845512").map(|p| p.kind),
+            Some(EmbedKind::Toot)
+        );
+        assert_eq!(
+            provider("https://github.com").map(|p| p.name),
+            Some("GitHub")
+        );
+        assert_eq!(provider("http://github.com/Xe/site"), None);
+        assert_eq!(provider("https://github.com.evil.example/Xe/site"), None);
+        assert_eq!(provider("https://github.com@evil.example/"), None);
+        assert_eq!(provider("https://example.com/"), None);
+        assert!(is_player(
+            "https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ"
+        ));
+        assert!(!is_player("https://www.youtube.com/embed/dQw4w9WgXcQ"));
+    }
+}
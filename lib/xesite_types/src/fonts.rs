@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+/// One subsetted font file, as listed in the manifest written by the
+/// `subset_fonts` tool.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Font {
+    pub family: String,
+    /// The CSS `font-style`, `normal` or `italic`.
+    pub style: String,
+    /// File name with the content hash in it, relative to the font folder.
+    pub file: String,
+    /// Whether nearly every page needs this font right away.
+    pub preload: bool,
+}
+
+impl Font {
+    /// The `@font-face` rule for this font, where `base` is the URL of the
+    /// folder the font is served from.
+    pub fn font_face(&self, base: &str) -> String {
+        format!(
+            r#"@font-face {{ font-family: "{family}"; font-style: {style}; font-weight: 100 900; font-display: swap; src: local("{family}"), url("{base}/{file}") format("woff2"); }}"#,
+            family = self.family,
+            style = self.style,
+            file = self.file,
+        )
+    }
+}
@@ -1,6 +1,14 @@
 use serde::{Deserialize, Serialize};
 
+pub mod archive;
+pub mod bench;
+pub mod character;
+pub mod embed;
+pub mod fonts;
 pub mod mastodon;
+pub mod media;
+pub mod route;
+pub mod site;
 
 #[derive(Eq, PartialEq, Deserialize, Default, Debug, Serialize, Clone)]
 pub struct Frontmatter {
@@ -28,6 +36,54 @@ pub struct Frontmatter {
     pub vod: Option<Vod>,
     #[serde(default)]
     pub skip_ads: bool,
+    /// Leave the post out of the spell check, for posts that are mostly in
+    /// another language.
+    #[serde(default, skip_serializing)]
+    pub skip_spellcheck: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event: Option<EventMeta>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub incident: Option<Incident>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_warning: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub originally_published: Option<OriginMeta>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outdated_as_of: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub successor: Option<String>,
+    #[serde(default, skip_serializing)]
+    pub unlisted: bool,
+    #[serde(default, skip_serializing)]
+    pub noindex: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub video: Option<Media>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio: Option<Media>,
+    /// Stylesheets in `static` that only this post needs, like
+    /// `/static/css/posts/foo.css`.
+    #[serde(default, skip_serializing)]
+    pub extra_css: Vec<String>,
+    /// JavaScript modules in `static` that only this post needs.
+    #[serde(default, skip_serializing)]
+    pub extra_scripts: Vec<String>,
+}
+
+/// What the server knows about who's reading a page, for templates that
+/// change with it. Pages rendered without a reader, like feeds and static
+/// exports, get the default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReaderPrefs {
+    /// The reader asked not to be shown ads.
+    pub no_ads: bool,
+    /// The reader is logged in and is one of my patrons.
+    pub patron: bool,
+}
+
+impl ReaderPrefs {
+    pub fn shows_ads(&self) -> bool {
+        !self.no_ads && !self.patron
+    }
 }
 
 fn frontmatter_about() -> String {
@@ -39,3 +95,95 @@ pub struct Vod {
     pub twitch: String,
     pub youtube: String,
 }
+
+/// A playable media file (talk recording, podcast episode, etc.) that is the
+/// primary content of a post. This is used to make link previews playable.
+#[derive(Eq, PartialEq, Deserialize, Default, Debug, Serialize, Clone)]
+pub struct Media {
+    pub url: String,
+    #[serde(rename = "type")]
+    pub media_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+}
+
+/// Where a republished post first ran.
+#[derive(Eq, PartialEq, Deserialize, Default, Debug, Serialize, Clone)]
+pub struct OriginMeta {
+    pub venue: String,
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+}
+
+/// A conference or meetup that a talk was (or will be) given at.
+#[derive(Eq, PartialEq, Deserialize, Default, Debug, Serialize, Clone)]
+pub struct EventMeta {
+    pub name: String,
+    pub start_date: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_date: Option<String>,
+    pub venue: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub talk_title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+/// A post in a [Series].
+#[derive(Eq, PartialEq, Deserialize, Default, Debug, Serialize, Clone)]
+pub struct SeriesPart {
+    pub title: String,
+    /// Where the post is, like `/blog/foo`.
+    pub url: String,
+}
+
+/// A multi-part series, as seen from one of its posts.
+#[derive(Eq, PartialEq, Deserialize, Default, Debug, Serialize, Clone)]
+pub struct Series {
+    /// The series' name, which is also where it's listed under
+    /// `/blog/series`.
+    pub title: String,
+    /// Every post in the series, oldest first.
+    pub parts: Vec<SeriesPart>,
+    /// Which of the parts is the post being looked at.
+    pub current: usize,
+}
+
+impl Series {
+    /// The part being looked at, counting from 1.
+    pub fn number(&self) -> usize {
+        self.current + 1
+    }
+
+    pub fn previous(&self) -> Option<&SeriesPart> {
+        self.current.checked_sub(1).and_then(|i| self.parts.get(i))
+    }
+
+    pub fn next(&self) -> Option<&SeriesPart> {
+        self.parts.get(self.current + 1)
+    }
+}
+
+/// Metadata for an incident report on the status page.
+#[derive(Eq, PartialEq, Deserialize, Default, Debug, Serialize, Clone)]
+pub struct Incident {
+    pub services: Vec<String>,
+    #[serde(default)]
+    pub timeline: Vec<IncidentUpdate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolved: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<String>,
+}
+
+#[derive(Eq, PartialEq, Deserialize, Default, Debug, Serialize, Clone)]
+pub struct IncidentUpdate {
+    pub time: String,
+    pub status: String,
+    pub message: String,
+}
@@ -119,12 +119,107 @@ pub struct Toot {
 
     #[serde(rename = "replies")]
     pub replies: Option<Replies>,
+
+    /// The options of a poll where people pick one.
+    #[serde(rename = "oneOf", default)]
+    pub one_of: Vec<PollOption>,
+
+    /// The options of a poll where people can pick more than one.
+    #[serde(rename = "anyOf", default)]
+    pub any_of: Vec<PollOption>,
+
+    #[serde(rename = "endTime")]
+    pub end_time: Option<DateTime<Utc>>,
+
+    /// When the poll closed. Mastodon sets this once it has.
+    #[serde(rename = "closed")]
+    pub closed: Option<String>,
+
+    #[serde(rename = "votersCount")]
+    pub voters_count: Option<u64>,
 }
 
 impl Toot {
     pub fn content_text(&self) -> String {
         html2text::from_read(std::io::Cursor::new(&self.content), 80)
     }
+
+    /// The poll in this toot, if it's a poll.
+    pub fn poll(&self) -> Option<Poll> {
+        let (kind, options) = match (self.one_of.is_empty(), self.any_of.is_empty()) {
+            (false, _) => (PollKind::One, &self.one_of),
+            (true, false) => (PollKind::Any, &self.any_of),
+            (true, true) => return None,
+        };
+        let votes: u64 = options.iter().map(PollOption::votes).sum();
+        Some(Poll {
+            kind,
+            options: options
+                .iter()
+                .map(|option| (option.name.clone(), option.votes()))
+                .collect(),
+            // When people can pick more than one option, the share of an
+            // option is out of the people who voted instead of the votes.
+            total: match kind {
+                PollKind::One => votes,
+                PollKind::Any => self.voters_count.unwrap_or(votes),
+            },
+            ends: self.end_time,
+            closed: self.closed.is_some(),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PollOption {
+    #[serde(rename = "name")]
+    pub name: String,
+
+    #[serde(rename = "replies")]
+    pub replies: Option<PollVotes>,
+}
+
+impl PollOption {
+    pub fn votes(&self) -> u64 {
+        self.replies.as_ref().map_or(0, |r| r.total_items)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PollVotes {
+    #[serde(rename = "totalItems")]
+    pub total_items: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PollKind {
+    /// People pick one option.
+    One,
+    /// People can pick more than one option.
+    Any,
+}
+
+/// A poll as embeds show it, see [Toot::poll].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Poll {
+    pub kind: PollKind,
+    /// Each option and how many votes it got.
+    pub options: Vec<(String, u64)>,
+    /// What the votes of each option are a share of.
+    pub total: u64,
+    pub ends: Option<DateTime<Utc>>,
+    pub closed: bool,
+}
+
+impl Poll {
+    /// The share of `votes` as a whole percentage.
+    pub fn percent(&self, votes: u64) -> u64 {
+        if self.total == 0 {
+            0
+        } else {
+            (votes * 100 + self.total / 2) / self.total
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -218,6 +313,22 @@ pub mod test {
     fn toot() {
         let _attachment: Toot = from_str(include_str!("./testdata/post_attachment.json")).unwrap();
         let _hashtags: Toot = from_str(include_str!("./testdata/post_hashtags.json")).unwrap();
-        let _mention: Toot = from_str(include_str!("./testdata/post_mention.json")).unwrap();
+        let mention: Toot = from_str(include_str!("./testdata/post_mention.json")).unwrap();
+        assert!(mention.poll().is_none());
+    }
+
+    #[test]
+    fn poll() {
+        let toot: Toot = from_str(include_str!("./testdata/post_poll.json")).unwrap();
+        let poll = toot.poll().unwrap();
+        assert_eq!(poll.kind, PollKind::One);
+        assert_eq!(
+            poll.options,
+            vec![("Tabs".to_string(), 3), ("Spaces".to_string(), 5)]
+        );
+        assert_eq!(poll.total, 8);
+        assert!(poll.closed);
+        assert_eq!(poll.percent(3), 38);
+        assert_eq!(poll.percent(5), 63);
     }
 }
@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+
+/// Who made an image or video on the CDN and what it can be used under, as
+/// listed in the media manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MediaCredit {
+    /// Path of the asset in the CDN bucket without the extension, the same
+    /// way posts refer to it: `hero/foo` or `blog/bar/baz`.
+    pub path: String,
+    pub creator: String,
+    /// Where the asset came from, if it's somewhere else too.
+    pub source: Option<String>,
+    /// The license name, such as `CC-BY-SA 4.0`.
+    pub license: String,
+    #[serde(rename = "licenseURL")]
+    pub license_url: Option<String>,
+}
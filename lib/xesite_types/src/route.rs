@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+
+const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
+/// A recorded route, such as a run, a hike or the walk from the hotel to a
+/// conference venue.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub struct Route {
+    pub name: Option<String>,
+    pub points: Vec<TrackPoint>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TrackPoint {
+    pub lat: f64,
+    pub lon: f64,
+    pub ele: Option<f64>,
+}
+
+impl TrackPoint {
+    /// The great-circle distance between two points in meters.
+    pub fn distance_to(&self, other: &TrackPoint) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let dlat = lat2 - lat1;
+        let dlon = (other.lon - self.lon).to_radians();
+
+        let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_M * a.sqrt().asin()
+    }
+}
+
+impl Route {
+    pub fn distance_m(&self) -> f64 {
+        self.points
+            .windows(2)
+            .map(|w| w[0].distance_to(&w[1]))
+            .sum()
+    }
+
+    /// Total climb in meters. Points without elevation data are skipped.
+    pub fn elevation_gain_m(&self) -> f64 {
+        self.elevations()
+            .windows(2)
+            .map(|w| (w[1] - w[0]).max(0.0))
+            .sum()
+    }
+
+    pub fn elevation_range_m(&self) -> Option<(f64, f64)> {
+        let eles = self.elevations();
+        if eles.is_empty() {
+            return None;
+        }
+        Some((
+            eles.iter().copied().fold(f64::INFINITY, f64::min),
+            eles.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        ))
+    }
+
+    pub fn elevations(&self) -> Vec<f64> {
+        self.points.iter().filter_map(|p| p.ele).collect()
+    }
+
+    /// The (min_lat, min_lon, max_lat, max_lon) bounding box of the route.
+    pub fn bounds(&self) -> Option<(f64, f64, f64, f64)> {
+        let first = self.points.first()?;
+        Some(self.points.iter().fold(
+            (first.lat, first.lon, first.lat, first.lon),
+            |(min_lat, min_lon, max_lat, max_lon), p| {
+                (
+                    min_lat.min(p.lat),
+                    min_lon.min(p.lon),
+                    max_lat.max(p.lat),
+                    max_lon.max(p.lon),
+                )
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(lat: f64, lon: f64, ele: f64) -> TrackPoint {
+        TrackPoint {
+            lat,
+            lon,
+            ele: Some(ele),
+        }
+    }
+
+    #[test]
+    fn distance() {
+        // one degree of latitude is about 111.2 km
+        let route = Route {
+            name: None,
+            points: vec![point(0.0, 0.0, 0.0), point(1.0, 0.0, 0.0)],
+        };
+        let km = route.distance_m() / 1000.0;
+        assert!((km - 111.19).abs() < 0.1, "got {km}");
+    }
+
+    #[test]
+    fn elevation() {
+        let route = Route {
+            name: None,
+            points: vec![
+                point(0.0, 0.0, 10.0),
+                point(0.0, 0.1, 30.0),
+                point(0.0, 0.2, 20.0),
+                point(0.0, 0.3, 25.0),
+            ],
+        };
+        assert_eq!(route.elevation_gain_m(), 25.0);
+        assert_eq!(route.elevation_range_m(), Some((10.0, 30.0)));
+    }
+}
@@ -0,0 +1,731 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// Site-wide settings that used to be hardcoded in the templates.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct SiteConfig {
+    /// The bucket on the CDN that static assets are stored in.
+    #[serde(rename = "cdnBucket")]
+    pub cdn_bucket: String,
+    /// Ethical Ads publisher ID. Ads are disabled when this is not set.
+    #[serde(rename = "adPublisher")]
+    pub ad_publisher: Option<String>,
+    pub donation: Donation,
+    pub mastodon: MastodonAccount,
+    #[serde(rename = "feedTitle")]
+    pub feed_title: String,
+    #[serde(rename = "feedDescription")]
+    pub feed_description: String,
+    /// Path to the Dhall file listing the characters and their stickers.
+    #[serde(rename = "charactersManifest")]
+    pub characters_manifest: String,
+    /// Path to the Dhall file with who made the images and videos on the
+    /// CDN and their licenses.
+    #[serde(rename = "mediaManifest")]
+    pub media_manifest: String,
+    /// How many words of a post index pages show when the post doesn't
+    /// have a `<!--more-->` marker.
+    #[serde(rename = "excerptWords")]
+    pub excerpt_words: usize,
+    /// How many posts each page of the blog index lists.
+    #[serde(rename = "postsPerPage")]
+    pub posts_per_page: usize,
+    /// The widths in pixels that heroes and pictures are resized to, smallest
+    /// first, so phones don't download images made for desktops. Empty when
+    /// the bucket only has one size of each image.
+    #[serde(rename = "imageWidths")]
+    pub image_widths: Vec<u32>,
+    pub profiles: Profiles,
+    pub branding: Branding,
+    pub signing: Signing,
+    /// The Tor onion service mirroring the site, if there is one.
+    pub onion: Option<Onion>,
+    /// Sponsors that take turns in the slot at the end of posts, see
+    /// `xesite_templates::sponsor_footer`.
+    #[serde(default)]
+    pub sponsors: Vec<Sponsor>,
+    /// The JSON file `link_archive` writes. When it's there, external links
+    /// in posts get a link to their archived copy, and links that are gone
+    /// point at the copy instead.
+    #[serde(rename = "linkArchive", default)]
+    pub link_archive: Option<String>,
+    /// Folder with template overrides. Only used in the dev environment.
+    #[serde(rename = "templateOverrides")]
+    pub template_overrides: Option<String>,
+    /// Which of the [Profiles] is in use. This is set from the `XESITE_ENV`
+    /// environment variable at startup, not from the config file.
+    #[serde(skip)]
+    pub environment: Environment,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Donation {
+    #[serde(rename = "patreonURL")]
+    pub patreon_url: String,
+    /// Cryptocurrency addresses or names that people can send money to.
+    pub addresses: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct MastodonAccount {
+    /// The full handle, such as `@cadey@pony.social`.
+    pub handle: String,
+    /// The ActivityPub actor ID.
+    pub actor: String,
+    /// The profile page people should be linked to.
+    pub url: String,
+}
+
+/// How the site looks when it's installed as an app.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Branding {
+    pub name: String,
+    #[serde(rename = "shortName")]
+    pub short_name: String,
+    pub description: String,
+    /// The colour of the browser UI around the site in dark mode, which is
+    /// the default. This matches the page background in `gruvbox-dark.css`.
+    #[serde(rename = "themeColor")]
+    pub theme_color: String,
+    #[serde(rename = "themeColorLight")]
+    pub theme_color_light: String,
+    /// Shown behind the icon while the installed app starts, and behind the
+    /// avatar in maskable icons.
+    #[serde(rename = "backgroundColor")]
+    pub background_color: String,
+}
+
+impl Default for Branding {
+    fn default() -> Self {
+        Self {
+            name: "Xe Iaso".into(),
+            short_name: "Xe".into(),
+            description: "Blog and Resume for Xe Iaso".into(),
+            theme_color: "#1d2021".into(),
+            theme_color_light: "#f9f5d7".into(),
+            background_color: "#1d2021".into(),
+        }
+    }
+}
+
+/// Someone who sponsors the site directly instead of through an ad network.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Sponsor {
+    pub name: String,
+    pub url: String,
+    /// The URL or path on this site of their logo.
+    pub logo: String,
+    /// A sentence or two about them, in plain text.
+    pub blurb: String,
+}
+
+/// Keys used to sign posts when they're published, see `scripts/sign`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Signing {
+    /// The minisign public key, the second line of `minisign.pub`. Posts
+    /// with a signature that doesn't check out against it aren't shown as
+    /// signed.
+    #[serde(rename = "minisignPublicKey")]
+    pub minisign_public_key: Option<String>,
+    /// Where to get the PGP key posts are signed with, either a URL or a
+    /// path on this site.
+    #[serde(rename = "pgpKeyURL")]
+    pub pgp_key_url: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Onion {
+    /// The onion address without a scheme, like `xxxx.onion`.
+    pub address: String,
+    /// Also advertise the onion service with `Alt-Svc`, so Tor Browser can
+    /// quietly use it for the normal address.
+    #[serde(rename = "altSvc")]
+    pub alt_svc: bool,
+}
+
+impl Onion {
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{path}", self.address)
+    }
+}
+
+/// Parses a `#rrggbb` colour into its red, green and blue parts.
+pub fn parse_color(color: &str) -> Option<[u8; 3]> {
+    let hex = color.strip_prefix('#')?;
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let part = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([part(0)?, part(2)?, part(4)?])
+}
+
+impl MastodonAccount {
+    pub fn rss_url(&self) -> String {
+        format!("{}.rss", self.actor)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, Default)]
+pub enum Environment {
+    Dev,
+    Staging,
+    #[default]
+    Prod,
+}
+
+impl Environment {
+    pub const VAR: &'static str = "XESITE_ENV";
+
+    /// Reads the environment from `XESITE_ENV`, defaulting to production.
+    pub fn from_env() -> Result<Self, String> {
+        match std::env::var(Self::VAR) {
+            Ok(val) => val.parse(),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+}
+
+impl FromStr for Environment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "dev" | "development" => Ok(Self::Dev),
+            "staging" => Ok(Self::Staging),
+            "prod" | "production" | "" => Ok(Self::Prod),
+            _ => Err(format!(
+                "{s:?} is not a known environment, expected dev, staging or prod"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dev => write!(f, "dev"),
+            Self::Staging => write!(f, "staging"),
+            Self::Prod => write!(f, "prod"),
+        }
+    }
+}
+
+/// The environment variable that overrides [Profile::asset_base].
+pub const ASSET_BASE_VAR: &str = "XESITE_ASSET_BASE";
+
+/// The settings that differ between environments.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Profile {
+    /// Base URL of the site without a trailing slash.
+    #[serde(rename = "baseURL")]
+    pub base_url: String,
+    /// Base URL of the CDN without a trailing slash.
+    #[serde(rename = "cdnBase")]
+    pub cdn_base: String,
+    /// Where static assets are loaded from instead of the CDN bucket, such
+    /// as a local copy of the bucket for working offline or a mirror. It
+    /// needs to have the same layout as the bucket. Overridden by the
+    /// `XESITE_ASSET_BASE` environment variable.
+    #[serde(rename = "assetBase")]
+    pub asset_base: Option<String>,
+    /// Where the service worker loads images from when the asset base is
+    /// down or slow, see [SiteConfig::asset_fallback]. It needs to have the
+    /// same layout as the bucket too, so the bucket's own origin works.
+    #[serde(rename = "assetFallback")]
+    pub asset_fallback: Option<String>,
+    pub ads: bool,
+    pub analytics: bool,
+    /// Don't put random IDs or cache busters in pages, so rendered pages are
+    /// the same every time.
+    #[serde(rename = "deterministicIDs")]
+    pub deterministic_ids: bool,
+    /// Send `Link` headers for the assets every page needs, so a CDN in
+    /// front of the site can turn them into 103 Early Hints.
+    #[serde(rename = "earlyHints")]
+    pub early_hints: bool,
+    /// Inline the critical CSS for the kind of page being rendered and load
+    /// the full stylesheets without blocking the first paint.
+    #[serde(rename = "inlineCriticalCSS")]
+    pub inline_critical_css: bool,
+}
+
+/// What the service worker needs to send image requests somewhere else when
+/// the asset base is down, see `static/js/sw.js`.
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct AssetFallback {
+    /// The asset base, where images are loaded from first.
+    pub primary: String,
+    /// Where the CDN serves resized stickers, if they come from the CDN.
+    /// The fallback only has the full size PNGs.
+    pub stickers: Option<String>,
+    pub fallback: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Profiles {
+    pub dev: Profile,
+    pub staging: Profile,
+    pub prod: Profile,
+}
+
+impl Profiles {
+    fn iter(&self) -> impl Iterator<Item = (Environment, &Profile)> {
+        [
+            (Environment::Dev, &self.dev),
+            (Environment::Staging, &self.staging),
+            (Environment::Prod, &self.prod),
+        ]
+        .into_iter()
+    }
+}
+
+impl Default for Profiles {
+    fn default() -> Self {
+        Self {
+            dev: Profile {
+                base_url: "http://localhost:3030".into(),
+                cdn_base: "https://cdn.xeiaso.net".into(),
+                asset_base: None,
+                asset_fallback: None,
+                ads: false,
+                analytics: false,
+                deterministic_ids: true,
+                early_hints: false,
+                inline_critical_css: false,
+            },
+            staging: Profile {
+                base_url: "https://staging.xeiaso.net".into(),
+                cdn_base: "https://cdn.xeiaso.net".into(),
+                asset_base: None,
+                asset_fallback: None,
+                ads: false,
+                analytics: true,
+                deterministic_ids: false,
+                early_hints: true,
+                inline_critical_css: true,
+            },
+            prod: Profile {
+                base_url: "https://xeiaso.net".into(),
+                cdn_base: "https://cdn.xeiaso.net".into(),
+                asset_base: None,
+                asset_fallback: None,
+                ads: true,
+                analytics: true,
+                deterministic_ids: false,
+                early_hints: true,
+                inline_critical_css: true,
+            },
+        }
+    }
+}
+
+impl Default for SiteConfig {
+    fn default() -> Self {
+        Self {
+            cdn_bucket: "christine-static".into(),
+            ad_publisher: Some("christinewebsite".into()),
+            donation: Donation {
+                patreon_url: "https://www.patreon.com/cadey".into(),
+                addresses: vec![
+                    "xeiaso.eth".into(),
+                    "0xeA223Ca8968Ca59e0Bc79Ba331c2F6f636A3fB82".into(),
+                ],
+            },
+            mastodon: MastodonAccount {
+                handle: "@cadey@pony.social".into(),
+                actor: "https://pony.social/users/cadey".into(),
+                url: "https://pony.social/@cadey".into(),
+            },
+            feed_title: "Xe's Blog".into(),
+            feed_description: "My blog posts and rants about various technology things.".into(),
+            characters_manifest: "./dhall/characters.dhall".into(),
+            media_manifest: "./dhall/media.dhall".into(),
+            excerpt_words: 60,
+            posts_per_page: 30,
+            image_widths: vec![480, 960, 1440],
+            profiles: Profiles::default(),
+            branding: Branding::default(),
+            signing: Signing::default(),
+            onion: None,
+            sponsors: vec![],
+            link_archive: None,
+            template_overrides: None,
+            environment: Environment::default(),
+        }
+    }
+}
+
+/// A problem with one field of the [SiteConfig].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationError {
+    pub field: String,
+    pub problem: String,
+}
+
+impl ValidationError {
+    fn new(field: impl Into<String>, problem: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            problem: problem.into(),
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "site.{}: {}", self.field, self.problem)
+    }
+}
+
+fn check_url(errors: &mut Vec<ValidationError>, field: impl Into<String>, url: &str) {
+    if !url.starts_with("https://") && !url.starts_with("http://") {
+        errors.push(ValidationError::new(
+            field,
+            format!("{url:?} is not an absolute http(s) URL"),
+        ));
+    }
+}
+
+fn check_base(errors: &mut Vec<ValidationError>, field: String, url: &str) {
+    check_url(errors, field.clone(), url);
+    if url.ends_with('/') {
+        errors.push(ValidationError::new(field, "must not end with a slash"));
+    }
+}
+
+impl SiteConfig {
+    /// The profile for the current [Environment].
+    pub fn profile(&self) -> &Profile {
+        match self.environment {
+            Environment::Dev => &self.profiles.dev,
+            Environment::Staging => &self.profiles.staging,
+            Environment::Prod => &self.profiles.prod,
+        }
+    }
+
+    pub fn cdn_base(&self) -> &str {
+        &self.profile().cdn_base
+    }
+
+    /// Where the files in the static CDN bucket are served from, without a
+    /// trailing slash.
+    pub fn asset_base(&self) -> String {
+        match &self.profile().asset_base {
+            Some(base) => base.clone(),
+            None => format!("{}/file/{}", self.cdn_base(), self.cdn_bucket),
+        }
+    }
+
+    /// URL of a file in the static CDN bucket.
+    pub fn cdn(&self, path: &str) -> String {
+        format!("{}/{path}", self.asset_base())
+    }
+
+    /// URL of a sticker resized to `size` pixels. The CDN resizes them on
+    /// the fly, a local copy of the bucket only has the full size PNG.
+    pub fn sticker(&self, name: &str, mood: &str, size: u32) -> String {
+        match self.profile().asset_base {
+            Some(_) => self.cdn(&format!("stickers/{name}/{mood}.png")),
+            None => format!("{}/sticker/{name}/{mood}/{size}", self.cdn_base()),
+        }
+    }
+
+    /// Where images from the asset base can be loaded from instead, if the
+    /// profile has an `assetFallback`.
+    pub fn asset_fallback(&self) -> Option<AssetFallback> {
+        let fallback = self.profile().asset_fallback.clone()?;
+        Some(AssetFallback {
+            primary: self.asset_base(),
+            stickers: self
+                .profile()
+                .asset_base
+                .is_none()
+                .then(|| format!("{}/sticker", self.cdn_base())),
+            fallback,
+        })
+    }
+
+    /// Uses the asset base from `XESITE_ASSET_BASE` if it's set.
+    pub fn asset_base_from_env(&mut self) {
+        if let Ok(base) = std::env::var(ASSET_BASE_VAR) {
+            let base = base.trim_end_matches('/').to_string();
+            match self.environment {
+                Environment::Dev => self.profiles.dev.asset_base = Some(base),
+                Environment::Staging => self.profiles.staging.asset_base = Some(base),
+                Environment::Prod => self.profiles.prod.asset_base = Some(base),
+            }
+        }
+    }
+
+    /// Absolute URL of a page on this site.
+    pub fn url(&self, path: &str) -> String {
+        format!(
+            "{}/{}",
+            self.profile().base_url,
+            path.trim_start_matches('/')
+        )
+    }
+
+    /// The Ethical Ads publisher ID, if ads should be shown.
+    pub fn ads(&self) -> Option<&str> {
+        if self.profile().ads {
+            self.ad_publisher.as_deref()
+        } else {
+            None
+        }
+    }
+
+    pub fn analytics(&self) -> bool {
+        self.profile().analytics
+    }
+
+    pub fn deterministic_ids(&self) -> bool {
+        self.profile().deterministic_ids
+    }
+
+    pub fn early_hints(&self) -> bool {
+        self.profile().early_hints
+    }
+
+    pub fn inline_critical_css(&self) -> bool {
+        self.profile().inline_critical_css
+    }
+
+    /// Checks everything that can be checked without touching the network
+    /// and returns every problem at once so they can be fixed in one go.
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = vec![];
+
+        for (env, profile) in self.profiles.iter() {
+            check_base(
+                &mut errors,
+                format!("profiles.{env}.baseURL"),
+                &profile.base_url,
+            );
+            check_base(
+                &mut errors,
+                format!("profiles.{env}.cdnBase"),
+                &profile.cdn_base,
+            );
+            if let Some(base) = &profile.asset_base {
+                check_base(&mut errors, format!("profiles.{env}.assetBase"), base);
+            }
+            if let Some(fallback) = &profile.asset_fallback {
+                check_base(
+                    &mut errors,
+                    format!("profiles.{env}.assetFallback"),
+                    fallback,
+                );
+            }
+        }
+        if self.cdn_bucket.is_empty() || self.cdn_bucket.contains('/') {
+            errors.push(ValidationError::new(
+                "cdnBucket",
+                format!("{:?} is not a valid bucket name", self.cdn_bucket),
+            ));
+        }
+        if self.excerpt_words == 0 {
+            errors.push(ValidationError::new("excerptWords", "must be at least 1"));
+        }
+        if self.posts_per_page == 0 {
+            errors.push(ValidationError::new("postsPerPage", "must be at least 1"));
+        }
+        if !self.image_widths.windows(2).all(|w| w[0] < w[1]) || self.image_widths.contains(&0) {
+            errors.push(ValidationError::new(
+                "imageWidths",
+                "must be positive and smallest first",
+            ));
+        }
+        if let Some(publisher) = &self.ad_publisher {
+            if publisher.trim().is_empty() {
+                errors.push(ValidationError::new(
+                    "adPublisher",
+                    "is empty, use None to disable ads",
+                ));
+            }
+        }
+        check_url(
+            &mut errors,
+            "donation.patreonURL",
+            &self.donation.patreon_url,
+        );
+        if self.donation.addresses.iter().any(|a| a.trim().is_empty()) {
+            errors.push(ValidationError::new(
+                "donation.addresses",
+                "contains an empty address",
+            ));
+        }
+
+        let handle: Vec<&str> = self.mastodon.handle.split('@').collect();
+        if handle.len() != 3
+            || !handle[0].is_empty()
+            || handle[1].is_empty()
+            || handle[2].is_empty()
+        {
+            errors.push(ValidationError::new(
+                "mastodon.handle",
+                format!(
+                    "{:?} should look like @user@instance.example",
+                    self.mastodon.handle
+                ),
+            ));
+        }
+        check_url(&mut errors, "mastodon.actor", &self.mastodon.actor);
+        check_url(&mut errors, "mastodon.url", &self.mastodon.url);
+
+        if self.feed_title.trim().is_empty() {
+            errors.push(ValidationError::new("feedTitle", "must not be empty"));
+        }
+        if self.branding.name.trim().is_empty() {
+            errors.push(ValidationError::new("branding.name", "must not be empty"));
+        }
+        for (field, color) in [
+            ("branding.themeColor", &self.branding.theme_color),
+            ("branding.themeColorLight", &self.branding.theme_color_light),
+            ("branding.backgroundColor", &self.branding.background_color),
+        ] {
+            if parse_color(color).is_none() {
+                errors.push(ValidationError::new(
+                    field,
+                    format!("{color:?} should look like #rrggbb"),
+                ));
+            }
+        }
+        if let Some(url) = &self.signing.pgp_key_url {
+            if !url.starts_with('/') {
+                check_url(&mut errors, "signing.pgpKeyURL", url);
+            }
+        }
+        if let Some(onion) = &self.onion {
+            if !onion.address.ends_with(".onion") || onion.address.contains('/') {
+                errors.push(ValidationError::new(
+                    "onion.address",
+                    format!("{:?} should look like xxxx.onion", onion.address),
+                ));
+            }
+        }
+        for (i, sponsor) in self.sponsors.iter().enumerate() {
+            check_url(&mut errors, format!("sponsors[{i}].url"), &sponsor.url);
+            if !sponsor.logo.starts_with('/') {
+                check_url(&mut errors, format!("sponsors[{i}].logo"), &sponsor.logo);
+            }
+        }
+        if self.characters_manifest.is_empty() {
+            errors.push(ValidationError::new(
+                "charactersManifest",
+                "must not be empty",
+            ));
+        }
+        if self.media_manifest.is_empty() {
+            errors.push(ValidationError::new("mediaManifest", "must not be empty"));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_is_valid() {
+        assert_eq!(SiteConfig::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let mut cfg = SiteConfig::default();
+        cfg.profiles.staging.cdn_base = "cdn.xeiaso.net/".into();
+        cfg.mastodon.handle = "cadey".into();
+
+        let errors = cfg.validate().unwrap_err();
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            vec![
+                "profiles.staging.cdnBase",
+                "profiles.staging.cdnBase",
+                "mastodon.handle"
+            ]
+        );
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!(parse_color("#1d2021"), Some([0x1d, 0x20, 0x21]));
+        assert_eq!(parse_color("1d2021"), None);
+        assert_eq!(parse_color("#fff"), None);
+        assert_eq!(parse_color("#gggggg"), None);
+    }
+
+    #[test]
+    fn cdn() {
+        let mut cfg = SiteConfig::default();
+        assert_eq!(
+            cfg.cdn("hero/foo.avif"),
+            "https://cdn.xeiaso.net/file/christine-static/hero/foo.avif"
+        );
+        assert_eq!(
+            cfg.sticker("mara", "hacker", 64),
+            "https://cdn.xeiaso.net/sticker/mara/hacker/64"
+        );
+
+        cfg.profiles.prod.asset_base = Some("http://localhost:8080/mirror".into());
+        assert_eq!(cfg.validate(), Ok(()));
+        assert_eq!(
+            cfg.cdn("hero/foo.avif"),
+            "http://localhost:8080/mirror/hero/foo.avif"
+        );
+        assert_eq!(
+            cfg.sticker("mara", "hacker", 64),
+            "http://localhost:8080/mirror/stickers/mara/hacker.png"
+        );
+
+        cfg.profiles.prod.asset_base = Some("/mirror/".into());
+        assert_eq!(cfg.validate().unwrap_err().len(), 2);
+    }
+
+    #[test]
+    fn asset_fallback() {
+        let mut cfg = SiteConfig::default();
+        assert_eq!(cfg.asset_fallback(), None);
+
+        cfg.profiles.prod.asset_fallback =
+            Some("https://f001.backblazeb2.com/file/christine-static".into());
+        assert_eq!(cfg.validate(), Ok(()));
+        assert_eq!(
+            cfg.asset_fallback(),
+            Some(AssetFallback {
+                primary: "https://cdn.xeiaso.net/file/christine-static".into(),
+                stickers: Some("https://cdn.xeiaso.net/sticker".into()),
+                fallback: "https://f001.backblazeb2.com/file/christine-static".into(),
+            })
+        );
+
+        cfg.profiles.prod.asset_base = Some("http://localhost:8080".into());
+        assert_eq!(cfg.asset_fallback().unwrap().stickers, None);
+
+        cfg.profiles.prod.asset_fallback = Some("backblaze".into());
+        assert_eq!(cfg.validate().unwrap_err().len(), 1);
+    }
+
+    #[test]
+    fn profiles() {
+        let mut cfg = SiteConfig::default();
+        assert_eq!(cfg.ads(), Some("christinewebsite"));
+        assert_eq!(cfg.url("/blog"), "https://xeiaso.net/blog");
+
+        cfg.environment = "dev".parse().unwrap();
+        assert_eq!(cfg.ads(), None);
+        assert!(!cfg.analytics());
+        assert!(cfg.deterministic_ids());
+        assert_eq!(cfg.url("blog"), "http://localhost:3030/blog");
+
+        assert!("qa".parse::<Environment>().is_err());
+    }
+}
@@ -0,0 +1,208 @@
+//! Sending activities to other servers' inboxes. Activities are queued and
+//! sent by one worker, [CONCURRENCY] requests at a time, so sending a post to
+//! thousands of followers doesn't tie everything else up. Failed deliveries
+//! are tried again later with exponential [backoff].
+//!
+//! The queue is only kept in memory, so retries that are still waiting are
+//! lost when the server restarts.
+
+use super::signature;
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use color_eyre::eyre::Result;
+use futures::StreamExt;
+use lazy_static::lazy_static;
+use prometheus::{opts, register_int_counter_vec, register_int_gauge, IntCounterVec, IntGauge};
+use serde_json::Value;
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{sync::Notify, time::Instant};
+use xesite_httpsig::PrivateKey;
+
+lazy_static! {
+    static ref DELIVERIES: IntCounterVec = register_int_counter_vec!(
+        opts!(
+            "activitypub_deliveries",
+            "Number of attempts to deliver activities to other servers"
+        ),
+        &["result"]
+    )
+    .unwrap();
+    static ref QUEUED: IntGauge = register_int_gauge!(opts!(
+        "activitypub_delivery_queue",
+        "Number of deliveries waiting to be sent or tried again"
+    ))
+    .unwrap();
+}
+
+/// How many deliveries are in flight at once.
+pub const CONCURRENCY: usize = 16;
+
+/// How many times a delivery is tried before giving up on it.
+pub const MAX_ATTEMPTS: u32 = 8;
+
+/// How long to wait before trying a delivery again after `attempt` failed
+/// tries: a minute, then two, then four, and so on.
+pub fn backoff(attempt: u32) -> Duration {
+    Duration::from_secs(60 << attempt.saturating_sub(1).min(10))
+}
+
+/// Whether a delivery that got `status` back could work later. Servers that
+/// say the request itself is bad get it once.
+pub fn retryable(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::REQUEST_TIMEOUT
+}
+
+struct Job {
+    inbox: String,
+    body: Arc<Vec<u8>>,
+    /// How many times this has failed so far.
+    attempt: u32,
+    due: Instant,
+}
+
+pub struct Queue {
+    jobs: Mutex<Vec<Job>>,
+    wake: Notify,
+    client: reqwest::Client,
+    key: PrivateKey,
+    key_id: String,
+}
+
+impl Queue {
+    pub fn new(key: PrivateKey, key_id: String) -> Result<Self> {
+        Ok(Self {
+            jobs: Mutex::new(Vec::new()),
+            wake: Notify::new(),
+            client: reqwest::Client::builder()
+                .user_agent(crate::APPLICATION_NAME)
+                .timeout(Duration::from_secs(30))
+                .build()?,
+            key,
+            key_id,
+        })
+    }
+
+    /// Queues `activity` for every inbox in `inboxes`. It's only serialized
+    /// once no matter how many inboxes there are.
+    pub fn push(&self, inboxes: impl IntoIterator<Item = String>, activity: &Value) -> Result<()> {
+        let body = Arc::new(serde_json::to_vec(activity)?);
+        let now = Instant::now();
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.extend(inboxes.into_iter().map(|inbox| Job {
+            inbox,
+            body: body.clone(),
+            attempt: 0,
+            due: now,
+        }));
+        QUEUED.set(jobs.len() as i64);
+        drop(jobs);
+        self.wake.notify_one();
+        Ok(())
+    }
+
+    /// How many deliveries are waiting to be sent or retried.
+    pub fn queued(&self) -> usize {
+        self.jobs.lock().unwrap().len()
+    }
+
+    /// Takes the jobs that are due, and says when the next one after them is.
+    fn due(&self) -> (Vec<Job>, Option<Instant>) {
+        let now = Instant::now();
+        let mut jobs = self.jobs.lock().unwrap();
+        let (due, later): (Vec<Job>, Vec<Job>) = jobs.drain(..).partition(|job| job.due <= now);
+        *jobs = later;
+        let next = jobs.iter().map(|job| job.due).min();
+        (due, next)
+    }
+
+    /// Sends everything that's queued, forever.
+    pub async fn run(self: Arc<Self>) {
+        loop {
+            let (due, next) = self.due();
+            futures::stream::iter(due)
+                .for_each_concurrent(CONCURRENCY, |job| self.deliver(job))
+                .await;
+            QUEUED.set(self.jobs.lock().unwrap().len() as i64);
+
+            let wait = next
+                .map(|next| next.saturating_duration_since(Instant::now()))
+                .unwrap_or(Duration::from_secs(60 * 60));
+            tokio::select! {
+                _ = self.wake.notified() => {}
+                _ = tokio::time::sleep(wait) => {}
+            }
+        }
+    }
+
+    async fn deliver(&self, mut job: Job) {
+        let why = match self.send(&job.inbox, &job.body).await {
+            Ok(()) => {
+                DELIVERIES.with_label_values(&["ok"]).inc();
+                return;
+            }
+            Err(why) => why,
+        };
+
+        job.attempt += 1;
+        let retry = why
+            .downcast_ref::<reqwest::Error>()
+            .map(|why| why.status().map(retryable).unwrap_or(true))
+            .unwrap_or(false);
+        if retry && job.attempt < MAX_ATTEMPTS {
+            warn!(
+                "can't deliver to {} (try {}): {why}",
+                job.inbox, job.attempt
+            );
+            DELIVERIES.with_label_values(&["retry"]).inc();
+            job.due = Instant::now() + backoff(job.attempt);
+            self.jobs.lock().unwrap().push(job);
+        } else {
+            error!("giving up on delivering to {}: {why}", job.inbox);
+            DELIVERIES.with_label_values(&["failed"]).inc();
+        }
+    }
+
+    async fn send(&self, inbox: &str, body: &[u8]) -> Result<()> {
+        let url = url::Url::parse(inbox)?;
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "content-type",
+            HeaderValue::from_static(super::CONTENT_TYPE),
+        );
+        signature::sign(&self.key, &self.key_id, &url, body, &mut headers)?;
+
+        self.client
+            .post(url)
+            .headers(headers)
+            .body(body.to_vec())
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles() {
+        assert_eq!(backoff(1), Duration::from_secs(60));
+        assert_eq!(backoff(2), Duration::from_secs(120));
+        assert_eq!(backoff(4), Duration::from_secs(480));
+        assert_eq!(backoff(40), backoff(11));
+    }
+
+    #[test]
+    fn retries() {
+        assert!(retryable(StatusCode::BAD_GATEWAY));
+        assert!(retryable(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!retryable(StatusCode::UNAUTHORIZED));
+        assert!(!retryable(StatusCode::GONE));
+    }
+}
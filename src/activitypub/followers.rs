@@ -0,0 +1,135 @@
+//! Who follows the site's actor. Kept in the database, see
+//! [crate::storage::Followers], and saved every time someone follows or
+//! unfollows.
+
+use crate::storage;
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+use tokio::sync::RwLock;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Follower {
+    pub id: String,
+    pub inbox: String,
+    /// The inbox of their whole server, if it has one.
+    #[serde(rename = "sharedInbox")]
+    pub shared_inbox: Option<String>,
+}
+
+impl Follower {
+    pub fn from_actor(actor: &Value) -> Option<Self> {
+        Some(Self {
+            id: actor["id"].as_str()?.to_string(),
+            inbox: actor["inbox"].as_str()?.to_string(),
+            shared_inbox: actor["endpoints"]["sharedInbox"]
+                .as_str()
+                .map(str::to_string),
+        })
+    }
+}
+
+/// The inboxes to send something to every follower, so each server gets it
+/// once through its shared inbox instead of once per follower there.
+pub fn inboxes<'a>(followers: impl IntoIterator<Item = &'a Follower>) -> Vec<String> {
+    followers
+        .into_iter()
+        .map(|f| f.shared_inbox.as_ref().unwrap_or(&f.inbox).clone())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// The followers in the database, with a copy in memory for counting them
+/// and sending them posts.
+pub struct Followers {
+    db: storage::Followers,
+    followers: RwLock<BTreeMap<String, Follower>>,
+}
+
+impl Followers {
+    /// Loads the followers in `db`. Ones that were saved to `legacy` before
+    /// there was a database are moved into it.
+    pub async fn new(db: storage::Followers, legacy: &Path) -> Result<Self> {
+        let imported = storage::import_json::<BTreeMap<String, Follower>>(legacy).await?;
+        if let Some(followers) = imported {
+            if db.is_empty().await? {
+                db.add(followers.into_values().collect()).await?;
+            }
+        }
+
+        let followers = db.all().await?;
+        Ok(Self {
+            db,
+            followers: RwLock::new(followers),
+        })
+    }
+
+    pub async fn add(&self, follower: Follower) -> Result<()> {
+        let mut followers = self.followers.write().await;
+        if followers.get(&follower.id) == Some(&follower) {
+            return Ok(());
+        }
+        self.db.add(vec![follower.clone()]).await?;
+        followers.insert(follower.id.clone(), follower);
+        Ok(())
+    }
+
+    pub async fn remove(&self, id: &str) -> Result<()> {
+        let mut followers = self.followers.write().await;
+        if !followers.contains_key(id) {
+            return Ok(());
+        }
+        self.db.remove(id).await?;
+        followers.remove(id);
+        Ok(())
+    }
+
+    pub async fn count(&self) -> usize {
+        self.followers.read().await.len()
+    }
+
+    /// See [inboxes].
+    pub async fn inboxes(&self) -> Vec<String> {
+        inboxes(self.followers.read().await.values())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn follower(id: &str, shared_inbox: Option<&str>) -> Follower {
+        Follower {
+            id: id.to_string(),
+            inbox: format!("{id}/inbox"),
+            shared_inbox: shared_inbox.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn shared_inboxes_once() {
+        let followers = vec![
+            follower(
+                "https://pony.social/users/a",
+                Some("https://pony.social/inbox"),
+            ),
+            follower(
+                "https://pony.social/users/b",
+                Some("https://pony.social/inbox"),
+            ),
+            follower("https://tiny.example/users/c", None),
+        ];
+        assert_eq!(
+            inboxes(&followers),
+            vec![
+                "https://pony.social/inbox".to_string(),
+                "https://tiny.example/users/c/inbox".to_string(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,383 @@
+//! The site's own fediverse account. Readers can follow it, and likes, boosts
+//! and replies to posts come back to its inbox, where they're checked and kept
+//! in a [store::Store].
+//!
+//! Posts are their own ActivityPub objects: the ID of a post is its URL. New
+//! posts are sent to followers through the [delivery::Queue] when they're
+//! published, see [Actor::publish] and [crate::events].
+
+use crate::{app::config::ActivityPub, storage::Storage};
+use axum::http::{HeaderMap, Method, StatusCode};
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
+use serde_json::{json, Value};
+use std::{path::Path, sync::Arc};
+use url::Url;
+use xesite_httpsig::{PrivateKey, PublicKey};
+use xesite_templates::context::site;
+
+pub mod delivery;
+pub mod followers;
+pub mod outbox;
+pub mod signature;
+pub mod store;
+
+use delivery::Queue;
+use followers::{Follower, Followers};
+use signature::Signed;
+use store::{Profile, Store};
+
+pub const CONTENT_TYPE: &str = "application/activity+json";
+
+pub fn actor_id() -> String {
+    site().url("/activitypub/actor")
+}
+
+pub fn inbox_url() -> String {
+    site().url("/activitypub/inbox")
+}
+
+pub fn outbox_url() -> String {
+    site().url("/activitypub/outbox")
+}
+
+pub fn followers_url() -> String {
+    site().url("/activitypub/followers")
+}
+
+pub fn key_id() -> String {
+    format!("{}#main-key", actor_id())
+}
+
+/// The link of the post an object ID refers to, if it's one of ours.
+/// `is_post` says whether a link is a post the site has, so activities about
+/// pages that don't exist aren't kept.
+pub fn post_link(id: &str, is_post: impl Fn(&str) -> bool) -> Option<String> {
+    let link = id.strip_prefix(&site().url("/"))?.trim_end_matches('/');
+    is_post(link).then(|| link.to_string())
+}
+
+fn same_origin(a: &str, b: &str) -> bool {
+    match (Url::parse(a), Url::parse(b)) {
+        (Ok(a), Ok(b)) => a.origin() == b.origin(),
+        _ => false,
+    }
+}
+
+/// Checks that `owner`, the actor document fetched for the key an activity
+/// was signed with, is the actor the activity says it's from and that the
+/// key is theirs. The key has to be on the actor's server, so nobody can
+/// serve a copy of someone else's actor with their own key in it.
+fn check_owner(claimed: &str, key_id: &str, owner: &Value) -> Result<(), Rejected> {
+    let key = &owner["publicKey"];
+    if !same_origin(claimed, key_id)
+        || owner["id"].as_str() != Some(claimed)
+        || key["id"].as_str() != Some(key_id)
+        || key["owner"].as_str().map_or(false, |o| o != claimed)
+    {
+        return Err(Rejected::Signature(eyre!(
+            "activity from {claimed} was signed by {key_id}"
+        )));
+    }
+    Ok(())
+}
+
+/// Why an activity sent to the inbox wasn't taken.
+#[derive(Debug, thiserror::Error)]
+pub enum Rejected {
+    #[error("bad signature: {0}")]
+    Signature(Report),
+    #[error("bad activity: {0}")]
+    Activity(String),
+    #[error(transparent)]
+    Internal(#[from] Report),
+}
+
+impl Rejected {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Rejected::Signature(_) => StatusCode::UNAUTHORIZED,
+            Rejected::Activity(_) => StatusCode::BAD_REQUEST,
+            Rejected::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+pub struct Actor {
+    pub cfg: ActivityPub,
+    public_key_pem: String,
+    pub store: Store,
+    pub followers: Followers,
+    pub queue: Arc<Queue>,
+    client: reqwest::Client,
+}
+
+impl Actor {
+    /// Loads the signing key, the followers and the interactions seen so far.
+    /// The key is made the first time. Returns None when the actor is turned
+    /// off.
+    pub async fn new(cfg: &ActivityPub, storage: &Storage) -> Result<Option<Self>> {
+        if !cfg.enabled {
+            return Ok(None);
+        }
+        let (key, new) = PrivateKey::load_or_generate(Path::new(&cfg.private_key_path), || {
+            PrivateKey::generate_rsa(2048)
+        })
+        .wrap_err_with(|| format!("can't load ActivityPub key {}", cfg.private_key_path))?;
+        if new {
+            warn!(
+                "made a new ActivityPub key at {}, keep it around",
+                cfg.private_key_path
+            );
+        }
+        let public_key_pem = key.public_key()?.to_pem()?;
+
+        Ok(Some(Self {
+            cfg: cfg.clone(),
+            public_key_pem,
+            store: Store::new(storage.interactions(), Path::new(&cfg.state_path)).await?,
+            followers: Followers::new(storage.followers(), Path::new(&cfg.followers_path)).await?,
+            queue: Arc::new(Queue::new(key, key_id())?),
+            client: reqwest::Client::builder()
+                .user_agent(crate::APPLICATION_NAME)
+                .redirect(crate::net::redirects())
+                .build()?,
+        }))
+    }
+
+    pub fn handle(&self) -> String {
+        format!("{}@{}", self.cfg.username, host())
+    }
+
+    /// The actor document.
+    pub fn document(&self) -> Value {
+        json!({
+            "@context": [
+                "https://www.w3.org/ns/activitystreams",
+                "https://w3id.org/security/v1",
+            ],
+            "id": actor_id(),
+            "type": "Service",
+            "preferredUsername": self.cfg.username,
+            "name": site().feed_title,
+            "summary": site().feed_description,
+            "url": site().url("/"),
+            "inbox": inbox_url(),
+            "outbox": outbox_url(),
+            "followers": followers_url(),
+            "endpoints": { "sharedInbox": inbox_url() },
+            "icon": { "type": "Image", "url": crate::app::ICON },
+            "publicKey": {
+                "id": key_id(),
+                "owner": actor_id(),
+                "publicKeyPem": self.public_key_pem,
+            },
+        })
+    }
+
+    /// The answer to a WebFinger lookup of `resource`, if it's this actor.
+    pub fn webfinger(&self, resource: &str) -> Option<Value> {
+        let handle = self.handle();
+        let wanted = resource.strip_prefix("acct:").unwrap_or(resource);
+        if wanted != handle && resource != actor_id() {
+            return None;
+        }
+        Some(json!({
+            "subject": format!("acct:{handle}"),
+            "aliases": [actor_id()],
+            "links": [{
+                "rel": "self",
+                "type": CONTENT_TYPE,
+                "href": actor_id(),
+            }],
+        }))
+    }
+
+    /// Fetches an ActivityPub document, if [crate::net::check] allows it.
+    async fn fetch(&self, url: &str) -> Result<reqwest::Response> {
+        crate::net::check(&Url::parse(url)?).await?;
+        Ok(self
+            .client
+            .get(url)
+            .header(reqwest::header::ACCEPT, CONTENT_TYPE)
+            .send()
+            .await?)
+    }
+
+    /// Checks the signature on an activity sent to the inbox, then follows it
+    /// up: follows are accepted, and everything else is applied to the posts
+    /// it's about. `is_post` is for [post_link].
+    #[instrument(skip(self, headers, body, is_post), err)]
+    pub async fn receive(
+        &self,
+        method: &Method,
+        path: &str,
+        headers: &HeaderMap,
+        body: &[u8],
+        is_post: &(dyn Fn(&str) -> bool + Sync),
+    ) -> Result<(), Rejected> {
+        let activity: Value = serde_json::from_slice(body)
+            .map_err(|why| Rejected::Activity(format!("not JSON: {why}")))?;
+        let claimed = store::object_id(&activity["actor"])
+            .ok_or_else(|| Rejected::Activity("no actor".into()))?;
+
+        let sig = Signed::check(headers, body, chrono::Utc::now())
+            .map_err(|why| Rejected::Signature(why.into()))?;
+
+        let key_url = sig.key_id().split('#').next().unwrap_or_default();
+        if !same_origin(key_url, claimed) {
+            return Err(Rejected::Signature(eyre!(
+                "activity from {claimed} was signed by {}",
+                sig.key_id()
+            )));
+        }
+        let resp = self
+            .fetch(key_url)
+            .await
+            .map_err(|why| Rejected::Signature(why.wrap_err("can't fetch the key")))?;
+        if resp.status() == StatusCode::GONE
+            && activity["type"] == "Delete"
+            && store::object_id(&activity["object"]) == Some(claimed)
+            && key_url == claimed
+        {
+            // Deleted accounts can't be looked up to check their signature,
+            // but their server saying they're gone is enough.
+            self.store.forget(claimed).await?;
+            self.followers.remove(claimed).await?;
+            return Ok(());
+        }
+        let owner: Value = resp
+            .error_for_status()
+            .map_err(Report::from)?
+            .json()
+            .await
+            .map_err(Report::from)?;
+
+        // Some servers serve the key on its own, with the actor it belongs to.
+        let owner = if owner["publicKey"].is_null() && owner["owner"].is_string() {
+            if owner["owner"].as_str() != Some(claimed) {
+                return Err(Rejected::Signature(eyre!(
+                    "{} belongs to {}, not {claimed}",
+                    sig.key_id(),
+                    owner["owner"]
+                )));
+            }
+            self.fetch(claimed)
+                .await?
+                .error_for_status()
+                .map_err(Report::from)?
+                .json()
+                .await
+                .map_err(Report::from)?
+        } else {
+            owner
+        };
+        check_owner(claimed, sig.key_id(), &owner)?;
+        let pem = owner["publicKey"]["publicKeyPem"]
+            .as_str()
+            .ok_or_else(|| Rejected::Signature(eyre!("{} has no public key", sig.key_id())))?;
+        PublicKey::from_pem(pem.as_bytes())
+            .and_then(|key| sig.verify(method, path, headers, &key))
+            .map_err(|why| Rejected::Signature(why.into()))?;
+
+        match activity["type"].as_str() {
+            Some("Follow")
+                if store::object_id(&activity["object"]) == Some(actor_id().as_str()) =>
+            {
+                let follower = Follower::from_actor(&owner)
+                    .ok_or_else(|| Rejected::Activity("actor has no inbox".into()))?;
+                let accept = json!({
+                    "@context": "https://www.w3.org/ns/activitystreams",
+                    "id": format!("{}#accepts/{}", actor_id(), uuid::Uuid::new_v4()),
+                    "type": "Accept",
+                    "actor": actor_id(),
+                    "object": activity,
+                });
+                self.queue.push([follower.inbox.clone()], &accept)?;
+                self.followers.add(follower).await?;
+            }
+            Some("Undo") if activity["object"]["type"] == "Follow" => {
+                self.followers.remove(claimed).await?;
+            }
+            _ => {
+                let profile = Profile::from_actor(&owner)
+                    .ok_or_else(|| Rejected::Activity("actor has no ID".into()))?;
+                self.store
+                    .apply(&activity, &profile, |id| post_link(id, is_post))
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Queues the posts followers haven't been sent yet.
+    pub async fn publish(&self, posts: &[crate::post::Post]) -> Result<()> {
+        let path = Path::new(&self.cfg.published_path);
+        let mut sent = outbox::Sent::load(path, posts).await?;
+        let unsent = sent.unsent(posts);
+        if unsent.is_empty() {
+            return Ok(());
+        }
+
+        let inboxes = self.followers.inboxes().await;
+        for post in unsent {
+            info!(
+                "sending {} to {} inboxes of {} followers",
+                post.link,
+                inboxes.len(),
+                self.followers.count().await
+            );
+            self.queue.push(inboxes.clone(), &outbox::create(post))?;
+            sent.links.insert(post.link.clone());
+        }
+        sent.save(path).await
+    }
+}
+
+/// The host of the site, as used in handles.
+fn host() -> String {
+    url::Url::parse(&site().url("/"))
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn post_links() {
+        let is_post = |link: &str| link == "blog/foo";
+        assert_eq!(
+            post_link(&site().url("/blog/foo"), is_post),
+            Some("blog/foo".to_string())
+        );
+        assert_eq!(post_link(&site().url("/blog/made-up"), is_post), None);
+        assert_eq!(post_link(&site().url("/"), is_post), None);
+        assert_eq!(post_link("https://pony.social/blog/foo", is_post), None);
+    }
+
+    #[test]
+    fn key_owners() {
+        let alice = "https://victim.social/users/alice";
+        let actor = |id: &str, key: &str, owner: &str| {
+            json!({
+                "id": id,
+                "publicKey": { "id": key, "owner": owner, "publicKeyPem": "..." },
+            })
+        };
+        let key = format!("{alice}#main-key");
+        assert!(check_owner(alice, &key, &actor(alice, &key, alice)).is_ok());
+
+        // A copy of Alice's actor with someone else's key, served elsewhere.
+        let spoofed = actor(alice, "https://evil.example/actor#k", alice);
+        assert!(check_owner(alice, "https://evil.example/actor#k", &spoofed).is_err());
+        // Alice's server, but the document is for someone else.
+        let bob = "https://victim.social/users/bob";
+        assert!(check_owner(alice, &key, &actor(bob, &key, bob)).is_err());
+        // A key that isn't the one on the actor, or belongs to someone else.
+        let other = format!("{bob}#main-key");
+        assert!(check_owner(alice, &other, &actor(alice, &key, alice)).is_err());
+        assert!(check_owner(alice, &key, &actor(alice, &key, bob)).is_err());
+    }
+}
@@ -0,0 +1,142 @@
+//! Posts as ActivityPub objects, and which of them followers have been sent.
+
+use super::{actor_id, followers_url};
+use crate::post::Post;
+use chrono::prelude::*;
+use color_eyre::eyre::Result;
+use maud::{html, PreEscaped};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{collections::BTreeSet, path::Path};
+use tokio::fs;
+use xesite_templates::context::site;
+
+/// How many of the latest posts the outbox lists.
+pub const OUTBOX_SIZE: usize = 20;
+
+/// Posts that are out and should go to followers.
+pub fn published(posts: &[Post]) -> impl Iterator<Item = &Post> {
+    let today = Utc::now().date_naive();
+    posts
+        .iter()
+        .filter(move |p| p.listed() && p.date.date_naive() <= today)
+        .filter(|p| p.front_matter.redirect_to.is_none())
+}
+
+/// A post as a Note with its title, excerpt and a link to the rest of it.
+/// It's only addressed to followers.
+pub fn note(post: &Post) -> Value {
+    let url = site().url(&post.link);
+    let content = html! {
+        p { a href=(url) { (post.front_matter.title) } }
+        (PreEscaped(&post.excerpt))
+    };
+
+    json!({
+        "id": url,
+        "type": "Note",
+        "attributedTo": actor_id(),
+        "published": post.date.to_rfc3339(),
+        "url": url,
+        "name": post.front_matter.title,
+        "content": content.into_string(),
+        "to": [followers_url()],
+        "tag": post
+            .front_matter
+            .tags
+            .iter()
+            .flatten()
+            .map(|tag| json!({ "type": "Hashtag", "name": format!("#{tag}") }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+pub fn create(post: &Post) -> Value {
+    let note = note(post);
+    json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": format!("{}#create", note["id"].as_str().unwrap_or_default()),
+        "type": "Create",
+        "actor": actor_id(),
+        "published": note["published"],
+        "to": [followers_url()],
+        "object": note,
+    })
+}
+
+/// The outbox collection, with the latest posts.
+pub fn collection(posts: &[Post]) -> Value {
+    let items: Vec<Value> = published(posts).take(OUTBOX_SIZE).map(create).collect();
+    json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": super::outbox_url(),
+        "type": "OrderedCollection",
+        "totalItems": published(posts).count(),
+        "orderedItems": items,
+    })
+}
+
+/// Links of the posts that have been sent to followers.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Sent {
+    pub links: BTreeSet<String>,
+}
+
+impl Sent {
+    /// Loads what's been sent so far. The first time, every post that's out
+    /// counts as sent, so followers don't get the whole archive at once.
+    pub async fn load(path: &Path, posts: &[Post]) -> Result<Self> {
+        match fs::read(path).await {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(_) => {
+                let sent = Self {
+                    links: published(posts).map(|p| p.link.clone()).collect(),
+                };
+                sent.save(path).await?;
+                Ok(sent)
+            }
+        }
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(path, serde_json::to_vec(self)?).await?;
+        Ok(())
+    }
+
+    /// Posts that are out but haven't been sent yet, oldest first.
+    pub fn unsent<'a>(&self, posts: &'a [Post]) -> Vec<&'a Post> {
+        let mut result: Vec<&Post> = published(posts)
+            .filter(|p| !self.links.contains(&p.link))
+            .collect();
+        result.reverse();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn notes_for_posts() -> Result<()> {
+        let posts = crate::post::load("blog").await?;
+        let post = published(&posts).next().unwrap();
+
+        let create = create(post);
+        assert_eq!(create["object"]["id"], site().url(&post.link));
+        assert_eq!(create["to"][0], followers_url());
+        assert!(create["object"]["content"]
+            .as_str()
+            .unwrap()
+            .contains(&site().url(&post.link)));
+
+        let sent = Sent {
+            links: published(&posts).skip(1).map(|p| p.link.clone()).collect(),
+        };
+        assert_eq!(sent.unsent(&posts), vec![post]);
+        Ok(())
+    }
+}
@@ -0,0 +1,212 @@
+//! Which HTTP signatures the inbox takes, and how the site signs what it
+//! sends, on top of [xesite_httpsig]. Activities sent to the inbox can be
+//! signed the draft-cavage-http-signatures way like Mastodon does, or with
+//! RFC 9421. What the site sends is signed the cavage way, since every server
+//! can check that.
+
+use axum::http::{HeaderMap, HeaderValue, Method, Uri};
+use chrono::prelude::*;
+use xesite_httpsig::{cavage, rfc9421, Error, PrivateKey, PublicKey, Result};
+
+/// How far off the date of a signed request can be from now, in seconds.
+pub const MAX_SKEW_SECS: i64 = 12 * 60 * 60;
+
+/// The headers every cavage signature on a POST to the inbox has to cover.
+pub const REQUIRED: &[&str] = &["(request-target)", "host", "date", "digest"];
+
+/// The components every RFC 9421 signature on a POST to the inbox has to
+/// cover.
+pub const REQUIRED_RFC9421: &[&str] = &["@method", "@target-uri", "content-digest"];
+
+/// The signature on a request to the inbox, before it's been checked against
+/// the sender's key.
+#[derive(Debug)]
+pub enum Signed {
+    Cavage(cavage::Signature),
+    Rfc9421(rfc9421::Signature),
+}
+
+impl Signed {
+    /// Reads the signature on a request and checks everything that doesn't need
+    /// the sender's key: that it covers what it has to, that it's recent and
+    /// that the digest matches the body.
+    pub fn check(headers: &HeaderMap, body: &[u8], now: DateTime<Utc>) -> Result<Self> {
+        if headers.contains_key("signature-input") {
+            let sig = rfc9421::signatures(headers)?
+                .into_iter()
+                .next()
+                .ok_or_else(|| Error::Missing("signature".into()))?;
+            if sig.input.keyid().is_none() {
+                return Err(Error::Missing("keyid parameter".into()));
+            }
+            sig.require(REQUIRED_RFC9421)?;
+            sig.check_age(now.timestamp(), MAX_SKEW_SECS)?;
+            xesite_httpsig::check_content_digest(headers, body)?;
+            return Ok(Signed::Rfc9421(sig));
+        }
+
+        let header = headers
+            .get("signature")
+            .ok_or_else(|| Error::Missing("Signature header".into()))?
+            .to_str()?;
+        let sig = cavage::Signature::parse(header)?;
+        sig.require(REQUIRED)?;
+        xesite_httpsig::check_digest(headers, body)?;
+        xesite_httpsig::check_date(headers, now, MAX_SKEW_SECS)?;
+        Ok(Signed::Cavage(sig))
+    }
+
+    pub fn key_id(&self) -> &str {
+        match self {
+            Signed::Cavage(sig) => &sig.key_id,
+            Signed::Rfc9421(sig) => sig.input.keyid().unwrap_or_default(),
+        }
+    }
+
+    /// Checks the signature against the sender's key. `path` is the path and
+    /// query the request was sent to.
+    pub fn verify(
+        &self,
+        method: &Method,
+        path: &str,
+        headers: &HeaderMap,
+        key: &PublicKey,
+    ) -> Result<()> {
+        match self {
+            Signed::Cavage(sig) => sig.verify(method, path, headers, key),
+            Signed::Rfc9421(sig) => {
+                let uri = target_uri(headers, path)?;
+                let msg = rfc9421::Message {
+                    method,
+                    uri: &uri,
+                    headers,
+                };
+                sig.verify(&msg, key)
+            }
+        }
+    }
+}
+
+/// The URI a request was sent to, on the host the sender asked for.
+fn target_uri(headers: &HeaderMap, path: &str) -> Result<Uri> {
+    let host = headers
+        .get("host")
+        .ok_or_else(|| Error::Missing("Host header".into()))?
+        .to_str()?;
+    format!("https://{host}{path}")
+        .parse()
+        .map_err(|_| Error::Malformed(format!("request target {host}{path}")))
+}
+
+/// Adds the Host, Date, Digest and Signature headers a POST of `body` to `url`
+/// needs for other servers to take it.
+pub fn sign(
+    key: &PrivateKey,
+    key_id: &str,
+    url: &url::Url,
+    body: &[u8],
+    headers: &mut HeaderMap,
+) -> Result<()> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| Error::Malformed(format!("URL {url} without a host")))?;
+    let host = match url.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host.to_string(),
+    };
+
+    headers.insert("host", HeaderValue::from_str(&host)?);
+    headers.insert(
+        "date",
+        HeaderValue::from_str(&xesite_httpsig::date(Utc::now()))?,
+    );
+    headers.insert(
+        "digest",
+        HeaderValue::from_str(&xesite_httpsig::digest(body))?,
+    );
+    let path = &url[url::Position::BeforePath..];
+    cavage::sign(key, key_id, &Method::POST, path, headers, REQUIRED)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xesite_httpsig::rfc9421::{Input, Item};
+
+    const KEY_ID: &str = "https://pony.social/users/mara#main-key";
+    const BODY: &[u8] = br#"{"type":"Like"}"#;
+
+    fn inbox() -> url::Url {
+        "https://xeiaso.net/activitypub/inbox".parse().unwrap()
+    }
+
+    #[test]
+    fn cavage_round_trip() {
+        let key = PrivateKey::generate_rsa(2048).unwrap();
+        let mut headers = HeaderMap::new();
+        sign(&key, KEY_ID, &inbox(), BODY, &mut headers).unwrap();
+        assert_eq!(headers["host"], "xeiaso.net");
+
+        let sig = Signed::check(&headers, BODY, Utc::now()).unwrap();
+        assert_eq!(sig.key_id(), KEY_ID);
+        let public = key.public_key().unwrap();
+        sig.verify(&Method::POST, "/activitypub/inbox", &headers, &public)
+            .unwrap();
+        assert!(sig
+            .verify(&Method::POST, "/activitypub/outbox", &headers, &public)
+            .is_err());
+
+        assert!(Signed::check(&headers, b"{}", Utc::now()).is_err());
+        assert!(Signed::check(&headers, BODY, Utc::now() + chrono::Duration::days(1)).is_err());
+    }
+
+    #[test]
+    fn rfc9421_round_trip() {
+        let key = PrivateKey::generate_ed25519().unwrap();
+        let now = Utc::now();
+        let uri: Uri = inbox().as_str().parse().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("host", HeaderValue::from_static("xeiaso.net"));
+        headers.insert(
+            "content-digest",
+            HeaderValue::from_str(&xesite_httpsig::content_digest(BODY)).unwrap(),
+        );
+        let msg = rfc9421::Message {
+            method: &Method::POST,
+            uri: &uri,
+            headers: &headers,
+        };
+        let input = Input::new(REQUIRED_RFC9421)
+            .with("created", Item::Integer(now.timestamp()))
+            .with("keyid", Item::String(KEY_ID.into()));
+        let sig = rfc9421::sign(&key, "sig1", &msg, input).unwrap();
+        sig.insert(&mut headers).unwrap();
+
+        let sig = Signed::check(&headers, BODY, now).unwrap();
+        assert!(matches!(sig, Signed::Rfc9421(_)));
+        assert_eq!(sig.key_id(), KEY_ID);
+        sig.verify(
+            &Method::POST,
+            "/activitypub/inbox",
+            &headers,
+            &key.public_key().unwrap(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn requires_coverage() {
+        let key = PrivateKey::generate_ed25519().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "date",
+            HeaderValue::from_str(&xesite_httpsig::date(Utc::now())).unwrap(),
+        );
+        cavage::sign(&key, KEY_ID, &Method::POST, "/", &mut headers, &["date"]).unwrap();
+        assert!(matches!(
+            Signed::check(&headers, BODY, Utc::now()),
+            Err(Error::NotCovered(_))
+        ));
+    }
+}
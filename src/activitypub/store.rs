@@ -0,0 +1,430 @@
+//! Likes, boosts and replies to posts, as sent to the inbox. They're kept in
+//! the database, see [crate::storage::PostInteractions], and saved every time
+//! something changes.
+
+use crate::storage::PostInteractions;
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+use tokio::sync::RwLock;
+
+/// Who sent an activity, from their actor document.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Profile {
+    pub id: String,
+    pub name: String,
+    /// Their profile page, or their ID if they don't have one.
+    pub url: String,
+    pub icon: Option<String>,
+}
+
+impl Profile {
+    pub fn from_actor(actor: &Value) -> Option<Self> {
+        let id = actor["id"].as_str()?.to_string();
+        let name = actor["name"]
+            .as_str()
+            .filter(|name| !name.is_empty())
+            .or_else(|| actor["preferredUsername"].as_str())
+            .unwrap_or(id.as_str())
+            .to_string();
+        Some(Self {
+            url: actor["url"].as_str().unwrap_or(id.as_str()).to_string(),
+            icon: actor["icon"]["url"].as_str().map(str::to_string),
+            id,
+            name,
+        })
+    }
+}
+
+/// A like or a boost.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reaction {
+    /// The ID of the Like or Announce, so it can be undone.
+    pub id: String,
+    pub actor: Profile,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reply {
+    /// The ID of the Note.
+    pub id: String,
+    pub actor: Profile,
+    /// Sanitized HTML.
+    pub content: String,
+    pub url: String,
+    pub published: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Interactions {
+    pub likes: Vec<Reaction>,
+    pub boosts: Vec<Reaction>,
+    pub replies: Vec<Reply>,
+}
+
+impl Interactions {
+    pub fn is_empty(&self) -> bool {
+        self.likes.is_empty() && self.boosts.is_empty() && self.replies.is_empty()
+    }
+
+    /// Drops everything by one actor, such as when their account is deleted.
+    fn forget(&mut self, actor: &str) -> bool {
+        let before = self.likes.len() + self.boosts.len() + self.replies.len();
+        self.likes.retain(|r| r.actor.id != actor);
+        self.boosts.retain(|r| r.actor.id != actor);
+        self.replies.retain(|r| r.actor.id != actor);
+        before != self.likes.len() + self.boosts.len() + self.replies.len()
+    }
+}
+
+/// Interactions by the link of the post they're about.
+pub type Posts = BTreeMap<String, Interactions>;
+
+/// The ID of an object the way activities refer to it, either as a bare ID or
+/// as the object itself.
+pub fn object_id(object: &Value) -> Option<&str> {
+    object.as_str().or_else(|| object["id"].as_str())
+}
+
+/// Applies an activity sent by `actor`. `post_link` turns an object ID into the
+/// link of the post it's about, if it's about one. Returns true if anything
+/// changed.
+pub fn apply(
+    posts: &mut Posts,
+    activity: &Value,
+    actor: &Profile,
+    post_link: impl Fn(&str) -> Option<String>,
+) -> bool {
+    let Some(id) = activity["id"].as_str() else {
+        return false;
+    };
+    let object = &activity["object"];
+
+    match activity["type"].as_str() {
+        Some(kind @ ("Like" | "Announce")) => {
+            let Some(link) = object_id(object).and_then(&post_link) else {
+                return false;
+            };
+            let interactions = posts.entry(link).or_default();
+            let reactions = match kind {
+                "Like" => &mut interactions.likes,
+                _ => &mut interactions.boosts,
+            };
+            if reactions.iter().any(|r| r.actor.id == actor.id) {
+                return false;
+            }
+            reactions.push(Reaction {
+                id: id.to_string(),
+                actor: actor.clone(),
+            });
+            true
+        }
+        Some("Create") => {
+            let Some(link) = object["inReplyTo"].as_str().and_then(&post_link) else {
+                return false;
+            };
+            let (Some(note), Some(content)) = (object_id(object), object["content"].as_str())
+            else {
+                return false;
+            };
+            let replies = &mut posts.entry(link).or_default().replies;
+            if replies.iter().any(|r| r.id == note) {
+                return false;
+            }
+            replies.push(Reply {
+                id: note.to_string(),
+                actor: actor.clone(),
+                content: xesite_templates::sanitize_toot(content),
+                url: object["url"].as_str().unwrap_or(note).to_string(),
+                published: object["published"].as_str().map(str::to_string),
+            });
+            true
+        }
+        Some("Delete") => {
+            let Some(target) = object_id(object) else {
+                return false;
+            };
+            if target == actor.id {
+                return posts
+                    .values_mut()
+                    .fold(false, |changed, i| i.forget(&actor.id) || changed);
+            }
+            let mut changed = false;
+            for interactions in posts.values_mut() {
+                let before = interactions.replies.len();
+                interactions
+                    .replies
+                    .retain(|r| !(r.id == target && r.actor.id == actor.id));
+                changed |= before != interactions.replies.len();
+            }
+            changed
+        }
+        Some("Undo") => {
+            let Some(target) = object_id(object) else {
+                return false;
+            };
+            let mut changed = false;
+            for interactions in posts.values_mut() {
+                for reactions in [&mut interactions.likes, &mut interactions.boosts] {
+                    let before = reactions.len();
+                    reactions.retain(|r| !(r.id == target && r.actor.id == actor.id));
+                    changed |= before != reactions.len();
+                }
+            }
+            changed
+        }
+        _ => false,
+    }
+}
+
+/// The newest `n` replies to any post, with the link of the post each one is
+/// about. Replies without a date come last.
+pub fn recent_replies(posts: &Posts, n: usize) -> Vec<(String, Reply)> {
+    let mut replies: Vec<(String, Reply)> = posts
+        .iter()
+        .flat_map(|(link, i)| i.replies.iter().map(|r| (link.clone(), r.clone())))
+        .collect();
+    replies.sort_by(|a, b| b.1.published.cmp(&a.1.published));
+    replies.truncate(n);
+    replies
+}
+
+/// Takes a reply down, such as when it's spam. Returns true if it was there.
+pub fn remove_reply(posts: &mut Posts, id: &str) -> bool {
+    posts.values_mut().fold(false, |changed, i| {
+        let before = i.replies.len();
+        i.replies.retain(|r| r.id != id);
+        changed || before != i.replies.len()
+    })
+}
+
+/// The posts whose interactions are different in `after`, with what they are
+/// now.
+fn changed(before: &Posts, after: &Posts) -> Vec<(String, Interactions)> {
+    let links: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    links
+        .into_iter()
+        .filter(|link| before.get(*link) != after.get(*link))
+        .map(|link| (link.clone(), after.get(link).cloned().unwrap_or_default()))
+        .collect()
+}
+
+/// The interactions in the database, with a copy in memory so posts can show
+/// them without waiting on it.
+pub struct Store {
+    db: PostInteractions,
+    posts: RwLock<Posts>,
+}
+
+impl Store {
+    /// Loads the interactions in `db`. Ones that were saved to `legacy` before
+    /// there was a database are moved into it.
+    pub async fn new(db: PostInteractions, legacy: &Path) -> Result<Self> {
+        if let Some(posts) = crate::storage::import_json::<Posts>(legacy).await? {
+            if db.is_empty().await? {
+                db.save(posts.into_iter().collect()).await?;
+            }
+        }
+
+        let posts = db.all().await?;
+        Ok(Self {
+            db,
+            posts: RwLock::new(posts),
+        })
+    }
+
+    /// Runs `f` on a copy of the interactions. If it changed anything, the
+    /// posts it changed are saved before the copy is kept, so the database
+    /// and memory never disagree. Returns true if anything changed.
+    async fn change(&self, f: impl FnOnce(&mut Posts) -> bool) -> Result<bool> {
+        let mut posts = self.posts.write().await;
+        let mut next = posts.clone();
+        if !f(&mut next) {
+            return Ok(false);
+        }
+        self.db.save(changed(&posts, &next)).await?;
+        *posts = next;
+        Ok(true)
+    }
+
+    /// Applies an activity and saves the result if it changed anything.
+    pub async fn apply(
+        &self,
+        activity: &Value,
+        actor: &Profile,
+        post_link: impl Fn(&str) -> Option<String>,
+    ) -> Result<()> {
+        self.change(|posts| apply(posts, activity, actor, post_link))
+            .await?;
+        Ok(())
+    }
+
+    /// Drops everything by an actor that doesn't exist any more.
+    pub async fn forget(&self, actor: &str) -> Result<()> {
+        self.change(|posts| {
+            posts
+                .values_mut()
+                .fold(false, |changed, i| i.forget(actor) || changed)
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// See [recent_replies].
+    pub async fn recent_replies(&self, n: usize) -> Vec<(String, Reply)> {
+        recent_replies(&*self.posts.read().await, n)
+    }
+
+    /// See [remove_reply].
+    pub async fn remove_reply(&self, id: &str) -> Result<bool> {
+        self.change(|posts| remove_reply(posts, id)).await
+    }
+
+    /// Everything sent about the post at `link`.
+    pub async fn get(&self, link: &str) -> Interactions {
+        self.posts
+            .read()
+            .await
+            .get(link)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn mara() -> Profile {
+        Profile::from_actor(&json!({
+            "id": "https://pony.social/users/mara",
+            "preferredUsername": "mara",
+            "url": "https://pony.social/@mara",
+        }))
+        .unwrap()
+    }
+
+    fn post_link(id: &str) -> Option<String> {
+        id.strip_prefix("https://xeiaso.net/").map(str::to_string)
+    }
+
+    #[test]
+    fn like_and_undo() {
+        let mut posts = Posts::new();
+        let like = json!({
+            "id": "https://pony.social/likes/1",
+            "type": "Like",
+            "object": "https://xeiaso.net/blog/foo",
+        });
+        assert!(apply(&mut posts, &like, &mara(), post_link));
+        assert!(!apply(&mut posts, &like, &mara(), post_link));
+        assert_eq!(posts["blog/foo"].likes[0].actor.name, "mara");
+
+        let undo = json!({
+            "id": "https://pony.social/undo/1",
+            "type": "Undo",
+            "object": like,
+        });
+        let someone_else = Profile {
+            id: "https://evil.example/users/mallory".into(),
+            ..mara()
+        };
+        assert!(!apply(&mut posts, &undo, &someone_else, post_link));
+        assert!(apply(&mut posts, &undo, &mara(), post_link));
+        assert!(posts["blog/foo"].is_empty());
+    }
+
+    #[test]
+    fn reply_and_delete() {
+        let mut posts = Posts::new();
+        let create = json!({
+            "id": "https://pony.social/statuses/1/activity",
+            "type": "Create",
+            "object": {
+                "id": "https://pony.social/statuses/1",
+                "type": "Note",
+                "inReplyTo": "https://xeiaso.net/blog/foo",
+                "content": "<p>nice <script>alert(1)</script></p>",
+            },
+        });
+        assert!(apply(&mut posts, &create, &mara(), post_link));
+        let reply = &posts["blog/foo"].replies[0];
+        assert_eq!(reply.content, "<p>nice </p>");
+        assert_eq!(reply.url, "https://pony.social/statuses/1");
+
+        let delete = json!({
+            "id": "https://pony.social/statuses/1#delete",
+            "type": "Delete",
+            "object": { "id": "https://pony.social/statuses/1", "type": "Tombstone" },
+        });
+        assert!(apply(&mut posts, &delete, &mara(), post_link));
+        assert!(posts["blog/foo"].is_empty());
+    }
+
+    #[test]
+    fn moderation() {
+        let mut posts = Posts::new();
+        for (n, published) in [(1, "2023-01-01T00:00:00Z"), (2, "2023-02-01T00:00:00Z")] {
+            let create = json!({
+                "id": format!("https://pony.social/statuses/{n}/activity"),
+                "type": "Create",
+                "object": {
+                    "id": format!("https://pony.social/statuses/{n}"),
+                    "type": "Note",
+                    "inReplyTo": format!("https://xeiaso.net/blog/post-{n}"),
+                    "content": "<p>hi</p>",
+                    "published": published,
+                },
+            });
+            assert!(apply(&mut posts, &create, &mara(), post_link));
+        }
+
+        let recent = recent_replies(&posts, 1);
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].0, "blog/post-2");
+
+        assert!(remove_reply(&mut posts, "https://pony.social/statuses/2"));
+        assert!(!remove_reply(&mut posts, "https://pony.social/statuses/2"));
+        assert!(posts["blog/post-2"].is_empty());
+        assert_eq!(recent_replies(&posts, 10)[0].0, "blog/post-1");
+    }
+
+    #[tokio::test]
+    async fn saves_what_changed() -> Result<()> {
+        let db = crate::storage::Storage::memory()?.interactions();
+        let store = Store::new(db.clone(), Path::new("/nonexistent/state.json")).await?;
+        let like = |n: u32| {
+            json!({
+                "id": format!("https://pony.social/likes/{n}"),
+                "type": "Like",
+                "object": format!("https://xeiaso.net/blog/post-{n}"),
+            })
+        };
+        store.apply(&like(1), &mara(), post_link).await?;
+        store.apply(&like(2), &mara(), post_link).await?;
+        store.forget(&mara().id).await?;
+        store.apply(&like(3), &mara(), post_link).await?;
+
+        let saved = db.all().await?;
+        assert_eq!(saved.keys().collect::<Vec<_>>(), vec!["blog/post-3"]);
+        assert_eq!(saved["blog/post-3"], store.get("blog/post-3").await);
+        Ok(())
+    }
+
+    #[test]
+    fn ignores_other_posts() {
+        let mut posts = Posts::new();
+        let like = json!({
+            "id": "https://pony.social/likes/2",
+            "type": "Like",
+            "object": "https://pony.social/statuses/2",
+        });
+        assert!(!apply(&mut posts, &like, &mara(), post_link));
+        assert!(posts.is_empty());
+    }
+}
@@ -0,0 +1,184 @@
+//! The admin area at `/.within/admin`: the fediverse replies and webmentions
+//! that came in, what's being read, background jobs and the in-memory caches.
+//! It's only there when `adminToken` or `auth.admins` is set. People log in
+//! with the token or as one of the admins, see [crate::auth]. Scripts can
+//! send the token in an `Authorization: Bearer` header instead.
+
+use crate::{
+    app::{Config, State},
+    auth::{self, Provider, Session},
+    tmpl,
+};
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+/// Where the admin area is.
+pub const PREFIX: &str = "/.within/admin";
+
+/// Whether the admin area is turned on.
+pub fn enabled(cfg: &Config) -> bool {
+    !cfg.admin_token.is_empty() || !cfg.auth.admins.is_empty()
+}
+
+/// Whether `given` is the admin token. Never true when there's no token.
+pub fn check_token(want: &str, given: &str) -> bool {
+    !want.is_empty() && auth::secret_eq(want, given)
+}
+
+/// Who logging in with `token` is. It's a hash of the token, so changing
+/// the token logs out everyone who logged in with the old one.
+pub fn token_user(token: &str) -> String {
+    format!("token:{}", hex::encode(&Sha256::digest(token)[..8]))
+}
+
+/// Whether `session` is one of the admins.
+pub fn is_admin(cfg: &Config, session: &Session) -> bool {
+    match session.via {
+        Provider::Token => {
+            !cfg.admin_token.is_empty() && session.me == token_user(&cfg.admin_token)
+        }
+        Provider::Oidc | Provider::IndieAuth => cfg.auth.admins.contains(&session.me),
+    }
+}
+
+/// The token a script sent, if it sent one.
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+/// Whether the request came with the admin token in an `Authorization:
+/// Bearer` header.
+pub fn has_token(cfg: &Config, headers: &HeaderMap) -> bool {
+    bearer_token(headers).map_or(false, |token| check_token(&cfg.admin_token, token))
+}
+
+/// Proof that a request is from an admin. Handlers that take this are only
+/// run for admins. Everyone else is sent to the login page, or gets a 404
+/// when the admin area is turned off.
+pub struct Admin {
+    /// How the admin logged in, or `None` for scripts that sent the token.
+    pub session: Option<Session>,
+}
+
+impl Admin {
+    /// What the forms on admin pages have to send back, see
+    /// [auth::CsrfForm].
+    pub fn csrf(&self) -> &str {
+        self.session.as_ref().map_or("", |s| s.csrf.as_str())
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Admin {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        let state = parts
+            .extensions
+            .get::<Arc<State>>()
+            .ok_or_else(|| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
+        if !enabled(&state.cfg) {
+            return Err((
+                StatusCode::NOT_FOUND,
+                tmpl::not_found(parts.uri.path().to_string()),
+            )
+                .into_response());
+        }
+        // Only the token can be sent as a header. Anything else, like Basic
+        // credentials a proxy added, doesn't fall back to the session cookie.
+        if parts.headers.contains_key(header::AUTHORIZATION) {
+            return if has_token(&state.cfg, &parts.headers) {
+                Ok(Admin { session: None })
+            } else {
+                Err(StatusCode::UNAUTHORIZED.into_response())
+            };
+        }
+        match state.auth.session(&parts.headers) {
+            Some(session) if is_admin(&state.cfg, &session) => Ok(Admin {
+                session: Some(session),
+            }),
+            Some(session) => Err((
+                StatusCode::FORBIDDEN,
+                tmpl::error(format!("{} isn't an admin.", session.me)),
+            )
+                .into_response()),
+            None => Err(auth::login_redirect(parts.uri.path())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn tokens() {
+        assert!(check_token("hunter2", "hunter2"));
+        assert!(!check_token("hunter2", "hunter3"));
+        assert!(!check_token("", ""));
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(bearer_token(&headers), None);
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer hunter2"),
+        );
+        assert_eq!(bearer_token(&headers), Some("hunter2"));
+
+        let cfg = Config {
+            admin_token: "hunter2".into(),
+            ..Default::default()
+        };
+        assert!(has_token(&cfg, &headers));
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Basic aHVudGVyMjpodW50ZXIy"),
+        );
+        assert!(!has_token(&cfg, &headers));
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer hunter3"),
+        );
+        assert!(!has_token(&cfg, &headers));
+    }
+
+    #[test]
+    fn admins() {
+        let mut cfg = Config {
+            admin_token: "hunter2".into(),
+            ..Default::default()
+        };
+        cfg.auth.admins = vec!["https://xeiaso.net/".into()];
+
+        let token = Session::new(token_user("hunter2"), None, Provider::Token);
+        let old_token = Session::new(token_user("hunter1"), None, Provider::Token);
+        let site = Session::new("https://xeiaso.net/".into(), None, Provider::IndieAuth);
+        let other = Session::new("https://example.com/".into(), None, Provider::IndieAuth);
+        // Nobody gets to be an admin by having a provider call them
+        // token_user().
+        let spoofed = Session::new(token_user("hunter2"), None, Provider::Oidc);
+
+        assert!(is_admin(&cfg, &token));
+        assert!(!is_admin(&cfg, &old_token));
+        assert!(is_admin(&cfg, &site));
+        assert!(!is_admin(&cfg, &other));
+        assert!(!is_admin(&cfg, &spoofed));
+
+        cfg.admin_token = String::new();
+        assert!(!is_admin(&cfg, &token));
+        assert!(enabled(&cfg));
+        cfg.auth.admins.clear();
+        assert!(!enabled(&cfg));
+    }
+}
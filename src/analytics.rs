@@ -0,0 +1,173 @@
+//! First-party analytics: how many times each post was read per day. Only
+//! the link of the post and the day are kept, nothing about the reader.
+//! Counts are kept in the database for [RETENTION_DAYS] so the popular posts
+//! don't reset on every deploy, and totals for each year are kept for good
+//! for the year in review.
+
+use crate::storage::{reads::Days, Reads};
+use chrono::prelude::*;
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, path::Path, sync::RwLock as SyncRwLock};
+use tokio::sync::RwLock;
+use xesite_templates::context::site;
+
+/// Days older than this are dropped.
+pub const RETENTION_DAYS: i64 = 90;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Window {
+    Month,
+    Quarter,
+}
+
+impl Window {
+    pub const ALL: [Window; 2] = [Window::Month, Window::Quarter];
+
+    pub fn days(self) -> i64 {
+        match self {
+            Window::Month => 30,
+            Window::Quarter => RETENTION_DAYS,
+        }
+    }
+}
+
+/// Links by how many times they were read since `since`, most read first.
+pub fn rank(days: &Days, since: NaiveDate) -> Vec<(String, u64)> {
+    let mut totals: HashMap<&str, u64> = HashMap::new();
+    for (_, counts) in days.range(since..) {
+        for (link, count) in counts {
+            *totals.entry(link).or_default() += count;
+        }
+    }
+    let mut result: Vec<(String, u64)> = totals
+        .into_iter()
+        .map(|(link, count)| (link.to_string(), count))
+        .collect();
+    result.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    result
+}
+
+pub struct Store {
+    reads: Reads,
+    /// Reads since the last refresh, which haven't been saved yet.
+    pending: RwLock<Days>,
+    /// Rankings as of the last refresh, so templates can read them without
+    /// waiting on a lock.
+    popular: SyncRwLock<HashMap<Window, Vec<(String, u64)>>>,
+}
+
+impl Store {
+    /// Counts reads in `reads`. Counts that were saved to `legacy` before
+    /// there was a database are moved into it.
+    pub async fn new(reads: Reads, legacy: &Path) -> Result<Self> {
+        if let Some(days) = crate::storage::import_json::<Days>(legacy).await? {
+            if reads.is_empty().await? {
+                reads.add(days).await?;
+            }
+        }
+
+        let store = Self {
+            reads,
+            pending: RwLock::new(Days::new()),
+            popular: SyncRwLock::new(HashMap::new()),
+        };
+        store.rerank().await?;
+        Ok(store)
+    }
+
+    /// Counts a read of the post at `link`.
+    pub async fn record(&self, link: &str) {
+        if !site().analytics() {
+            return;
+        }
+        let today = Utc::now().date_naive();
+        *self
+            .pending
+            .write()
+            .await
+            .entry(today)
+            .or_default()
+            .entry(link.to_string())
+            .or_default() += 1;
+    }
+
+    /// Drops old days, saves the counts and updates the rankings.
+    #[instrument(skip(self), err)]
+    pub async fn refresh(&self) -> Result<()> {
+        let pending = std::mem::take(&mut *self.pending.write().await);
+        self.reads.add(pending).await?;
+        let cutoff = Utc::now().date_naive() - chrono::Duration::days(RETENTION_DAYS);
+        self.reads.prune(cutoff + chrono::Duration::days(1)).await?;
+        self.rerank().await
+    }
+
+    async fn rerank(&self) -> Result<()> {
+        let today = Utc::now().date_naive();
+        let days = self
+            .reads
+            .since(today - chrono::Duration::days(RETENTION_DAYS - 1))
+            .await?;
+        let popular = Window::ALL
+            .iter()
+            .map(|window| {
+                let since = today - chrono::Duration::days(window.days() - 1);
+                (*window, rank(&days, since))
+            })
+            .collect();
+        *self.popular.write().unwrap() = popular;
+        Ok(())
+    }
+
+    /// How many times each link was read in `year`, counting the reads that
+    /// haven't been saved yet. Empty when they can't be looked up.
+    pub async fn year(&self, year: i32) -> HashMap<String, u64> {
+        let mut counts = match self.reads.year(year).await {
+            Ok(counts) => counts,
+            Err(why) => {
+                error!("can't look up the reads in {year}: {why}");
+                return HashMap::new();
+            }
+        };
+        let pending = self.pending.read().await;
+        for (_, day) in pending.iter().filter(|(day, _)| day.year() == year) {
+            for (link, count) in day {
+                *counts.entry(link.clone()).or_default() += count;
+            }
+        }
+        counts
+    }
+
+    /// The `n` most read links in `window`, as of the last refresh.
+    pub fn popular(&self, n: usize, window: Window) -> Vec<(String, u64)> {
+        self.popular
+            .read()
+            .unwrap()
+            .get(&window)
+            .map(|ranked| ranked.iter().take(n).cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranks_within_window() {
+        let day = |d| NaiveDate::from_ymd_opt(2023, 7, d).unwrap();
+        let mut days = Days::new();
+        days.insert(day(1), HashMap::from([("blog/old".to_string(), 100)]));
+        days.insert(
+            day(20),
+            HashMap::from([("blog/a".to_string(), 3), ("blog/b".to_string(), 5)]),
+        );
+        days.insert(day(21), HashMap::from([("blog/a".to_string(), 4)]));
+
+        assert_eq!(
+            rank(&days, day(10)),
+            vec![("blog/a".to_string(), 7), ("blog/b".to_string(), 5)]
+        );
+        assert_eq!(rank(&days, day(1))[0], ("blog/old".to_string(), 100));
+    }
+}
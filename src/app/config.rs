@@ -1,4 +1,4 @@
-use crate::signalboost::Person;
+use crate::{analytics::Window, signalboost::Person};
 use chrono::prelude::*;
 use maud::{html, Markup, Render};
 use serde::{Deserialize, Serialize};
@@ -6,6 +6,15 @@ use std::{
     collections::HashMap,
     fmt::{self, Display},
 };
+use xesite_templates::{
+    context::{self, cdn, site},
+    og::OpenGraph,
+};
+use xesite_types::{
+    character::{self, ArtistCredit, ChatTheme, Hovercard, StickerPack},
+    site::SiteConfig,
+    ReaderPrefs,
+};
 
 mod markdown_string;
 use markdown_string::MarkdownString;
@@ -21,6 +30,12 @@ pub struct Config {
     pub clack_set: Vec<String>,
     #[serde(rename = "miToken")]
     pub mi_token: String,
+    /// The token for the admin area, see [crate::admin]. It's turned off
+    /// when this is empty.
+    #[serde(rename = "adminToken")]
+    pub admin_token: String,
+    pub auth: Auth,
+    pub backup: Backup,
     #[serde(rename = "jobHistory")]
     pub job_history: Vec<Job>,
     #[serde(rename = "seriesDescriptions")]
@@ -32,8 +47,202 @@ pub struct Config {
     #[serde(rename = "contactLinks")]
     pub contact_links: Vec<Link>,
     pub pronouns: Vec<PronounSet>,
-    pub characters: Vec<Character>,
     pub vods: Vec<VOD>,
+    #[serde(rename = "statusServices")]
+    pub status_services: Vec<StatusService>,
+    #[serde(rename = "statusHistoryPath")]
+    pub status_history_path: String,
+    /// The database, see [crate::storage].
+    #[serde(rename = "databasePath")]
+    pub database_path: String,
+    /// Where daily read counts were kept before there was a database. They
+    /// are moved into it, see [crate::analytics].
+    #[serde(rename = "analyticsPath")]
+    pub analytics_path: String,
+    /// Where job states were kept before there was a database, see
+    /// [crate::jobs].
+    #[serde(rename = "jobsPath")]
+    pub jobs_path: String,
+    /// What posts and assets looked like last time, see [crate::events].
+    #[serde(rename = "eventsPath")]
+    pub events_path: String,
+    pub books: Vec<Book>,
+    /// Changes to the site itself, see [crate::handlers::changelog].
+    pub changelog: Vec<ChangelogEntry>,
+    /// Paths that moved. Posts with `redirect_to` are redirected too, see
+    /// [crate::edge::rules].
+    pub redirects: Vec<Redirect>,
+    pub ipfs: Ipfs,
+    #[serde(rename = "pageBudget")]
+    pub page_budget: PageBudget,
+    #[serde(rename = "onThisDay")]
+    pub on_this_day: OnThisDay,
+    pub maintenance: Maintenance,
+    /// The sections of the home page in order, see [crate::tmpl::home].
+    pub homepage: Vec<HomeSection>,
+    #[serde(rename = "activityPub")]
+    pub activitypub: ActivityPub,
+    pub site: SiteConfig,
+    /// Loaded from [SiteConfig::characters_manifest] at startup.
+    #[serde(skip)]
+    pub characters: Vec<Character>,
+}
+
+/// The site's own fediverse account, see [crate::activitypub].
+#[derive(Clone, Debug, Deserialize, Serialize, Default)]
+pub struct ActivityPub {
+    pub enabled: bool,
+    /// The part of the handle before the @, the part after it is the site's
+    /// domain.
+    pub username: String,
+    /// Where likes, boosts and replies to posts were kept before there was a
+    /// database. They are moved into it, see [crate::activitypub::store].
+    #[serde(rename = "statePath")]
+    pub state_path: String,
+    /// The PEM-encoded RSA key the actor signs with.
+    #[serde(rename = "privateKeyPath")]
+    pub private_key_path: String,
+    /// Where followers were kept before there was a database. They are moved
+    /// into it, see [crate::activitypub::followers].
+    #[serde(rename = "followersPath")]
+    pub followers_path: String,
+    /// Which posts followers have been sent already.
+    #[serde(rename = "publishedPath")]
+    pub published_path: String,
+}
+
+/// Encrypted backups in S3-compatible object storage, see [crate::backup].
+#[derive(Clone, Debug, Deserialize, Serialize, Default)]
+pub struct Backup {
+    /// Like `https://s3.us-west-001.backblazeb2.com`. Backups are turned
+    /// off when this or `key` is empty.
+    pub endpoint: String,
+    pub region: String,
+    pub bucket: String,
+    /// What the names of backups start with in the bucket.
+    pub prefix: String,
+    #[serde(rename = "accessKeyID")]
+    pub access_key_id: String,
+    #[serde(rename = "secretAccessKey")]
+    pub secret_access_key: String,
+    /// 32 bytes in base64 that backups are encrypted with.
+    pub key: String,
+    /// When the `backup` job runs, see [crate::jobs::Schedule].
+    pub schedule: String,
+    /// How many backups are kept. Older ones are deleted.
+    pub keep: usize,
+}
+
+impl Backup {
+    pub fn enabled(&self) -> bool {
+        !self.endpoint.is_empty() && !self.key.is_empty()
+    }
+}
+
+/// Logging in with OpenID Connect or IndieAuth, see [crate::auth].
+#[derive(Clone, Debug, Deserialize, Serialize, Default)]
+pub struct Auth {
+    /// The key session cookies are signed with. When this is empty a new
+    /// one is made every time the site starts, which logs everyone out.
+    #[serde(rename = "sessionKey")]
+    pub session_key: String,
+    pub oidc: Option<Oidc>,
+    /// Whether people can log in with their own website.
+    #[serde(rename = "indieAuth")]
+    pub indie_auth: bool,
+    /// Who can use the admin area after logging in: verified email
+    /// addresses from the OpenID Connect provider or IndieAuth profile URLs.
+    pub admins: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Default)]
+pub struct Oidc {
+    /// The provider's issuer URL, where
+    /// `/.well-known/openid-configuration` is found.
+    pub issuer: String,
+    /// What the login button says, such as "Log in with Google".
+    pub name: String,
+    #[serde(rename = "clientID")]
+    pub client_id: String,
+    #[serde(rename = "clientSecret")]
+    pub client_secret: String,
+}
+
+/// One part of the home page. Sites list these in the order they should show
+/// up in.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum HomeSection {
+    Hero,
+    About,
+    /// This many of the latest posts with their excerpts.
+    LatestPosts(usize),
+    PopularPosts(PopularSection),
+    OnThisDay,
+    /// Links to the stream and the latest VOD.
+    Streaming,
+    /// Everyone supporting the site on Patreon.
+    Supporters,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PopularSection {
+    pub count: usize,
+    pub window: Window,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Redirect {
+    pub from: String,
+    pub to: String,
+    pub status: u16,
+}
+
+/// Where static exports are published on IPFS, see [crate::ipfs].
+#[derive(Clone, Debug, Deserialize, Serialize, Default)]
+pub struct Ipfs {
+    #[serde(rename = "releasesPath")]
+    pub releases_path: String,
+    /// An IPFS pinning service API endpoint to pin releases with, as well as
+    /// the local node.
+    #[serde(rename = "pinningServiceURL")]
+    pub pinning_service_url: Option<String>,
+    #[serde(rename = "pinningToken")]
+    pub pinning_token: String,
+}
+
+/// How heavy a post is allowed to be, see [crate::budget].
+#[derive(Clone, Debug, Deserialize, Serialize, Default)]
+pub struct PageBudget {
+    #[serde(rename = "maxBytes")]
+    pub max_bytes: u64,
+    /// Fail instead of warning when a post is over budget.
+    pub strict: bool,
+    /// JSON file mapping paths in the CDN bucket to their sizes in bytes.
+    #[serde(rename = "cdnSizes")]
+    pub cdn_sizes: String,
+}
+
+/// Whether to toot old posts on their anniversary, see
+/// [crate::on_this_day]. The token comes from `MASTODON_TOKEN`.
+#[derive(Clone, Debug, Deserialize, Serialize, Default)]
+pub struct OnThisDay {
+    pub toot: bool,
+    #[serde(rename = "statePath")]
+    pub state_path: String,
+}
+
+/// Serving pages from a static export while the site is being worked on,
+/// see [crate::maintenance].
+#[derive(Clone, Debug, Deserialize, Serialize, Default)]
+pub struct Maintenance {
+    /// Whether the site starts in maintenance mode. It can be turned on and
+    /// off in the admin area too.
+    pub enabled: bool,
+    /// The folder `xesite export` wrote to.
+    #[serde(rename = "exportPath")]
+    pub export_path: String,
+    /// What the banner says is going on, if anything.
+    pub message: String,
 }
 
 #[derive(Clone, Deserialize, Serialize, Default)]
@@ -102,31 +311,71 @@ pub struct Character {
     pub description: MarkdownString,
     pub pronouns: PronounSet,
     pub stickers: Vec<String>,
+    pub chat: Option<ChatTheme>,
+    #[serde(rename = "stickerLicense")]
+    pub sticker_license: StickerLicense,
+    /// Who drew the stickers, if it isn't who [StickerLicense::attribution]
+    /// names.
+    pub artist: Option<ArtistCredit>,
 }
 
-impl Render for Character {
-    fn render(&self) -> Markup {
-        html! {
-            h3 #(self.sticker_name) {(self.name)}
-            (xesite_templates::sticker(self.sticker_name.clone(), self.default_pose.clone()))
-            p {(self.description)}
-            details {
-                summary { "Pronouns (" (self.pronouns.nominative) "/" (self.pronouns.accusative) ")" }
-                (self.pronouns)
-            }
+/// Whether a character's stickers can be downloaded as a pack, see
+/// [crate::stickers].
+#[derive(Clone, Deserialize, Serialize, Default)]
+pub struct StickerLicense {
+    pub redistributable: bool,
+    pub license: String,
+    #[serde(rename = "licenseURL")]
+    pub license_url: Option<String>,
+    pub attribution: String,
+}
 
-            details {
-                summary { "All stickers" }
-                .grid {
-                    @for sticker in &self.stickers {
-                        .cell."-3of12" {
-                            (xesite_templates::sticker(self.sticker_name.clone(), sticker.clone()))
-                            br;
-                            (sticker)
-                        }
-                    }
-                }
-            }
+impl StickerLicense {
+    /// What's wrong with the license, if anything.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+        if self.redistributable && self.license.is_empty() {
+            problems.push("redistributable stickers need a license".to_string());
+        }
+        if self.redistributable && self.attribution.is_empty() {
+            problems.push("redistributable stickers need an attribution".to_string());
+        }
+        problems
+    }
+}
+
+impl Character {
+    pub fn hovercard(&self) -> Hovercard {
+        Hovercard {
+            name: self.name.clone(),
+            sticker_name: self.sticker_name.clone(),
+            default_pose: self.default_pose.clone(),
+            pronouns: format!("{}/{}", self.pronouns.nominative, self.pronouns.accusative),
+            bio: self.description.html().to_string(),
+        }
+    }
+
+    /// What the characters page shows about them.
+    pub fn card(&self) -> character::Character {
+        let license = &self.sticker_license;
+        character::Character {
+            name: self.name.clone(),
+            sticker_name: self.sticker_name.clone(),
+            default_pose: self.default_pose.clone(),
+            pronouns: format!("{}/{}", self.pronouns.nominative, self.pronouns.accusative),
+            description: self.description.html().to_string(),
+            stickers: self.stickers.clone(),
+            artist: self.artist.clone().or_else(|| {
+                (!license.attribution.is_empty()).then(|| ArtistCredit {
+                    name: license.attribution.clone(),
+                    url: None,
+                })
+            }),
+            pack: license.redistributable.then(|| StickerPack {
+                path: crate::stickers::pack_path(self),
+                license: license.license.clone(),
+                license_url: license.license_url.clone(),
+            }),
         }
     }
 }
@@ -342,6 +591,27 @@ impl Render for Job {
     }
 }
 
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum ProbeKind {
+    HTTP,
+    TCP,
+    Ping,
+}
+
+impl Default for ProbeKind {
+    fn default() -> Self {
+        ProbeKind::HTTP
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Default)]
+pub struct StatusService {
+    pub name: String,
+    pub description: String,
+    pub kind: ProbeKind,
+    pub target: String,
+}
+
 #[derive(Clone, Deserialize, Serialize, Default)]
 pub struct VOD {
     pub title: String,
@@ -359,25 +629,23 @@ impl VOD {
     }
 }
 
-impl Render for VOD {
-    fn render(&self) -> Markup {
-        html! {
-            meta name="twitter:card" content="summary";
-            meta name="twitter:site" content="@theprincessxena";
-            meta name="twitter:title" content={(self.title)};
-            meta property="og:type" content="website";
-            meta property="og:title" content={(self.title)};
-            meta property="og:site_name" content="Xe's Blog";
-            meta name="description" content={(self.title) " - Xe's Blog"};
-            meta name="author" content="Xe Iaso";
+impl VOD {
+    /// The page for this VOD, with ads unless `prefs` says not to.
+    pub fn page(&self, prefs: ReaderPrefs) -> Markup {
+        context::open_graph(
+            OpenGraph::new(&self.title)
+                .author("Xe Iaso")
+                .creator(&site().mastodon.handle),
+        );
 
+        html! {
             h1 {(self.title)}
             small {"Streamed on " (self.detri())}
 
-            (xesite_templates::advertiser_nag(Some(html!{
+            (xesite_templates::advertiser_nag(prefs, Some(html!{
                 (xesite_templates::conv("Cadey".into(), "coffee".into(), html!{
                     "Hi. This page embeds a video file that is potentially multiple hours long. Hosting this stuff is not free. Bandwidth in particular is expensive. If you really want to continue to block ads, please consider donating via "
-                        a href="https://patreon.com/cadey" {"Patreon"}
+                        a href=(site().donation.patreon_url) {"Patreon"}
                     " because servers and bandwidth do not grow on trees."
                 }))
             })))
@@ -394,3 +662,144 @@ impl Render for VOD {
         }
     }
 }
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum BookStatus {
+    Reading,
+    Finished,
+    Abandoned,
+    WantToRead,
+}
+
+impl Default for BookStatus {
+    fn default() -> Self {
+        BookStatus::WantToRead
+    }
+}
+
+impl Display for BookStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BookStatus::Reading => write!(f, "Currently reading"),
+            BookStatus::Finished => write!(f, "Finished"),
+            BookStatus::Abandoned => write!(f, "Did not finish"),
+            BookStatus::WantToRead => write!(f, "Want to read"),
+        }
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize, Default)]
+pub struct Book {
+    pub title: String,
+    pub author: String,
+    pub status: BookStatus,
+    pub rating: Option<u8>,
+    #[serde(rename = "startDate")]
+    pub start_date: Option<NaiveDate>,
+    #[serde(rename = "finishDate")]
+    pub finish_date: Option<NaiveDate>,
+    pub notes: MarkdownString,
+    /// CDN path of the cover image, without an extension.
+    pub cover: Option<String>,
+}
+
+impl Book {
+    /// The year this book was finished in, if it was finished.
+    pub fn finished_in(&self) -> Option<i32> {
+        match self.status {
+            BookStatus::Finished => self.finish_date.map(|d| d.year()),
+            _ => None,
+        }
+    }
+
+    pub fn stars(&self) -> Option<String> {
+        self.rating.map(|r| {
+            format!(
+                "{}{}",
+                "★".repeat(r.min(5) as usize),
+                "☆".repeat(5 - r.min(5) as usize)
+            )
+        })
+    }
+
+    pub fn rfc2822(&self) -> String {
+        self.finish_date
+            .unwrap_or_default()
+            .format("%a, %d %b %Y 00:00:00 +0000")
+            .to_string()
+    }
+}
+
+impl Render for Book {
+    fn render(&self) -> Markup {
+        html! {
+            .book {
+                @if let Some(cover) = &self.cover {
+                    picture.book-cover {
+                        source type="image/avif" srcset=(cdn(&format!("{cover}.avif")));
+                        source type="image/webp" srcset=(cdn(&format!("{cover}.webp")));
+                        img loading="lazy" alt={"cover of " (self.title)} src=(cdn(&format!("{cover}-smol.png")));
+                    }
+                }
+                div {
+                    b {(self.title)}
+                    " by "
+                    (self.author)
+                    br;
+                    small {
+                        (self.status.to_string())
+                        @if let Some(date) = self.finish_date {
+                            " on " (date.format("M%m %d %Y").to_string())
+                        }
+                        @if let Some(stars) = self.stars() {
+                            " " span.book-rating title={(self.rating.unwrap_or(0)) " out of 5"} {(stars)}
+                        }
+                    }
+                    @if !self.notes.html().is_empty() {
+                        (self.notes)
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// What part of the site a [ChangelogEntry] changed.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum ChangelogCategory {
+    Design,
+    Feature,
+    Content,
+    Fix,
+    Infrastructure,
+}
+
+impl Display for ChangelogCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChangelogCategory::Design => write!(f, "Design"),
+            ChangelogCategory::Feature => write!(f, "Feature"),
+            ChangelogCategory::Content => write!(f, "Content"),
+            ChangelogCategory::Fix => write!(f, "Fix"),
+            ChangelogCategory::Infrastructure => write!(f, "Infrastructure"),
+        }
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ChangelogEntry {
+    pub date: NaiveDate,
+    pub category: ChangelogCategory,
+    pub description: MarkdownString,
+}
+
+impl ChangelogEntry {
+    /// What the entry is called in feeds, like `Design: 2023-07-01`.
+    pub fn title(&self) -> String {
+        format!("{}: {}", self.category, self.date)
+    }
+
+    pub fn rfc2822(&self) -> String {
+        self.date.format("%a, %d %b %Y 00:00:00 +0000").to_string()
+    }
+}
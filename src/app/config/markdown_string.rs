@@ -54,6 +54,12 @@ impl<'de> Deserialize<'de> for MarkdownString {
     }
 }
 
+impl MarkdownString {
+    pub fn html(&self) -> &str {
+        &self.0
+    }
+}
+
 impl Render for MarkdownString {
     fn render(&self) -> Markup {
         html! {
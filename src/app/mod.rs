@@ -1,14 +1,24 @@
 use crate::{post::Post, signalboost::Person};
 use chrono::prelude::*;
-use color_eyre::eyre::Result;
-use std::{path::PathBuf, sync::Arc};
-use tracing::{error, instrument};
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tracing::{error, instrument, warn};
+use xesite_types::{
+    character::{ChatTheme, StickerRegistry},
+    site::Environment,
+};
 
 pub mod config;
 pub mod poke;
 
 pub use config::*;
 
+/// Written by the `subset_fonts` tool when the fonts are built.
+const FONT_MANIFEST: &str = "./static/css/iosevka/manifest.json";
+
 #[instrument]
 async fn patrons() -> Result<Option<patreon::Users>> {
     let mut p = dirs::home_dir().unwrap_or(".".into());
@@ -51,15 +61,149 @@ pub struct State {
     pub blog: Vec<Post>,
     pub gallery: Vec<Post>,
     pub talks: Vec<Post>,
+    pub incidents: Vec<Post>,
+    pub garden: crate::notes::Garden,
     pub everything: Vec<Post>,
     pub jf: xe_jsonfeed::Feed,
     pub sitemap: Vec<u8>,
     pub patrons: Option<patreon::Users>,
     pub mi: mi::Client,
+    pub status: Arc<crate::status::Monitor>,
+    pub analytics: Arc<crate::analytics::Store>,
+    /// Estimates of how many people follow the site.
+    pub subscribers: crate::subscribers::Tracker,
+    /// The database, see [crate::storage].
+    pub storage: crate::storage::Storage,
+    /// Backups of the database and state files, if they're turned on.
+    pub backups: Option<Arc<crate::backup::Backups>>,
+    /// The site's fediverse account, if it has one.
+    pub activitypub: Option<Arc<crate::activitypub::Actor>>,
+    /// Which stickers posts use.
+    pub moods: crate::moods::Report,
+    pub auth: crate::auth::Auth,
+    /// Background jobs, which are added in `main`.
+    pub jobs: Arc<crate::jobs::Scheduler>,
+    /// Publish hooks, which are subscribed to in `main`.
+    pub events: Arc<crate::events::Bus>,
+    pub maintenance: crate::maintenance::Mode,
+}
+
+impl State {
+    /// Whether `link`, like `blog/foo`, is a listed post, talk, gallery
+    /// entry or note.
+    pub fn has_post(&self, link: &str) -> bool {
+        self.blog
+            .iter()
+            .chain(&self.gallery)
+            .chain(&self.talks)
+            .chain(&self.garden.notes)
+            .any(|post| post.link == link && post.listed())
+    }
 }
 
 pub async fn init(cfg: PathBuf) -> Result<State> {
-    let cfg: Arc<Config> = Arc::new(serde_dhall::from_file(cfg).parse()?);
+    let mut cfg: Config = serde_dhall::from_file(cfg).parse()?;
+    cfg.site.environment =
+        Environment::from_env().map_err(|why| eyre!("{}: {why}", Environment::VAR))?;
+    cfg.site.asset_base_from_env();
+    if let Err(errors) = cfg.site.validate() {
+        let errors: Vec<String> = errors.iter().map(|e| format!("  {e}")).collect();
+        return Err(eyre!("site config is invalid:\n{}", errors.join("\n")));
+    }
+    if let Some(redirect) = cfg.redirects.iter().find(|r| !(300..400).contains(&r.status)) {
+        return Err(eyre!(
+            "redirect from {} has status {}, which isn't a redirect",
+            redirect.from,
+            redirect.status
+        ));
+    }
+    cfg.characters = serde_dhall::from_file(&cfg.site.characters_manifest)
+        .parse()
+        .wrap_err_with(|| {
+            format!(
+                "can't load characters manifest {}",
+                cfg.site.characters_manifest
+            )
+        })?;
+    let problems: Vec<String> = cfg
+        .characters
+        .iter()
+        .flat_map(|ch| {
+            let chat = ch
+                .chat
+                .iter()
+                .flat_map(ChatTheme::problems)
+                .map(move |problem| format!("  characters.{}.chat: {problem}", ch.sticker_name));
+            let license = ch
+                .sticker_license
+                .problems()
+                .into_iter()
+                .map(move |problem| {
+                    format!("  characters.{}.stickerLicense: {problem}", ch.sticker_name)
+                });
+            // The default pose is what unknown moods fall back to.
+            let pose = (!ch.stickers.contains(&ch.default_pose)).then(|| {
+                format!(
+                    "  characters.{}.defaultPose: {} isn't one of their stickers",
+                    ch.sticker_name, ch.default_pose
+                )
+            });
+            chat.chain(license).chain(pose)
+        })
+        .collect();
+    if !problems.is_empty() {
+        return Err(eyre!(
+            "characters manifest is invalid:\n{}",
+            problems.join("\n")
+        ));
+    }
+    let media = serde_dhall::from_file(&cfg.site.media_manifest)
+        .parse()
+        .wrap_err_with(|| format!("can't load media manifest {}", cfg.site.media_manifest))?;
+    xesite_templates::context::install(cfg.site.clone());
+    xesite_templates::context::install_media(media);
+    xesite_templates::context::install_hovercards(
+        cfg.characters
+            .iter()
+            .map(config::Character::hovercard)
+            .collect(),
+    );
+    let mut stickers = StickerRegistry::default();
+    for ch in &cfg.characters {
+        stickers.add(
+            ch.sticker_name.clone(),
+            ch.default_pose.clone(),
+            ch.stickers.clone(),
+        );
+    }
+    xesite_templates::context::install_stickers(stickers);
+    xesite_templates::context::install_chat_themes(
+        cfg.characters
+            .iter()
+            .filter_map(|ch| Some((ch.sticker_name.clone(), ch.chat.clone()?)))
+            .collect(),
+    );
+    // The link archive is only there once `link_archive` has been run.
+    if let Some(path) = &cfg.site.link_archive {
+        match std::fs::read_to_string(path) {
+            Ok(links) => {
+                let links = serde_json::from_str(&links)
+                    .wrap_err_with(|| format!("can't parse link archive {path}"))?;
+                xesite_templates::context::install_link_archive(links);
+            }
+            Err(why) => warn!("can't read link archive {path}, not linking archived copies: {why}"),
+        }
+    }
+    // The font manifest is only there when the fonts have been built.
+    match std::fs::read_to_string(FONT_MANIFEST) {
+        Ok(manifest) => {
+            let fonts = serde_json::from_str(&manifest)
+                .wrap_err_with(|| format!("can't parse font manifest {FONT_MANIFEST}"))?;
+            xesite_templates::context::install_fonts(fonts);
+        }
+        Err(why) => warn!("can't read font manifest {FONT_MANIFEST}, not using web fonts: {why}"),
+    }
+    let cfg = Arc::new(cfg);
     let sb = cfg.signalboost.clone();
     let mi = mi::Client::new(
         cfg.clone().mi_token.clone(),
@@ -68,6 +212,17 @@ pub async fn init(cfg: PathBuf) -> Result<State> {
     let blog = crate::post::load("blog").await?;
     let gallery = crate::post::load("gallery").await?;
     let talks = crate::post::load("talks").await?;
+    let incidents = crate::post::load_all("incidents").await?;
+    let garden = crate::notes::Garden::new(crate::post::load_all("notes").await?);
+    let moods = crate::moods::Report::compute(
+        &cfg.characters,
+        blog.iter()
+            .chain(&gallery)
+            .chain(&talks)
+            .chain(&incidents)
+            .chain(&garden.notes)
+            .map(|post| (post.link.as_str(), post.body_html.as_str())),
+    );
     let mut everything: Vec<Post> = vec![];
 
     {
@@ -86,21 +241,22 @@ pub async fn init(cfg: PathBuf) -> Result<State> {
     let everything: Vec<Post> = everything
         .into_iter()
         .filter(|p| today.num_days_from_ce() >= p.date.num_days_from_ce())
+        .filter(Post::listed)
         .take(5)
         .collect();
 
     let mut jfb = xe_jsonfeed::Feed::builder()
-        .title("Xe's Blog")
-        .description("My blog posts and rants about various technology things.")
+        .title(cfg.site.feed_title.clone())
+        .description(cfg.site.feed_description.clone())
         .author(
             xe_jsonfeed::Author::new()
                 .name("Xe")
-                .url("https://xeiaso.net")
+                .url(cfg.site.url("/"))
                 .avatar(ICON),
         )
-        .feed_url("https://xeiaso.net/blog.json")
+        .feed_url(cfg.site.url("blog.json"))
         .user_comment("This is a JSON feed of my blogposts. For more information read: https://jsonfeed.org/version/1")
-        .home_page_url("https://xeiaso.net")
+        .home_page_url(cfg.site.url("/"))
         .icon(ICON)
         .favicon(ICON);
 
@@ -112,28 +268,48 @@ pub async fn init(cfg: PathBuf) -> Result<State> {
     let mut sm: Vec<u8> = vec![];
     let smw = sitemap::writer::SiteMapWriter::new(&mut sm);
     let mut urlwriter = smw.start_urlset()?;
-    for url in &[
-        "https://xeiaso.net/resume",
-        "https://xeiaso.net/contact",
-        "https://xeiaso.net/",
-        "https://xeiaso.net/blog",
-        "https://xeiaso.net/signalboost",
+    for path in &[
+        "resume",
+        "contact",
+        "",
+        "blog",
+        "notes",
+        "books",
+        "signalboost",
     ] {
-        urlwriter.url(*url)?;
+        urlwriter.url(cfg.site.url(path))?;
     }
 
-    for post in &blog {
-        urlwriter.url(format!("https://xeiaso.net/{}", post.link))?;
-    }
-    for post in &gallery {
-        urlwriter.url(format!("https://xeiaso.net/{}", post.link))?;
-    }
-    for post in &talks {
-        urlwriter.url(format!("https://xeiaso.net/{}", post.link))?;
+    for post in blog
+        .iter()
+        .chain(&gallery)
+        .chain(&talks)
+        .chain(&incidents)
+        .chain(&garden.notes)
+    {
+        if !post.indexable() {
+            continue;
+        }
+        urlwriter.url(cfg.site.url(&post.link))?;
     }
 
     urlwriter.end()?;
 
+    let status = crate::status::Monitor::new(
+        cfg.status_services.clone(),
+        cfg.status_history_path.clone().into(),
+    )
+    .await?;
+    let storage = crate::storage::Storage::open(cfg.database_path.clone().into()).await?;
+    let analytics =
+        crate::analytics::Store::new(storage.reads(), Path::new(&cfg.analytics_path)).await?;
+    let subscribers = crate::subscribers::Tracker::new(storage.subscriber_counts()).await?;
+    let backups = crate::backup::Backups::new(cfg.clone(), storage.clone())?;
+    let activitypub = crate::activitypub::Actor::new(&cfg.activitypub, &storage).await?;
+    let auth = crate::auth::Auth::new(&cfg.auth)?;
+    let jobs = crate::jobs::Scheduler::new(storage.jobs(), Path::new(&cfg.jobs_path)).await?;
+    let events = crate::events::Bus::new(cfg.events_path.clone().into(), "./static".into());
+
     Ok(State {
         mi,
         cfg,
@@ -141,10 +317,23 @@ pub async fn init(cfg: PathBuf) -> Result<State> {
         blog,
         gallery,
         talks,
+        incidents,
+        garden,
         everything,
         jf: jfb.build(),
         sitemap: sm,
         patrons: patrons().await?,
+        status: Arc::new(status),
+        analytics: Arc::new(analytics),
+        subscribers,
+        storage,
+        backups: backups.map(Arc::new),
+        activitypub: activitypub.map(Arc::new),
+        moods,
+        auth,
+        jobs: Arc::new(jobs),
+        events: Arc::new(events),
+        maintenance: crate::maintenance::Mode::new(&cfg.maintenance),
     })
 }
 
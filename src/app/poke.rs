@@ -2,22 +2,18 @@ use color_eyre::eyre::Result;
 use std::{env, time::Duration};
 use tokio::time::sleep as delay_for;
 
-#[instrument(err)]
-pub async fn the_cloud() -> Result<()> {
+/// Gives the server a moment to start listening, since new posts are found
+/// right as the site starts.
+async fn settle() {
     info!("waiting for things to settle");
     delay_for(Duration::from_secs(10)).await;
-
-    info!("poking mi");
-    mi().await?;
-
-    info!("poking google");
-    google().await?;
-
-    Ok(())
 }
 
+/// Asks Google to look at the sitemap again.
 #[instrument(err)]
-async fn google() -> Result<()> {
+pub async fn google() -> Result<()> {
+    settle().await;
+    info!("poking google");
     let cli = reqwest::Client::new();
     cli.get("https://www.google.com/ping")
         .query(&[("sitemap", "https://xeiaso.net/sitemap.xml")])
@@ -29,8 +25,11 @@ async fn google() -> Result<()> {
     Ok(())
 }
 
+/// Asks mi to read the feeds again, which sends webmentions for new posts.
 #[instrument(err)]
-async fn mi() -> Result<()> {
+pub async fn mi() -> Result<()> {
+    settle().await;
+    info!("poking mi");
     let cli = mi::Client::new(env::var("MI_TOKEN")?, crate::APPLICATION_NAME.to_string())?;
     cli.refresh().await?;
 
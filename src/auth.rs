@@ -0,0 +1,778 @@
+//! Logging in. People can log in with an OpenID Connect provider, or with
+//! their own website over IndieAuth. Either way they get a session cookie
+//! that says who they are, signed with `auth.sessionKey`. The admin area
+//! uses it, see [crate::admin], and so can anything else that needs to know
+//! who's reading: handlers that take a [Session] are only run for people who
+//! are logged in, and ones that take an `Option<Session>` are run for
+//! everyone.
+//!
+//! Logged in browsers have to send the session's CSRF token with every form
+//! that changes something, see [CsrfForm].
+
+use crate::{
+    app::{config, State},
+    tmpl,
+};
+use axum::{
+    async_trait,
+    body::{Bytes, HttpBody},
+    extract::{FromRequest, FromRequestParts},
+    http::{header, request::Parts, HeaderMap, Request, StatusCode},
+    response::{AppendHeaders, IntoResponse, Redirect, Response},
+    BoxError,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Utc;
+use color_eyre::eyre::{eyre, Result};
+use hmac::{Hmac, Mac};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use url::{Host, Url};
+
+/// Where logging in happens.
+pub const PREFIX: &str = "/.within/auth";
+
+/// The cookie the session is kept in.
+pub const SESSION_COOKIE: &str = "xesite-session";
+
+/// The cookie that remembers a login while the person is at their provider.
+const FLOW_COOKIE: &str = "xesite-login";
+
+/// How long a session lasts, in seconds.
+const SESSION_SECS: i64 = 60 * 60 * 24 * 30;
+
+/// How long someone has to log in at their provider, in seconds.
+const FLOW_SECS: i64 = 60 * 10;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Provider {
+    /// The admin token, see [crate::admin].
+    Token,
+    Oidc,
+    IndieAuth,
+}
+
+/// Who is logged in.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Session {
+    /// A verified email address or an IndieAuth profile URL.
+    pub me: String,
+    pub name: Option<String>,
+    pub via: Provider,
+    /// What forms have to send back, see [CsrfForm].
+    pub csrf: String,
+    /// When the session ends, as a Unix timestamp.
+    pub expires: i64,
+}
+
+impl Session {
+    pub fn new(me: String, name: Option<String>, via: Provider) -> Self {
+        Self {
+            me,
+            name,
+            via,
+            csrf: random_token(),
+            expires: Utc::now().timestamp() + SESSION_SECS,
+        }
+    }
+}
+
+/// A login that was started but isn't finished yet.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+struct Flow {
+    via: Provider,
+    /// Sent to the provider and back, so only the browser that started
+    /// logging in can finish it.
+    state: String,
+    /// The PKCE code verifier.
+    verifier: String,
+    /// The IndieAuth authorization endpoint, which the profile that comes
+    /// back has to use too.
+    endpoint: Option<String>,
+    next: String,
+    expires: i64,
+}
+
+impl Flow {
+    fn new(via: Provider, endpoint: Option<String>, next: &str) -> Self {
+        Self {
+            via,
+            state: random_token(),
+            verifier: random_token(),
+            endpoint,
+            next: next.to_string(),
+            expires: Utc::now().timestamp() + FLOW_SECS,
+        }
+    }
+}
+
+fn random_token() -> String {
+    hex::encode(rand::random::<[u8; 32]>())
+}
+
+/// Whether `given` is `want`. They're hashed first so comparing them takes
+/// the same time no matter how much of `given` is right.
+pub fn secret_eq(want: &str, given: &str) -> bool {
+    Sha256::digest(want) == Sha256::digest(given)
+}
+
+/// The PKCE code challenge for `verifier`, see RFC 7636.
+fn challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier))
+}
+
+/// Where to go after logging in. Only paths on this site are allowed, so
+/// login links can't be used to send people somewhere else.
+pub fn safe_next(next: Option<&str>) -> String {
+    match next {
+        Some(next)
+            if next.starts_with('/')
+                && !next.starts_with("//")
+                && !next.contains('\\')
+                && !next.chars().any(char::is_control) =>
+        {
+            next.to_string()
+        }
+        _ => "/".to_string(),
+    }
+}
+
+/// Sends someone to the login page, and back to `next` after that.
+pub fn login_redirect(next: &str) -> Response {
+    let query: String = url::form_urlencoded::Serializer::new(String::new())
+        .append_pair("next", next)
+        .finish();
+    Redirect::to(&format!("{PREFIX}/login?{query}")).into_response()
+}
+
+/// The value of the cookie `name`, if the request has it.
+pub fn cookie_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|c| c.trim().split_once('='))
+        .find(|(n, _)| *n == name)
+        .map(|(_, value)| value)
+}
+
+/// A `Set-Cookie` header. Cookies are removed by setting them with a
+/// `max_age` of 0.
+pub fn cookie(name: &str, value: &str, path: &str, max_age: i64) -> String {
+    // Lax so the cookies are sent when a provider redirects back here.
+    format!("{name}={value}; Path={path}; Max-Age={max_age}; HttpOnly; Secure; SameSite=Lax")
+}
+
+/// A profile URL the way IndieAuth wants it: an https URL with a domain name
+/// that [crate::net::is_public]. `example.com` is taken to mean
+/// `https://example.com/`.
+pub fn canonical_me(me: &str) -> Option<Url> {
+    let me = me.trim();
+    let url = if me.contains("://") {
+        Url::parse(me)
+    } else {
+        Url::parse(&format!("https://{me}"))
+    }
+    .ok()?;
+    let ok = crate::net::is_public(&url)
+        && matches!(url.host(), Some(Host::Domain(_)))
+        && url.username().is_empty()
+        && url.password().is_none()
+        && url.fragment().is_none();
+    ok.then_some(url)
+}
+
+/// The first `rel` link in the `Link` headers.
+fn link_header(headers: &HeaderMap, rel: &str, base: &Url) -> Option<Url> {
+    headers
+        .get_all(header::LINK)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .find_map(|link| {
+            let (target, params) = link.trim().split_once(';')?;
+            let target = target.trim().strip_prefix('<')?.strip_suffix('>')?;
+            params
+                .split(';')
+                .filter_map(|param| param.trim().strip_prefix("rel="))
+                .any(|rels| rels.trim_matches('"').split_whitespace().any(|r| r == rel))
+                .then(|| base.join(target).ok())
+                .flatten()
+        })
+}
+
+/// The first `<link>` to `rel` in an HTML page.
+fn link_html(html: &str, rel: &str, base: &Url) -> Option<Url> {
+    let selector = scraper::Selector::parse(&format!(r#"link[rel~="{rel}"][href]"#)).ok()?;
+    let page = scraper::Html::parse_document(html);
+    let href = page.select(&selector).next()?.value().attr("href")?;
+    base.join(href).ok()
+}
+
+#[derive(Deserialize)]
+struct Discovery {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    userinfo_endpoint: String,
+}
+
+#[derive(Deserialize)]
+struct Tokens {
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+struct UserInfo {
+    sub: String,
+    name: Option<String>,
+    email: Option<String>,
+    #[serde(default)]
+    email_verified: bool,
+}
+
+impl UserInfo {
+    /// Who this is: their email address if the provider checked it,
+    /// otherwise their ID at the provider.
+    fn me(&self, issuer: &str) -> String {
+        match &self.email {
+            Some(email) if self.email_verified => email.clone(),
+            _ => format!("{issuer}#{}", self.sub),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Metadata {
+    authorization_endpoint: String,
+}
+
+#[derive(Deserialize)]
+struct Profile {
+    me: String,
+}
+
+/// Signs cookies and talks to the providers.
+pub struct Auth {
+    key: Vec<u8>,
+    client: reqwest::Client,
+    /// For IndieAuth, where whoever logs in picks the URLs.
+    indieauth: reqwest::Client,
+}
+
+impl Auth {
+    pub fn new(cfg: &config::Auth) -> Result<Self> {
+        let key = if cfg.session_key.is_empty() {
+            if cfg.oidc.is_some() || cfg.indie_auth {
+                warn!(
+                    "auth.sessionKey isn't set, everyone will be logged out when the site restarts"
+                );
+            }
+            rand::random::<[u8; 32]>().to_vec()
+        } else {
+            cfg.session_key.as_bytes().to_vec()
+        };
+        Ok(Self {
+            key,
+            client: reqwest::Client::builder()
+                .user_agent(crate::APPLICATION_NAME)
+                .build()?,
+            indieauth: reqwest::Client::builder()
+                .user_agent(crate::APPLICATION_NAME)
+                .redirect(crate::net::redirects())
+                .build()?,
+        })
+    }
+
+    fn mac(&self) -> Hmac<Sha256> {
+        Hmac::new_from_slice(&self.key).expect("HMAC takes keys of any length")
+    }
+
+    /// `value` as JSON with a signature, for putting in a cookie.
+    fn seal<T: Serialize>(&self, value: &T) -> String {
+        let body = URL_SAFE_NO_PAD.encode(serde_json::to_vec(value).unwrap());
+        let mut mac = self.mac();
+        mac.update(body.as_bytes());
+        format!(
+            "{body}.{}",
+            URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+        )
+    }
+
+    /// What [Auth::seal] made, if nobody changed it.
+    fn open<T: DeserializeOwned>(&self, sealed: &str) -> Option<T> {
+        let (body, signature) = sealed.split_once('.')?;
+        let mut mac = self.mac();
+        mac.update(body.as_bytes());
+        mac.verify_slice(&URL_SAFE_NO_PAD.decode(signature).ok()?)
+            .ok()?;
+        serde_json::from_slice(&URL_SAFE_NO_PAD.decode(body).ok()?).ok()
+    }
+
+    /// Who sent the request, if they're logged in.
+    pub fn session(&self, headers: &HeaderMap) -> Option<Session> {
+        let session: Session = self.open(cookie_value(headers, SESSION_COOKIE)?)?;
+        (session.expires > Utc::now().timestamp()).then_some(session)
+    }
+
+    /// Logs `session` in and goes on to `next`.
+    pub fn login(&self, session: Session, next: &str) -> Response {
+        info!("auth: {} logged in with {:?}", session.me, session.via);
+        (
+            AppendHeaders([
+                (
+                    header::SET_COOKIE,
+                    cookie(SESSION_COOKIE, &self.seal(&session), "/", SESSION_SECS),
+                ),
+                (header::SET_COOKIE, cookie(FLOW_COOKIE, "", PREFIX, 0)),
+            ]),
+            Redirect::to(next),
+        )
+            .into_response()
+    }
+
+    pub fn logout(&self) -> Response {
+        (
+            [(header::SET_COOKIE, cookie(SESSION_COOKIE, "", "/", 0))],
+            Redirect::to("/"),
+        )
+            .into_response()
+    }
+
+    /// Sends someone off to log in at `url`, remembering `flow` for when
+    /// they come back.
+    fn start(&self, url: Url, flow: &Flow) -> Response {
+        (
+            [(
+                header::SET_COOKIE,
+                cookie(FLOW_COOKIE, &self.seal(flow), PREFIX, FLOW_SECS),
+            )],
+            Redirect::to(url.as_str()),
+        )
+            .into_response()
+    }
+
+    /// The login that someone came back from their provider with. It has
+    /// to have been started in the same browser, not long ago.
+    fn finish(&self, headers: &HeaderMap, via: Provider, state: &str) -> Result<Flow> {
+        let flow: Flow = cookie_value(headers, FLOW_COOKIE)
+            .and_then(|sealed| self.open(sealed))
+            .ok_or_else(|| eyre!("no login was started in this browser"))?;
+        if flow.via != via || !secret_eq(&flow.state, state) {
+            return Err(eyre!(
+                "the login that came back isn't the one that was started"
+            ));
+        }
+        if flow.expires <= Utc::now().timestamp() {
+            return Err(eyre!("logging in took too long"));
+        }
+        Ok(flow)
+    }
+
+    async fn discover(&self, issuer: &str) -> Result<Discovery> {
+        let issuer = issuer.trim_end_matches('/');
+        let url = format!("{issuer}/.well-known/openid-configuration");
+        let discovery: Discovery = self
+            .client
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if discovery.issuer.trim_end_matches('/') != issuer {
+            return Err(eyre!("{url} is for {}", discovery.issuer));
+        }
+        Ok(discovery)
+    }
+
+    /// Starts logging in at the OpenID Connect provider.
+    pub async fn start_oidc(
+        &self,
+        oidc: &config::Oidc,
+        redirect_uri: &str,
+        next: &str,
+    ) -> Result<Response> {
+        let discovery = self.discover(&oidc.issuer).await?;
+        let flow = Flow::new(Provider::Oidc, None, next);
+        let mut url = Url::parse(&discovery.authorization_endpoint)?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &oidc.client_id)
+            .append_pair("redirect_uri", redirect_uri)
+            .append_pair("scope", "openid profile email")
+            .append_pair("state", &flow.state)
+            .append_pair("code_challenge", &challenge(&flow.verifier))
+            .append_pair("code_challenge_method", "S256");
+        Ok(self.start(url, &flow))
+    }
+
+    /// Finishes logging in when the OpenID Connect provider sends someone
+    /// back. The code is traded for their details straight from the
+    /// provider, so the ID token doesn't have to be checked.
+    pub async fn finish_oidc(
+        &self,
+        oidc: &config::Oidc,
+        redirect_uri: &str,
+        headers: &HeaderMap,
+        code: &str,
+        state: &str,
+    ) -> Result<Response> {
+        let flow = self.finish(headers, Provider::Oidc, state)?;
+        let discovery = self.discover(&oidc.issuer).await?;
+        let tokens: Tokens = self
+            .client
+            .post(&discovery.token_endpoint)
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", redirect_uri),
+                ("client_id", &oidc.client_id),
+                ("client_secret", &oidc.client_secret),
+                ("code_verifier", &flow.verifier),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let user: UserInfo = self
+            .client
+            .get(&discovery.userinfo_endpoint)
+            .bearer_auth(&tokens.access_token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let session = Session::new(user.me(&oidc.issuer), user.name, Provider::Oidc);
+        Ok(self.login(session, &flow.next))
+    }
+
+    /// Finds the IndieAuth authorization endpoint of the profile `me`,
+    /// either from its metadata or from the older `authorization_endpoint`
+    /// link. Every URL is [crate::net::check]ed before it's used.
+    async fn authorization_endpoint(&self, me: &Url) -> Result<Url> {
+        crate::net::check(me).await?;
+        let resp = self
+            .indieauth
+            .get(me.clone())
+            .send()
+            .await?
+            .error_for_status()?;
+        let base = resp.url().clone();
+        let headers = resp.headers().clone();
+        let body = resp.text().await?;
+        let find =
+            |rel: &str| link_header(&headers, rel, &base).or_else(|| link_html(&body, rel, &base));
+
+        let endpoint = if let Some(metadata) = find("indieauth-metadata") {
+            crate::net::check(&metadata).await?;
+            let metadata: Metadata = self
+                .indieauth
+                .get(metadata)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            Url::parse(&metadata.authorization_endpoint)?
+        } else {
+            find("authorization_endpoint")
+                .ok_or_else(|| eyre!("{me} doesn't say where to log in with IndieAuth"))?
+        };
+        crate::net::check(&endpoint).await?;
+        Ok(endpoint)
+    }
+
+    /// Starts logging in as the website `me`. `client_id` is this site's
+    /// home page.
+    pub async fn start_indieauth(
+        &self,
+        me: &str,
+        client_id: &str,
+        redirect_uri: &str,
+        next: &str,
+    ) -> Result<Response> {
+        let me = canonical_me(me).ok_or_else(|| eyre!("{me:?} isn't a website"))?;
+        let endpoint = self.authorization_endpoint(&me).await?;
+        let flow = Flow::new(Provider::IndieAuth, Some(endpoint.to_string()), next);
+        let mut url = endpoint;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", client_id)
+            .append_pair("redirect_uri", redirect_uri)
+            .append_pair("state", &flow.state)
+            .append_pair("code_challenge", &challenge(&flow.verifier))
+            .append_pair("code_challenge_method", "S256")
+            .append_pair("me", me.as_str());
+        Ok(self.start(url, &flow))
+    }
+
+    /// Finishes logging in when the authorization endpoint sends someone
+    /// back.
+    pub async fn finish_indieauth(
+        &self,
+        client_id: &str,
+        redirect_uri: &str,
+        headers: &HeaderMap,
+        code: &str,
+        state: &str,
+    ) -> Result<Response> {
+        let flow = self.finish(headers, Provider::IndieAuth, state)?;
+        let endpoint = flow
+            .endpoint
+            .as_deref()
+            .ok_or_else(|| eyre!("the login has no authorization endpoint"))?;
+        crate::net::check(&Url::parse(endpoint)?).await?;
+        let profile: Profile = self
+            .indieauth
+            .post(endpoint)
+            .header(reqwest::header::ACCEPT, "application/json")
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("client_id", client_id),
+                ("redirect_uri", redirect_uri),
+                ("code_verifier", &flow.verifier),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        // Any endpoint can say anyone logged in, so the profile it names
+        // has to agree that the endpoint speaks for it.
+        let me = canonical_me(&profile.me).ok_or_else(|| {
+            eyre!(
+                "{endpoint} logged in {:?}, which isn't a website",
+                profile.me
+            )
+        })?;
+        if self.authorization_endpoint(&me).await?.as_str() != endpoint {
+            return Err(eyre!("{me} doesn't log in with {endpoint}"));
+        }
+
+        let session = Session::new(me.to_string(), None, Provider::IndieAuth);
+        Ok(self.login(session, &flow.next))
+    }
+}
+
+fn app_state(parts: &Parts) -> Result<&Arc<State>, Response> {
+    parts
+        .extensions
+        .get::<Arc<State>>()
+        .ok_or_else(|| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Session {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        let state = app_state(parts)?;
+        state
+            .auth
+            .session(&parts.headers)
+            .ok_or_else(|| login_redirect(parts.uri.path_and_query().map_or("/", |pq| pq.as_str())))
+    }
+}
+
+/// A form that changes something. Logged in browsers have to send the
+/// session's CSRF token in its `csrf` field, so other sites can't get
+/// someone's browser to submit it for them. Only scripts that send the admin
+/// token as a Bearer token don't need one, since another site can't know it.
+/// Other `Authorization` headers, like Basic credentials a browser remembers
+/// for a proxy, don't count.
+pub struct CsrfForm<T>(pub T);
+
+#[async_trait]
+impl<T, S, B> FromRequest<S, B> for CsrfForm<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        let app = req
+            .extensions()
+            .get::<Arc<State>>()
+            .cloned()
+            .ok_or_else(|| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
+        let headers = req.headers().clone();
+        let body = Bytes::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        if !crate::admin::has_token(&app.cfg, &headers) {
+            let given = url::form_urlencoded::parse(&body)
+                .find(|(name, _)| name == "csrf")
+                .map(|(_, value)| value.into_owned())
+                .unwrap_or_default();
+            let ok = app
+                .auth
+                .session(&headers)
+                .map_or(false, |session| secret_eq(&session.csrf, &given));
+            if !ok {
+                return Err((
+                    StatusCode::FORBIDDEN,
+                    tmpl::error(
+                        "This form is out of date. Go back, reload the page and try again.",
+                    ),
+                )
+                    .into_response());
+            }
+        }
+
+        serde_urlencoded::from_bytes(&body)
+            .map(CsrfForm)
+            .map_err(|why| (StatusCode::BAD_REQUEST, why.to_string()).into_response())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn auth() -> Auth {
+        Auth::new(&config::Auth {
+            session_key: "hunter2".into(),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn sessions() {
+        let auth = auth();
+        let session = Session::new("https://example.com/".into(), None, Provider::IndieAuth);
+        let sealed = auth.seal(&session);
+        assert_eq!(auth.open::<Session>(&sealed), Some(session.clone()));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::COOKIE,
+            HeaderValue::from_str(&format!("theme=dark; {SESSION_COOKIE}={sealed}")).unwrap(),
+        );
+        assert_eq!(auth.session(&headers), Some(session.clone()));
+
+        // Someone else's key, a changed session and an old session are all
+        // turned away.
+        let other = Auth::new(&config::Auth {
+            session_key: "hunter3".into(),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(other.open::<Session>(&sealed), None);
+        let (_, signature) = sealed.split_once('.').unwrap();
+        let forged = Session {
+            me: "mallory@example.com".into(),
+            ..session.clone()
+        };
+        let forged = format!(
+            "{}.{signature}",
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&forged).unwrap())
+        );
+        assert_eq!(auth.open::<Session>(&forged), None);
+        let expired = Session {
+            expires: 0,
+            ..session
+        };
+        headers.insert(
+            header::COOKIE,
+            HeaderValue::from_str(&format!("{SESSION_COOKIE}={}", auth.seal(&expired))).unwrap(),
+        );
+        assert_eq!(auth.session(&headers), None);
+    }
+
+    #[test]
+    fn flows() {
+        let auth = auth();
+        let flow = Flow::new(Provider::Oidc, None, "/.within/admin");
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::COOKIE,
+            HeaderValue::from_str(&format!("{FLOW_COOKIE}={}", auth.seal(&flow))).unwrap(),
+        );
+        assert_eq!(
+            auth.finish(&headers, Provider::Oidc, &flow.state).unwrap(),
+            flow
+        );
+        assert!(auth.finish(&headers, Provider::Oidc, "guess").is_err());
+        assert!(auth
+            .finish(&headers, Provider::IndieAuth, &flow.state)
+            .is_err());
+        assert!(auth
+            .finish(&HeaderMap::new(), Provider::Oidc, &flow.state)
+            .is_err());
+    }
+
+    #[test]
+    fn pkce() {
+        // From RFC 7636, appendix B.
+        assert_eq!(
+            challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[test]
+    fn next() {
+        assert_eq!(safe_next(Some("/.within/admin?x=1")), "/.within/admin?x=1");
+        assert_eq!(safe_next(Some("//evil.example")), "/");
+        assert_eq!(safe_next(Some("/\\evil.example")), "/");
+        assert_eq!(safe_next(Some("https://evil.example")), "/");
+        assert_eq!(safe_next(None), "/");
+    }
+
+    #[test]
+    fn profile_urls() {
+        assert_eq!(
+            canonical_me("example.com").map(String::from),
+            Some("https://example.com/".into())
+        );
+        assert_eq!(
+            canonical_me("https://example.com/~me").map(String::from),
+            Some("https://example.com/~me".into())
+        );
+        assert_eq!(canonical_me("http://example.com/"), None);
+        assert_eq!(canonical_me("localhost"), None);
+        assert_eq!(canonical_me("https://db.internal/"), None);
+        assert_eq!(canonical_me("https://127.0.0.1/"), None);
+        assert_eq!(canonical_me("mailto:me@example.com"), None);
+        assert_eq!(canonical_me("https://example.com/#me"), None);
+    }
+
+    #[test]
+    fn discovery() {
+        let base = Url::parse("https://example.com/").unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::LINK,
+            HeaderValue::from_static(
+                r#"</webmention>; rel="webmention", <https://auth.example/>; rel="authorization_endpoint""#,
+            ),
+        );
+        assert_eq!(
+            link_header(&headers, "authorization_endpoint", &base).map(String::from),
+            Some("https://auth.example/".into())
+        );
+        assert_eq!(link_header(&headers, "indieauth-metadata", &base), None);
+
+        let html = r#"<html><head><link rel="me indieauth-metadata" href="/meta"></head></html>"#;
+        assert_eq!(
+            link_html(html, "indieauth-metadata", &base).map(String::from),
+            Some("https://example.com/meta".into())
+        );
+        assert_eq!(link_html(html, "authorization_endpoint", &base), None);
+    }
+}
@@ -0,0 +1,396 @@
+//! Encrypted backups of what changes while the site runs: the database
+//! (read counts and job states, see [crate::storage]) and the JSON files
+//! that aren't in it yet, like fediverse replies and followers, status
+//! history and the event ledger.
+//!
+//! Each backup is a zip file encrypted with XChaCha20-Poly1305 and put in
+//! S3-compatible object storage. The `backup` job makes one on a schedule,
+//! there's a button for it in the admin area, and only the newest `keep` of
+//! them are kept. `xesite restore-backup` puts one back.
+//!
+//! The fediverse private key isn't backed up. It's set up with the site,
+//! not made while it runs.
+
+use crate::{app::config::Config, jobs::Schedule, storage::Storage};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use chrono::prelude::*;
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    io::{Cursor, Read, Write},
+    path::Path,
+};
+use tokio::fs;
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+pub mod s3;
+
+/// What every backup starts with, so other files aren't mistaken for one.
+/// It's also authenticated with the rest of the backup.
+const MAGIC: &[u8] = b"xesite-backup-1\n";
+const NONCE_LEN: usize = 24;
+const EXTENSION: &str = ".xesite-backup";
+
+/// Where things are in the zip file.
+const DATABASE: &str = "xesite.db";
+const MANIFEST: &str = "manifest.json";
+
+/// What's in a backup and where it came from.
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Manifest {
+    pub created: DateTime<Utc>,
+    /// The commit of the site that made it.
+    pub commit: String,
+    /// The JSON files in it, by their names in [files].
+    pub files: Vec<String>,
+}
+
+/// The JSON files that are backed up, by their names in the zip file and
+/// where they're kept.
+fn files(cfg: &Config) -> Vec<(&'static str, String)> {
+    vec![
+        // These two are in the database now. They're still here so backups
+        // from before then restore them, and they get imported on startup.
+        ("activitypub/state.json", cfg.activitypub.state_path.clone()),
+        (
+            "activitypub/followers.json",
+            cfg.activitypub.followers_path.clone(),
+        ),
+        (
+            "activitypub/published.json",
+            cfg.activitypub.published_path.clone(),
+        ),
+        ("status-history.json", cfg.status_history_path.clone()),
+        ("events.json", cfg.events_path.clone()),
+        ("on-this-day.json", cfg.on_this_day.state_path.clone()),
+    ]
+}
+
+/// Parses the `key` in the config.
+fn parse_key(key: &str) -> Result<XChaCha20Poly1305> {
+    let key = STANDARD
+        .decode(key.trim())
+        .wrap_err("the backup key isn't base64")?;
+    XChaCha20Poly1305::new_from_slice(&key)
+        .map_err(|_| eyre!("the backup key is {} bytes, not 32", key.len()))
+}
+
+fn encrypt(cipher: &XChaCha20Poly1305, data: &[u8]) -> Result<Vec<u8>> {
+    let nonce = rand::random::<[u8; NONCE_LEN]>();
+    let sealed = cipher
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: data,
+                aad: MAGIC,
+            },
+        )
+        .map_err(|_| eyre!("can't encrypt the backup"))?;
+    let mut result = Vec::with_capacity(MAGIC.len() + NONCE_LEN + sealed.len());
+    result.extend_from_slice(MAGIC);
+    result.extend_from_slice(&nonce);
+    result.extend(sealed);
+    Ok(result)
+}
+
+fn decrypt(cipher: &XChaCha20Poly1305, data: &[u8]) -> Result<Vec<u8>> {
+    let rest = data
+        .strip_prefix(MAGIC)
+        .ok_or_else(|| eyre!("that's not a backup"))?;
+    if rest.len() < NONCE_LEN {
+        return Err(eyre!("the backup is cut off"));
+    }
+    let (nonce, sealed) = rest.split_at(NONCE_LEN);
+    cipher
+        .decrypt(
+            XNonce::from_slice(nonce),
+            Payload {
+                msg: sealed,
+                aad: MAGIC,
+            },
+        )
+        .map_err(|_| eyre!("can't decrypt the backup, it was changed or the key is wrong"))
+}
+
+/// Zips up everything in a backup.
+fn pack(manifest: &Manifest, entries: &BTreeMap<String, Vec<u8>>) -> Result<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    zip.start_file(MANIFEST, options)?;
+    zip.write_all(&serde_json::to_vec_pretty(manifest)?)?;
+    for (name, data) in entries {
+        zip.start_file(name, options)?;
+        zip.write_all(data)?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+fn unpack(data: &[u8]) -> Result<(Manifest, BTreeMap<String, Vec<u8>>)> {
+    let mut zip = ZipArchive::new(Cursor::new(data))?;
+    let mut entries = BTreeMap::new();
+    for i in 0..zip.len() {
+        let mut file = zip.by_index(i)?;
+        let mut data = vec![];
+        file.read_to_end(&mut data)?;
+        entries.insert(file.name().to_string(), data);
+    }
+    let manifest = entries
+        .remove(MANIFEST)
+        .ok_or_else(|| eyre!("the backup has no {MANIFEST}"))?;
+    Ok((serde_json::from_slice(&manifest)?, entries))
+}
+
+/// What a backup made at `now` is called in the bucket. Names sort by when
+/// they were made.
+fn name(prefix: &str, now: DateTime<Utc>) -> String {
+    format!("{prefix}{}{EXTENSION}", now.format("%Y-%m-%dT%H-%M-%SZ"))
+}
+
+/// The backups in `names` that are past the newest `keep`. At least one is
+/// always kept.
+fn expired(names: &[String], keep: usize) -> Vec<String> {
+    let mut names: Vec<&String> = names.iter().filter(|n| n.ends_with(EXTENSION)).collect();
+    names.sort();
+    let cut = names.len().saturating_sub(keep.max(1));
+    names[..cut].iter().map(|n| n.to_string()).collect()
+}
+
+/// A path next to `near` to put a copy of the database, since SQLite
+/// backups go to files.
+fn scratch(near: &Path) -> std::path::PathBuf {
+    let mut path = near.as_os_str().to_owned();
+    path.push(format!(".backup-{}", uuid::Uuid::new_v4()));
+    path.into()
+}
+
+/// Makes and restores backups.
+pub struct Backups {
+    cfg: std::sync::Arc<Config>,
+    storage: Storage,
+    bucket: s3::Bucket,
+    cipher: XChaCha20Poly1305,
+    pub schedule: Schedule,
+}
+
+impl Backups {
+    /// Backups for the site, or nothing when they're turned off.
+    pub fn new(cfg: std::sync::Arc<Config>, storage: Storage) -> Result<Option<Self>> {
+        if !cfg.backup.enabled() {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            bucket: s3::Bucket::new(&cfg.backup)?,
+            cipher: parse_key(&cfg.backup.key)?,
+            schedule: cfg
+                .backup
+                .schedule
+                .parse()
+                .wrap_err("the backup schedule is wrong")?,
+            cfg,
+            storage,
+        }))
+    }
+
+    /// Everything in a backup, encrypted.
+    async fn archive(&self, now: DateTime<Utc>) -> Result<Vec<u8>> {
+        let mut entries = BTreeMap::new();
+
+        let copy = scratch(Path::new(&self.cfg.database_path));
+        self.storage.snapshot(copy.clone()).await?;
+        let db = fs::read(&copy).await;
+        fs::remove_file(&copy).await?;
+        entries.insert(DATABASE.to_string(), db?);
+
+        let mut manifest = Manifest {
+            created: now,
+            commit: env!("GITHUB_SHA").to_string(),
+            files: vec![],
+        };
+        for (name, path) in files(&self.cfg) {
+            // Files for features that are turned off aren't there.
+            if let Ok(data) = fs::read(&path).await {
+                manifest.files.push(name.to_string());
+                entries.insert(name.to_string(), data);
+            }
+        }
+
+        let cipher = self.cipher.clone();
+        tokio::task::spawn_blocking(move || encrypt(&cipher, &pack(&manifest, &entries)?)).await?
+    }
+
+    /// Makes a backup, uploads it and deletes the ones that are too old.
+    /// Returns its name.
+    pub async fn run(&self) -> Result<String> {
+        let now = Utc::now();
+        let name = name(&self.cfg.backup.prefix, now);
+        let data = self.archive(now).await?;
+        let size = data.len();
+        self.bucket.put(&name, data).await?;
+        info!("backup: uploaded {name} ({size} bytes)");
+
+        for old in expired(&self.list().await?, self.cfg.backup.keep) {
+            self.bucket.delete(&old).await?;
+            info!("backup: deleted {old}");
+        }
+        Ok(name)
+    }
+
+    /// Makes a backup to download instead of uploading it. Returns what
+    /// it's called and what's in it.
+    pub async fn export(&self) -> Result<(String, Vec<u8>)> {
+        let now = Utc::now();
+        let file = name("xesite-", now);
+        Ok((file, self.archive(now).await?))
+    }
+
+    /// The names of every backup in the bucket, oldest first.
+    pub async fn list(&self) -> Result<Vec<String>> {
+        let mut names = self.bucket.list(&self.cfg.backup.prefix).await?;
+        names.retain(|n| n.ends_with(EXTENSION));
+        Ok(names)
+    }
+
+    /// Puts back the backup called `name`, or the newest one. The site
+    /// shouldn't be running while this happens, or it'll write its own
+    /// state back over the restored files. Returns the backup's name.
+    pub async fn restore(&self, name: Option<&str>) -> Result<String> {
+        let name = match name {
+            Some(name) => name.to_string(),
+            None => self
+                .list()
+                .await?
+                .pop()
+                .ok_or_else(|| eyre!("there are no backups"))?,
+        };
+        let data = self.bucket.get(&name).await?;
+        let cipher = self.cipher.clone();
+        let (manifest, mut entries) =
+            tokio::task::spawn_blocking(move || unpack(&decrypt(&cipher, &data)?)).await??;
+        info!(
+            "backup: restoring {name}, made {} by commit {}",
+            manifest.created, manifest.commit
+        );
+
+        let db = entries
+            .remove(DATABASE)
+            .ok_or_else(|| eyre!("{name} has no database"))?;
+        let copy = scratch(Path::new(&self.cfg.database_path));
+        fs::write(&copy, db).await?;
+        let restored = self.storage.restore(copy.clone()).await;
+        fs::remove_file(&copy).await?;
+        restored?;
+
+        for (file, path) in files(&self.cfg) {
+            let Some(data) = entries.remove(file) else {
+                continue;
+            };
+            if let Some(parent) = Path::new(&path).parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            fs::write(&path, data).await?;
+            info!("backup: restored {path}");
+        }
+        Ok(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key() -> XChaCha20Poly1305 {
+        parse_key(&STANDARD.encode([7; 32])).unwrap()
+    }
+
+    #[test]
+    fn keys() {
+        assert!(parse_key(&STANDARD.encode([7; 32])).is_ok());
+        assert!(parse_key(&STANDARD.encode([7; 16])).is_err());
+        assert!(parse_key("not base64!").is_err());
+    }
+
+    #[test]
+    fn encryption() -> Result<()> {
+        let sealed = encrypt(&key(), b"hello")?;
+        assert!(sealed.starts_with(MAGIC));
+        assert_eq!(decrypt(&key(), &sealed)?, b"hello");
+
+        let mut tampered = sealed.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(decrypt(&key(), &tampered).is_err());
+
+        let other = parse_key(&STANDARD.encode([8; 32]))?;
+        assert!(decrypt(&other, &sealed).is_err());
+        assert!(decrypt(&key(), b"hello").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn retention() {
+        let names: Vec<String> = [
+            "xesite/2023-01-03T00-00-00Z.xesite-backup",
+            "xesite/2023-01-01T00-00-00Z.xesite-backup",
+            "xesite/notes.txt",
+            "xesite/2023-01-02T00-00-00Z.xesite-backup",
+        ]
+        .map(String::from)
+        .to_vec();
+        assert_eq!(
+            expired(&names, 2),
+            vec!["xesite/2023-01-01T00-00-00Z.xesite-backup"]
+        );
+        assert_eq!(expired(&names, 0).len(), 2);
+        assert!(expired(&names, 5).is_empty());
+        assert_eq!(
+            name(
+                "xesite/",
+                Utc.with_ymd_and_hms(2023, 1, 3, 4, 5, 6).unwrap()
+            ),
+            "xesite/2023-01-03T04-05-06Z.xesite-backup"
+        );
+    }
+
+    #[tokio::test]
+    async fn round_trip() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("xesite-backup-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).await?;
+        let path = dir.join("xesite.db");
+
+        let storage = Storage::open(path.clone()).await?;
+        let days = BTreeMap::from([(
+            Utc::now().date_naive(),
+            [("blog/foo".to_string(), 3)].into(),
+        )]);
+        storage.reads().add(days).await?;
+
+        let copy = scratch(&path);
+        storage.snapshot(copy.clone()).await?;
+        let db = fs::read(&copy).await?;
+        let manifest = Manifest {
+            created: Utc::now(),
+            commit: "test".into(),
+            files: vec!["events.json".into()],
+        };
+        let entries = BTreeMap::from([
+            (DATABASE.to_string(), db),
+            ("events.json".to_string(), b"{}".to_vec()),
+        ]);
+        let sealed = encrypt(&key(), &pack(&manifest, &entries)?)?;
+        let (got_manifest, got_entries) = unpack(&decrypt(&key(), &sealed)?)?;
+        assert_eq!(got_manifest, manifest);
+        assert_eq!(got_entries, entries);
+
+        let other = Storage::memory()?;
+        assert!(other.reads().is_empty().await?);
+        other.restore(copy).await?;
+        assert!(!other.reads().is_empty().await?);
+
+        fs::remove_dir_all(dir).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,287 @@
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use scraper::{Html, Selector};
+use std::{env, fs};
+use tracing::info;
+
+/// The stylesheets every page loads, in order.
+const STYLESHEETS: &[&str] = &[
+    "./static/css/hack.css",
+    "./static/css/gruvbox-dark.css",
+    "./static/css/shim.css",
+];
+
+const OUT_DIR: &str = "./static/css/critical";
+
+/// Pseudo-classes and pseudo-elements that depend on what the reader is
+/// doing, so they can't be matched against a static page. Rules using them
+/// are kept if the rest of the selector matches.
+const DYNAMIC: &[&str] = &[
+    "::before",
+    "::after",
+    "::selection",
+    "::placeholder",
+    "::marker",
+    ":before",
+    ":after",
+    ":hover",
+    ":focus-within",
+    ":focus-visible",
+    ":focus",
+    ":active",
+    ":visited",
+    ":link",
+    ":target",
+    ":checked",
+];
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    color_eyre::install()?;
+    tracing_subscriber::fmt::init();
+
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        eprintln!("Usage: {} <kind>=<url>...", args[0]);
+        eprintln!(
+            "Example: {} index=http://localhost:3030/ post=http://localhost:3030/blog/xeact-0.0.69-2021-11-18",
+            args[0]
+        );
+        std::process::exit(2);
+    }
+
+    let mut css = String::new();
+    for path in STYLESHEETS {
+        css.push_str(&fs::read_to_string(path).wrap_err_with(|| format!("can't read {path}"))?);
+        css.push('\n');
+    }
+    let rules = parse(&css);
+
+    let cli = reqwest::Client::builder()
+        .user_agent("github.com/Xe/site critical_css")
+        .build()?;
+
+    fs::create_dir_all(OUT_DIR)?;
+    for arg in &args[1..] {
+        let (kind, url) = arg
+            .split_once('=')
+            .ok_or_else(|| eyre!("{arg} should look like kind=url"))?;
+        let html = cli
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let critical = extract(&rules, &Html::parse_document(&html));
+        info!("{kind}: {} of {} bytes", critical.len(), css.len());
+        fs::write(format!("{OUT_DIR}/{kind}.css"), critical)?;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+enum Rule {
+    /// A normal rule like `a { color: red }`.
+    Style { selectors: String, body: String },
+    /// An at-rule with rules inside it, like `@media`.
+    Group { prelude: String, rules: Vec<Rule> },
+    /// Any other at-rule, like `@keyframes`. These are always kept.
+    Other(String),
+}
+
+fn strip_comments(css: &str) -> String {
+    let mut result = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = rest.find("/*") {
+        result.push_str(&rest[..start]);
+        rest = match rest[start + 2..].find("*/") {
+            Some(end) => &rest[start + 2 + end + 2..],
+            None => "",
+        };
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Finds the index of the brace that closes the block starting at `open`.
+fn matching_brace(css: &str, open: usize) -> usize {
+    let mut depth = 0;
+    let mut quote = None;
+    for (i, c) in css[open..].char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '{') => depth += 1,
+            (None, '}') => {
+                depth -= 1;
+                if depth == 0 {
+                    return open + i;
+                }
+            }
+            _ => {}
+        }
+    }
+    css.len()
+}
+
+fn parse(css: &str) -> Vec<Rule> {
+    parse_rules(&strip_comments(css))
+}
+
+fn parse_rules(css: &str) -> Vec<Rule> {
+    let mut rules = vec![];
+    let mut rest = css;
+
+    loop {
+        let trimmed = rest.trim_start();
+        if trimmed.is_empty() {
+            break;
+        }
+
+        // Statements like @import and @charset don't have a block.
+        if trimmed.starts_with('@') {
+            let semicolon = trimmed.find(';');
+            let brace = trimmed.find('{');
+            if let (Some(semicolon), brace) = (semicolon, brace) {
+                if brace.map(|brace| semicolon < brace).unwrap_or(true) {
+                    rules.push(Rule::Other(trimmed[..=semicolon].trim().to_string()));
+                    rest = &trimmed[semicolon + 1..];
+                    continue;
+                }
+            }
+        }
+
+        let Some(open) = trimmed.find('{') else {
+            break;
+        };
+        let close = matching_brace(trimmed, open);
+        let prelude = trimmed[..open].trim().to_string();
+        let body = &trimmed[open + 1..close.min(trimmed.len())];
+
+        if prelude.starts_with("@media") || prelude.starts_with("@supports") {
+            rules.push(Rule::Group {
+                prelude,
+                rules: parse_rules(body),
+            });
+        } else if prelude.starts_with('@') {
+            rules.push(Rule::Other(format!("{prelude}{{{body}}}")));
+        } else {
+            rules.push(Rule::Style {
+                selectors: prelude,
+                body: body.trim().to_string(),
+            });
+        }
+
+        rest = trimmed.get(close + 1..).unwrap_or("");
+    }
+
+    rules
+}
+
+/// Splits a selector list on the commas that aren't inside parentheses.
+fn split_selectors(selectors: &str) -> Vec<&str> {
+    let mut result = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in selectors.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                result.push(selectors[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    result.push(selectors[start..].trim());
+    result
+}
+
+fn matches(selector: &str, page: &Html) -> bool {
+    let mut selector = selector.to_string();
+    for pseudo in DYNAMIC {
+        selector = selector.replace(pseudo, "");
+    }
+    let selector = selector.trim();
+    if selector.is_empty() || selector.ends_with(['>', '+', '~']) {
+        return true;
+    }
+    match Selector::parse(selector) {
+        Ok(selector) => page.select(&selector).next().is_some(),
+        // Better to keep a rule than to break the page.
+        Err(_) => true,
+    }
+}
+
+/// The rules from `rules` that apply to something in `page`.
+fn extract(rules: &[Rule], page: &Html) -> String {
+    let mut result = String::new();
+    for rule in rules {
+        match rule {
+            Rule::Style { selectors, body } => {
+                let used: Vec<&str> = split_selectors(selectors)
+                    .into_iter()
+                    .filter(|selector| matches(selector, page))
+                    .collect();
+                if !used.is_empty() {
+                    result.push_str(&format!("{}{{{}}}\n", used.join(","), body));
+                }
+            }
+            Rule::Group { prelude, rules } => {
+                let inner = extract(rules, page);
+                if !inner.is_empty() {
+                    result.push_str(&format!("{prelude}{{\n{inner}}}\n"));
+                }
+            }
+            Rule::Other(rule) => {
+                result.push_str(rule);
+                result.push('\n');
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CSS: &str = r#"
+/* the basics */
+@import url("other.css");
+body { color: red; }
+.unused, p.lead { margin: 0; }
+a:hover { color: blue; }
+@media (max-width: 600px) {
+    .unused { display: none; }
+    body { font-size: 12px; }
+}
+@keyframes spin { from { rotate: 0; } to { rotate: 360deg; } }
+"#;
+
+    #[test]
+    fn parses() {
+        let rules = parse(CSS);
+        assert_eq!(rules.len(), 6);
+        assert_eq!(rules[0], Rule::Other(r#"@import url("other.css");"#.into()));
+        assert!(matches!(&rules[4], Rule::Group { rules, .. } if rules.len() == 2));
+        assert!(matches!(&rules[5], Rule::Other(rule) if rule.starts_with("@keyframes spin{")));
+    }
+
+    #[test]
+    fn keeps_used_rules() {
+        let page = Html::parse_document(
+            r#"<html><body><p class="lead"><a href="/">hi</a></p></body></html>"#,
+        );
+        let critical = extract(&parse(CSS), &page);
+        assert!(critical.contains("body{color: red;}"));
+        assert!(critical.contains("p.lead{margin: 0;}"));
+        assert!(!critical.contains(".unused"));
+        assert!(critical.contains("a:hover{color: blue;}"));
+        assert!(critical.contains("@media (max-width: 600px){\nbody{font-size: 12px;}\n}"));
+        assert!(critical.contains("@keyframes spin"));
+    }
+}
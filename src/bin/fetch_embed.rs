@@ -0,0 +1,227 @@
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use scraper::{Html, Selector};
+use serde::Deserialize;
+use std::{collections::HashMap, env, fs};
+use tracing::{debug, info};
+use xesite_types::embed::{self, Embed, EmbedKind, Provider};
+
+const DATA_DIR: &str = "./data/embeds";
+const IMAGE_DIR: &str = "./static/img/embeds";
+
+/// The parts of an oEmbed response that embeds use.
+#[derive(Deserialize)]
+struct OEmbed {
+    title: Option<String>,
+    author_name: Option<String>,
+    thumbnail_url: Option<String>,
+    html: Option<String>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    color_eyre::install()?;
+    tracing_subscriber::fmt::init();
+
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        eprintln!("Usage: {} <url>...", args[0]);
+        std::process::exit(2);
+    }
+
+    let cli = reqwest::Client::builder()
+        .user_agent("github.com/Xe/site fetch_embed")
+        .build()?;
+
+    fs::create_dir_all(DATA_DIR)?;
+    fs::create_dir_all(IMAGE_DIR)?;
+    for url in &args[1..] {
+        let provider =
+            embed::provider(url).ok_or_else(|| eyre!("{url} isn't from an allowed provider"))?;
+        if provider.kind == EmbedKind::Toot {
+            return Err(eyre!(
+                "{url} is a toot, fetch it with fetch_mastodon_post instead"
+            ));
+        }
+
+        let mut embed = match provider.oembed {
+            Some(endpoint) => from_oembed(&cli, provider, endpoint, url).await?,
+            None => {
+                let page = cli
+                    .get(url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .text()
+                    .await?;
+                from_opengraph(provider, url, &Html::parse_document(&page))
+            }
+        };
+        if let Some(image) = embed.image.take() {
+            embed.image = Some(save_image(&cli, url, &image).await?);
+        }
+
+        let hash = xesite::hash_string(url.clone());
+        let fname = format!("{DATA_DIR}/{hash}.json");
+        let mut fout = fs::File::create(&fname)?;
+        serde_json::to_writer_pretty(&mut fout, &embed)?;
+        info!("wrote {url} to {fname}");
+    }
+
+    Ok(())
+}
+
+async fn from_oembed(
+    cli: &reqwest::Client,
+    provider: &Provider,
+    endpoint: &str,
+    url: &str,
+) -> Result<Embed> {
+    debug!("fetching {url} from {endpoint}");
+    let oembed: OEmbed = cli
+        .get(endpoint)
+        .query(&[("url", url), ("format", "json")])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let player = oembed.html.as_deref().and_then(player_of);
+    if provider.kind == EmbedKind::Video && player.is_none() {
+        return Err(eyre!("{} didn't give a player for {url}", provider.name));
+    }
+
+    Ok(Embed {
+        url: url.to_string(),
+        kind: provider.kind,
+        provider: provider.name.to_string(),
+        title: oembed.title.unwrap_or_else(|| url.to_string()),
+        description: None,
+        author: oembed.author_name,
+        image: oembed.thumbnail_url,
+        player,
+    })
+}
+
+/// The player in the `iframe` of an oEmbed's HTML. YouTube players are
+/// loaded from youtube-nocookie.com so they don't set cookies until the
+/// video is played.
+fn player_of(html: &str) -> Option<String> {
+    let selector = Selector::parse("iframe[src]").unwrap();
+    let src = Html::parse_fragment(html)
+        .select(&selector)
+        .next()?
+        .value()
+        .attr("src")?
+        .replace(
+            "https://www.youtube.com/embed/",
+            "https://www.youtube-nocookie.com/embed/",
+        );
+    embed::is_player(&src).then_some(src)
+}
+
+fn from_opengraph(provider: &Provider, url: &str, page: &Html) -> Embed {
+    let selector = Selector::parse("meta[property][content]").unwrap();
+    let og: HashMap<&str, &str> = page
+        .select(&selector)
+        .filter_map(|el| {
+            let property = el.value().attr("property")?.strip_prefix("og:")?;
+            Some((property, el.value().attr("content")?))
+        })
+        .collect();
+    let title = og.get("title").map(|s| s.to_string()).or_else(|| {
+        let selector = Selector::parse("title").unwrap();
+        page.select(&selector)
+            .next()
+            .map(|el| el.text().collect::<String>().trim().to_string())
+    });
+
+    Embed {
+        url: url.to_string(),
+        kind: provider.kind,
+        provider: og
+            .get("site_name")
+            .copied()
+            .unwrap_or(provider.name)
+            .to_string(),
+        title: title.unwrap_or_else(|| url.to_string()),
+        description: og.get("description").map(|s| s.to_string()),
+        author: None,
+        image: og
+            .get("image")
+            .filter(|s| s.starts_with("https://"))
+            .map(|s| s.to_string()),
+        player: None,
+    }
+}
+
+/// Downloads the thumbnail at `image` for the embed of `url` into `static`
+/// and returns where it's served from, so readers don't load it from the
+/// provider.
+async fn save_image(cli: &reqwest::Client, url: &str, image: &str) -> Result<String> {
+    debug!("fetching {image}");
+    let resp = cli.get(image).send().await?.error_for_status()?;
+    let ext = match resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|ct| ct.to_str().ok())
+    {
+        Some("image/jpeg") => "jpg",
+        Some("image/png") => "png",
+        Some("image/webp") => "webp",
+        Some("image/avif") => "avif",
+        other => return Err(eyre!("{image} isn't a picture I know about: {other:?}")),
+    };
+    let name = format!("{}.{ext}", xesite::hash_string(url.to_string()));
+    fs::write(format!("{IMAGE_DIR}/{name}"), resp.bytes().await?)
+        .wrap_err_with(|| format!("can't save {image}"))?;
+    Ok(format!("/static/img/embeds/{name}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn players() {
+        assert_eq!(
+            player_of(
+                r#"<iframe width="200" height="113" src="https://www.youtube.com/embed/dQw4w9WgXcQ?feature=oembed" allowfullscreen></iframe>"#
+            ),
+            Some("https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ?feature=oembed".into())
+        );
+        assert_eq!(
+            player_of(r#"<iframe src="https://evil.example/embed/1"></iframe>"#),
+            None
+        );
+        assert_eq!(player_of("<p>no player</p>"), None);
+    }
+
+    #[test]
+    fn opengraph() {
+        let github = embed::provider("https://github.com/Xe/site").unwrap();
+        let page = Html::parse_document(
+            r#"<html><head><title>Xe/site</title>
+<meta property="og:title" content="GitHub - Xe/site: My website">
+<meta property="og:description" content="My website.">
+<meta property="og:image" content="https://opengraph.githubassets.com/1/Xe/site">
+<meta property="og:site_name" content="GitHub">
+</head></html>"#,
+        );
+        let embed = from_opengraph(github, "https://github.com/Xe/site", &page);
+        assert_eq!(embed.title, "GitHub - Xe/site: My website");
+        assert_eq!(embed.description.as_deref(), Some("My website."));
+        assert_eq!(
+            embed.image.as_deref(),
+            Some("https://opengraph.githubassets.com/1/Xe/site")
+        );
+        assert_eq!(embed.kind, EmbedKind::Link);
+
+        let page = Html::parse_document(
+            r#"<html><head><title> Bare </title><meta property="og:image" content="javascript:alert(1)"></head></html>"#,
+        );
+        let embed = from_opengraph(github, "https://github.com/Xe", &page);
+        assert_eq!(embed.title, "Bare");
+        assert_eq!(embed.provider, "GitHub");
+        assert_eq!(embed.image, None);
+    }
+}
@@ -0,0 +1,344 @@
+use color_eyre::eyre::Result;
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    path::{Path, PathBuf},
+};
+use tracing::warn;
+
+/// Paragraphs shorter than this are too generic to be worth comparing.
+const MIN_WORDS: usize = 12;
+/// Words per shingle.
+const SHINGLE: usize = 4;
+const BANDS: usize = 32;
+const ROWS: usize = 4;
+/// How similar two paragraphs have to be to be reported.
+const THRESHOLD: f64 = 0.7;
+/// Paragraphs in more posts than this are boilerplate on purpose, like the
+/// credits at the end of gallery posts.
+const MAX_COPIES: usize = 3;
+
+const CONTENT_EXTENSIONS: &[&str] = &["markdown", "md"];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Paragraph {
+    file: PathBuf,
+    /// Line the paragraph starts on, counting from 1.
+    line: usize,
+    text: String,
+}
+
+/// Splits a post into its prose paragraphs, skipping front matter and code
+/// blocks.
+fn paragraphs(file: &Path, source: &str) -> Vec<Paragraph> {
+    let mut result = vec![];
+    let mut current: Option<(usize, String)> = None;
+    let mut in_code = false;
+    let mut in_front_matter = source.starts_with("---");
+
+    let mut finish = |current: &mut Option<(usize, String)>| {
+        if let Some((line, text)) = current.take() {
+            if text.split_whitespace().count() >= MIN_WORDS {
+                result.push(Paragraph {
+                    file: file.to_path_buf(),
+                    line,
+                    text,
+                });
+            }
+        }
+    };
+
+    for (i, line) in source.lines().enumerate() {
+        let trimmed = line.trim();
+        if in_front_matter {
+            if i != 0 && trimmed == "---" {
+                in_front_matter = false;
+            }
+            continue;
+        }
+        if trimmed.starts_with("```") {
+            finish(&mut current);
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+        // Tables repeat their headers, which isn't interesting.
+        if trimmed.is_empty() || trimmed.starts_with('|') {
+            finish(&mut current);
+            continue;
+        }
+        match &mut current {
+            Some((_, text)) => {
+                text.push(' ');
+                text.push_str(trimmed);
+            }
+            None => current = Some((i + 1, trimmed.to_string())),
+        }
+    }
+    finish(&mut current);
+
+    result
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// FNV-1a, so signatures are the same on every run.
+fn hash(data: &[u8], seed: u64) -> u64 {
+    let mut h = 0xcbf29ce484222325 ^ seed.wrapping_mul(0x9e3779b97f4a7c15);
+    for b in data {
+        h ^= *b as u64;
+        h = h.wrapping_mul(0x100000001b3);
+    }
+    h
+}
+
+fn shingles(text: &str) -> HashSet<u64> {
+    let words = words(text);
+    if words.len() < SHINGLE {
+        return HashSet::from([hash(words.join(" ").as_bytes(), 0)]);
+    }
+    words
+        .windows(SHINGLE)
+        .map(|w| hash(w.join(" ").as_bytes(), 0))
+        .collect()
+}
+
+/// Mixes a shingle with the seed of one of the minhash functions.
+fn rehash(shingle: u64, seed: u64) -> u64 {
+    let mut x = shingle ^ seed.wrapping_mul(0x9e3779b97f4a7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+fn minhash(shingles: &HashSet<u64>) -> Vec<u64> {
+    (0..(BANDS * ROWS) as u64)
+        .map(|seed| {
+            shingles
+                .iter()
+                .map(|s| rehash(*s, seed + 1))
+                .min()
+                .unwrap_or(u64::MAX)
+        })
+        .collect()
+}
+
+fn jaccard(a: &HashSet<u64>, b: &HashSet<u64>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+/// Pairs of paragraphs that are at least [THRESHOLD] similar. Candidates
+/// come from locality-sensitive hashing of the minhash signatures and are
+/// then compared exactly.
+fn duplicates(paragraphs: &[Paragraph]) -> Vec<(usize, usize, f64)> {
+    let shingles: Vec<HashSet<u64>> = paragraphs.iter().map(|p| shingles(&p.text)).collect();
+    let mut buckets: HashMap<(usize, Vec<u64>), Vec<usize>> = HashMap::new();
+    for (i, s) in shingles.iter().enumerate() {
+        let signature = minhash(s);
+        for (band, rows) in signature.chunks(ROWS).enumerate() {
+            buckets.entry((band, rows.to_vec())).or_default().push(i);
+        }
+    }
+
+    // Repetition inside one post is usually on purpose.
+    let mut candidates = HashSet::new();
+    for members in buckets.values() {
+        for (n, a) in members.iter().enumerate() {
+            for b in &members[n + 1..] {
+                if paragraphs[*a].file != paragraphs[*b].file {
+                    candidates.insert((*a, *b));
+                }
+            }
+        }
+    }
+
+    let mut result: Vec<(usize, usize, f64)> = candidates
+        .into_iter()
+        .map(|(a, b)| (a, b, jaccard(&shingles[a], &shingles[b])))
+        .filter(|(_, _, similarity)| *similarity >= THRESHOLD)
+        .collect();
+    result.sort_by_key(|(a, b, _)| (*a, *b));
+    result
+}
+
+/// Groups pairs of similar paragraphs into sets of copies, leaving out
+/// boilerplate that is in more than [MAX_COPIES] posts.
+fn groups(paragraphs: &[Paragraph], pairs: &[(usize, usize, f64)]) -> Vec<Vec<usize>> {
+    let mut parent: Vec<usize> = (0..paragraphs.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    for (a, b, _) in pairs {
+        let (a, b) = (root(&mut parent, *a), root(&mut parent, *b));
+        parent[a.max(b)] = a.min(b);
+    }
+
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (a, b, _) in pairs {
+        for i in [*a, *b] {
+            let group = groups.entry(root(&mut parent, i)).or_default();
+            if !group.contains(&i) {
+                group.push(i);
+            }
+        }
+    }
+
+    let mut result: Vec<Vec<usize>> = groups
+        .into_values()
+        .filter(|group| {
+            let files: HashSet<&PathBuf> = group.iter().map(|i| &paragraphs[*i].file).collect();
+            files.len() <= MAX_COPIES
+        })
+        .map(|mut group| {
+            group.sort();
+            group
+        })
+        .collect();
+    result.sort();
+    result
+}
+
+fn scan(dir: &Path, result: &mut Vec<Paragraph>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            scan(&path, result)?;
+        } else if path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| CONTENT_EXTENSIONS.contains(&ext))
+            .unwrap_or(false)
+        {
+            result.extend(paragraphs(&path, &fs::read_to_string(&path)?));
+        }
+    }
+    Ok(())
+}
+
+fn excerpt(text: &str) -> String {
+    let words: Vec<&str> = text.split_whitespace().take(10).collect();
+    format!("{}...", words.join(" "))
+}
+
+fn main() -> Result<()> {
+    color_eyre::install()?;
+    tracing_subscriber::fmt::init();
+
+    let mut args: Vec<String> = env::args().collect();
+    let strict = args.iter().any(|a| a == "--strict");
+    args.retain(|a| a != "--strict");
+    if args.len() < 2 {
+        eprintln!("Usage: {} [--strict] <content folder>...", args[0]);
+        std::process::exit(2);
+    }
+
+    let mut all = vec![];
+    for dir in &args[1..] {
+        scan(Path::new(dir), &mut all)?;
+    }
+
+    let found = groups(&all, &duplicates(&all));
+    for group in &found {
+        let places: Vec<String> = group
+            .iter()
+            .map(|i| format!("{}:{}", all[*i].file.display(), all[*i].line))
+            .collect();
+        warn!(
+            "duplicated paragraph {:?} in {}",
+            excerpt(&all[group[0]].text),
+            places.join(", ")
+        );
+    }
+
+    if strict && !found.is_empty() {
+        eprintln!("found {} duplicated paragraphs", found.len());
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POST: &str = "---
+title: test
+---
+
+This is the first paragraph of the post, and it is long enough to be compared
+with other paragraphs.
+
+```
+This is a code block, and it is long enough to be compared but it won't be.
+```
+
+short one
+";
+
+    #[test]
+    fn splits_paragraphs() {
+        let paragraphs = paragraphs(Path::new("a.markdown"), POST);
+        assert_eq!(paragraphs.len(), 1);
+        assert_eq!(paragraphs[0].line, 5);
+        assert!(paragraphs[0].text.ends_with("other paragraphs."));
+    }
+
+    #[test]
+    fn finds_near_duplicates() {
+        let para = |file: &str, text: &str| Paragraph {
+            file: file.into(),
+            line: 1,
+            text: text.into(),
+        };
+        let found = duplicates(&[
+            para(
+                "a.markdown",
+                "When you split a long draft into two posts it's easy to leave the same paragraph in both of them by accident.",
+            ),
+            para(
+                "b.markdown",
+                "Nix is a package manager that builds every package in isolation so that builds can be reproduced later.",
+            ),
+            para(
+                "c.markdown",
+                "When you split a long draft into two posts, it's easy to leave the same paragraph in both of them by mistake.",
+            ),
+        ]);
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].0, found[0].1), (0, 2));
+    }
+
+    #[test]
+    fn skips_boilerplate() {
+        let paragraphs: Vec<Paragraph> = (0..5)
+            .map(|i| Paragraph {
+                file: format!("{i}.markdown").into(),
+                line: 1,
+                text: "Created with a drawing app on a tablet using a stylus, shared here for you to enjoy.".into(),
+            })
+            .collect();
+        assert_eq!(
+            groups(&paragraphs, &duplicates(&paragraphs)),
+            Vec::<Vec<usize>>::new()
+        );
+        assert_eq!(
+            groups(&paragraphs[..2], &duplicates(&paragraphs[..2])),
+            vec![vec![0, 1]]
+        );
+    }
+}
@@ -0,0 +1,218 @@
+use chrono::{DateTime, Duration, Utc};
+use color_eyre::eyre::{Result, WrapErr};
+use regex::Regex;
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env, fs,
+};
+use tracing::{debug, info, warn};
+use url::Url;
+use xesite_types::archive::{ArchivedLink, LinkArchive};
+
+const OUT: &str = "./data/link_archive.json";
+const CONTENT: &[&str] = &["blog", "talks", "gallery", "notes", "incidents"];
+
+/// Links aren't checked again until they're this old.
+const RECHECK_DAYS: i64 = 30;
+
+/// Links to these aren't external, or are already archived.
+const SKIP_HOSTS: &[&str] = &["xeiaso.net", "archive.org", "localhost"];
+
+#[derive(Deserialize)]
+struct Available {
+    archived_snapshots: Snapshots,
+}
+
+#[derive(Deserialize)]
+struct Snapshots {
+    closest: Option<Snapshot>,
+}
+
+#[derive(Deserialize)]
+struct Snapshot {
+    available: bool,
+    url: String,
+}
+
+/// The external links in a post's markdown.
+fn links(markdown: &str) -> BTreeSet<String> {
+    let link = Regex::new(r#"https?://[^\s<>"'()\[\]`]+"#).unwrap();
+    link.find_iter(markdown)
+        .map(|m| {
+            m.as_str()
+                .trim_end_matches(['.', ',', ';', ':', '!', '?', '*', '_'])
+        })
+        .filter(|link| {
+            let Ok(url) = Url::parse(link) else {
+                return false;
+            };
+            let host = url.host_str().unwrap_or_default();
+            !SKIP_HOSTS
+                .iter()
+                .any(|skip| host == *skip || host.ends_with(&format!(".{skip}")))
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+/// Whether `link` was checked recently enough to be left alone.
+fn is_fresh(link: &ArchivedLink, now: DateTime<Utc>) -> bool {
+    DateTime::parse_from_rfc3339(&link.checked).map_or(false, |checked| {
+        now - checked.with_timezone(&Utc) < Duration::days(RECHECK_DAYS)
+    })
+}
+
+/// Whether the page at `url` is gone. Pages that time out or fail in other
+/// ways might be back later, so only missing pages and hosts that can't be
+/// reached count.
+async fn is_dead(cli: &reqwest::Client, url: &str) -> bool {
+    match cli.get(url).send().await {
+        Ok(resp) => matches!(resp.status().as_u16(), 404 | 410),
+        Err(why) => {
+            debug!("{url}: {why}");
+            why.is_connect()
+        }
+    }
+}
+
+/// The newest copy of `url` on the Internet Archive.
+async fn snapshot(cli: &reqwest::Client, url: &str) -> Result<Option<String>> {
+    let available: Available = cli
+        .get("https://archive.org/wayback/available")
+        .query(&[("url", url)])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(available
+        .archived_snapshots
+        .closest
+        .filter(|snapshot| snapshot.available)
+        .map(|snapshot| snapshot.url.replacen("http://", "https://", 1)))
+}
+
+/// Asks the Internet Archive to save `url` and returns where the copy is.
+async fn save(cli: &reqwest::Client, url: &str) -> Result<Option<String>> {
+    let resp = cli
+        .get(format!("https://web.archive.org/save/{url}"))
+        .send()
+        .await?
+        .error_for_status()?;
+    let saved = resp.url().as_str();
+    Ok(saved.contains("/web/").then(|| saved.to_string()))
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    color_eyre::install()?;
+    tracing_subscriber::fmt::init();
+
+    let args: Vec<String> = env::args().collect();
+    let save_missing = args.iter().any(|a| a == "--save");
+    if args.iter().skip(1).any(|a| a != "--save") {
+        eprintln!("Usage: {} [--save]", args[0]);
+        std::process::exit(2);
+    }
+
+    let mut archive: LinkArchive = match fs::read_to_string(OUT) {
+        Ok(data) => serde_json::from_str(&data).wrap_err_with(|| format!("can't parse {OUT}"))?,
+        Err(_) => LinkArchive::new(),
+    };
+
+    let mut all = BTreeSet::new();
+    for dir in CONTENT {
+        for path in glob::glob(&format!("{dir}/**/*.markdown"))? {
+            let path = path?;
+            let markdown = fs::read_to_string(&path)
+                .wrap_err_with(|| format!("can't read {}", path.display()))?;
+            all.extend(links(&markdown));
+        }
+    }
+    info!("{} external links", all.len());
+
+    let cli = reqwest::Client::builder()
+        .user_agent("github.com/Xe/site link_archive")
+        .timeout(std::time::Duration::from_secs(15))
+        .build()?;
+    let now = Utc::now();
+    let (mut dead, mut archived) = (0, 0);
+
+    for url in &all {
+        if archive.get(url).map_or(false, |link| is_fresh(link, now)) {
+            continue;
+        }
+        let is_dead = is_dead(&cli, url).await;
+        let mut found = snapshot(&cli, url).await.unwrap_or_else(|why| {
+            warn!("can't look up {url} on the Internet Archive: {why}");
+            None
+        });
+        if found.is_none() && !is_dead && save_missing {
+            found = save(&cli, url).await.unwrap_or_else(|why| {
+                warn!("can't save {url} to the Internet Archive: {why}");
+                None
+            });
+            // The Internet Archive limits how often pages can be saved.
+            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+        }
+        let old = archive.remove(url).unwrap_or_default();
+        let link = ArchivedLink {
+            snapshot: found.or(old.snapshot),
+            dead: is_dead,
+            checked: now.to_rfc3339(),
+        };
+        if link.dead {
+            dead += 1;
+            warn!("{url} is gone, archived copy: {:?}", link.snapshot);
+        }
+        if link.snapshot.is_some() {
+            archived += 1;
+        }
+        archive.insert(url.clone(), link);
+    }
+    info!("checked links, {dead} are gone and {archived} have copies");
+
+    let sorted: BTreeMap<_, _> = archive.iter().collect();
+    fs::write(OUT, serde_json::to_string_pretty(&sorted)?)?;
+    info!("wrote {OUT}");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_links() {
+        let found = links(
+            r#"See [the docs](https://docs.rs/axum/latest/axum/), <https://example.com/a?b=c>.
+Also https://example.org/page. And [me](https://xeiaso.net/blog), ![](https://cdn.xeiaso.net/file/x.png)
+and <a href="https://web.archive.org/web/2020/https://gone.example/">a copy</a>."#,
+        );
+        assert_eq!(
+            found.into_iter().collect::<Vec<_>>(),
+            vec![
+                "https://docs.rs/axum/latest/axum/",
+                "https://example.com/a?b=c",
+                "https://example.org/page",
+            ]
+        );
+    }
+
+    #[test]
+    fn freshness() {
+        let now = Utc::now();
+        let link = |checked: DateTime<Utc>| ArchivedLink {
+            checked: checked.to_rfc3339(),
+            ..Default::default()
+        };
+        assert!(is_fresh(&link(now - Duration::days(1)), now));
+        assert!(!is_fresh(
+            &link(now - Duration::days(RECHECK_DAYS + 1)),
+            now
+        ));
+        assert!(!is_fresh(&ArchivedLink::default(), now));
+    }
+}
@@ -0,0 +1,260 @@
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use regex::Regex;
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    env, fs,
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+use tracing::{info, warn};
+use xesite_types::Frontmatter;
+
+const DICTIONARY: &str = "./dhall/dictionary.dhall";
+const CHARACTERS: &str = "./dhall/characters.dhall";
+const CONTENT_EXTENSIONS: &[&str] = &["markdown", "md"];
+
+#[derive(Deserialize)]
+struct Character {
+    name: String,
+    #[serde(rename = "stickerName")]
+    sticker_name: String,
+}
+
+/// Every word the project dictionary accepts, lowercased.
+fn dictionary() -> Result<HashSet<String>> {
+    let groups: HashMap<String, Vec<String>> = serde_dhall::from_file(DICTIONARY)
+        .parse()
+        .wrap_err_with(|| format!("can't load {DICTIONARY}"))?;
+    let characters: Vec<Character> = serde_dhall::from_file(CHARACTERS)
+        .parse()
+        .wrap_err_with(|| format!("can't load {CHARACTERS}"))?;
+
+    Ok(groups
+        .into_values()
+        .flatten()
+        .chain(
+            characters
+                .into_iter()
+                .flat_map(|c| [c.name, c.sticker_name]),
+        )
+        .map(|w| w.to_lowercase())
+        .collect())
+}
+
+/// The prose of a post, line by line, without front matter, code, links or
+/// HTML. Lines are numbered from 1.
+fn prose(source: &str) -> Vec<(usize, String)> {
+    lazy_static::lazy_static! {
+        static ref NOISE: Regex = Regex::new(
+            r#"`[^`]*`|<[^>]*>|\]\([^)]*\)|\]\[[^\]]*\]|^\s*\[[^\]]+\]:.*$|https?://\S+|\{\{[^}]*\}\}"#
+        )
+        .unwrap();
+    }
+
+    let mut result = vec![];
+    let mut in_code = false;
+    let mut in_front_matter = source.starts_with("---");
+
+    for (i, line) in source.lines().enumerate() {
+        let trimmed = line.trim();
+        if in_front_matter {
+            if i != 0 && trimmed == "---" {
+                in_front_matter = false;
+            }
+            continue;
+        }
+        if trimmed.starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code || line.starts_with("    ") {
+            continue;
+        }
+        let text = NOISE.replace_all(line, " ");
+        if !text.trim().is_empty() {
+            result.push((i + 1, text.into_owned()));
+        }
+    }
+
+    result
+}
+
+/// Words worth checking. Acronyms, identifiers and anything with a digit in
+/// it are left alone.
+fn words(line: &str) -> Vec<&str> {
+    line.split(|c: char| !(c.is_alphabetic() || c == '\'' || c == '-'))
+        .map(|w| w.trim_matches(|c| c == '\'' || c == '-'))
+        .filter(|w| w.chars().count() > 1)
+        .filter(|w| !w.chars().all(|c| c.is_uppercase() || c == '-'))
+        .filter(|w| !w.chars().skip(1).any(char::is_uppercase) || w.contains('-'))
+        .collect()
+}
+
+/// Asks hunspell which of `words` it doesn't know.
+fn unknown(words: &BTreeSet<&str>) -> Result<HashSet<String>> {
+    let mut child = Command::new("hunspell")
+        .args(["-d", "en_US", "-l"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .wrap_err("can't run hunspell, is it installed?")?;
+    {
+        let mut stdin = child.stdin.take().unwrap();
+        for word in words {
+            writeln!(stdin, "{word}")?;
+        }
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(eyre!("hunspell exited with {}", output.status));
+    }
+    Ok(String::from_utf8(output.stdout)?
+        .lines()
+        .map(str::to_string)
+        .collect())
+}
+
+/// Misspelled words in a post and the lines they are on.
+fn check(source: &str, dictionary: &HashSet<String>) -> Result<BTreeMap<String, Vec<usize>>> {
+    let lines = prose(source);
+    let mut candidates = BTreeSet::new();
+    for (_, text) in &lines {
+        for word in words(text) {
+            if !dictionary.contains(&word.to_lowercase()) {
+                candidates.insert(word);
+            }
+        }
+    }
+    if candidates.is_empty() {
+        return Ok(BTreeMap::new());
+    }
+    let unknown = unknown(&candidates)?;
+
+    let mut result: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (line, text) in &lines {
+        for word in words(text) {
+            if unknown.contains(word) {
+                let lines = result.entry(word.to_string()).or_default();
+                if !lines.contains(line) {
+                    lines.push(*line);
+                }
+            }
+        }
+    }
+    Ok(result)
+}
+
+fn skipped(source: &str) -> bool {
+    if !source.starts_with("---") {
+        return false;
+    }
+    let Some(end) = source[3..].find("\n---") else {
+        return false;
+    };
+    serde_yaml::from_str::<Frontmatter>(&source[3..end + 3])
+        .map(|fm| fm.skip_spellcheck)
+        .unwrap_or(false)
+}
+
+fn scan(dir: &Path, dictionary: &HashSet<String>, found: &mut usize) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            scan(&path, dictionary, found)?;
+            continue;
+        }
+        if !path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| CONTENT_EXTENSIONS.contains(&ext))
+            .unwrap_or(false)
+        {
+            continue;
+        }
+
+        let source = fs::read_to_string(&path)?;
+        if skipped(&source) {
+            info!("skipping {}", path.display());
+            continue;
+        }
+        for (word, lines) in check(&source, dictionary)? {
+            let lines: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+            warn!("{}:{}: {word}", path.display(), lines.join(","));
+            *found += 1;
+        }
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    color_eyre::install()?;
+    tracing_subscriber::fmt::init();
+
+    let mut args: Vec<String> = env::args().collect();
+    let strict = args.iter().any(|a| a == "--strict");
+    args.retain(|a| a != "--strict");
+    if args.len() < 2 {
+        eprintln!("Usage: {} [--strict] <content folder>...", args[0]);
+        std::process::exit(2);
+    }
+
+    let dictionary = dictionary()?;
+    let mut found = 0;
+    for dir in &args[1..] {
+        scan(Path::new(dir), &dictionary, &mut found)?;
+    }
+
+    if strict && found != 0 {
+        eprintln!("found {found} misspelled words");
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_markup() {
+        let post = "---
+title: test
+---
+
+Install it with `nix-env -i hello` from [nixpkgs](https://github.com/NixOS/nixpkgs).
+
+```
+fn main() {}
+```
+
+<xeblog-conv name=\"Mara\" mood=\"hacker\">Teh words</xeblog-conv>
+";
+        let lines = prose(post);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].0, 5);
+        assert_eq!(
+            words(&lines[0].1),
+            vec!["Install", "it", "with", "from", "nixpkgs"]
+        );
+        assert_eq!(lines[1].0, 11);
+        assert_eq!(words(&lines[1].1), vec!["Teh", "words"]);
+    }
+
+    #[test]
+    fn leaves_identifiers_alone() {
+        assert_eq!(
+            words("the HTTP API uses ReplicaSets and x86 but isn't well-known"),
+            vec!["the", "uses", "and", "but", "isn't", "well-known"]
+        );
+    }
+
+    #[test]
+    fn skips_posts() {
+        assert!(skipped(
+            "---\ntitle: hi\ndate: 2023-01-01\nskip_spellcheck: true\n---\n\nhi"
+        ));
+        assert!(!skipped("---\ntitle: hi\ndate: 2023-01-01\n---\n\nhi"));
+    }
+}
@@ -0,0 +1,203 @@
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeSet, env, fs, path::Path, process::Command};
+use tracing::info;
+use xesite_types::fonts::Font;
+
+/// Code points every font keeps even if no post uses them yet, so templates
+/// and comments don't fall back to another font.
+const BASE_RANGES: &[(u32, u32)] = &[
+    (0x0020, 0x017F),
+    (0x00D7, 0x00D7),
+    (0x00F7, 0x00F7),
+    (0x2000, 0x206F),
+    (0x2074, 0x2074),
+    (0x20AC, 0x20AC),
+    (0x2122, 0x2122),
+    (0x2190, 0x21BB),
+    (0x2212, 0x2212),
+    (0x2215, 0x2215),
+    (0xF8FF, 0xF8FF),
+    (0xFEFF, 0xFEFF),
+    (0xFFFD, 0xFFFD),
+];
+
+/// Fonts that the head of every page asks the browser to preload.
+const PRELOAD: &[&str] = &["iosevka-aile-regular"];
+
+const CONTENT_EXTENSIONS: &[&str] = &["markdown", "md", "dhall"];
+
+fn main() -> Result<()> {
+    color_eyre::install()?;
+    tracing_subscriber::fmt::init();
+
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 4 {
+        eprintln!(
+            "Usage: {} <folder of ttf files> <output folder> <content folder>...",
+            args[0]
+        );
+        std::process::exit(2);
+    }
+
+    let mut chars = BTreeSet::new();
+    for dir in &args[3..] {
+        scan(Path::new(dir), &mut chars)?;
+    }
+    let unicodes = unicode_ranges(&chars);
+    info!("keeping {}", unicodes);
+
+    let out = Path::new(&args[2]);
+    fs::create_dir_all(out)?;
+
+    let mut manifest = vec![];
+    for entry in fs::read_dir(&args[1])? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("ttf") {
+            continue;
+        }
+        let stem = path.file_stem().unwrap().to_str().unwrap().to_string();
+        let tmp = out.join(format!("{stem}.tmp.woff2"));
+
+        let status = Command::new("pyftsubset")
+            .arg(&path)
+            .arg(format!("--output-file={}", tmp.display()))
+            .args([
+                "--flavor=woff2",
+                "--layout-features=*",
+                "--no-hinting",
+                "--desubroutinize",
+            ])
+            .arg(format!("--unicodes={unicodes}"))
+            .status()
+            .wrap_err("can't run pyftsubset")?;
+        if !status.success() {
+            return Err(eyre!(
+                "pyftsubset failed for {}: {}",
+                path.display(),
+                status
+            ));
+        }
+
+        let data = fs::read(&tmp)?;
+        fs::remove_file(&tmp)?;
+        let hash = hex::encode(Sha256::digest(&data));
+        let file = format!("{stem}.{}.woff2", &hash[..8]);
+        fs::write(out.join(&file), &data)?;
+        info!("{} -> {} ({} bytes)", path.display(), file, data.len());
+
+        let (family, style) = family_and_style(&stem);
+        manifest.push(Font {
+            family,
+            style,
+            file,
+            preload: PRELOAD.contains(&stem.as_str()),
+        });
+    }
+
+    manifest.sort_by(|a, b| a.file.cmp(&b.file));
+    fs::write(
+        out.join("manifest.json"),
+        serde_json::to_string_pretty(&manifest)?,
+    )?;
+
+    Ok(())
+}
+
+/// Adds every character in the content files under `dir` to `chars`.
+fn scan(dir: &Path, chars: &mut BTreeSet<char>) -> Result<()> {
+    for entry in fs::read_dir(dir).wrap_err_with(|| format!("can't read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            scan(&path, chars)?;
+            continue;
+        }
+        let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+        if !CONTENT_EXTENSIONS.contains(&ext) {
+            continue;
+        }
+        let body =
+            fs::read_to_string(&path).wrap_err_with(|| format!("can't read {}", path.display()))?;
+        chars.extend(body.chars().filter(|c| !c.is_control()));
+    }
+    Ok(())
+}
+
+/// Formats the base ranges plus `chars` the way pyftsubset wants them,
+/// merging neighbouring code points into ranges.
+fn unicode_ranges(chars: &BTreeSet<char>) -> String {
+    let mut points: BTreeSet<u32> = chars.iter().map(|c| *c as u32).collect();
+    for (start, end) in BASE_RANGES {
+        points.extend(*start..=*end);
+    }
+
+    let mut ranges: Vec<(u32, u32)> = vec![];
+    for point in points {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == point => *end = point,
+            _ => ranges.push((point, point)),
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            if start == end {
+                format!("U+{start:04X}")
+            } else {
+                format!("U+{start:04X}-{end:04X}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Turns a file name like `iosevka-aile-italic` into the family and style
+/// the CSS uses (`Iosevka Aile Iaso`, `italic`).
+fn family_and_style(stem: &str) -> (String, String) {
+    let mut parts: Vec<&str> = stem.split('-').collect();
+    let style = match parts.last() {
+        Some(&"italic") => "italic",
+        _ => "normal",
+    };
+    if parts.len() > 1 {
+        parts.pop();
+    }
+    let mut family: Vec<String> = parts
+        .iter()
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect();
+    family.push("Iaso".into());
+    (family.join(" "), style.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_include_content() {
+        let chars: BTreeSet<char> = "ŋəʃʒ".chars().collect();
+        let ranges = unicode_ranges(&chars);
+        assert!(ranges.starts_with("U+0020-017F,"));
+        assert!(ranges.contains(",U+0259,U+0283,U+0292,"));
+    }
+
+    #[test]
+    fn families() {
+        assert_eq!(
+            family_and_style("iosevka-aile-italic"),
+            ("Iosevka Aile Iaso".into(), "italic".into())
+        );
+        assert_eq!(
+            family_and_style("iosevka-curly-regular"),
+            ("Iosevka Curly Iaso".into(), "normal".into())
+        );
+    }
+}
@@ -0,0 +1,315 @@
+use color_eyre::eyre::{bail, Result, WrapErr};
+use regex::{Captures, Regex};
+use serde::Serialize;
+use std::{collections::HashMap, env, fs, io::Cursor, path::PathBuf, process::Command};
+use tracing::{info, warn};
+use xesite_types::{site::SiteConfig, Frontmatter};
+
+const OUT: &str = "./var/zine";
+const CONTENT: &str = "blog";
+const TEMPLATE: &str = include_str!("zine.typ");
+
+/// What `zine.typ` reads from `zine.json`.
+#[derive(Serialize)]
+struct Zine {
+    title: String,
+    author: String,
+    url: String,
+    from: String,
+    to: String,
+    parts: Vec<Part>,
+}
+
+#[derive(Serialize)]
+struct Part {
+    title: String,
+    date: String,
+    url: String,
+    /// The part as typst, relative to the work folder.
+    file: String,
+}
+
+struct Post {
+    slug: String,
+    front_matter: Frontmatter,
+    body: String,
+}
+
+/// Splits a post into its front matter and its markdown.
+fn split(source: &str) -> Option<(Frontmatter, &str)> {
+    let rest = source.strip_prefix("---")?;
+    let end = rest.find("\n---")?;
+    let front_matter = serde_yaml::from_str(&rest[..end]).ok()?;
+    Some((front_matter, &rest[end + 4..]))
+}
+
+/// The listed posts in `series`, oldest first.
+fn load(series: &str) -> Result<Vec<Post>> {
+    let mut posts = vec![];
+    for path in glob::glob(&format!("{CONTENT}/*.markdown"))? {
+        let path = path?;
+        let source =
+            fs::read_to_string(&path).wrap_err_with(|| format!("can't read {}", path.display()))?;
+        let Some((front_matter, body)) = split(&source) else {
+            warn!("can't parse the front matter of {}", path.display());
+            continue;
+        };
+        if front_matter.series.as_deref() != Some(series) || front_matter.unlisted {
+            continue;
+        }
+        posts.push(Post {
+            slug: path.file_stem().unwrap().to_string_lossy().into_owned(),
+            front_matter,
+            body: body.to_string(),
+        });
+    }
+    posts.sort_by(|a, b| a.front_matter.date.cmp(&b.front_matter.date));
+    Ok(posts)
+}
+
+fn attr<'a>(attrs: &'a str, name: &str) -> Option<&'a str> {
+    Regex::new(&format!(r#"\b{name}="([^"]*)""#))
+        .unwrap()
+        .captures(attrs)
+        .map(|caps| caps.get(1).unwrap().as_str())
+}
+
+/// Rewrites the site's components into markdown pandoc understands.
+/// Conversations become quotes, heroes and pictures become images, toots
+/// become links and the rest only make sense on a screen, so they're left
+/// out.
+fn flatten(markdown: &str, site: &SiteConfig) -> String {
+    let component = Regex::new(r"(?s)<xeblog-([a-z]+)([^>]*)>(.*?)</xeblog-[a-z]+>").unwrap();
+    component
+        .replace_all(markdown, |caps: &Captures| {
+            let attrs = &caps[2];
+            let block = match &caps[1] {
+                "conv" => {
+                    let name = attr(attrs, "name").unwrap_or_default();
+                    let quote = caps[3]
+                        .trim()
+                        .lines()
+                        .map(|line| format!("> {}", line.trim_start()))
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    format!(
+                        "> **{name}:** {}",
+                        quote.strip_prefix("> ").unwrap_or(&quote)
+                    )
+                }
+                "hero" => format!(
+                    "![{}]({})",
+                    attr(attrs, "prompt").unwrap_or_default(),
+                    site.cdn(&format!(
+                        "hero/{}-smol.png",
+                        attr(attrs, "file").unwrap_or_default()
+                    ))
+                ),
+                "picture" => format!(
+                    "![]({})",
+                    site.cdn(&format!(
+                        "{}-smol.png",
+                        attr(attrs, "path").unwrap_or_default()
+                    ))
+                ),
+                "toot" => format!("<{}>", attr(attrs, "url").unwrap_or_default()),
+                _ => return String::new(),
+            };
+            format!("\n\n{block}\n\n")
+        })
+        .into_owned()
+}
+
+fn image_link() -> Regex {
+    Regex::new(r"!\[([^\]]*)\]\(([^)\s]+)[^)]*\)").unwrap()
+}
+
+/// The images in `markdown`, in order, without repeats.
+fn images(markdown: &str) -> Vec<String> {
+    let mut found: Vec<String> = vec![];
+    for caps in image_link().captures_iter(markdown) {
+        if !found.iter().any(|url| url == &caps[2]) {
+            found.push(caps[2].to_string());
+        }
+    }
+    found
+}
+
+/// The image at `url`, from `static` for the site's own files.
+async fn fetch(cli: &reqwest::Client, url: &str) -> Result<Vec<u8>> {
+    if url.starts_with('/') {
+        return Ok(fs::read(format!(".{url}"))?);
+    }
+    Ok(cli
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?
+        .to_vec())
+}
+
+/// Turns an image into a grayscale PNG, since zines are photocopied.
+fn grayscale(data: &[u8]) -> Result<Vec<u8>> {
+    let mut png = vec![];
+    image::load_from_memory(data)?
+        .grayscale()
+        .write_to(&mut Cursor::new(&mut png), image::ImageOutputFormat::Png)?;
+    Ok(png)
+}
+
+fn run(cmd: &mut Command) -> Result<()> {
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let status = cmd
+        .status()
+        .wrap_err_with(|| format!("can't run {program}"))?;
+    if !status.success() {
+        bail!("{program} failed: {status}");
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    color_eyre::install()?;
+    tracing_subscriber::fmt::init();
+
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 2 {
+        eprintln!("Usage: {} <series>", args[0]);
+        std::process::exit(2);
+    }
+    let series = &args[1];
+
+    let posts = load(series)?;
+    if posts.is_empty() {
+        bail!("there aren't any listed posts in the series {series}");
+    }
+    info!("{} parts in {series}", posts.len());
+
+    let site = SiteConfig::default();
+    let dir = PathBuf::from(OUT).join(series);
+    let images_dir = dir.join("parts").join("images");
+    fs::create_dir_all(&images_dir)?;
+
+    let cli = reqwest::Client::builder()
+        .user_agent("github.com/Xe/site zine")
+        .timeout(std::time::Duration::from_secs(30))
+        .build()?;
+    // Image paths are relative to the part that shows them.
+    let mut local: HashMap<String, String> = HashMap::new();
+    let mut parts = vec![];
+
+    for (i, post) in posts.iter().enumerate() {
+        let markdown = flatten(&post.body, &site);
+        for url in images(&markdown) {
+            if local.contains_key(&url) {
+                continue;
+            }
+            match fetch(&cli, &url).await.and_then(|data| grayscale(&data)) {
+                Ok(png) => {
+                    let name = format!("images/{}.png", local.len() + 1);
+                    fs::write(dir.join("parts").join(&name), png)?;
+                    local.insert(url, name);
+                }
+                Err(why) => warn!("leaving out {url}: {why}"),
+            }
+        }
+        let markdown =
+            image_link().replace_all(&markdown, |caps: &Captures| match local.get(&caps[2]) {
+                Some(name) => format!("![{}]({name})", &caps[1]),
+                None => String::new(),
+            });
+
+        let source = dir.join("parts").join(format!("{}.md", i + 1));
+        let file = format!("parts/{}.typ", i + 1);
+        fs::write(&source, markdown.as_ref())?;
+        run(Command::new("pandoc")
+            .args(["--from", "markdown", "--to", "typst"])
+            .arg("--shift-heading-level-by=1")
+            .arg("--output")
+            .arg(dir.join(&file))
+            .arg(&source))?;
+
+        parts.push(Part {
+            title: post.front_matter.title.clone(),
+            date: post.front_matter.date.chars().take(10).collect(),
+            url: site.url(&format!("blog/{}", post.slug)),
+            file,
+        });
+    }
+
+    let zine = Zine {
+        title: series.clone(),
+        author: posts[0]
+            .front_matter
+            .author
+            .clone()
+            .unwrap_or_else(|| "Xe Iaso".to_string()),
+        url: site.url(&format!("blog/series/{series}")),
+        from: parts[0].date.clone(),
+        to: parts[parts.len() - 1].date.clone(),
+        parts,
+    };
+    fs::write(dir.join("zine.json"), serde_json::to_string_pretty(&zine)?)?;
+    fs::write(dir.join("zine.typ"), TEMPLATE)?;
+
+    let pdf = format!("{series}.pdf");
+    let booklet = format!("{series}-booklet.pdf");
+    run(Command::new("typst")
+        .current_dir(&dir)
+        .args(["compile", "zine.typ", &pdf]))?;
+    // Two pages to a side of a landscape letter sheet, in the order that
+    // makes a booklet when the pile is folded in half and stapled.
+    run(Command::new("pdfjam")
+        .current_dir(&dir)
+        .args(["--booklet", "true", "--landscape", "--letterpaper"])
+        .args(["--outfile", &booklet, &pdf]))?;
+
+    info!("wrote {}", dir.join(&booklet).display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flattens_components() {
+        let site = SiteConfig::default();
+        let post = r#"Hello.
+
+<xeblog-conv name="Mara" mood="hacker">Does this work
+on paper?</xeblog-conv>
+<xeblog-hero ai="Stable Diffusion" file="golden-hill" prompt="a golden hill"></xeblog-hero>
+
+<xeblog-toot url="https://pony.social/@cadey/1"></xeblog-toot>
+<xeblog-video path="talks/foo"></xeblog-video>
+
+![a diagram](/static/img/pageview_flowchart.png "How it works")
+"#;
+        let flat = flatten(post, &site);
+        assert!(flat.contains("> **Mara:** Does this work\n> on paper?"));
+        assert!(flat.contains("<https://pony.social/@cadey/1>"));
+        assert!(!flat.contains("xeblog"));
+        assert_eq!(
+            images(&flat),
+            vec![
+                site.cdn("hero/golden-hill-smol.png"),
+                "/static/img/pageview_flowchart.png".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn splits_front_matter() {
+        let (front_matter, body) =
+            split("---\ntitle: Tarot for Hackers\ndate: 2019-07-24\nseries: magick\n---\n\nHi")
+                .unwrap();
+        assert_eq!(front_matter.title, "Tarot for Hackers");
+        assert_eq!(front_matter.series.as_deref(), Some("magick"));
+        assert_eq!(body.trim(), "Hi");
+        assert!(split("no front matter").is_none());
+    }
+}
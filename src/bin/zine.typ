@@ -0,0 +1,48 @@
+// The zine a series is printed as. The zine binary copies this next to
+// zine.json and the parts it made with pandoc, see docs/zine.markdown.
+
+#let zine = json("zine.json")
+
+// Pandoc's typst output uses this for thematic breaks but only defines it
+// in its standalone template.
+#let horizontalrule = align(center, line(length: 30%, stroke: 0.5pt))
+
+#set document(title: zine.title, author: zine.author)
+#set text(10pt, font: "Iosevka Aile Iaso")
+#set par(justify: true)
+#set page(
+  paper: "us-statement",
+  margin: (inside: 0.75in, outside: 0.5in, y: 0.6in),
+)
+
+#show heading.where(level: 1): it => {
+  pagebreak(weak: true)
+  text(16pt, it)
+}
+#show raw: set text(8pt, font: "Iosevka Curly Iaso")
+#show link: underline
+#set image(fit: "contain")
+
+#page(numbering: none)[
+  #align(center + horizon)[
+    #text(28pt, weight: "bold", zine.title)
+
+    #v(1em)
+    #zine.parts.len() parts, #zine.from to #zine.to
+
+    #v(3em)
+    #zine.author \
+    #link(zine.url)
+  ]
+]
+
+#page(numbering: none, outline(title: "Contents", depth: 1))
+
+#set page(numbering: "1")
+#counter(page).update(1)
+
+#for part in zine.parts {
+  heading(level: 1, part.title)
+  text(8pt, style: "italic")[#part.date · #link(part.url)]
+  include part.file
+}
@@ -0,0 +1,276 @@
+//! Works out how much a reader downloads to see each post: the HTML, the
+//! local files it references and the images it pulls from the CDN. CDN
+//! sizes come from a manifest of the bucket when it has the file, and are
+//! guessed from the file type otherwise.
+
+use crate::app::{config::PageBudget, State};
+use axum::{body::Body, http::Request, Router};
+use color_eyre::eyre::{eyre, Result};
+use scraper::{Html, Selector};
+use std::{collections::HashMap, fmt, path::Path, sync::Arc};
+use tower::ServiceExt;
+
+/// Guessed sizes for CDN files that aren't in the manifest.
+const ESTIMATES: &[(&str, u64)] = &[
+    ("avif", 150_000),
+    ("webp", 200_000),
+    ("png", 400_000),
+    ("jpg", 300_000),
+    ("mp4", 5_000_000),
+];
+
+/// Guess for CDN files that aren't in the manifest or [ESTIMATES].
+const DEFAULT_ESTIMATE: u64 = 250_000;
+
+/// Everything a page makes the browser fetch while loading. Only the first
+/// `source` of a `picture` counts, as that's what a modern browser picks, and
+/// only the widest image in its `srcset`, as that's what a desktop picks.
+pub fn assets(html: &str) -> Vec<String> {
+    lazy_static::lazy_static! {
+        static ref PICTURE: Selector = Selector::parse("picture").unwrap();
+        static ref SOURCE: Selector = Selector::parse("source[srcset]").unwrap();
+        static ref OTHERS: Selector = Selector::parse(
+            "img[src], script[src], video[poster], link[rel=stylesheet][href], link[rel=preload][href], link[rel=modulepreload][href]"
+        )
+        .unwrap();
+    }
+
+    let doc = Html::parse_document(html);
+    let mut result: Vec<String> = vec![];
+    let mut push = |url: &str| {
+        if !url.is_empty() && !result.iter().any(|u| u == url) {
+            result.push(url.to_string());
+        }
+    };
+
+    for picture in doc.select(&PICTURE) {
+        if let Some(srcset) = picture
+            .select(&SOURCE)
+            .next()
+            .and_then(|s| s.value().attr("srcset"))
+        {
+            if let Some(url) = srcset
+                .split(',')
+                .last()
+                .and_then(|c| c.split_whitespace().next())
+            {
+                push(url);
+            }
+        }
+    }
+    for el in doc.select(&OTHERS) {
+        if el.value().name() == "img"
+            && el
+                .parent()
+                .and_then(|p| p.value().as_element())
+                .map(|p| p.name() == "picture")
+                .unwrap_or(false)
+        {
+            continue;
+        }
+        let attr = match el.value().name() {
+            "img" | "script" => "src",
+            "video" => "poster",
+            _ => "href",
+        };
+        if let Some(url) = el.value().attr(attr) {
+            push(url);
+        }
+    }
+
+    result
+}
+
+/// The weight of one page in bytes.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Weight {
+    pub path: String,
+    pub html: u64,
+    pub local: u64,
+    pub cdn: u64,
+    /// CDN files whose size was guessed.
+    pub estimated: usize,
+    /// Local files that don't exist.
+    pub missing: Vec<String>,
+}
+
+impl Weight {
+    pub fn total(&self) -> u64 {
+        self.html + self.local + self.cdn
+    }
+}
+
+impl fmt::Display for Weight {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:>8} KiB  {} (html {} KiB, local {} KiB, cdn {} KiB",
+            self.total() / 1024,
+            self.path,
+            self.html / 1024,
+            self.local / 1024,
+            self.cdn / 1024,
+        )?;
+        if self.estimated != 0 {
+            write!(f, ", {} cdn files estimated", self.estimated)?;
+        }
+        if !self.missing.is_empty() {
+            write!(f, ", missing {}", self.missing.join(" "))?;
+        }
+        write!(f, ")")
+    }
+}
+
+fn estimate(path: &str) -> u64 {
+    let ext = path.rsplit('.').next().unwrap_or("");
+    ESTIMATES
+        .iter()
+        .find(|(e, _)| *e == ext)
+        .map(|(_, size)| *size)
+        .unwrap_or(DEFAULT_ESTIMATE)
+}
+
+/// Weighs a rendered page. `static_dir` is where `/static` is served from
+/// and `cdn_prefix` is the URL of the CDN bucket with a trailing slash.
+pub fn weigh(
+    path: &str,
+    html: &str,
+    static_dir: &Path,
+    cdn_prefix: &str,
+    cdn_sizes: &HashMap<String, u64>,
+) -> Weight {
+    let mut weight = Weight {
+        path: path.to_string(),
+        html: html.len() as u64,
+        ..Default::default()
+    };
+
+    for url in assets(html) {
+        if let Some(file) = url.strip_prefix(cdn_prefix) {
+            match cdn_sizes.get(file) {
+                Some(size) => weight.cdn += size,
+                None => {
+                    weight.cdn += estimate(file);
+                    weight.estimated += 1;
+                }
+            }
+        } else if let Some(file) = url.strip_prefix("/static/") {
+            let file = file.split(['?', '#']).next().unwrap_or(file);
+            match std::fs::metadata(static_dir.join(file)) {
+                Ok(meta) => weight.local += meta.len(),
+                Err(_) => weight.missing.push(url),
+            }
+        }
+    }
+
+    weight
+}
+
+/// Weighs every post and logs a report, heaviest first. Returns an error if
+/// a post is over budget and the budget is strict.
+pub async fn check(state: Arc<State>, app: Router, budget: &PageBudget) -> Result<Vec<Weight>> {
+    let cdn_sizes: HashMap<String, u64> = match std::fs::read(&budget.cdn_sizes) {
+        Ok(data) => serde_json::from_slice(&data)?,
+        Err(why) => {
+            warn!(
+                "can't read CDN sizes from {}, guessing them all: {why}",
+                budget.cdn_sizes
+            );
+            HashMap::new()
+        }
+    };
+    let site = xesite_templates::context::site();
+    let cdn_prefix = site.cdn("");
+
+    let mut weights = vec![];
+    for post in state.everything.iter() {
+        let path = format!("/{}", post.link);
+        let resp = app
+            .clone()
+            .oneshot(Request::builder().uri(&path).body(Body::empty())?)
+            .await?;
+        let body = hyper::body::to_bytes(resp.into_body()).await?;
+        let html = String::from_utf8_lossy(&body);
+        weights.push(weigh(
+            &path,
+            &html,
+            Path::new("./static"),
+            &cdn_prefix,
+            &cdn_sizes,
+        ));
+    }
+    weights.sort_by_key(|w| std::cmp::Reverse(w.total()));
+
+    let over: Vec<&Weight> = weights
+        .iter()
+        .filter(|w| w.total() > budget.max_bytes)
+        .collect();
+    for weight in &weights {
+        if weight.total() > budget.max_bytes {
+            warn!("over budget: {weight}");
+        } else {
+            info!("{weight}");
+        }
+    }
+
+    if budget.strict && !over.is_empty() {
+        return Err(eyre!(
+            "{} posts are over the budget of {} KiB",
+            over.len(),
+            budget.max_bytes / 1024
+        ));
+    }
+    Ok(weights)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#"<!DOCTYPE html><html><head>
+<link rel="stylesheet" href="/static/css/hack.css">
+<link rel="stylesheet" href="/static/css/nope.css">
+<script src="https://example.com/script.js"></script>
+</head><body>
+<picture>
+<source type="image/avif" srcset="https://cdn.xeiaso.net/file/christine-static/hero/foo.avif">
+<source type="image/webp" srcset="https://cdn.xeiaso.net/file/christine-static/hero/foo.webp">
+<img src="https://cdn.xeiaso.net/file/christine-static/hero/foo-smol.png">
+</picture>
+<img src="https://cdn.xeiaso.net/file/christine-static/blog/bar.png">
+</body></html>"#;
+
+    #[test]
+    fn finds_assets() {
+        assert_eq!(
+            assets(PAGE),
+            vec![
+                "https://cdn.xeiaso.net/file/christine-static/hero/foo.avif",
+                "/static/css/hack.css",
+                "/static/css/nope.css",
+                "https://example.com/script.js",
+                "https://cdn.xeiaso.net/file/christine-static/blog/bar.png",
+            ]
+        );
+    }
+
+    #[test]
+    fn weighs_page() {
+        let sizes = HashMap::from([("hero/foo.avif".to_string(), 1000)]);
+        let weight = weigh(
+            "/blog/foo",
+            PAGE,
+            Path::new("./static"),
+            "https://cdn.xeiaso.net/file/christine-static/",
+            &sizes,
+        );
+        assert_eq!(weight.html, PAGE.len() as u64);
+        assert_eq!(weight.cdn, 1000 + estimate("blog/bar.png"));
+        assert_eq!(weight.estimated, 1);
+        assert_eq!(weight.missing, vec!["/static/css/nope.css"]);
+        assert_eq!(
+            weight.local,
+            std::fs::metadata("./static/css/hack.css").unwrap().len()
+        );
+    }
+}
@@ -0,0 +1,245 @@
+//! A weekly digest of new posts, new notes and the best fediverse replies.
+//! Every Monday the `digest` job drafts an issue for the week before, see
+//! [draft]. Drafts are edited and published from the admin area, and
+//! published issues are on `/digest`, in `/digest.rss` and ready to paste
+//! into an email at `/digest/:week/email`.
+
+use crate::{activitypub::store::Reply, app::State, post::Post, tmpl};
+use chrono::{prelude::*, Duration};
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::info;
+use xesite_templates::context::{self, RenderTarget};
+
+/// How many fediverse replies an issue highlights.
+const HIGHLIGHTS: usize = 5;
+
+/// A post or note in an issue.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Item {
+    pub link: String,
+    pub title: String,
+    /// The post's excerpt, as HTML.
+    pub summary: String,
+}
+
+impl From<&Post> for Item {
+    fn from(post: &Post) -> Self {
+        Self {
+            link: post.link.clone(),
+            title: post.front_matter.title.clone(),
+            summary: post.excerpt.clone(),
+        }
+    }
+}
+
+/// A fediverse reply in an issue, with the post it's about.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Highlight {
+    pub link: String,
+    pub title: String,
+    pub author: String,
+    pub author_url: String,
+    /// Sanitized HTML, as it was sent to the inbox.
+    pub content: String,
+    pub url: String,
+}
+
+/// One week's digest. Everything in it is copied from the posts and replies
+/// when it's assembled, so editing a post later doesn't change old issues.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Issue {
+    /// The Monday the week starts on.
+    pub week: NaiveDate,
+    /// What's written at the top, in markdown.
+    pub intro: String,
+    pub posts: Vec<Item>,
+    pub notes: Vec<Item>,
+    pub replies: Vec<Highlight>,
+    /// When it was published, or `None` while it's a draft.
+    pub published: Option<DateTime<Utc>>,
+}
+
+impl Issue {
+    pub fn link(&self) -> String {
+        format!("digest/{}", self.week)
+    }
+
+    pub fn title(&self) -> String {
+        format!("The week of {}", self.week.format("%B %-d, %Y"))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.posts.is_empty() && self.notes.is_empty() && self.replies.is_empty()
+    }
+
+    /// Takes the post, note or reply at `link` out of the issue. Returns
+    /// true if it was there.
+    pub fn drop_item(&mut self, link: &str) -> bool {
+        let before = self.posts.len() + self.notes.len() + self.replies.len();
+        self.posts.retain(|i| i.link != link);
+        self.notes.retain(|i| i.link != link);
+        self.replies.retain(|h| h.url != link);
+        before != self.posts.len() + self.notes.len() + self.replies.len()
+    }
+
+    /// The issue for feeds, see [tmpl::digest::body].
+    pub fn feed_html(&self) -> String {
+        context::with_target(RenderTarget::Feed, || {
+            tmpl::digest::body(self).into_string()
+        })
+    }
+}
+
+/// The Monday of the week `day` is in.
+pub fn week_of(day: NaiveDate) -> NaiveDate {
+    day - Duration::days(day.weekday().num_days_from_monday() as i64)
+}
+
+fn in_week(week: NaiveDate, day: NaiveDate) -> bool {
+    day >= week && day < week + Duration::days(7)
+}
+
+/// Gathers the listed `posts` and `notes` from the week starting on `week`,
+/// oldest first, and the newest fediverse replies sent that week.
+pub fn assemble<'a>(
+    week: NaiveDate,
+    posts: impl IntoIterator<Item = &'a Post>,
+    notes: impl IntoIterator<Item = &'a Post>,
+    replies: &[(String, Reply)],
+) -> Issue {
+    let mut titles = HashMap::new();
+    let mut listed_in_week = |posts: &mut dyn Iterator<Item = &'a Post>| {
+        let mut items: Vec<&Post> = posts
+            .inspect(|post| {
+                titles.insert(post.link.as_str(), post.front_matter.title.as_str());
+            })
+            .filter(|post| post.listed() && in_week(week, post.date.date_naive()))
+            .collect();
+        items.sort_by_key(|post| post.date);
+        items.into_iter().map(Item::from).collect::<Vec<_>>()
+    };
+    let posts = listed_in_week(&mut posts.into_iter());
+    let notes = listed_in_week(&mut notes.into_iter());
+
+    let mut sent: Vec<(DateTime<FixedOffset>, &str, &Reply)> = replies
+        .iter()
+        .filter_map(|(link, reply)| {
+            let published = DateTime::parse_from_rfc3339(reply.published.as_deref()?).ok()?;
+            in_week(week, published.date_naive()).then_some((published, link.as_str(), reply))
+        })
+        .collect();
+    sent.sort_by(|a, b| b.0.cmp(&a.0));
+    let replies = sent
+        .into_iter()
+        .take(HIGHLIGHTS)
+        .map(|(_, link, reply)| Highlight {
+            link: link.to_string(),
+            title: titles.get(link).copied().unwrap_or(link).to_string(),
+            author: reply.actor.name.clone(),
+            author_url: reply.actor.url.clone(),
+            content: reply.content.clone(),
+            url: reply.url.clone(),
+        })
+        .collect();
+
+    Issue {
+        week,
+        posts,
+        notes,
+        replies,
+        ..Default::default()
+    }
+}
+
+/// [assemble]s the week starting on `week` from everything the site has.
+pub async fn gather(state: &State, week: NaiveDate) -> Issue {
+    let replies = match &state.activitypub {
+        Some(ap) => ap.store.recent_replies(usize::MAX).await,
+        None => vec![],
+    };
+    assemble(
+        week,
+        state.blog.iter().chain(&state.gallery).chain(&state.talks),
+        &state.garden.notes,
+        &replies,
+    )
+}
+
+/// Saves a draft of the issue for the week starting on `week`, unless there
+/// already is one or nothing happened that week.
+pub async fn draft(state: &State, week: NaiveDate) -> Result<Option<Issue>> {
+    let digests = state.storage.digests();
+    if digests.get(week).await?.is_some() {
+        return Ok(None);
+    }
+    let issue = gather(state, week).await;
+    if issue.is_empty() {
+        info!("digest: nothing happened in the week of {week}");
+        return Ok(None);
+    }
+    digests.save(&issue).await?;
+    info!("digest: drafted the week of {week}");
+    Ok(Some(issue))
+}
+
+/// The week before the one `today` is in, which is what the job drafts.
+pub fn last_week(today: NaiveDate) -> NaiveDate {
+    week_of(today) - Duration::days(7)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activitypub::store::Profile;
+
+    #[test]
+    fn weeks() {
+        let day = |m, d| NaiveDate::from_ymd_opt(2023, m, d).unwrap();
+        assert_eq!(week_of(day(7, 12)), day(7, 10));
+        assert_eq!(week_of(day(7, 10)), day(7, 10));
+        assert_eq!(week_of(day(7, 16)), day(7, 10));
+        assert_eq!(last_week(day(7, 17)), day(7, 10));
+        assert_eq!(last_week(day(7, 2)), day(6, 19));
+    }
+
+    #[tokio::test]
+    async fn assembles() -> Result<()> {
+        let blog = crate::post::load("blog").await?;
+        let newest = blog.iter().find(|p| p.listed()).unwrap();
+        let week = week_of(newest.date.date_naive());
+        let reply = |id: &str, published: &str| Reply {
+            id: id.into(),
+            actor: Profile {
+                name: "Mara".into(),
+                url: "https://mara.example/".into(),
+                ..Default::default()
+            },
+            content: "<p>Nice!</p>".into(),
+            url: format!("https://mara.example/{id}"),
+            published: Some(published.into()),
+        };
+        let replies = vec![
+            (
+                newest.link.clone(),
+                reply("1", &format!("{week}T12:00:00Z")),
+            ),
+            (newest.link.clone(), reply("2", "1999-01-01T12:00:00Z")),
+        ];
+
+        let mut issue = assemble(week, &blog, [], &replies);
+        assert!(issue.posts.iter().any(|i| i.link == newest.link));
+        assert!(issue.notes.is_empty());
+        assert_eq!(issue.replies.len(), 1);
+        assert_eq!(issue.replies[0].title, newest.front_matter.title);
+        assert_eq!(issue.link(), format!("digest/{week}"));
+
+        assert!(issue.drop_item("https://mara.example/1"));
+        assert!(!issue.drop_item("https://mara.example/1"));
+        assert!(issue.replies.is_empty());
+
+        assert!(assemble(week + Duration::days(7000), &blog, [], &replies).is_empty());
+        Ok(())
+    }
+}
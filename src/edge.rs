@@ -0,0 +1,208 @@
+//! Redirects, rewrites and response headers in one place, so the server and
+//! the configs exported for CDN edges (`xesite export-edge`) can't drift
+//! apart.
+
+use crate::{app::Redirect, post::Post};
+use color_eyre::eyre::{eyre, Result};
+use std::{fmt::Write, fs, path::Path};
+
+pub const CACHE_CONTROL: &str = "public, max-age=3600, stale-if-error=60";
+pub const WEBMENTION: &str =
+    r#"<https://mi.within.website/api/webmention/accept>; rel="webmention""#;
+pub const CLACKS: &str = "Ashlynn";
+pub const HACKER: &str =
+    "If you are reading this, check out /signalboost to find people for your team";
+
+/// Headers set on every response.
+pub const HEADERS: &[(&str, &str)] = &[
+    ("Cache-Control", CACHE_CONTROL),
+    ("Link", WEBMENTION),
+    ("X-Clacks-Overhead", CLACKS),
+    ("X-Hacker", HACKER),
+];
+
+/// Paths that are served from a file somewhere else without redirecting.
+pub const REWRITES: &[(&str, &str)] = &[
+    ("/.well-known/assetlinks.json", "/static/assetlinks.json"),
+    ("/robots.txt", "/static/robots.txt"),
+    ("/favicon.ico", "/static/favicon/favicon.ico"),
+];
+
+/// A redirect or rewrite. A status of 200 means a rewrite.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rule {
+    pub from: String,
+    pub to: String,
+    pub status: u16,
+}
+
+impl Rule {
+    pub fn is_rewrite(&self) -> bool {
+        self.status == 200
+    }
+}
+
+/// Every redirect and rewrite: the rewrites above, the configured redirects
+/// and posts that moved somewhere else with `redirect_to`.
+pub fn rules(redirects: &[Redirect], posts: &[Post]) -> Vec<Rule> {
+    let mut result: Vec<Rule> = REWRITES
+        .iter()
+        .map(|(from, to)| Rule {
+            from: from.to_string(),
+            to: to.to_string(),
+            status: 200,
+        })
+        .collect();
+    result.extend(redirects.iter().map(|r| Rule {
+        from: r.from.clone(),
+        to: r.to.clone(),
+        status: r.status,
+    }));
+    result.extend(posts.iter().filter_map(|post| {
+        post.front_matter.redirect_to.as_ref().map(|to| Rule {
+            from: format!("/{}", post.link),
+            to: to.clone(),
+            status: 301,
+        })
+    }));
+    result
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// `_redirects` and `_headers`, as used by Netlify and Cloudflare Pages.
+    Netlify,
+    /// A Caddyfile snippet named `xesite`.
+    Caddy,
+    /// An nginx config to include in a `server` block.
+    Nginx,
+}
+
+impl std::str::FromStr for Format {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "netlify" => Ok(Format::Netlify),
+            "caddy" => Ok(Format::Caddy),
+            "nginx" => Ok(Format::Nginx),
+            _ => Err(eyre!("unknown format {s:?}, use netlify, caddy or nginx")),
+        }
+    }
+}
+
+/// Quotes a value for Caddy and nginx configs.
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The files to write for `format`, as (file name, contents) pairs.
+pub fn export(format: Format, rules: &[Rule]) -> Vec<(&'static str, String)> {
+    let mut out = String::new();
+    match format {
+        Format::Netlify => {
+            for rule in rules {
+                writeln!(out, "{} {} {}", rule.from, rule.to, rule.status).unwrap();
+            }
+            let mut headers = String::from("/*\n");
+            for (name, value) in HEADERS {
+                writeln!(headers, "  {name}: {value}").unwrap();
+            }
+            vec![("_redirects", out), ("_headers", headers)]
+        }
+        Format::Caddy => {
+            out.push_str("(xesite) {\n\theader {\n");
+            for (name, value) in HEADERS {
+                writeln!(out, "\t\t{name} {}", quote(value)).unwrap();
+            }
+            out.push_str("\t}\n");
+            for rule in rules {
+                if rule.is_rewrite() {
+                    writeln!(out, "\trewrite {} {}", rule.from, rule.to).unwrap();
+                } else {
+                    writeln!(out, "\tredir {} {} {}", rule.from, rule.to, rule.status).unwrap();
+                }
+            }
+            out.push_str("}\n");
+            vec![("Caddyfile", out)]
+        }
+        Format::Nginx => {
+            for (name, value) in HEADERS {
+                writeln!(out, "add_header {name} {} always;", quote(value)).unwrap();
+            }
+            for rule in rules {
+                if rule.is_rewrite() {
+                    writeln!(
+                        out,
+                        "location = {} {{ rewrite ^ {} last; }}",
+                        rule.from, rule.to
+                    )
+                    .unwrap();
+                } else {
+                    writeln!(
+                        out,
+                        "location = {} {{ return {} {}; }}",
+                        rule.from,
+                        rule.status,
+                        quote(&rule.to)
+                    )
+                    .unwrap();
+                }
+            }
+            vec![("xesite.conf", out)]
+        }
+    }
+}
+
+/// Writes the edge config for `format` into `dir`.
+pub fn write(format: Format, rules: &[Rule], dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    for (name, contents) in export(format, rules) {
+        let path = dir.join(name);
+        fs::write(&path, contents)?;
+        info!("wrote {}", path.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<Rule> {
+        rules(
+            &[Redirect {
+                from: "/old".into(),
+                to: "/new".into(),
+                status: 308,
+            }],
+            &[],
+        )
+    }
+
+    #[test]
+    fn netlify() {
+        let files = export(Format::Netlify, &sample());
+        assert_eq!(files[0].0, "_redirects");
+        assert!(files[0].1.contains("/robots.txt /static/robots.txt 200\n"));
+        assert!(files[0].1.contains("/old /new 308\n"));
+        assert!(files[1].1.starts_with("/*\n  Cache-Control: public"));
+    }
+
+    #[test]
+    fn caddy() {
+        let (_, caddy) = &export(Format::Caddy, &sample())[0];
+        assert!(caddy.contains("\trewrite /favicon.ico /static/favicon/favicon.ico\n"));
+        assert!(caddy.contains("\tredir /old /new 308\n"));
+        assert!(caddy.contains(
+            r#"Link "<https://mi.within.website/api/webmention/accept>; rel=\"webmention\"""#
+        ));
+    }
+
+    #[test]
+    fn nginx() {
+        let (_, nginx) = &export(Format::Nginx, &sample())[0];
+        assert!(nginx.contains("location = /old { return 308 \"/new\"; }\n"));
+        assert!(nginx.contains("add_header X-Clacks-Overhead \"Ashlynn\" always;\n"));
+    }
+}
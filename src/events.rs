@@ -0,0 +1,364 @@
+//! Things that happen to the site's content: a post coming out, a post
+//! changing between deploys, or a static asset changing. Subsystems that
+//! care about them [Bus::subscribe] with a name when the site starts,
+//! instead of being called one after another from wherever posts are
+//! loaded. `add_subscribers` in `main.rs` has the list.
+//!
+//! Posts are loaded once at startup, so events come from comparing them to
+//! a [Ledger] of what was there last time, which is saved to `eventsPath`.
+//! The `events` job does this every few minutes, which is how a post whose
+//! date was in the future gets a [Event::PostPublished] once its day comes.
+//! The first time there's no ledger, everything is taken as already sent
+//! so subscribers don't get the whole archive at once.
+
+use crate::post::Post;
+use chrono::prelude::*;
+use color_eyre::eyre::Result;
+use futures::future::BoxFuture;
+use lazy_static::lazy_static;
+use prometheus::{opts, register_int_counter_vec, IntCounterVec};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    future::Future,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::{fs, sync::OnceCell};
+
+lazy_static! {
+    static ref HANDLED: IntCounterVec = register_int_counter_vec!(
+        opts!("events_handled", "Number of events each subscriber handled"),
+        &["event", "subscriber", "result"]
+    )
+    .unwrap();
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// A post is out: it's new, or its date just came.
+    PostPublished { link: String },
+    /// A post that was already out changed.
+    PostUpdated { link: String },
+    /// A file in `static` was added or changed. `path` starts with
+    /// `/static/`.
+    AssetChanged { path: String },
+}
+
+impl Event {
+    /// What the event is called in logs and metrics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Event::PostPublished { .. } => "post_published",
+            Event::PostUpdated { .. } => "post_updated",
+            Event::AssetChanged { .. } => "asset_changed",
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::PostPublished { link } | Event::PostUpdated { link } => {
+                write!(f, "{} {link}", self.kind())
+            }
+            Event::AssetChanged { path } => write!(f, "{} {path}", self.kind()),
+        }
+    }
+}
+
+/// What a post looks like, so changes to it can be noticed.
+fn post_hash(post: &Post) -> String {
+    let mut h = Sha256::new();
+    h.update(post.front_matter.title.as_bytes());
+    h.update([0]);
+    h.update(post.body_html.as_bytes());
+    hex::encode(h.finalize())
+}
+
+/// Hashes of every file under `dir`, by the path it's served at.
+pub fn asset_hashes(dir: &Path) -> Result<BTreeMap<String, String>> {
+    let mut result = BTreeMap::new();
+    for path in glob::glob(&format!("{}/**/*", dir.display()))? {
+        let path = path?;
+        if !path.is_file() {
+            continue;
+        }
+        let name = path.strip_prefix(dir)?.to_string_lossy().replace('\\', "/");
+        result.insert(
+            format!("/static/{name}"),
+            hex::encode(Sha256::digest(std::fs::read(&path)?)),
+        );
+    }
+    Ok(result)
+}
+
+/// What every post and asset looked like the last time events were sent.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Ledger {
+    pub posts: BTreeMap<String, String>,
+    pub assets: BTreeMap<String, String>,
+}
+
+impl Ledger {
+    async fn load(path: &Path) -> Result<Option<Self>> {
+        match fs::read(path).await {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    async fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(path, serde_json::to_vec(self)?).await?;
+        Ok(())
+    }
+
+    /// Updates the ledger to match `posts` and `assets` and returns what
+    /// changed. Posts dated after `today` aren't out yet, so they're left
+    /// for later.
+    pub fn update<'a>(
+        &mut self,
+        posts: impl IntoIterator<Item = &'a Post>,
+        assets: &BTreeMap<String, String>,
+        today: NaiveDate,
+    ) -> Vec<Event> {
+        let mut result = vec![];
+        for post in posts {
+            if post.date.date_naive() > today {
+                continue;
+            }
+            let hash = post_hash(post);
+            match self.posts.insert(post.link.clone(), hash.clone()) {
+                None => result.push(Event::PostPublished {
+                    link: post.link.clone(),
+                }),
+                Some(old) if old != hash => result.push(Event::PostUpdated {
+                    link: post.link.clone(),
+                }),
+                Some(_) => {}
+            }
+        }
+        for (path, hash) in assets {
+            if self.assets.insert(path.clone(), hash.clone()).as_ref() != Some(hash) {
+                result.push(Event::AssetChanged { path: path.clone() });
+            }
+        }
+        result
+    }
+}
+
+type Handler = Box<dyn Fn(Event) -> BoxFuture<'static, Result<()>> + Send + Sync>;
+
+struct Subscriber {
+    name: String,
+    handler: Handler,
+}
+
+/// Sends events to everything that subscribed to them.
+pub struct Bus {
+    path: PathBuf,
+    static_dir: PathBuf,
+    subscribers: std::sync::Mutex<Vec<Arc<Subscriber>>>,
+    /// Assets are part of the deploy, so they're only hashed once.
+    assets: OnceCell<BTreeMap<String, String>>,
+}
+
+impl Bus {
+    pub fn new(path: PathBuf, static_dir: PathBuf) -> Self {
+        Self {
+            path,
+            static_dir,
+            subscribers: std::sync::Mutex::new(vec![]),
+            assets: OnceCell::new(),
+        }
+    }
+
+    /// Calls `handler` with every event. Subscribers get events in the order
+    /// they subscribed, one at a time.
+    pub fn subscribe<F, Fut>(&self, name: &str, handler: F)
+    where
+        F: Fn(Event) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.subscribers.lock().unwrap().push(Arc::new(Subscriber {
+            name: name.to_string(),
+            handler: Box::new(move |event| Box::pin(handler(event))),
+        }));
+    }
+
+    /// Sends `event` to every subscriber. A subscriber that fails is logged
+    /// and doesn't stop the rest from getting it.
+    pub async fn emit(&self, event: Event) {
+        let subscribers = self.subscribers.lock().unwrap().clone();
+        debug!("events: {event}");
+        for sub in subscribers {
+            let result = match (sub.handler)(event.clone()).await {
+                Ok(()) => "ok",
+                Err(why) => {
+                    error!("events: {} can't handle {event}: {why:?}", sub.name);
+                    "error"
+                }
+            };
+            HANDLED
+                .with_label_values(&[event.kind(), &sub.name, result])
+                .inc();
+        }
+    }
+
+    /// Compares `posts` and the static assets to the ledger and sends
+    /// an event for everything that changed.
+    pub async fn check<'a>(&self, posts: impl IntoIterator<Item = &'a Post>) -> Result<()> {
+        let assets = self
+            .assets
+            .get_or_try_init(|| {
+                let dir = self.static_dir.clone();
+                async move { tokio::task::spawn_blocking(move || asset_hashes(&dir)).await? }
+            })
+            .await?;
+        let today = Utc::now().date_naive();
+        let (mut ledger, first) = match Ledger::load(&self.path).await? {
+            Some(ledger) => (ledger, false),
+            None => (Ledger::default(), true),
+        };
+        let events = ledger.update(posts, assets, today);
+        if first {
+            info!(
+                "events: starting a new ledger with {} posts",
+                ledger.posts.len()
+            );
+            return ledger.save(&self.path).await;
+        }
+        if events.is_empty() {
+            return Ok(());
+        }
+        // Saved first, so a subscriber that takes down the site doesn't get
+        // the same events over and over.
+        ledger.save(&self.path).await?;
+        for event in events {
+            self.emit(event).await;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::Mutex;
+
+    #[tokio::test]
+    async fn ledger() -> Result<()> {
+        let posts = crate::post::load("blog").await?;
+        let mut post = posts[0].clone();
+        let today = post.date.date_naive();
+        let mut assets = BTreeMap::from([("/static/a.css".to_string(), "1".to_string())]);
+
+        let mut ledger = Ledger::default();
+        let events = ledger.update([&post], &assets, today);
+        assert_eq!(
+            events,
+            vec![
+                Event::PostPublished {
+                    link: post.link.clone()
+                },
+                Event::AssetChanged {
+                    path: "/static/a.css".into()
+                },
+            ]
+        );
+        assert!(ledger.update([&post], &assets, today).is_empty());
+
+        post.body_html.push_str("<p>Update: it works now.</p>");
+        assets.insert("/static/a.css".into(), "2".into());
+        let events = ledger.update([&post], &assets, today);
+        assert_eq!(
+            events,
+            vec![
+                Event::PostUpdated {
+                    link: post.link.clone()
+                },
+                Event::AssetChanged {
+                    path: "/static/a.css".into()
+                },
+            ]
+        );
+
+        // Posts from the future wait for their day.
+        let mut later = post.clone();
+        later.link = "blog/later".into();
+        later.date = later.date + chrono::Duration::days(1);
+        assert!(ledger.update([&later], &assets, today).is_empty());
+        assert_eq!(
+            ledger.update([&later], &assets, later.date.date_naive()),
+            vec![Event::PostPublished {
+                link: "blog/later".into()
+            }]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn subscribers_get_events_in_order() {
+        let got = Arc::new(Mutex::new(vec![]));
+        let bus = Bus::new("./var/test-events.json".into(), "./static".into());
+        for name in ["first", "broken", "last"] {
+            let got = got.clone();
+            bus.subscribe(name, move |event| {
+                let got = got.clone();
+                async move {
+                    got.lock().await.push(format!("{name}: {event}"));
+                    match name {
+                        "broken" => Err(color_eyre::eyre::eyre!("oops")),
+                        _ => Ok(()),
+                    }
+                }
+            });
+        }
+        bus.emit(Event::PostPublished {
+            link: "blog/foo".into(),
+        })
+        .await;
+        assert_eq!(
+            *got.lock().await,
+            vec![
+                "first: post_published blog/foo",
+                "broken: post_published blog/foo",
+                "last: post_published blog/foo",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn first_check_sends_nothing() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("xesite-events-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("static"))?;
+        std::fs::write(dir.join("static/a.css"), "body {}")?;
+        let posts = crate::post::load("blog").await?;
+
+        let got = Arc::new(Mutex::new(vec![]));
+        let bus = Bus::new(dir.join("events.json"), dir.join("static"));
+        {
+            let got = got.clone();
+            bus.subscribe("test", move |event| {
+                let got = got.clone();
+                async move {
+                    got.lock().await.push(event);
+                    Ok(())
+                }
+            });
+        }
+        bus.check(&posts).await?;
+        bus.check(&posts).await?;
+        assert!(got.lock().await.is_empty());
+
+        let ledger = Ledger::load(&dir.join("events.json")).await?.unwrap();
+        assert!(ledger.assets.contains_key("/static/a.css"));
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
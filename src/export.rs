@@ -0,0 +1,232 @@
+//! Renders every page of the site to a folder, so it can be served from
+//! object storage or a CDN when the server is down. Pages are rendered by
+//! sending requests through the same router the server uses, so an export
+//! looks exactly like the live site did when it was made.
+
+use crate::{app::State, edge, handlers::books};
+use axum::{
+    body::Body,
+    http::{header::CONTENT_TYPE, Request, StatusCode},
+    Router,
+};
+use chrono::prelude::*;
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tower::ServiceExt;
+
+/// Pages that don't depend on the content.
+const PAGES: &[&str] = &[
+    "/",
+    "/characters",
+    "/contact",
+    "/feeds",
+    "/offline",
+    "/resume",
+    "/patrons",
+    "/signalboost",
+    "/salary-transparency",
+    "/pronouns",
+    "/status",
+    "/books",
+    "/changelog",
+    "/vods",
+    "/blog",
+    "/blog/series",
+    "/gallery",
+    "/notes",
+    "/talks",
+    "/jsonfeed",
+    "/blog.json",
+    "/blog.atom",
+    "/blog.rss",
+    "/incidents.rss",
+    "/books.rss",
+    "/changelog.rss",
+    "/sitemap.xml",
+    "/sw.js",
+    "/manifest.json",
+    "/icons/maskable/192.png",
+    "/icons/maskable/512.png",
+    "/api/pronouns",
+    "/api/new_post",
+    "/api/salary_transparency.json",
+    "/.within/website.within.xesite/new_post",
+];
+
+/// Every path to render.
+pub fn paths(state: &State) -> Vec<String> {
+    let mut result: BTreeSet<String> = PAGES.iter().map(|p| p.to_string()).collect();
+
+    for post in state.blog.iter().chain(&state.gallery).chain(&state.talks) {
+        result.insert(format!("/{}", post.link));
+        if post.signatures.minisign.is_some() {
+            result.insert(format!("{}.minisig", post.source_url()));
+        }
+        if post.signatures.pgp.is_some() {
+            result.insert(format!("{}.asc", post.source_url()));
+        }
+        if post.signatures.is_signed() {
+            result.insert(post.source_url());
+        }
+        // Every post has a card for its oEmbed thumbnail, even when link
+        // previews use another image.
+        result.insert(crate::og::card_path(post));
+    }
+    for post in &state.blog {
+        let name = post.link.trim_start_matches("blog/");
+        result.insert(format!("/api/blog/{name}"));
+        if let Some(series) = &post.front_matter.series {
+            result.insert(format!("/blog/series/{series}"));
+        }
+        let year = post.date.year();
+        if post.listed() && post.date.date_naive() <= Utc::now().date_naive() {
+            result.insert(format!("/blog/{year}/"));
+            result.insert(format!("/blog/{year}/{:02}/", post.date.month()));
+        }
+        result.insert(format!("/blog/stats/{year}"));
+        result.insert(format!("/api/stats/{year}"));
+    }
+    for page in 2..=crate::tmpl::blog_pages(&state.blog) {
+        result.insert(crate::tmpl::blog_page(page));
+    }
+    for post in &state.talks {
+        let name = post.link.trim_start_matches("talks/");
+        result.insert(format!("/api/talks/{name}"));
+    }
+    for post in state.garden.notes.iter().chain(&state.incidents) {
+        result.insert(format!("/{}", post.link));
+    }
+    for year in books::by_year(&state.cfg.books).keys() {
+        result.insert(format!("/books/{year}"));
+    }
+    for vod in &state.cfg.vods {
+        result.insert(format!(
+            "/vods/{}/{}/{}",
+            vod.date.year(),
+            vod.date.month(),
+            vod.slug
+        ));
+    }
+
+    result.into_iter().collect()
+}
+
+/// Where the response for `path` goes in the export. HTML pages get their
+/// own folder with an `index.html` so that object storage and CDNs serve
+/// them at the same URL as the server does. Everything else is written to
+/// exactly its path.
+pub fn file_for(dir: &Path, path: &str, content_type: &str) -> PathBuf {
+    let path = path.trim_start_matches('/');
+    if content_type.starts_with("text/html") {
+        dir.join(path).join("index.html")
+    } else {
+        dir.join(path)
+    }
+}
+
+pub async fn export(state: Arc<State>, app: Router, dir: &Path) -> Result<()> {
+    let made_at = Utc::now().format("%Y-%m-%d %H:%M UTC").to_string();
+    xesite_templates::context::install_static_export(made_at);
+    // Otherwise the export would be a copy of the last one.
+    state.maintenance.set(false);
+
+    fs::create_dir_all(dir)?;
+    let mut failed = vec![];
+
+    for path in paths(&state) {
+        let resp = app
+            .clone()
+            .oneshot(Request::builder().uri(&path).body(Body::empty())?)
+            .await?;
+        let status = resp.status();
+        // The patrons page is a teapot when it works.
+        if !status.is_success() && status != StatusCode::IM_A_TEAPOT {
+            warn!("{path}: {status}");
+            failed.push(path);
+            continue;
+        }
+
+        let content_type = resp
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|ct| ct.to_str().ok())
+            .unwrap_or("")
+            .to_string();
+        let body = hyper::body::to_bytes(resp.into_body()).await?;
+
+        let fname = file_for(dir, &path, &content_type);
+        if let Some(parent) = fname.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&fname, &body).wrap_err_with(|| format!("can't write {}", fname.display()))?;
+        debug!("{path} -> {}", fname.display());
+    }
+
+    copy_dir(Path::new("./static"), &dir.join("static"))?;
+
+    // Redirects and headers for CDNs that read Netlify-style files.
+    let mut posts = state.blog.clone();
+    posts.extend(state.gallery.iter().cloned());
+    posts.extend(state.talks.iter().cloned());
+    edge::write(
+        edge::Format::Netlify,
+        &edge::rules(&state.cfg.redirects, &posts),
+        dir,
+    )?;
+
+    if failed.is_empty() {
+        info!("exported the site to {}", dir.display());
+        Ok(())
+    } else {
+        Err(eyre!(
+            "can't export {} pages: {}",
+            failed.len(),
+            failed.join(", ")
+        ))
+    }
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_names() {
+        let dir = Path::new("out");
+        assert_eq!(
+            file_for(dir, "/", "text/html; charset=utf-8"),
+            Path::new("out/index.html")
+        );
+        assert_eq!(
+            file_for(dir, "/blog/foo", "text/html"),
+            Path::new("out/blog/foo/index.html")
+        );
+        assert_eq!(
+            file_for(dir, "/blog.rss", "application/rss+xml"),
+            Path::new("out/blog.rss")
+        );
+        assert_eq!(
+            file_for(dir, "/api/blog/foo", "application/json"),
+            Path::new("out/api/blog/foo")
+        );
+    }
+}
@@ -8,10 +8,11 @@ export interface MastodonShareButtonProps {
   url: string;
   series?: string;
   tags: string;
+  account: string;
 }
 
 export default function MastodonShareButton(
-  { title, url = u(), series, tags }: MastodonShareButtonProps,
+  { title, url = u(), series, tags, account }: MastodonShareButtonProps,
 ) {
   let defaultURL = localStorage["mastodon_instance"];
 
@@ -25,7 +26,7 @@ ${url}
 
 ${series ? "#" + series + " " : ""}${
     tags ? tags.map((x) => "#" + x).join(" ") : ""
-  } @cadey@pony.social`;
+  } ${account}`;
 
   const [getURL, setURL] = useState(defaultURL);
   const [getToot, setToot] = useState(tootTemplate);
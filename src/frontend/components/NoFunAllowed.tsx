@@ -1,22 +1,51 @@
 // @jsxImportSource xeact
 // @jsxRuntime automatic
 
-import { c } from "xeact";
+// Keep in sync with ESSENTIAL_SLIDES_KEY in xesite_templates.
+const KEY = "xesite-slides";
+const CLASS = "slides-essential-only";
 
-const onclick = () => {
-  Array.from(c("xeblog-slides-fluff")).forEach((el) =>
-    el.classList.toggle("hidden")
-  );
+const filtered = () => document.querySelector(`.${CLASS}`) !== null;
+
+// Remembers the choice for later talks and puts it in the URL, so reloading
+// or sharing the page keeps it without any JavaScript.
+const save = (essential: boolean) => {
+  try {
+    if (essential) {
+      localStorage.setItem(KEY, "essential");
+    } else {
+      localStorage.removeItem(KEY);
+    }
+  } catch (_) {
+    // Storage can be turned off, the URL still works.
+  }
+
+  const url = new URL(window.location.href);
+  if (essential) {
+    url.searchParams.set("slides", "essential");
+  } else {
+    url.searchParams.delete("slides");
+  }
+  history.replaceState(null, "", url);
 };
 
 export default function NoFunAllowed() {
   const button = (
-    <button
-      class=""
-      onclick={() => onclick()}
-    >
-      No fun allowed
+    <button class="">
+      {filtered() ? "Show all slides" : "No fun allowed"}
     </button>
   );
+  button.onclick = () => {
+    const essential = !filtered();
+    if (essential) {
+      document.documentElement.classList.add(CLASS);
+    } else {
+      document.querySelectorAll(`.${CLASS}`).forEach((el) =>
+        el.classList.remove(CLASS)
+      );
+    }
+    save(essential);
+    button.textContent = essential ? "Show all slides" : "No fun allowed";
+  };
   return button;
 }
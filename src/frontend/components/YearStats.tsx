@@ -0,0 +1,79 @@
+// @jsxImportSource xeact
+// @jsxRuntime automatic
+
+import { x } from "xeact";
+
+export interface YearStatsProps {
+  year: string;
+}
+
+interface PostRef {
+  title: string;
+  link: string;
+  value: number;
+}
+
+interface Stats {
+  year: number;
+  posts: number;
+  words: number;
+  top_tags: [string, number][];
+  most_read: PostRef[];
+  longest?: PostRef;
+}
+
+const postLink = ({ title, link }: PostRef) => <a href={`/${link}`}>{title}</a>;
+
+const render = (stats: Stats) => (
+  <div>
+    <dl>
+      <dt>Posts</dt>
+      <dd>{stats.posts.toString()}</dd>
+      <dt>Words</dt>
+      <dd>{stats.words.toLocaleString()}</dd>
+      {stats.longest
+        ? [
+          <dt>Longest post</dt>,
+          <dd>
+            {postLink(stats.longest)} ({stats.longest.value.toString()} words)
+          </dd>,
+        ]
+        : []}
+    </dl>
+    {stats.top_tags.length !== 0
+      ? [
+        <h3>Top tags</h3>,
+        <ol>
+          {stats.top_tags.map(([tag, count]) => <li>{tag} ({count.toString()})</li>)}
+        </ol>,
+      ]
+      : []}
+    {stats.most_read.length !== 0
+      ? [
+        <h3>Most read</h3>,
+        <ol>
+          {stats.most_read.map((post) => (
+            <li>{postLink(post)} ({post.value.toString()} views)</li>
+          ))}
+        </ol>,
+      ]
+      : []}
+  </div>
+);
+
+export default function YearStats({ year }: YearStatsProps) {
+  const root = <div class="year-stats">Loading stats for {year}...</div>;
+
+  fetch(`/api/stats/${year}`)
+    .then((resp) => resp.json())
+    .then((stats: Stats) => {
+      x(root);
+      root.appendChild(render(stats));
+    })
+    .catch((why) => {
+      x(root);
+      root.appendChild(<span>Can't load stats for {year}: {why.toString()}</span>);
+    });
+
+  return root;
+}
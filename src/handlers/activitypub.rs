@@ -0,0 +1,96 @@
+use crate::{activitypub, app::State};
+use axum::{
+    body::Bytes,
+    extract::{Extension, OriginalUri, Query},
+    http::{header, HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// Something served with the ActivityPub content type.
+fn activity_json(value: serde_json::Value) -> Response {
+    (
+        [(header::CONTENT_TYPE, activitypub::CONTENT_TYPE)],
+        Json(value),
+    )
+        .into_response()
+}
+
+#[instrument(skip(state))]
+pub async fn actor(Extension(state): Extension<Arc<State>>) -> Response {
+    match &state.activitypub {
+        Some(actor) => activity_json(actor.document()),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+#[instrument(skip(state))]
+pub async fn outbox(Extension(state): Extension<Arc<State>>) -> Response {
+    match &state.activitypub {
+        Some(_) => activity_json(activitypub::outbox::collection(&state.blog)),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Only how many followers there are, not who they are.
+#[instrument(skip(state))]
+pub async fn followers(Extension(state): Extension<Arc<State>>) -> Response {
+    let Some(actor) = &state.activitypub else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    activity_json(serde_json::json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": activitypub::followers_url(),
+        "type": "OrderedCollection",
+        "totalItems": actor.followers.count().await,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebFingerQuery {
+    pub resource: String,
+}
+
+#[instrument(skip(state))]
+pub async fn webfinger(
+    Query(query): Query<WebFingerQuery>,
+    Extension(state): Extension<Arc<State>>,
+) -> Response {
+    match state
+        .activitypub
+        .as_ref()
+        .and_then(|actor| actor.webfinger(&query.resource))
+    {
+        Some(result) => (
+            [(header::CONTENT_TYPE, "application/jrd+json")],
+            Json(result),
+        )
+            .into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+#[instrument(skip(state, headers, body))]
+pub async fn inbox(
+    Extension(state): Extension<Arc<State>>,
+    method: Method,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    let Some(actor) = &state.activitypub else {
+        return StatusCode::NOT_FOUND;
+    };
+    let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+
+    let is_post = |link: &str| state.has_post(link);
+    match actor
+        .receive(&method, path, &headers, &body, &is_post)
+        .await
+    {
+        Ok(()) => StatusCode::ACCEPTED,
+        Err(why) => why.status(),
+    }
+}
@@ -0,0 +1,362 @@
+use crate::{
+    admin::{Admin, PREFIX},
+    analytics::Window,
+    app::State,
+    auth::CsrfForm,
+    digest,
+    tmpl::{self, admin::Dashboard},
+};
+use axum::{
+    extract::{Extension, Path, Query},
+    http::{header, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+};
+use chrono::{NaiveDate, Utc};
+use maud::Markup;
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::{error, info, instrument};
+
+/// How many replies and webmentions the dashboard shows.
+const RECENT: usize = 20;
+
+/// Admin and login pages are never cached or indexed.
+pub(super) fn private(page: Markup) -> Response {
+    (
+        [
+            (header::CACHE_CONTROL, "no-store"),
+            (header::HeaderName::from_static("x-robots-tag"), "noindex"),
+        ],
+        page,
+    )
+        .into_response()
+}
+
+/// Goes back to the dashboard and says what was done.
+fn done(notice: &str) -> Response {
+    done_at(PREFIX, notice)
+}
+
+/// Like [done], but goes back to the admin page at `path`.
+fn done_at(path: &str, notice: &str) -> Response {
+    let query: String = url::form_urlencoded::Serializer::new(String::new())
+        .append_pair("done", notice)
+        .finish();
+    Redirect::to(&format!("{path}?{query}")).into_response()
+}
+
+fn failed(what: &str, why: color_eyre::eyre::Report) -> Response {
+    error!("admin: can't {what}: {why:?}");
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        tmpl::error(format!("can't {what}")),
+    )
+        .into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DashboardQuery {
+    pub done: Option<String>,
+}
+
+#[instrument(skip(state, admin))]
+pub async fn dashboard(
+    admin: Admin,
+    Extension(state): Extension<Arc<State>>,
+    Query(query): Query<DashboardQuery>,
+) -> Response {
+    let (replies, fediverse) = match &state.activitypub {
+        Some(ap) => (
+            ap.store.recent_replies(RECENT).await,
+            Some((ap.queue.queued(), ap.followers.count().await)),
+        ),
+        None => (vec![], None),
+    };
+    let mentions = state
+        .blog
+        .iter()
+        .chain(&state.gallery)
+        .chain(&state.talks)
+        .flat_map(|post| {
+            post.mentions
+                .iter()
+                .map(|m| (post.link.clone(), m.source.clone(), m.title.clone()))
+        })
+        .take(RECENT)
+        .collect();
+
+    private(tmpl::admin::dashboard(&Dashboard {
+        me: admin.session.as_ref().map(|s| s.me.clone()),
+        csrf: admin.csrf().to_string(),
+        notice: query.done,
+        replies,
+        mentions,
+        popular_month: state.analytics.popular(10, Window::Month),
+        popular_quarter: state.analytics.popular(10, Window::Quarter),
+        fediverse,
+        services: state.status.snapshot().await,
+        jobs: state.jobs.snapshot().await,
+        og_cards: crate::og::cached(),
+        sticker_packs: crate::stickers::cached(),
+        backups: state.backups.is_some(),
+        maintenance: state.maintenance.is_on(),
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RemoveReply {
+    pub id: String,
+}
+
+#[instrument(skip(state, _admin))]
+pub async fn remove_reply(
+    _admin: Admin,
+    Extension(state): Extension<Arc<State>>,
+    CsrfForm(form): CsrfForm<RemoveReply>,
+) -> Response {
+    let Some(ap) = &state.activitypub else {
+        return done("The fediverse account is turned off");
+    };
+    match ap.store.remove_reply(&form.id).await {
+        Ok(true) => {
+            info!("admin: removed reply {}", form.id);
+            done("Removed the reply")
+        }
+        Ok(false) => done("That reply was already gone"),
+        Err(why) => failed("remove the reply", why),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ForgetActor {
+    pub actor: String,
+}
+
+#[instrument(skip(state, _admin))]
+pub async fn forget_actor(
+    _admin: Admin,
+    Extension(state): Extension<Arc<State>>,
+    CsrfForm(form): CsrfForm<ForgetActor>,
+) -> Response {
+    let Some(ap) = &state.activitypub else {
+        return done("The fediverse account is turned off");
+    };
+    match ap.store.forget(&form.actor).await {
+        Ok(()) => {
+            info!("admin: removed everything from {}", form.actor);
+            done(&format!("Removed everything from {}", form.actor))
+        }
+        Err(why) => failed("remove the account's replies", why),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Purge {
+    pub cache: String,
+}
+
+#[instrument(skip(_admin))]
+pub async fn purge(_admin: Admin, CsrfForm(form): CsrfForm<Purge>) -> Response {
+    let (n, what) = match form.cache.as_str() {
+        "og" => (crate::og::purge(), "link preview cards"),
+        "stickers" => (crate::stickers::purge(), "sticker packs"),
+        _ => return (StatusCode::BAD_REQUEST, "no such cache").into_response(),
+    };
+    info!("admin: purged {n} {what}");
+    done(&format!("Purged {n} {what}"))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Backup {
+    /// `bucket` to upload it, or `download` to get it here.
+    pub to: String,
+}
+
+#[instrument(skip(state, _admin))]
+pub async fn backup(
+    _admin: Admin,
+    Extension(state): Extension<Arc<State>>,
+    CsrfForm(form): CsrfForm<Backup>,
+) -> Response {
+    let Some(backups) = &state.backups else {
+        return done("Backups are turned off");
+    };
+    match form.to.as_str() {
+        "bucket" => match backups.run().await {
+            Ok(name) => {
+                info!("admin: backed up to {name}");
+                done(&format!("Backed up to {name}"))
+            }
+            Err(why) => failed("back up", why),
+        },
+        "download" => match backups.export().await {
+            Ok((name, data)) => {
+                info!("admin: exported {name}");
+                (
+                    [
+                        (header::CACHE_CONTROL, "no-store".to_string()),
+                        (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+                        (
+                            header::CONTENT_DISPOSITION,
+                            format!("attachment; filename=\"{name}\""),
+                        ),
+                    ],
+                    data,
+                )
+                    .into_response()
+            }
+            Err(why) => failed("export a backup", why),
+        },
+        _ => (StatusCode::BAD_REQUEST, "no such place").into_response(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Maintenance {
+    /// `on` or `off`.
+    pub mode: String,
+}
+
+#[instrument(skip(state, _admin))]
+pub async fn maintenance(
+    _admin: Admin,
+    Extension(state): Extension<Arc<State>>,
+    CsrfForm(form): CsrfForm<Maintenance>,
+) -> Response {
+    let on = match form.mode.as_str() {
+        "on" => true,
+        "off" => false,
+        _ => return (StatusCode::BAD_REQUEST, "no such mode").into_response(),
+    };
+    state.maintenance.set(on);
+    info!("admin: turned maintenance mode {}", form.mode);
+    done(&format!("Turned maintenance mode {}", form.mode))
+}
+
+#[instrument(skip(state, admin))]
+pub async fn digests(
+    admin: Admin,
+    Extension(state): Extension<Arc<State>>,
+    Query(query): Query<DashboardQuery>,
+) -> Response {
+    match state.storage.digests().list(true).await {
+        Ok(issues) => private(tmpl::admin::digests(
+            admin.csrf(),
+            query.done.as_deref(),
+            &issues,
+        )),
+        Err(why) => failed("list the digests", why),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DraftDigest {
+    /// The Monday of the week to draft, or last week if it's not given.
+    pub week: Option<NaiveDate>,
+}
+
+#[instrument(skip(state, _admin))]
+pub async fn draft_digest(
+    _admin: Admin,
+    Extension(state): Extension<Arc<State>>,
+    CsrfForm(form): CsrfForm<DraftDigest>,
+) -> Response {
+    let week = digest::week_of(
+        form.week
+            .unwrap_or_else(|| digest::last_week(Utc::now().date_naive())),
+    );
+    let path = format!("{PREFIX}/digests");
+    match digest::draft(&state, week).await {
+        Ok(Some(issue)) => done_at(&path, &format!("Drafted {}", issue.title())),
+        Ok(None) => done_at(
+            &path,
+            &format!("The week of {week} already has an issue, or nothing happened in it"),
+        ),
+        Err(why) => failed("draft the digest", why),
+    }
+}
+
+#[instrument(skip(state, admin))]
+pub async fn digest(
+    admin: Admin,
+    Extension(state): Extension<Arc<State>>,
+    Path(week): Path<NaiveDate>,
+    Query(query): Query<DashboardQuery>,
+) -> Response {
+    match state.storage.digests().get(week).await {
+        Ok(Some(issue)) => private(tmpl::admin::digest(
+            admin.csrf(),
+            query.done.as_deref(),
+            &issue,
+        )),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            tmpl::not_found(format!("{PREFIX}/digests/{week}")),
+        )
+            .into_response(),
+        Err(why) => failed("load the digest", why),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EditDigest {
+    /// `save`, `publish`, `unpublish`, `rebuild` or `drop`.
+    pub action: String,
+    pub intro: Option<String>,
+    /// What `drop` leaves out, see [digest::Issue::drop_item].
+    pub item: Option<String>,
+}
+
+#[instrument(skip(state, _admin))]
+pub async fn edit_digest(
+    _admin: Admin,
+    Extension(state): Extension<Arc<State>>,
+    Path(week): Path<NaiveDate>,
+    CsrfForm(form): CsrfForm<EditDigest>,
+) -> Response {
+    let digests = state.storage.digests();
+    let mut issue = match digests.get(week).await {
+        Ok(Some(issue)) => issue,
+        Ok(None) => return (StatusCode::NOT_FOUND, "no such issue").into_response(),
+        Err(why) => return failed("load the digest", why),
+    };
+    if let Some(intro) = form.intro {
+        if let Err(why) = xesite_markdown::render(&intro) {
+            return (
+                StatusCode::BAD_REQUEST,
+                tmpl::error(format!("The intro can't be rendered: {why}")),
+            )
+                .into_response();
+        }
+        issue.intro = intro;
+    }
+    let notice = match form.action.as_str() {
+        "save" => "Saved",
+        "publish" => {
+            issue.published = Some(Utc::now());
+            "Published"
+        }
+        "unpublish" => {
+            issue.published = None;
+            "Unpublished"
+        }
+        "rebuild" => {
+            issue = digest::Issue {
+                intro: issue.intro,
+                published: issue.published,
+                ..digest::gather(&state, week).await
+            };
+            "Rebuilt"
+        }
+        "drop" => match &form.item {
+            Some(item) if issue.drop_item(item) => "Left it out",
+            _ => "That was already left out",
+        },
+        _ => return (StatusCode::BAD_REQUEST, "no such action").into_response(),
+    };
+    if let Err(why) = digests.save(&issue).await {
+        return failed("save the digest", why);
+    }
+    info!("admin: {} the digest for {week}", form.action);
+    done_at(&format!("{PREFIX}/digests/{week}"), notice)
+}
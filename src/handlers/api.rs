@@ -2,10 +2,17 @@ use crate::{
     app::{config::Job, PronounSet, State},
     handlers::Result,
     post::Post,
+    stats::YearStats,
+    tmpl,
+};
+use axum::{
+    extract::{Extension, Json, Path, Query},
+    http::StatusCode,
+    response::{IntoResponse, Response},
 };
-use axum::extract::{Extension, Json, Path};
 use lazy_static::lazy_static;
 use prometheus::{opts, register_int_counter_vec, IntCounterVec};
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
 lazy_static! {
@@ -90,3 +97,91 @@ pub async fn talk(
         }
     }
 }
+
+#[derive(Debug, Deserialize)]
+pub struct OembedQuery {
+    pub url: String,
+    pub maxwidth: Option<u32>,
+    pub maxheight: Option<u32>,
+    pub format: Option<String>,
+}
+
+/// The embed of a post, see [crate::oembed].
+#[instrument(skip(state))]
+pub async fn oembed(
+    Query(query): Query<OembedQuery>,
+    Extension(state): Extension<Arc<State>>,
+) -> Response {
+    super::HIT_COUNTER.with_label_values(&["oembed"]).inc();
+    if query
+        .format
+        .as_deref()
+        .map_or(false, |format| format != "json")
+    {
+        return StatusCode::NOT_IMPLEMENTED.into_response();
+    }
+    let post = crate::oembed::link_of(&query.url)
+        .and_then(|link| state.everything.iter().find(|post| post.link == link));
+    match post {
+        Some(post) => {
+            Json(crate::oembed::embed(post, query.maxwidth, query.maxheight)).into_response()
+        }
+        None => super::Error::PostNotFound(query.url).into_response(),
+    }
+}
+
+#[instrument(skip(state))]
+pub async fn year_stats(
+    Path(year): Path<i32>,
+    Extension(state): Extension<Arc<State>>,
+) -> Json<YearStats> {
+    super::HIT_COUNTER
+        .with_label_values(&["year_stats_json"])
+        .inc();
+
+    let reads = state.analytics.year(year).await;
+    Json(YearStats::compute(&state.blog, year, |p| {
+        reads.get(&p.link).copied().unwrap_or_default()
+    }))
+}
+
+#[instrument(skip(state))]
+pub async fn moods(Extension(state): Extension<Arc<State>>) -> Json<crate::moods::Report> {
+    super::HIT_COUNTER.with_label_values(&["moods_json"]).inc();
+
+    Json(state.moods.clone())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PostsQuery {
+    pub page: Option<usize>,
+}
+
+/// A page of the blog index rendered as list items, see
+/// [tmpl::blog_page_items].
+#[derive(Debug, Serialize)]
+pub struct PostsPage {
+    pub page: usize,
+    pub pages: usize,
+    pub html: String,
+    /// Where to fetch the page after this one from, if there is one.
+    pub next: Option<String>,
+}
+
+#[instrument(skip(state))]
+pub async fn posts(
+    Query(query): Query<PostsQuery>,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<PostsPage>> {
+    super::HIT_COUNTER.with_label_values(&["posts_json"]).inc();
+    let page = query.page.unwrap_or(1);
+    let pages = tmpl::blog_pages(&state.blog);
+    let html = tmpl::blog_page_items(&state.blog, page).ok_or(super::Error::PageNotFound(page))?;
+
+    Ok(Json(PostsPage {
+        page,
+        pages,
+        html: html.0,
+        next: (page < pages).then(|| tmpl::blog_api_page(page + 1)),
+    }))
+}
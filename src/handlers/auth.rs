@@ -0,0 +1,250 @@
+use super::admin::private;
+use crate::{
+    admin::{self, PREFIX as ADMIN},
+    app::State,
+    auth::{safe_next, CsrfForm, Provider, Session, PREFIX},
+    tmpl,
+};
+use axum::{
+    extract::{Extension, Form, Query},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::{error, info, instrument};
+
+/// Whether there's any way to log in.
+fn enabled(state: &State) -> bool {
+    !state.cfg.admin_token.is_empty() || state.cfg.auth.oidc.is_some() || state.cfg.auth.indie_auth
+}
+
+/// Where providers send people back to.
+fn redirect_uri(state: &State, provider: &str) -> String {
+    state.cfg.site.url(&format!("{PREFIX}/{provider}/callback"))
+}
+
+/// What IndieAuth knows the site as.
+fn client_id(state: &State) -> String {
+    state.cfg.site.url("/")
+}
+
+/// Shows the login page again with what went wrong.
+fn failed(state: &State, status: StatusCode, next: &str, why: &str) -> Response {
+    (
+        status,
+        private(tmpl::auth::login(&state.cfg, next, Some(why))),
+    )
+        .into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginQuery {
+    pub next: Option<String>,
+}
+
+#[instrument(skip(state))]
+pub async fn login_page(
+    Extension(state): Extension<Arc<State>>,
+    Query(query): Query<LoginQuery>,
+) -> Response {
+    if !enabled(&state) {
+        return super::not_found_page(&format!("{PREFIX}/login"));
+    }
+    let next = safe_next(query.next.as_deref());
+    private(tmpl::auth::login(&state.cfg, &next, None))
+}
+
+#[derive(Deserialize)]
+pub struct TokenLogin {
+    pub token: String,
+    pub next: Option<String>,
+}
+
+#[instrument(skip(state, form))]
+pub async fn token(
+    Extension(state): Extension<Arc<State>>,
+    Form(form): Form<TokenLogin>,
+) -> Response {
+    if state.cfg.admin_token.is_empty() {
+        return super::not_found_page(&format!("{PREFIX}/token"));
+    }
+    let next = safe_next(form.next.as_deref().or(Some(ADMIN)));
+    if !admin::check_token(&state.cfg.admin_token, &form.token) {
+        return failed(
+            &state,
+            StatusCode::UNAUTHORIZED,
+            &next,
+            "That's not the admin token.",
+        );
+    }
+    let session = Session::new(admin::token_user(&form.token), None, Provider::Token);
+    state.auth.login(session, &next)
+}
+
+#[instrument(skip(state))]
+pub async fn oidc(
+    Extension(state): Extension<Arc<State>>,
+    Query(query): Query<LoginQuery>,
+) -> Response {
+    let Some(oidc) = &state.cfg.auth.oidc else {
+        return super::not_found_page(&format!("{PREFIX}/oidc"));
+    };
+    let next = safe_next(query.next.as_deref());
+    match state
+        .auth
+        .start_oidc(oidc, &redirect_uri(&state, "oidc"), &next)
+        .await
+    {
+        Ok(resp) => resp,
+        Err(why) => {
+            error!("auth: can't start logging in with {}: {why:?}", oidc.issuer);
+            failed(
+                &state,
+                StatusCode::BAD_GATEWAY,
+                &next,
+                &format!("Can't reach {} right now.", oidc.name),
+            )
+        }
+    }
+}
+
+/// What providers send people back with.
+#[derive(Debug, Deserialize)]
+pub struct Callback {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+}
+
+#[instrument(skip(state, headers, query))]
+pub async fn oidc_callback(
+    Extension(state): Extension<Arc<State>>,
+    headers: HeaderMap,
+    Query(query): Query<Callback>,
+) -> Response {
+    let Some(oidc) = &state.cfg.auth.oidc else {
+        return super::not_found_page(&format!("{PREFIX}/oidc/callback"));
+    };
+    let (Some(code), Some(login)) = (&query.code, &query.state) else {
+        let why = query.error.as_deref().unwrap_or("no code");
+        return failed(
+            &state,
+            StatusCode::UNAUTHORIZED,
+            "/",
+            &format!("{} didn't log you in: {why}", oidc.name),
+        );
+    };
+    match state
+        .auth
+        .finish_oidc(oidc, &redirect_uri(&state, "oidc"), &headers, code, login)
+        .await
+    {
+        Ok(resp) => resp,
+        Err(why) => {
+            error!("auth: can't log in with {}: {why:?}", oidc.issuer);
+            failed(
+                &state,
+                StatusCode::UNAUTHORIZED,
+                "/",
+                &format!("Can't log you in with {}: {why}", oidc.name),
+            )
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IndieLogin {
+    pub me: String,
+    pub next: Option<String>,
+}
+
+#[instrument(skip(state))]
+pub async fn indieauth(
+    Extension(state): Extension<Arc<State>>,
+    Form(form): Form<IndieLogin>,
+) -> Response {
+    if !state.cfg.auth.indie_auth {
+        return super::not_found_page(&format!("{PREFIX}/indieauth"));
+    }
+    let next = safe_next(form.next.as_deref());
+    match state
+        .auth
+        .start_indieauth(
+            &form.me,
+            &client_id(&state),
+            &redirect_uri(&state, "indieauth"),
+            &next,
+        )
+        .await
+    {
+        Ok(resp) => resp,
+        Err(why) => {
+            error!("auth: can't start logging in as {}: {why:?}", form.me);
+            failed(
+                &state,
+                StatusCode::BAD_REQUEST,
+                &next,
+                &format!(
+                    "Can't log in as {}. Check the address and try again.",
+                    form.me
+                ),
+            )
+        }
+    }
+}
+
+#[instrument(skip(state, headers, query))]
+pub async fn indieauth_callback(
+    Extension(state): Extension<Arc<State>>,
+    headers: HeaderMap,
+    Query(query): Query<Callback>,
+) -> Response {
+    if !state.cfg.auth.indie_auth {
+        return super::not_found_page(&format!("{PREFIX}/indieauth/callback"));
+    }
+    let (Some(code), Some(login)) = (&query.code, &query.state) else {
+        let why = query.error.as_deref().unwrap_or("no code");
+        return failed(
+            &state,
+            StatusCode::UNAUTHORIZED,
+            "/",
+            &format!("Your website didn't log you in: {why}"),
+        );
+    };
+    match state
+        .auth
+        .finish_indieauth(
+            &client_id(&state),
+            &redirect_uri(&state, "indieauth"),
+            &headers,
+            code,
+            login,
+        )
+        .await
+    {
+        Ok(resp) => resp,
+        Err(why) => {
+            error!("auth: can't log in with IndieAuth: {why:?}");
+            failed(
+                &state,
+                StatusCode::UNAUTHORIZED,
+                "/",
+                "Can't log you in with your website. Try again in a bit.",
+            )
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Logout {}
+
+#[instrument(skip(state, session, _form))]
+pub async fn logout(
+    Extension(state): Extension<Arc<State>>,
+    session: Session,
+    CsrfForm(_form): CsrfForm<Logout>,
+) -> Response {
+    info!("auth: {} logged out", session.me);
+    state.auth.logout()
+}
@@ -1,5 +1,5 @@
 use super::Result;
-use crate::{app::State, post::Post, tmpl};
+use crate::{app::State, post::Post, prefs, stats::YearStats, tmpl};
 use axum::{
     extract::{Extension, Path},
     http::StatusCode,
@@ -22,10 +22,79 @@ lazy_static! {
 #[instrument(skip(state))]
 pub async fn index(Extension(state): Extension<Arc<State>>) -> Result<Markup> {
     let state = state.clone();
-    let result = tmpl::post_index(&state.blog, "Blogposts", true);
+    let result = tmpl::blog_index(&state.blog, 1).unwrap();
     Ok(result)
 }
 
+#[instrument(skip(state))]
+pub async fn page(
+    Path(page): Path<usize>,
+    Extension(state): Extension<Arc<State>>,
+) -> (StatusCode, Markup) {
+    match tmpl::blog_index(&state.blog, page) {
+        Some(result) => (StatusCode::OK, result),
+        None => (
+            StatusCode::NOT_FOUND,
+            tmpl::not_found(format!("/blog/page/{page}")),
+        ),
+    }
+}
+
+#[instrument(skip(state))]
+pub async fn stats(
+    Path(year): Path<i32>,
+    Extension(state): Extension<Arc<State>>,
+) -> (StatusCode, Markup) {
+    let reads = state.analytics.year(year).await;
+    let stats = YearStats::compute(&state.blog, year, |p| {
+        reads.get(&p.link).copied().unwrap_or_default()
+    });
+    if stats.posts == 0 {
+        return (
+            StatusCode::NOT_FOUND,
+            tmpl::not_found(format!("/blog/stats/{year}")),
+        );
+    }
+
+    (StatusCode::OK, tmpl::year_stats(&stats))
+}
+
+/// Parses a year or month from an archive URL.
+fn archive_part<T: std::str::FromStr>(part: &str) -> Option<T> {
+    part.parse().ok()
+}
+
+#[instrument(skip(state))]
+pub async fn archive_year(
+    Path(name): Path<String>,
+    Extension(state): Extension<Arc<State>>,
+) -> (StatusCode, Markup) {
+    match archive_part(&name).and_then(|year| tmpl::archive(&state.blog, year, None)) {
+        Some(page) => (StatusCode::OK, page),
+        None => (
+            StatusCode::NOT_FOUND,
+            tmpl::not_found(format!("/blog/{name}/")),
+        ),
+    }
+}
+
+#[instrument(skip(state))]
+pub async fn archive_month(
+    Path((name, month)): Path<(String, String)>,
+    Extension(state): Extension<Arc<State>>,
+) -> (StatusCode, Markup) {
+    let page = archive_part(&name)
+        .zip(archive_part(&month))
+        .and_then(|(year, month)| tmpl::archive(&state.blog, year, Some(month)));
+    match page {
+        Some(page) => (StatusCode::OK, page),
+        None => (
+            StatusCode::NOT_FOUND,
+            tmpl::not_found(format!("/blog/{name}/{month}/")),
+        ),
+    }
+}
+
 #[instrument(skip(state))]
 pub async fn series(Extension(state): Extension<Arc<State>>) -> Result<Markup> {
     let state = state.clone();
@@ -49,6 +118,9 @@ pub async fn series_view(
         if post.front_matter.series.as_ref().unwrap() != &series {
             continue;
         }
+        if !post.listed() {
+            continue;
+        }
         posts.push(post.clone());
     }
 
@@ -101,8 +173,23 @@ pub async fn post_view(
             HIT_COUNTER
                 .with_label_values(&[name.clone().as_str()])
                 .inc();
+            state.analytics.record(&post.link).await;
+            let interactions = match &state.activitypub {
+                Some(actor) => actor.store.get(&post.link).await,
+                None => Default::default(),
+            };
             let body = maud::PreEscaped(&post.body_html);
-            Ok((StatusCode::OK, tmpl::blog::blog(&post, body, referer)))
+            Ok((
+                StatusCode::OK,
+                tmpl::blog::blog(
+                    &post,
+                    &state.blog,
+                    body,
+                    referer,
+                    &interactions,
+                    prefs::for_request(&state, &headers),
+                ),
+            ))
         }
     }
 }
@@ -0,0 +1,143 @@
+use crate::{
+    app::{Book, BookStatus, State},
+    tmpl::base,
+};
+use axum::{extract::Path, Extension};
+use http::StatusCode;
+use lazy_static::lazy_static;
+use maud::{html, Markup};
+use prometheus::{opts, register_int_counter_vec, IntCounterVec};
+use std::{collections::BTreeMap, sync::Arc};
+use tracing::instrument;
+
+lazy_static! {
+    static ref HIT_COUNTER: IntCounterVec = register_int_counter_vec!(
+        opts!("books_hits", "Number of hits to reading log pages"),
+        &["page"]
+    )
+    .unwrap();
+}
+
+/// Finished books grouped by the year they were finished in, newest first.
+pub(crate) fn by_year(books: &Vec<Book>) -> BTreeMap<i32, Vec<&Book>> {
+    let mut result: BTreeMap<i32, Vec<&Book>> = BTreeMap::new();
+    for book in books {
+        if let Some(year) = book.finished_in() {
+            result.entry(year).or_default().push(book);
+        }
+    }
+    for books in result.values_mut() {
+        books.sort_by(|a, b| b.finish_date.cmp(&a.finish_date));
+    }
+    result
+}
+
+fn summary(year: i32, books: &Vec<&Book>) -> Markup {
+    let rated: Vec<u8> = books.iter().filter_map(|b| b.rating).collect();
+    let authors = {
+        let mut authors: Vec<&str> = books.iter().map(|b| b.author.as_str()).collect();
+        authors.sort();
+        authors.dedup();
+        authors.len()
+    };
+
+    html! {
+        p {
+            "In " (year) " I finished "
+            (books.len())
+            @if books.len() == 1 {" book"} @else {" books"}
+            " by "
+            (authors)
+            @if authors == 1 {" author"} @else {" authors"}
+            "."
+            @if !rated.is_empty() {
+                " The average rating was "
+                (format!("{:.1}", rated.iter().map(|r| *r as f64).sum::<f64>() / rated.len() as f64))
+                " out of 5."
+            }
+        }
+    }
+}
+
+#[instrument(skip(state))]
+pub async fn list(Extension(state): Extension<Arc<State>>) -> Markup {
+    HIT_COUNTER.with_label_values(&["index"]).inc();
+    let state = state.clone();
+    let cfg = state.cfg.clone();
+    let years = by_year(&cfg.books);
+
+    base(
+        Some("Reading Log"),
+        None,
+        html! {
+            h1 {"Reading Log"}
+            p {
+                "This is a list of books I have been reading, with some short notes about them. If you want to follow along, there is an "
+                a href="/books.rss" {"RSS feed"}
+                " of every book I finish."
+            }
+
+            @for (status, title) in [(BookStatus::Reading, "Currently reading"), (BookStatus::WantToRead, "Up next")] {
+                @let books: Vec<&Book> = cfg.books.iter().filter(|b| b.status == status).collect();
+                @if !books.is_empty() {
+                    h2 {(title)}
+                    @for book in books {
+                        (book)
+                    }
+                }
+            }
+
+            @for (year, books) in years.iter().rev() {
+                h2 {
+                    a href={"/books/" (year)} {(year)}
+                }
+                (summary(*year, books))
+                @for book in books {
+                    (book)
+                }
+            }
+        },
+    )
+}
+
+#[instrument(skip(state))]
+pub async fn year(
+    Extension(state): Extension<Arc<State>>,
+    Path(year): Path<i32>,
+) -> (StatusCode, Markup) {
+    let state = state.clone();
+    let cfg = state.cfg.clone();
+    let years = by_year(&cfg.books);
+
+    let books = match years.get(&year) {
+        Some(books) => books,
+        None => {
+            return (
+                StatusCode::NOT_FOUND,
+                crate::tmpl::error(html! {
+                    "I didn't finish any books in " (year) ", or at least I didn't write them down."
+                }),
+            )
+        }
+    };
+    HIT_COUNTER.with_label_values(&["year"]).inc();
+
+    let title = format!("Books I read in {year}");
+    (
+        StatusCode::OK,
+        base(
+            Some(&title),
+            None,
+            html! {
+                h1 {(title)}
+                (summary(year, books))
+                @for book in books {
+                    (book)
+                }
+                p {
+                    a href="/books" {"Back to the reading log"}
+                }
+            },
+        ),
+    )
+}
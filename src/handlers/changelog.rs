@@ -0,0 +1,95 @@
+use crate::{
+    app::{ChangelogEntry, State},
+    tmpl::base,
+};
+use axum::Extension;
+use lazy_static::lazy_static;
+use maud::{html, Markup};
+use prometheus::{opts, register_int_counter_vec, IntCounterVec};
+use std::sync::Arc;
+use tracing::instrument;
+
+lazy_static! {
+    static ref HIT_COUNTER: IntCounterVec = register_int_counter_vec!(
+        opts!("changelog_hits", "Number of hits to the site changelog"),
+        &["page"]
+    )
+    .unwrap();
+}
+
+/// The changelog newest first, each entry with its anchor on the page. The
+/// first entry for a day is at the date, later ones get a number after it.
+pub(crate) fn entries(changelog: &[ChangelogEntry]) -> Vec<(String, ChangelogEntry)> {
+    let mut sorted: Vec<&ChangelogEntry> = changelog.iter().collect();
+    // Stable, so entries from the same day stay in the order they're written.
+    sorted.sort_by(|a, b| b.date.cmp(&a.date));
+    let mut result: Vec<(String, ChangelogEntry)> = vec![];
+    for entry in sorted {
+        let same_day = result.iter().filter(|(_, e)| e.date == entry.date).count();
+        let anchor = match same_day {
+            0 => entry.date.to_string(),
+            n => format!("{}-{}", entry.date, n + 1),
+        };
+        result.push((anchor, entry.clone()));
+    }
+    result
+}
+
+#[instrument(skip(state))]
+pub async fn index(Extension(state): Extension<Arc<State>>) -> Markup {
+    HIT_COUNTER.with_label_values(&["index"]).inc();
+    let entries = entries(&state.cfg.changelog);
+
+    base(
+        Some("Site changelog"),
+        None,
+        html! {
+            h1 {"Site changelog"}
+            p {
+                "Changes to this website itself, like new features and redesigns. There is an "
+                a href="/changelog.rss" {"RSS feed"}
+                " of them too."
+            }
+
+            @if entries.is_empty() {
+                p {"Nothing has changed yet."}
+            }
+            @for (anchor, entry) in &entries {
+                .changelog-entry id=(anchor) {
+                    p {
+                        a href={"#" (anchor)} {
+                            time datetime=(entry.date) {(entry.date.format("M%m %d %Y").to_string())}
+                        }
+                        " "
+                        span.changelog-category {(entry.category.to_string())}
+                    }
+                    (entry.description)
+                }
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::ChangelogCategory;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn anchors() {
+        let entry = |d| ChangelogEntry {
+            date: NaiveDate::from_ymd_opt(2023, 7, d).unwrap(),
+            category: ChangelogCategory::Feature,
+            description: Default::default(),
+        };
+        let anchors: Vec<String> = entries(&[entry(1), entry(3), entry(3), entry(2)])
+            .into_iter()
+            .map(|(anchor, _)| anchor)
+            .collect();
+        assert_eq!(
+            anchors,
+            vec!["2023-07-03", "2023-07-03-2", "2023-07-02", "2023-07-01"]
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use crate::{app::State, digest::Issue, tmpl};
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use chrono::NaiveDate;
+use lazy_static::lazy_static;
+use prometheus::{opts, register_int_counter_vec, IntCounterVec};
+use std::sync::Arc;
+use tracing::{error, instrument};
+use xesite_templates::context::{self, RenderTarget};
+
+lazy_static! {
+    static ref HIT_COUNTER: IntCounterVec = register_int_counter_vec!(
+        opts!("digest_hits", "Number of hits to weekly digest pages"),
+        &["kind"]
+    )
+    .unwrap();
+}
+
+fn broken(why: color_eyre::eyre::Report) -> Response {
+    error!("can't load the digest: {why:?}");
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        tmpl::error("can't load the digest"),
+    )
+        .into_response()
+}
+
+/// The published issue for `week`. Drafts aren't shown to anyone but the
+/// admin, see [crate::handlers::admin::digest].
+async fn published(state: &State, week: NaiveDate) -> Result<Issue, Response> {
+    match state.storage.digests().get(week).await {
+        Ok(Some(issue)) if issue.published.is_some() => Ok(issue),
+        Ok(_) => Err((
+            StatusCode::NOT_FOUND,
+            tmpl::not_found(format!("/digest/{week}")),
+        )
+            .into_response()),
+        Err(why) => Err(broken(why)),
+    }
+}
+
+#[instrument(skip(state))]
+pub async fn index(Extension(state): Extension<Arc<State>>) -> Response {
+    HIT_COUNTER.with_label_values(&["index"]).inc();
+    match state.storage.digests().list(false).await {
+        Ok(issues) => tmpl::digest::index(&issues).into_response(),
+        Err(why) => broken(why),
+    }
+}
+
+#[instrument(skip(state))]
+pub async fn issue(
+    Extension(state): Extension<Arc<State>>,
+    Path(week): Path<NaiveDate>,
+) -> Response {
+    HIT_COUNTER.with_label_values(&["issue"]).inc();
+    match published(&state, week).await {
+        Ok(issue) => tmpl::digest::page(&issue).into_response(),
+        Err(resp) => resp,
+    }
+}
+
+#[instrument(skip(state))]
+pub async fn email(
+    Extension(state): Extension<Arc<State>>,
+    Path(week): Path<NaiveDate>,
+) -> Response {
+    HIT_COUNTER.with_label_values(&["email"]).inc();
+    match published(&state, week).await {
+        Ok(issue) => context::with_target(RenderTarget::Email, || tmpl::digest::email(&issue))
+            .into_response(),
+        Err(resp) => resp,
+    }
+}
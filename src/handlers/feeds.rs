@@ -1,14 +1,16 @@
 use super::{Result, LAST_MODIFIED};
 use crate::{
-    app::State,
+    app::{Book, State},
     post::{NewPost, Post},
     templates,
 };
 use axum::{body, extract::Extension, response::Response, Json};
+use http::{header::USER_AGENT, HeaderMap};
 use lazy_static::lazy_static;
 use prometheus::{opts, register_int_counter_vec, IntCounterVec};
+use sha2::{Digest, Sha256};
 use std::sync::Arc;
-use tracing::instrument;
+use tracing::{error, instrument};
 
 lazy_static! {
     pub static ref HIT_COUNTER: IntCounterVec = register_int_counter_vec!(
@@ -16,13 +18,29 @@ lazy_static! {
         &["kind"]
     )
     .unwrap();
-    pub static ref ETAG: String = format!(r#"W/"{}""#, uuid::Uuid::new_v4().to_string().replace("-", ""));
-    pub static ref CACHEBUSTER: String = uuid::Uuid::new_v4().to_string().replace("-", "");
 }
 
-#[instrument(skip(state))]
-pub async fn jsonfeed(Extension(state): Extension<Arc<State>>) -> Json<xe_jsonfeed::Feed> {
+/// The ETag of a feed, from a hash of its contents so it stays the same
+/// across restarts and only changes when the feed does.
+pub fn etag(body: &[u8]) -> String {
+    format!(r#"W/"{}""#, hex::encode(&Sha256::digest(body)[..16]))
+}
+
+/// Notes how many subscribers the reader fetching the `kind` feed says it
+/// has, see [crate::subscribers].
+fn count_subscribers(state: &State, kind: &str, headers: &HeaderMap) {
+    if let Some(ua) = headers.get(USER_AGENT).and_then(|ua| ua.to_str().ok()) {
+        state.subscribers.saw(kind, ua);
+    }
+}
+
+#[instrument(skip(state, headers))]
+pub async fn jsonfeed(
+    Extension(state): Extension<Arc<State>>,
+    headers: HeaderMap,
+) -> Json<xe_jsonfeed::Feed> {
     HIT_COUNTER.with_label_values(&["json"]).inc();
+    count_subscribers(&state, "json", &headers);
     let state = state.clone();
     Json(state.jf.clone())
 }
@@ -35,30 +53,123 @@ pub async fn new_post(Extension(state): Extension<Arc<State>>) -> Result<Json<Ne
     Ok(Json(p.new_post))
 }
 
-#[instrument(skip(state))]
-pub async fn atom(Extension(state): Extension<Arc<State>>) -> Result<Response> {
+#[instrument(skip(state, headers))]
+pub async fn atom(Extension(state): Extension<Arc<State>>, headers: HeaderMap) -> Result<Response> {
     HIT_COUNTER.with_label_values(&["atom"]).inc();
+    count_subscribers(&state, "atom", &headers);
     let state = state.clone();
     let mut buf = Vec::new();
     templates::blog_atom_xml(&mut buf, state.everything.clone())?;
     Ok(Response::builder()
         .status(200)
         .header("Content-Type", "application/atom+xml")
-        .header("ETag", ETAG.clone())
+        .header("ETag", etag(&buf))
         .header("Last-Modified", &*LAST_MODIFIED)
         .body(body::boxed(body::Full::from(buf)))?)
 }
 
-#[instrument(skip(state))]
-pub async fn rss(Extension(state): Extension<Arc<State>>) -> Result<Response> {
+#[instrument(skip(state, headers))]
+pub async fn rss(Extension(state): Extension<Arc<State>>, headers: HeaderMap) -> Result<Response> {
     HIT_COUNTER.with_label_values(&["rss"]).inc();
+    count_subscribers(&state, "rss", &headers);
     let state = state.clone();
     let mut buf = Vec::new();
     templates::blog_rss_xml(&mut buf, state.everything.clone())?;
     Ok(Response::builder()
         .status(200)
         .header("Content-Type", "application/rss+xml")
-        .header("ETag", ETAG.clone())
+        .header("ETag", etag(&buf))
+        .header("Last-Modified", &*LAST_MODIFIED)
+        .body(body::boxed(body::Full::from(buf)))?)
+}
+
+#[instrument(skip(state, headers))]
+pub async fn incidents_rss(
+    Extension(state): Extension<Arc<State>>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    HIT_COUNTER.with_label_values(&["incidents_rss"]).inc();
+    count_subscribers(&state, "incidents_rss", &headers);
+    let incidents = state
+        .incidents
+        .iter()
+        .filter(|post| post.listed())
+        .cloned()
+        .collect();
+    let mut buf = Vec::new();
+    templates::incidents_rss_xml(&mut buf, incidents)?;
+    Ok(Response::builder()
+        .status(200)
+        .header("Content-Type", "application/rss+xml")
+        .header("ETag", etag(&buf))
+        .header("Last-Modified", &*LAST_MODIFIED)
+        .body(body::boxed(body::Full::from(buf)))?)
+}
+
+#[instrument(skip(state, headers))]
+pub async fn digest_rss(
+    Extension(state): Extension<Arc<State>>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    HIT_COUNTER.with_label_values(&["digest_rss"]).inc();
+    count_subscribers(&state, "digest_rss", &headers);
+    let issues = match state.storage.digests().list(false).await {
+        Ok(issues) => issues,
+        Err(why) => {
+            error!("can't load the digest: {why:?}");
+            return Ok(Response::builder()
+                .status(503)
+                .body(body::boxed(body::Full::from("can't load the digest")))?);
+        }
+    };
+    let mut buf = Vec::new();
+    templates::digest_rss_xml(&mut buf, issues)?;
+    Ok(Response::builder()
+        .status(200)
+        .header("Content-Type", "application/rss+xml")
+        .header("ETag", etag(&buf))
+        .body(body::boxed(body::Full::from(buf)))?)
+}
+
+#[instrument(skip(state, headers))]
+pub async fn books_rss(
+    Extension(state): Extension<Arc<State>>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    HIT_COUNTER.with_label_values(&["books_rss"]).inc();
+    count_subscribers(&state, "books_rss", &headers);
+    let state = state.clone();
+    let mut books: Vec<Book> = state
+        .cfg
+        .books
+        .iter()
+        .filter(|b| b.finished_in().is_some())
+        .cloned()
+        .collect();
+    books.sort_by(|a, b| b.finish_date.cmp(&a.finish_date));
+    let mut buf = Vec::new();
+    templates::books_rss_xml(&mut buf, books)?;
+    Ok(Response::builder()
+        .status(200)
+        .header("Content-Type", "application/rss+xml")
+        .header("ETag", etag(&buf))
+        .header("Last-Modified", &*LAST_MODIFIED)
+        .body(body::boxed(body::Full::from(buf)))?)
+}
+
+#[instrument(skip(state, headers))]
+pub async fn changelog_rss(
+    Extension(state): Extension<Arc<State>>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    HIT_COUNTER.with_label_values(&["changelog_rss"]).inc();
+    count_subscribers(&state, "changelog_rss", &headers);
+    let mut buf = Vec::new();
+    templates::changelog_rss_xml(&mut buf, super::changelog::entries(&state.cfg.changelog))?;
+    Ok(Response::builder()
+        .status(200)
+        .header("Content-Type", "application/rss+xml")
+        .header("ETag", etag(&buf))
         .header("Last-Modified", &*LAST_MODIFIED)
         .body(body::boxed(body::Full::from(buf)))?)
 }
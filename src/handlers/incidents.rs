@@ -0,0 +1,42 @@
+use crate::{app::State, post::Post, tmpl};
+use axum::extract::{Extension, Path};
+use http::StatusCode;
+use lazy_static::lazy_static;
+use maud::Markup;
+use prometheus::{opts, register_int_counter_vec, IntCounterVec};
+use std::sync::Arc;
+use tracing::instrument;
+
+lazy_static! {
+    static ref HIT_COUNTER: IntCounterVec = register_int_counter_vec!(
+        opts!("incident_hits", "Number of hits to incident reports"),
+        &["name"]
+    )
+    .unwrap();
+}
+
+#[instrument(skip(state))]
+pub async fn post_view(
+    Path(name): Path<String>,
+    Extension(state): Extension<Arc<State>>,
+) -> (StatusCode, Markup) {
+    let mut want: Option<&Post> = None;
+    let want_link = format!("incidents/{}", name);
+
+    for post in &state.incidents {
+        if post.link == want_link {
+            want = Some(&post);
+        }
+    }
+
+    match want {
+        None => (StatusCode::NOT_FOUND, tmpl::not_found(want_link)),
+        Some(post) => {
+            HIT_COUNTER
+                .with_label_values(&[name.clone().as_str()])
+                .inc();
+            let body = maud::PreEscaped(&post.body_html);
+            (StatusCode::OK, tmpl::blog::incident(&post, body))
+        }
+    }
+}
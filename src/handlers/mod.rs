@@ -1,21 +1,32 @@
 use crate::{app::State, tmpl};
 use axum::{
     body,
-    extract::Extension,
+    extract::{Extension, Path},
     http::StatusCode,
     response::{Html, IntoResponse, Response},
+    Json,
 };
 use chrono::{Datelike, Timelike, Utc, Weekday};
 use lazy_static::lazy_static;
 use maud::Markup;
 use prometheus::{opts, register_int_counter_vec, IntCounterVec};
 use std::sync::Arc;
-use tracing::instrument;
+use tracing::{error, instrument};
+use xesite_templates::context::site;
 
+pub mod activitypub;
+pub mod admin;
 pub mod api;
+pub mod auth;
 pub mod blog;
+pub mod changelog;
+pub mod digest;
 pub mod feeds;
 pub mod gallery;
+pub mod books;
+pub mod incidents;
+pub mod notes;
+pub mod prefs;
 pub mod streams;
 pub mod talks;
 
@@ -82,9 +93,8 @@ pub async fn characters(Extension(state): Extension<Arc<State>>) -> Markup {
 pub async fn index(Extension(state): Extension<Arc<State>>) -> Result<Markup> {
     HIT_COUNTER.with_label_values(&["index"]).inc();
     let state = state.clone();
-    let cfg = state.cfg.clone();
 
-    Ok(tmpl::index(&cfg.default_author, &cfg.notable_projects))
+    Ok(tmpl::home::index(&state))
 }
 
 #[instrument(skip(state))]
@@ -105,10 +115,18 @@ pub async fn pronouns(Extension(state): Extension<Arc<State>>) -> Markup {
     crate::tmpl::pronoun_page(&cfg.pronouns)
 }
 
-#[instrument]
-pub async fn feeds() -> Markup {
+#[instrument(skip(state))]
+pub async fn status(Extension(state): Extension<Arc<State>>) -> Markup {
+    HIT_COUNTER.with_label_values(&["status"]).inc();
+    let state = state.clone();
+
+    tmpl::status(&state.status.snapshot().await, &state.incidents)
+}
+
+#[instrument(skip(state))]
+pub async fn feeds(Extension(state): Extension<Arc<State>>) -> Markup {
     HIT_COUNTER.with_label_values(&["feeds"]).inc();
-    crate::tmpl::feeds()
+    crate::tmpl::feeds(state.subscribers.trend())
 }
 
 #[axum_macros::debug_handler]
@@ -152,9 +170,187 @@ pub async fn signalboost(Extension(state): Extension<Arc<State>>) -> Markup {
 }
 
 #[instrument]
-pub async fn not_found(uri: axum::http::Uri) -> (StatusCode, Markup) {
+pub async fn manifest() -> Json<serde_json::Value> {
+    Json(crate::pwa::manifest())
+}
+
+#[instrument]
+pub async fn maskable_icon(Path(file): Path<String>) -> Response {
+    let size = file
+        .strip_suffix(".png")
+        .and_then(|size| size.parse::<u32>().ok())
+        .filter(|size| crate::pwa::MASKABLE_SIZES.contains(size));
+    let Some(size) = size else {
+        return (StatusCode::NOT_FOUND, tmpl::not_found(format!("/icons/maskable/{file}")))
+            .into_response();
+    };
+
+    match crate::pwa::maskable_icon(size) {
+        Ok(icon) => ([("Content-Type", "image/png")], icon).into_response(),
+        Err(why) => {
+            error!("can't make maskable icon of size {size}: {why:?}");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                tmpl::error("can't make this icon"),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// The generated link preview card of a post without any images.
+#[instrument(skip(state))]
+pub async fn og_card(
+    Extension(state): Extension<Arc<State>>,
+    Path(link): Path<String>,
+) -> Response {
+    let post = link
+        .strip_suffix(".png")
+        .and_then(|link| state.everything.iter().find(|post| post.link == link));
+    let Some(post) = post else {
+        return (StatusCode::NOT_FOUND, tmpl::not_found(format!("/og/{link}"))).into_response();
+    };
+
+    match crate::og::card(post).await {
+        Ok(card) => ([("Content-Type", "image/png")], card).into_response(),
+        Err(why) => {
+            error!("can't make a card for {}: {why:?}", post.link);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                tmpl::error("can't make this card"),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Which stickers posts use, for deciding what art to commission next. It
+/// isn't linked from anywhere and search engines are asked not to index it.
+#[instrument(skip(state))]
+pub async fn moods(Extension(state): Extension<Arc<State>>) -> impl IntoResponse {
+    HIT_COUNTER.with_label_values(&["moods"]).inc();
+    ([("X-Robots-Tag", "noindex")], tmpl::moods(&state.moods))
+}
+
+/// A character's stickers as a zip file, see [crate::stickers].
+#[instrument(skip(state))]
+pub async fn sticker_pack(
+    Extension(state): Extension<Arc<State>>,
+    Path(file): Path<String>,
+) -> Response {
+    let ch = file
+        .strip_suffix(".zip")
+        .and_then(|name| {
+            state
+                .cfg
+                .characters
+                .iter()
+                .find(|ch| ch.sticker_name == name)
+        })
+        .filter(|ch| ch.sticker_license.redistributable);
+    let Some(ch) = ch else {
+        return not_found_page(&format!("/stickers/{file}"));
+    };
+
+    match crate::stickers::pack(ch).await {
+        Ok(pack) => (
+            [
+                ("Content-Type", "application/zip".to_string()),
+                (
+                    "Content-Disposition",
+                    format!(r#"attachment; filename="{}-stickers.zip""#, ch.sticker_name),
+                ),
+            ],
+            pack,
+        )
+            .into_response(),
+        Err(why) => {
+            error!("can't make a sticker pack for {}: {why:?}", ch.sticker_name);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                tmpl::error("can't make this sticker pack"),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// The markdown a post was rendered from and its signatures, so readers can
+/// check the signatures themselves.
+#[instrument(skip(state))]
+pub async fn source(
+    Path((dir, file)): Path<(String, String)>,
+    Extension(state): Extension<Arc<State>>,
+) -> Response {
+    let posts = match dir.as_str() {
+        "blog" => &state.blog,
+        "talks" => &state.talks,
+        "gallery" => &state.gallery,
+        _ => return not_found_page(&format!("/source/{dir}/{file}")),
+    };
+    let (stem, kind) = if let Some(stem) = file.strip_suffix(".markdown.minisig") {
+        (stem, "minisig")
+    } else if let Some(stem) = file.strip_suffix(".markdown.asc") {
+        (stem, "asc")
+    } else if let Some(stem) = file.strip_suffix(".markdown") {
+        (stem, "markdown")
+    } else {
+        return not_found_page(&format!("/source/{dir}/{file}"));
+    };
+    let link = format!("{dir}/{stem}");
+    let Some(post) = posts.iter().find(|post| post.link == link) else {
+        return not_found_page(&format!("/source/{dir}/{file}"));
+    };
+
+    HIT_COUNTER.with_label_values(&["source"]).inc();
+    let body = match kind {
+        "minisig" => post.signatures.minisign.clone(),
+        "asc" => post.signatures.pgp.clone(),
+        _ => tokio::fs::read_to_string(format!("./{link}.markdown")).await.ok(),
+    };
+    match body {
+        Some(body) => ([("Content-Type", "text/plain; charset=utf-8")], body).into_response(),
+        None => not_found_page(&format!("/source/{dir}/{file}")),
+    }
+}
+
+fn not_found_page(path: &str) -> Response {
+    (StatusCode::NOT_FOUND, tmpl::not_found(path)).into_response()
+}
+
+#[instrument]
+pub async fn offline() -> Markup {
+    HIT_COUNTER.with_label_values(&["offline"]).inc();
+    tmpl::offline()
+}
+
+/// The service worker, with the precache list for this build in front of
+/// the code in `static/js/sw.js`.
+#[instrument]
+pub async fn service_worker() -> Result<Response> {
+    let body = format!(
+        "const VERSION = {};\nconst PRECACHE = {};\nconst FALLBACK = {};\n\n{}",
+        serde_json::to_string(tmpl::cachebuster())?,
+        serde_json::to_string(&tmpl::precache())?,
+        serde_json::to_string(&site().asset_fallback())?,
+        include_str!("../../static/js/sw.js"),
+    );
+    Ok(Response::builder()
+        .status(200)
+        .header("Content-Type", "application/javascript")
+        .body(body::boxed(body::Full::from(body)))?)
+}
+
+#[instrument(skip(state))]
+pub async fn not_found(Extension(state): Extension<Arc<State>>, uri: axum::http::Uri) -> Response {
+    if let Some(redirect) = state.cfg.redirects.iter().find(|r| r.from == uri.path()) {
+        HIT_COUNTER.with_label_values(&["redirect"]).inc();
+        let status = StatusCode::from_u16(redirect.status).unwrap_or(StatusCode::MOVED_PERMANENTLY);
+        return (status, [("Location", redirect.to.clone())]).into_response();
+    }
+
     HIT_COUNTER.with_label_values(&["not_found"]).inc();
-    (StatusCode::NOT_FOUND, tmpl::not_found(uri.path()))
+    (StatusCode::NOT_FOUND, tmpl::not_found(uri.path())).into_response()
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -165,6 +361,9 @@ pub enum Error {
     #[error("post not found: {0}")]
     PostNotFound(String),
 
+    #[error("page not found: {0}")]
+    PageNotFound(usize),
+
     #[error("patreon key not working, poke me to get this fixed")]
     NoPatrons,
 
@@ -176,6 +375,9 @@ pub enum Error {
 
     #[error("string conversion error: {0}")]
     ToStr(#[from] http::header::ToStrError),
+
+    #[error("json error: {0}")]
+    JSON(#[from] serde_json::Error),
 }
 
 pub type Result<T = Html<Vec<u8>>> = std::result::Result<T, Error>;
@@ -189,7 +391,9 @@ impl IntoResponse for Error {
 
         Response::builder()
             .status(match self {
-                Error::SeriesNotFound(_) | Error::PostNotFound(_) => StatusCode::NOT_FOUND,
+                Error::SeriesNotFound(_) | Error::PostNotFound(_) | Error::PageNotFound(_) => {
+                    StatusCode::NOT_FOUND
+                }
                 _ => StatusCode::INTERNAL_SERVER_ERROR,
             })
             .body(body)
@@ -0,0 +1,45 @@
+use crate::{app::State, tmpl};
+use axum::extract::{Extension, Path};
+use http::StatusCode;
+use lazy_static::lazy_static;
+use maud::Markup;
+use prometheus::{opts, register_int_counter_vec, IntCounterVec};
+use std::sync::Arc;
+use tracing::instrument;
+
+lazy_static! {
+    static ref HIT_COUNTER: IntCounterVec = register_int_counter_vec!(
+        opts!("notes_hits", "Number of hits to notes in the digital garden"),
+        &["name"]
+    )
+    .unwrap();
+}
+
+#[instrument(skip(state))]
+pub async fn index(Extension(state): Extension<Arc<State>>) -> Markup {
+    let state = state.clone();
+    tmpl::notes_index(&state.garden)
+}
+
+#[instrument(skip(state))]
+pub async fn post_view(
+    Path(name): Path<String>,
+    Extension(state): Extension<Arc<State>>,
+) -> (StatusCode, Markup) {
+    match state.garden.get(&name) {
+        None => (
+            StatusCode::NOT_FOUND,
+            tmpl::not_found(format!("notes/{name}")),
+        ),
+        Some(note) => {
+            HIT_COUNTER
+                .with_label_values(&[name.clone().as_str()])
+                .inc();
+            let body = maud::PreEscaped(&note.body_html);
+            (
+                StatusCode::OK,
+                tmpl::blog::note(note, body, state.garden.backlinks(&name)),
+            )
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use super::admin::private;
+use crate::{app::State, auth::safe_next, prefs, tmpl};
+use axum::{
+    extract::{Extension, Form},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Redirect, Response},
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::instrument;
+
+#[instrument(skip(state, headers))]
+pub async fn page(Extension(state): Extension<Arc<State>>, headers: HeaderMap) -> Response {
+    private(tmpl::prefs::page(prefs::for_request(&state, &headers)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Change {
+    /// `on` or `off`.
+    pub ads: String,
+    pub next: Option<String>,
+}
+
+/// Saves what the reader asked for. This isn't a [crate::auth::CsrfForm],
+/// since most readers aren't logged in and another site changing someone's
+/// preferences can't do much harm.
+#[instrument(skip(state, headers))]
+pub async fn set(
+    Extension(state): Extension<Arc<State>>,
+    headers: HeaderMap,
+    Form(form): Form<Change>,
+) -> Response {
+    let mut prefs = prefs::for_request(&state, &headers);
+    prefs.no_ads = form.ads == "off";
+    let next = safe_next(form.next.as_deref().or(Some(prefs::PREFIX)));
+    (
+        [(header::SET_COOKIE, prefs::set_cookie(prefs))],
+        Redirect::to(&next),
+    )
+        .into_response()
+}
@@ -2,11 +2,11 @@ use crate::{
     app::{State, VOD},
     tmpl::base,
 };
-use axum::{extract::Path, Extension};
+use axum::{extract::Path, http::HeaderMap, Extension};
 use chrono::prelude::*;
 use http::StatusCode;
 use lazy_static::lazy_static;
-use maud::{html, Markup, Render};
+use maud::{html, Markup};
 use prometheus::{opts, register_int_counter_vec, IntCounterVec};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
@@ -66,6 +66,7 @@ pub struct ShowArgs {
 pub async fn show(
     Extension(state): Extension<Arc<State>>,
     Path(args): Path<ShowArgs>,
+    headers: HeaderMap,
 ) -> (StatusCode, Markup) {
     let state = state.clone();
     let cfg = state.cfg.clone();
@@ -90,5 +91,9 @@ pub async fn show(
     let vod = found.unwrap();
     HIT_COUNTER.with_label_values(&[&vod.slug]).inc();
 
-    (StatusCode::OK, base(Some(&vod.title), None, vod.render()))
+    let prefs = crate::prefs::for_request(&state, &headers);
+    (
+        StatusCode::OK,
+        base(Some(&vod.title), None, vod.page(prefs)),
+    )
 }
@@ -1,10 +1,11 @@
 use super::Result;
-use crate::{app::State, post::Post, tmpl};
-use axum::extract::{Extension, Path};
+use crate::{app::State, post::Post, prefs, tmpl};
+use axum::extract::{Extension, Path, Query};
 use http::{header::HeaderMap, StatusCode};
 use lazy_static::lazy_static;
 use maud::Markup;
 use prometheus::{opts, register_int_counter_vec, IntCounterVec};
+use serde::Deserialize;
 use std::sync::Arc;
 use tracing::instrument;
 
@@ -14,18 +15,33 @@ lazy_static! {
         &["name"]
     )
     .unwrap();
+    static ref SLIDE_VARIANTS: IntCounterVec = register_int_counter_vec!(
+        opts!(
+            "talks_slide_variant_hits",
+            "Number of talk views with all slides or only the essential ones"
+        ),
+        &["variant"]
+    )
+    .unwrap();
+}
+
+#[derive(Deserialize)]
+pub struct TalkQuery {
+    /// `essential` to only show the essential slides.
+    slides: Option<String>,
 }
 
 #[instrument(skip(state))]
 pub async fn index(Extension(state): Extension<Arc<State>>) -> Result<Markup> {
     let state = state.clone();
-    Ok(tmpl::post_index(&state.talks, "Talks", false))
+    Ok(tmpl::post_index(&state.talks, "Talks"))
 }
 
 #[instrument(skip(state, headers))]
 pub async fn post_view(
     Path(name): Path<String>,
     Extension(state): Extension<Arc<State>>,
+    Query(query): Query<TalkQuery>,
     headers: HeaderMap,
 ) -> Result<(StatusCode, Markup)> {
     let mut want: Option<Post> = None;
@@ -50,8 +66,17 @@ pub async fn post_view(
             HIT_COUNTER
                 .with_label_values(&[name.clone().as_str()])
                 .inc();
+            state.analytics.record(&post.link).await;
             let body = maud::PreEscaped(&post.body_html);
-            Ok((StatusCode::OK, tmpl::blog::talk(&post, body, referer)))
+            let prefs = prefs::for_request(&state, &headers);
+            let essential = query.slides.as_deref() == Some("essential");
+            SLIDE_VARIANTS
+                .with_label_values(&[if essential { "essential" } else { "all" }])
+                .inc();
+            Ok((
+                StatusCode::OK,
+                tmpl::blog::talk(&post, body, referer, prefs, essential),
+            ))
         }
     }
 }
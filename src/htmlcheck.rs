@@ -0,0 +1,273 @@
+//! Checks rendered pages for HTML mistakes that browsers silently paper over.
+
+use crate::app::State;
+use axum::{body::Body, http::Request, Router};
+use color_eyre::eyre::{eyre, Result};
+use html5ever::{parse_document, tendril::TendrilSink, tree_builder::TreeBuilderOpts, ParseOpts};
+use markup5ever_rcdom::{Handle, NodeData, RcDom};
+use std::{collections::HashMap, fmt, sync::Arc};
+use tower::ServiceExt;
+
+/// Index pages that are checked along with every post.
+const INDEXES: &[&str] = &["/", "/blog", "/talks", "/gallery", "/feeds"];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Problem {
+    /// The HTML parser had to recover from something, such as a `div`
+    /// inside of a `p` or a stray closing tag.
+    Parse(String),
+    DuplicateId {
+        id: String,
+        count: usize,
+    },
+    InvalidAttribute {
+        element: String,
+        name: String,
+    },
+    EmptyAttribute {
+        element: String,
+        name: String,
+    },
+    /// An inline script looks up an element by an ID that isn't on the page.
+    MissingElement {
+        id: String,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Parse(why) => write!(f, "parse error: {why}"),
+            Problem::DuplicateId { id, count } => write!(f, "id {id:?} is used {count} times"),
+            Problem::InvalidAttribute { element, name } => {
+                write!(f, "<{element}> has an invalid attribute {name:?}")
+            }
+            Problem::EmptyAttribute { element, name } => {
+                write!(f, "<{element}> has an empty {name} attribute")
+            }
+            Problem::MissingElement { id } => {
+                write!(f, "a script looks up #{id} but nothing has that id")
+            }
+        }
+    }
+}
+
+/// Attributes that point at something and are useless when empty.
+const NON_EMPTY: &[&str] = &["id", "href", "src", "srcset", "for"];
+
+fn valid_attribute_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':' | '.'))
+}
+
+/// IDs that inline scripts look up with `getElementById` or Xeact's `g`.
+fn script_targets(script: &str) -> Vec<String> {
+    lazy_static::lazy_static! {
+        static ref LOOKUP: regex::Regex =
+            regex::Regex::new(r#"(?:getElementById|\bg)\(\s*["']([^"']+)["']\s*\)"#).unwrap();
+    }
+
+    LOOKUP
+        .captures_iter(script)
+        .map(|c| c[1].to_string())
+        .collect()
+}
+
+#[derive(Default)]
+struct Walker {
+    ids: HashMap<String, usize>,
+    targets: Vec<String>,
+    problems: Vec<Problem>,
+}
+
+impl Walker {
+    fn walk(&mut self, node: &Handle) {
+        if let NodeData::Element { name, attrs, .. } = &node.data {
+            let element = name.local.to_string();
+            for attr in attrs.borrow().iter() {
+                let attr_name = attr.name.local.to_string();
+                if !valid_attribute_name(&attr_name) {
+                    self.problems.push(Problem::InvalidAttribute {
+                        element: element.clone(),
+                        name: attr_name.clone(),
+                    });
+                }
+                if NON_EMPTY.contains(&attr_name.as_str()) && attr.value.trim().is_empty() {
+                    self.problems.push(Problem::EmptyAttribute {
+                        element: element.clone(),
+                        name: attr_name.clone(),
+                    });
+                }
+                if attr_name == "id" {
+                    *self.ids.entry(attr.value.to_string()).or_default() += 1;
+                }
+            }
+
+            if element == "script" {
+                let mut script = String::new();
+                for child in node.children.borrow().iter() {
+                    if let NodeData::Text { contents } = &child.data {
+                        script.push_str(&contents.borrow());
+                    }
+                }
+                self.targets.extend(script_targets(&script));
+            }
+        }
+
+        for child in node.children.borrow().iter() {
+            self.walk(child);
+        }
+    }
+}
+
+/// Parses a page and returns everything wrong with it.
+pub fn check(html: &str) -> Vec<Problem> {
+    let opts = ParseOpts {
+        tree_builder: TreeBuilderOpts {
+            exact_errors: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut dom = parse_document(RcDom::default(), opts).one(html);
+
+    let mut walker = Walker::default();
+    walker.walk(&dom.document);
+
+    let mut problems: Vec<Problem> = dom
+        .errors
+        .drain(..)
+        .map(|why| Problem::Parse(why.to_string()))
+        .collect();
+    problems.append(&mut walker.problems);
+
+    let mut duplicates: Vec<(String, usize)> = walker
+        .ids
+        .iter()
+        .filter(|(_, count)| **count > 1)
+        .map(|(id, count)| (id.clone(), *count))
+        .collect();
+    duplicates.sort();
+    problems.extend(
+        duplicates
+            .into_iter()
+            .map(|(id, count)| Problem::DuplicateId { id, count }),
+    );
+
+    for id in walker.targets {
+        if !walker.ids.contains_key(&id) {
+            problems.push(Problem::MissingElement { id });
+        }
+    }
+
+    problems
+}
+
+/// The pages that were checked and what's wrong with the ones that aren't
+/// clean.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub checked: usize,
+    pub broken: Vec<(String, Vec<Problem>)>,
+}
+
+/// Renders the index pages and every post through the router and checks
+/// them. Returns an error if anything is wrong and `strict` is set.
+pub async fn check_site(state: Arc<State>, app: Router, strict: bool) -> Result<Report> {
+    let posts = state
+        .everything
+        .iter()
+        .map(|post| format!("/{}", post.link));
+    let paths = INDEXES.iter().map(|path| path.to_string()).chain(posts);
+
+    let mut report = Report::default();
+    for path in paths {
+        let resp = app
+            .clone()
+            .oneshot(Request::builder().uri(&path).body(Body::empty())?)
+            .await?;
+        if !resp.status().is_success() {
+            return Err(eyre!("{path} answered with {}", resp.status()));
+        }
+        let body = hyper::body::to_bytes(resp.into_body()).await?;
+        report.checked += 1;
+
+        let problems = check(&String::from_utf8_lossy(&body));
+        if problems.is_empty() {
+            continue;
+        }
+        for problem in &problems {
+            warn!("{path}: {problem}");
+        }
+        report.broken.push((path, problems));
+    }
+
+    info!(
+        "{} of {} pages have HTML problems",
+        report.broken.len(),
+        report.checked
+    );
+    if strict && !report.broken.is_empty() {
+        return Err(eyre!("{} pages have HTML problems", report.broken.len()));
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maud::PreEscaped;
+
+    #[test]
+    fn finds_problems() {
+        let problems = check(
+            r#"<!DOCTYPE html><html><head><title>hi</title></head><body>
+<p><div id="a"></div></p>
+<span id="a"></span>
+<a href="">nowhere</a>
+<script>g("b")</script>
+</body></html>"#,
+        );
+
+        assert!(problems.iter().any(|p| matches!(p, Problem::Parse(_))));
+        assert!(problems.contains(&Problem::DuplicateId {
+            id: "a".into(),
+            count: 2
+        }));
+        assert!(problems.contains(&Problem::EmptyAttribute {
+            element: "a".into(),
+            name: "href".into()
+        }));
+        assert!(problems.contains(&Problem::MissingElement { id: "b".into() }));
+    }
+
+    #[test]
+    fn clean_page() {
+        let page = maud::html! {
+            (maud::DOCTYPE)
+            html lang="en" {
+                head { title { "hi" } }
+                body {
+                    div #root { p { "hi" } }
+                    script { (PreEscaped(r#"g("root")"#)) }
+                }
+            }
+        };
+        assert_eq!(check(&page.0), vec![]);
+    }
+
+    #[tokio::test]
+    async fn every_page() -> Result<()> {
+        let state = Arc::new(crate::app::init("./config.dhall".into()).await?);
+        let app = crate::router(state.clone());
+        let report = check_site(state.clone(), app, false).await?;
+
+        assert_eq!(report.checked, INDEXES.len() + state.everything.len());
+        for (path, problems) in &report.broken {
+            assert!(!problems.is_empty(), "{path} is listed without problems");
+        }
+        Ok(())
+    }
+}
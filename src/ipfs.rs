@@ -0,0 +1,151 @@
+//! Publishes static exports (see [crate::export]) to IPFS, so there are
+//! content-addressed mirrors of the site. This shells out to the `ipfs` CLI
+//! of a local node, which makes the export findable as long as that node is
+//! up. A pinning service can be configured to keep it around after that.
+
+use crate::app::config::Ipfs;
+use chrono::prelude::*;
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::{fs, process::Command};
+
+/// One published export.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Release {
+    /// The commit the export was made from.
+    pub commit: String,
+    pub cid: String,
+    #[serde(rename = "publishedAt")]
+    pub published_at: DateTime<Utc>,
+}
+
+/// The DNS record that points `domain` at `cid` for IPFS gateways and
+/// resolvers that support DNSLink.
+pub fn dnslink(domain: &str, cid: &str) -> String {
+    format!("_dnslink.{domain}. 300 IN TXT \"dnslink=/ipfs/{cid}\"")
+}
+
+/// The host part of a base URL.
+pub fn domain(base_url: &str) -> &str {
+    let host = base_url
+        .split_once("://")
+        .map(|(_, h)| h)
+        .unwrap_or(base_url);
+    host.split('/').next().unwrap_or(host)
+}
+
+/// Where the CAR file for an export of `dir` is written. It can't go inside
+/// the export, so it goes next to it.
+pub fn car_path(dir: &Path) -> PathBuf {
+    dir.with_extension("car")
+}
+
+async fn ipfs(args: &[&str]) -> Result<Vec<u8>> {
+    let output = Command::new("ipfs")
+        .args(args)
+        .output()
+        .await
+        .wrap_err("can't run ipfs, is it installed?")?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "ipfs {} exited with {}: {}",
+            args.join(" "),
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// Adds and pins `dir` on the local node, returning the CID of the folder.
+async fn add(dir: &Path) -> Result<String> {
+    let dir = dir.to_str().ok_or(eyre!("{} isn't UTF-8", dir.display()))?;
+    let out = ipfs(&["add", "--recursive", "--cid-version=1", "--quieter", dir]).await?;
+    let cid = String::from_utf8(out)?.trim().to_string();
+    if cid.is_empty() {
+        return Err(eyre!("ipfs add didn't print a CID"));
+    }
+    Ok(cid)
+}
+
+/// Asks a pinning service (see <https://ipfs.github.io/pinning-services-api-spec/>)
+/// to pin `cid`.
+async fn pin_remote(endpoint: &str, token: &str, cid: &str, name: &str) -> Result<()> {
+    let resp = reqwest::Client::new()
+        .post(format!("{}/pins", endpoint.trim_end_matches('/')))
+        .bearer_auth(token)
+        .json(&serde_json::json!({ "cid": cid, "name": name }))
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        return Err(eyre!(
+            "pinning service returned {}: {}",
+            resp.status(),
+            resp.text().await.unwrap_or_default()
+        ));
+    }
+    Ok(())
+}
+
+async fn load_releases(path: &Path) -> Result<Vec<Release>> {
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    Ok(serde_json::from_slice(&fs::read(path).await?)?)
+}
+
+/// Publishes the export in `dir`, writes its CAR file next to it and
+/// records the release.
+pub async fn publish(cfg: &Ipfs, base_url: &str, dir: &Path) -> Result<Release> {
+    let cid = add(dir).await?;
+    info!("added {} as {cid}", dir.display());
+
+    let car = car_path(dir);
+    fs::write(&car, ipfs(&["dag", "export", &cid]).await?).await?;
+    info!("wrote {}", car.display());
+
+    let release = Release {
+        commit: env!("GITHUB_SHA").to_string(),
+        cid: cid.clone(),
+        published_at: Utc::now(),
+    };
+
+    if let Some(endpoint) = &cfg.pinning_service_url {
+        let name = format!("{}-{}", domain(base_url), release.commit);
+        pin_remote(endpoint, &cfg.pinning_token, &cid, &name).await?;
+        info!("pinned {cid} with {endpoint}");
+    }
+
+    let path = Path::new(&cfg.releases_path);
+    let mut releases = load_releases(path).await?;
+    releases.push(release.clone());
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    fs::write(path, serde_json::to_vec_pretty(&releases)?).await?;
+
+    Ok(release)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dnslink_record() {
+        assert_eq!(
+            dnslink(domain("https://xeiaso.net"), "bafyexample"),
+            "_dnslink.xeiaso.net. 300 IN TXT \"dnslink=/ipfs/bafyexample\""
+        );
+        assert_eq!(domain("https://staging.xeiaso.net/"), "staging.xeiaso.net");
+    }
+
+    #[test]
+    fn car_next_to_export() {
+        assert_eq!(
+            car_path(Path::new("out/site")),
+            PathBuf::from("out/site.car")
+        );
+    }
+}
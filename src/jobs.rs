@@ -0,0 +1,541 @@
+//! Work that happens every so often, like checking on services or sending
+//! new posts to followers. Jobs are added to the [Scheduler] at startup with
+//! a [Schedule], and each one runs on its own task so a slow job doesn't
+//! hold up the others. When each job last ran is saved in the database, see
+//! [crate::storage::JobStates], so a job that was due while the site was
+//! down runs as soon as it's back.
+//!
+//! On shutdown, jobs that are running get to finish, and nothing new is
+//! started.
+
+use crate::storage::JobStates;
+use chrono::{prelude::*, Duration as ChronoDuration};
+use color_eyre::eyre::{eyre, Result};
+use futures::future::BoxFuture;
+use lazy_static::lazy_static;
+use prometheus::{
+    opts, register_histogram_vec, register_int_counter_vec, register_int_gauge_vec, HistogramVec,
+    IntCounterVec, IntGaugeVec,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap, fmt, future::Future, path::Path, str::FromStr, sync::Arc, time::Duration,
+};
+use tokio::{
+    sync::{watch, Mutex, RwLock},
+    task::JoinHandle,
+    time::{sleep, timeout, Instant},
+};
+
+/// How long running jobs get to finish when the site shuts down.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(30);
+
+lazy_static! {
+    static ref RUNS: IntCounterVec = register_int_counter_vec!(
+        opts!("job_runs", "Number of times each background job ran"),
+        &["job", "result"]
+    )
+    .unwrap();
+    static ref DURATION: HistogramVec = register_histogram_vec!(
+        "job_duration_seconds",
+        "How long background jobs take to run",
+        &["job"]
+    )
+    .unwrap();
+    static ref LAST_SUCCESS: IntGaugeVec = register_int_gauge_vec!(
+        opts!(
+            "job_last_success_timestamp",
+            "When each background job last worked, as a Unix timestamp"
+        ),
+        &["job"]
+    )
+    .unwrap();
+}
+
+/// When a job runs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Schedule {
+    /// This long after it last ran.
+    Every(Duration),
+    Cron(Box<Cron>),
+}
+
+impl Schedule {
+    /// The first time the job should run after `last` ran.
+    pub fn next_after(&self, last: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Schedule::Every(every) => Some(last + ChronoDuration::from_std(*every).ok()?),
+            Schedule::Cron(cron) => cron.next_after(last),
+        }
+    }
+}
+
+/// Parses durations like `90s`, `15m`, `1h` and `1d`.
+pub fn parse_duration(inp: &str) -> Result<Duration> {
+    let inp = inp.trim();
+    let split = inp
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| eyre!("{inp:?} has no unit"))?;
+    let (n, unit) = inp.split_at(split);
+    let n: u64 = n.parse().map_err(|_| eyre!("{inp:?} isn't a duration"))?;
+    let secs = match unit {
+        "s" => n,
+        "m" => n * 60,
+        "h" => n * 60 * 60,
+        "d" => n * 60 * 60 * 24,
+        _ => return Err(eyre!("{inp:?} has an unknown unit {unit:?}")),
+    };
+    if secs == 0 {
+        return Err(eyre!("{inp:?} is too short"));
+    }
+    Ok(Duration::from_secs(secs))
+}
+
+/// Schedules are written like crontab lines (`0 9 * * 1` is every Monday at
+/// 09:00 UTC), or as `@hourly`, `@daily`, `@weekly` or `@every 15m`.
+impl FromStr for Schedule {
+    type Err = color_eyre::eyre::Report;
+
+    fn from_str(inp: &str) -> Result<Self> {
+        let inp = inp.trim();
+        if let Some(every) = inp.strip_prefix("@every ") {
+            return Ok(Schedule::Every(parse_duration(every)?));
+        }
+        let cron = match inp {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            cron => cron,
+        };
+        Ok(Schedule::Cron(Box::new(cron.parse()?)))
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Schedule::Every(every) => write!(f, "every {}s", every.as_secs()),
+            Schedule::Cron(cron) => write!(f, "{}", cron.source),
+        }
+    }
+}
+
+/// A crontab schedule. Each field is a set of the values it matches, as
+/// bits.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cron {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether the day of the month or the day of the week was left as `*`.
+    /// Like cron, when both are given a day matching either is a match.
+    any_day: bool,
+    any_weekday: bool,
+}
+
+/// Parses one crontab field like `*`, `*/15`, `1,15` or `1-5`, where values
+/// go from `min` to `max`.
+fn cron_field(field: &str, min: u32, max: u32) -> Result<u64> {
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>()?),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (start.parse()?, end.parse()?),
+                None if step == 1 => (range.parse()?, range.parse()?),
+                None => (range.parse()?, max),
+            },
+        };
+        if step == 0 || start < min || end > max || start > end {
+            return Err(eyre!("{part:?} isn't between {min} and {max}"));
+        }
+        for n in (start..=end).step_by(step as usize) {
+            bits |= 1 << n;
+        }
+    }
+    Ok(bits)
+}
+
+impl FromStr for Cron {
+    type Err = color_eyre::eyre::Report;
+
+    fn from_str(inp: &str) -> Result<Self> {
+        let fields: Vec<&str> = inp.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(eyre!("{inp:?} doesn't have 5 fields"));
+        };
+        let mut cron = Cron {
+            source: fields.join(" "),
+            minutes: cron_field(minutes, 0, 59)?,
+            hours: cron_field(hours, 0, 23)?,
+            days: cron_field(days, 1, 31)?,
+            months: cron_field(months, 1, 12)?,
+            weekdays: cron_field(weekdays, 0, 7)?,
+            any_day: days == "*",
+            any_weekday: weekdays == "*",
+        };
+        // Sunday is both 0 and 7.
+        if cron.weekdays & (1 << 7) != 0 {
+            cron.weekdays |= 1;
+        }
+        if cron.next_after(Utc.timestamp_opt(0, 0).unwrap()).is_none() {
+            return Err(eyre!("{inp:?} never happens"));
+        }
+        Ok(cron)
+    }
+}
+
+impl Cron {
+    fn day_matches(&self, t: DateTime<Utc>) -> bool {
+        let day = self.days & (1 << t.day()) != 0;
+        let weekday = self.weekdays & (1 << t.weekday().num_days_from_sunday()) != 0;
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    /// The first minute after `after` that matches.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut t = after.with_second(0)?.with_nanosecond(0)? + ChronoDuration::minutes(1);
+        // Enough to get through leap years and days that only some months
+        // have.
+        for _ in 0..10_000 {
+            if self.months & (1 << t.month()) == 0 {
+                let (year, month) = if t.month() == 12 {
+                    (t.year() + 1, 1)
+                } else {
+                    (t.year(), t.month() + 1)
+                };
+                t = Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).single()?;
+            } else if !self.day_matches(t) {
+                t = Utc.from_utc_datetime(&t.date_naive().succ_opt()?.and_hms_opt(0, 0, 0)?);
+            } else if self.hours & (1 << t.hour()) == 0 {
+                t = t.with_minute(0)? + ChronoDuration::hours(1);
+            } else if self.minutes & (1 << t.minute()) == 0 {
+                t += ChronoDuration::minutes(1);
+            } else {
+                return Some(t);
+            }
+        }
+        None
+    }
+}
+
+/// What happened the last time a job ran. This is what's saved.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct JobState {
+    pub last_run: Option<DateTime<Utc>>,
+    pub last_success: Option<DateTime<Utc>>,
+    /// Why the last run failed, if it did.
+    pub last_error: Option<String>,
+    pub runs: u64,
+    pub failures: u64,
+}
+
+impl JobState {
+    /// When the job should run next. Jobs that have never run, or missed
+    /// their time, run at `now`.
+    pub fn next_run(&self, schedule: &Schedule, now: DateTime<Utc>) -> DateTime<Utc> {
+        let Some(last) = self.last_run else {
+            return now;
+        };
+        match schedule.next_after(last) {
+            Some(next) => next.max(now),
+            None => now,
+        }
+    }
+}
+
+/// How a job is doing, for the admin area.
+#[derive(Clone, Debug)]
+pub struct Status {
+    pub name: String,
+    pub schedule: String,
+    pub state: JobState,
+    pub next_run: DateTime<Utc>,
+    pub running: bool,
+}
+
+type JobFn = Box<dyn Fn() -> BoxFuture<'static, Result<()>> + Send + Sync>;
+
+struct Job {
+    name: String,
+    schedule: Schedule,
+    run: JobFn,
+}
+
+/// Runs every job when it's due.
+pub struct Scheduler {
+    store: JobStates,
+    jobs: std::sync::Mutex<Vec<Arc<Job>>>,
+    states: RwLock<HashMap<String, JobState>>,
+    running: RwLock<Vec<String>>,
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+    shutdown: watch::Sender<bool>,
+}
+
+impl Scheduler {
+    /// Keeps job states in `store`. States that were saved to `legacy`
+    /// before there was a database are moved into it.
+    pub async fn new(store: JobStates, legacy: &Path) -> Result<Self> {
+        let legacy: Option<HashMap<String, JobState>> = crate::storage::import_json(legacy).await?;
+        for (name, state) in legacy.into_iter().flatten() {
+            store.save(&name, &state).await?;
+        }
+        let states = store.all().await?;
+        Ok(Self {
+            store,
+            jobs: std::sync::Mutex::new(vec![]),
+            states: RwLock::new(states),
+            running: RwLock::new(vec![]),
+            tasks: Mutex::new(vec![]),
+            shutdown: watch::channel(false).0,
+        })
+    }
+
+    /// Adds a job. Jobs have to be added before [Scheduler::start].
+    pub fn add<F, Fut>(&self, name: &str, schedule: Schedule, job: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.jobs.lock().unwrap().push(Arc::new(Job {
+            name: name.to_string(),
+            schedule,
+            run: Box::new(move || Box::pin(job())),
+        }));
+    }
+
+    /// Starts running every job on its own task.
+    pub async fn start(self: Arc<Self>) {
+        let jobs = self.jobs.lock().unwrap().clone();
+        let mut tasks = self.tasks.lock().await;
+        for job in jobs {
+            info!("jobs: {} runs {}", job.name, job.schedule);
+            tasks.push(tokio::spawn(self.clone().run(job)));
+        }
+    }
+
+    async fn run(self: Arc<Self>, job: Arc<Job>) {
+        let mut shutdown = self.shutdown.subscribe();
+        loop {
+            if *shutdown.borrow() {
+                return;
+            }
+            let next = self
+                .state(&job.name)
+                .await
+                .next_run(&job.schedule, Utc::now());
+            let wait = (next - Utc::now()).to_std().unwrap_or_default();
+            tokio::select! {
+                _ = sleep(wait) => {}
+                _ = shutdown.changed() => return,
+            }
+            self.run_once(&job).await;
+        }
+    }
+
+    /// Runs `job` now and saves how it went.
+    async fn run_once(&self, job: &Job) {
+        self.running.write().await.push(job.name.clone());
+        let started = Instant::now();
+        let result = (job.run)().await;
+        DURATION
+            .with_label_values(&[&job.name])
+            .observe(started.elapsed().as_secs_f64());
+        self.running.write().await.retain(|name| *name != job.name);
+
+        let now = Utc::now();
+        let state = {
+            let mut states = self.states.write().await;
+            let state = states.entry(job.name.clone()).or_default();
+            state.last_run = Some(now);
+            state.runs += 1;
+            match &result {
+                Ok(()) => {
+                    state.last_success = Some(now);
+                    state.last_error = None;
+                    RUNS.with_label_values(&[&job.name, "ok"]).inc();
+                    LAST_SUCCESS
+                        .with_label_values(&[&job.name])
+                        .set(now.timestamp());
+                }
+                Err(why) => {
+                    error!("jobs: {} failed: {why:?}", job.name);
+                    state.last_error = Some(why.to_string());
+                    state.failures += 1;
+                    RUNS.with_label_values(&[&job.name, "error"]).inc();
+                }
+            }
+            state.clone()
+        };
+        if let Err(why) = self.store.save(&job.name, &state).await {
+            error!("jobs: can't save how {} went: {why}", job.name);
+        }
+    }
+
+    async fn state(&self, name: &str) -> JobState {
+        self.states
+            .read()
+            .await
+            .get(name)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// How every job is doing.
+    pub async fn snapshot(&self) -> Vec<Status> {
+        let jobs = self.jobs.lock().unwrap().clone();
+        let running = self.running.read().await.clone();
+        let now = Utc::now();
+        let mut result = vec![];
+        for job in jobs {
+            let state = self.state(&job.name).await;
+            result.push(Status {
+                name: job.name.clone(),
+                schedule: job.schedule.to_string(),
+                next_run: state.next_run(&job.schedule, now),
+                running: running.contains(&job.name),
+                state,
+            });
+        }
+        result
+    }
+
+    /// Stops starting jobs and waits a while for the ones that are running
+    /// to finish.
+    pub async fn shutdown(&self) {
+        let _ = self.shutdown.send(true);
+        let tasks: Vec<_> = self.tasks.lock().await.drain(..).collect();
+        if timeout(SHUTDOWN_GRACE, futures::future::join_all(tasks))
+            .await
+            .is_err()
+        {
+            warn!(
+                "jobs: {:?} didn't finish in time",
+                self.running.read().await
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn at(t: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(t).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn schedules() {
+        let next = |schedule: &str, after: &str| {
+            schedule
+                .parse::<Schedule>()
+                .unwrap()
+                .next_after(at(after))
+                .unwrap()
+                .to_rfc3339()
+        };
+        assert_eq!(
+            next("*/15 * * * *", "2023-05-01T10:07:30Z"),
+            "2023-05-01T10:15:00+00:00"
+        );
+        assert_eq!(
+            next("@hourly", "2023-05-01T10:00:00Z"),
+            "2023-05-01T11:00:00+00:00"
+        );
+        // 2023-05-01 is a Monday.
+        assert_eq!(
+            next("0 9 * * 1", "2023-05-01T09:00:00Z"),
+            "2023-05-08T09:00:00+00:00"
+        );
+        assert_eq!(
+            next("30 2 29 2 *", "2023-03-01T00:00:00Z"),
+            "2024-02-29T02:30:00+00:00"
+        );
+        // Either the 1st or a Sunday.
+        assert_eq!(
+            next("0 0 1 * 7", "2023-05-01T12:00:00Z"),
+            "2023-05-07T00:00:00+00:00"
+        );
+        assert_eq!(
+            next("@every 90m", "2023-05-01T10:00:00Z"),
+            "2023-05-01T11:30:00+00:00"
+        );
+
+        for bad in [
+            "* * * *",
+            "60 * * * *",
+            "*/0 * * * *",
+            "0 0 31 2 *",
+            "@every",
+            "@every 5",
+        ] {
+            assert!(bad.parse::<Schedule>().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn missed_jobs_run_now() {
+        let schedule = Schedule::Every(Duration::from_secs(60 * 60));
+        let now = at("2023-05-01T10:00:00Z");
+        assert_eq!(JobState::default().next_run(&schedule, now), now);
+
+        let ran = JobState {
+            last_run: Some(at("2023-05-01T09:30:00Z")),
+            ..Default::default()
+        };
+        assert_eq!(ran.next_run(&schedule, now), at("2023-05-01T10:30:00Z"));
+        let missed = JobState {
+            last_run: Some(at("2023-04-01T00:00:00Z")),
+            ..Default::default()
+        };
+        assert_eq!(missed.next_run(&schedule, now), now);
+    }
+
+    #[tokio::test]
+    async fn runs_and_saves() -> Result<()> {
+        let storage = crate::storage::Storage::memory()?;
+        let legacy = Path::new("./var/no-such-jobs.json");
+        let scheduler = Arc::new(Scheduler::new(storage.jobs(), legacy).await?);
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = count.clone();
+        scheduler.add("count", "@hourly".parse()?, move || {
+            let counter = counter.clone();
+            async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }
+        });
+        scheduler.add("fail", "@hourly".parse()?, || async { Err(eyre!("nope")) });
+
+        scheduler.clone().start().await;
+        // Both have never run, so they run straight away and then wait an
+        // hour.
+        while scheduler.snapshot().await.iter().any(|s| s.state.runs == 0) {
+            tokio::task::yield_now().await;
+        }
+        scheduler.shutdown().await;
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        let saved = Scheduler::new(storage.jobs(), legacy).await?;
+        let count = saved.state("count").await;
+        assert_eq!(count.runs, 1);
+        assert!(count.last_success.is_some());
+        let fail = saved.state("fail").await;
+        assert_eq!(fail.last_error.as_deref(), Some("nope"));
+        assert_eq!(fail.failures, 1);
+        Ok(())
+    }
+}
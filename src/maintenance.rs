@@ -0,0 +1,190 @@
+//! Maintenance mode, for when the parts of the site that change while it
+//! runs are degraded, like during a database migration. Instead of 503-ing
+//! everything, pages are served from a [static export](crate::export) with a
+//! banner saying what's going on.
+//!
+//! Only HTML pages come from the export. Pages that aren't in it, feeds and
+//! the API are still served live, since they're made from posts that were
+//! loaded at startup. Anything that isn't a GET or HEAD would change state,
+//! so it gets a 503 and is asked to come back later, apart from the admin
+//! area and logging in, which are always live so maintenance mode can be
+//! turned off again.
+
+use crate::app::{config, State};
+use axum::{
+    extract::Extension,
+    http::{header, Method, Request, StatusCode},
+    middleware::Next,
+    response::{Html, IntoResponse, Response},
+};
+use lazy_static::lazy_static;
+use prometheus::{opts, register_int_counter_vec, IntCounterVec};
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+lazy_static! {
+    static ref RESPONSES: IntCounterVec = register_int_counter_vec!(
+        opts!(
+            "maintenance_responses",
+            "Number of requests handled in maintenance mode by how they were served"
+        ),
+        &["served"]
+    )
+    .unwrap();
+}
+
+/// Paths that are always served by the live site.
+const LIVE: &[&str] = &["/.within/", "/metrics", "/static/"];
+
+/// How long clients are asked to wait before trying a refused request again,
+/// in seconds.
+const RETRY_AFTER: &str = "300";
+
+/// Whether the site is in maintenance mode, and where the export is.
+pub struct Mode {
+    on: AtomicBool,
+    dir: PathBuf,
+    message: String,
+}
+
+impl Mode {
+    pub fn new(cfg: &config::Maintenance) -> Self {
+        let mode = Self {
+            on: AtomicBool::new(false),
+            dir: cfg.export_path.clone().into(),
+            message: cfg.message.clone(),
+        };
+        mode.set(cfg.enabled);
+        mode
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.on.load(Ordering::Relaxed)
+    }
+
+    pub fn set(&self, on: bool) {
+        if on && !self.dir.join("index.html").exists() {
+            warn!(
+                "maintenance: {} doesn't look like an export, pages will be served live",
+                self.dir.display()
+            );
+        }
+        self.on.store(on, Ordering::Relaxed);
+    }
+
+    /// Where the exported copy of the page at `path` would be. Paths that
+    /// could leave the export aren't looked for.
+    fn page_for(&self, path: &str) -> Option<PathBuf> {
+        let path = path.trim_matches('/');
+        if path.contains(['%', '\\']) || path.split('/').any(|seg| seg == ".." || seg == ".") {
+            return None;
+        }
+        Some(page_in(&self.dir, path))
+    }
+}
+
+fn page_in(dir: &Path, path: &str) -> PathBuf {
+    if path.is_empty() {
+        dir.join("index.html")
+    } else {
+        dir.join(path).join("index.html")
+    }
+}
+
+/// Puts `banner` at the top of the body of `page`.
+fn with_banner(page: &str, banner: &str) -> String {
+    let at = page
+        .find("<body")
+        .and_then(|start| page[start..].find('>').map(|end| start + end + 1))
+        .unwrap_or(0);
+    let mut result = String::with_capacity(page.len() + banner.len());
+    result.push_str(&page[..at]);
+    result.push_str(banner);
+    result.push_str(&page[at..]);
+    result
+}
+
+pub async fn middleware<B>(
+    Extension(state): Extension<Arc<State>>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let mode = &state.maintenance;
+    let path = req.uri().path().to_string();
+    if !mode.is_on() || LIVE.iter().any(|live| path.starts_with(live)) {
+        return next.run(req).await;
+    }
+
+    if req.method() != Method::GET && req.method() != Method::HEAD {
+        RESPONSES.with_label_values(&["refused"]).inc();
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(header::RETRY_AFTER, RETRY_AFTER)],
+            "The site is down for maintenance, please try again later.",
+        )
+            .into_response();
+    }
+
+    let page = match mode.page_for(&path) {
+        Some(file) => tokio::fs::read_to_string(&file).await.ok(),
+        None => None,
+    };
+    let Some(page) = page else {
+        RESPONSES.with_label_values(&["live"]).inc();
+        return next.run(req).await;
+    };
+
+    RESPONSES.with_label_values(&["export"]).inc();
+    let banner = xesite_templates::maintenance_banner(&mode.message).0;
+    (
+        // Don't let anything remember the maintenance copy.
+        [(header::CACHE_CONTROL, "no-store")],
+        Html(with_banner(&page, &banner)),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages() {
+        let mode = Mode {
+            on: AtomicBool::new(true),
+            dir: "./var/export".into(),
+            message: String::new(),
+        };
+        assert_eq!(
+            mode.page_for("/"),
+            Some(PathBuf::from("./var/export/index.html"))
+        );
+        assert_eq!(
+            mode.page_for("/blog/"),
+            Some(PathBuf::from("./var/export/blog/index.html"))
+        );
+        assert_eq!(
+            mode.page_for("/blog/2023/05/"),
+            Some(PathBuf::from("./var/export/blog/2023/05/index.html"))
+        );
+        assert_eq!(mode.page_for("/blog/../../etc/passwd"), None);
+        assert_eq!(mode.page_for("/blog/%2e%2e/secrets"), None);
+    }
+
+    #[test]
+    fn banners() {
+        assert_eq!(
+            with_banner(
+                r#"<html><body class="snow"><p>hi</p></body></html>"#,
+                "<b>!</b>"
+            ),
+            r#"<html><body class="snow"><b>!</b><p>hi</p></body></html>"#
+        );
+        assert_eq!(with_banner("<p>hi</p>", "<b>!</b>"), "<b>!</b><p>hi</p>");
+    }
+}
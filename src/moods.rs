@@ -0,0 +1,207 @@
+//! Which character stickers get used by which posts, and which are never
+//! used at all. It's made when the site starts so there's something to go
+//! off of when commissioning new sticker art.
+
+use crate::app::config::Character;
+use lazy_static::lazy_static;
+use maud::{html, Markup, Render};
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+lazy_static! {
+    /// Matches both `stickers/{name}/{mood}.png` in the static bucket and
+    /// `sticker/{name}/{mood}/{size}` on the resizing CDN.
+    static ref STICKER: Regex = Regex::new(r#"/stickers?/([a-z0-9_-]+)/([a-z0-9_-]+)[./]"#).unwrap();
+}
+
+/// One sticker and the posts it's in.
+#[derive(Clone, Debug, Default, Serialize, PartialEq)]
+pub struct MoodUsage {
+    pub character: String,
+    pub mood: String,
+    /// Links of the posts the sticker is in, sorted.
+    pub posts: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, PartialEq)]
+pub struct CharacterUsage {
+    pub name: String,
+    #[serde(rename = "stickerName")]
+    pub sticker_name: String,
+    /// The character's stickers that are used, most used first.
+    pub moods: Vec<MoodUsage>,
+    /// The character's stickers that no post uses.
+    pub unused: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, PartialEq)]
+pub struct Report {
+    pub characters: Vec<CharacterUsage>,
+    /// Stickers that posts use but aren't in the characters manifest, most
+    /// likely typos or art that hasn't been added yet.
+    pub unknown: Vec<MoodUsage>,
+}
+
+/// The `(character, mood)` pairs of every sticker in some HTML.
+pub fn stickers(html: &str) -> BTreeSet<(String, String)> {
+    STICKER
+        .captures_iter(html)
+        .map(|cap| (cap[1].to_string(), cap[2].to_string()))
+        .collect()
+}
+
+impl Report {
+    /// Looks for stickers in posts, which are `(link, body_html)`.
+    pub fn compute<'a>(
+        characters: &[Character],
+        posts: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Self {
+        let mut used: BTreeMap<(String, String), Vec<String>> = BTreeMap::new();
+        for (link, html) in posts {
+            for sticker in stickers(html) {
+                used.entry(sticker).or_default().push(link.to_string());
+            }
+        }
+        for posts in used.values_mut() {
+            posts.sort();
+            posts.dedup();
+        }
+
+        let characters = characters
+            .iter()
+            .map(|ch| {
+                let mut moods: Vec<MoodUsage> = vec![];
+                let mut unused = vec![];
+                for mood in &ch.stickers {
+                    match used.remove(&(ch.sticker_name.clone(), mood.clone())) {
+                        Some(posts) => moods.push(MoodUsage {
+                            character: ch.sticker_name.clone(),
+                            mood: mood.clone(),
+                            posts,
+                        }),
+                        None => unused.push(mood.clone()),
+                    }
+                }
+                moods.sort_by(|a, b| b.posts.len().cmp(&a.posts.len()));
+                CharacterUsage {
+                    name: ch.name.clone(),
+                    sticker_name: ch.sticker_name.clone(),
+                    moods,
+                    unused,
+                }
+            })
+            .collect();
+
+        // Everything that's left isn't in the manifest.
+        let unknown = used
+            .into_iter()
+            .map(|((character, mood), posts)| MoodUsage {
+                character,
+                mood,
+                posts,
+            })
+            .collect();
+
+        Self {
+            characters,
+            unknown,
+        }
+    }
+}
+
+impl Render for MoodUsage {
+    fn render(&self) -> Markup {
+        html! {
+            details {
+                summary {
+                    code {(self.character) "/" (self.mood)}
+                    " (" (self.posts.len()) ")"
+                }
+                ul {
+                    @for post in &self.posts {
+                        li { a href={"/" (post)} {(post)} }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Render for Report {
+    fn render(&self) -> Markup {
+        html! {
+            .mood-report {
+                @for ch in &self.characters {
+                    h3 #(ch.sticker_name) {(ch.name)}
+                    @for mood in &ch.moods {
+                        (mood)
+                    }
+                    @if !ch.unused.is_empty() {
+                        p {
+                            "Never used: "
+                            @for (i, mood) in ch.unused.iter().enumerate() {
+                                @if i != 0 { ", " }
+                                code {(mood)}
+                            }
+                        }
+                    }
+                }
+
+                @if !self.unknown.is_empty() {
+                    h3 {"Not in the characters manifest"}
+                    @for mood in &self.unknown {
+                        (mood)
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_stickers() {
+        let mara = Character {
+            name: "Mara".into(),
+            sticker_name: "mara".into(),
+            stickers: vec!["hacker".into(), "hmm".into(), "wat".into()],
+            ..Default::default()
+        };
+        let posts = [
+            (
+                "blog/a",
+                r#"<img src="https://cdn.xeiaso.net/file/christine-static/stickers/mara/hacker.png">
+<img src="https://cdn.xeiaso.net/sticker/mara/hacker/64">"#,
+            ),
+            (
+                "blog/b",
+                r#"<img src="https://cdn.xeiaso.net/sticker/mara/hmm/64">
+<source srcset="https://cdn.xeiaso.net/file/christine-static/stickers/mara/hacker.avif">
+<img src="https://cdn.xeiaso.net/sticker/mara/sad/64">"#,
+            ),
+        ];
+        let report = Report::compute(&[mara], posts);
+
+        let mara = &report.characters[0];
+        assert_eq!(
+            mara.moods
+                .iter()
+                .map(|m| (m.mood.as_str(), m.posts.len()))
+                .collect::<Vec<_>>(),
+            vec![("hacker", 2), ("hmm", 1)]
+        );
+        assert_eq!(mara.unused, vec!["wat"]);
+        assert_eq!(
+            report.unknown,
+            vec![MoodUsage {
+                character: "mara".into(),
+                mood: "sad".into(),
+                posts: vec!["blog/b".into()],
+            }]
+        );
+    }
+}
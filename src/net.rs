@@ -0,0 +1,107 @@
+//! Guards for requests to URLs that someone else picked, like the keys
+//! activities are signed with or the website someone logs in as. Anyone can
+//! make the site fetch those, so they're kept from reaching this machine or
+//! the private network it's on.
+
+use color_eyre::eyre::{bail, Result};
+use std::net::IpAddr;
+use url::{Host, Url};
+
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            // 100.64.0.0/10 is carrier-grade NAT, which is private too.
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || (a == 100 && b & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public_ip(ip.into());
+            }
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || first & 0xfe00 == 0xfc00
+                || first & 0xffc0 == 0xfe80)
+        }
+    }
+}
+
+/// Whether `url` may be fetched: it has to be https and not on this machine
+/// or a private network.
+pub fn is_public(url: &Url) -> bool {
+    if url.scheme() != "https" {
+        return false;
+    }
+    match url.host() {
+        Some(Host::Domain(host)) => {
+            let host = host.trim_end_matches('.').to_ascii_lowercase();
+            host != "localhost"
+                && ![".localhost", ".local", ".internal"]
+                    .iter()
+                    .any(|suffix| host.ends_with(suffix))
+        }
+        Some(Host::Ipv4(ip)) => is_public_ip(ip.into()),
+        Some(Host::Ipv6(ip)) => is_public_ip(ip.into()),
+        None => false,
+    }
+}
+
+/// Checks that `url` [is_public] and, if its host is a name, that the name
+/// doesn't point at a private address either.
+pub async fn check(url: &Url) -> Result<()> {
+    if !is_public(url) {
+        bail!("won't fetch {url}");
+    }
+    if let Some(Host::Domain(host)) = url.host() {
+        let port = url.port_or_known_default().unwrap_or(443);
+        for addr in tokio::net::lookup_host((host, port)).await? {
+            if !is_public_ip(addr.ip()) {
+                bail!("won't fetch {url}, {host} is on a private network");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Follows up to 5 redirects, and only to URLs that [is_public].
+pub fn redirects() -> reqwest::redirect::Policy {
+    reqwest::redirect::Policy::custom(|attempt| {
+        if attempt.previous().len() < 5 && is_public(attempt.url()) {
+            attempt.follow()
+        } else {
+            attempt.stop()
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn public_urls() {
+        let public = |url: &str| is_public(&Url::parse(url).unwrap());
+        assert!(public("https://pony.social/users/mara"));
+        assert!(public("https://1.1.1.1/actor"));
+        assert!(!public("http://pony.social/users/mara"));
+        assert!(!public("https://localhost/actor"));
+        assert!(!public("https://db.internal/actor"));
+        assert!(!public("https://127.0.0.1/actor"));
+        assert!(!public("https://10.0.0.5/actor"));
+        assert!(!public("https://169.254.169.254/latest/meta-data"));
+        assert!(!public("https://100.100.1.1/actor"));
+        assert!(!public("https://[::1]/actor"));
+        assert!(!public("https://[fd00::1]/actor"));
+        assert!(!public("https://[::ffff:192.168.1.1]/actor"));
+        assert!(!public("file:///etc/passwd"));
+    }
+}
@@ -0,0 +1,135 @@
+//! The digital garden: a tree of permanent notes that link to each other with
+//! `[[wiki links]]` instead of being sorted by date like the blog.
+
+use crate::post::Post;
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+
+lazy_static! {
+    static ref WIKILINK: Regex = Regex::new(r"\[\[([^\[\]|]+)(?:\|([^\[\]]+))?\]\]").unwrap();
+}
+
+/// Turns the name of a note into the slug used in its URL.
+pub fn slugify(name: &str) -> String {
+    name.trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
+/// Replaces `[[note]]` and `[[note|label]]` with markdown links to the
+/// matching note and returns the slugs of every note that was linked to.
+/// Code blocks and inline code are left alone so that things like bash's
+/// `[[ -f foo ]]` don't get mangled.
+pub fn expand_wikilinks(input: &str) -> (String, Vec<String>) {
+    let mut links: Vec<String> = vec![];
+    let mut result = String::with_capacity(input.len());
+    let mut in_fence = false;
+
+    for line in input.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        }
+        if in_fence || trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            result.push_str(line);
+            continue;
+        }
+
+        let segments: Vec<String> = line
+            .split('`')
+            .enumerate()
+            .map(|(i, segment)| {
+                if i % 2 == 1 {
+                    return segment.to_string();
+                }
+                WIKILINK
+                    .replace_all(segment, |caps: &Captures| {
+                        let slug = slugify(&caps[1]);
+                        let label = caps.get(2).map_or(&caps[1], |m| m.as_str()).trim();
+                        if !links.contains(&slug) {
+                            links.push(slug.clone());
+                        }
+                        format!("[{label}](/notes/{slug})")
+                    })
+                    .to_string()
+            })
+            .collect();
+        result.push_str(&segments.join("`"));
+    }
+
+    (result, links)
+}
+
+pub struct Garden {
+    pub notes: Vec<Post>,
+    /// The slug of a note to the slugs of every note that links to it.
+    pub backlinks: HashMap<String, Vec<String>>,
+}
+
+impl Garden {
+    pub fn new(notes: Vec<Post>) -> Self {
+        let mut backlinks: HashMap<String, Vec<String>> = HashMap::new();
+        for note in &notes {
+            for link in &note.wikilinks {
+                backlinks
+                    .entry(link.clone())
+                    .or_default()
+                    .push(note.slug().to_string());
+            }
+        }
+
+        Self { notes, backlinks }
+    }
+
+    pub fn get(&self, slug: &str) -> Option<&Post> {
+        self.notes.iter().find(|note| note.slug() == slug)
+    }
+
+    pub fn backlinks(&self, slug: &str) -> Vec<&Post> {
+        self.backlinks
+            .get(slug)
+            .map(|slugs| slugs.iter().filter_map(|slug| self.get(slug)).collect())
+            .unwrap_or_default()
+    }
+
+    /// Every link between two notes that both exist, as (from, to) pairs.
+    pub fn edges(&self) -> Vec<(&Post, &Post)> {
+        self.notes
+            .iter()
+            .flat_map(|from| {
+                from.wikilinks
+                    .iter()
+                    .filter_map(move |to| self.get(to).map(|to| (from, to)))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wikilinks() {
+        let (md, links) = expand_wikilinks("See [[Nix Flakes]] and [[nixos|my NixOS notes]].\n");
+        assert_eq!(
+            md,
+            "See [Nix Flakes](/notes/nix-flakes) and [my NixOS notes](/notes/nixos).\n"
+        );
+        assert_eq!(links, vec!["nix-flakes", "nixos"]);
+    }
+
+    #[test]
+    fn wikilinks_skip_code() {
+        let input = "```bash\nif [[ -f foo ]]; then\n```\nuse `[[x]]` for [[y]]\n";
+        let (md, links) = expand_wikilinks(input);
+        assert_eq!(
+            md,
+            "```bash\nif [[ -f foo ]]; then\n```\nuse `[[x]]` for [y](/notes/y)\n"
+        );
+        assert_eq!(links, vec!["y"]);
+    }
+}
@@ -0,0 +1,128 @@
+//! An [oEmbed](https://oembed.com) provider, so other sites and fediverse
+//! software can show posts as cards. Post pages link to their embed with a
+//! `<link rel="alternate" type="application/json+oembed">` for discovery.
+//!
+//! Embeds are `rich`: a quote of the post's title and excerpt, with its
+//! [generated card](crate::og) as the thumbnail. The excerpt is cleaned so
+//! only basic formatting and links are left in it.
+
+use crate::{og, post::Post};
+use maud::{html, PreEscaped};
+use serde::Serialize;
+use url::Url;
+use xesite_templates::context::site;
+
+/// Where embeds are served.
+pub const PATH: &str = "/oembed";
+
+/// How big embeds are when the consumer doesn't say, in pixels.
+const WIDTH: u32 = 600;
+const HEIGHT: u32 = 400;
+
+/// An oEmbed response. Only the JSON format is supported.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct Embed {
+    pub version: &'static str,
+    pub r#type: &'static str,
+    pub title: String,
+    pub author_name: String,
+    pub author_url: String,
+    pub provider_name: String,
+    pub provider_url: String,
+    pub cache_age: u32,
+    pub thumbnail_url: String,
+    pub thumbnail_width: u32,
+    pub thumbnail_height: u32,
+    pub html: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Where the embed of `post` is, for its discovery link.
+pub fn discovery_url(post: &Post) -> String {
+    let query: String = url::form_urlencoded::Serializer::new(String::new())
+        .append_pair("url", &site().url(&post.link))
+        .append_pair("format", "json")
+        .finish();
+    site().url(&format!("{PATH}?{query}"))
+}
+
+/// The link of the post at `url`, if `url` is on this site.
+pub fn link_of(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let base = Url::parse(&site().url("/")).ok()?;
+    if url.host_str()? != base.host_str()? {
+        return None;
+    }
+    Some(url.path().trim_matches('/').to_string())
+}
+
+/// The embed of `post`, no bigger than `max_width` by `max_height`.
+pub fn embed(post: &Post, max_width: Option<u32>, max_height: Option<u32>) -> Embed {
+    let url = site().url(&post.link);
+    let excerpt = ammonia::Builder::default()
+        .url_schemes(["http", "https"].into())
+        .clean(&post.excerpt)
+        .to_string();
+    let html = html! {
+        blockquote.xesite-embed cite=(url) {
+            p { strong { a href=(url) { (post.front_matter.title) } } }
+            (PreEscaped(excerpt))
+            p { "— " a href=(site().url("/")) { "Xe Iaso" } ", " (post.detri()) }
+        }
+    };
+    let (thumbnail_width, thumbnail_height) = og::CARD_SIZE;
+
+    Embed {
+        version: "1.0",
+        r#type: "rich",
+        title: post.front_matter.title.clone(),
+        author_name: post
+            .front_matter
+            .author
+            .clone()
+            .unwrap_or_else(|| "Xe Iaso".to_string()),
+        author_url: site().url("/"),
+        provider_name: site().feed_title.clone(),
+        provider_url: site().url("/"),
+        cache_age: 3600,
+        thumbnail_url: site().url(&og::card_path(post)),
+        thumbnail_width,
+        thumbnail_height,
+        html: html.0,
+        width: max_width.map_or(WIDTH, |w| w.min(WIDTH)),
+        height: max_height.map_or(HEIGHT, |h| h.min(HEIGHT)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn embeds() -> color_eyre::eyre::Result<()> {
+        let posts = crate::post::load("blog").await?;
+        let post = &posts[0];
+
+        assert_eq!(link_of(&site().url(&post.link)), Some(post.link.clone()));
+        assert_eq!(
+            link_of(&format!("{}/?utm_source=x#top", site().url(&post.link))),
+            Some(post.link.clone())
+        );
+        assert_eq!(link_of("https://example.com/blog/foo"), None);
+        assert_eq!(link_of("not a url"), None);
+        assert!(discovery_url(post).contains("/oembed?url=http"));
+
+        let embed = embed(post, Some(400), None);
+        assert_eq!(embed.r#type, "rich");
+        assert_eq!((embed.width, embed.height), (400, HEIGHT));
+        assert!(embed
+            .thumbnail_url
+            .ends_with(&format!("/og/{}.png", post.link)));
+        assert!(embed
+            .html
+            .starts_with(r#"<blockquote class="xesite-embed""#));
+        assert!(!embed.html.contains("<script"));
+        Ok(())
+    }
+}
@@ -0,0 +1,185 @@
+//! Picks the image that link previews show for a post. Posts with a hero
+//! use it. Otherwise the front matter image is used, then the
+//! first image in the post, and as a last resort a card is drawn with the
+//! stickers of the characters in the post, so shares always have a picture.
+//! Every post has a card, since they're also the thumbnails of
+//! [embeds](crate::oembed).
+
+use crate::post::Post;
+use color_eyre::eyre::{eyre, Result};
+use image::{imageops, DynamicImage, ImageOutputFormat, Rgba, RgbaImage};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::{collections::HashMap, io::Cursor, sync::Mutex};
+use xesite_templates::context::site;
+use xesite_types::site::parse_color;
+
+/// The size Facebook, Mastodon and friends want preview images to be.
+pub const CARD_SIZE: (u32, u32) = (1200, 630);
+
+/// At most this many characters are put on a card.
+const MAX_STICKERS: usize = 3;
+
+/// Used when a post has no stickers in it.
+const AVATAR: &str = "./static/img/avatar.png";
+
+lazy_static! {
+    static ref IMG: Regex = Regex::new(r#"<img[^>]*\ssrc="([^"]+)""#).unwrap();
+    static ref HERO: Regex =
+        Regex::new(r#"(?s)<figure class="hero"[^>]*>.*?<img[^>]*\ssrc="([^"]+)""#).unwrap();
+    static ref STICKER: Regex = Regex::new(r#"stickers/([a-z0-9_-]+)/([a-z0-9_-]+)\.png"#).unwrap();
+    static ref CARDS: Mutex<HashMap<String, Vec<u8>>> = Mutex::new(HashMap::new());
+    static ref CLIENT: reqwest::Client = reqwest::Client::builder()
+        .user_agent(crate::APPLICATION_NAME)
+        .build()
+        .unwrap();
+}
+
+/// Where a post's generated card is served.
+pub fn card_path(post: &Post) -> String {
+    format!("/og/{}.png", post.link)
+}
+
+/// The link preview image of a post.
+pub fn image_for(post: &Post) -> String {
+    if let Some(hero) = HERO.captures(&post.body_html) {
+        return hero[1].to_string();
+    }
+    if let Some(image) = &post.front_matter.image {
+        return image.clone();
+    }
+    if let Some(img) = IMG.captures(&post.body_html) {
+        return img[1].to_string();
+    }
+    site().url(&card_path(post))
+}
+
+/// Whether [image_for] falls back to a generated card for this post.
+pub fn needs_card(post: &Post) -> bool {
+    image_for(post) == site().url(&card_path(post))
+}
+
+/// The stickers in a post as `(character, mood)`, one per character.
+pub fn stickers(html: &str) -> Vec<(String, String)> {
+    let mut result: Vec<(String, String)> = vec![];
+    for cap in STICKER.captures_iter(html) {
+        if result.len() == MAX_STICKERS {
+            break;
+        }
+        if !result.iter().any(|(name, _)| name == &cap[1]) {
+            result.push((cap[1].to_string(), cap[2].to_string()));
+        }
+    }
+    result
+}
+
+pub(crate) async fn fetch_sticker(name: &str, mood: &str) -> Result<DynamicImage> {
+    let url = xesite_templates::context::cdn(&format!("stickers/{name}/{mood}.png"));
+    let resp = CLIENT.get(&url).send().await?.error_for_status()?;
+    Ok(image::load_from_memory(&resp.bytes().await?)?)
+}
+
+/// Draws the stickers side by side on the theme colour.
+fn render_card(stickers: &[DynamicImage], background: &str) -> Result<Vec<u8>> {
+    let [r, g, b] =
+        parse_color(background).ok_or_else(|| eyre!("{background:?} is not a colour"))?;
+    let (width, height) = CARD_SIZE;
+    let mut canvas = RgbaImage::from_pixel(width, height, Rgba([r, g, b, 255]));
+
+    if !stickers.is_empty() {
+        let slot = width / stickers.len() as u32;
+        let fit = slot.min(height) * 4 / 5;
+        for (i, sticker) in stickers.iter().enumerate() {
+            let sticker = sticker
+                .resize(fit, fit, imageops::FilterType::Lanczos3)
+                .to_rgba8();
+            let x = slot * i as u32 + (slot - sticker.width()) / 2;
+            let y = (height - sticker.height()) / 2;
+            imageops::overlay(&mut canvas, &sticker, x as i64, y as i64);
+        }
+    }
+
+    let mut buf = Vec::new();
+    DynamicImage::ImageRgba8(canvas)
+        .write_to(&mut Cursor::new(&mut buf), ImageOutputFormat::Png)?;
+    Ok(buf)
+}
+
+/// The generated card for a post as a PNG. Cards are only drawn once per
+/// post.
+pub async fn card(post: &Post) -> Result<Vec<u8>> {
+    if let Some(card) = CARDS.lock().unwrap().get(&post.link) {
+        return Ok(card.clone());
+    }
+
+    let mut images = vec![];
+    for (name, mood) in stickers(&post.body_html) {
+        match fetch_sticker(&name, &mood).await {
+            Ok(image) => images.push(image),
+            Err(why) => warn!("can't fetch sticker {name}/{mood} for {}: {why}", post.link),
+        }
+    }
+    if images.is_empty() {
+        images.push(image::open(AVATAR)?);
+    }
+
+    let card = render_card(&images, &site().branding.background_color)?;
+    CARDS
+        .lock()
+        .unwrap()
+        .insert(post.link.clone(), card.clone());
+    Ok(card)
+}
+
+/// How many cards have been drawn since the last [purge].
+pub fn cached() -> usize {
+    CARDS.lock().unwrap().len()
+}
+
+/// Forgets the card for the post at `link`, if it was drawn.
+pub fn forget(link: &str) -> bool {
+    CARDS.lock().unwrap().remove(link).is_some()
+}
+
+/// Forgets every card, so they're drawn again the next time they're asked
+/// for. Returns how many there were.
+pub fn purge() -> usize {
+    let mut cards = CARDS.lock().unwrap();
+    let n = cards.len();
+    cards.clear();
+    n
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_sticker_per_character() {
+        let html = r#"
+<img src="https://cdn.xeiaso.net/file/christine-static/stickers/mara/hacker.png">
+<img src="https://cdn.xeiaso.net/file/christine-static/stickers/cadey/enby.png">
+<img src="https://cdn.xeiaso.net/file/christine-static/stickers/mara/happy.png">
+"#;
+        assert_eq!(
+            stickers(html),
+            vec![
+                ("mara".to_string(), "hacker".to_string()),
+                ("cadey".to_string(), "enby".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn card_is_centered() -> Result<()> {
+        let sticker =
+            DynamicImage::ImageRgba8(RgbaImage::from_pixel(64, 64, Rgba([255, 0, 0, 255])));
+        let png = render_card(&[sticker], "#1d2021")?;
+        let card = image::load_from_memory(&png)?.to_rgba8();
+
+        assert_eq!(card.dimensions(), CARD_SIZE);
+        assert_eq!(card.get_pixel(0, 0), &Rgba([0x1d, 0x20, 0x21, 255]));
+        assert_eq!(card.get_pixel(600, 315), &Rgba([255, 0, 0, 255]));
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+//! Resurfaces posts that were published on today's date in earlier years,
+//! on the home page and, if it's turned on, on the fediverse once a day.
+
+use crate::{app::config::OnThisDay, post::Post};
+use chrono::prelude::*;
+use color_eyre::eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+use std::env;
+use tokio::fs;
+use xesite_templates::context::site;
+
+/// Listed posts from today's month and day in earlier years, newest first.
+pub fn posts(posts: &[Post], today: NaiveDate) -> Vec<&Post> {
+    let mut result: Vec<&Post> = posts
+        .iter()
+        .filter(|p| {
+            let date = p.date.date_naive();
+            p.listed()
+                && date.year() < today.year()
+                && date.month() == today.month()
+                && date.day() == today.day()
+        })
+        .collect();
+    result.sort_by(|a, b| b.date.cmp(&a.date));
+    result
+}
+
+/// What gets tooted for a post.
+pub fn toot_text(post: &Post, today: NaiveDate) -> String {
+    let years = today.year() - post.date.year();
+    format!(
+        "On this day {years} {} ago: {}\n\n{}",
+        if years == 1 { "year" } else { "years" },
+        post.front_matter.title,
+        site().url(&post.link)
+    )
+}
+
+/// The last day that was tooted about, so restarts don't toot twice.
+#[derive(Default, Deserialize, Serialize)]
+struct Progress {
+    last_tooted: Option<NaiveDate>,
+}
+
+/// The server the site's Mastodon account is on.
+fn instance() -> Result<String> {
+    let url = url::Url::parse(&site().mastodon.url)?;
+    Ok(url.origin().ascii_serialization())
+}
+
+async fn toot(cli: &reqwest::Client, token: &str, text: &str) -> Result<()> {
+    cli.post(format!("{}/api/v1/statuses", instance()?))
+        .bearer_auth(token)
+        .form(&[("status", text), ("visibility", "public")])
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// Toots today's posts if that hasn't happened yet.
+#[instrument(skip(blog, cfg), err)]
+pub async fn announce(blog: &[Post], cfg: &OnThisDay) -> Result<()> {
+    let today = Utc::now().date_naive();
+    let mut progress: Progress = match fs::read(&cfg.state_path).await {
+        Ok(data) => serde_json::from_slice(&data)?,
+        Err(_) => Progress::default(),
+    };
+    if progress.last_tooted == Some(today) {
+        return Ok(());
+    }
+
+    let posts = posts(blog, today);
+    if !posts.is_empty() {
+        let token = env::var("MASTODON_TOKEN")
+            .map_err(|_| eyre!("MASTODON_TOKEN is needed to toot posts from this day"))?;
+        let cli = reqwest::Client::builder()
+            .user_agent(crate::APPLICATION_NAME)
+            .build()?;
+        for post in posts {
+            toot(&cli, &token, &toot_text(post, today)).await?;
+            info!("tooted {} for {today}", post.link);
+        }
+    }
+
+    progress.last_tooted = Some(today);
+    if let Some(parent) = std::path::Path::new(&cfg.state_path).parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    fs::write(&cfg.state_path, serde_json::to_vec(&progress)?).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn finds_anniversaries() -> Result<()> {
+        let blog = crate::post::load("blog").await?;
+        let first = blog.iter().filter(|p| p.listed()).last().unwrap();
+        let today = first
+            .date
+            .date_naive()
+            .with_year(first.date.year() + 3)
+            .unwrap();
+
+        let found = posts(&blog, today);
+        assert!(found.iter().any(|p| p.link == first.link));
+        assert!(found
+            .iter()
+            .all(|p| p.date.month() == today.month() && p.date.day() == today.day()));
+        assert!(toot_text(first, today).starts_with("On this day 3 years ago: "));
+        Ok(())
+    }
+}
@@ -0,0 +1,155 @@
+//! Support for serving the site over a Tor onion service. Clearnet pages
+//! point Tor Browser at the onion address, and pages served over the onion
+//! address don't load anything from the CDN directly, the CDN is proxied
+//! through the site instead.
+
+use axum::{
+    body::{self, Full},
+    extract::Path,
+    http::{header, HeaderValue, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use lazy_static::lazy_static;
+use xesite_templates::context::site;
+
+/// Where the CDN is proxied on the onion service.
+pub const CDN_PROXY: &str = "/cdn";
+
+lazy_static! {
+    static ref CLIENT: reqwest::Client = reqwest::Client::builder()
+        .user_agent(crate::APPLICATION_NAME)
+        .build()
+        .unwrap();
+}
+
+fn is_onion<B>(req: &Request<B>) -> bool {
+    req.headers()
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .map(|host| host.split(':').next().unwrap_or(host).ends_with(".onion"))
+        .unwrap_or(false)
+}
+
+/// Whether a response is text that could mention the CDN.
+fn is_text(resp: &Response) -> bool {
+    resp.headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|ct| ct.to_str().ok())
+        .map(|ct| {
+            ct.starts_with("text/")
+                || ct.starts_with("application/json")
+                || ct.starts_with("application/javascript")
+                || ct.contains("xml")
+        })
+        .unwrap_or(false)
+}
+
+/// Points every CDN URL in `body` at the proxy.
+pub fn rewrite_cdn(body: &str, cdn_base: &str) -> String {
+    body.replace(cdn_base, CDN_PROXY)
+}
+
+pub async fn middleware<B>(req: Request<B>, next: Next<B>) -> Response {
+    let Some(onion) = &site().onion else {
+        return next.run(req).await;
+    };
+
+    if !is_onion(&req) {
+        let path = req
+            .uri()
+            .path_and_query()
+            .map(|pq| pq.as_str().to_string())
+            .unwrap_or("/".into());
+        let mut resp = next.run(req).await;
+        let headers = resp.headers_mut();
+        if let Ok(location) = HeaderValue::from_str(&onion.url(&path)) {
+            headers.insert("Onion-Location", location);
+        }
+        if onion.alt_svc {
+            if let Ok(alt_svc) = HeaderValue::from_str(&format!(
+                "h2=\"{}:443\"; ma=86400; persist=1",
+                onion.address
+            )) {
+                headers.insert(header::ALT_SVC, alt_svc);
+            }
+        }
+        return resp;
+    }
+
+    let resp = next.run(req).await;
+    if !is_text(&resp) {
+        return resp;
+    }
+    let (mut parts, body) = resp.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(why) => {
+            error!("can't read response body to rewrite CDN links: {why}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let body = match std::str::from_utf8(&body) {
+        Ok(text) => rewrite_cdn(text, site().cdn_base()).into_bytes(),
+        Err(_) => body.to_vec(),
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, body::boxed(Full::from(body)))
+}
+
+/// Fetches a file from the CDN for readers on the onion service.
+#[instrument]
+pub async fn cdn_proxy(Path(path): Path<String>) -> Response {
+    let bucket = format!("file/{}/", site().cdn_bucket);
+    if site().onion.is_none() || !path.starts_with(&bucket) || path.contains("..") {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    let url = format!("{}/{path}", site().cdn_base());
+    let resp = match CLIENT.get(&url).send().await {
+        Ok(resp) => resp,
+        Err(why) => {
+            error!("can't fetch {url}: {why}");
+            return StatusCode::BAD_GATEWAY.into_response();
+        }
+    };
+    let status = StatusCode::from_u16(resp.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+    let content_type = resp.headers().get(reqwest::header::CONTENT_TYPE).cloned();
+    match resp.bytes().await {
+        Ok(bytes) => {
+            let mut resp = (status, bytes).into_response();
+            if let Some(ct) =
+                content_type.and_then(|ct| HeaderValue::from_bytes(ct.as_bytes()).ok())
+            {
+                resp.headers_mut().insert(header::CONTENT_TYPE, ct);
+            }
+            resp
+        }
+        Err(why) => {
+            error!("can't read {url}: {why}");
+            StatusCode::BAD_GATEWAY.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrites_cdn_links() {
+        let html = r#"<img src="https://cdn.xeiaso.net/file/christine-static/hero/foo.avif">"#;
+        assert_eq!(
+            rewrite_cdn(html, "https://cdn.xeiaso.net"),
+            r#"<img src="/cdn/file/christine-static/hero/foo.avif">"#
+        );
+    }
+
+    #[test]
+    fn onion_hosts() {
+        let req = |host: &str| Request::builder().header("Host", host).body(()).unwrap();
+        assert!(is_onion(&req("xxxx.onion")));
+        assert!(is_onion(&req("xxxx.onion:80")));
+        assert!(!is_onion(&req("xeiaso.net")));
+    }
+}
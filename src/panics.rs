@@ -0,0 +1,114 @@
+//! Keeps a panic while rendering one page from taking the connection down
+//! with it. [layer] catches the panic, logs it in the request's span with
+//! the backtrace that [install] saved, counts it and sends a 500 page
+//! instead.
+
+use crate::tmpl;
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use lazy_static::lazy_static;
+use prometheus::{opts, register_int_counter, IntCounter};
+use std::{any::Any, backtrace::Backtrace, cell::RefCell, panic};
+use tower_http::catch_panic::CatchPanicLayer;
+
+lazy_static! {
+    static ref PANICS: IntCounter = register_int_counter!(opts!(
+        "request_panics",
+        "Number of requests that panicked while being handled"
+    ))
+    .unwrap();
+}
+
+thread_local! {
+    /// Where the panic hook leaves the backtrace for [respond]. The panic is
+    /// caught on the thread it happened on before anything else runs there.
+    static BACKTRACE: RefCell<Option<Backtrace>> = RefCell::new(None);
+}
+
+/// Saves the backtrace of every panic for [layer] to log, then does
+/// whatever was done with panics before. By the time a panic is caught the
+/// stack has unwound, so this is the only place the backtrace can come
+/// from.
+pub fn install() {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        BACKTRACE.with(|bt| *bt.borrow_mut() = Some(Backtrace::force_capture()));
+        previous(info);
+    }));
+}
+
+/// What was passed to `panic!`, if it was a string.
+fn message(payload: &(dyn Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(msg) => msg,
+        None => payload
+            .downcast_ref::<String>()
+            .map_or("(not a string)", String::as_str),
+    }
+}
+
+fn respond(payload: Box<dyn Any + Send + 'static>) -> Response {
+    PANICS.inc();
+    let backtrace = BACKTRACE
+        .with(|bt| bt.borrow_mut().take())
+        .map(|bt| bt.to_string())
+        .unwrap_or_default();
+    error!("panicked: {}\n{backtrace}", message(payload.as_ref()));
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        [(header::CACHE_CONTROL, "no-store")],
+        tmpl::panicked(),
+    )
+        .into_response()
+}
+
+/// Turns panics into 500 pages. It goes inside the trace layer so the
+/// request's span is still open when the panic is logged.
+pub fn layer() -> CatchPanicLayer<fn(Box<dyn Any + Send + 'static>) -> Response> {
+    CatchPanicLayer::custom(respond as fn(_) -> _)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request, routing::get, Router};
+    use color_eyre::eyre::Result;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn panics_get_a_page() -> Result<()> {
+        async fn broken() -> &'static str {
+            panic!("oh no");
+        }
+        let app = Router::new()
+            .route("/broken", get(broken))
+            .route("/fine", get(|| async { "ok" }))
+            .layer(layer());
+
+        let before = PANICS.get();
+        let resp = app
+            .clone()
+            .oneshot(Request::builder().uri("/broken").body(Body::empty())?)
+            .await?;
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(resp.headers()[header::CACHE_CONTROL], "no-store");
+        let body = hyper::body::to_bytes(resp.into_body()).await?;
+        assert!(String::from_utf8_lossy(&body).contains("Cadey"));
+        assert_eq!(PANICS.get(), before + 1);
+
+        let resp = app
+            .oneshot(Request::builder().uri("/fine").body(Body::empty())?)
+            .await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+        Ok(())
+    }
+
+    #[test]
+    fn messages() {
+        assert_eq!(message(&"static"), "static");
+        assert_eq!(message(&String::from("formatted")), "formatted");
+        assert_eq!(message(&42), "(not a string)");
+    }
+}
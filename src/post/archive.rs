@@ -0,0 +1,88 @@
+//! Groups posts by the year and month they were published in, for the
+//! `/blog/2023/` and `/blog/2023/07/` archive pages.
+
+use super::Post;
+use chrono::prelude::*;
+use std::collections::BTreeMap;
+
+/// Listed posts that are already out, by year and then month. Posts in a
+/// month are newest first.
+pub fn by_month(posts: &[Post]) -> BTreeMap<i32, BTreeMap<u32, Vec<&Post>>> {
+    let today = Utc::now().date_naive();
+    let mut result: BTreeMap<i32, BTreeMap<u32, Vec<&Post>>> = BTreeMap::new();
+    for post in posts {
+        if !post.listed() || post.date.date_naive() > today {
+            continue;
+        }
+        result
+            .entry(post.date.year())
+            .or_default()
+            .entry(post.date.month())
+            .or_default()
+            .push(post);
+    }
+    for months in result.values_mut() {
+        for posts in months.values_mut() {
+            posts.sort_by(|a, b| b.date.cmp(&a.date));
+        }
+    }
+    result
+}
+
+/// The number of posts in every year.
+pub fn year_counts(archive: &BTreeMap<i32, BTreeMap<u32, Vec<&Post>>>) -> Vec<(i32, usize)> {
+    archive
+        .iter()
+        .rev()
+        .map(|(year, months)| (*year, months.values().map(Vec::len).sum()))
+        .collect()
+}
+
+pub fn month_name(month: u32) -> &'static str {
+    const NAMES: [&str; 12] = [
+        "January",
+        "February",
+        "March",
+        "April",
+        "May",
+        "June",
+        "July",
+        "August",
+        "September",
+        "October",
+        "November",
+        "December",
+    ];
+    NAMES.get(month as usize - 1).copied().unwrap_or("Smarch")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use color_eyre::eyre::Result;
+
+    #[tokio::test]
+    async fn blog_archive() -> Result<()> {
+        let posts = crate::post::load("blog").await?;
+        let archive = by_month(&posts);
+
+        let counted: usize = year_counts(&archive).iter().map(|(_, count)| count).sum();
+        let today = Utc::now().date_naive();
+        let listed = posts
+            .iter()
+            .filter(|p| p.listed() && p.date.date_naive() <= today)
+            .count();
+        assert_eq!(counted, listed);
+
+        for (year, months) in &archive {
+            for (month, posts) in months {
+                assert!(posts.windows(2).all(|w| w[0].date >= w[1].date));
+                assert!(posts
+                    .iter()
+                    .all(|p| p.date.year() == *year && p.date.month() == *month));
+            }
+        }
+        assert_eq!(month_name(7), "July");
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+//! Short previews of posts for index pages. A post is cut at a `<!--more-->`
+//! comment if it has one, and after a number of words otherwise. The cut is
+//! made in the rendered HTML, so every element that is open at the cut is
+//! closed again and the excerpt is always well-formed.
+
+use scraper::{ElementRef, Html, Node};
+use std::fmt::Write;
+
+/// Put this in a post to choose where its excerpt ends.
+pub const MORE: &str = "more";
+
+/// Elements that don't make sense out of context or that do things, so
+/// they're left out of excerpts. Interactive components are `div`s with
+/// `data-xeact-component` and are left out too.
+const SKIP: &[&str] = &[
+    "script", "style", "noscript", "iframe", "video", "audio", "object", "embed", "form", "button",
+    "input", "select", "textarea", "meta", "link", "figure", "picture", "img", "table", "pre",
+    "sup",
+];
+
+const VOID: &[&str] = &["br", "hr", "wbr"];
+
+/// Attributes that would be duplicated or broken when several excerpts are
+/// on one page. Event handlers are dropped too.
+const DROP_ATTRS: &[&str] = &["id", "name", "for"];
+
+#[derive(Default)]
+struct Writer {
+    out: String,
+    words: usize,
+    limit: usize,
+    done: bool,
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+impl Writer {
+    fn text(&mut self, text: &str) {
+        let cut = text
+            .split_whitespace()
+            .nth(self.limit - self.words)
+            .map(|word| word.as_ptr() as usize - text.as_ptr() as usize);
+        match cut {
+            Some(end) => {
+                self.out.push_str(&escape(text[..end].trim_end()));
+                self.out.push('…');
+                self.done = true;
+            }
+            None => {
+                self.words += text.split_whitespace().count();
+                self.out.push_str(&escape(text));
+            }
+        }
+    }
+
+    fn walk(&mut self, element: ElementRef) {
+        for child in element.children() {
+            if self.done {
+                return;
+            }
+            match child.value() {
+                Node::Text(text) => self.text(text),
+                Node::Comment(comment) if comment.trim() == MORE => {
+                    self.done = true;
+                    return;
+                }
+                Node::Element(el) => {
+                    let Some(child) = ElementRef::wrap(child) else {
+                        continue;
+                    };
+                    let name = el.name();
+                    if SKIP.contains(&name) || el.attr("data-xeact-component").is_some() {
+                        continue;
+                    }
+                    let _ = write!(self.out, "<{name}");
+                    for (attr, value) in el.attrs() {
+                        if DROP_ATTRS.contains(&attr) || attr.starts_with("on") {
+                            continue;
+                        }
+                        let _ = write!(
+                            self.out,
+                            " {attr}=\"{}\"",
+                            escape(value).replace('"', "&quot;")
+                        );
+                    }
+                    self.out.push('>');
+                    if VOID.contains(&name) {
+                        continue;
+                    }
+                    self.walk(child);
+                    let _ = write!(self.out, "</{name}>");
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// The excerpt of a rendered post, at most `words` words long unless it has
+/// a `<!--more-->` marker.
+pub fn excerpt(html: &str, words: usize) -> String {
+    let has_marker = html.contains(&format!("<!--{MORE}-->"));
+    let doc = Html::parse_fragment(html);
+    let mut writer = Writer {
+        limit: if has_marker { usize::MAX } else { words },
+        ..Default::default()
+    };
+    writer.walk(doc.root_element());
+    writer.out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cuts_at_word_count() {
+        assert_eq!(
+            excerpt("<p>one two <em>three four</em> five</p><p>six</p>", 3),
+            "<p>one two <em>three…</em></p>"
+        );
+    }
+
+    #[test]
+    fn cuts_at_marker() {
+        assert_eq!(
+            excerpt("<p>one two three</p><!--more--><p>four</p>", 1),
+            "<p>one two three</p>"
+        );
+    }
+
+    #[test]
+    fn leaves_out_components() {
+        let html = r#"<h2 id="intro">Intro</h2><script>alert(1)</script><div data-xeact-component="Video"><p>js</p></div><p>a &lt;b&gt;</p>"#;
+        assert_eq!(excerpt(html, 10), "<h2>Intro</h2><p>a &lt;b&gt;</p>");
+    }
+
+    #[test]
+    fn short_posts_are_whole() {
+        assert_eq!(excerpt("<p>hi<br>there</p>", 10), "<p>hi<br>there</p>");
+    }
+}
@@ -0,0 +1,102 @@
+//! Stylesheets and scripts that only one post needs, from its `extra_css`
+//! and `extra_scripts`. They have to be files in `static`, so they're
+//! fingerprinted with a hash of their contents instead of the build's
+//! cachebuster, and get an `integrity` attribute with the same hash so a
+//! `Content-Security-Policy` can allow them by hash.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use xesite_templates::context::{AssetKind, Include};
+use xesite_types::Frontmatter;
+
+const PREFIX: &str = "/static/";
+
+/// How many hex digits of the hash go in the URL.
+const FINGERPRINT_LEN: usize = 16;
+
+fn include(static_dir: &Path, path: &str, kind: AssetKind) -> Result<Include> {
+    let Some(name) = path.strip_prefix(PREFIX) else {
+        return Err(eyre!("{path} isn't in {PREFIX}"));
+    };
+    if name.contains(['?', '#', '%', '\\']) || name.split('/').any(|seg| seg == ".." || seg == ".")
+    {
+        return Err(eyre!("{path} isn't a plain path"));
+    }
+    let data =
+        std::fs::read(static_dir.join(name)).wrap_err_with(|| format!("can't read {path}"))?;
+    let digest = Sha256::digest(&data);
+    Ok(Include {
+        href: format!(
+            "{path}?bustCache={}",
+            &hex::encode(digest)[..FINGERPRINT_LEN]
+        ),
+        kind,
+        integrity: format!("sha256-{}", STANDARD.encode(digest)),
+    })
+}
+
+/// The stylesheets and then the scripts asked for by `front_matter`, with
+/// the files looked up in `static_dir`.
+pub fn includes(front_matter: &Frontmatter, static_dir: &Path) -> Result<Vec<Include>> {
+    let css = front_matter
+        .extra_css
+        .iter()
+        .map(|path| include(static_dir, path, AssetKind::Style));
+    let scripts = front_matter
+        .extra_scripts
+        .iter()
+        .map(|path| include(static_dir, path, AssetKind::Module));
+    css.chain(scripts).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn includes_are_fingerprinted() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("xesite-extras-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("js"))?;
+        std::fs::write(dir.join("post.css"), "body {}")?;
+        std::fs::write(dir.join("js/post.js"), "")?;
+
+        let front_matter = Frontmatter {
+            extra_css: vec!["/static/post.css".into()],
+            extra_scripts: vec!["/static/js/post.js".into()],
+            ..Default::default()
+        };
+        let got = includes(&front_matter, &dir)?;
+        assert_eq!(
+            got,
+            vec![
+                Include {
+                    href: "/static/post.css?bustCache=62368a1a29259b30".into(),
+                    kind: AssetKind::Style,
+                    integrity: "sha256-YjaKGiklmzC6wjXA513HAMmzus8VE61XCOT+SmwNZWA=".into(),
+                },
+                Include {
+                    href: "/static/js/post.js?bustCache=e3b0c44298fc1c14".into(),
+                    kind: AssetKind::Module,
+                    integrity: "sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=".into(),
+                },
+            ]
+        );
+
+        for path in [
+            "post.css",
+            "https://example.com/post.css",
+            "/static/../Cargo.toml",
+            "/static/missing.css",
+        ] {
+            let front_matter = Frontmatter {
+                extra_css: vec![path.into()],
+                ..Default::default()
+            };
+            assert!(includes(&front_matter, &dir).is_err(), "{path}");
+        }
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -2,21 +2,58 @@ use chrono::prelude::*;
 use color_eyre::eyre::{eyre, Result, WrapErr};
 use glob::glob;
 use serde::{Deserialize, Serialize};
-use std::{borrow::Borrow, cmp::Ordering, path::PathBuf};
+use std::{borrow::Borrow, cmp::Ordering, collections::HashMap, path::PathBuf};
 use tokio::fs;
+use xesite_markdown::toc::{self, Heading};
+use xesite_templates::context::{self, site, Include, Preload};
+use xesite_types::{media::MediaCredit, Series, SeriesPart};
 
+pub mod archive;
+pub mod excerpt;
+pub mod extras;
 pub mod frontmatter;
 pub mod schemaorg;
+pub mod signature;
 
 #[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
 pub struct Post {
     pub front_matter: frontmatter::Data,
     pub link: String,
     pub body_html: String,
+    /// The body rendered for feeds, with conversations as chat logs instead
+    /// of stickers.
+    #[serde(skip)]
+    pub feed_body_html: String,
     pub date: DateTime<FixedOffset>,
     pub mentions: Vec<mi::WebMention>,
     pub new_post: NewPost,
     pub read_time_estimate_minutes: u64,
+    /// Slugs of the notes this post links to with `[[wiki links]]`.
+    pub wikilinks: Vec<String>,
+    pub word_count: usize,
+    /// The start of the post for index pages, see [excerpt].
+    #[serde(skip)]
+    pub excerpt: String,
+    /// Assets the rendered body asked to preload, such as its hero image.
+    #[serde(skip)]
+    pub preloads: Vec<Preload>,
+    /// The post's own stylesheets and scripts, see [extras].
+    #[serde(skip)]
+    pub includes: Vec<Include>,
+    /// Credits for the images and videos in the body, see
+    /// [xesite_templates::media_credits].
+    #[serde(skip)]
+    pub credits: Vec<MediaCredit>,
+    /// The headings in the body, which all have ids, for its table of
+    /// contents.
+    #[serde(skip)]
+    pub toc: Vec<Heading>,
+    #[serde(skip)]
+    pub signatures: signature::Signatures,
+    /// Where the post is in its series, if it's in one with other posts.
+    /// It's set once every post in the folder is loaded, see [link_series].
+    #[serde(skip)]
+    pub series: Option<Series>,
 }
 
 /// Used with the Android app to show information in a widget.
@@ -27,35 +64,22 @@ pub struct NewPost {
     pub link: String,
 }
 
-impl Into<schemaorg::Article> for &Post {
-    fn into(self) -> schemaorg::Article {
-        schemaorg::Article {
-            context: "https://schema.org".to_string(),
-            r#type: "Article".to_string(),
-            headline: self.front_matter.title.clone(),
-            image: "https://xeiaso.net/static/img/avatar.png".to_string(),
-            url: format!("https://xeiaso.net/{}", self.link),
-            date_published: self.date.format("%Y-%m-%d").to_string(),
-        }
-    }
-}
-
 impl Into<xe_jsonfeed::Item> for Post {
     fn into(self) -> xe_jsonfeed::Item {
         let mut result = xe_jsonfeed::Item::builder()
             .title(self.front_matter.title.clone())
-            .content_html(self.body_html)
-            .id(format!("https://xeiaso.net/{}", self.link))
+            .content_html(self.feed_html())
+            .id(site().url(&self.link))
             .url(if let Some(url) = self.front_matter.redirect_to.as_ref() {
                 url.clone()
             } else {
-                format!("https://xeiaso.net/{}", self.link)
+                site().url(&self.link)
             })
             .date_published(self.date.to_rfc3339())
             .author(
                 xe_jsonfeed::Author::new()
                     .name("Xe Iaso")
-                    .url("https://xeiaso.net")
+                    .url(site().url("/"))
                     .avatar("https://xeiaso.net/static/img/avatar.png"),
             )
             .xesite_frontmatter(self.front_matter.clone());
@@ -98,6 +122,91 @@ impl Post {
     pub fn detri(&self) -> String {
         self.date.format("M%m %d %Y").to_string()
     }
+
+    /// The HTML used for this post in feeds. Posts with a content warning only
+    /// get a summary so that feed readers don't show the body unprompted.
+    pub fn feed_html(&self) -> String {
+        match &self.front_matter.content_warning {
+            Some(warning) => format!(
+                "<p>Content warning: {}. Please <a href=\"{}\">read this post on the website</a>.</p>",
+                maud::html! { (warning) }.0,
+                site().url(&self.link)
+            ),
+            None => self.feed_body_html.clone(),
+        }
+    }
+
+    /// Where the markdown this post was rendered from is served, so readers
+    /// can check it against its signatures.
+    pub fn source_url(&self) -> String {
+        format!("/source/{}.markdown", self.link)
+    }
+
+    /// Registers the assets the body depends on for the page about to be
+    /// rendered, along with the post's own stylesheets and scripts. Bodies
+    /// are rendered once at startup, so their components can't do this
+    /// themselves.
+    pub fn preload_assets(&self) {
+        for asset in &self.preloads {
+            context::preload(asset.clone());
+        }
+        for asset in &self.includes {
+            context::include(asset.clone());
+        }
+    }
+
+    /// The date after which this post should be considered out of date, if any.
+    pub fn outdated_as_of(&self) -> Option<NaiveDate> {
+        self.front_matter
+            .outdated_as_of
+            .as_ref()
+            .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+    }
+
+    pub fn is_outdated(&self) -> bool {
+        match self.outdated_as_of() {
+            Some(date) => Utc::now().date_naive() >= date,
+            None => false,
+        }
+    }
+
+    /// Whether this post is about an event that hasn't happened yet.
+    pub fn upcoming_event(&self) -> bool {
+        let event = match &self.front_matter.event {
+            Some(event) => event,
+            None => return false,
+        };
+        let end = event.end_date.as_ref().unwrap_or(&event.start_date);
+
+        match NaiveDate::parse_from_str(end, "%Y-%m-%d") {
+            Ok(date) => date >= Utc::now().date_naive(),
+            Err(_) => false,
+        }
+    }
+
+    /// The last part of the post's link, usually the file name.
+    pub fn slug(&self) -> &str {
+        self.link.rsplit('/').next().unwrap_or(&self.link)
+    }
+
+    /// Whether this is an incident report that hasn't been resolved yet.
+    pub fn ongoing_incident(&self) -> bool {
+        match &self.front_matter.incident {
+            Some(incident) => incident.resolved.is_none(),
+            None => false,
+        }
+    }
+
+    /// Unlisted posts are reachable by URL but are left out of indexes,
+    /// feeds and the sitemap.
+    pub fn listed(&self) -> bool {
+        !self.front_matter.unlisted
+    }
+
+    /// Whether search engines should be allowed to index this post.
+    pub fn indexable(&self) -> bool {
+        self.listed() && !self.front_matter.noindex
+    }
 }
 
 async fn read_post(dir: &str, fname: PathBuf, cli: &Option<mi::Client>) -> Result<Post> {
@@ -109,14 +218,39 @@ async fn read_post(dir: &str, fname: PathBuf, cli: &Option<mi::Client>) -> Resul
     let body = fs::read_to_string(fname.clone())
         .await
         .wrap_err_with(|| format!("can't read {:?}", fname))?;
+    let signatures = signature::Signatures::load(&fname, body.as_bytes()).await;
     let (front_matter, content_offset) = frontmatter::parse(body.clone().as_str())
         .wrap_err_with(|| format!("can't parse frontmatter of {:?}", fname))?;
     let body = &body[content_offset..];
+    let (body, wikilinks) = if dir == "notes" {
+        crate::notes::expand_wikilinks(body)
+    } else {
+        (body.to_string(), vec![])
+    };
     let date = NaiveDate::parse_from_str(&front_matter.clone().date, "%Y-%m-%d")
         .map_err(|why| eyre!("error parsing date in {:?}: {}", fname, why))?;
+    if let Some(outdated_as_of) = &front_matter.outdated_as_of {
+        NaiveDate::parse_from_str(outdated_as_of, "%Y-%m-%d")
+            .map_err(|why| eyre!("error parsing outdated_as_of in {:?}: {}", fname, why))?;
+    }
     let link = format!("{}/{}", dir, fname.file_stem().unwrap().to_str().unwrap());
-    let body_html = xesite_markdown::render(&body)
-        .wrap_err_with(|| format!("can't parse markdown for {:?}", fname))?;
+    let includes = extras::includes(&front_matter, "./static".as_ref())
+        .wrap_err_with(|| format!("can't load the extra assets of {:?}", fname))?;
+    let ((body_html, credits), preloads) =
+        context::collect(|| context::collect_credits(|| xesite_markdown::render(&body)));
+    let body_html = body_html.wrap_err_with(|| format!("can't parse markdown for {:?}", fname))?;
+    let (body_html, toc) = toc::inject_ids(&body_html)
+        .wrap_err_with(|| format!("can't find the headings in {:?}", fname))?;
+    // The assets and credits are the same as the web version's, so the ones
+    // collected here are thrown away.
+    let (feed_body_html, _) = context::collect(|| {
+        context::with_target(context::RenderTarget::Feed, || {
+            context::collect_credits(|| xesite_markdown::render(&body)).0
+        })
+    });
+    let feed_body_html =
+        feed_body_html.wrap_err_with(|| format!("can't parse markdown for {:?}", fname))?;
+    let excerpt = excerpt::excerpt(&body_html, site().excerpt_words);
     let date: DateTime<FixedOffset> = DateTime::<Utc>::from_utc(
         NaiveDateTime::new(date, NaiveTime::from_hms_opt(0, 0, 0).unwrap()),
         Utc,
@@ -148,34 +282,48 @@ async fn read_post(dir: &str, fname: PathBuf, cli: &Option<mi::Client>) -> Resul
         None => vec![],
     };
 
-    let time_taken = estimated_read_time::text(
-        &body,
-        &estimated_read_time::Options::new()
-            .technical_document(true)
-            .technical_difficulty(1)
-            .build()
-            .unwrap_or_default(),
-    );
-    let read_time_estimate_minutes = time_taken.seconds() / 60;
+    let word_count = xesite_templates::stats::word_count(&body);
+    let read_time_estimate_minutes = xesite_templates::stats::minutes(word_count) as u64;
 
     let new_post = NewPost {
         title: front_matter.title.clone(),
         summary: format!("{} minute read", read_time_estimate_minutes),
-        link: format!("https://xeiaso.net/{}", link),
+        link: site().url(&link),
     };
 
     Ok(Post {
         front_matter,
         link,
         body_html,
+        feed_body_html,
         date,
         mentions,
         new_post,
         read_time_estimate_minutes,
+        wikilinks,
+        word_count,
+        excerpt,
+        preloads,
+        includes,
+        credits,
+        toc,
+        signatures,
+        series: None,
     })
 }
 
 pub async fn load(dir: &str) -> Result<Vec<Post>> {
+    let result = load_all(dir).await?;
+
+    if result.len() == 0 {
+        Err(eyre!("no posts loaded"))
+    } else {
+        Ok(result)
+    }
+}
+
+/// Like [load], but it is fine for the folder to be empty.
+pub async fn load_all(dir: &str) -> Result<Vec<Post>> {
     let cli = match std::env::var("MI_TOKEN") {
         Ok(token) => mi::Client::new(token.to_string(), crate::APPLICATION_NAME.to_string()).ok(),
         Err(_) => None,
@@ -191,13 +339,93 @@ pub async fn load(dir: &str) -> Result<Vec<Post>> {
         .map(Result::unwrap)
         .collect();
 
-    if result.len() == 0 {
-        Err(eyre!("no posts loaded"))
-    } else {
-        result.sort();
-        result.reverse();
-        Ok(result)
+    result.sort();
+    result.reverse();
+    link_series(&mut result);
+    Ok(result)
+}
+
+/// Tells every listed post in a series with more than one listed post where
+/// it is in it. Parts are in the order they were published.
+pub fn link_series(posts: &mut [Post]) {
+    let mut parts: HashMap<String, Vec<(DateTime<FixedOffset>, SeriesPart)>> = HashMap::new();
+    for post in posts.iter().filter(|post| post.listed()) {
+        if let Some(series) = &post.front_matter.series {
+            parts.entry(series.clone()).or_default().push((
+                post.date,
+                SeriesPart {
+                    title: post.front_matter.title.clone(),
+                    url: format!("/{}", post.link),
+                },
+            ));
+        }
     }
+
+    for post in posts.iter_mut() {
+        post.series = None;
+        let Some(title) = post.front_matter.series.clone() else {
+            continue;
+        };
+        let Some(parts) = parts.get_mut(&title).filter(|parts| parts.len() > 1) else {
+            continue;
+        };
+        parts.sort_by_key(|(date, _)| *date);
+        let url = format!("/{}", post.link);
+        let Some(current) = parts.iter().position(|(_, part)| part.url == url) else {
+            continue;
+        };
+        post.series = Some(Series {
+            title,
+            parts: parts.iter().map(|(_, part)| part.clone()).collect(),
+            current,
+        });
+    }
+}
+
+/// How much each tag a post shares with another counts for in [related].
+const TAG_SCORE: f64 = 2.0;
+/// How much being in the same series counts for in [related].
+const SERIES_SCORE: f64 = 3.0;
+
+fn tags(post: &Post) -> Vec<String> {
+    post.front_matter
+        .tags
+        .iter()
+        .flatten()
+        .map(|tag| tag.to_lowercase())
+        .collect()
+}
+
+/// Up to `n` listed posts from `all` with a tag or series in common with
+/// `current`, best match first. Every shared tag and the same series add to
+/// a post's score. Newer posts get up to one more point, so the newer of two
+/// posts that match as well comes first.
+pub fn related<'a>(current: &Post, all: &'a [Post], n: usize) -> Vec<&'a Post> {
+    let Some(newest) = all.iter().map(|post| post.date).max() else {
+        return vec![];
+    };
+    let wanted = tags(current);
+    let mut scored: Vec<(f64, &Post)> = all
+        .iter()
+        .filter(|post| {
+            post.listed() && post.link != current.link && post.front_matter.redirect_to.is_none()
+        })
+        .filter_map(|post| {
+            let shared = tags(post).iter().filter(|tag| wanted.contains(tag)).count();
+            let same_series = current.front_matter.series.is_some()
+                && post.front_matter.series == current.front_matter.series;
+            if shared == 0 && !same_series {
+                return None;
+            }
+            let years = (newest - post.date).num_days() as f64 / 365.0;
+            let score = shared as f64 * TAG_SCORE
+                + if same_series { SERIES_SCORE } else { 0.0 }
+                + 1.0 / (1.0 + years);
+            Some((score, post))
+        })
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored.into_iter().take(n).map(|(_, post)| post).collect()
 }
 
 #[cfg(test)]
@@ -211,6 +439,51 @@ mod tests {
         load("blog").await.expect("posts to load");
     }
 
+    #[tokio::test]
+    async fn series() -> Result<()> {
+        let posts = load("blog").await?;
+        let post = posts
+            .iter()
+            .find(|p| p.series.is_some())
+            .expect("a post in a series");
+        let series = post.series.as_ref().unwrap();
+        assert_eq!(series.parts[series.current].url, format!("/{}", post.link));
+        assert!(series.parts.len() > 1);
+
+        let dates: Vec<_> = series
+            .parts
+            .iter()
+            .map(|part| posts.iter().find(|p| format!("/{}", p.link) == part.url))
+            .map(|p| p.unwrap().date)
+            .collect();
+        assert!(dates.windows(2).all(|w| w[0] <= w[1]));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn related_posts() -> Result<()> {
+        let posts = load("blog").await?;
+        let post = posts
+            .iter()
+            .find(|p| p.listed() && p.series.is_some())
+            .expect("a post in a series");
+
+        let found = related(post, &posts, 3);
+        assert!(!found.is_empty() && found.len() <= 3);
+        let wanted = tags(post);
+        for other in &found {
+            assert_ne!(other.link, post.link);
+            assert!(other.listed());
+            assert!(
+                other.front_matter.series == post.front_matter.series
+                    || tags(other).iter().any(|tag| wanted.contains(tag))
+            );
+        }
+        assert!(related(post, &posts, 0).is_empty());
+        assert!(related(post, &[], 3).is_empty());
+        Ok(())
+    }
+
     #[tokio::test]
     async fn gallery() -> Result<()> {
         let _ = pretty_env_logger::try_init();
@@ -224,4 +497,53 @@ mod tests {
         load("talks").await?;
         Ok(())
     }
+
+    /// Fails if loading or rendering the blogposts gets slower. Loading is
+    /// mostly markdown, so it gets its own budget and the template budget can
+    /// be tight. Both are per post, on average, so they don't have to change
+    /// as posts are added. `XESITE_RENDER_BUDGET_SCALE` multiplies them for
+    /// slow machines. Timings are only meaningful on an optimised build with
+    /// nothing else running, so this is ignored unless asked for:
+    /// `cargo test --release -p xesite render_budget -- --ignored --test-threads=1`.
+    #[tokio::test]
+    #[ignore]
+    async fn render_budget() -> Result<()> {
+        const LOAD_BUDGET: std::time::Duration = std::time::Duration::from_millis(50);
+        const TEMPLATE_BUDGET: std::time::Duration = std::time::Duration::from_millis(2);
+
+        let scale: u32 = std::env::var("XESITE_RENDER_BUDGET_SCALE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(1);
+
+        let start = std::time::Instant::now();
+        let posts = load("blog").await?;
+        let loading = start.elapsed() / posts.len() as u32;
+
+        let start = std::time::Instant::now();
+        for post in &posts {
+            crate::tmpl::blog::blog(
+                post,
+                &posts,
+                maud::PreEscaped(&post.body_html),
+                None,
+                &Default::default(),
+                Default::default(),
+            );
+        }
+        let templates = start.elapsed() / posts.len() as u32;
+
+        println!("per post: {loading:?} loading, {templates:?} in templates");
+        assert!(
+            loading <= LOAD_BUDGET * scale,
+            "loading took {loading:?} per post, the budget is {:?}",
+            LOAD_BUDGET * scale
+        );
+        assert!(
+            templates <= TEMPLATE_BUDGET * scale,
+            "templates took {templates:?} per post, the budget is {:?}",
+            TEMPLATE_BUDGET * scale
+        );
+        Ok(())
+    }
 }
@@ -1,14 +1,112 @@
+//! The [schema.org](https://schema.org) types posts are described with in
+//! JSON-LD, see [crate::tmpl::structured_data].
+
 use serde::{Deserialize, Serialize};
 
+fn context() -> String {
+    "https://schema.org".to_string()
+}
+
+#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct Person {
+    #[serde(rename = "@type")]
+    pub r#type: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+impl Person {
+    pub fn new(name: impl Into<String>, url: Option<String>) -> Self {
+        Self {
+            r#type: "Person".to_string(),
+            name: name.into(),
+            url,
+        }
+    }
+}
+
 #[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
-pub struct Article {
-    #[serde(rename = "@context")]
+pub struct BlogPosting {
+    #[serde(rename = "@context", default = "context")]
     pub context: String,
     #[serde(rename = "@type")]
     pub r#type: String,
     pub headline: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub image: String,
+    pub url: String,
+    #[serde(rename = "mainEntityOfPage")]
+    pub main_entity_of_page: String,
+    #[serde(rename = "datePublished")]
+    pub date_published: String,
+    #[serde(rename = "dateModified", skip_serializing_if = "Option::is_none")]
+    pub date_modified: Option<String>,
+    pub author: Person,
+    pub publisher: Person,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub keywords: Vec<String>,
+    #[serde(rename = "wordCount")]
+    pub word_count: usize,
+    /// How long it takes to read, as an ISO 8601 duration like `PT5M`.
+    #[serde(rename = "timeRequired")]
+    pub time_required: String,
+}
+
+/// The event a talk was given at.
+#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct Event {
+    #[serde(rename = "@type")]
+    pub r#type: String,
+    pub name: String,
+    #[serde(rename = "startDate")]
+    pub start_date: String,
+    #[serde(rename = "endDate", skip_serializing_if = "Option::is_none")]
+    pub end_date: Option<String>,
+    pub location: Place,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct Place {
+    #[serde(rename = "@type")]
+    pub r#type: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+}
+
+/// A file that goes with a talk, like its slides or a recording.
+#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct MediaObject {
+    #[serde(rename = "@type")]
+    pub r#type: String,
+    #[serde(rename = "contentUrl")]
+    pub content_url: String,
+    #[serde(rename = "encodingFormat", skip_serializing_if = "Option::is_none")]
+    pub encoding_format: Option<String>,
+}
+
+#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct PresentationDigitalDocument {
+    #[serde(rename = "@context", default = "context")]
+    pub context: String,
+    #[serde(rename = "@type")]
+    pub r#type: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
     pub image: String,
     pub url: String,
     #[serde(rename = "datePublished")]
     pub date_published: String,
+    pub author: Person,
+    #[serde(rename = "recordedAt", skip_serializing_if = "Option::is_none")]
+    pub recorded_at: Option<Event>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub encoding: Vec<MediaObject>,
+    #[serde(rename = "sameAs", skip_serializing_if = "Vec::is_empty", default)]
+    pub same_as: Vec<String>,
 }
@@ -0,0 +1,88 @@
+//! Detached signatures made with `scripts/sign` when a post is published.
+//! They live next to the post as `<post>.markdown.minisig` and
+//! `<post>.markdown.asc`.
+
+use color_eyre::eyre::{eyre, Result};
+use minisign_verify::{PublicKey, Signature};
+use std::path::Path;
+use tokio::fs;
+use xesite_templates::context::site;
+
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+pub struct Signatures {
+    /// The minisign signature. This is only set if it checks out against
+    /// the configured public key.
+    pub minisign: Option<String>,
+    /// An armored PGP signature. The server doesn't check these.
+    pub pgp: Option<String>,
+}
+
+impl Signatures {
+    /// Loads the signatures for the post at `fname`, whose raw contents are
+    /// `contents`. Signatures that don't match are left out with a warning,
+    /// usually because the post was edited after it was signed.
+    pub async fn load(fname: &Path, contents: &[u8]) -> Self {
+        let minisign = match read(fname, "minisig").await {
+            Some(signature) => match &site().signing.minisign_public_key {
+                Some(key) => match verify(key, contents, &signature) {
+                    Ok(()) => Some(signature),
+                    Err(why) => {
+                        warn!("minisign signature for {} is bad: {why}", fname.display());
+                        None
+                    }
+                },
+                None => {
+                    warn!(
+                        "{} has a minisign signature but no public key is configured",
+                        fname.display()
+                    );
+                    None
+                }
+            },
+            None => None,
+        };
+
+        Self {
+            minisign,
+            pgp: read(fname, "asc").await,
+        }
+    }
+
+    pub fn is_signed(&self) -> bool {
+        self.minisign.is_some() || self.pgp.is_some()
+    }
+}
+
+async fn read(fname: &Path, ext: &str) -> Option<String> {
+    let mut path = fname.as_os_str().to_owned();
+    path.push(format!(".{ext}"));
+    fs::read_to_string(path).await.ok()
+}
+
+pub fn verify(public_key: &str, contents: &[u8], signature: &str) -> Result<()> {
+    let public_key =
+        PublicKey::from_base64(public_key.trim()).map_err(|why| eyre!("bad public key: {why}"))?;
+    let signature = Signature::decode(signature).map_err(|why| eyre!("bad signature: {why}"))?;
+    public_key
+        .verify(contents, &signature, false)
+        .map_err(|why| eyre!("{why}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // From the minisign-verify test suite.
+    const PUBLIC_KEY: &str = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+    const SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RUQf6LRCGA9i559r3g7V1qNyJDApGip8MfqcadIgT9CuhV3EMhHoN1mGTkUidF/z7SrlQgXdy8ofjb7bNJJylDOocrCo8KLzZwo=
+trusted comment: timestamp:1556193335\tfile:test
+y/rUw2y8/hOUYjZU71eHp/Wo1KZ40fGy2VJEDl34XMJM+TX48Ss/17u3IvIfbVR1FkZZSNCisQbuQY+bHwhEBg==
+";
+
+    #[test]
+    fn verifies() {
+        assert!(verify(PUBLIC_KEY, b"test", SIGNATURE).is_ok());
+        assert!(verify(PUBLIC_KEY, b"tampered", SIGNATURE).is_err());
+    }
+}
@@ -0,0 +1,161 @@
+//! Things readers can ask the site to do for them, like not showing ads.
+//! They're kept in a cookie so they work without logging in. Patrons who
+//! log in with the email address on their Patreon account don't see ads
+//! either, whatever the cookie says.
+
+use crate::{
+    app::State,
+    auth::{cookie, cookie_value, Provider, Session, SESSION_COOKIE},
+};
+use axum::{
+    http::{header, HeaderMap, HeaderValue, Request},
+    middleware::Next,
+    response::Response,
+};
+use xesite_types::ReaderPrefs;
+
+/// Where readers change their preferences.
+pub const PREFIX: &str = "/.within/prefs";
+
+/// The cookie preferences are kept in.
+pub const COOKIE: &str = "xesite-prefs";
+
+/// How long the cookie lasts, in seconds.
+const COOKIE_SECS: i64 = 60 * 60 * 24 * 365;
+
+/// The flag in the cookie for [ReaderPrefs::no_ads].
+const NO_ADS: &str = "no-ads";
+
+/// What the cookie in `headers` asks for. Flags are separated with dots,
+/// and ones this version doesn't know are ignored.
+fn parse(headers: &HeaderMap) -> ReaderPrefs {
+    let flags = cookie_value(headers, COOKIE).unwrap_or_default();
+    ReaderPrefs {
+        no_ads: flags.split('.').any(|flag| flag == NO_ADS),
+        patron: false,
+    }
+}
+
+/// How pages for readers with preferences or a session can be cached.
+const PRIVATE: &str = "private, no-cache";
+
+/// Whether `session` is one of `patrons`. Patreon can't log people in here,
+/// so it's by the email address on their Patreon account, which only
+/// counts when an OpenID Connect provider checked it.
+fn is_patron(patrons: &patreon::Users, session: &Session) -> bool {
+    session.via == Provider::Oidc
+        && patrons.iter().any(|patron| {
+            patron
+                .attributes
+                .email
+                .as_deref()
+                .map_or(false, |email| email.eq_ignore_ascii_case(&session.me))
+        })
+}
+
+/// The preferences of whoever sent `headers`.
+pub fn for_request(state: &State, headers: &HeaderMap) -> ReaderPrefs {
+    let mut prefs = parse(headers);
+    if let (Some(patrons), Some(session)) = (&state.patrons, state.auth.session(headers)) {
+        prefs.patron = is_patron(patrons, &session);
+    }
+    prefs
+}
+
+/// Whether pages for whoever sent `headers` could be different from
+/// everyone else's.
+fn is_personal(headers: &HeaderMap) -> bool {
+    cookie_value(headers, COOKIE).map_or(false, |flags| !flags.is_empty())
+        || cookie_value(headers, SESSION_COOKIE).is_some()
+}
+
+/// Marks HTML pages for readers with preferences or a session private, so
+/// only their own browser keeps them. Everyone else's pages stay public, and
+/// caches don't have to tell readers apart by their cookies.
+pub async fn middleware<B>(req: Request<B>, next: Next<B>) -> Response {
+    let personal = is_personal(req.headers());
+    let mut resp = next.run(req).await;
+    let is_html = resp
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|ct| ct.to_str().ok())
+        .map_or(false, |ct| ct.starts_with("text/html"));
+    if personal && is_html {
+        resp.headers_mut()
+            .entry(header::CACHE_CONTROL)
+            .or_insert(HeaderValue::from_static(PRIVATE));
+    }
+    resp
+}
+
+/// A `Set-Cookie` header that saves `prefs`.
+pub fn set_cookie(prefs: ReaderPrefs) -> String {
+    if prefs.no_ads {
+        cookie(COOKIE, NO_ADS, "/", COOKIE_SECS)
+    } else {
+        cookie(COOKIE, "", "/", 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::header;
+
+    #[test]
+    fn cookies() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(parse(&headers), ReaderPrefs::default());
+
+        headers.insert(
+            header::COOKIE,
+            "xesite-session=abc; xesite-prefs=future.no-ads"
+                .parse()
+                .unwrap(),
+        );
+        let prefs = parse(&headers);
+        assert!(prefs.no_ads);
+        assert!(!prefs.shows_ads());
+        assert!(set_cookie(prefs).starts_with("xesite-prefs=no-ads; Path=/; Max-Age=31536000;"));
+        assert!(set_cookie(ReaderPrefs::default()).contains("Max-Age=0;"));
+    }
+
+    #[test]
+    fn patrons() {
+        let patrons: patreon::Users = serde_json::from_value(serde_json::json!([{
+            "id": "1",
+            "type": "user",
+            "links": null,
+            "attributes": {
+                "first_name": "Mara",
+                "last_name": "",
+                "full_name": "Mara",
+                "vanity": null,
+                "about": null,
+                "gender": 0,
+                "image_url": "",
+                "thumb_url": "",
+                "created": "2020-01-01T00:00:00Z",
+                "url": "https://www.patreon.com/mara",
+                "email": "Mara@example.com",
+            },
+        }]))
+        .unwrap();
+        let patron = |me: &str, via| is_patron(&patrons, &Session::new(me.into(), None, via));
+        assert!(patron("mara@example.com", Provider::Oidc));
+        assert!(!patron("numa@example.com", Provider::Oidc));
+        // IndieAuth profiles aren't checked email addresses.
+        assert!(!patron("mara@example.com", Provider::IndieAuth));
+        assert!(!patron("https://www.patreon.com/mara", Provider::IndieAuth));
+    }
+
+    #[test]
+    fn personal() {
+        let mut headers = HeaderMap::new();
+        assert!(!is_personal(&headers));
+        headers.insert(header::COOKIE, "xesite-prefs=".parse().unwrap());
+        assert!(!is_personal(&headers));
+        headers.insert(header::COOKIE, "xesite-prefs=no-ads".parse().unwrap());
+        assert!(is_personal(&headers));
+    }
+}
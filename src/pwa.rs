@@ -0,0 +1,133 @@
+//! The web app manifest and the icons that make the site installable.
+
+use color_eyre::eyre::{eyre, Result};
+use image::{imageops, DynamicImage, ImageOutputFormat, Rgba, RgbaImage};
+use serde_json::{json, Value};
+use std::{io::Cursor, sync::OnceLock};
+use xesite_templates::context::site;
+use xesite_types::site::parse_color;
+
+/// The square avatar that maskable icons are made from.
+const AVATAR: &str = "./static/img/avatar.png";
+
+/// Launchers crop maskable icons to any shape that fits in the middle 80%
+/// of the icon, so the avatar is shrunk to fit inside that.
+const SAFE_ZONE: f32 = 0.8;
+
+pub const MASKABLE_SIZES: [u32; 2] = [192, 512];
+
+/// The prebuilt icons in `static`, with their sizes.
+const ICONS: &[(&str, u32)] = &[
+    ("/static/favicon/android-icon-36x36.png", 36),
+    ("/static/favicon/android-icon-48x48.png", 48),
+    ("/static/favicon/android-icon-72x72.png", 72),
+    ("/static/favicon/android-icon-96x96.png", 96),
+    ("/static/favicon/android-icon-144x144.png", 144),
+    ("/static/favicon/android-icon-192x192.png", 192),
+    ("/static/favicon/android-launchericon-512-512.png", 512),
+    ("/static/img/avatar.png", 1024),
+];
+
+pub fn manifest() -> Value {
+    let branding = &site().branding;
+    let mut icons: Vec<Value> = ICONS
+        .iter()
+        .map(|(src, size)| {
+            json!({
+                "src": src,
+                "sizes": format!("{size}x{size}"),
+                "type": "image/png",
+                "purpose": "any",
+            })
+        })
+        .collect();
+    icons.extend(MASKABLE_SIZES.iter().map(|size| {
+        json!({
+            "src": format!("/icons/maskable/{size}.png"),
+            "sizes": format!("{size}x{size}"),
+            "type": "image/png",
+            "purpose": "maskable",
+        })
+    }));
+
+    json!({
+        "name": branding.name,
+        "short_name": branding.short_name,
+        "description": branding.description,
+        "theme_color": branding.theme_color,
+        "background_color": branding.background_color,
+        "display": "standalone",
+        "scope": "/",
+        "start_url": "/",
+        "orientation": "any",
+        "icons": icons,
+    })
+}
+
+static MASKABLE: [OnceLock<Vec<u8>>; MASKABLE_SIZES.len()] = [OnceLock::new(), OnceLock::new()];
+
+/// The avatar on the background colour, as a PNG of the given size. Icons
+/// are only made once per size.
+pub fn maskable_icon(size: u32) -> Result<&'static [u8]> {
+    let index = MASKABLE_SIZES
+        .iter()
+        .position(|s| *s == size)
+        .ok_or_else(|| eyre!("no maskable icon of size {size}"))?;
+    if let Some(icon) = MASKABLE[index].get() {
+        return Ok(icon);
+    }
+
+    let avatar = image::open(AVATAR)?;
+    let icon = render_maskable(&avatar, size, &site().branding.background_color)?;
+    Ok(MASKABLE[index].get_or_init(|| icon))
+}
+
+fn render_maskable(avatar: &DynamicImage, size: u32, background: &str) -> Result<Vec<u8>> {
+    let [r, g, b] =
+        parse_color(background).ok_or_else(|| eyre!("{background:?} is not a colour"))?;
+    let mut canvas = RgbaImage::from_pixel(size, size, Rgba([r, g, b, 255]));
+
+    let inner = (size as f32 * SAFE_ZONE) as u32;
+    let avatar = avatar
+        .resize(inner, inner, imageops::FilterType::Lanczos3)
+        .to_rgba8();
+    let x = (size - avatar.width()) / 2;
+    let y = (size - avatar.height()) / 2;
+    imageops::overlay(&mut canvas, &avatar, x as i64, y as i64);
+
+    let mut buf = Vec::new();
+    DynamicImage::ImageRgba8(canvas)
+        .write_to(&mut Cursor::new(&mut buf), ImageOutputFormat::Png)?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maskable_keeps_the_corners_clear() -> Result<()> {
+        let avatar =
+            DynamicImage::ImageRgba8(RgbaImage::from_pixel(64, 64, Rgba([255, 0, 0, 255])));
+        let png = render_maskable(&avatar, 100, "#1d2021")?;
+        let icon = image::load_from_memory(&png)?.to_rgba8();
+
+        assert_eq!(icon.dimensions(), (100, 100));
+        assert_eq!(icon.get_pixel(0, 0), &Rgba([0x1d, 0x20, 0x21, 255]));
+        assert_eq!(icon.get_pixel(50, 50), &Rgba([255, 0, 0, 255]));
+        Ok(())
+    }
+
+    #[test]
+    fn manifest_lists_maskable_icons() {
+        let manifest = manifest();
+        let maskable = manifest["icons"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|icon| icon["purpose"] == "maskable")
+            .count();
+        assert_eq!(maskable, MASKABLE_SIZES.len());
+        assert_eq!(manifest["theme_color"], site().branding.theme_color);
+    }
+}
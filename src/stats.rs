@@ -0,0 +1,142 @@
+use crate::post::Post;
+use chrono::prelude::*;
+use maud::{html, Markup, Render};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// How many tags and popular posts to show in a year in review.
+const TOP_N: usize = 5;
+
+#[derive(Clone, Debug, Default, Serialize, PartialEq)]
+pub struct PostRef {
+    pub title: String,
+    pub link: String,
+    pub value: u64,
+}
+
+impl PostRef {
+    fn new(post: &Post, value: u64) -> Self {
+        Self {
+            title: post.front_matter.title.clone(),
+            link: post.link.clone(),
+            value,
+        }
+    }
+}
+
+/// Statistics about everything published in a given year.
+#[derive(Clone, Debug, Default, Serialize, PartialEq)]
+pub struct YearStats {
+    pub year: i32,
+    pub posts: usize,
+    pub words: usize,
+    pub top_tags: Vec<(String, usize)>,
+    pub most_read: Vec<PostRef>,
+    pub longest: Option<PostRef>,
+}
+
+impl YearStats {
+    /// Computes the statistics for the posts published in `year`. `hits` is
+    /// used to look up how many times a post has been viewed.
+    pub fn compute(posts: &[Post], year: i32, hits: impl Fn(&Post) -> u64) -> Self {
+        let posts: Vec<&Post> = posts
+            .iter()
+            .filter(|p| p.date.year() == year)
+            .filter(|p| p.listed())
+            .collect();
+
+        let mut tags: HashMap<&str, usize> = HashMap::new();
+        for tag in posts
+            .iter()
+            .flat_map(|p| p.front_matter.tags.iter().flatten())
+        {
+            *tags.entry(tag.as_str()).or_default() += 1;
+        }
+        let mut top_tags: Vec<(String, usize)> =
+            tags.into_iter().map(|(k, v)| (k.to_string(), v)).collect();
+        top_tags.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        top_tags.truncate(TOP_N);
+
+        let mut most_read: Vec<PostRef> = posts
+            .iter()
+            .map(|p| PostRef::new(p, hits(p)))
+            .filter(|p| p.value != 0)
+            .collect();
+        most_read.sort_by(|a, b| b.value.cmp(&a.value));
+        most_read.truncate(TOP_N);
+
+        let longest = posts
+            .iter()
+            .max_by_key(|p| p.word_count)
+            .map(|p| PostRef::new(p, p.word_count as u64));
+
+        Self {
+            year,
+            posts: posts.len(),
+            words: posts.iter().map(|p| p.word_count).sum(),
+            top_tags,
+            most_read,
+            longest,
+        }
+    }
+}
+
+impl Render for YearStats {
+    fn render(&self) -> Markup {
+        html! {
+            .year-stats {
+                dl {
+                    dt {"Posts"}
+                    dd {(self.posts)}
+                    dt {"Words"}
+                    dd {(self.words)}
+                    @if let Some(longest) = &self.longest {
+                        dt {"Longest post"}
+                        dd {
+                            a href={"/" (longest.link)} {(longest.title)}
+                            " (" (longest.value) " words)"
+                        }
+                    }
+                }
+
+                @if !self.top_tags.is_empty() {
+                    h3 {"Top tags"}
+                    ol {
+                        @for (tag, count) in &self.top_tags {
+                            li {(tag) " (" (count) ")"}
+                        }
+                    }
+                }
+
+                @if !self.most_read.is_empty() {
+                    h3 {"Most read"}
+                    ol {
+                        @for post in &self.most_read {
+                            li {
+                                a href={"/" (post.link)} {(post.title)}
+                                " (" (post.value) " views)"
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use color_eyre::eyre::Result;
+
+    #[tokio::test]
+    async fn blog_2020() -> Result<()> {
+        let posts = crate::post::load("blog").await?;
+        let stats = YearStats::compute(&posts, 2020, |_| 0);
+
+        assert_ne!(stats.posts, 0);
+        assert!(stats.words >= stats.longest.as_ref().unwrap().value as usize);
+        assert!(stats.most_read.is_empty());
+        Ok(())
+    }
+}
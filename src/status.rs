@@ -0,0 +1,213 @@
+use crate::app::config::{ProbeKind, StatusService};
+use chrono::prelude::*;
+use color_eyre::eyre::{eyre, Report, Result};
+use lazy_static::lazy_static;
+use prometheus::{opts, register_int_gauge_vec, IntGaugeVec};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    io,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+use tokio::{fs, net::TcpStream, process::Command, sync::RwLock, time::timeout};
+
+/// How many checks are kept per service. At one check a minute this is the
+/// last hour and a half.
+pub const HISTORY_LEN: usize = 90;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+lazy_static! {
+    static ref SERVICE_UP: IntGaugeVec = register_int_gauge_vec!(
+        opts!("status_service_up", "Whether a monitored service is up"),
+        &["service"]
+    )
+    .unwrap();
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Check {
+    pub time: DateTime<Utc>,
+    pub up: bool,
+    pub latency_ms: u64,
+    /// Everything that went wrong, for the log and the admin area. It can
+    /// name internal hosts, so it stays off the public page.
+    pub error: Option<String>,
+    /// What went wrong in a few words, like "timed out" or "HTTP 502".
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Runs probes against the configured services and keeps a rolling history
+/// of the results on disk so that restarts don't wipe the uptime bars.
+pub struct Monitor {
+    services: Vec<StatusService>,
+    path: PathBuf,
+    history: RwLock<HashMap<String, Vec<Check>>>,
+}
+
+impl Monitor {
+    pub async fn new(services: Vec<StatusService>, path: PathBuf) -> Result<Self> {
+        let history = if path.exists() {
+            serde_json::from_slice(&fs::read(&path).await?)?
+        } else {
+            HashMap::new()
+        };
+
+        Ok(Self {
+            services,
+            path,
+            history: RwLock::new(history),
+        })
+    }
+
+    #[instrument(skip(self), err)]
+    pub async fn check_all(&self) -> Result<()> {
+        let checks = futures::future::join_all(self.services.iter().map(probe)).await;
+
+        let mut history = self.history.write().await;
+        for (svc, check) in self.services.iter().zip(checks) {
+            SERVICE_UP
+                .with_label_values(&[&svc.name])
+                .set(check.up as i64);
+            if !check.up {
+                warn!("{} is down: {:?}", svc.name, check.error);
+            }
+
+            let entry = history.entry(svc.name.clone()).or_default();
+            entry.push(check);
+            if entry.len() > HISTORY_LEN {
+                entry.drain(..entry.len() - HISTORY_LEN);
+            }
+        }
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(&self.path, serde_json::to_vec(&*history)?).await?;
+
+        Ok(())
+    }
+
+    /// Every configured service along with its check history, oldest first.
+    pub async fn snapshot(&self) -> Vec<(StatusService, Vec<Check>)> {
+        let history = self.history.read().await;
+        self.services
+            .iter()
+            .map(|svc| {
+                (
+                    svc.clone(),
+                    history.get(&svc.name).cloned().unwrap_or_default(),
+                )
+            })
+            .collect()
+    }
+}
+
+async fn probe(svc: &StatusService) -> Check {
+    let start = Instant::now();
+    let result = match svc.kind {
+        ProbeKind::HTTP => probe_http(&svc.target).await,
+        ProbeKind::TCP => probe_tcp(&svc.target).await,
+        ProbeKind::Ping => probe_ping(&svc.target).await,
+    };
+
+    Check {
+        time: Utc::now(),
+        up: result.is_ok(),
+        latency_ms: start.elapsed().as_millis() as u64,
+        reason: result.as_ref().err().map(reason),
+        error: result.err().map(|why| why.to_string()),
+    }
+}
+
+/// A coarse reason for a failed probe that doesn't say what was probed.
+fn reason(why: &Report) -> String {
+    let mut connect = false;
+    for cause in why.chain() {
+        if cause.is::<tokio::time::error::Elapsed>() {
+            return "timed out".into();
+        }
+        if let Some(err) = cause.downcast_ref::<reqwest::Error>() {
+            if let Some(status) = err.status() {
+                return format!("HTTP {}", status.as_u16());
+            }
+            if err.is_timeout() {
+                return "timed out".into();
+            }
+            connect |= err.is_connect();
+        }
+        if let Some(err) = cause.downcast_ref::<io::Error>() {
+            match err.kind() {
+                io::ErrorKind::ConnectionRefused => return "connection refused".into(),
+                io::ErrorKind::TimedOut => return "timed out".into(),
+                _ => {}
+            }
+        }
+    }
+    if connect {
+        "couldn't connect".into()
+    } else {
+        "down".into()
+    }
+}
+
+async fn probe_http(target: &str) -> Result<()> {
+    reqwest::Client::builder()
+        .timeout(PROBE_TIMEOUT)
+        .user_agent(crate::APPLICATION_NAME)
+        .build()?
+        .get(target)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+async fn probe_tcp(target: &str) -> Result<()> {
+    timeout(PROBE_TIMEOUT, TcpStream::connect(target)).await??;
+    Ok(())
+}
+
+async fn probe_ping(target: &str) -> Result<()> {
+    let status = Command::new("ping")
+        .args(["-c", "1", "-W", "5", target])
+        .status()
+        .await?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(eyre!("ping exited with {}", status))
+    }
+}
+
+/// The percentage of checks that succeeded.
+pub fn uptime(checks: &[Check]) -> Option<f64> {
+    if checks.is_empty() {
+        return None;
+    }
+    let up = checks.iter().filter(|c| c.up).count();
+    Some(up as f64 / checks.len() as f64 * 100.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reasons_hide_targets() {
+        let refused = Report::new(io::Error::from(io::ErrorKind::ConnectionRefused));
+        assert_eq!(reason(&refused), "connection refused");
+        assert_eq!(reason(&eyre!("http://10.0.0.5:8080/ is down")), "down");
+    }
+
+    #[tokio::test]
+    async fn reasons_for_timeouts() {
+        let elapsed = timeout(Duration::ZERO, std::future::pending::<()>())
+            .await
+            .unwrap_err();
+        assert_eq!(reason(&Report::new(elapsed)), "timed out");
+    }
+}
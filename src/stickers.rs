@@ -0,0 +1,246 @@
+//! Sticker packs: every sticker of a character in a zip file that can be
+//! imported into Signal or Telegram. Both want 512 by 512 pixel PNGs with an
+//! emoji for each sticker, so that's what goes in the pack along with a
+//! `manifest.json` listing them and the license the stickers are under.
+//! Only characters whose `stickerLicense` says they can be shared get a pack.
+
+use crate::app::config::Character;
+use color_eyre::eyre::Result;
+use image::{imageops, DynamicImage, ImageOutputFormat, Rgba, RgbaImage};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    io::{Cursor, Write},
+    sync::Mutex,
+};
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+/// How big each sticker in a pack is, in pixels.
+pub const STICKER_SIZE: u32 = 512;
+
+lazy_static! {
+    static ref PACKS: Mutex<HashMap<String, Vec<u8>>> = Mutex::new(HashMap::new());
+}
+
+/// Where a character's sticker pack is served.
+pub fn pack_path(ch: &Character) -> String {
+    format!("/stickers/{}.zip", ch.sticker_name)
+}
+
+/// The emoji a sticker is found by in Signal and Telegram.
+pub fn emoji(mood: &str) -> &'static str {
+    match mood {
+        "aha" | "cheer" => "💡",
+        "angy" | "rage" => "😠",
+        "coffee" => "☕",
+        "concern" | "dismay" => "😟",
+        "delet" | "delet2" => "🗑",
+        "enby" => "💛",
+        "facepalm" => "🤦",
+        "grin" | "happy" => "😄",
+        "hacker" => "💻",
+        "hmm" | "think" | "thinking" => "🤔",
+        "hug" => "🤗",
+        "neutral" => "😐",
+        "percussive-maintenance" => "🔨",
+        "sh0rck" | "wat" | "wut" => "😲",
+        "sleepy" | "yawn" => "😴",
+        "smug" => "😏",
+        "stare" => "👀",
+        "sus" => "🤨",
+        "vibe" => "🎶",
+        _ => "🙂",
+    }
+}
+
+/// What `manifest.json` in a pack says.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Manifest {
+    pub title: String,
+    pub author: String,
+    pub license: String,
+    #[serde(rename = "licenseUrl", skip_serializing_if = "Option::is_none")]
+    pub license_url: Option<String>,
+    /// The file name of the sticker shown for the pack.
+    pub cover: String,
+    pub stickers: Vec<ManifestSticker>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ManifestSticker {
+    pub file: String,
+    pub emoji: String,
+}
+
+pub fn manifest(ch: &Character) -> Manifest {
+    Manifest {
+        title: ch.name.clone(),
+        author: ch.sticker_license.attribution.clone(),
+        license: ch.sticker_license.license.clone(),
+        license_url: ch.sticker_license.license_url.clone(),
+        cover: format!("{}.png", ch.default_pose),
+        stickers: ch
+            .stickers
+            .iter()
+            .map(|mood| ManifestSticker {
+                file: format!("{mood}.png"),
+                emoji: emoji(mood).to_string(),
+            })
+            .collect(),
+    }
+}
+
+/// Scales a sticker to fit in [STICKER_SIZE] pixels and centres it on a
+/// transparent square, since Signal only takes square stickers.
+fn square(sticker: &DynamicImage) -> Result<Vec<u8>> {
+    let mut canvas = RgbaImage::from_pixel(STICKER_SIZE, STICKER_SIZE, Rgba([0, 0, 0, 0]));
+    let sticker = sticker
+        .resize(STICKER_SIZE, STICKER_SIZE, imageops::FilterType::Lanczos3)
+        .to_rgba8();
+    let x = (STICKER_SIZE - sticker.width()) / 2;
+    let y = (STICKER_SIZE - sticker.height()) / 2;
+    imageops::overlay(&mut canvas, &sticker, x as i64, y as i64);
+
+    let mut buf = Vec::new();
+    DynamicImage::ImageRgba8(canvas)
+        .write_to(&mut Cursor::new(&mut buf), ImageOutputFormat::Png)?;
+    Ok(buf)
+}
+
+/// Zips up the manifest and the stickers, which are `(mood, png)`.
+fn write_pack(manifest: &Manifest, stickers: &[(String, Vec<u8>)]) -> Result<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let text = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    // PNGs are already compressed.
+    let png = FileOptions::default().compression_method(CompressionMethod::Stored);
+
+    zip.start_file("manifest.json", text)?;
+    zip.write_all(&serde_json::to_vec_pretty(manifest)?)?;
+
+    zip.start_file("LICENSE.txt", text)?;
+    writeln!(
+        zip,
+        "{}'s stickers are by {} and licensed under {}.",
+        manifest.title, manifest.author, manifest.license
+    )?;
+    if let Some(url) = &manifest.license_url {
+        writeln!(zip, "{url}")?;
+    }
+
+    for (mood, data) in stickers {
+        zip.start_file(format!("{mood}.png"), png)?;
+        zip.write_all(data)?;
+    }
+
+    Ok(zip.finish()?.into_inner())
+}
+
+/// A character's sticker pack as a zip file. Packs are only made once per
+/// character, and only if every sticker could be fetched.
+pub async fn pack(ch: &Character) -> Result<Vec<u8>> {
+    if let Some(pack) = PACKS.lock().unwrap().get(&ch.sticker_name) {
+        return Ok(pack.clone());
+    }
+
+    let mut stickers = vec![];
+    for mood in &ch.stickers {
+        let image = crate::og::fetch_sticker(&ch.sticker_name, mood).await?;
+        stickers.push((mood.clone(), square(&image)?));
+    }
+
+    let pack = write_pack(&manifest(ch), &stickers)?;
+    PACKS
+        .lock()
+        .unwrap()
+        .insert(ch.sticker_name.clone(), pack.clone());
+    Ok(pack)
+}
+
+/// How many packs have been made since the last [purge].
+pub fn cached() -> usize {
+    PACKS.lock().unwrap().len()
+}
+
+/// Forgets every pack, so they're made again from the CDN the next time
+/// they're asked for. Returns how many there were.
+pub fn purge() -> usize {
+    let mut packs = PACKS.lock().unwrap();
+    let n = packs.len();
+    packs.clear();
+    n
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::config::StickerLicense;
+    use std::io::Read;
+
+    fn mara() -> Character {
+        Character {
+            name: "Mara".into(),
+            sticker_name: "mara".into(),
+            default_pose: "hacker".into(),
+            stickers: vec!["hacker".into(), "hmm".into()],
+            sticker_license: StickerLicense {
+                redistributable: true,
+                license: "CC BY-NC-SA 4.0".into(),
+                license_url: None,
+                attribution: "Xe Iaso".into(),
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn stickers_are_square() -> Result<()> {
+        let sticker =
+            DynamicImage::ImageRgba8(RgbaImage::from_pixel(200, 100, Rgba([255, 0, 0, 255])));
+        let png = image::load_from_memory(&square(&sticker)?)?.to_rgba8();
+
+        assert_eq!(png.dimensions(), (STICKER_SIZE, STICKER_SIZE));
+        assert_eq!(png.get_pixel(0, 0), &Rgba([0, 0, 0, 0]));
+        assert_eq!(png.get_pixel(256, 256), &Rgba([255, 0, 0, 255]));
+        Ok(())
+    }
+
+    #[test]
+    fn pack_has_manifest() -> Result<()> {
+        let ch = mara();
+        let manifest = manifest(&ch);
+        assert_eq!(manifest.cover, "hacker.png");
+        assert_eq!(
+            manifest.stickers,
+            vec![
+                ManifestSticker {
+                    file: "hacker.png".into(),
+                    emoji: "💻".into()
+                },
+                ManifestSticker {
+                    file: "hmm.png".into(),
+                    emoji: "🤔".into()
+                },
+            ]
+        );
+
+        let stickers = vec![
+            ("hacker".to_string(), b"png".to_vec()),
+            ("hmm".to_string(), b"png".to_vec()),
+        ];
+        let pack = write_pack(&manifest, &stickers)?;
+        let mut zip = zip::ZipArchive::new(Cursor::new(pack))?;
+        let mut names: Vec<&str> = zip.file_names().collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec!["LICENSE.txt", "hacker.png", "hmm.png", "manifest.json"]
+        );
+
+        let mut json = String::new();
+        zip.by_name("manifest.json")?.read_to_string(&mut json)?;
+        assert!(json.contains(r#""author": "Xe Iaso""#));
+        assert!(!json.contains("licenseUrl"));
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+//! Issues of the weekly digest, drafts and published, see [crate::digest].
+
+use super::Storage;
+use crate::digest::Issue;
+use chrono::NaiveDate;
+use color_eyre::eyre::Result;
+use rusqlite::{params, OptionalExtension};
+
+#[derive(Clone)]
+pub struct Digests(pub(super) Storage);
+
+fn parse(json: String) -> rusqlite::Result<Issue> {
+    serde_json::from_str(&json).map_err(|why| {
+        rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(why))
+    })
+}
+
+impl Digests {
+    /// The issue for the week starting on `week`, if there is one.
+    pub async fn get(&self, week: NaiveDate) -> Result<Option<Issue>> {
+        self.0
+            .call(move |conn| {
+                conn.query_row(
+                    "SELECT issue FROM digests WHERE week = ?1",
+                    params![week],
+                    |row| row.get(0),
+                )
+                .optional()?
+                .map(parse)
+                .transpose()
+            })
+            .await
+    }
+
+    /// Saves `issue` over whatever was there for its week.
+    pub async fn save(&self, issue: &Issue) -> Result<()> {
+        let (week, published) = (issue.week, issue.published);
+        let json = serde_json::to_string(issue)?;
+        self.0
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO digests (week, issue, published) VALUES (?1, ?2, ?3)
+                     ON CONFLICT (week) DO UPDATE SET
+                         issue = excluded.issue,
+                         published = excluded.published",
+                    params![week, json, published],
+                )?;
+                Ok(())
+            })
+            .await
+    }
+
+    /// Every issue, newest first. Drafts are left out unless `drafts` is
+    /// true.
+    pub async fn list(&self, drafts: bool) -> Result<Vec<Issue>> {
+        self.0
+            .call(move |conn| {
+                let mut stmt = conn.prepare_cached(
+                    "SELECT issue FROM digests
+                     WHERE ?1 OR published IS NOT NULL
+                     ORDER BY week DESC",
+                )?;
+                let rows = stmt.query_map(params![drafts], |row| row.get(0))?;
+                rows.map(|row| parse(row?)).collect()
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[tokio::test]
+    async fn drafts_and_published() -> Result<()> {
+        let digests = Storage::memory()?.digests();
+        let week = |d| NaiveDate::from_ymd_opt(2023, 7, d).unwrap();
+        let mut issue = Issue {
+            week: week(3),
+            ..Default::default()
+        };
+        digests.save(&issue).await?;
+        digests
+            .save(&Issue {
+                week: week(10),
+                ..Default::default()
+            })
+            .await?;
+        assert_eq!(digests.list(true).await?.len(), 2);
+        assert!(digests.list(false).await?.is_empty());
+
+        issue.intro = "Hi!".into();
+        issue.published = Some(Utc::now());
+        digests.save(&issue).await?;
+        assert_eq!(digests.get(week(3)).await?, Some(issue.clone()));
+        assert_eq!(digests.list(false).await?, vec![issue]);
+        assert_eq!(digests.list(true).await?[0].week, week(10));
+        assert_eq!(digests.get(week(17)).await?, None);
+        Ok(())
+    }
+}
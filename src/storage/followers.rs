@@ -0,0 +1,102 @@
+//! Who follows the site's fediverse actor, see
+//! [crate::activitypub::followers].
+
+use super::Storage;
+use crate::activitypub::followers::Follower;
+use color_eyre::eyre::Result;
+use rusqlite::params;
+use std::collections::BTreeMap;
+
+#[derive(Clone)]
+pub struct Followers(pub(super) Storage);
+
+impl Followers {
+    /// Every follower, by their ID.
+    pub async fn all(&self) -> Result<BTreeMap<String, Follower>> {
+        self.0
+            .call(|conn| {
+                let mut stmt =
+                    conn.prepare_cached("SELECT id, inbox, shared_inbox FROM followers")?;
+                let rows = stmt.query_map([], |row| {
+                    let follower = Follower {
+                        id: row.get(0)?,
+                        inbox: row.get(1)?,
+                        shared_inbox: row.get(2)?,
+                    };
+                    Ok((follower.id.clone(), follower))
+                })?;
+                rows.collect()
+            })
+            .await
+    }
+
+    /// Saves each follower over what was there for them.
+    pub async fn add(&self, followers: Vec<Follower>) -> Result<()> {
+        self.0
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                {
+                    let mut stmt = tx.prepare_cached(
+                        "INSERT INTO followers (id, inbox, shared_inbox) VALUES (?1, ?2, ?3)
+                         ON CONFLICT (id) DO UPDATE SET
+                             inbox = excluded.inbox,
+                             shared_inbox = excluded.shared_inbox",
+                    )?;
+                    for f in &followers {
+                        stmt.execute(params![f.id, f.inbox, f.shared_inbox])?;
+                    }
+                }
+                tx.commit()
+            })
+            .await
+    }
+
+    pub async fn remove(&self, id: &str) -> Result<()> {
+        let id = id.to_string();
+        self.0
+            .call(move |conn| {
+                conn.execute("DELETE FROM followers WHERE id = ?1", params![id])?;
+                Ok(())
+            })
+            .await
+    }
+
+    pub async fn is_empty(&self) -> Result<bool> {
+        self.0
+            .call(|conn| {
+                conn.query_row("SELECT NOT EXISTS (SELECT 1 FROM followers)", [], |row| {
+                    row.get(0)
+                })
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn add_and_remove() -> Result<()> {
+        let followers = Storage::memory()?.followers();
+        let mara = |inbox: &str| Follower {
+            id: "https://pony.social/users/mara".into(),
+            inbox: inbox.into(),
+            shared_inbox: None,
+        };
+
+        followers
+            .add(vec![mara("https://pony.social/users/mara/inbox")])
+            .await?;
+        followers
+            .add(vec![mara("https://pony.social/inbox")])
+            .await?;
+        let all = followers.all().await?;
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[&mara("").id].inbox, "https://pony.social/inbox");
+
+        followers.remove(&mara("").id).await?;
+        assert!(followers.is_empty().await?);
+        Ok(())
+    }
+}
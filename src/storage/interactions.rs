@@ -0,0 +1,110 @@
+//! Likes, boosts and replies to posts from the fediverse, see
+//! [crate::activitypub::store].
+
+use super::Storage;
+use crate::activitypub::store::{Interactions, Posts};
+use color_eyre::eyre::Result;
+use rusqlite::params;
+
+#[derive(Clone)]
+pub struct PostInteractions(pub(super) Storage);
+
+fn parse(json: String) -> rusqlite::Result<Interactions> {
+    serde_json::from_str(&json).map_err(|why| {
+        rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(why))
+    })
+}
+
+impl PostInteractions {
+    /// Everything, by the link of the post it's about.
+    pub async fn all(&self) -> Result<Posts> {
+        self.0
+            .call(|conn| {
+                let mut stmt =
+                    conn.prepare_cached("SELECT link, interactions FROM interactions")?;
+                let rows = stmt.query_map([], |row| Ok((row.get(0)?, parse(row.get(1)?)?)))?;
+                rows.collect()
+            })
+            .await
+    }
+
+    /// Saves the interactions of each post over what was there, all at once.
+    /// Posts without any are dropped.
+    pub async fn save(&self, posts: Vec<(String, Interactions)>) -> Result<()> {
+        let mut rows = vec![];
+        for (link, interactions) in posts {
+            let json = match interactions.is_empty() {
+                true => None,
+                false => Some(serde_json::to_string(&interactions)?),
+            };
+            rows.push((link, json));
+        }
+        self.0
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                {
+                    let mut upsert = tx.prepare_cached(
+                        "INSERT INTO interactions (link, interactions) VALUES (?1, ?2)
+                         ON CONFLICT (link) DO UPDATE SET interactions = excluded.interactions",
+                    )?;
+                    let mut delete =
+                        tx.prepare_cached("DELETE FROM interactions WHERE link = ?1")?;
+                    for (link, json) in &rows {
+                        match json {
+                            Some(json) => upsert.execute(params![link, json])?,
+                            None => delete.execute(params![link])?,
+                        };
+                    }
+                }
+                tx.commit()
+            })
+            .await
+    }
+
+    pub async fn is_empty(&self) -> Result<bool> {
+        self.0
+            .call(|conn| {
+                conn.query_row(
+                    "SELECT NOT EXISTS (SELECT 1 FROM interactions)",
+                    [],
+                    |row| row.get(0),
+                )
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activitypub::store::{Profile, Reaction};
+
+    #[tokio::test]
+    async fn save_and_drop() -> Result<()> {
+        let interactions = Storage::memory()?.interactions();
+        assert!(interactions.is_empty().await?);
+
+        let liked = Interactions {
+            likes: vec![Reaction {
+                id: "https://pony.social/likes/1".into(),
+                actor: Profile::default(),
+            }],
+            ..Default::default()
+        };
+        interactions
+            .save(vec![
+                ("blog/foo".into(), liked.clone()),
+                ("blog/bar".into(), Interactions::default()),
+            ])
+            .await?;
+        let all = interactions.all().await?;
+        assert_eq!(all.len(), 1);
+        assert_eq!(all["blog/foo"], liked);
+
+        interactions
+            .save(vec![("blog/foo".into(), Interactions::default())])
+            .await?;
+        assert!(interactions.is_empty().await?);
+        Ok(())
+    }
+}
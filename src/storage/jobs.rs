@@ -0,0 +1,65 @@
+//! When each background job last ran, see [crate::jobs].
+
+use super::Storage;
+use crate::jobs::JobState;
+use color_eyre::eyre::Result;
+use rusqlite::params;
+use std::collections::HashMap;
+
+#[derive(Clone)]
+pub struct JobStates(pub(super) Storage);
+
+impl JobStates {
+    /// Every job's state, by name.
+    pub async fn all(&self) -> Result<HashMap<String, JobState>> {
+        self.0
+            .call(|conn| {
+                let mut stmt = conn.prepare_cached(
+                    "SELECT name, last_run, last_success, last_error, runs, failures
+                     FROM job_states",
+                )?;
+                let rows = stmt.query_map([], |row| {
+                    Ok((
+                        row.get(0)?,
+                        JobState {
+                            last_run: row.get(1)?,
+                            last_success: row.get(2)?,
+                            last_error: row.get(3)?,
+                            runs: row.get::<_, i64>(4)? as u64,
+                            failures: row.get::<_, i64>(5)? as u64,
+                        },
+                    ))
+                })?;
+                rows.collect()
+            })
+            .await
+    }
+
+    /// Saves how the job called `name` is doing.
+    pub async fn save(&self, name: &str, state: &JobState) -> Result<()> {
+        let (name, state) = (name.to_string(), state.clone());
+        self.0
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO job_states (name, last_run, last_success, last_error, runs, failures)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                     ON CONFLICT (name) DO UPDATE SET
+                         last_run = excluded.last_run,
+                         last_success = excluded.last_success,
+                         last_error = excluded.last_error,
+                         runs = excluded.runs,
+                         failures = excluded.failures",
+                    params![
+                        name,
+                        state.last_run,
+                        state.last_success,
+                        state.last_error,
+                        state.runs as i64,
+                        state.failures as i64,
+                    ],
+                )?;
+                Ok(())
+            })
+            .await
+    }
+}
@@ -0,0 +1,232 @@
+//! The site's SQLite database, for what changes while it runs. Each kind of
+//! data gets a typed repository, like [Reads] for analytics or [JobStates]
+//! for background jobs, so features don't each invent a file format.
+//!
+//! The schema is built by [MIGRATIONS], in order. The database's
+//! `user_version` is how many of them have run, so new ones go on the end
+//! and old ones are never changed.
+
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use rusqlite::{backup::Progress, Connection, DatabaseName};
+use serde::de::DeserializeOwned;
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use tokio::fs;
+
+pub mod digests;
+pub mod followers;
+pub mod interactions;
+pub mod jobs;
+pub mod reads;
+pub mod subscribers;
+
+pub use digests::Digests;
+pub use followers::Followers;
+pub use interactions::PostInteractions;
+pub use jobs::JobStates;
+pub use reads::Reads;
+pub use subscribers::SubscriberCounts;
+
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE reads (
+        day TEXT NOT NULL,
+        link TEXT NOT NULL,
+        count INTEGER NOT NULL,
+        PRIMARY KEY (day, link)
+    );",
+    "CREATE TABLE job_states (
+        name TEXT PRIMARY KEY,
+        last_run TEXT,
+        last_success TEXT,
+        last_error TEXT,
+        runs INTEGER NOT NULL,
+        failures INTEGER NOT NULL
+    );",
+    "CREATE TABLE digests (
+        week TEXT PRIMARY KEY,
+        issue TEXT NOT NULL,
+        published TEXT
+    );",
+    "CREATE TABLE subscribers (
+        day TEXT PRIMARY KEY,
+        feeds INTEGER NOT NULL,
+        fediverse INTEGER NOT NULL
+    );",
+    "CREATE TABLE interactions (
+        link TEXT PRIMARY KEY,
+        interactions TEXT NOT NULL
+    );",
+    "CREATE TABLE followers (
+        id TEXT PRIMARY KEY,
+        inbox TEXT NOT NULL,
+        shared_inbox TEXT
+    );",
+    "CREATE TABLE yearly_reads (
+        year INTEGER NOT NULL,
+        link TEXT NOT NULL,
+        count INTEGER NOT NULL,
+        PRIMARY KEY (year, link)
+    );
+    INSERT INTO yearly_reads (year, link, count)
+        SELECT CAST(substr(day, 1, 4) AS INTEGER), link, SUM(count)
+        FROM reads GROUP BY 1, 2;",
+];
+
+/// Runs the migrations the database hasn't had yet and returns how many.
+fn migrate(conn: &mut Connection) -> Result<usize> {
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    let version = version as usize;
+    if version > MIGRATIONS.len() {
+        return Err(eyre!(
+            "the database has had {version} migrations, but this version of the site only knows {}",
+            MIGRATIONS.len()
+        ));
+    }
+    for (i, sql) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(sql)
+            .wrap_err_with(|| format!("can't run migration {}", i + 1))?;
+        tx.pragma_update(None, "user_version", (i + 1) as i64)?;
+        tx.commit()?;
+    }
+    Ok(MIGRATIONS.len() - version)
+}
+
+/// A connection to the database. Clones share it.
+#[derive(Clone)]
+pub struct Storage {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl Storage {
+    /// Opens the database at `path`, making it if it isn't there, and
+    /// brings its schema up to date.
+    pub async fn open(path: PathBuf) -> Result<Self> {
+        tokio::task::spawn_blocking(move || {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let conn = Connection::open(&path)
+                .wrap_err_with(|| format!("can't open database {}", path.display()))?;
+            conn.pragma_update(None, "journal_mode", "WAL")?;
+            Self::new(conn)
+        })
+        .await?
+    }
+
+    /// A database that only lasts as long as the process, for tests.
+    pub fn memory() -> Result<Self> {
+        Self::new(Connection::open_in_memory()?)
+    }
+
+    fn new(mut conn: Connection) -> Result<Self> {
+        let ran = migrate(&mut conn)?;
+        if ran != 0 {
+            info!("storage: ran {ran} migrations");
+        }
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Runs `f` with the connection on a thread where it can block.
+    pub async fn call<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let conn = self.conn.clone();
+        Ok(tokio::task::spawn_blocking(move || f(&mut conn.lock().unwrap())).await??)
+    }
+
+    /// Copies the database as it is right now to `path`, see
+    /// [crate::backup].
+    pub async fn snapshot(&self, path: PathBuf) -> Result<()> {
+        self.call(move |conn| conn.backup(DatabaseName::Main, path, None))
+            .await
+    }
+
+    /// Replaces everything in the database with the copy at `path`. A copy
+    /// from an older version of the site is migrated.
+    pub async fn restore(&self, path: PathBuf) -> Result<()> {
+        self.call(move |conn| conn.restore(DatabaseName::Main, path, None::<fn(Progress)>))
+            .await?;
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || migrate(&mut conn.lock().unwrap())).await??;
+        Ok(())
+    }
+
+    pub fn reads(&self) -> Reads {
+        Reads(self.clone())
+    }
+
+    pub fn jobs(&self) -> JobStates {
+        JobStates(self.clone())
+    }
+
+    pub fn digests(&self) -> Digests {
+        Digests(self.clone())
+    }
+
+    pub fn subscriber_counts(&self) -> SubscriberCounts {
+        SubscriberCounts(self.clone())
+    }
+
+    pub fn interactions(&self) -> PostInteractions {
+        PostInteractions(self.clone())
+    }
+
+    pub fn followers(&self) -> Followers {
+        Followers(self.clone())
+    }
+}
+
+/// Reads a JSON file from before the database, if there is one, and moves
+/// it out of the way so it's only imported once.
+pub async fn import_json<T: DeserializeOwned>(path: &Path) -> Result<Option<T>> {
+    let data = match fs::read(path).await {
+        Ok(data) => data,
+        Err(_) => return Ok(None),
+    };
+    let value = serde_json::from_slice(&data)
+        .wrap_err_with(|| format!("can't import {}", path.display()))?;
+    let mut imported = path.as_os_str().to_owned();
+    imported.push(".imported");
+    fs::rename(path, &imported).await?;
+    info!("storage: imported {} into the database", path.display());
+    Ok(Some(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrations() -> Result<()> {
+        let mut conn = Connection::open_in_memory()?;
+        assert_eq!(migrate(&mut conn)?, MIGRATIONS.len());
+        assert_eq!(migrate(&mut conn)?, 0);
+
+        conn.pragma_update(None, "user_version", 1000)?;
+        assert!(migrate(&mut conn).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn imports_once() -> Result<()> {
+        let path =
+            std::env::temp_dir().join(format!("xesite-import-{}.json", uuid::Uuid::new_v4()));
+        fs::write(&path, "[1, 2, 3]").await?;
+
+        let got: Option<Vec<u8>> = import_json(&path).await?;
+        assert_eq!(got, Some(vec![1, 2, 3]));
+        assert_eq!(import_json::<Vec<u8>>(&path).await?, None);
+
+        let mut imported = path.into_os_string();
+        imported.push(".imported");
+        fs::remove_file(imported).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,130 @@
+//! How many times each post was read per day, see [crate::analytics]. Days are
+//! pruned, so each year's totals are also kept in `yearly_reads`, which isn't.
+
+use super::Storage;
+use chrono::{Datelike, NaiveDate};
+use color_eyre::eyre::Result;
+use rusqlite::params;
+use std::collections::{BTreeMap, HashMap};
+
+/// Read counts by day, then by link.
+pub type Days = BTreeMap<NaiveDate, HashMap<String, u64>>;
+
+#[derive(Clone)]
+pub struct Reads(pub(super) Storage);
+
+impl Reads {
+    /// Adds `days` to the counts that are already there, and to their years.
+    pub async fn add(&self, days: Days) -> Result<()> {
+        self.0
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                {
+                    let mut daily = tx.prepare_cached(
+                        "INSERT INTO reads (day, link, count) VALUES (?1, ?2, ?3)
+                         ON CONFLICT (day, link) DO UPDATE SET count = count + excluded.count",
+                    )?;
+                    let mut yearly = tx.prepare_cached(
+                        "INSERT INTO yearly_reads (year, link, count) VALUES (?1, ?2, ?3)
+                         ON CONFLICT (year, link) DO UPDATE SET count = count + excluded.count",
+                    )?;
+                    for (day, counts) in &days {
+                        for (link, count) in counts {
+                            daily.execute(params![day, link, *count as i64])?;
+                            yearly.execute(params![day.year(), link, *count as i64])?;
+                        }
+                    }
+                }
+                tx.commit()
+            })
+            .await
+    }
+
+    /// The counts from `since` on.
+    pub async fn since(&self, since: NaiveDate) -> Result<Days> {
+        self.0
+            .call(move |conn| {
+                let mut stmt =
+                    conn.prepare_cached("SELECT day, link, count FROM reads WHERE day >= ?1")?;
+                let mut days = Days::new();
+                let rows = stmt.query_map(params![since], |row| {
+                    Ok((row.get(0)?, row.get(1)?, row.get::<_, i64>(2)?))
+                })?;
+                for row in rows {
+                    let (day, link, count): (NaiveDate, String, i64) = row?;
+                    days.entry(day).or_default().insert(link, count as u64);
+                }
+                Ok(days)
+            })
+            .await
+    }
+
+    /// How many times each link was read in `year`.
+    pub async fn year(&self, year: i32) -> Result<HashMap<String, u64>> {
+        self.0
+            .call(move |conn| {
+                let mut stmt =
+                    conn.prepare_cached("SELECT link, count FROM yearly_reads WHERE year = ?1")?;
+                let rows = stmt.query_map(params![year], |row| {
+                    Ok((row.get(0)?, row.get::<_, i64>(1)? as u64))
+                })?;
+                rows.collect()
+            })
+            .await
+    }
+
+    /// Forgets the days before `before` and returns how many counts there
+    /// were.
+    pub async fn prune(&self, before: NaiveDate) -> Result<usize> {
+        self.0
+            .call(move |conn| conn.execute("DELETE FROM reads WHERE day < ?1", params![before]))
+            .await
+    }
+
+    pub async fn is_empty(&self) -> Result<bool> {
+        self.0
+            .call(|conn| {
+                conn.query_row("SELECT NOT EXISTS (SELECT 1 FROM reads)", [], |row| {
+                    row.get(0)
+                })
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn counts_add_up() -> Result<()> {
+        let reads = Storage::memory()?.reads();
+        let day = |d| NaiveDate::from_ymd_opt(2023, 7, d).unwrap();
+        assert!(reads.is_empty().await?);
+
+        reads
+            .add(Days::from([(
+                day(1),
+                HashMap::from([("blog/a".to_string(), 2)]),
+            )]))
+            .await?;
+        reads
+            .add(Days::from([
+                (day(1), HashMap::from([("blog/a".to_string(), 3)])),
+                (day(2), HashMap::from([("blog/b".to_string(), 1)])),
+            ]))
+            .await?;
+        assert!(!reads.is_empty().await?);
+        assert_eq!(reads.since(day(1)).await?[&day(1)]["blog/a"], 5);
+        assert_eq!(reads.since(day(2)).await?.len(), 1);
+
+        assert_eq!(reads.prune(day(2)).await?, 1);
+        assert!(!reads.since(day(1)).await?.contains_key(&day(1)));
+
+        // Pruning days leaves the year alone.
+        let year = reads.year(2023).await?;
+        assert_eq!((year["blog/a"], year["blog/b"]), (5, 1));
+        assert!(reads.year(2022).await?.is_empty());
+        Ok(())
+    }
+}
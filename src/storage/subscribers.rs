@@ -0,0 +1,86 @@
+//! How many subscribers the site had each day, see [crate::subscribers].
+
+use super::Storage;
+use chrono::NaiveDate;
+use color_eyre::eyre::Result;
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+/// One day's estimate.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Count {
+    pub day: NaiveDate,
+    /// What feed readers said they fetch the feeds for.
+    pub feeds: u64,
+    /// The fediverse account's followers.
+    pub fediverse: u64,
+}
+
+impl Count {
+    pub fn total(&self) -> u64 {
+        self.feeds.saturating_add(self.fediverse)
+    }
+}
+
+#[derive(Clone)]
+pub struct SubscriberCounts(pub(super) Storage);
+
+impl SubscriberCounts {
+    /// Saves `count` over whatever was there for its day.
+    pub async fn record(&self, count: Count) -> Result<()> {
+        self.0
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO subscribers (day, feeds, fediverse) VALUES (?1, ?2, ?3)
+                     ON CONFLICT (day) DO UPDATE SET
+                         feeds = excluded.feeds,
+                         fediverse = excluded.fediverse",
+                    params![count.day, count.feeds as i64, count.fediverse as i64],
+                )?;
+                Ok(())
+            })
+            .await
+    }
+
+    /// The counts from `since` on, oldest first.
+    pub async fn since(&self, since: NaiveDate) -> Result<Vec<Count>> {
+        self.0
+            .call(move |conn| {
+                let mut stmt = conn.prepare_cached(
+                    "SELECT day, feeds, fediverse FROM subscribers WHERE day >= ?1 ORDER BY day",
+                )?;
+                let rows = stmt.query_map(params![since], |row| {
+                    Ok(Count {
+                        day: row.get(0)?,
+                        feeds: row.get::<_, i64>(1)? as u64,
+                        fediverse: row.get::<_, i64>(2)? as u64,
+                    })
+                })?;
+                rows.collect()
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn one_per_day() -> Result<()> {
+        let counts = Storage::memory()?.subscriber_counts();
+        let day = |d| NaiveDate::from_ymd_opt(2023, 7, d).unwrap();
+        let count = |d, feeds| Count {
+            day: day(d),
+            feeds,
+            fediverse: 10,
+        };
+
+        counts.record(count(2, 5)).await?;
+        counts.record(count(1, 3)).await?;
+        counts.record(count(2, 7)).await?;
+        assert_eq!(counts.since(day(1)).await?, vec![count(1, 3), count(2, 7)]);
+        assert_eq!(counts.since(day(2)).await?[0].total(), 17);
+        Ok(())
+    }
+}
@@ -0,0 +1,241 @@
+//! Estimates of how many people follow the site. Feed readers that fetch a
+//! feed for many people, like Feedly or Inoreader, say how many in their
+//! User-Agent, and the fediverse account knows its followers. The
+//! `subscribers` job adds them up once a day and keeps the totals in the
+//! database, so the feeds page can show a badge and how it's changed.
+//!
+//! Readers that fetch feeds for themselves don't say anything, so this is
+//! always an underestimate.
+
+use crate::storage::{subscribers::Count, SubscriberCounts};
+use chrono::{prelude::*, Duration};
+use color_eyre::eyre::Result;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::{
+    collections::HashMap,
+    sync::{Mutex, RwLock},
+};
+
+/// Reports older than this are from readers that stopped fetching.
+const FRESH_DAYS: i64 = 7;
+
+/// How far back the badge looks for its trend.
+pub const TREND_DAYS: i64 = 30;
+
+/// The feed readers whose counts are believed, by the first word of their
+/// User-Agent. Anyone can claim to have subscribers, so only services known to
+/// fetch feeds for many people count.
+const AGGREGATORS: &[&str] = &[
+    "Feedly",
+    "Inoreader",
+    "NewsBlur",
+    "Feedbin",
+    "BazQux",
+    "theoldreader.com",
+];
+
+/// The most subscribers one reader of one feed can count for. Bigger reports
+/// are made up or broken.
+const MAX_REPORTED: u64 = 100_000;
+
+lazy_static! {
+    static ref SUBSCRIBERS: Regex = Regex::new(r"(\d+) (?:subscribers|readers)").unwrap();
+}
+
+/// The feed reader that sent `user_agent` and how many subscribers it says
+/// it has, if it's one of the [AGGREGATORS] and says. Counts are capped at
+/// [MAX_REPORTED].
+pub fn reported(user_agent: &str) -> Option<(String, u64)> {
+    let count: u64 = SUBSCRIBERS.captures(user_agent)?[1].parse().ok()?;
+    let name = user_agent
+        .split(|c: char| c == '/' || c.is_whitespace())
+        .next()
+        .filter(|name| AGGREGATORS.contains(name))?;
+    Some((name.to_string(), count.min(MAX_REPORTED)))
+}
+
+/// The newest total and how much it changed over [TREND_DAYS].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Trend {
+    pub readers: u64,
+    /// `None` until there's a count from before today.
+    pub change: Option<i64>,
+}
+
+/// The [Trend] in `counts`, which are oldest first.
+pub fn trend(counts: &[Count]) -> Option<Trend> {
+    let (first, latest) = (counts.first()?, counts.last()?);
+    Some(Trend {
+        readers: latest.total(),
+        change: (first.day != latest.day).then(|| latest.total() as i64 - first.total() as i64),
+    })
+}
+
+pub struct Tracker {
+    counts: SubscriberCounts,
+    /// The newest report from each reader of each feed. These are only in
+    /// memory, and readers that fetch every hour or so fill them back in
+    /// after a restart.
+    reports: Mutex<HashMap<(String, String), (u64, DateTime<Utc>)>>,
+    /// The counts from the last [TREND_DAYS], for templates.
+    recent: RwLock<Vec<Count>>,
+    started: DateTime<Utc>,
+}
+
+impl Tracker {
+    pub async fn new(counts: SubscriberCounts) -> Result<Self> {
+        let tracker = Self {
+            counts,
+            reports: Mutex::new(HashMap::new()),
+            recent: RwLock::new(vec![]),
+            started: Utc::now(),
+        };
+        tracker.reload().await?;
+        Ok(tracker)
+    }
+
+    /// Notes what the reader that sent `user_agent` says about the feed
+    /// `feed`.
+    pub fn saw(&self, feed: &str, user_agent: &str) {
+        if let Some((reader, count)) = reported(user_agent) {
+            self.reports
+                .lock()
+                .unwrap()
+                .insert((feed.to_string(), reader), (count, Utc::now()));
+        }
+    }
+
+    /// How many subscribers readers have reported lately, over all feeds.
+    fn feeds(&self, now: DateTime<Utc>) -> u64 {
+        let mut reports = self.reports.lock().unwrap();
+        reports.retain(|_, (_, seen)| now - *seen < Duration::days(FRESH_DAYS));
+        reports
+            .values()
+            .fold(0, |sum: u64, (count, _)| sum.saturating_add(*count))
+    }
+
+    /// Saves today's estimate, with `fediverse` followers.
+    #[instrument(skip(self), err)]
+    pub async fn refresh(&self, fediverse: u64) -> Result<()> {
+        let now = Utc::now();
+        let mut feeds = self.feeds(now);
+        // Just after a restart most readers haven't fetched again yet, so
+        // don't let the count drop until they've had a day to.
+        if now - self.started < Duration::days(1) {
+            let last = self.recent.read().unwrap().last().map(|c| c.feeds);
+            feeds = feeds.max(last.unwrap_or_default());
+        }
+        let count = Count {
+            day: now.date_naive(),
+            feeds,
+            fediverse,
+        };
+        self.counts.record(count).await?;
+        info!(
+            "subscribers: {} from feeds, {} on the fediverse",
+            count.feeds, count.fediverse
+        );
+        self.reload().await
+    }
+
+    async fn reload(&self) -> Result<()> {
+        let since = Utc::now().date_naive() - Duration::days(TREND_DAYS);
+        *self.recent.write().unwrap() = self.counts.since(since).await?;
+        Ok(())
+    }
+
+    /// The badge's numbers, as of the last refresh.
+    pub fn trend(&self) -> Option<Trend> {
+        trend(&self.recent.read().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Storage;
+
+    #[test]
+    fn reads_user_agents() {
+        assert_eq!(
+            reported("Feedly/1.0 (+http://www.feedly.com/fetcher.html; 123 subscribers; like FeedFetcher-Google)"),
+            Some(("Feedly".into(), 123))
+        );
+        assert_eq!(
+            reported("NewsBlur Feed Fetcher - 12 subscribers - http://www.newsblur.com/site/1/xe"),
+            Some(("NewsBlur".into(), 12))
+        );
+        assert_eq!(
+            reported("Inoreader/1.0 (+http://www.inoreader.com/feed-fetcher; 4 subscribers; )"),
+            Some(("Inoreader".into(), 4))
+        );
+        assert_eq!(reported("Mozilla/5.0 (X11; Linux x86_64)"), None);
+        assert_eq!(reported("MyScraper/1.0 (1000000 subscribers)"), None);
+        assert_eq!(
+            reported("Feedly/1.0 (+http://www.feedly.com/fetcher.html; 99999999999999999999 subscribers; )"),
+            None
+        );
+        assert_eq!(
+            reported("Feedly/1.0 (+http://www.feedly.com/fetcher.html; 18446744073709551615 subscribers; )"),
+            Some(("Feedly".into(), MAX_REPORTED))
+        );
+    }
+
+    #[test]
+    fn trends() {
+        let day = |d| NaiveDate::from_ymd_opt(2023, 7, d).unwrap();
+        let count = |d, feeds| Count {
+            day: day(d),
+            feeds,
+            fediverse: 10,
+        };
+        assert_eq!(trend(&[]), None);
+        assert_eq!(
+            trend(&[count(1, 5)]),
+            Some(Trend {
+                readers: 15,
+                change: None
+            })
+        );
+        assert_eq!(
+            trend(&[count(1, 5), count(2, 3), count(3, 9)]),
+            Some(Trend {
+                readers: 19,
+                change: Some(4)
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn adds_up_feeds() -> Result<()> {
+        let tracker = Tracker::new(Storage::memory()?.subscriber_counts()).await?;
+        assert_eq!(tracker.trend(), None);
+
+        let feedly = "Feedly/1.0 (+http://www.feedly.com/fetcher.html; 100 subscribers; )";
+        tracker.saw("rss", feedly);
+        tracker.saw("rss", &feedly.replace("100", "120"));
+        tracker.saw(
+            "atom",
+            "Inoreader/1.0 (+http://www.inoreader.com/feed-fetcher; 5 subscribers; )",
+        );
+        tracker.saw("rss", "curl/8.0");
+        tracker.saw("rss", "curl/8.0 (5000 subscribers)");
+        tracker.refresh(30).await?;
+
+        assert_eq!(
+            tracker.trend(),
+            Some(Trend {
+                readers: 155,
+                change: None
+            })
+        );
+        assert_eq!(tracker.feeds(Utc::now() + Duration::days(FRESH_DAYS)), 0);
+
+        // A restart doesn't lose the readers that haven't fetched since.
+        let restarted = Tracker::new(tracker.counts.clone()).await?;
+        restarted.refresh(31).await?;
+        assert_eq!(restarted.trend().unwrap().readers, 156);
+        Ok(())
+    }
+}
@@ -0,0 +1,294 @@
+//! The pages of the admin area, see [crate::admin].
+
+use super::base;
+use crate::{
+    activitypub::store::Reply, admin::PREFIX, app::StatusService, auth, digest::Issue, jobs,
+    status::Check,
+};
+use maud::{html, Markup, PreEscaped};
+
+/// Everything the dashboard shows, gathered by the handler.
+pub struct Dashboard {
+    /// Who is logged in, or `None` for scripts that sent the token.
+    pub me: Option<String>,
+    /// What the buttons send back, see [crate::auth::CsrfForm].
+    pub csrf: String,
+    /// What was just done, such as "Removed 3 cards".
+    pub notice: Option<String>,
+    pub replies: Vec<(String, Reply)>,
+    /// Webmentions as `(post link, source, title)`.
+    pub mentions: Vec<(String, String, Option<String>)>,
+    pub popular_month: Vec<(String, u64)>,
+    pub popular_quarter: Vec<(String, u64)>,
+    /// How many deliveries are queued and how many followers there are, if
+    /// the fediverse account is turned on.
+    pub fediverse: Option<(usize, usize)>,
+    pub services: Vec<(StatusService, Vec<Check>)>,
+    pub jobs: Vec<jobs::Status>,
+    pub og_cards: usize,
+    pub sticker_packs: usize,
+    /// Whether backups are turned on, see [crate::backup].
+    pub backups: bool,
+    /// Whether pages are being served from the export, see
+    /// [crate::maintenance].
+    pub maintenance: bool,
+}
+
+/// A button that POSTs `fields` to `action`.
+fn button(csrf: &str, action: &str, fields: &[(&str, &str)], label: &str) -> Markup {
+    html! {
+        form.admin-action method="post" action=(action) {
+            input type="hidden" name="csrf" value=(csrf);
+            @for (name, value) in fields {
+                input type="hidden" name=(name) value=(value);
+            }
+            button type="submit" { (label) }
+        }
+    }
+}
+
+fn popular(title: &str, posts: &[(String, u64)]) -> Markup {
+    html! {
+        h3 { (title) }
+        @if posts.is_empty() {
+            p { "Nothing has been read yet." }
+        } @else {
+            ol {
+                @for (link, count) in posts {
+                    li { a href={"/" (link)} { (link) } " (" (count) ")" }
+                }
+            }
+        }
+    }
+}
+
+pub fn dashboard(d: &Dashboard) -> Markup {
+    base(
+        Some("Admin"),
+        None,
+        html! {
+            h1 { "Admin" }
+            @if let Some(notice) = &d.notice {
+                .notice { (notice) }
+            }
+            @if let Some(me) = &d.me {
+                p {
+                    "Logged in as " (me) ". "
+                    (button(&d.csrf, &format!("{}/logout", auth::PREFIX), &[], "Log out"))
+                }
+            }
+
+            h2 { "Fediverse replies" }
+            @if d.replies.is_empty() {
+                p { "No replies." }
+            }
+            @for (link, reply) in &d.replies {
+                .admin-reply {
+                    p {
+                        a href=(reply.actor.url) { (reply.actor.name) }
+                        " on "
+                        a href={"/" (link)} { (link) }
+                        @if let Some(published) = &reply.published {
+                            " at " (published)
+                        }
+                        " ("
+                        a href=(reply.url) { "original" }
+                        ")"
+                    }
+                    blockquote { (PreEscaped(&reply.content)) }
+                    (button(&d.csrf, &format!("{PREFIX}/replies/remove"), &[("id", &reply.id)], "Remove this reply"))
+                    (button(&d.csrf, &format!("{PREFIX}/actors/forget"), &[("actor", &reply.actor.id)], "Remove everything from this account"))
+                }
+            }
+
+            h2 { "Webmentions" }
+            p {
+                "Webmentions are moderated in mi. These are the ones the posts were loaded with."
+            }
+            @if d.mentions.is_empty() {
+                p { "No webmentions." }
+            } @else {
+                ul {
+                    @for (link, source, title) in &d.mentions {
+                        li {
+                            a href=(source) { (title.as_deref().unwrap_or(source)) }
+                            " on "
+                            a href={"/" (link)} { (link) }
+                        }
+                    }
+                }
+            }
+
+            h2 { "Analytics" }
+            (popular("Last 30 days", &d.popular_month))
+            (popular("Last 90 days", &d.popular_quarter))
+
+            h2 { "Background jobs" }
+            dl {
+                @for job in &d.jobs {
+                    dt { (job.name) " (" (job.schedule) ")" }
+                    dd {
+                        @if job.running {
+                            "running now"
+                        } @else {
+                            "next at " (job.next_run.to_rfc3339())
+                        }
+                        @if let Some(last) = &job.state.last_run {
+                            ", last ran at " (last.to_rfc3339())
+                        }
+                        ", " (job.state.failures) " of " (job.state.runs) " runs failed"
+                        @if let Some(error) = &job.state.last_error {
+                            ": " (error)
+                        }
+                    }
+                }
+                @if let Some((queued, followers)) = d.fediverse {
+                    dt { "Fediverse deliveries queued" }
+                    dd { (queued) }
+                    dt { "Followers" }
+                    dd { (followers) }
+                }
+                @for (svc, checks) in &d.services {
+                    dt { "Status check for " (svc.name) }
+                    dd {
+                        @match checks.last() {
+                            Some(check) => {
+                                (if check.up { "up" } else { "down" })
+                                " at " (check.time.to_rfc3339())
+                                @if let Some(error) = &check.error {
+                                    ": " (error)
+                                }
+                            },
+                            None => "not checked yet",
+                        }
+                    }
+                }
+            }
+
+            h2 { "Weekly digest" }
+            p {
+                "Drafts are made every Monday by the "
+                code { "digest" }
+                " job. "
+                a href={(PREFIX) "/digests"} { "Edit and publish them" }
+                "."
+            }
+
+            h2 { "Caches" }
+            dl {
+                dt { "Link preview cards" }
+                dd { (d.og_cards) (button(&d.csrf, &format!("{PREFIX}/purge"), &[("cache", "og")], "Purge")) }
+                dt { "Sticker packs" }
+                dd { (d.sticker_packs) (button(&d.csrf, &format!("{PREFIX}/purge"), &[("cache", "stickers")], "Purge")) }
+            }
+
+            h2 { "Maintenance mode" }
+            @if d.maintenance {
+                p { "On. Pages are being served from the static export." }
+                (button(&d.csrf, &format!("{PREFIX}/maintenance"), &[("mode", "off")], "Turn off"))
+            } @else {
+                p { "Off. Turn it on to serve pages from the static export while the site is being worked on." }
+                (button(&d.csrf, &format!("{PREFIX}/maintenance"), &[("mode", "on")], "Turn on"))
+            }
+
+            @if d.backups {
+                h2 { "Backups" }
+                p {
+                    "The database and state files are backed up on a schedule, see the "
+                    code { "backup" }
+                    " job. Downloads are encrypted with the same key."
+                }
+                (button(&d.csrf, &format!("{PREFIX}/backup"), &[("to", "bucket")], "Back up now"))
+                (button(&d.csrf, &format!("{PREFIX}/backup"), &[("to", "download")], "Download a backup"))
+            }
+        },
+    )
+}
+
+pub fn digests(csrf: &str, notice: Option<&str>, issues: &[Issue]) -> Markup {
+    base(
+        Some("Weekly digest"),
+        None,
+        html! {
+            h1 { "Weekly digest" }
+            p { a href=(PREFIX) { "Back to the dashboard" } }
+            @if let Some(notice) = notice {
+                .notice { (notice) }
+            }
+            (button(csrf, &format!("{PREFIX}/digests"), &[], "Draft last week's issue now"))
+            @if issues.is_empty() {
+                p { "There aren't any issues yet." }
+            } @else {
+                ul {
+                    @for issue in issues {
+                        li {
+                            a href={(PREFIX) "/digests/" (issue.week)} { (issue.title()) }
+                            @match issue.published {
+                                Some(published) => { " (published " (published.to_rfc3339()) ")" },
+                                None => " (draft)",
+                            }
+                        }
+                    }
+                }
+            }
+        },
+    )
+}
+
+pub fn digest(csrf: &str, notice: Option<&str>, issue: &Issue) -> Markup {
+    let action = format!("{PREFIX}/digests/{}", issue.week);
+    base(
+        Some("Weekly digest"),
+        None,
+        html! {
+            h1 { (issue.title()) }
+            p { a href={(PREFIX) "/digests"} { "All issues" } }
+            @if let Some(notice) = notice {
+                .notice { (notice) }
+            }
+            form.admin-action method="post" action=(action) {
+                input type="hidden" name="csrf" value=(csrf);
+                label for="intro" { "Intro, in markdown" }
+                textarea #intro name="intro" rows="8" { (issue.intro) }
+                button type="submit" name="action" value="save" { "Save" }
+                @if issue.published.is_some() {
+                    button type="submit" name="action" value="unpublish" { "Save and unpublish" }
+                } @else {
+                    button type="submit" name="action" value="publish" { "Save and publish" }
+                }
+            }
+            p {
+                "Rebuilding gathers the week's posts, notes and replies again, keeping the intro."
+            }
+            (button(csrf, &action, &[("action", "rebuild")], "Rebuild"))
+            @if issue.published.is_some() {
+                p {
+                    a href={"/" (issue.link())} { "On the site" }
+                    " · "
+                    a href={"/" (issue.link()) "/email"} { "Email version" }
+                }
+            }
+
+            h2 { "What's in it" }
+            ul {
+                @for item in issue.posts.iter().chain(&issue.notes) {
+                    li {
+                        (item.title)
+                        (button(csrf, &action, &[("action", "drop"), ("item", &item.link)], "Leave out"))
+                    }
+                }
+                @for reply in &issue.replies {
+                    li {
+                        "Reply from " (reply.author) " on " (reply.title)
+                        (button(csrf, &action, &[("action", "drop"), ("item", &reply.url)], "Leave out"))
+                    }
+                }
+            }
+
+            h2 { "Preview" }
+            article.digest {
+                (super::digest::body(issue))
+            }
+        },
+    )
+}
@@ -0,0 +1,45 @@
+//! The login page, see [crate::auth].
+
+use super::base;
+use crate::{app::Config, auth::PREFIX};
+use maud::{html, Markup};
+
+/// Every way of logging in that's turned on. `next` is where to go after.
+pub fn login(cfg: &Config, next: &str, failed: Option<&str>) -> Markup {
+    base(
+        Some("Log in"),
+        None,
+        html! {
+            h1 { "Log in" }
+            @if let Some(why) = failed {
+                .warning { (why) }
+            }
+            @if let Some(oidc) = &cfg.auth.oidc {
+                form method="get" action={(PREFIX) "/oidc"} {
+                    input type="hidden" name="next" value=(next);
+                    button type="submit" { (oidc.name) }
+                }
+            }
+            @if cfg.auth.indie_auth {
+                form method="post" action={(PREFIX) "/indieauth"} {
+                    input type="hidden" name="next" value=(next);
+                    label for="me" { "Your website" }
+                    " "
+                    input #me type="url" name="me" placeholder="https://example.com/" autocomplete="url" required;
+                    " "
+                    button type="submit" { "Log in with IndieAuth" }
+                }
+            }
+            @if !cfg.admin_token.is_empty() {
+                form method="post" action={(PREFIX) "/token"} {
+                    input type="hidden" name="next" value=(next);
+                    label for="token" { "Admin token" }
+                    " "
+                    input #token type="password" name="token" autocomplete="current-password" required;
+                    " "
+                    button type="submit" { "Log in" }
+                }
+            }
+        },
+    )
+}
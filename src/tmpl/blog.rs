@@ -1,39 +1,129 @@
-use super::{base, nag};
-use crate::post::{schemaorg::Article, Post};
+use super::{base, base_for, nag, PageKind};
+use crate::{
+    activitypub::store::{Interactions, Reaction},
+    post::Post,
+};
 use maud::{html, Markup, PreEscaped};
-use xesite_templates::xeact_component;
+use xesite_templates::{
+    breadcrumbs,
+    context::{self, site},
+    og::OpenGraph,
+    xeact_component_with, Crumb, Hydrate,
+};
+use xesite_types::ReaderPrefs;
+
+/// Posts with fewer headings than this don't get a table of contents.
+const TOC_HEADINGS: usize = 3;
+
+/// How many posts are suggested at the bottom of a post.
+const RELATED_POSTS: usize = 3;
+
+/// The table of contents of a long post.
+fn toc(post: &Post) -> Markup {
+    html! {
+        @if post.toc.len() >= TOC_HEADINGS {
+            (xesite_markdown::toc::render(&post.toc))
+        }
+    }
+}
+
+/// What link previews of a post show. Posts by guests don't credit the
+/// site's fediverse account.
+fn open_graph(post: &Post) -> OpenGraph {
+    let fm = &post.front_matter;
+    let mut og = OpenGraph::new(&fm.title)
+        .url(site().url(&post.link))
+        .image(crate::og::image_for(post))
+        .published(post.date.to_rfc3339())
+        .author(fm.author.as_deref().unwrap_or("Xe Iaso"))
+        .reading_time(post.read_time_estimate_minutes);
+    if fm.author.is_none() {
+        og = og.creator(&site().mastodon.handle);
+    }
+    if let Some(video) = &fm.video {
+        og = og.video(video.clone());
+    }
+    if let Some(audio) = &fm.audio {
+        og = og.audio(audio.clone());
+    }
+    og
+}
 
+/// The post's link preview tags, which go in the `<head>`, and the rest of
+/// what describes it to other sites.
 fn post_metadata(post: &Post) -> Markup {
-    let art: Article = post.into();
-    let json = PreEscaped(serde_json::to_string(&art).unwrap());
+    context::open_graph(open_graph(post));
 
     html! {
-        meta name="twitter:card" content="summary";
-        meta name="twitter:site" content="@theprincessxena";
-        meta name="twitter:title" content={(post.front_matter.title)};
-        meta property="og:type" content="website";
-        meta property="og:title" content={(post.front_matter.title)};
-        meta property="og:site_name" content="Xe's Blog";
-        meta name="description" content={(post.front_matter.title) " - Xe's Blog"};
-        meta name="author" content="Xe Iaso";
+        @if !post.indexable() {
+            meta name="robots" content="noindex";
+        }
 
         @if let Some(redirect_to) = &post.front_matter.redirect_to {
             link rel="canonical" href=(redirect_to);
             meta http-equiv="refresh" content=(format!("0;URL='{redirect_to}'"));
+        } @else if let Some(origin) = &post.front_matter.originally_published {
+            link rel="canonical" href=(origin.url);
+        } @else {
+            link rel="canonical" href=(site().url(&post.link));
+        }
+        link rel="alternate" type="application/json+oembed" href=(crate::oembed::discovery_url(post)) title=(post.front_matter.title);
+
+        @if post.link.starts_with("talks/") {
+            (super::structured_data::talk(post))
         } @else {
-            link rel="canonical" href={"https://xeiaso.net/" (post.link)};
+            (super::structured_data::blog_posting(post))
         }
+    }
+}
+
+/// The sponsor slot at the end of a post, for readers who see ads.
+fn sponsor(post: &Post, prefs: ReaderPrefs) -> Markup {
+    if post.front_matter.skip_ads || !prefs.shows_ads() {
+        return html! {};
+    }
+    xesite_templates::sponsor_footer(post.slug(), site().sponsors.clone())
+}
 
-        script type="application/ld+json" {(json)}
+/// Where a post sits on the site: its section, its series if it has one, then
+/// the post itself.
+fn crumbs(post: &Post) -> Vec<Crumb> {
+    let mut crumbs = vec![];
+    if post.link.starts_with("talks/") {
+        crumbs.push(Crumb::new("Talks", "/talks"));
+    } else {
+        crumbs.push(Crumb::new("Blog", "/blog"));
+        if let Some(series) = &post.front_matter.series {
+            crumbs.push(Crumb::new(series, format!("/blog/series/{series}")));
+        }
     }
+    crumbs.push(Crumb::new(
+        &post.front_matter.title,
+        format!("/{}", post.link),
+    ));
+    crumbs
 }
 
 fn share_button(post: &Post) -> Markup {
-    return xeact_component("MastodonShareButton", serde_json::json!({
+    return xeact_component_with("MastodonShareButton", serde_json::json!({
         "title": post.front_matter.title,
         "series": post.front_matter.series,
-        "tags": post.front_matter.tags.as_ref().unwrap_or(&Vec::new())
-    }));
+        "tags": post.front_matter.tags.as_ref().unwrap_or(&Vec::new()),
+        "account": site().mastodon.handle,
+    }), Hydrate::Idle);
+}
+
+fn signature_badge(post: &Post) -> Markup {
+    let signatures = &post.signatures;
+    html! {
+        @if signatures.is_signed() {
+            (xesite_templates::signature_badge(
+                post.source_url(),
+                signatures.minisign.is_some(),
+                signatures.pgp.is_some(),
+            ))
+        }
+    }
 }
 
 fn twitch_vod(post: &Post) -> Markup {
@@ -52,34 +142,145 @@ fn twitch_vod(post: &Post) -> Markup {
     }
 }
 
-pub fn blog(post: &Post, body: PreEscaped<&String>, referer: Option<String>) -> Markup {
-    base(
+fn facepile(title: &str, reactions: &[Reaction]) -> Markup {
+    html! {
+        @if !reactions.is_empty() {
+            h4 { (title) " (" (reactions.len()) ")" }
+            p.facepile {
+                @for reaction in reactions {
+                    a href=(reaction.actor.url) title=(reaction.actor.name) {
+                        @if let Some(icon) = &reaction.actor.icon {
+                            img src=(icon) alt=(reaction.actor.name) loading="lazy" width="32" height="32";
+                        } @else {
+                            (reaction.actor.name)
+                        }
+                    }
+                    " "
+                }
+            }
+        }
+    }
+}
+
+/// Likes, boosts and replies from the fediverse.
+fn fediverse(interactions: &Interactions) -> Markup {
+    html! {
+        @if !interactions.is_empty() {
+            section.fediverse {
+                (facepile("Likes", &interactions.likes))
+                (facepile("Boosts", &interactions.boosts))
+                @if !interactions.replies.is_empty() {
+                    h4 { "Replies" }
+                    @for reply in &interactions.replies {
+                        .reply {
+                            p {
+                                a href=(reply.actor.url) { (reply.actor.name) }
+                                " "
+                                a href=(reply.url) { small { (reply.published.as_deref().unwrap_or("said")) } }
+                            }
+                            (PreEscaped(&reply.content))
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Up to `n` posts from `all` that are like `current`, as cards. See
+/// [crate::post::related] for how they're picked.
+pub fn related_posts(current: &Post, all: &[Post], n: usize) -> Markup {
+    let related = crate::post::related(current, all, n);
+    html! {
+        @if !related.is_empty() {
+            section.related-posts {
+                h2 { "You might also like" }
+                .grid {
+                    @for post in related {
+                        .card.cell."-4of12" {
+                            header.card-header {
+                                a href={"/" (post.link)} { (post.front_matter.title) }
+                            }
+                            .card-content {
+                                small {
+                                    (post.detri())
+                                    " · "
+                                    (xesite_templates::stats::minutes(post.word_count))
+                                    " min"
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A blog post. `all` is every blog post, for [related_posts].
+pub fn blog(
+    post: &Post,
+    all: &[Post],
+    body: PreEscaped<&String>,
+    referer: Option<String>,
+    interactions: &Interactions,
+    prefs: ReaderPrefs,
+) -> Markup {
+    post.preload_assets();
+    base_for(
+        PageKind::Post,
         Some(&post.front_matter.title),
         None,
         html! {
             (post_metadata(post))
             @if !post.front_matter.skip_ads {
-                (nag::referer(post, referer))
+                (nag::referer(post, referer, prefs))
             }
 
+            (breadcrumbs(crumbs(post)))
+
             article {
                 h1 {(post.front_matter.title)}
 
                 (nag::prerelease(post))
+                (nag::outdated(post))
 
-                small {
-                    "Read time in minutes: "
-                    (post.read_time_estimate_minutes)
+                @if let Some(origin) = &post.front_matter.originally_published {
+                    (xesite_templates::originally_published(origin.clone()))
                 }
 
+                @if let Some(event) = &post.front_matter.event {
+                    (xesite_templates::event_card(event.clone()))
+                }
+
+                small { (xesite_templates::stats::reading_time(post.word_count)) }
+
+                @if let Some(series) = &post.series {
+                    (xesite_templates::series_nav(series))
+                }
+
+                (toc(post))
+
                 div {
-                    (body)
+                    @if let Some(warning) = &post.front_matter.content_warning {
+                        (xesite_templates::content_warning(warning.clone(), html! { (body) }))
+                    } @else {
+                        (body)
+                    }
+                }
+
+                @if let Some(series) = &post.series {
+                    (xesite_templates::series_nav(series))
                 }
             }
 
             hr;
 
+            (xesite_templates::hovercards(&post.body_html))
+            (xesite_templates::media_credits(&post.credits))
+            (signature_badge(post))
             (share_button(post))
+            (sponsor(post, prefs))
             (twitch_vod(post))
 
             p {
@@ -107,6 +308,8 @@ pub fn blog(post: &Post, body: PreEscaped<&String>, referer: Option<String>) ->
                }
             }
 
+            (related_posts(post, all, RELATED_POSTS))
+
             @if post.mentions.is_empty() {
                 p {
                     "This post was not "
@@ -123,6 +326,8 @@ pub fn blog(post: &Post, body: PreEscaped<&String>, referer: Option<String>) ->
                 }
             }
 
+            (fediverse(interactions))
+
             p {
                 "The art for Mara was drawn by "
                 a href="https://selic.re/" {"Selicre"}
@@ -181,32 +386,170 @@ pub fn gallery(post: &Post) -> Markup {
     )
 }
 
-pub fn talk(post: &Post, body: PreEscaped<&String>, referer: Option<String>) -> Markup {
+pub fn note(post: &Post, body: PreEscaped<&String>, backlinks: Vec<&Post>) -> Markup {
+    post.preload_assets();
     base(
         Some(&post.front_matter.title),
         None,
         html! {
             (post_metadata(post))
 
-            @if !post.front_matter.skip_ads {
-                (nag::referer(post, referer))
+            article {
+                h1 {(post.front_matter.title)}
+
+                (nag::outdated(post))
+
+                (body)
             }
 
+            hr;
+
+            @if !backlinks.is_empty() {
+                h2 {"Linked from"}
+                ul {
+                    @for backlink in backlinks {
+                        li {
+                            a href={"/" (backlink.link)} {(backlink.front_matter.title)}
+                        }
+                    }
+                }
+            }
+
+            p {
+                "This note was first planted on "
+                (post.detri())
+                ". Notes in the "
+                a href="/notes" {"garden"}
+                " get updated over time as I learn more."
+            }
+        },
+    )
+}
+
+pub fn incident(post: &Post, body: PreEscaped<&String>) -> Markup {
+    let incident = post.front_matter.incident.clone().unwrap_or_default();
+
+    base(
+        Some(&post.front_matter.title),
+        None,
+        html! {
+            (post_metadata(post))
+
             article {
                 h1 {(post.front_matter.title)}
 
-                (nag::prerelease(post))
+                .incident {
+                    p {
+                        "Status: "
+                        @if let Some(resolved) = &incident.resolved {
+                            span.status-up {"resolved"}
+                            " at "
+                            (resolved)
+                        } @else {
+                            span.status-down {"ongoing"}
+                        }
+                    }
+                    p {
+                        "Affected services: "
+                        @for svc in &incident.services {
+                            code {(svc)}
+                            " "
+                        }
+                    }
+
+                    @if !incident.timeline.is_empty() {
+                        h2 {"Timeline"}
+                        ul.incident-timeline {
+                            @for update in &incident.timeline {
+                                li {
+                                    time { (update.time) }
+                                    " - "
+                                    b { (update.status) }
+                                    ": "
+                                    (update.message)
+                                }
+                            }
+                        }
+                    }
+
+                    @if let Some(resolution) = &incident.resolution {
+                        h2 {"Resolution"}
+                        p {(resolution)}
+                    }
+                }
 
                 (body)
             }
 
+            hr;
+
+            p {
+                "This incident report was posted on "
+                (post.detri())
+                ". See the "
+                a href="/status" {"status page"}
+                " for the current state of things."
+            }
+        },
+    )
+}
+
+/// A talk. With `essential_slides` only the slides marked essential are
+/// shown, see [xesite_templates::talk_warning].
+pub fn talk(
+    post: &Post,
+    body: PreEscaped<&String>,
+    referer: Option<String>,
+    prefs: ReaderPrefs,
+    essential_slides: bool,
+) -> Markup {
+    post.preload_assets();
+    base_for(
+        PageKind::Talk,
+        Some(&post.front_matter.title),
+        None,
+        html! {
+            (post_metadata(post))
+
+            @if !post.front_matter.skip_ads {
+                (nag::referer(post, referer, prefs))
+            }
+
+            (breadcrumbs(crumbs(post)))
+
+            (xesite_templates::essential_slides_hint())
+            article class=[essential_slides.then_some("slides-essential-only")] {
+                h1 {(post.front_matter.title)}
+
+                (nag::prerelease(post))
+                (nag::outdated(post))
+
+                @if let Some(origin) = &post.front_matter.originally_published {
+                    (xesite_templates::originally_published(origin.clone()))
+                }
+
+                @if let Some(event) = &post.front_matter.event {
+                    (xesite_templates::event_card(event.clone()))
+                }
+
+                @if let Some(warning) = &post.front_matter.content_warning {
+                    (xesite_templates::content_warning(warning.clone(), html! { (body) }))
+                } @else {
+                    (body)
+                }
+            }
+
             @if let Some(slides) = &post.front_matter.slides_link {
                 a href=(slides) {"Link to the slides"}
             }
 
             hr;
 
+            (xesite_templates::hovercards(&post.body_html))
+            (xesite_templates::media_credits(&post.credits))
+            (signature_badge(post))
             (share_button(post))
+            (sponsor(post, prefs))
 
             p {
                 "This talk was posted on "
@@ -0,0 +1,119 @@
+//! The weekly digest's pages, feed entries and emails, see [crate::digest].
+
+use super::base;
+use crate::digest::{Issue, Item};
+use maud::{html, Markup, PreEscaped, DOCTYPE};
+use xesite_templates::context::site;
+
+/// The issue's intro as HTML. It's checked when it's saved, so this only
+/// comes up empty for intros that were never written.
+fn intro(issue: &Issue) -> String {
+    xesite_markdown::render(&issue.intro).unwrap_or_default()
+}
+
+fn items(title: &str, items: &[Item]) -> Markup {
+    html! {
+        @if !items.is_empty() {
+            h2 { (title) }
+            @for item in items {
+                h3 { a href=(site().url(&item.link)) { (item.title) } }
+                (PreEscaped(&item.summary))
+            }
+        }
+    }
+}
+
+/// What's in an issue. Links are absolute so it works the same in feeds
+/// and emails.
+pub fn body(issue: &Issue) -> Markup {
+    html! {
+        (PreEscaped(intro(issue)))
+        (items("New posts", &issue.posts))
+        (items("New notes", &issue.notes))
+        @if !issue.replies.is_empty() {
+            h2 { "From the fediverse" }
+            @for reply in &issue.replies {
+                blockquote.digest-reply cite=(reply.url) {
+                    (PreEscaped(&reply.content))
+                    p {
+                        "— "
+                        a href=(reply.author_url) { (reply.author) }
+                        " on "
+                        a href=(site().url(&reply.link)) { (reply.title) }
+                        " ("
+                        a href=(reply.url) { "original" }
+                        ")"
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn index(issues: &[Issue]) -> Markup {
+    base(
+        Some("Weekly digest"),
+        None,
+        html! {
+            h1 { "Weekly digest" }
+            p {
+                "Once a week I round up what I posted and what people said about it. It's also in "
+                a href="/digest.rss" { "an RSS feed" }
+                "."
+            }
+            @if issues.is_empty() {
+                p { "There aren't any issues yet." }
+            } @else {
+                ul {
+                    @for issue in issues {
+                        li { a href={"/" (issue.link())} { (issue.title()) } }
+                    }
+                }
+            }
+        },
+    )
+}
+
+pub fn page(issue: &Issue) -> Markup {
+    let title = issue.title();
+    base(
+        Some(&title),
+        None,
+        html! {
+            article.digest {
+                h1 { (title) }
+                (body(issue))
+                p { a href="/digest" { "All issues" } }
+            }
+        },
+    )
+}
+
+/// A page that can be pasted into an email as it is. Mail clients ignore
+/// stylesheets, so it only has formatting they all understand. The handler
+/// renders it for `RenderTarget::Email`.
+pub fn email(issue: &Issue) -> Markup {
+    let title = issue.title();
+    html! {
+        (DOCTYPE)
+        html lang="en" {
+            head {
+                meta charset="utf-8";
+                meta name="viewport" content="width=device-width, initial-scale=1";
+                title { (site().feed_title) ": " (title) }
+            }
+            body style="max-width: 40em; margin: 0 auto; padding: 1em; font-family: sans-serif; line-height: 1.5;" {
+                h1 { (title) }
+                (body(issue))
+                hr;
+                p {
+                    small {
+                        "You're getting this because you asked for my weekly digest. "
+                        a href=(site().url(&issue.link())) { "Read it on the web" }
+                        "."
+                    }
+                }
+            }
+        }
+    }
+}
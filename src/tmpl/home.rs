@@ -0,0 +1,210 @@
+//! The home page, put together from the sections listed in the `homepage`
+//! config, in that order.
+
+use super::{base_for, excerpt_card, schema_person, PageKind};
+use crate::{
+    analytics::Window,
+    app::{HomeSection, State},
+    post::Post,
+};
+use chrono::prelude::*;
+use maud::{html, Markup};
+use xesite_templates::{
+    context::{self, site},
+    og::OpenGraph,
+};
+
+pub fn index(state: &State) -> Markup {
+    let xe = &state.cfg.default_author;
+    context::open_graph(
+        OpenGraph::new(&xe.name)
+            .description(&xe.job_title)
+            .url(site().url("/"))
+            .author(&xe.name),
+    );
+
+    base_for(
+        PageKind::Index,
+        None,
+        None,
+        html! {
+            link rel="authorization_endpoint" href="https://idp.christine.website/auth";
+            link rel="canonical" href=(site().url("/"));
+            meta name="google-site-verification" content="rzs9eBEquMYr9Phrg0Xm0mIwFjDBcbdgJ3jF6Disy-k";
+            (schema_person(&xe))
+
+            @for section in &state.cfg.homepage {
+                (render(state, section))
+            }
+        },
+    )
+}
+
+pub fn render(state: &State, section: &HomeSection) -> Markup {
+    match section {
+        HomeSection::Hero => hero(state),
+        HomeSection::About => about(state),
+        HomeSection::LatestPosts(count) => latest_posts(&state.blog, *count),
+        HomeSection::PopularPosts(popular) => popular_posts(state, popular.count, popular.window),
+        HomeSection::OnThisDay => on_this_day(&state.blog),
+        HomeSection::Streaming => streaming(state),
+        HomeSection::Supporters => supporters(state),
+    }
+}
+
+fn hero(state: &State) -> Markup {
+    let xe = &state.cfg.default_author;
+    html! {
+        .grid {
+            .cell."-3of12".content {
+                img src="/static/img/avatar.png" alt="My Avatar";
+                br;
+                a href="/contact" class="justify-content-center" { "Contact me" }
+            }
+            .cell."-9of12".content {
+                h1 {(xe.name)}
+                h4 {(xe.job_title)}
+            }
+        }
+    }
+}
+
+fn about(state: &State) -> Markup {
+    html! {
+        section.home-section {
+            h5 { "Skills" }
+            ul {
+                li { "Go, Lua, Haskell, C, Rust and other languages" }
+                li { "Docker (deployment, development & more)" }
+                li { "Mashups of data" }
+                li { "kastermakfa" }
+            }
+
+            h5 { "Highlighted Projects" }
+            ul {
+                @for project in &state.cfg.notable_projects {
+                    li {(project)}
+                }
+            }
+
+            h5 { "Quick Links" }
+            ul {
+                li {a href="https://github.com/Xe" rel="me" {"GitHub"}}
+                li {a href="https://twitter.com/theprincessxena" rel="me" {"Twitter"}}
+                li {a href=(site().mastodon.url) rel="me" {"Fediverse"}}
+                li {a href=(site().donation.patreon_url) rel="me" {"Patreon"}}
+            }
+
+            p {
+                "Looking for someone for your team? Check "
+                a href="/signalboost" { "here" }
+                "."
+            }
+        }
+    }
+}
+
+fn latest_posts(blog: &[Post], count: usize) -> Markup {
+    let today = Utc::now();
+    html! {
+        section.home-section {
+            h5 { "Latest posts" }
+            @for post in blog.iter().filter(|p| p.listed() && p.front_matter.redirect_to.is_none() && today.num_days_from_ce() >= p.date.num_days_from_ce()).take(count) {
+                (excerpt_card(post))
+            }
+            p { a href="/blog" { "Every post" } }
+        }
+    }
+}
+
+/// The `n` most read posts in `window`, or nothing if nobody has read
+/// anything yet.
+pub fn popular_posts(state: &State, n: usize, window: Window) -> Markup {
+    let posts: Vec<(&Post, u64)> = state
+        .analytics
+        .popular(n, window)
+        .into_iter()
+        .filter_map(|(link, count)| {
+            state
+                .everything
+                .iter()
+                .find(|post| post.link == link)
+                .map(|post| (post, count))
+        })
+        .collect();
+    let title = match window {
+        Window::Month => "Popular this month",
+        Window::Quarter => "Popular lately",
+    };
+
+    html! {
+        @if !posts.is_empty() {
+            section.home-section {
+                h5 { (title) }
+                ol {
+                    @for (post, _) in posts {
+                        li { a href={"/" (post.link)} { (post.front_matter.title) } }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Posts from today's date in earlier years.
+pub fn on_this_day(blog: &[Post]) -> Markup {
+    let today = Utc::now().date_naive();
+    let posts = crate::on_this_day::posts(blog, today);
+
+    html! {
+        @if !posts.is_empty() {
+            section.home-section {
+                h5 { "On this day" }
+                ul {
+                    @for post in posts {
+                        li {
+                            a href={"/" (post.link)} { (post.front_matter.title) }
+                            " (" (post.date.year()) ")"
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Where to watch streams, and the latest recording.
+fn streaming(state: &State) -> Markup {
+    let latest = state.cfg.vods.iter().max_by_key(|vod| vod.date);
+    html! {
+        section.home-section.streaming {
+            p {
+                "I sometimes stream programming and games on "
+                a href="https://www.twitch.tv/princessxen" { "Twitch" }
+                "."
+                @if let Some(vod) = latest {
+                    " The latest stream was "
+                    a href=(format!("/vods/{}/{}/{}", vod.date.year(), vod.date.month(), vod.slug)) { (vod.title) }
+                    "."
+                }
+            }
+        }
+    }
+}
+
+fn supporters(state: &State) -> Markup {
+    html! {
+        @if let Some(patrons) = &state.patrons {
+            section.home-section {
+                h5 { "Supported by" }
+                p.supporters {
+                    @for (i, patron) in patrons.iter().enumerate() {
+                        @if i != 0 { ", " }
+                        (patron.attributes.full_name)
+                    }
+                }
+                p { a href="/patrons" { "Every patron" } }
+            }
+        }
+    }
+}
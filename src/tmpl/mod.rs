@@ -1,17 +1,142 @@
-use crate::{app::*, post::Post, signalboost::Person};
+use crate::{
+    app::*,
+    notes::Garden,
+    post::{archive, Post},
+    signalboost::Person,
+    stats::YearStats,
+    status::{self, Check},
+};
 use chrono::prelude::*;
 use lazy_static::lazy_static;
 use maud::{html, Markup, PreEscaped, Render, DOCTYPE};
+use xesite_templates::{
+    breadcrumbs,
+    context::{font_faces, site, take_includes, take_open_graph, take_preloads, Preload},
+    Crumb,
+};
 use patreon::Users;
+use std::collections::HashMap;
 
+pub mod admin;
+pub mod auth;
 pub mod blog;
+pub mod digest;
+pub mod home;
 pub mod nag;
+pub mod prefs;
+pub mod structured_data;
 
 lazy_static! {
-    static ref CACHEBUSTER: String = uuid::Uuid::new_v4().to_string().replace("-", "");
+    static ref CACHEBUSTER: String = if site().deterministic_ids() {
+        env!("CARGO_PKG_VERSION").to_string()
+    } else {
+        uuid::Uuid::new_v4().to_string().replace("-", "")
+    };
 }
 
+/// The assets every page needs, for the preload hints in [base] and the
+/// early hints header.
+pub fn critical_preloads() -> Vec<Preload> {
+    xesite_templates::context::critical_preloads(&CACHEBUSTER)
+}
+
+/// The kinds of page that have their own critical CSS, written to
+/// `static/css/critical` by the `critical_css` tool.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PageKind {
+    Index,
+    Post,
+    Talk,
+}
+
+impl PageKind {
+    const ALL: [PageKind; 3] = [PageKind::Index, PageKind::Post, PageKind::Talk];
+
+    fn name(&self) -> &'static str {
+        match self {
+            PageKind::Index => "index",
+            PageKind::Post => "post",
+            PageKind::Talk => "talk",
+        }
+    }
+
+    /// The critical CSS for this kind of page, if it has been generated.
+    fn critical_css(&self) -> Option<&'static str> {
+        CRITICAL_CSS.get(self.name()).map(String::as_str)
+    }
+}
+
+lazy_static! {
+    static ref CRITICAL_CSS: HashMap<&'static str, String> = PageKind::ALL
+        .iter()
+        .filter_map(|kind| {
+            std::fs::read_to_string(format!("./static/css/critical/{}.css", kind.name()))
+                .ok()
+                .map(|css| (kind.name(), css))
+        })
+        .collect();
+}
+
+const STYLESHEETS: &[&str] = &["hack", "gruvbox-dark", "shim"];
+
 pub fn base(title: Option<&str>, styles: Option<&str>, content: Markup) -> Markup {
+    layout(None, title, styles, content)
+}
+
+/// Like [base], but inlines the critical CSS for `kind` when the profile
+/// asks for it and loads the full stylesheets without blocking rendering.
+pub fn base_for(
+    kind: PageKind,
+    title: Option<&str>,
+    styles: Option<&str>,
+    content: Markup,
+) -> Markup {
+    layout(Some(kind), title, styles, content)
+}
+
+fn layout(
+    kind: Option<PageKind>,
+    title: Option<&str>,
+    styles: Option<&str>,
+    content: Markup,
+) -> Markup {
+    // The content has already been rendered, so everything it depends on
+    // has been registered by now.
+    let mut preloads = critical_preloads();
+    preloads.extend(take_preloads());
+    let preloads = html! {
+        @for preload in &preloads {
+            (preload)
+        }
+    };
+    let includes = html! {
+        @for include in take_includes() {
+            (include)
+        }
+    };
+    let meta = html! {
+        @if let Some(og) = take_open_graph() {
+            (xesite_templates::og::render_head_meta(&og))
+        }
+    };
+
+    if let Some(result) = xesite_templates::overrides::lookup(
+        "layout",
+        &[
+            ("title", &html! {(title.unwrap_or("Xe Iaso"))}),
+            ("styles", &PreEscaped(styles.unwrap_or_default().to_string())),
+            ("cachebuster", &html! {(*CACHEBUSTER)}),
+            ("banner", &xesite_templates::environment_banner()),
+            ("xeact", &xesite_templates::xeact_runtime(&CACHEBUSTER)),
+            ("preloads", &preloads),
+            ("includes", &includes),
+            ("meta", &meta),
+            ("content", &content),
+        ],
+    ) {
+        return result;
+    }
+
     let now = Utc::now();
     html! {
         (DOCTYPE)
@@ -27,18 +152,40 @@ pub fn base(title: Option<&str>, styles: Option<&str>, content: Markup) -> Marku
                     }
                 }
                 meta name="viewport" content="width=device-width, initial-scale=1.0";
-                link rel="stylesheet" href={"/static/css/hack.css?bustCache=" (*CACHEBUSTER)};
-                link rel="stylesheet" href={"/static/css/gruvbox-dark.css?bustCache=" (*CACHEBUSTER)};
-                link rel="stylesheet" href={"/static/css/shim.css?bustCache=" (*CACHEBUSTER)};
+                (meta)
+                (preloads)
+                @let font_faces = font_faces();
+                @if !font_faces.is_empty() {
+                    style { (PreEscaped(font_faces)) }
+                }
+                @let critical = kind
+                    .filter(|_| site().inline_critical_css())
+                    .and_then(|kind| kind.critical_css());
+                @if let Some(critical) = critical {
+                    style { (PreEscaped(critical)) }
+                    @for sheet in STYLESHEETS {
+                        link rel="preload" as="style" href={"/static/css/" (sheet) ".css?bustCache=" (*CACHEBUSTER)} onload="this.onload=null;this.rel='stylesheet'";
+                    }
+                    noscript {
+                        @for sheet in STYLESHEETS {
+                            link rel="stylesheet" href={"/static/css/" (sheet) ".css?bustCache=" (*CACHEBUSTER)};
+                        }
+                    }
+                } @else {
+                    @for sheet in STYLESHEETS {
+                        link rel="stylesheet" href={"/static/css/" (sheet) ".css?bustCache=" (*CACHEBUSTER)};
+                    }
+                }
                 @match now.month() {
                     12|1|2 => {
                         link rel="stylesheet" href={"/static/css/snow.css?bustCache=" (*CACHEBUSTER)};
                     }
                     _ => {},
                 }
-                link rel="manifest" href="/static/manifest.json";
-                link rel="alternate" title="Xe's Blog" type="application/rss+xml" href="https://xeiaso.net/blog.rss";
-                link rel="alternate" title="Xe's Blog" type="application/json" href="https://xeiaso.net/blog.json";
+                link rel="manifest" href="/manifest.json";
+                (xesite_templates::xeact_runtime(&CACHEBUSTER))
+                link rel="alternate" title=(site().feed_title) type="application/rss+xml" href=(site().url("blog.rss"));
+                link rel="alternate" title=(site().feed_title) type="application/json" href=(site().url("blog.json"));
                 link rel="apple-touch-icon" sizes="57x57" href="/static/favicon/apple-icon-57x57.png";
                 link rel="apple-touch-icon" sizes="60x60" href="/static/favicon/apple-icon-60x60.png";
                 link rel="apple-touch-icon" sizes="72x72" href="/static/favicon/apple-icon-72x72.png";
@@ -53,17 +200,28 @@ pub fn base(title: Option<&str>, styles: Option<&str>, content: Markup) -> Marku
                 link rel="icon" type="image/png" sizes="32x32" href="/static/favicon/favicon-32x32.png";
                 link rel="icon" type="image/png" sizes="96x96" href="/static/favicon/favicon-96x96.png";
                 link rel="icon" type="image/png" sizes="16x16" href="/static/favicon/favicon-16x16.png";
-                meta name="msapplication-TileColor" content="#ffffff";
+                meta name="application-name" content=(site().branding.name);
+                meta name="apple-mobile-web-app-title" content=(site().branding.short_name);
+                meta name="apple-mobile-web-app-capable" content="yes";
+                meta name="apple-mobile-web-app-status-bar-style" content="black-translucent";
+                meta name="msapplication-TileColor" content=(site().branding.background_color);
                 meta name="msapplication-TileImage" content="/static/favicon/ms-icon-144x144.png";
-                meta name="theme-color" content="#ffffff";
+                meta name="theme-color" media="(prefers-color-scheme: dark)" content=(site().branding.theme_color);
+                meta name="theme-color" media="(prefers-color-scheme: light)" content=(site().branding.theme_color_light);
                 link href="https://mi.within.website/api/webmention/accept" rel="webmention";
+                @if let Some(url) = &site().signing.pgp_key_url {
+                    link rel="pgpkey" href=(url);
+                }
                 @if let Some(styles) = styles {
                     style {
                         (PreEscaped(styles))
                     }
                 }
+                (includes)
             }
             body.snow.hack.gruvbox-dark {
+                (xesite_templates::environment_banner())
+                (xesite_templates::static_export_banner())
                 .container {
                     header {
                         span.logo {}
@@ -103,7 +261,7 @@ pub fn base(title: Option<&str>, styles: Option<&str>, content: Markup) -> Marku
                         }
                         p {
                             "Like what you see? Donate on "
-                            a href="https://www.patreon.com/cadey" { "Patreon" }
+                            a href=(site().donation.patreon_url) { "Patreon" }
                             " like "
                             a href="/patrons" { "these awesome people" }
                             "!"
@@ -133,14 +291,145 @@ pub fn base(title: Option<&str>, styles: Option<&str>, content: Markup) -> Marku
     }
 }
 
-pub fn post_index(posts: &Vec<Post>, title: &str, show_extra: bool) -> Markup {
+/// How many of the newest posts get an excerpt on the blog index.
+const RECENT_EXCERPTS: usize = 3;
+
+/// A post's title, date and excerpt, with a link to the rest of it.
+fn excerpt_card(post: &Post) -> Markup {
+    html! {
+        .excerpt {
+            h3 { a href={"/" (post.link)} { (post.front_matter.title) } }
+            small { (post.detri()) }
+            (PreEscaped(&post.excerpt))
+            p { a href={"/" (post.link)} { "Continue reading" } }
+        }
+    }
+}
+
+/// Posts with an event that hasn't happened yet.
+fn upcoming_events(posts: &[Post]) -> Markup {
+    html! {
+        @let upcoming: Vec<&Post> = posts.iter().filter(|p| p.listed() && p.upcoming_event()).collect();
+        @if !upcoming.is_empty() {
+            h2 { "Upcoming events" }
+            .grid {
+                @for post in upcoming {
+                    .cell."-6of12" {
+                        (xesite_templates::event_card(post.front_matter.event.clone().unwrap()))
+                        a href={"/" (post.link)} { (post.front_matter.title) }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A list item for every post.
+fn post_items<'a>(posts: impl Iterator<Item = &'a Post>) -> Markup {
+    html! {
+        @for post in posts {
+            li {
+                (post.detri())
+                " - "
+                a href={ @if post.front_matter.redirect_to.as_ref().is_some() {(post.front_matter.redirect_to.as_ref().unwrap())} @else {"/" (post.link)}} { (post.front_matter.title) }
+            }
+        }
+    }
+}
+
+/// Every post in a list, newest first.
+fn post_list<'a>(posts: impl Iterator<Item = &'a Post>) -> Markup {
+    html! {
+        p {
+            ul {
+                (post_items(posts))
+            }
+        }
+    }
+}
+
+pub fn post_index(posts: &Vec<Post>, title: &str) -> Markup {
     let today = Utc::now().date_naive();
     base(
         Some(title),
         None,
         html! {
             h1 { (title) }
-            @if show_extra {
+            (upcoming_events(posts))
+            (post_list(posts.iter().filter(|p| p.listed() && today.num_days_from_ce() >= p.date.num_days_from_ce())))
+        },
+    )
+}
+
+/// The path of a page of the blog index.
+pub fn blog_page(page: usize) -> String {
+    match page {
+        1 => "/blog".to_string(),
+        page => format!("/blog/page/{page}"),
+    }
+}
+
+fn published(posts: &[Post]) -> Vec<&Post> {
+    let today = Utc::now().date_naive();
+    posts
+        .iter()
+        .filter(|p| p.listed() && today.num_days_from_ce() >= p.date.num_days_from_ce())
+        .collect()
+}
+
+/// How many pages the blog index is split into.
+pub fn blog_pages(posts: &[Post]) -> usize {
+    let per_page = site().posts_per_page;
+    ((published(posts).len() + per_page - 1) / per_page).max(1)
+}
+
+/// Where scripts can fetch the posts of a page of the blog index from, see
+/// [blog_page_items].
+pub fn blog_api_page(page: usize) -> String {
+    format!("/api/posts?page={page}")
+}
+
+/// The posts on a page of the blog index, or None if there aren't that many
+/// pages.
+fn page_of(posts: &[Post], page: usize) -> Option<Vec<&Post>> {
+    if page == 0 || page > blog_pages(posts) {
+        return None;
+    }
+    let per_page = site().posts_per_page;
+    Some(
+        published(posts)
+            .into_iter()
+            .skip((page - 1) * per_page)
+            .take(per_page)
+            .collect(),
+    )
+}
+
+/// The list items of a page of the blog index, so the index can load the
+/// next page as the reader scrolls instead of sending them to it.
+pub fn blog_page_items(posts: &[Post], page: usize) -> Option<Markup> {
+    page_of(posts, page).map(|posts| post_items(posts.into_iter()))
+}
+
+/// One page of the blog index, or None if there aren't that many pages. The
+/// first page also has the feeds and the latest posts with their excerpts.
+pub fn blog_index(posts: &Vec<Post>, page: usize) -> Option<Markup> {
+    let pages = blog_pages(posts);
+    let on_page = page_of(posts, page)?;
+    let listed = published(posts);
+    let title = match page {
+        1 => "Blogposts".to_string(),
+        page => format!("Blogposts, page {page}"),
+    };
+
+    Some(base(
+        Some(&title),
+        None,
+        html! {
+            link rel="canonical" href=(site().url(&blog_page(page)));
+            h1 { (title) }
+            @if page == 1 {
+                (upcoming_events(posts))
                 p {
                     "If you have a compatible reader, be sure to check out my "
                     a href="/blog.rss" { "RSS feed" }
@@ -153,20 +442,98 @@ pub fn post_index(posts: &Vec<Post>, title: &str, show_extra: bool) -> Markup {
                     a href="/blog/series" { "here" }
                     "."
                 }
+                h2 { "Recent posts" }
+                @for post in listed.iter().filter(|p| p.front_matter.redirect_to.is_none()).take(RECENT_EXCERPTS) {
+                    (excerpt_card(post))
+                }
+                h2 { "All posts" }
             }
             p {
+                ul.post-list data-next=[(page < pages).then(|| blog_api_page(page + 1))] {
+                    (post_items(on_page.into_iter()))
+                }
+            }
+            (xesite_templates::pagination(page, pages, blog_page))
+            script src="/static/js/more-posts.js" defer {}
+            (archive_nav(posts))
+        },
+    ))
+}
+
+/// Links to the archive page of every year with posts in it.
+pub fn archive_nav(posts: &[Post]) -> Markup {
+    let archive = archive::by_month(posts);
+    html! {
+        nav.archive-nav {
+            "Archive: "
+            @for (i, (year, count)) in archive::year_counts(&archive).into_iter().enumerate() {
+                @if i != 0 { " · " }
+                a href={"/blog/" (year) "/"} { (year) }
+                " (" (count) ")"
+            }
+        }
+    }
+}
+
+/// The posts of a year, or of one month if `month` is set.
+pub fn archive(posts: &[Post], year: i32, month: Option<u32>) -> Option<Markup> {
+    let by_month = archive::by_month(posts);
+    let months = by_month.get(&year)?;
+    let months: Vec<(&u32, &Vec<&Post>)> = months
+        .iter()
+        .rev()
+        .filter(|(m, _)| month.map(|month| **m == month).unwrap_or(true))
+        .collect();
+    if months.is_empty() {
+        return None;
+    }
+    let count: usize = months.iter().map(|(_, posts)| posts.len()).sum();
+    let title = match month {
+        Some(month) => format!("Posts from {} {year}", archive::month_name(month)),
+        None => format!("Posts from {year}"),
+    };
+
+    let mut crumbs = vec![
+        Crumb::new("Blog", "/blog"),
+        Crumb::new(year.to_string(), format!("/blog/{year}/")),
+    ];
+    if let Some(month) = month {
+        crumbs.push(Crumb::new(
+            archive::month_name(month),
+            format!("/blog/{year}/{month:02}/"),
+        ));
+    }
+
+    Some(base(
+        Some(&title),
+        None,
+        html! {
+            (breadcrumbs(crumbs))
+            h1 { (title) }
+            p {
+                (count) @if count == 1 { " post" } @else { " posts" }
+                @if month.is_some() {
+                    ". See every post from " a href={"/blog/" (year) "/"} { (year) } "."
+                }
+            }
+            @for (month, posts) in months {
+                h2 {
+                    a href=(format!("/blog/{year}/{month:02}/")) { (archive::month_name(*month)) }
+                    " (" (posts.len()) ")"
+                }
                 ul {
-                    @for post in posts.iter().filter(|p| today.num_days_from_ce() >= p.date.num_days_from_ce()) {
+                    @for post in posts {
                         li {
-                            (post.detri())
+                            (post.date.format("%d"))
                             " - "
-                                a href={ @if post.front_matter.redirect_to.as_ref().is_some() {(post.front_matter.redirect_to.as_ref().unwrap())} @else {"/" (post.link)}} { (post.front_matter.title) }
+                            a href={ @if let Some(url) = &post.front_matter.redirect_to {(url)} @else {"/" (post.link)}} { (post.front_matter.title) }
                         }
                     }
                 }
             }
+            (archive_nav(posts))
         },
-    )
+    ))
 }
 
 pub fn gallery_index(posts: &Vec<Post>) -> Markup {
@@ -179,7 +546,7 @@ pub fn gallery_index(posts: &Vec<Post>) -> Markup {
             p {"Here are links to a lot of the art I have done in the last few years."}
 
             .grid {
-                @for post in posts {
+                @for post in posts.iter().filter(|p| p.listed()) {
                     .card.cell."-4of12".blogpost-card {
                         header."card-header" {
                             (post.front_matter.title)
@@ -247,9 +614,9 @@ pub fn characters(characters: &Vec<Character>) -> Markup {
                 " to help illustrate my point. These characters are written off of a set of tropes to help give them a place in the discussions. The characters are just that, characters. Their dialogues are fiction, unless otherwise indicated everything that happens in those dialogues are products of the author's imagination or are used in a fictitious manner. Any resemblance to actual persons (living or dead) is purely coincidental."
             }
 
-            @for character in characters {
-                (character)
-            }
+            (xesite_templates::characters_page(
+                &characters.iter().map(Character::card).collect::<Vec<_>>()
+            ))
 
             h2 {"Other People"}
 
@@ -272,7 +639,7 @@ pub fn patrons(patrons: &Users) -> Markup {
 
             p {
                 "These awesome people donate to me on "
-                a href="https://www.patreon.com/cadey" {"Patreon"}
+                a href=(site().donation.patreon_url) {"Patreon"}
                 ". If you would like to show up in this list, please donate to me on Patreon. This is refreshed every time the site is deployed."
             }
 
@@ -352,6 +719,85 @@ pub fn error(why: impl Render) -> Markup {
     )
 }
 
+/// What people see when the site panicked while making their page, see
+/// [crate::panics]. What went wrong is only in the logs.
+pub fn panicked() -> Markup {
+    base(
+        Some("Something broke"),
+        None,
+        html! {
+            h1 {"Something broke"}
+            (xesite_templates::conv("Cadey".into(), "facepalm".into(), html! {
+                "Sorry, the site fell over while making this page. It's been logged and the rest of the site still works, so try again in a bit."
+            }))
+            p {
+                "You could also "
+                a href="/" {"go home"}
+                " or "
+                a href="https://github.com/Xe/site/issues/new" {"report this issue"}
+                " so it can be fixed."
+            }
+        },
+    )
+}
+
+/// The sticker on the offline page. It's precached by the service worker, so
+/// every format the browser could pick is listed.
+const OFFLINE_STICKER: (&str, &str) = ("Mara", "hmm");
+
+pub fn offline() -> Markup {
+    let (name, mood) = OFFLINE_STICKER;
+    base(
+        Some("Offline"),
+        None,
+        html! {
+            h1 {"You're offline"}
+            (xesite_templates::conv(name.into(), mood.into(), html! {
+                "Looks like the internet went away. Pages you've read before still work, try going back to one of them."
+            }))
+            p {
+                "Once you're back online, "
+                a href="/" {"reload the page"}
+                "."
+            }
+        },
+    )
+}
+
+/// What the service worker caches when it's installed: the stylesheets,
+/// scripts and fonts of this build, the offline page and its sticker.
+pub fn precache() -> Vec<String> {
+    let mut result: Vec<String> = STYLESHEETS
+        .iter()
+        .map(|sheet| format!("/static/css/{sheet}.css?bustCache={}", *CACHEBUSTER))
+        .collect();
+    result.push(format!(
+        "/static/js/xeact-runtime.js?cacheBuster={}",
+        *CACHEBUSTER
+    ));
+    result.push("/static/js/installsw.js".into());
+    result.push("/manifest.json".into());
+    result.extend(
+        xesite_templates::context::fonts()
+            .iter()
+            .map(|font| format!("{}/{}", xesite_templates::context::FONT_PATH, font.file)),
+    );
+    result.push("/offline".into());
+    let (name, mood) = OFFLINE_STICKER;
+    for ext in ["avif", "webp", "png"] {
+        result.push(site().cdn(&format!(
+            "stickers/{}/{mood}.{ext}",
+            name.to_lowercase()
+        )));
+    }
+    result
+}
+
+/// The version of this build the service worker names its caches after.
+pub fn cachebuster() -> &'static str {
+    &CACHEBUSTER
+}
+
 pub fn not_found(path: impl Render) -> Markup {
     base(
         Some("Not found"),
@@ -416,69 +862,6 @@ fn schema_person(a: &Author) -> Markup {
     }
 }
 
-pub fn index(xe: &Author, projects: &Vec<Link>) -> Markup {
-    base(
-        None,
-        None,
-        html! {
-            link rel="authorization_endpoint" href="https://idp.christine.website/auth";
-            link rel="canonical" href="https://xeiaso.net/";
-            meta name="google-site-verification" content="rzs9eBEquMYr9Phrg0Xm0mIwFjDBcbdgJ3jF6Disy-k";
-            (schema_person(&xe))
-
-            meta name="twitter:card" content="summary";
-            meta name="twitter:site" content="@theprincessxena";
-            meta name="twitter:title" content=(xe.name);
-            meta name="twitter:description" content=(xe.job_title);
-            meta property="og:type" content="website";
-            meta property="og:title" content=(xe.name);
-            meta property="og:site_name" content=(xe.job_title);
-            meta name="description" content=(xe.job_title);
-            meta name="author" content=(xe.name);
-
-            .grid {
-                .cell."-3of12".content {
-                    img src="/static/img/avatar.png" alt="My Avatar";
-                    br;
-                    a href="/contact" class="justify-content-center" { "Contact me" }
-                }
-                .cell."-9of12".content {
-                    h1 {(xe.name)}
-                    h4 {(xe.job_title)}
-                    h5 { "Skills" }
-                    ul {
-                        li { "Go, Lua, Haskell, C, Rust and other languages" }
-                        li { "Docker (deployment, development & more)" }
-                        li { "Mashups of data" }
-                        li { "kastermakfa" }
-                    }
-
-                    h5 { "Highlighted Projects" }
-                    ul {
-                        @for project in projects {
-                            li {(project)}
-                        }
-                    }
-
-                    h5 { "Quick Links" }
-                    ul {
-                        li {a href="https://github.com/Xe" rel="me" {"GitHub"}}
-                        li {a href="https://twitter.com/theprincessxena" rel="me" {"Twitter"}}
-                        li {a href="https://pony.social/@cadey" rel="me" {"Fediverse"}}
-                        li {a href="https://www.patreon.com/cadey" rel="me" {"Patreon"}}
-                    }
-
-                    p {
-                        "Looking for someone for your team? Check "
-                        a href="/signalboost" { "here" }
-                        "."
-                    }
-                }
-            }
-        },
-    )
-}
-
 pub fn blog_series(series: &Vec<SeriesDescription>) -> Markup {
     base(
         Some("Blogposts by series"),
@@ -504,30 +887,39 @@ pub fn series_view(name: &str, desc: &str, posts: &Vec<Post>) -> Markup {
         Some(&format!("{name} posts")),
         None,
         html! {
+            (breadcrumbs(vec![
+                Crumb::new("Blog", "/blog"),
+                Crumb::new("Series", "/blog/series"),
+                Crumb::new(name, format!("/blog/series/{name}")),
+            ]))
             h1 {"Series: " (name)}
 
             p {(desc)}
 
-            ul {
-                @for post in posts {
-                    li {
-                        (post.detri())
-                        " - "
-                        a href={"/" (post.link)} {(post.front_matter.title)}
-                    }
-                }
+            @for post in posts {
+                (excerpt_card(post))
             }
         },
     )
 }
 
-pub fn feeds() -> Markup {
+pub fn feeds(trend: Option<crate::subscribers::Trend>) -> Markup {
     base(
         Some("My Feeds"),
         None,
         html! {
             h1 { "My Feeds" }
 
+            @if let Some(trend) = trend {
+                p {
+                    (xesite_templates::subscriber_badge(
+                        trend.readers,
+                        trend.change,
+                        crate::subscribers::TREND_DAYS,
+                    ))
+                }
+            }
+
             ul {
                 li {
                     "Blog: "
@@ -537,9 +929,21 @@ pub fn feeds() -> Markup {
                     " - "
                     a href="/blog.json" { "JSONFeed" }
                 }
+                li {
+                    "Weekly digest: "
+                    a href="/digest.rss" { "RSS" }
+                }
+                li {
+                    "Reading log: "
+                    a href="/books.rss" { "RSS" }
+                }
+                li {
+                    "Site changelog: "
+                    a href="/changelog.rss" { "RSS" }
+                }
                 li {
                     "Mastodon: "
-                    a href="https://pony.social/users/cadey.rss" { "RSS" }
+                    a href=(site().mastodon.rss_url()) { "RSS" }
                 }
             }
         },
@@ -636,3 +1040,193 @@ pub fn pronoun_page(pronouns: &Vec<PronounSet>) -> Markup {
         },
     )
 }
+
+pub fn status(services: &Vec<(StatusService, Vec<Check>)>, incidents: &Vec<Post>) -> Markup {
+    base(
+        Some("Status"),
+        None,
+        html! {
+            h1 {"Status"}
+            p {
+                "This page shows the status of the services that I run or depend on. These are checked about once a minute from the same machine that serves this website. Incident reports are also available as an "
+                a href="/incidents.rss" {"RSS feed"}
+                "."
+            }
+
+            @for incident in incidents.iter().filter(|p| p.ongoing_incident()) {
+                .warning {
+                    "Ongoing incident: "
+                    a href={"/" (incident.link)} {(incident.front_matter.title)}
+                }
+            }
+
+            @for (svc, checks) in services {
+                .status-service {
+                    h3 {
+                        (svc.name)
+                        " "
+                        @match checks.last() {
+                            Some(check) if check.up => span.status-up { "up" },
+                            Some(_) => span.status-down { "down" },
+                            None => span.status-unknown { "unknown" },
+                        }
+                    }
+                    p {
+                        (svc.description)
+                        @if let Some(uptime) = status::uptime(checks) {
+                            " - "
+                            (format!("{uptime:.2}"))
+                            "% uptime over the last "
+                            (checks.len())
+                            " checks"
+                        }
+                    }
+                    @for incident in incidents.iter().filter(|p| p.listed() && p.ongoing_incident() && p.front_matter.incident.as_ref().unwrap().services.contains(&svc.name)) {
+                        p {
+                            "Affected by "
+                            a href={"/" (incident.link)} {(incident.front_matter.title)}
+                        }
+                    }
+                    .uptime-bar {
+                        @for _ in checks.len()..status::HISTORY_LEN {
+                            span.uptime-unknown title="no data" {}
+                        }
+                        @for check in checks {
+                            @if check.up {
+                                span.uptime-up title={(check.time.to_rfc3339()) ": up in " (check.latency_ms) "ms"} {}
+                            } @else {
+                                span.uptime-down title={(check.time.to_rfc3339()) ": " (check.reason.as_deref().unwrap_or("down"))} {}
+                            }
+                        }
+                    }
+                }
+            }
+
+            @if !incidents.is_empty() {
+                h2 {"Incident history"}
+                ul {
+                    @for incident in incidents.iter().filter(|p| p.listed()) {
+                        li {
+                            (incident.detri())
+                            " - "
+                            a href={"/" (incident.link)} {(incident.front_matter.title)}
+                            @if incident.ongoing_incident() {
+                                " (ongoing)"
+                            }
+                        }
+                    }
+                }
+            }
+        },
+    )
+}
+
+fn notes_graph(garden: &Garden) -> Markup {
+    const SIZE: f64 = 640.0;
+    const RADIUS: f64 = 260.0;
+
+    // Unlisted notes are only for people who have the link, so they and the
+    // links to and from them are left out.
+    let notes: Vec<&Post> = garden.notes.iter().filter(|n| n.listed()).collect();
+    let edges: Vec<(&Post, &Post)> = garden
+        .edges()
+        .into_iter()
+        .filter(|(from, to)| from.listed() && to.listed())
+        .collect();
+
+    let count = notes.len().max(1) as f64;
+    let position = |slug: &str| -> (f64, f64) {
+        let idx = notes.iter().position(|n| n.slug() == slug).unwrap_or(0) as f64;
+        let angle = idx / count * std::f64::consts::TAU;
+        (
+            SIZE / 2.0 + RADIUS * angle.cos(),
+            SIZE / 2.0 + RADIUS * angle.sin(),
+        )
+    };
+
+    html! {
+        svg.notes-graph xmlns="http://www.w3.org/2000/svg" viewBox={"0 0 " (SIZE) " " (SIZE)} role="img" aria-label="Graph of how the notes link to each other" {
+            @for (from, to) in edges {
+                @let (x1, y1) = position(from.slug());
+                @let (x2, y2) = position(to.slug());
+                line x1=(format!("{x1:.1}")) y1=(format!("{y1:.1}")) x2=(format!("{x2:.1}")) y2=(format!("{y2:.1}")) stroke="#665c54" stroke-width="1" {}
+            }
+            @for note in &notes {
+                @let (x, y) = position(note.slug());
+                a href={"/" (note.link)} {
+                    circle cx=(format!("{x:.1}")) cy=(format!("{y:.1}")) r="6" fill="#fabd2f" {}
+                    text x=(format!("{x:.1}")) y=(format!("{:.1}", y - 10.0)) text-anchor="middle" fill="currentColor" font-size="12" {
+                        (note.front_matter.title)
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn notes_index(garden: &Garden) -> Markup {
+    base(
+        Some("Notes"),
+        None,
+        html! {
+            h1 {"Notes"}
+            p {
+                "This is my digital garden. Unlike the "
+                a href="/blog" {"blog"}
+                ", these notes are not sorted by date and are never really finished. They link to each other, so feel free to wander around."
+            }
+
+            @if garden.notes.is_empty() {
+                p {"Nothing has been planted here yet."}
+            } @else {
+                (notes_graph(garden))
+
+                ul {
+                    @for note in garden.notes.iter().filter(|n| n.listed()) {
+                        li {
+                            a href={"/" (note.link)} {(note.front_matter.title)}
+                            @let backlinks = garden.backlinks(note.slug()).len();
+                            @if backlinks != 0 {
+                                " ("
+                                (backlinks)
+                                @if backlinks == 1 {" backlink"} @else {" backlinks"}
+                                ")"
+                            }
+                        }
+                    }
+                }
+            }
+        },
+    )
+}
+
+pub fn moods(report: &crate::moods::Report) -> Markup {
+    base(
+        Some("Sticker usage"),
+        None,
+        html! {
+            h1 {"Sticker usage"}
+            p {
+                "Which posts use each character's stickers, and which stickers no posts use. The same is at "
+                a href="/.within/stickers.json" {"/.within/stickers.json"}
+                "."
+            }
+            (report)
+        },
+    )
+}
+
+pub fn year_stats(stats: &YearStats) -> Markup {
+    let title = format!("{} in review", stats.year);
+    base(
+        Some(&title),
+        None,
+        html! {
+            h1 {(title)}
+            p {
+                "Here is what I wrote about in " (stats.year) ". View counts are since the server last restarted, so take them with a grain of salt."
+            }
+            (stats)
+        },
+    )
+}
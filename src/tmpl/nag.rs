@@ -2,6 +2,7 @@ use crate::post::Post;
 use lazy_static::lazy_static;
 use maud::{html, Markup};
 use regex::Regex;
+use xesite_types::ReaderPrefs;
 
 // XXX(S): needed because release builds need this
 #[allow(unused)]
@@ -13,7 +14,7 @@ lazy_static! {
 }
 
 #[cfg(debug_assertions)]
-pub fn referer(_: &Post, _: Option<String>) -> Markup {
+pub fn referer(_: &Post, _: Option<String>, _: ReaderPrefs) -> Markup {
     html! {
         .warning {
             "This is a development instance of xesite. Things here are probably unfinished or in drafting. Don't take anything here super seriously. If you want to share this to an online aggregator, please don't. Drafts are not finalized yet for a reason. Please don't be the reason I need to implement more advanced security than just obscurity."
@@ -23,11 +24,11 @@ pub fn referer(_: &Post, _: Option<String>) -> Markup {
 }
 
 #[cfg(not(debug_assertions))]
-pub fn referer(post: &Post, referer: Option<String>) -> Markup {
+pub fn referer(post: &Post, referer: Option<String>, prefs: ReaderPrefs) -> Markup {
     use xesite_templates::conv as xeblog_conv;
 
     if referer.is_none() {
-        return xesite_templates::advertiser_nag(None);
+        return xesite_templates::advertiser_nag(prefs, None);
     }
 
     let referer = referer.unwrap();
@@ -37,7 +38,7 @@ pub fn referer(post: &Post, referer: Option<String>) -> Markup {
     }
 
     if HACKER_NEWS.is_match(&referer) {
-        return xesite_templates::advertiser_nag(Some(xeblog_conv(
+        let pronouns = xeblog_conv(
             "Mara".into(),
             "hacker".into(),
             html! {
@@ -45,7 +46,12 @@ pub fn referer(post: &Post, referer: Option<String>) -> Markup {
                 a href="/pronouns" {"this page"}
                 " that explains the pronouns that you should be using. tl;dr: the author of this website is NOT male. Please do not use \"he\" or \"him\" when referring to the author."
             },
-        )));
+        );
+        // This is worth saying even to people who don't see ads.
+        if !prefs.shows_ads() {
+            return pronouns;
+        }
+        return xesite_templates::advertiser_nag(prefs, Some(pronouns));
     }
 
     if LOBSTERS.is_match(&referer) {
@@ -58,7 +64,32 @@ pub fn referer(post: &Post, referer: Option<String>) -> Markup {
         );
     }
 
-    xesite_templates::advertiser_nag(None)
+    xesite_templates::advertiser_nag(prefs, None)
+}
+
+pub fn outdated(post: &Post) -> Markup {
+    use xesite_templates::conv as xeblog_conv;
+
+    if !post.is_outdated() {
+        return html! {};
+    }
+
+    html! {
+        .warning {
+            (xeblog_conv("Mara".into(), "hmm".into(), html!{
+                "Hey, this post was written in "
+                (post.date.year())
+                " and has been marked as outdated since "
+                (post.outdated_as_of().unwrap().format("M%m %d %Y").to_string())
+                ". The software and facts in it have probably changed, so take it with a grain of salt."
+                @if let Some(successor) = &post.front_matter.successor {
+                    " There is "
+                    a href=(successor) {"a newer post"}
+                    " that covers this topic with more up to date information."
+                }
+            }))
+        }
+    }
 }
 
 #[cfg(debug_assertions)]
@@ -78,7 +109,7 @@ pub fn prerelease(post: &Post) -> Markup {
                     "Hey, this post is set to go live on "
                     (format!("{}", post.detri()))
                     " UTC. Right now you are reading a pre-publication version of this post. Please do not share this on social media. This post will automatically go live for everyone on the intended publication date. If you want access to these posts, please join the "
-                    a href="https://www.patreon.com/cadey" { "Patreon" }
+                    a href=(xesite_templates::context::site().donation.patreon_url) { "Patreon" }
                     ". It helps me afford the copyeditor that I contract for the technical content I write."
                     br;
                 }))
@@ -0,0 +1,35 @@
+//! The reader preferences page, see [crate::prefs].
+
+use super::base;
+use crate::prefs::PREFIX;
+use maud::{html, Markup};
+use xesite_types::ReaderPrefs;
+
+pub fn page(prefs: ReaderPrefs) -> Markup {
+    base(
+        Some("Preferences"),
+        None,
+        html! {
+            h1 { "Preferences" }
+            p { "These are kept in a cookie, so they only apply to this browser." }
+            h2 { "Ads" }
+            @if prefs.patron {
+                p { "You're one of my patrons, so you never see ads. Thank you!" }
+            } @else if prefs.no_ads {
+                p { "Ads are turned off. The ad network's script isn't loaded at all." }
+                form method="post" action=(PREFIX) {
+                    input type="hidden" name="ads" value="on";
+                    button type="submit" { "Turn ads back on" }
+                }
+            } @else {
+                p {
+                    "Ads help pay for the site. If you'd rather not see them, you can turn them off here instead of using an ad blocker."
+                }
+                form method="post" action=(PREFIX) {
+                    input type="hidden" name="ads" value="off";
+                    button type="submit" { "Turn ads off" }
+                }
+            }
+        },
+    )
+}
@@ -0,0 +1,172 @@
+//! The JSON-LD that describes posts and talks to search engines, so they
+//! can show them as rich results. See [crate::post::schemaorg].
+
+use crate::post::{
+    schemaorg::{BlogPosting, Event, MediaObject, Person, Place, PresentationDigitalDocument},
+    Post,
+};
+use maud::Markup;
+use scraper::Html;
+use xesite_templates::{context::site, json_ld};
+
+/// The post's excerpt as plain text.
+fn description(post: &Post) -> Option<String> {
+    let text = Html::parse_fragment(&post.excerpt)
+        .root_element()
+        .text()
+        .collect::<String>();
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!text.is_empty()).then_some(text)
+}
+
+fn author(post: &Post) -> Person {
+    match &post.front_matter.author {
+        Some(name) => Person::new(name, None),
+        None => Person::new("Xe Iaso", Some(site().url("/"))),
+    }
+}
+
+/// Makes `url` absolute if it points at this site.
+fn absolute(url: &str) -> String {
+    if url.starts_with('/') {
+        site().url(url)
+    } else {
+        url.to_string()
+    }
+}
+
+pub fn blog_posting(post: &Post) -> Markup {
+    let fm = &post.front_matter;
+    let url = site().url(&post.link);
+    let keywords = fm
+        .series
+        .iter()
+        .chain(fm.tags.iter().flatten())
+        .cloned()
+        .collect();
+
+    json_ld(&BlogPosting {
+        context: "https://schema.org".to_string(),
+        r#type: "BlogPosting".to_string(),
+        headline: fm.title.clone(),
+        description: description(post),
+        image: crate::og::image_for(post),
+        url: url.clone(),
+        main_entity_of_page: url,
+        date_published: post.date.to_rfc3339(),
+        date_modified: fm.outdated_as_of.clone(),
+        author: author(post),
+        publisher: Person::new("Xe Iaso", Some(site().url("/"))),
+        keywords,
+        word_count: post.word_count,
+        time_required: format!("PT{}M", post.read_time_estimate_minutes),
+    })
+}
+
+/// A talk's slides, with the event it was given at and its recordings.
+pub fn talk(post: &Post) -> Markup {
+    let fm = &post.front_matter;
+    let recorded_at = fm.event.as_ref().map(|event| Event {
+        r#type: "Event".to_string(),
+        name: event.name.clone(),
+        start_date: event.start_date.clone(),
+        end_date: event.end_date.clone(),
+        location: Place {
+            r#type: "Place".to_string(),
+            name: event.venue.clone(),
+            address: event.location.clone(),
+        },
+        url: event.url.clone(),
+    });
+    let encoding = fm
+        .slides_link
+        .iter()
+        .map(|slides| MediaObject {
+            r#type: "MediaObject".to_string(),
+            content_url: absolute(slides),
+            encoding_format: slides
+                .ends_with(".pdf")
+                .then(|| "application/pdf".to_string()),
+        })
+        .chain(fm.video.iter().map(|video| MediaObject {
+            r#type: "VideoObject".to_string(),
+            content_url: absolute(&video.url),
+            encoding_format: Some(video.media_type.clone()),
+        }))
+        .collect();
+    let same_as = fm
+        .vod
+        .iter()
+        .flat_map(|vod| [&vod.youtube, &vod.twitch])
+        .filter(|url| !url.is_empty())
+        .cloned()
+        .collect();
+
+    json_ld(&PresentationDigitalDocument {
+        context: "https://schema.org".to_string(),
+        r#type: "PresentationDigitalDocument".to_string(),
+        name: fm.title.clone(),
+        description: description(post),
+        image: crate::og::image_for(post),
+        url: site().url(&post.link),
+        date_published: post.date.to_rfc3339(),
+        author: author(post),
+        recorded_at,
+        encoding,
+        same_as,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use color_eyre::eyre::Result;
+    use serde_json::Value;
+
+    /// The JSON in the script tag `markup` is.
+    fn json(markup: Markup) -> Value {
+        let html = markup.into_string();
+        let json = html
+            .strip_prefix(r#"<script type="application/ld+json">"#)
+            .and_then(|rest| rest.strip_suffix("</script>"))
+            .unwrap();
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[tokio::test]
+    async fn blog_posting() -> Result<()> {
+        let blog = crate::post::load("blog").await?;
+        let post = &blog[0];
+        let data = json(super::blog_posting(post));
+        assert_eq!(data["@type"], "BlogPosting");
+        assert_eq!(data["headline"], post.front_matter.title.as_str());
+        assert_eq!(data["url"], site().url(&post.link).as_str());
+        assert_eq!(data["wordCount"], post.word_count);
+        assert_eq!(data["author"]["@type"], "Person");
+        assert!(data["image"].as_str().unwrap().starts_with("http"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn talk() -> Result<()> {
+        let talks = crate::post::load("talks").await?;
+        let post = talks
+            .iter()
+            .find(|p| p.front_matter.slides_link.is_some())
+            .unwrap();
+        let data = json(super::talk(post));
+        assert_eq!(data["@type"], "PresentationDigitalDocument");
+        assert_eq!(data["name"], post.front_matter.title.as_str());
+        assert!(data["encoding"][0]["contentUrl"]
+            .as_str()
+            .unwrap()
+            .starts_with("http"));
+        Ok(())
+    }
+
+    #[test]
+    fn escapes_script() {
+        let html = script(&serde_json::json!({"name": "</script><b>"})).into_string();
+        assert_eq!(html.matches("</script>").count(), 1);
+    }
+}
@@ -0,0 +1,37 @@
+// Makes the chapter links of players rendered by
+// xesite_templates::audio_player_with jump the player instead of opening the
+// file, and marks the chapter that's playing with aria-current.
+(function () {
+    document.querySelectorAll(".audio-player").forEach((player) => {
+        const audio = player.querySelector("audio");
+        const links = Array.from(player.querySelectorAll(".audio-chapters a"));
+        if (!audio || links.length === 0) {
+            return;
+        }
+        const starts = links.map((link) => Number(link.dataset.start));
+
+        links.forEach((link, i) => {
+            link.addEventListener("click", (e) => {
+                e.preventDefault();
+                audio.currentTime = starts[i];
+                audio.play();
+            });
+        });
+
+        audio.addEventListener("timeupdate", () => {
+            let current = 0;
+            starts.forEach((start, i) => {
+                if (audio.currentTime >= start) {
+                    current = i;
+                }
+            });
+            links.forEach((link, i) => {
+                if (i === current) {
+                    link.setAttribute("aria-current", "true");
+                } else {
+                    link.removeAttribute("aria-current");
+                }
+            });
+        });
+    });
+})();
@@ -0,0 +1,32 @@
+// Shows the copy button on code blocks rendered by
+// xesite_templates::code_block. Without this script, or in browsers without
+// the clipboard API, the button stays hidden and the code can still be
+// selected and copied by hand.
+(function () {
+    if (!navigator.clipboard) {
+        return;
+    }
+
+    document.querySelectorAll(".code-block").forEach((block) => {
+        const button = block.querySelector(".code-block-copy");
+        const code = block.querySelector("code");
+        if (!button || !code) {
+            return;
+        }
+
+        button.hidden = false;
+        button.addEventListener("click", async () => {
+            try {
+                // The line numbers are CSS, so they aren't in the text.
+                await navigator.clipboard.writeText(code.textContent);
+                button.textContent = "Copied!";
+            } catch (why) {
+                console.error("can't copy code:", why);
+                button.textContent = "Can't copy";
+            }
+            setTimeout(() => {
+                button.textContent = "Copy";
+            }, 2000);
+        });
+    });
+})();
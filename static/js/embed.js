@@ -0,0 +1,26 @@
+// Swaps the thumbnails of videos rendered by xesite_templates::embed for the
+// provider's player when they're clicked, so nothing loads from the provider
+// until the reader asks for it.
+(function () {
+    document.querySelectorAll(".embed-video[data-player]").forEach((figure) => {
+        const facade = figure.querySelector(".embed-video-facade");
+        if (!facade) {
+            return;
+        }
+
+        facade.addEventListener("click", (e) => {
+            e.preventDefault();
+            const player = new URL(figure.dataset.player);
+            player.searchParams.set("autoplay", "1");
+
+            const iframe = document.createElement("iframe");
+            iframe.src = player.toString();
+            iframe.title = figure.dataset.title;
+            iframe.allow = "autoplay; encrypted-media; picture-in-picture; fullscreen";
+            iframe.allowFullscreen = true;
+            iframe.referrerPolicy = "strict-origin-when-cross-origin";
+            facade.replaceWith(iframe);
+            iframe.focus();
+        });
+    });
+})();
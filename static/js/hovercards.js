@@ -0,0 +1,51 @@
+// Shows a character's hovercard in a popover when their name in a
+// conversation is hovered or focused. The cards are rendered at the end of
+// the post as disclosures, so without this script they're still there.
+(function () {
+    const popover = document.createElement("div");
+    popover.className = "hovercard-popover";
+    popover.setAttribute("role", "tooltip");
+    popover.id = "hovercard-popover";
+    popover.hidden = true;
+    document.body.append(popover);
+
+    let hideTimer = null;
+
+    const show = (link) => {
+        const card = document.getElementById(`hovercard-${link.dataset.character}`);
+        if (!card) {
+            return;
+        }
+        clearTimeout(hideTimer);
+        popover.replaceChildren(card.querySelector(".hovercard-body").cloneNode(true));
+        const rect = link.getBoundingClientRect();
+        popover.style.top = `${rect.bottom + window.scrollY + 4}px`;
+        popover.style.left = `${rect.left + window.scrollX}px`;
+        popover.hidden = false;
+        link.setAttribute("aria-describedby", popover.id);
+    };
+
+    const hide = (link) => {
+        hideTimer = setTimeout(() => {
+            popover.hidden = true;
+        }, 200);
+        link.removeAttribute("aria-describedby");
+    };
+
+    for (const link of document.querySelectorAll("a[data-character]")) {
+        link.addEventListener("mouseenter", () => show(link));
+        link.addEventListener("focus", () => show(link));
+        link.addEventListener("mouseleave", () => hide(link));
+        link.addEventListener("blur", () => hide(link));
+    }
+
+    popover.addEventListener("mouseenter", () => clearTimeout(hideTimer));
+    popover.addEventListener("mouseleave", () => {
+        popover.hidden = true;
+    });
+    document.addEventListener("keydown", (event) => {
+        if (event.key === "Escape") {
+            popover.hidden = true;
+        }
+    });
+})();
@@ -0,0 +1,43 @@
+// Loads the next page of the blog index when the reader scrolls to the end
+// of the list. Without this script, or if loading fails, the page links are
+// still there.
+(function () {
+    const list = document.querySelector("ul.post-list[data-next]");
+    const nav = document.querySelector("nav.pagination");
+    if (!list || !nav || !("IntersectionObserver" in window)) {
+        return;
+    }
+
+    const end = document.createElement("div");
+    list.after(end);
+    nav.hidden = true;
+
+    let loading = false;
+    const observer = new IntersectionObserver(async (entries) => {
+        if (loading || !entries.some((entry) => entry.isIntersecting)) {
+            return;
+        }
+        loading = true;
+
+        try {
+            const resp = await fetch(list.dataset.next);
+            if (!resp.ok) {
+                throw new Error(`${resp.status} ${resp.statusText}`);
+            }
+            const page = await resp.json();
+            list.insertAdjacentHTML("beforeend", page.html);
+            if (page.next) {
+                list.dataset.next = page.next;
+            } else {
+                observer.disconnect();
+            }
+        } catch (err) {
+            console.error("can't load more posts:", err);
+            observer.disconnect();
+            nav.hidden = false;
+        }
+        loading = false;
+    });
+
+    observer.observe(end);
+})();
@@ -0,0 +1,59 @@
+// Steps through slide decks rendered by xesite_templates::slide_deck one
+// slide at a time. Without this script the slides stay stacked and
+// #slide-N links still scroll to them.
+(function () {
+    document.querySelectorAll(".slide-deck").forEach((deck) => {
+        const slides = Array.from(deck.querySelectorAll(".slide-deck-slide"));
+        const controls = deck.querySelector(".slide-deck-controls");
+        if (!controls || slides.length === 0) {
+            return;
+        }
+        const prev = controls.querySelector(".slide-deck-prev");
+        const next = controls.querySelector(".slide-deck-next");
+        const counter = controls.querySelector(".slide-deck-counter");
+        let current = 0;
+
+        const show = (n, link) => {
+            current = Math.max(0, Math.min(slides.length - 1, n));
+            slides.forEach((slide, i) => {
+                slide.hidden = i !== current;
+            });
+            counter.textContent = `${current + 1} / ${slides.length}`;
+            prev.disabled = current === 0;
+            next.disabled = current === slides.length - 1;
+            if (link) {
+                history.replaceState(null, "", `#${slides[current].id}`);
+            }
+        };
+
+        const fromHash = () => {
+            const i = slides.findIndex((slide) => `#${slide.id}` === location.hash);
+            if (i !== -1) {
+                show(i, false);
+                deck.scrollIntoView();
+            }
+        };
+
+        deck.classList.add("slide-deck-active");
+        deck.tabIndex = 0;
+        controls.hidden = false;
+        prev.addEventListener("click", () => show(current - 1, true));
+        next.addEventListener("click", () => show(current + 1, true));
+        deck.addEventListener("keydown", (e) => {
+            const to = {
+                ArrowLeft: current - 1,
+                ArrowRight: current + 1,
+                Home: 0,
+                End: slides.length - 1,
+            }[e.key];
+            if (to !== undefined) {
+                e.preventDefault();
+                show(to, true);
+            }
+        });
+        window.addEventListener("hashchange", fromHash);
+
+        show(0, false);
+        fromHash();
+    });
+})();
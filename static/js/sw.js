@@ -1 +1,171 @@
-self.addEventListener('install', function (event) {});
+// The service worker for offline reading. This file isn't served as is:
+// /sw.js puts VERSION (the cache buster of the running build), PRECACHE
+// (the fingerprinted shell assets and the offline page) and FALLBACK (where
+// images go when the asset base is down, or null) in front of it.
+//
+// Pages are fetched from the network first and kept in a cache as they're
+// visited, so posts someone has read stay readable offline. Static assets
+// are served from the cache first. Every new build gets new caches and the
+// old ones are thrown away.
+//
+// When FALLBACK is set, images from the asset base get a second chance. If
+// the asset base fails or hasn't answered after FALLBACK_AFTER, the same
+// file is asked for from the fallback too and whichever answers first is
+// used. Then images go straight to the fallback for PRIMARY_COOLDOWN, so a
+// CDN outage only makes the first few images wait.
+
+const SHELL = `shell-${VERSION}`;
+const PAGES = `pages-${VERSION}`;
+const ASSETS = `assets-${VERSION}`;
+const OFFLINE = "/offline";
+const MAX_PAGES = 50;
+const FALLBACK_AFTER = 2500;
+const PRIMARY_COOLDOWN = 5 * 60 * 1000;
+
+let primaryDownUntil = 0;
+
+const sameOrigin = (url) => new URL(url, self.location).origin === self.location.origin;
+
+self.addEventListener("install", (event) => {
+  event.waitUntil(
+    (async () => {
+      const cache = await caches.open(SHELL);
+      // Cross-origin assets like stickers on the CDN don't send CORS
+      // headers, so they have to be cached as opaque responses.
+      await cache.addAll(PRECACHE.filter(sameOrigin));
+      await Promise.all(
+        PRECACHE.filter((url) => !sameOrigin(url)).map(async (url) => {
+          const resp = await fetch(url, { mode: "no-cors" });
+          await cache.put(url, resp);
+        })
+      );
+      await self.skipWaiting();
+    })()
+  );
+});
+
+self.addEventListener("activate", (event) => {
+  event.waitUntil(
+    (async () => {
+      const keep = [SHELL, PAGES, ASSETS];
+      for (const name of await caches.keys()) {
+        if (!keep.includes(name)) {
+          await caches.delete(name);
+        }
+      }
+      await self.clients.claim();
+    })()
+  );
+});
+
+const trim = async (cache, max) => {
+  const keys = await cache.keys();
+  for (const key of keys.slice(0, Math.max(keys.length - max, 0))) {
+    await cache.delete(key);
+  }
+};
+
+const page = async (request) => {
+  try {
+    const resp = await fetch(request);
+    if (resp.ok) {
+      const cache = await caches.open(PAGES);
+      await cache.put(request, resp.clone());
+      await trim(cache, MAX_PAGES);
+    }
+    return resp;
+  } catch (why) {
+    return (
+      (await caches.match(request)) ||
+      (await caches.match(OFFLINE)) ||
+      Response.error()
+    );
+  }
+};
+
+const asset = async (request) => {
+  const cached = await caches.match(request);
+  if (cached) {
+    return cached;
+  }
+  const resp = await fetch(request);
+  if (resp.ok || resp.type === "opaque") {
+    const cache = await caches.open(ASSETS);
+    await cache.put(request, resp.clone());
+  }
+  return resp;
+};
+
+// Where the image at `url` is on the fallback, or null if it isn't from the
+// asset base.
+const fallbackURL = (url) => {
+  if (!FALLBACK) {
+    return null;
+  }
+  if (url.startsWith(`${FALLBACK.primary}/`)) {
+    return FALLBACK.fallback + url.slice(FALLBACK.primary.length);
+  }
+  // The CDN resizes stickers, the fallback only has the full size PNGs.
+  if (FALLBACK.stickers && url.startsWith(`${FALLBACK.stickers}/`)) {
+    const [name, mood] = url.slice(FALLBACK.stickers.length + 1).split("/");
+    if (name && mood) {
+      return `${FALLBACK.fallback}/stickers/${name}/${mood}.png`;
+    }
+  }
+  return null;
+};
+
+const failover = async (request, fallback) => {
+  const backup = () =>
+    fetch(fallback, { mode: request.mode, credentials: request.credentials });
+  if (Date.now() < primaryDownUntil) {
+    return backup();
+  }
+
+  // Images from other origins are opaque, so a server error can't be seen.
+  // Only network errors, timeouts and errors from CORS requests count.
+  const primary = fetch(request).then((resp) => {
+    if (resp.status >= 500) {
+      throw new Error(`${request.url}: ${resp.status}`);
+    }
+    return resp;
+  });
+  const first = await Promise.race([
+    primary.then(
+      () => "ok",
+      () => "failed"
+    ),
+    new Promise((resolve) => setTimeout(() => resolve("slow"), FALLBACK_AFTER)),
+  ]);
+  if (first === "ok") {
+    return primary;
+  }
+  primaryDownUntil = Date.now() + PRIMARY_COOLDOWN;
+  return first === "failed" ? backup() : Promise.any([primary, backup()]);
+};
+
+self.addEventListener("fetch", (event) => {
+  const { request } = event;
+  if (request.method !== "GET") {
+    return;
+  }
+
+  if (request.mode === "navigate") {
+    event.respondWith(page(request));
+    return;
+  }
+
+  const url = new URL(request.url);
+  if (
+    (sameOrigin(url) && url.pathname.startsWith("/static/")) ||
+    PRECACHE.includes(request.url)
+  ) {
+    event.respondWith(asset(request));
+    return;
+  }
+
+  const fallback = request.destination === "image" && fallbackURL(request.url);
+  if (fallback) {
+    event.respondWith(failover(request, fallback));
+  }
+});
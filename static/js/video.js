@@ -0,0 +1,24 @@
+// Plays the HLS streams of videos rendered by xesite_templates::video_native
+// in browsers that can't play HLS on their own, using hls.js. Browsers that
+// can, like Safari, never load it.
+(function () {
+    const HLS = "https://esm.sh/v113/hls.js@1.3.5/es2022/hls.mjs";
+    const videos = Array.from(document.querySelectorAll("video.video-native"))
+        .filter((video) => !video.dataset.hlsAttached)
+        .filter((video) => video.canPlayType("application/vnd.apple.mpegurl") === "");
+    if (videos.length === 0) {
+        return;
+    }
+
+    import(HLS).then(({ default: Hls }) => {
+        if (!Hls.isSupported()) {
+            return;
+        }
+        videos.forEach((video) => {
+            video.dataset.hlsAttached = "true";
+            const hls = new Hls();
+            hls.loadSource(video.dataset.stream);
+            hls.attachMedia(video);
+        });
+    });
+})();
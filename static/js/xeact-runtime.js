@@ -0,0 +1,82 @@
+// Mounts every Xeact component on the page. Components are placeholders like
+// <div data-xeact-component="Video" data-xeact-props='{"path": "..."}'>
+// rendered by xesite_templates::xeact_component. Each component module is
+// only imported once no matter how many times it shows up on the page.
+// Whatever the placeholder has in it (a noscript warning, or the fallback
+// from xeact_component_with_fallback) is replaced by the component.
+//
+// data-xeact-hydrate says when to mount a component: "load" (right away),
+// "visible" (when it scrolls into view), "idle" (when the browser is idle) or
+// "interaction" (when the reader hovers over, focuses or taps it).
+
+const cacheBuster = new URL(import.meta.url).searchParams.get("cacheBuster");
+const modules = {};
+
+const load = (name) => {
+  if (modules[name] === undefined) {
+    modules[name] = import(
+      `/static/xeact/${name}.js?cacheBuster=${cacheBuster}`
+    );
+  }
+  return modules[name];
+};
+
+const x = (elem) => {
+  while (elem.lastChild) {
+    elem.removeChild(elem.lastChild);
+  }
+};
+
+const mount = async (root) => {
+  const name = root.dataset.xeactComponent;
+  try {
+    const { default: Component } = await load(name);
+    const props = JSON.parse(root.dataset.xeactProps || "null");
+    x(root);
+    root.appendChild(Component(props));
+  } catch (why) {
+    console.error(`can't mount component ${name}:`, why);
+  }
+};
+
+const visible = (root) => {
+  if (!("IntersectionObserver" in window)) {
+    return mount(root);
+  }
+  const observer = new IntersectionObserver(
+    (entries) => {
+      if (entries.some((entry) => entry.isIntersecting)) {
+        observer.disconnect();
+        mount(root);
+      }
+    },
+    { rootMargin: "200px" }
+  );
+  observer.observe(root);
+};
+
+const idle = (root) => {
+  if ("requestIdleCallback" in window) {
+    requestIdleCallback(() => mount(root), { timeout: 2000 });
+  } else {
+    setTimeout(() => mount(root), 200);
+  }
+};
+
+const interaction = (root) => {
+  const events = ["pointerover", "focusin", "touchstart"];
+  const go = () => {
+    events.forEach((event) => root.removeEventListener(event, go));
+    mount(root);
+  };
+  events.forEach((event) =>
+    root.addEventListener(event, go, { once: true, passive: true })
+  );
+};
+
+const strategies = { load: mount, visible, idle, interaction };
+
+document.querySelectorAll("[data-xeact-component]").forEach((root) => {
+  const strategy = strategies[root.dataset.xeactHydrate] || mount;
+  strategy(root);
+});